@@ -1,5 +1,5 @@
 use anyhow::Result;
-use memex::embed::{EmbedderHandle, ModelChoice};
+use memex::embed::{EmbedRuntimeConfig, EmbedderHandle, ModelChoice};
 use std::time::Instant;
 
 fn generate_texts(n: usize) -> Vec<String> {
@@ -137,6 +137,26 @@ fn main() -> Result<()> {
         let _ = results;
     }
 
+    // Test 4: batch size sweep (maps to `embedding_batch_size` in config.toml)
+    println!("\n--- Test 4: MiniLM batch sizes on CPU ---");
+    for batch_size in [16, 32, 64, 128, 256] {
+        let runtime = EmbedRuntimeConfig {
+            batch_size: Some(batch_size),
+            ..EmbedRuntimeConfig::from_env()?
+        };
+        let mut embedder = EmbedderHandle::with_model_and_runtime(ModelChoice::MiniLM, &runtime)?;
+        let _ = embedder.embed_texts(&["warmup"])?;
+
+        let start = Instant::now();
+        let _ = embedder.embed_texts(&text_refs)?;
+        let elapsed = start.elapsed();
+        println!(
+            "  batch {batch_size}: {}ms ({:.0} texts/sec)",
+            elapsed.as_millis(),
+            500.0 / elapsed.as_secs_f64()
+        );
+    }
+
     println!("\nDone!");
     Ok(())
 }
@@ -0,0 +1,278 @@
+//! When agent-assisted work happens: records and sessions counted by hour of
+//! day, weekday, day or month, for `memex stats --by`.
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, TimeZone, Timelike};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt::Write as _;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Widest bar in a chart, in cells.
+const BAR_WIDTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum Bucket {
+    /// Hour of the day, 00 to 23
+    Hour,
+    /// Day of the week, Monday first
+    Weekday,
+    /// Calendar day
+    Day,
+    /// Calendar month
+    Month,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActivityRow {
+    pub label: String,
+    pub records: u64,
+    /// Sessions that started in the bucket.
+    pub sessions: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivityReport {
+    pub by: Bucket,
+    pub rows: Vec<ActivityRow>,
+}
+
+/// Counts record and session-start timestamps (Unix millis) into buckets
+/// in `tz`. Hours and weekdays always list every bucket; days and months
+/// run from the first to the last one with activity, gaps included.
+pub fn bucket_activity<Tz: TimeZone>(
+    by: Bucket,
+    records: &[u64],
+    session_starts: &[u64],
+    tz: &Tz,
+) -> ActivityReport {
+    let local = |ts: u64| DateTime::from_timestamp_millis(ts as i64).map(|at| at.with_timezone(tz));
+    let rows = match by {
+        Bucket::Hour | Bucket::Weekday => {
+            let labels: Vec<String> = match by {
+                Bucket::Hour => (0..24).map(|hour| format!("{hour:02}")).collect(),
+                _ => WEEKDAYS.iter().map(|day| day.to_string()).collect(),
+            };
+            let slot = |ts: u64| {
+                local(ts).map(|at| match by {
+                    Bucket::Hour => at.hour() as usize,
+                    _ => at.weekday().num_days_from_monday() as usize,
+                })
+            };
+            let mut rows: Vec<ActivityRow> = labels
+                .into_iter()
+                .map(|label| ActivityRow {
+                    label,
+                    records: 0,
+                    sessions: 0,
+                })
+                .collect();
+            for slot in records.iter().filter_map(|&ts| slot(ts)) {
+                rows[slot].records += 1;
+            }
+            for slot in session_starts.iter().filter_map(|&ts| slot(ts)) {
+                rows[slot].sessions += 1;
+            }
+            rows
+        }
+        Bucket::Day | Bucket::Month => {
+            let day = |ts: u64| {
+                local(ts).and_then(|at| match by {
+                    Bucket::Month => at.date_naive().with_day(1),
+                    _ => Some(at.date_naive()),
+                })
+            };
+            let record_days: Vec<NaiveDate> = records.iter().filter_map(|&ts| day(ts)).collect();
+            let session_days: Vec<NaiveDate> =
+                session_starts.iter().filter_map(|&ts| day(ts)).collect();
+            let all = record_days.iter().chain(&session_days);
+            let (Some(first), Some(last)) = (all.clone().min(), all.max()) else {
+                return ActivityReport {
+                    by,
+                    rows: Vec::new(),
+                };
+            };
+            let mut starts = Vec::new();
+            let mut at = *first;
+            while at <= *last {
+                starts.push(at);
+                let next = match by {
+                    Bucket::Month => at.checked_add_months(Months::new(1)),
+                    _ => at.succ_opt(),
+                };
+                let Some(next) = next else {
+                    break;
+                };
+                at = next;
+            }
+            let position = |date: &NaiveDate| starts.binary_search(date).ok();
+            let mut rows: Vec<ActivityRow> = starts
+                .iter()
+                .map(|start| ActivityRow {
+                    label: match by {
+                        Bucket::Month => start.format("%Y-%m").to_string(),
+                        _ => start.format("%Y-%m-%d %a").to_string(),
+                    },
+                    records: 0,
+                    sessions: 0,
+                })
+                .collect();
+            for slot in record_days.iter().filter_map(position) {
+                rows[slot].records += 1;
+            }
+            for slot in session_days.iter().filter_map(position) {
+                rows[slot].sessions += 1;
+            }
+            rows
+        }
+    };
+    ActivityReport { by, rows }
+}
+
+/// One line per bucket: label, record and session counts, and a bar
+/// scaled to the busiest bucket's records.
+pub fn render_chart(report: &ActivityReport) -> String {
+    let mut out = String::new();
+    let label_width = report
+        .rows
+        .iter()
+        .map(|row| row.label.len())
+        .max()
+        .unwrap_or(0)
+        .max(by_label(report.by).len());
+    let records_width = report
+        .rows
+        .iter()
+        .map(|row| row.records.to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("records".len());
+    let sessions_width = report
+        .rows
+        .iter()
+        .map(|row| row.sessions.to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("sessions".len());
+    let peak = report.rows.iter().map(|row| row.records).max().unwrap_or(0);
+    let _ = writeln!(
+        out,
+        "{:<label_width$}  {:>records_width$}  {:>sessions_width$}",
+        by_label(report.by),
+        "records",
+        "sessions"
+    );
+    for row in &report.rows {
+        let line = format!(
+            "{:<label_width$}  {:>records_width$}  {:>sessions_width$}  {}",
+            row.label,
+            row.records,
+            row.sessions,
+            bar(row.records, peak)
+        );
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+    out
+}
+
+fn by_label(by: Bucket) -> &'static str {
+    match by {
+        Bucket::Hour => "hour",
+        Bucket::Weekday => "weekday",
+        Bucket::Day => "day",
+        Bucket::Month => "month",
+    }
+}
+
+/// A bar of full and eighth blocks; any activity shows at least a sliver.
+fn bar(value: u64, peak: u64) -> String {
+    if value == 0 || peak == 0 {
+        return String::new();
+    }
+    let eighths = ((value as f64 / peak as f64) * (BAR_WIDTH * 8) as f64).round() as usize;
+    let eighths = eighths.max(1);
+    let mut out = "█".repeat(eighths / 8);
+    let partial = eighths % 8;
+    if partial > 0 {
+        out.push([' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'][partial]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn ms(at: &str) -> u64 {
+        DateTime::parse_from_rfc3339(at).unwrap().timestamp_millis() as u64
+    }
+
+    #[test]
+    fn buckets_by_hour_weekday_and_month() {
+        let records = [
+            ms("2024-06-03T09:15:00Z"),
+            ms("2024-06-03T09:45:00Z"),
+            ms("2024-06-05T22:00:00Z"),
+            ms("2024-08-01T09:00:00Z"),
+        ];
+        let sessions = [ms("2024-06-03T09:00:00Z"), ms("2024-08-01T08:59:00Z")];
+
+        let hours = bucket_activity(Bucket::Hour, &records, &sessions, &Utc);
+        assert_eq!(hours.rows.len(), 24);
+        assert_eq!((hours.rows[9].records, hours.rows[9].sessions), (3, 1));
+        assert_eq!(hours.rows[22].records, 1);
+
+        let weekdays = bucket_activity(Bucket::Weekday, &records, &sessions, &Utc);
+        assert_eq!(weekdays.rows[0].label, "Mon");
+        assert_eq!(weekdays.rows[0].records, 2);
+        assert_eq!(weekdays.rows[3].records, 1);
+
+        // July had nothing but still gets a row.
+        let months = bucket_activity(Bucket::Month, &records, &sessions, &Utc);
+        let labels: Vec<&str> = months.rows.iter().map(|row| row.label.as_str()).collect();
+        assert_eq!(labels, ["2024-06", "2024-07", "2024-08"]);
+        assert_eq!(months.rows[1].records, 0);
+        assert_eq!(months.rows[2].sessions, 1);
+
+        let days = bucket_activity(Bucket::Day, &records, &sessions, &Utc);
+        assert_eq!(days.rows.first().unwrap().label, "2024-06-03 Mon");
+        assert_eq!(days.rows.len(), 60);
+        assert!(bucket_activity(Bucket::Day, &[], &[], &Utc).rows.is_empty());
+    }
+
+    #[test]
+    fn charts_align_and_scale_to_the_peak() {
+        let report = ActivityReport {
+            by: Bucket::Weekday,
+            rows: vec![
+                ActivityRow {
+                    label: "Mon".to_string(),
+                    records: 1200,
+                    sessions: 8,
+                },
+                ActivityRow {
+                    label: "Tue".to_string(),
+                    records: 3,
+                    sessions: 1,
+                },
+                ActivityRow {
+                    label: "Wed".to_string(),
+                    records: 0,
+                    sessions: 0,
+                },
+            ],
+        };
+        let chart = render_chart(&report);
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines[0], "weekday  records  sessions");
+        assert_eq!(
+            lines[1],
+            format!("Mon         1200         8  {}", "█".repeat(40))
+        );
+        assert_eq!(lines[2], "Tue            3         1  ▏");
+        assert_eq!(lines[3], "Wed            0         0");
+    }
+}
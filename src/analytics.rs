@@ -1,4 +1,5 @@
-use crate::types::{Record, SourceFilter, SourceKind};
+use crate::state::ProjectAliases;
+use crate::types::{Record, SUMMARY_ROLE, SourceFilter, SourceKind};
 use anyhow::{Context, Result};
 use rusqlite::{Connection, OpenFlags, OptionalExtension, params, params_from_iter};
 use std::collections::HashMap;
@@ -6,7 +7,8 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::Duration;
 
-const SCHEMA_VERSION: i64 = 2;
+const SCHEMA_VERSION: i64 = 3;
+const SESSION_TITLE_MAX_CHARS: usize = 80;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ProjectGrouping {
@@ -23,12 +25,25 @@ pub struct SessionRow {
     pub project: String,
     pub display_project: String,
     pub cwd: Option<String>,
+    pub started_at: u64,
+    pub last_at: u64,
+    pub message_count: u64,
+    pub title: Option<String>,
+}
+
+/// Per-session values looked up for search results, which only know the
+/// records that matched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionDetails {
+    pub display_project: String,
+    pub started_at: u64,
     pub last_at: u64,
     pub message_count: u64,
 }
 
 pub struct AnalyticsStore {
     conn: Connection,
+    aliases: ProjectAliases,
 }
 
 pub struct AnalyticsWriter {
@@ -52,6 +67,29 @@ struct SessionAccumulator {
     started_at: u64,
     last_at: u64,
     message_count: u64,
+    title: Option<String>,
+    title_at: u64,
+    title_kind: TitleKind,
+}
+
+/// Where a session title came from. Agent-written summaries win over titles
+/// inferred from the opening user message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum TitleKind {
+    #[default]
+    None,
+    Message,
+    Summary,
+}
+
+impl TitleKind {
+    fn as_str(self) -> Option<&'static str> {
+        match self {
+            TitleKind::None => None,
+            TitleKind::Message => Some("message"),
+            TitleKind::Summary => Some("summary"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -71,7 +109,10 @@ impl AnalyticsStore {
         }
         let conn = Connection::open(path)?;
         conn.busy_timeout(Duration::from_secs(2))?;
-        let store = Self { conn };
+        let store = Self {
+            conn,
+            aliases: ProjectAliases::default(),
+        };
         store.init()?;
         Ok(store)
     }
@@ -83,7 +124,20 @@ impl AnalyticsStore {
         )?;
         conn.busy_timeout(Duration::from_secs(2))?;
         conn.pragma_update(None, "query_only", true)?;
-        Ok(Self { conn })
+        Ok(Self {
+            conn,
+            aliases: ProjectAliases::default(),
+        })
+    }
+
+    /// Shows renamed projects by their alias in every query result.
+    pub fn with_project_aliases(mut self, aliases: ProjectAliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    fn display_project(&self, project: &str) -> String {
+        shown_project_name(project, &self.aliases)
     }
 
     fn init(&self) -> Result<()> {
@@ -95,6 +149,27 @@ impl AnalyticsStore {
                 key TEXT PRIMARY KEY,
                 value TEXT NOT NULL
             );
+            "#,
+        )?;
+        let previous_schema_version: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'schema_version'",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .and_then(|value| value.parse().ok());
+        if previous_schema_version != Some(SCHEMA_VERSION) {
+            // Session rows are a cache of the Tantivy index; rebuilding them is
+            // cheaper than migrating columns in place.
+            self.conn.execute_batch(
+                "DROP TABLE IF EXISTS sessions;
+                 DELETE FROM meta WHERE key = 'analytics_complete';",
+            )?;
+        }
+        self.conn.execute_batch(
+            r#"
             CREATE TABLE IF NOT EXISTS sessions (
                 source TEXT NOT NULL,
                 session_id TEXT NOT NULL,
@@ -108,6 +183,8 @@ impl AnalyticsStore {
                 last_at INTEGER NOT NULL,
                 message_count INTEGER NOT NULL DEFAULT 0,
                 resolution_status TEXT NOT NULL DEFAULT '',
+                title TEXT,
+                title_kind TEXT,
                 PRIMARY KEY (source, session_id, source_path)
             );
             CREATE INDEX IF NOT EXISTS sessions_last_at_idx ON sessions(last_at);
@@ -118,19 +195,6 @@ impl AnalyticsStore {
             CREATE INDEX IF NOT EXISTS sessions_source_last_at_idx ON sessions(source, last_at);
             "#,
         )?;
-        let previous_schema_version: Option<i64> = self
-            .conn
-            .query_row(
-                "SELECT value FROM meta WHERE key = 'schema_version'",
-                [],
-                |row| row.get::<_, String>(0),
-            )
-            .optional()?
-            .and_then(|value| value.parse().ok());
-        if previous_schema_version != Some(SCHEMA_VERSION) {
-            self.conn
-                .execute("DELETE FROM meta WHERE key = 'analytics_complete'", [])?;
-        }
         self.conn.execute(
             "INSERT INTO meta(key, value) VALUES('schema_version', ?1)
              ON CONFLICT(key) DO UPDATE SET value = excluded.value",
@@ -193,6 +257,14 @@ impl AnalyticsStore {
         Ok(())
     }
 
+    pub fn delete_session(&self, session_id: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM sessions WHERE session_id = ?1",
+            params![session_id],
+        )?;
+        Ok(())
+    }
+
     pub fn query_sessions(
         &self,
         source: Option<SourceFilter>,
@@ -204,7 +276,7 @@ impl AnalyticsStore {
         let mut sql = String::from(
             "SELECT source, session_id, source_path, project,
                     COALESCE(NULLIF(repo_project, ''), project) AS display_project,
-                    cwd, last_at, message_count
+                    cwd, started_at, last_at, message_count, title
              FROM sessions",
         );
         let mut clauses = Vec::new();
@@ -254,7 +326,7 @@ impl AnalyticsStore {
                 ProjectGrouping::Flat => project.clone(),
                 ProjectGrouping::Repository => row.get(4)?,
             };
-            let display_project = display_project_name(&raw_display_project);
+            let display_project = self.display_project(&raw_display_project);
             Ok(SessionRow {
                 source,
                 session_id: row.get(1)?,
@@ -262,8 +334,10 @@ impl AnalyticsStore {
                 project,
                 display_project,
                 cwd: row.get(5)?,
-                last_at: row.get::<_, i64>(6)?.max(0) as u64,
-                message_count: row.get::<_, i64>(7)?.max(0) as u64,
+                started_at: row.get::<_, i64>(6)?.max(0) as u64,
+                last_at: row.get::<_, i64>(7)?.max(0) as u64,
+                message_count: row.get::<_, i64>(8)?.max(0) as u64,
+                title: row.get(9)?,
             })
         })?;
 
@@ -301,7 +375,7 @@ impl AnalyticsStore {
         let rows = stmt.query_map(params_from_iter(values), |row| row.get::<_, String>(0))?;
         let mut projects = Vec::new();
         for row in rows {
-            let project = display_project_name(&row?);
+            let project = self.display_project(&row?);
             if !project.is_empty() {
                 projects.push(project);
             }
@@ -311,6 +385,22 @@ impl AnalyticsStore {
         Ok(projects)
     }
 
+    /// Every project and repository name as stored, before display
+    /// decoding and aliases.
+    pub fn stored_project_names(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT project FROM sessions
+             UNION SELECT repo_project FROM sessions WHERE repo_project != ''",
+        )?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut names = Vec::new();
+        for row in rows {
+            names.push(row?);
+        }
+        names.sort();
+        Ok(names)
+    }
+
     pub fn query_source_timestamps(&self, since_ms: Option<u64>) -> Result<Vec<(SourceKind, u64)>> {
         let mut sql = String::from("SELECT source, last_at FROM sessions");
         let mut values: Vec<rusqlite::types::Value> = Vec::new();
@@ -389,7 +479,52 @@ impl AnalyticsStore {
         let mut out = Vec::new();
         for row in rows {
             let (project, last_at) = row?;
-            out.push((display_project_name(&project), last_at));
+            out.push((self.display_project(&project), last_at));
+        }
+        Ok(out)
+    }
+
+    /// When each session started, for sessions starting in `since_ms..until_ms`.
+    pub fn query_session_starts(
+        &self,
+        source: Option<SourceFilter>,
+        since_ms: Option<u64>,
+        until_ms: Option<u64>,
+    ) -> Result<Vec<u64>> {
+        let mut sql = String::from("SELECT started_at FROM sessions");
+        let mut clauses = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        if let Some(source) = source {
+            let labels = source.storage_labels();
+            let placeholders = std::iter::repeat_n("?", labels.len())
+                .collect::<Vec<_>>()
+                .join(", ");
+            clauses.push(format!("source IN ({placeholders})"));
+            values.extend(
+                labels
+                    .iter()
+                    .map(|label| rusqlite::types::Value::Text((*label).to_string())),
+            );
+        }
+        if let Some(since_ms) = since_ms {
+            clauses.push("started_at >= ?".to_string());
+            values.push(rusqlite::types::Value::Integer(since_ms as i64));
+        }
+        if let Some(until_ms) = until_ms {
+            clauses.push("started_at <= ?".to_string());
+            values.push(rusqlite::types::Value::Integer(until_ms as i64));
+        }
+        if !clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&clauses.join(" AND "));
+        }
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), |row| {
+            Ok(row.get::<_, i64>(0)?.max(0) as u64)
+        })?;
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(row?);
         }
         Ok(out)
     }
@@ -416,14 +551,14 @@ impl AnalyticsStore {
                 |row| row.get(0),
             )
             .optional()?;
-        Ok(project.map(|project| display_project_name(&project)))
+        Ok(project.map(|project| self.display_project(&project)))
     }
 
-    pub fn query_session_projects(
+    pub fn query_session_details(
         &self,
         sessions: &[(SourceKind, String, String)],
         grouping: ProjectGrouping,
-    ) -> Result<HashMap<(SourceKind, String, String), String>> {
+    ) -> Result<HashMap<(SourceKind, String, String), SessionDetails>> {
         if sessions.is_empty() {
             return Ok(HashMap::new());
         }
@@ -438,7 +573,8 @@ impl AnalyticsStore {
         .collect::<Vec<_>>()
         .join(" OR ");
         let mut stmt = self.conn.prepare(&format!(
-            "SELECT source, session_id, source_path, {display_expr}
+            "SELECT source, session_id, source_path, {display_expr},
+                    started_at, last_at, message_count
              FROM sessions WHERE {conditions}"
         ))?;
         let values = sessions
@@ -455,21 +591,23 @@ impl AnalyticsStore {
                 row.get::<_, String>(0)?,
                 row.get::<_, String>(1)?,
                 row.get::<_, String>(2)?,
-                row.get::<_, String>(3)?,
+                SessionDetails {
+                    display_project: self.display_project(&row.get::<_, String>(3)?),
+                    started_at: row.get::<_, i64>(4)?.max(0) as u64,
+                    last_at: row.get::<_, i64>(5)?.max(0) as u64,
+                    message_count: row.get::<_, i64>(6)?.max(0) as u64,
+                },
             ))
         })?;
-        let mut projects = HashMap::new();
+        let mut details = HashMap::new();
         for row in rows {
-            let (source, session_id, source_path, project) = row?;
+            let (source, session_id, source_path, detail) = row?;
             let Some(source) = SourceKind::from_label(&source) else {
                 continue;
             };
-            projects.insert(
-                (source, session_id, source_path),
-                display_project_name(&project),
-            );
+            details.insert((source, session_id, source_path), detail);
         }
-        Ok(projects)
+        Ok(details)
     }
 }
 
@@ -492,6 +630,11 @@ impl AnalyticsWriter {
     }
 
     pub fn record(&mut self, record: &Record) -> Result<()> {
+        // Notes, todo lists and plans are kept beside the session, not
+        // messages of it.
+        if crate::types::is_side_role(&record.role) {
+            return Ok(());
+        }
         let key = SessionKey {
             source: record.source,
             session_id: record.session_id.clone(),
@@ -506,6 +649,9 @@ impl AnalyticsWriter {
                 started_at: record.ts,
                 last_at: record.ts,
                 message_count: 0,
+                title: None,
+                title_at: 0,
+                title_kind: TitleKind::None,
             });
         if record.ts < entry.started_at {
             entry.started_at = record.ts;
@@ -516,6 +662,26 @@ impl AnalyticsWriter {
                 entry.project = record.project.clone();
             }
         }
+        if record.role == SUMMARY_ROLE {
+            // Later summaries describe more of the conversation.
+            if let Some(title) = session_title(&record.text)
+                && (entry.title_kind != TitleKind::Summary || record.ts >= entry.title_at)
+            {
+                entry.title = Some(title);
+                entry.title_at = record.ts;
+                entry.title_kind = TitleKind::Summary;
+            }
+            return Ok(());
+        }
+        if record.role == "user"
+            && entry.title_kind != TitleKind::Summary
+            && (entry.title_kind == TitleKind::None || record.ts < entry.title_at)
+            && let Some(title) = session_title(&record.text)
+        {
+            entry.title = Some(title);
+            entry.title_at = record.ts;
+            entry.title_kind = TitleKind::Message;
+        }
         entry.message_count = entry.message_count.saturating_add(1);
         Ok(())
     }
@@ -538,9 +704,10 @@ impl AnalyticsWriter {
                 r#"
                 INSERT INTO sessions(
                     source, session_id, source_path, project, cwd, git_root, git_common_dir,
-                    repo_project, started_at, last_at, message_count, resolution_status
+                    repo_project, started_at, last_at, message_count, resolution_status,
+                    title, title_kind
                 )
-                VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
                 ON CONFLICT(source, session_id, source_path) DO UPDATE SET
                     project = excluded.project,
                     cwd = excluded.cwd,
@@ -550,7 +717,17 @@ impl AnalyticsWriter {
                     started_at = MIN(sessions.started_at, excluded.started_at),
                     last_at = MAX(sessions.last_at, excluded.last_at),
                     message_count = sessions.message_count + excluded.message_count,
-                    resolution_status = excluded.resolution_status
+                    resolution_status = excluded.resolution_status,
+                    title = CASE
+                        WHEN sessions.title IS NULL OR excluded.title_kind = 'summary'
+                            THEN COALESCE(excluded.title, sessions.title)
+                        ELSE sessions.title
+                    END,
+                    title_kind = CASE
+                        WHEN sessions.title IS NULL OR excluded.title_kind = 'summary'
+                            THEN COALESCE(excluded.title_kind, sessions.title_kind)
+                        ELSE sessions.title_kind
+                    END
                 "#,
             )?;
             for (session, metadata) in sessions {
@@ -567,6 +744,8 @@ impl AnalyticsWriter {
                     session.last_at as i64,
                     session.message_count as i64,
                     metadata.resolution_status,
+                    session.title,
+                    session.title_kind.as_str(),
                 ])?;
             }
         }
@@ -694,7 +873,16 @@ fn common_dir_project_name(path: &str) -> Option<String> {
     path_file_name(path.to_string_lossy().as_ref())
 }
 
-fn display_project_name(project: &str) -> String {
+/// How `project` is shown: its alias if it was renamed, otherwise a decoded
+/// path for names derived from one.
+pub(crate) fn shown_project_name(project: &str, aliases: &ProjectAliases) -> String {
+    match aliases.alias(project) {
+        Some(alias) => alias.to_string(),
+        None => display_project_name(project),
+    }
+}
+
+pub(crate) fn display_project_name(project: &str) -> String {
     decode_encoded_project_path(project).unwrap_or_else(|| project.to_string())
 }
 
@@ -885,6 +1073,32 @@ fn parse_copilot_workspace_cwd(contents: &str) -> CopilotWorkspaceCwd {
     workspace
 }
 
+/// Derives a one-line session title from a user message or agent summary:
+/// its lines joined, whitespace-collapsed and capped in length.
+/// Messages that open with an injected context block (`<environment_context>`,
+/// `<command-name>`, ...) are skipped so harness boilerplate never becomes a
+/// title.
+pub(crate) fn session_title(text: &str) -> Option<String> {
+    let text = text.trim_start();
+    if text.starts_with('<') {
+        return None;
+    }
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    if collapsed.chars().count() <= SESSION_TITLE_MAX_CHARS {
+        return Some(collapsed);
+    }
+    let mut title: String = collapsed
+        .chars()
+        .take(SESSION_TITLE_MAX_CHARS - 1)
+        .collect();
+    title.truncate(title.trim_end().len());
+    title.push('…');
+    Some(title)
+}
+
 pub fn analytics_path(state_dir: &Path) -> PathBuf {
     state_dir.join("analytics.sqlite")
 }
@@ -921,7 +1135,7 @@ pub fn backfill_from_index(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::RecordLinks;
+
     use std::fs;
 
     fn record(project: &str, session_id: &str, source_path: &Path, ts: u64) -> Record {
@@ -934,11 +1148,8 @@ mod tests {
             turn_id: ts as u32,
             role: "user".to_string(),
             text: "hello".to_string(),
-            tool_name: None,
-            tool_input: None,
-            tool_output: None,
-            links: RecordLinks::default(),
             source_path: source_path.to_string_lossy().to_string(),
+            ..Default::default()
         }
     }
 
@@ -984,6 +1195,57 @@ mod tests {
         assert_eq!(rows[0].last_at, 20);
     }
 
+    #[test]
+    fn session_title_prefers_summary_over_first_user_message() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let transcript = tmp.path().join("session.jsonl");
+        let db = tmp.path().join("analytics.sqlite");
+        let mut writer = AnalyticsWriter::open(&db).expect("open analytics");
+        let mut injected = record("memex", "s1", &transcript, 5);
+        injected.text = "<environment_context>\n  <cwd>/tmp</cwd>\n</environment_context>".into();
+        let mut second = record("memex", "s1", &transcript, 20);
+        second.text = "and then the follow-up".to_string();
+        let mut first = record("memex", "s1", &transcript, 10);
+        first.text = "  Why   is the\nindexer slow?".to_string();
+        for record in [&injected, &second, &first] {
+            writer.record(record).expect("record");
+        }
+        writer.flush().expect("flush");
+
+        let store = AnalyticsStore::open(&db).expect("open store");
+        let rows = store
+            .query_sessions(None, None, None, ProjectGrouping::Flat, None)
+            .expect("query");
+        assert_eq!(rows[0].title.as_deref(), Some("Why is the indexer slow?"));
+
+        let mut writer = AnalyticsWriter::open(&db).expect("reopen analytics");
+        let mut summary = record("memex", "s1", &transcript, 30);
+        summary.role = SUMMARY_ROLE.to_string();
+        summary.text = "Indexer performance investigation".to_string();
+        writer.record(&summary).expect("record summary");
+        writer
+            .record(&record("memex", "s1", &transcript, 40))
+            .expect("record");
+        writer.flush().expect("flush");
+
+        let rows = store
+            .query_sessions(None, None, None, ProjectGrouping::Flat, None)
+            .expect("query");
+        assert_eq!(
+            rows[0].title.as_deref(),
+            Some("Indexer performance investigation")
+        );
+        assert_eq!(rows[0].message_count, 4);
+    }
+
+    #[test]
+    fn session_title_truncates_long_messages() {
+        let title = session_title(&"word ".repeat(40)).expect("title");
+        assert_eq!(title.chars().count(), SESSION_TITLE_MAX_CHARS);
+        assert!(title.ends_with('…'));
+        assert_eq!(session_title("   \n  "), None);
+    }
+
     #[test]
     fn read_only_store_rejects_writes() {
         let tmp = tempfile::tempdir().expect("tempdir");
@@ -0,0 +1,240 @@
+//! `memex append`: messages written straight into the index by scripts and
+//! agents memex has no adapter for. Each session's messages go to a JSONL
+//! log under the imports directory, read like any other transcript, so they
+//! survive a full reindex; appending then reads on in just that log, and the
+//! message is searchable once the call returns.
+
+use crate::config::Paths;
+use crate::index::SearchIndex;
+use crate::ingest::{
+    FileTask, IngestOptions, IngestReport, ParseContext, SourceAdapter, imports_dir, ingest_paths,
+};
+use crate::types::{AgentSettings, NOTE_ROLE, Record, RecordLinks, SourceKind, TURN_ROLE};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// One appended message: a line of its session's log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppendedMessage {
+    pub session_id: String,
+    pub project: String,
+    pub role: String,
+    pub text: String,
+    /// Milliseconds since the epoch.
+    pub ts: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+}
+
+pub fn append_dir(paths: &Paths) -> PathBuf {
+    imports_dir(paths).join("append")
+}
+
+/// Log holding `session_id`'s appended messages. Characters that can't go
+/// in a file name become `_`.
+pub fn append_log_path(paths: &Paths, session_id: &str) -> PathBuf {
+    let name: String = session_id
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    append_dir(paths).join(format!("{name}.jsonl"))
+}
+
+/// Adds `message` to its session's log and indexes it, along with anything
+/// else in the log not indexed yet. Callers hold the write lock.
+pub fn append_message(
+    paths: &Paths,
+    index: &SearchIndex,
+    options: &IngestOptions,
+    message: &AppendedMessage,
+) -> Result<IngestReport> {
+    if message.session_id.trim().is_empty() {
+        return Err(anyhow!("session id is empty"));
+    }
+    if message.text.trim().is_empty() {
+        return Err(anyhow!("nothing to append: the text is empty"));
+    }
+    match message.role.as_str() {
+        "" => return Err(anyhow!("role is empty")),
+        NOTE_ROLE | TURN_ROLE => {
+            return Err(anyhow!(
+                "role {} is reserved; use `memex note` for notes",
+                message.role
+            ));
+        }
+        _ => {}
+    }
+    let path = append_log_path(paths, &message.session_id);
+    fs::create_dir_all(append_dir(paths))?;
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(line.as_bytes())?;
+    ingest_paths(paths, index, options, SourceKind::APPENDED, &[path])
+}
+
+/// Reads the logs [`append_message`] writes.
+pub(crate) struct AppendAdapter {
+    pub(crate) dir: PathBuf,
+}
+
+impl SourceAdapter for AppendAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::APPENDED
+    }
+
+    fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        if !self.dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut logs: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect();
+        logs.sort();
+        Ok(logs)
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        let contents = fs::read(&task.path)?;
+        let start = (task.offset as usize).min(contents.len());
+        let mut offset = start;
+        let mut turn_id = task.turn_id;
+        for line in contents[start..].split_inclusive(|byte| *byte == b'\n') {
+            // A line still being written is read next time.
+            if !line.ends_with(b"\n") {
+                break;
+            }
+            offset += line.len();
+            let Ok(message) = serde_json::from_slice::<AppendedMessage>(line) else {
+                continue;
+            };
+            ctx.send(appended_record(
+                message,
+                ctx.next_doc_id(),
+                turn_id,
+                &task.path,
+            ))?;
+            turn_id += 1;
+        }
+        ctx.add_parsed_bytes(SourceKind::APPENDED, (offset - start) as u64);
+        ctx.finish_file(task, offset as u64, turn_id)
+    }
+}
+
+fn appended_record(message: AppendedMessage, doc_id: u64, turn_id: u32, log: &Path) -> Record {
+    Record {
+        source: SourceKind::APPENDED,
+        doc_id,
+        ts: message.ts,
+        project: message.project,
+        session_id: message.session_id,
+        turn_id,
+        tool_input: (message.role == "tool_use").then(|| message.text.clone()),
+        tool_output: (message.role == "tool_result").then(|| message.text.clone()),
+        role: message.role,
+        text: message.text,
+        tool_name: message.tool_name,
+        links: RecordLinks::default(),
+        settings: AgentSettings::default(),
+        attachments: Vec::new(),
+        source_path: log.to_string_lossy().to_string(),
+        ingested_at: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IndexedToolContentLimits;
+    use crate::embed::{EmbedRuntimeConfig, ModelChoice};
+
+    fn options() -> IngestOptions {
+        IngestOptions {
+            claude_source: PathBuf::from("/nonexistent"),
+            include_agents: false,
+            include_codex: false,
+            include_opencode: false,
+            include_cursor: false,
+            include_pi: false,
+            include_copilot: false,
+            embeddings: false,
+            backfill_embeddings: false,
+            model: ModelChoice::default(),
+            embed_runtime: EmbedRuntimeConfig::default(),
+            tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
+        }
+    }
+
+    fn message(role: &str, text: &str, ts: u64) -> AppendedMessage {
+        AppendedMessage {
+            session_id: "bot/run 7".to_string(),
+            project: "homegrown".to_string(),
+            role: role.to_string(),
+            text: text.to_string(),
+            ts,
+            tool_name: None,
+        }
+    }
+
+    #[test]
+    fn appended_messages_are_searchable_at_once_and_kept_on_reingest() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = options();
+
+        let report = append_message(
+            &paths,
+            &index,
+            &options,
+            &message("user", "rotate the staging certs", 1_000),
+        )
+        .expect("append");
+        assert_eq!(report.records_added, 1);
+        append_message(
+            &paths,
+            &index,
+            &options,
+            &message("assistant", "rotated both", 2_000),
+        )
+        .expect("append reply");
+        assert!(append_message(&paths, &index, &options, &message("turn", "x", 3_000)).is_err());
+
+        let mut records = index.records_by_session_id("bot/run 7").expect("records");
+        records.sort_by_key(|record| record.turn_id);
+        let texts: Vec<&str> = records
+            .iter()
+            .filter(|record| record.role != TURN_ROLE)
+            .map(|record| record.text.as_str())
+            .collect();
+        assert_eq!(texts, ["rotate the staging certs", "rotated both"]);
+        assert!(records.iter().all(|r| r.source == SourceKind::APPENDED));
+        assert_eq!(records[0].project, "homegrown");
+        assert_eq!(
+            records[0].source_path,
+            append_log_path(&paths, "bot/run 7").to_string_lossy()
+        );
+
+        // A normal ingest finds the log already indexed.
+        let report = crate::ingest::ingest_all(&paths, &index, &options).expect("ingest");
+        assert_eq!(report.records_added, 0);
+    }
+}
@@ -0,0 +1,652 @@
+//! Snapshots of the whole data directory for `memex backup`: the index,
+//! vectors, state and config.toml in one zstd-compressed tar archive, with a
+//! SHA-256 per file in a manifest so a restore can tell a damaged archive
+//! from a good one before it touches anything.
+
+use crate::config::Paths;
+use crate::moment::now_millis;
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub const BACKUP_EXTENSION: &str = ".tar.zst";
+
+/// Manifest entry name; written after every file, as the archive's last entry.
+const MANIFEST: &str = "manifest.json";
+const MANIFEST_FORMAT: u32 = 1;
+const ZSTD_LEVEL: i32 = 3;
+
+/// Directories under the data root that a backup carries.
+const DATA_DIRS: [&str; 4] = ["index", "index-shards", "vectors", "state"];
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: u32,
+    pub memex_version: String,
+    /// Unix millis when the backup was taken.
+    pub created_at: u64,
+    pub files: Vec<BackupFile>,
+}
+
+impl BackupManifest {
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// Path relative to the data root, `/`-separated.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub name: String,
+    pub path: PathBuf,
+    /// Size of the compressed archive.
+    pub size: u64,
+    /// Unix millis the archive was last written.
+    pub modified_at: u64,
+}
+
+/// Writes a backup of `paths` into `dir` and returns its path. The archive
+/// only appears under its final name once complete. Callers hold the write
+/// lock so nothing changes while files are read.
+pub fn create_backup(paths: &Paths, dir: &Path) -> Result<(PathBuf, BackupManifest)> {
+    fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    let created_at = now_millis();
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let path = dir.join(format!("memex-{stamp}{BACKUP_EXTENSION}"));
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+    let partial = dir.join(format!(".memex-{stamp}{BACKUP_EXTENSION}.partial"));
+
+    let result = (|| -> Result<BackupManifest> {
+        let mut encoder = zstd::Encoder::new(File::create(&partial)?, ZSTD_LEVEL)?;
+        encoder.include_checksum(true)?;
+        let mut builder = tar::Builder::new(encoder);
+        let mut files = Vec::new();
+        for relative in backup_files(paths)? {
+            let source = live_path(paths, &relative);
+            let metadata = fs::metadata(&source)?;
+            let mut header = tar::Header::new_gnu();
+            header.set_size(metadata.len());
+            header.set_mode(0o644);
+            header.set_mtime(
+                metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |duration| duration.as_secs()),
+            );
+            let mut reader = HashingReader::new(File::open(&source)?);
+            builder
+                .append_data(&mut header, &relative, &mut reader)
+                .with_context(|| format!("back up {relative}"))?;
+            let (size, sha256) = reader.finish();
+            if size != metadata.len() {
+                bail!("{relative} changed while it was being backed up");
+            }
+            files.push(BackupFile {
+                path: relative,
+                size,
+                sha256,
+            });
+        }
+        let manifest = BackupManifest {
+            format: MANIFEST_FORMAT,
+            memex_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at,
+            files,
+        };
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(json.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(created_at / 1000);
+        builder.append_data(&mut header, MANIFEST, json.as_slice())?;
+        let mut file = builder.into_inner()?.finish()?;
+        file.flush()?;
+        file.sync_all()?;
+        Ok(manifest)
+    })();
+    match result {
+        Ok(manifest) => {
+            fs::rename(&partial, &path)?;
+            Ok((path, manifest))
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&partial);
+            Err(err)
+        }
+    }
+}
+
+/// Files a backup carries, sorted and named as if under a single data root
+/// whatever the layout: everything under the data directories except lock
+/// files, plus config.toml.
+fn backup_files(paths: &Paths) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for dir in DATA_DIRS {
+        let base = paths.entry(dir);
+        if !base.exists() {
+            continue;
+        }
+        for entry in walkdir::WalkDir::new(&base).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() || is_lock_file(entry.file_name()) {
+                continue;
+            }
+            let relative = archive_path(entry.path().strip_prefix(&base)?)?;
+            files.push(format!("{dir}/{relative}"));
+        }
+    }
+    if paths.config.is_file() {
+        files.push(CONFIG_FILE.to_string());
+    }
+    Ok(files)
+}
+
+/// Where the file an archive names `relative` lives in `paths`.
+fn live_path(paths: &Paths, relative: &str) -> PathBuf {
+    match relative.split_once('/') {
+        Some((top, rest)) => paths.entry(top).join(rest),
+        None => paths.entry(relative),
+    }
+}
+
+/// memex's write lock, the vector manifest lock and Tantivy's own locks
+/// belong to whoever holds them now, not to the snapshot.
+fn is_lock_file(name: &std::ffi::OsStr) -> bool {
+    let name = name.to_string_lossy();
+    name == "write.lock"
+        || name == "manifest.lock"
+        || (name.starts_with(".tantivy-") && name.ends_with(".lock"))
+}
+
+fn archive_path(relative: &Path) -> Result<String> {
+    let parts: Vec<String> = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => Ok(part.to_string_lossy().into_owned()),
+            _ => Err(anyhow!("unexpected path {}", relative.display())),
+        })
+        .collect::<Result<_>>()?;
+    Ok(parts.join("/"))
+}
+
+/// Backups in `dir`, newest first.
+pub fn list_backups(dir: &Path) -> Result<Vec<BackupInfo>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("read {}", dir.display())),
+    };
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || !name.ends_with(BACKUP_EXTENSION) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        backups.push(BackupInfo {
+            name,
+            path: entry.path(),
+            size: metadata.len(),
+            modified_at: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_millis() as u64),
+        });
+    }
+    backups.sort_by(|a, b| {
+        b.modified_at
+            .cmp(&a.modified_at)
+            .then_with(|| b.name.cmp(&a.name))
+    });
+    Ok(backups)
+}
+
+/// The archive `name` refers to: a path to one, a backup in `dir` with or
+/// without its extension, or `latest`.
+pub fn resolve_backup(dir: &Path, name: &str) -> Result<PathBuf> {
+    if name == "latest" {
+        return list_backups(dir)?
+            .into_iter()
+            .next()
+            .map(|backup| backup.path)
+            .ok_or_else(|| anyhow!("no backups in {}", dir.display()));
+    }
+    let given = PathBuf::from(name);
+    if given.is_file() {
+        return Ok(given);
+    }
+    for candidate in [
+        dir.join(name),
+        dir.join(format!("{name}{BACKUP_EXTENSION}")),
+    ] {
+        if candidate.is_file() {
+            return Ok(candidate);
+        }
+    }
+    Err(anyhow!("no backup named {name} (see `memex backup list`)"))
+}
+
+/// Replaces the data in `paths` with the backup at `archive`. Every file is
+/// unpacked and checked against the manifest first; only then are the index,
+/// vectors, state and config.toml swapped in by renames, which are put back
+/// if one fails partway. Callers hold the write lock, which stays in place
+/// throughout.
+pub fn restore_backup(paths: &Paths, archive: &Path) -> Result<BackupManifest> {
+    let names: Vec<&str> = DATA_DIRS.into_iter().chain([CONFIG_FILE]).collect();
+    let cleanup = |suffix: &str| -> Result<()> {
+        for name in &names {
+            let path = beside(&paths.entry(name), suffix);
+            if path.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    };
+    cleanup(STAGED)?;
+    cleanup(PREVIOUS)?;
+    let manifest = match unpack_verified(archive, |name| beside(&paths.entry(name), STAGED)) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            let _ = cleanup(STAGED);
+            return Err(err);
+        }
+    };
+    if let Err(err) = swap_in(paths, &names) {
+        let _ = cleanup(STAGED);
+        // Only empty ones: anything a failed rollback left aside stays there.
+        for name in &names {
+            let _ = fs::remove_dir(beside(&paths.entry(name), PREVIOUS));
+        }
+        return Err(err.context("restore failed; the data directory was left as it was"));
+    }
+    cleanup(STAGED)?;
+    cleanup(PREVIOUS)?;
+    Ok(manifest)
+}
+
+/// Suffixes of the restored copy of an entry and of the live one it
+/// replaces, kept beside it so every move is a rename within one
+/// filesystem, even when state or config.toml live outside the data root.
+const STAGED: &str = ".restore";
+const PREVIOUS: &str = ".previous";
+
+fn beside(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Unpacks `archive`, each top-level entry into `staged(name)`, hashing each
+/// file, and checks the result against the archive's manifest: same files,
+/// sizes and checksums.
+fn unpack_verified(archive: &Path, staged: impl Fn(&str) -> PathBuf) -> Result<BackupManifest> {
+    let file = File::open(archive).with_context(|| format!("open {}", archive.display()))?;
+    let mut tar = tar::Archive::new(zstd::Decoder::new(file)?);
+    let mut unpacked: BTreeMap<String, (u64, String)> = BTreeMap::new();
+    let mut manifest: Option<BackupManifest> = None;
+    for entry in tar.entries().context("read backup archive")? {
+        let mut entry = entry.context("read backup archive")?;
+        let relative = archive_path(&entry.path()?)?;
+        if !entry.header().entry_type().is_file() {
+            bail!("unexpected entry {relative} in backup");
+        }
+        if relative == MANIFEST {
+            let mut json = String::new();
+            entry.read_to_string(&mut json)?;
+            manifest = Some(serde_json::from_str(&json).context("parse backup manifest")?);
+            continue;
+        }
+        let top = relative.split('/').next().unwrap_or_default();
+        if !(DATA_DIRS.contains(&top) && relative.contains('/')) && relative != CONFIG_FILE {
+            bail!("unexpected entry {relative} in backup");
+        }
+        let target = match relative.split_once('/') {
+            Some((top, rest)) => staged(top).join(rest),
+            None => staged(&relative),
+        };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = HashingWriter::new(File::create(&target)?);
+        io::copy(&mut entry, &mut writer).with_context(|| format!("unpack {relative}"))?;
+        unpacked.insert(relative, writer.finish()?);
+    }
+    let manifest =
+        manifest.ok_or_else(|| anyhow!("backup has no manifest; it may be truncated"))?;
+    if manifest.format > MANIFEST_FORMAT {
+        bail!(
+            "backup was written by memex {}, which is newer than this one",
+            manifest.memex_version
+        );
+    }
+    for file in &manifest.files {
+        match unpacked.remove(&file.path) {
+            Some((size, sha256)) if size == file.size && sha256 == file.sha256 => {}
+            Some(_) => bail!("backup is damaged: {} fails its checksum", file.path),
+            None => bail!("backup is damaged: {} is missing", file.path),
+        }
+    }
+    if let Some(extra) = unpacked.keys().next() {
+        bail!("backup is damaged: {extra} is not in its manifest");
+    }
+    Ok(manifest)
+}
+
+/// Moves each of `names` from where `paths` keeps it to its previous copy
+/// and its staged copy into its place, undoing every move made so far when
+/// one fails. Directories stay put and only what is in them moves, so lock
+/// files held in them, memex's write lock in state among them, stay with
+/// whoever holds them.
+fn swap_in(paths: &Paths, names: &[&str]) -> Result<()> {
+    // (from, to) for every rename done, newest last.
+    let mut done: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut emptied = Vec::new();
+    let result = (|| -> Result<()> {
+        for name in names {
+            let live = paths.entry(name);
+            let staged = beside(&live, STAGED);
+            let previous = beside(&live, PREVIOUS);
+            if !live.is_dir() && !staged.is_dir() {
+                if live.exists() {
+                    fs::rename(&live, &previous)
+                        .with_context(|| format!("move {} aside", live.display()))?;
+                    done.push((live.clone(), previous));
+                }
+                if staged.exists() {
+                    if let Some(parent) = live.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::rename(&staged, &live)
+                        .with_context(|| format!("move restored {name} into place"))?;
+                    done.push((staged, live));
+                }
+                continue;
+            }
+            if live.is_dir() {
+                fs::create_dir_all(&previous)?;
+                for entry in fs::read_dir(&live)? {
+                    let entry = entry?;
+                    if is_lock_file(&entry.file_name()) {
+                        continue;
+                    }
+                    let aside = previous.join(entry.file_name());
+                    fs::rename(entry.path(), &aside)
+                        .with_context(|| format!("move {} aside", entry.path().display()))?;
+                    done.push((entry.path(), aside));
+                }
+            }
+            if staged.is_dir() {
+                fs::create_dir_all(&live)?;
+                for entry in fs::read_dir(&staged)? {
+                    let entry = entry?;
+                    let target = live.join(entry.file_name());
+                    fs::rename(entry.path(), &target)
+                        .with_context(|| format!("move restored {name} into place"))?;
+                    done.push((entry.path(), target));
+                }
+            } else {
+                emptied.push(live);
+            }
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        for (from, to) in done.into_iter().rev() {
+            let _ = fs::rename(&to, &from);
+        }
+        return result;
+    }
+    // Left only its lock files, if any, by a backup that didn't have it.
+    for dir in emptied {
+        if dir.is_dir()
+            && fs::read_dir(&dir)?
+                .filter_map(|entry| entry.ok())
+                .all(|entry| is_lock_file(&entry.file_name()))
+        {
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+    Ok(())
+}
+
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            bytes: 0,
+        }
+    }
+
+    fn finish(self) -> (u64, String) {
+        (self.bytes, format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            bytes: 0,
+        }
+    }
+
+    fn finish(mut self) -> io::Result<(u64, String)> {
+        self.inner.flush()?;
+        Ok((self.bytes, format!("{:x}", self.hasher.finalize())))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn data_dir(root: &Path) -> Paths {
+        let paths = Paths::new(Some(root.to_path_buf())).unwrap();
+        write(&paths.index.join("meta.json"), "{\"segments\":[]}");
+        write(&paths.index.join(".tantivy-writer.lock"), "");
+        write(&paths.vectors.join("vectors.bin"), "0123456789");
+        write(&paths.state.join("ingest.json"), "{\"files\":{}}");
+        write(&paths.state.join("write.lock"), "42");
+        write(&root.join("config.toml"), "embeddings = true\n");
+        paths
+    }
+
+    /// Names under `dir` left over from staging a restore.
+    fn leftovers(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(STAGED) || name.ends_with(PREVIOUS))
+            .collect()
+    }
+
+    #[test]
+    fn restores_a_snapshot_over_later_changes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = data_dir(&tmp.path().join("memex"));
+        let backups = tmp.path().join("backups");
+        let (archive, manifest) = create_backup(&paths, &backups).unwrap();
+        let saved: Vec<&str> = manifest
+            .files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect();
+        assert_eq!(
+            saved,
+            [
+                "index/meta.json",
+                "vectors/vectors.bin",
+                "state/ingest.json",
+                "config.toml"
+            ]
+        );
+        let listed = list_backups(&backups).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(resolve_backup(&backups, "latest").unwrap(), archive);
+
+        write(&paths.vectors.join("vectors.bin"), "clobbered");
+        write(&paths.index.join("segment.idx"), "new segment");
+        fs::remove_file(tmp.path().join("memex/config.toml")).unwrap();
+        restore_backup(&paths, &archive).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(paths.vectors.join("vectors.bin")).unwrap(),
+            "0123456789"
+        );
+        assert!(!paths.index.join("segment.idx").exists());
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("memex/config.toml")).unwrap(),
+            "embeddings = true\n"
+        );
+        // The write lock the caller holds is never moved or replaced.
+        assert_eq!(
+            fs::read_to_string(paths.state.join("write.lock")).unwrap(),
+            "42"
+        );
+        assert!(leftovers(&tmp.path().join("memex")).is_empty());
+    }
+
+    #[test]
+    fn backs_up_state_and_config_kept_outside_the_data_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = Paths::with_layout(crate::config::Layout::xdg(tmp.path(), |_| None));
+        write(&paths.index.join("meta.json"), "{\"segments\":[]}");
+        write(&paths.state.join("ingest.json"), "{\"files\":{}}");
+        write(&paths.config, "embeddings = true\n");
+        let (archive, manifest) = create_backup(&paths, &tmp.path().join("backups")).unwrap();
+        let saved: Vec<&str> = manifest
+            .files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect();
+        assert_eq!(
+            saved,
+            ["index/meta.json", "state/ingest.json", "config.toml"]
+        );
+
+        fs::remove_file(paths.state.join("ingest.json")).unwrap();
+        write(&paths.state.join("write.lock"), "42");
+        fs::remove_file(&paths.config).unwrap();
+        restore_backup(&paths, &archive).unwrap();
+        assert!(paths.state.join("ingest.json").is_file());
+        assert_eq!(
+            fs::read_to_string(paths.state.join("write.lock")).unwrap(),
+            "42"
+        );
+        assert!(paths.config.is_file());
+        for dir in [
+            &paths.root,
+            paths.state.parent().unwrap(),
+            paths.config.parent().unwrap(),
+        ] {
+            assert!(leftovers(dir).is_empty(), "{}", dir.display());
+        }
+    }
+
+    #[test]
+    fn refuses_a_damaged_archive_without_touching_the_data() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = data_dir(&tmp.path().join("memex"));
+        let (archive, _) = create_backup(&paths, &tmp.path().join("backups")).unwrap();
+
+        // Same file list, but one file's contents swapped for others.
+        let mut tar = tar::Archive::new(zstd::Decoder::new(File::open(&archive).unwrap()).unwrap());
+        let tampered = tmp.path().join("tampered.tar.zst");
+        let mut builder = tar::Builder::new(
+            zstd::Encoder::new(File::create(&tampered).unwrap(), 3)
+                .unwrap()
+                .auto_finish(),
+        );
+        for entry in tar.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            if path == Path::new("vectors/vectors.bin") {
+                data = b"9876543210".to_vec();
+            }
+            let mut header = entry.header().clone();
+            builder
+                .append_data(&mut header, &path, data.as_slice())
+                .unwrap();
+        }
+        drop(builder.into_inner().unwrap());
+
+        write(&paths.vectors.join("vectors.bin"), "current");
+        let err = restore_backup(&paths, &tampered).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("vectors/vectors.bin fails its checksum"),
+            "{err}"
+        );
+        assert_eq!(
+            fs::read_to_string(paths.vectors.join("vectors.bin")).unwrap(),
+            "current"
+        );
+        assert!(leftovers(&tmp.path().join("memex")).is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+//! Search quality benchmark: labelled queries run against the index and
+//! scored by recall@k and mean reciprocal rank, so ranking changes can be
+//! compared with numbers.
+
+use crate::types::Record;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// A query and the results that should come back for it. With `sessions`,
+/// results are judged per session (the first hit of each counts); otherwise
+/// per record by `docs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BenchQuery {
+    pub query: String,
+    #[serde(default)]
+    pub sessions: Vec<String>,
+    #[serde(default)]
+    pub docs: Vec<u64>,
+    /// Restrict this query to one project.
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BenchFile {
+    queries: Vec<BenchQuery>,
+}
+
+/// Reads a TOML file of `[[queries]]` tables, or JSON of the form
+/// `{"queries": [{query, sessions}]}` when the file ends in `.json`.
+pub fn load_queries(path: &Path) -> Result<Vec<BenchQuery>> {
+    let data = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let file: BenchFile = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&data).with_context(|| format!("parse {}", path.display()))?
+    } else {
+        toml::from_str(&data).with_context(|| format!("parse {}", path.display()))?
+    };
+    if file.queries.is_empty() {
+        return Err(anyhow!("{} has no queries", path.display()));
+    }
+    for query in &file.queries {
+        if query.sessions.is_empty() && query.docs.is_empty() {
+            return Err(anyhow!(
+                "query '{}' lists no relevant sessions or docs",
+                query.query
+            ));
+        }
+    }
+    Ok(file.queries)
+}
+
+impl BenchQuery {
+    /// Recall among the top `k` of `ranked` (best first) and the reciprocal
+    /// rank of the first relevant result, 0 when none made the top `k`.
+    pub fn score(&self, ranked: &[Record], k: usize) -> (f64, f64) {
+        let (relevant, judged): (HashSet<String>, Vec<String>) = if self.sessions.is_empty() {
+            (
+                self.docs.iter().map(u64::to_string).collect(),
+                ranked
+                    .iter()
+                    .map(|record| record.doc_id.to_string())
+                    .collect(),
+            )
+        } else {
+            let mut seen = HashSet::new();
+            (
+                self.sessions.iter().cloned().collect(),
+                ranked
+                    .iter()
+                    .filter(|record| seen.insert(record.session_id.as_str()))
+                    .map(|record| record.session_id.clone())
+                    .collect(),
+            )
+        };
+        let mut found = 0usize;
+        let mut reciprocal_rank = 0.0;
+        for (rank, item) in judged.iter().take(k).enumerate() {
+            if relevant.contains(item) {
+                found += 1;
+                if reciprocal_rank == 0.0 {
+                    reciprocal_rank = 1.0 / (rank + 1) as f64;
+                }
+            }
+        }
+        (found as f64 / relevant.len() as f64, reciprocal_rank)
+    }
+}
+
+/// Averages over every query run in one search mode.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModeReport {
+    pub mode: String,
+    pub queries: usize,
+    pub k: usize,
+    pub recall: f64,
+    pub mrr: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct ModeScores {
+    queries: usize,
+    recall: f64,
+    reciprocal_rank: f64,
+}
+
+impl ModeScores {
+    pub fn add(&mut self, query: &BenchQuery, ranked: &[Record], k: usize) {
+        let (recall, reciprocal_rank) = query.score(ranked, k);
+        self.queries += 1;
+        self.recall += recall;
+        self.reciprocal_rank += reciprocal_rank;
+    }
+
+    pub fn report(&self, mode: &str, k: usize) -> ModeReport {
+        let queries = self.queries.max(1) as f64;
+        ModeReport {
+            mode: mode.to_string(),
+            queries: self.queries,
+            k,
+            recall: self.recall / queries,
+            mrr: self.reciprocal_rank / queries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SourceKind;
+
+    fn hit(doc_id: u64, session_id: &str) -> Record {
+        Record {
+            source: SourceKind::Claude,
+            doc_id,
+            project: "memex".to_string(),
+            session_id: session_id.to_string(),
+            role: "user".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scores_recall_and_reciprocal_rank_per_session_or_doc() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let path = tmp.path().join("queries.toml");
+        std::fs::write(
+            &path,
+            "[[queries]]\nquery = \"flaky login test\"\nsessions = [\"b\", \"d\"]\n\n[[queries]]\nquery = \"rotate index\"\ndocs = [7]\n",
+        )
+        .expect("write");
+        let queries = load_queries(&path).expect("load");
+        let ranked = [hit(1, "a"), hit(2, "a"), hit(3, "b"), hit(7, "c")];
+
+        // Sessions rank a, b, c: b is second, d never shows up.
+        assert_eq!(queries[0].score(&ranked, 10), (0.5, 0.5));
+        assert_eq!(queries[0].score(&ranked, 1), (0.0, 0.0));
+        // Doc 7 is the fourth record.
+        assert_eq!(queries[1].score(&ranked, 4), (1.0, 0.25));
+        assert_eq!(queries[1].score(&ranked, 3), (0.0, 0.0));
+
+        let mut scores = ModeScores::default();
+        scores.add(&queries[0], &ranked, 10);
+        scores.add(&queries[1], &ranked, 10);
+        let report = scores.report("lexical", 10);
+        assert_eq!(report.queries, 2);
+        assert_eq!(report.recall, 0.75);
+        assert_eq!(report.mrr, 0.375);
+
+        std::fs::write(&path, "[[queries]]\nquery = \"unlabelled\"\n").expect("write");
+        assert!(load_queries(&path).is_err());
+
+        let json = tmp.path().join("queries.json");
+        std::fs::write(
+            &json,
+            r#"{"queries": [{"query": "rotate index", "docs": [7]}]}"#,
+        )
+        .expect("write");
+        assert_eq!(load_queries(&json).expect("load json")[0].docs, [7]);
+    }
+}
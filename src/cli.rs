@@ -1,28 +1,75 @@
-use crate::analytics::{AnalyticsStore, analytics_path, backfill_from_index};
-use crate::config::{Paths, UserConfig, default_claude_source};
-use crate::embed::{EmbedRuntimeConfig, EmbedderHandle, ModelChoice};
-use crate::index::{QueryOptions, SearchIndex};
-use crate::ingest::{IngestOptions, ingest_all, ingest_if_stale};
+use crate::activity::{Bucket, bucket_activity, render_chart};
+use crate::analytics::{
+    AnalyticsStore, analytics_path, backfill_from_index, display_project_name, shown_project_name,
+};
+use crate::append::{AppendedMessage, append_dir, append_message};
+use crate::backup;
+use crate::bench::{ModeScores, load_queries};
+use crate::bundle::BundleOptions;
+use crate::config::{
+    Layout, Paths, UserConfig, default_claude_source, move_path, plan_home_migration,
+};
+use crate::elastic::{BULK_BATCH, BulkOutcome, ElasticTarget, ExportState, export_state_path};
+use crate::embed::{EmbedRuntimeConfig, EmbedderHandle, MEMEX_OFFLINE_ENV, ModelChoice};
+use crate::export::{EmbeddingExport, EmbeddingFormat, EmbeddingRow, npy_metadata_path};
+use crate::graph::ConversationGraph;
+use crate::hyperlink::Hyperlinks;
+use crate::index::{ANY_ATTACHMENT, QueryOptions, QueryTuning, SearchIndex, extract_query_filters};
+use crate::ingest::{
+    ArchiveImport, FileDecision, IngestOptions, ReindexScope, ScanExplanation, chatgpt_import_dir,
+    explain_scan, import_archive, import_chatgpt, imports_dir, ingest_all, ingest_if_stale,
+    plan_scan, reindex_scoped,
+};
+use crate::knowledge::{Knowledge, knowledge_path, refresh as refresh_knowledge};
+use crate::lock::{WriteLock, busy_message};
+use crate::moment::now_millis;
+use crate::notes::{SessionNotes, notes_path, restore_notes, set_note};
+use crate::outcomes::{
+    Outcome, OutcomePeriod, SessionOutcomes, outcome_stats, outcomes_path, set_outcome,
+};
+use crate::query::RecordQuery;
+use crate::repair::{self, VectorRepair};
+use crate::retention::{RetentionReport, RetentionState, prune, retention_state_path};
+use crate::scoring::{FusionExplanation, ScoreExplanation, apply_recency};
+use crate::shell::{find_in_path, notify_desktop, shell_command};
+use crate::state::{
+    IngestErrors, IngestState, ProjectAliases, ServiceStatus, ingest_errors_path,
+    project_aliases_path, project_frecency_path, rank_projects, record_project_visits,
+    record_service_run, service_status_path,
+};
+use crate::team::{
+    PUSH_BATCH, PushResponse, PushState, TeamRemote, TeamStore, push_state_path, records_to_push,
+    sanitize,
+};
+use crate::template::{Template, output_template};
+use crate::text::{self, pad_end, pad_start, summarize, take_width, take_width_end};
+use crate::topics::{TopicOptions, Topics, cluster_sessions, topics_path};
 use crate::transfer::{
     TransferMode as CoreTransferMode, TransferOptions, TransferTarget as CoreTransferTarget,
     transfer_session,
 };
+use crate::trash::Trash;
 use crate::tui;
-use crate::types::{RecordLinks, SourceFilter};
+use crate::types::{
+    AgentSettings, Attachment, NOTE_ROLE, RecordCursor, RecordLinks, SourceFilter, TURN_ROLE,
+};
 use crate::usage::{CostMode, UsageQuery, scan_usage};
-use crate::vector::VectorIndex;
+use crate::vector::{
+    ArchiveTier, StoredVectors, VectorIndex, archive_vectors_dir, vector_store_dirs,
+    vector_store_exists,
+};
 use anyhow::{Result, anyhow};
 use chrono::SecondsFormat;
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use regex::RegexBuilder;
 use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::io::Write;
-use std::path::PathBuf;
-use std::time::Duration;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(
@@ -42,6 +89,9 @@ pub struct Cli {
     /// Defaults to the interactive TUI when no command is given
     #[command(subcommand)]
     command: Option<Commands>,
+    /// No network access: skip update checks and never download models
+    #[arg(long, global = true)]
+    offline: bool,
 }
 
 #[derive(Args, Clone)]
@@ -115,45 +165,62 @@ EXAMPLES:
             hide = true
         )]
         watch_interval: u64,
+        /// Set by the index service: record each run's outcome and notify on failure
+        #[arg(long, hide = true)]
+        service: bool,
     },
-    /// Delete existing index and rebuild from scratch
-    Reindex {
-        #[command(flatten)]
-        index: IndexArgs,
-    },
-    /// Generate embeddings for semantic search (requires existing index)
-    Embed {
-        /// Embedding model: minilm (fast), bge, nomic, gemma (default, best quality), potion (tiny)
-        #[arg(long)]
-        model: Option<String>,
-        /// Path to memex data directory [default: ~/.memex]
-        #[arg(long)]
-        root: Option<PathBuf>,
-    },
+    #[command(flatten)]
+    Ingest(IngestCommand),
     /// Search indexed conversation history
     #[command(after_help = "\
 EXAMPLES:
     memex search \"error handling\"
     memex search \"API design\" --source claude --limit 50
     memex search \"auth\" --since 2024-01-01T00:00:00Z --semantic
+    memex search \"auth\" --since \"last week\" --until yesterday
+    memex search 'flaky test after:last-monday before:\"2 days ago\"'
     memex search \"bug\" --fields score,session_id,snippet --json-array
+    memex search \"auth\" --as-of 2024-01-16
+    memex search \"migration model:o3 sandbox:read-only\"
+    memex search \"retry backoff\" --hybrid --explain -v
+    memex search \"panic\" --no-semantic
+    memex search -q \"tantivy segment merge\" -q \"index compaction\"
+    memex search \"retry\" --template '{{ ts }} {{ session_id }} {{ snippet | truncate(80) }}'
 
 TIMESTAMP FORMAT:
     RFC3339: 2024-01-15T10:30:00Z or 2024-01-15T10:30:00-05:00
+    Date: 2024-01-15 (midnight UTC)
     Unix seconds: 1705315800
     Unix milliseconds: 1705315800000
+    Phrases: yesterday, last monday, this week, last month, 2 weeks ago
+    (from the start of the day or period they name, in local time)
 
 OUTPUT FIELDS (--fields):
-    score, ts, doc_id, project, role, session_id, source, source_path, text, snippet, matches
+    score, ts, doc_id, project, role, session_id, source, source_path, ingested_at, text, snippet,
+    snippet_highlights, matches
     event_id, parent_event_id, logical_parent_event_id, parent_session_id, thread_source, conversation_kind
-    parent_tool_use_id, source_tool_use_id, source_tool_assistant_uuid")]
+    thread, parent_tool_use_id, source_tool_use_id, source_tool_assistant_uuid
+    model, sandbox, approval_policy, attachments, edits, explain
+
+QUERY FILTERS:
+    model:o3, sandbox:workspace-write, approval:on-request (Codex sessions)
+    has:image, has:document, has:attachment
+    edited:src/main.rs (records whose patch edits a file ending in that path)
+    cmd:cargo (tool calls whose shell command contains that word)
+    file:src/index.rs (records that mention, read or edit a file ending in that path)
+    after:yesterday, before:\"2 weeks ago\" (any TIMESTAMP FORMAT; hyphens for spaces work too)")]
     Search {
         /// Search query (keywords or natural language for semantic search)
-        query: String,
+        query: Option<String>,
+        /// Another phrasing to search for; results of every phrasing are
+        /// merged, keeping each session's best match. Repeatable.
+        #[arg(short = 'q', long = "query", value_name = "QUERY")]
+        queries: Vec<String>,
         /// Filter by project name
         #[arg(long)]
         project: Option<String>,
-        /// Filter by role (user, assistant, tool_use, tool_result)
+        /// Filter by role (user, assistant, tool_use, tool_result, note, or turn
+        /// for whole exchanges)
         #[arg(long)]
         role: Option<String>,
         /// Filter by tool name (e.g., Read, Edit, Bash)
@@ -171,21 +238,35 @@ OUTPUT FIELDS (--fields):
         /// Use hybrid search combining BM25 keyword and semantic scores
         #[arg(long)]
         hybrid: bool,
+        /// Keyword search only, and don't embed new records when refreshing
+        /// the index first, so the embedding model never loads
+        #[arg(long, conflicts_with_all = ["semantic", "hybrid"])]
+        no_semantic: bool,
         /// Minimum score threshold to include in results
         #[arg(long)]
         min_score: Option<f32>,
-        /// Weight for recency boost (0 = no boost, higher = more recent preferred)
-        #[arg(long, default_value_t = 1.0)]
-        recency_weight: f32,
-        /// Half-life in days for recency decay (lower = faster decay)
-        #[arg(long, default_value_t = 30.0)]
-        recency_half_life_days: f32,
-        /// Only include results after this timestamp (RFC3339 or unix seconds/ms)
+        /// Scoring profile: default, precision, recall, recent, or one from config
+        #[arg(long)]
+        profile: Option<String>,
+        /// Weight for recency boost (0 = no boost, higher = more recent preferred).
+        /// Overrides the scoring profile.
+        #[arg(long)]
+        recency_weight: Option<f32>,
+        /// Half-life in days for recency decay (lower = faster decay).
+        /// Overrides the scoring profile.
+        #[arg(long)]
+        recency_half_life_days: Option<f32>,
+        /// Only include results after this timestamp (RFC3339, unix seconds/ms
+        /// or a phrase like "last week")
         #[arg(long, value_name = "TIMESTAMP")]
         since: Option<String>,
-        /// Only include results before this timestamp (RFC3339 or unix seconds/ms)
+        /// Only include results before this timestamp (RFC3339, unix seconds/ms
+        /// or a phrase like "yesterday")
         #[arg(long, value_name = "TIMESTAMP")]
         until: Option<String>,
+        /// Only consider records memex had ingested by this timestamp
+        #[arg(long = "as-of", value_name = "TIMESTAMP")]
+        as_of: Option<String>,
         /// Maximum number of results to return
         #[arg(long, default_value_t = 20)]
         limit: usize,
@@ -201,18 +282,49 @@ OUTPUT FIELDS (--fields):
         /// Comma-separated list of fields to include in output
         #[arg(long, value_name = "FIELDS")]
         fields: Option<String>,
+        /// Render each result through a template: the template itself, a
+        /// name from `[templates]` in config.toml, or a template file
+        #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["verbose", "json_array"])]
+        template: Option<String>,
         /// Sort results by score or timestamp
         #[arg(long, value_enum, default_value = "score")]
         sort: SortBy,
         /// Show verbose output with inline text preview
         #[arg(short, long)]
         verbose: bool,
+        /// Show why each result scored what it did: BM25 per matching term,
+        /// vector similarity, fusion ranks and weights, and the recency boost
+        #[arg(long)]
+        explain: bool,
+        /// Search the team server (`team_url` in config.toml) instead of the
+        /// local index; keyword search only
+        #[arg(long, conflicts_with_all = ["semantic", "hybrid", "explain"])]
+        remote: bool,
         /// Path to memex data directory [default: ~/.memex]
         #[arg(long)]
         root: Option<PathBuf>,
     },
+    #[command(flatten)]
+    Lookup(LookupCommand),
     /// Interactive terminal UI for browsing sessions
     Tui {
+        /// Most sessions listed for a query [default: tui_result_limit or 200]
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Messages shown around each match [default: preview_match_context, or
+        /// the match's whole exchange]
+        #[arg(long)]
+        context: Option<usize>,
+        /// Newest messages shown without a query [default: preview_tail_messages or 10]
+        #[arg(long)]
+        tail: Option<usize>,
+        /// Characters shown per message [default: preview_message_chars or 4000]
+        #[arg(long)]
+        message_chars: Option<usize>,
+        /// Start in lexical-only mode (toggle with `L`): index refreshes skip
+        /// embeddings and previews skip related sessions
+        #[arg(long)]
+        no_semantic: bool,
         /// Path to memex data directory [default: ~/.memex]
         #[arg(long)]
         root: Option<PathBuf>,
@@ -222,34 +334,14 @@ OUTPUT FIELDS (--fields):
         #[command(subcommand)]
         action: IndexServiceCommand,
     },
-    /// Display all messages from a specific session
-    Session {
-        /// Session ID (from search results or TUI)
-        session_id: String,
-        /// Show human-readable output with timestamps and role labels
-        #[arg(short, long)]
-        verbose: bool,
-        /// Path to memex data directory [default: ~/.memex]
-        #[arg(long)]
-        root: Option<PathBuf>,
-    },
-    /// Display a single document by its internal ID
-    Show {
-        /// Document ID (from search results)
-        doc_id: u64,
-        /// Pretty-print JSON output
-        #[arg(short, long)]
-        verbose: bool,
-        /// Path to memex data directory [default: ~/.memex]
-        #[arg(long)]
-        root: Option<PathBuf>,
-    },
-    /// Show index statistics (document count, vector count, storage paths)
-    Stats {
-        /// Path to memex data directory [default: ~/.memex]
-        #[arg(long)]
-        root: Option<PathBuf>,
-    },
+    #[command(flatten)]
+    Browse(BrowseCommand),
+    #[command(flatten)]
+    Maintain(MaintainCommand),
+    #[command(flatten)]
+    Annotate(AnnotateCommand),
+    #[command(flatten)]
+    Inspect(InspectCommand),
     /// Reconstruct local token usage from agent logs
     #[command(after_help = "\
 EXAMPLES:
@@ -293,1111 +385,6376 @@ EXAMPLES:
         force: bool,
     },
     /// Update memex to the latest version
+    #[command(
+        alias = "self-update",
+        after_help = "\
+EXAMPLES:
+    memex update
+    memex self-update --yes
+
+Downloads the release archive for this platform, verifies it against the
+published SHA-256 checksum, and atomically replaces the running executable.
+Refused when offline (`offline = true` in config.toml, or `--offline`)."
+    )]
     Update {
         /// Skip confirmation prompt
         #[arg(short = 'y', long)]
         yes: bool,
+        /// Path to memex data directory, whose config.toml may set `offline`
+        /// [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
     },
-    /// Share a session via agentexport
+    #[command(flatten)]
+    Sharing(SharingCommand),
+}
+
+/// Rebuilding, importing and embedding; flattened into [`Commands`].
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum IngestCommand {
+    /// Delete existing index and rebuild from scratch, or rebuild just some sources
     #[command(after_help = "\
+A source name for --source (claude, codex, opencode, cursor, pi, copilot,
+chatgpt) or a --path glob rebuilds just the matching files and keeps
+everything else, including existing embeddings.
+
 EXAMPLES:
-    memex share abc123              # Share session abc123
-    memex share abc123 --title \"Bug fix session\"  # Share with custom title
+    memex reindex                                   # Rebuild everything
+    memex reindex --source codex                    # Re-read only Codex sessions
+    memex reindex --path '~/.claude/projects/-Users-me-Code-app/*.jsonl'")]
+    Reindex {
+        #[command(flatten)]
+        index: IndexArgs,
+        /// Only rebuild source files whose full path matches this glob
+        #[arg(long, value_name = "GLOB")]
+        path: Option<String>,
+    },
+    /// Bulk-import a directory of old Claude and Codex transcripts
+    #[command(after_help = "\
+Tuned for a one-shot import of years of logs rather than day-to-day
+indexing: a larger index writer heap, no segment merges or commits until the
+end, embeddings computed on their own thread while indexing continues (with
+an ETA), then one commit and a merge into a single segment.
 
-REQUIREMENTS:
-    Requires agentexport to be installed: brew install nicosuave/tap/agentexport")]
-    Share {
-        /// Session ID (from search results or TUI)
-        session_id: String,
-        /// Title for the share (optional)
+Codex rollout-*.jsonl files are read as Codex sessions and every other .jsonl
+as a Claude session. Files already imported and unchanged are skipped, so an
+interrupted import can be rerun.
+
+EXAMPLES:
+    memex import-archive ~/backups/claude-2023
+    memex import-archive /mnt/old-laptop/.codex/sessions --embeddings
+    memex import-archive ./logs --heap-mb 4096")]
+    ImportArchive {
+        /// Directory to search for transcripts
+        dir: PathBuf,
+        /// Index writer memory in MiB; more makes fewer, larger segments
+        #[arg(long, default_value_t = 1024, value_parser = clap::value_parser!(u64).range(64..))]
+        heap_mb: u64,
+        /// Include agent subprocess conversations (Claude Code subagents)
         #[arg(long)]
-        title: Option<String>,
+        include_agents: bool,
+        /// Generate embeddings for semantic search during the import
+        #[arg(long)]
+        embeddings: bool,
+        /// Skip embedding generation (overrides config default)
+        #[arg(long)]
+        no_embeddings: bool,
+        /// Embedding model: minilm (fast), bge, nomic, gemma (default, best quality), potion (tiny)
+        #[arg(long)]
+        model: Option<String>,
         /// Path to memex data directory [default: ~/.memex]
         #[arg(long)]
         root: Option<PathBuf>,
     },
-    /// Transfer an indexed session into another agent backend
-    #[command(after_help = "\
+    /// Import conversations from a ChatGPT data export
+    #[command(
+        name = "import-chatgpt",
+        after_help = "\
+Reads conversations.json from the export OpenAI emails (Settings > Data
+controls > Export data): the .zip itself, the directory it unpacks to, or
+the file. Each conversation becomes a session from the chatgpt source under
+the chatgpt project, titled as in ChatGPT; edited messages keep only the
+branch last shown. Search them with --source chatgpt.
+
+Every export holds the whole account, so importing a newer one replaces the
+conversations from the last import, and importing the same one again does
+nothing. Unpacking a .zip needs `unzip` on PATH.
+
 EXAMPLES:
-    memex transfer abc123
-    memex transfer abc123 --to pi
-    memex transfer abc123 --to opencode
-    memex transfer abc123 --mode strict --turns 80
-    memex transfer abc123 --source pi --to codex
-    memex transfer abc123 --dry-run")]
-    Transfer {
-        /// Session ID (from search results or TUI)
-        session_id: String,
-        /// Filter by source when a session id appears in multiple backends
+    memex import-chatgpt ~/Downloads/chatgpt-export.zip
+    memex import-chatgpt ~/Downloads/chatgpt-export/conversations.json --embeddings
+    memex search 'semver regex' --source chatgpt"
+    )]
+    ImportChatgpt {
+        /// Export .zip, its unpacked directory, or conversations.json
+        export: PathBuf,
+        /// Generate embeddings for semantic search during the import
         #[arg(long)]
-        source: Option<SourceFilter>,
-        /// Target backend to import into
-        #[arg(long, value_enum, default_value = "codex")]
-        to: TransferTarget,
-        /// Compact imports text turns; strict includes tool activity as text notes
-        #[arg(long, value_enum, default_value = "compact")]
-        mode: TransferMode,
-        /// Limit imported turns (Pi defaults to 60 and caps at 400)
+        embeddings: bool,
+        /// Skip embedding generation (overrides config default)
         #[arg(long)]
-        turns: Option<usize>,
-        /// Generate the intermediate transcript without importing into the target
+        no_embeddings: bool,
+        /// Embedding model: minilm (fast), bge, nomic, gemma (default, best quality), potion (tiny)
         #[arg(long)]
-        dry_run: bool,
+        model: Option<String>,
         /// Path to memex data directory [default: ~/.memex]
         #[arg(long)]
         root: Option<PathBuf>,
     },
-}
-
-#[derive(Debug, Clone, Copy, ValueEnum)]
-#[value(rename_all = "kebab-case")]
-enum TransferTarget {
-    Codex,
-    Claude,
-    Copilot,
-    Cursor,
-    Opencode,
-    Pi,
-}
-
-impl From<TransferTarget> for CoreTransferTarget {
-    fn from(value: TransferTarget) -> Self {
-        match value {
-            TransferTarget::Codex => CoreTransferTarget::Codex,
-            TransferTarget::Claude => CoreTransferTarget::Claude,
-            TransferTarget::Copilot => CoreTransferTarget::Copilot,
-            TransferTarget::Cursor => CoreTransferTarget::Cursor,
-            TransferTarget::Opencode => CoreTransferTarget::Opencode,
-            TransferTarget::Pi => CoreTransferTarget::Pi,
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, ValueEnum)]
-#[value(rename_all = "kebab-case")]
-enum TransferMode {
-    Compact,
-    Strict,
-}
+    /// Generate embeddings for semantic search (requires existing index)
+    Embed {
+        /// Embedding model: minilm (fast), bge, nomic, gemma (default, best quality), potion (tiny)
+        #[arg(long)]
+        model: Option<String>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Work with stored embeddings
+    #[command(after_help = "\
+EXAMPLES:
+    memex embeddings export -o embeddings.parquet
+    memex embeddings export -o embeddings.npy --project memex
+
+Parquet files have doc_id, session_id, project, ts and a vector list column.
+.npy exports write a float32 matrix plus a .jsonl file beside it with each
+row's metadata, in the same order:
+    pandas.read_parquet(\"embeddings.parquet\")
+    numpy.load(\"embeddings.npy\"), pandas.read_json(\"embeddings.jsonl\", lines=True)")]
+    Embeddings {
+        #[command(subcommand)]
+        action: EmbeddingsCommand,
+    },
+    /// List embedding models and download them ahead of time
+    #[command(after_help = "\
+EXAMPLES:
+    memex models list
+    memex models pull gemma
 
-impl From<TransferMode> for CoreTransferMode {
-    fn from(value: TransferMode) -> Self {
-        match value {
-            TransferMode::Compact => CoreTransferMode::Compact,
-            TransferMode::Strict => CoreTransferMode::Strict,
-        }
-    }
+Models download on first use otherwise. With `offline = true` in config.toml,
+`--offline` or MEMEX_OFFLINE=1, a model that is not downloaded yet is an error
+instead of a download.")]
+    Models {
+        #[command(subcommand)]
+        action: ModelsCommand,
+    },
 }
 
+/// Answer lookups, SQL and search benchmarks; flattened into [`Commands`].
 #[derive(Subcommand)]
-enum IndexServiceCommand {
-    /// Enable automatic background indexing (launchd on macOS, systemd on Linux)
-    Enable {
-        #[command(flatten)]
-        index: IndexArgs,
-        /// Service label/name [default: com.memex.index (macOS) or memex-index (Linux)]
+#[allow(clippy::large_enum_variant)]
+enum LookupCommand {
+    /// Check whether you already asked a question, and what the agent answered
+    #[command(after_help = "\
+EXAMPLES:
+    memex asked \"how do I rotate a tantivy index\"
+    memex asked \"fix flaky ci\" --project memex --threshold 0.8
+
+Compares the question against the embeddings of your past prompts, so it
+needs embeddings (`memex embed`). Exits with status 1 when nothing similar
+was asked before.")]
+    Asked {
+        /// The question you are about to ask
+        question: String,
+        /// Minimum cosine similarity (0-1) for a past prompt to count
+        #[arg(long, default_value_t = 0.85)]
+        threshold: f32,
+        /// Maximum number of earlier askings to show
+        #[arg(long, default_value_t = 3)]
+        limit: usize,
+        /// Filter by project name
         #[arg(long)]
-        label: Option<String>,
-        /// Run as a long-lived process instead of periodic execution
+        project: Option<String>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
         #[arg(long)]
-        continuous: bool,
-        /// Seconds between index checks in continuous mode [default: 30]
-        #[arg(long, value_parser = clap::value_parser!(u64).range(1..), value_name = "SECONDS")]
-        poll_interval: Option<u64>,
-        /// Seconds between invocations in interval mode [default: 3600]
-        #[arg(long, value_parser = clap::value_parser!(u64).range(1..), value_name = "SECONDS")]
-        interval: Option<u64>,
-        /// Path for stdout log file [default: ~/.memex/index-service.log] (macOS only)
+        source: Option<SourceFilter>,
+        /// Emit newline-delimited JSON
         #[arg(long)]
-        stdout: Option<PathBuf>,
-        /// Path for stderr log file [default: ~/.memex/index-service.err.log] (macOS only)
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
         #[arg(long)]
-        stderr: Option<PathBuf>,
-        /// Path to write launchd plist (macOS only) [default: ~/.memex/index-service.plist]
+        root: Option<PathBuf>,
+    },
+    /// Run read-only SQL over indexed records
+    #[command(after_help = "\
+EXAMPLES:
+    memex query \"SELECT project, count(*) AS prompts FROM records WHERE role = 'user' GROUP BY project ORDER BY prompts DESC\"
+    memex query \"SELECT date(ts / 1000, 'unixepoch') AS day, count(*) FROM records GROUP BY day\" --json
+
+TABLES:
+    records   doc_id, ts, ingested_at, source, project, session_id, turn_id, role,
+              text, tool_name, tool_input, tool_output, model, parent_session_id,
+              attachments (count), source_path. Timestamps are unix millis.
+    sessions  one row per session: source, session_id, project, repo_project, cwd,
+              started_at, last_at, message_count, title (after `memex index`).
+
+The query runs against an in-memory SQLite copy of the index, so any SQLite
+function works and nothing can be modified.")]
+    Query {
+        /// SQL statement to run
+        sql: String,
+        /// Emit one JSON object per row
         #[arg(long)]
-        plist: Option<PathBuf>,
-        /// Path to systemd user directory (Linux only) [default: ~/.config/systemd/user]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
         #[arg(long)]
-        systemd_dir: Option<PathBuf>,
+        root: Option<PathBuf>,
     },
-    /// Disable and remove the background indexing service
-    Disable {
-        /// Service label/name [default: com.memex.index (macOS) or memex-index (Linux)]
+    /// Measure search quality against labelled queries
+    Bench {
+        #[command(subcommand)]
+        action: BenchCommand,
+    },
+}
+
+/// Reading sessions, projects and what they touched; flattened into [`Commands`].
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum BrowseCommand {
+    /// Display all messages from a specific session
+    Session {
+        /// Session ID (from search results or TUI)
+        session_id: String,
+        /// Show human-readable output with timestamps and role labels
+        #[arg(short, long)]
+        verbose: bool,
+        /// Path to memex data directory [default: ~/.memex]
         #[arg(long)]
-        label: Option<String>,
-        /// Path to launchd plist (macOS only) [default: ~/.memex/index-service.plist]
+        root: Option<PathBuf>,
+    },
+    /// Read every session of a project as one chronological stream
+    #[command(after_help = "\
+EXAMPLES:
+    memex history --project memex -v
+    memex history --project memex --since 2024-06-01 --until 2024-06-08 -v
+    memex history --project memex --no-tools | jq -r .text
+
+Records from all of the project's sessions are interleaved by time, so a week
+of agent work on a repo reads as one narrative; -v marks where the stream moves
+to another session. Defaults to the last 7 days. In the TUI, press H on a
+session for the same view of its project.")]
+    History {
+        /// Project name or alias
         #[arg(long)]
-        plist: Option<PathBuf>,
-        /// Path to systemd user directory (Linux only) [default: ~/.config/systemd/user]
+        project: String,
+        /// Start of the stream [default: 7 days before --until]
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        since: Option<String>,
+        /// End of the stream [default: now]
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        until: Option<String>,
+        /// Leave out tool calls and results
         #[arg(long)]
-        systemd_dir: Option<PathBuf>,
+        no_tools: bool,
+        /// Maximum number of records, keeping the newest
+        #[arg(long, default_value_t = 5000)]
+        limit: usize,
+        /// Show human-readable output with timestamps and role labels
+        #[arg(short, long)]
+        verbose: bool,
         /// Path to memex data directory [default: ~/.memex]
         #[arg(long)]
         root: Option<PathBuf>,
     },
-}
-
-pub fn run() -> Result<()> {
-    let cli = Cli::parse();
-    // Bare `memex` opens the TUI home screen.
-    let command = cli.command.unwrap_or(Commands::Tui { root: None });
-    let should_check = !matches!(command, Commands::Tui { .. } | Commands::Update { .. });
-    if should_check {
-        check_for_update_async(None);
-    }
-    match command {
-        Commands::Index {
-            index,
-            watch,
-            watch_interval,
-        } => {
-            if watch {
-                run_index_loop(&index, watch_interval)?;
+    /// Show the raw source files behind a session
+    #[command(after_help = "\
+EXAMPLES:
+    memex where 5f1c2a3e-9b7d-4c11-8e2f-0a1b2c3d4e5f
+    memex where \"flaky login test\"     # the best matching session
+    memex where 5f1c2a3e-... --json
+
+For each transcript file that contributed records: its size, the byte range of
+the lines that mention the session (the whole file when none do), how far
+ingest has read it, and the records, turns and time span indexed from it.")]
+    Where {
+        /// Session ID, or a search query whose best matching session is shown
+        target: String,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Show the sessions you were in around a moment, across all projects
+    #[command(after_help = "\
+EXAMPLES:
+    memex at \"3pm yesterday\"
+    memex at \"last friday morning\"
+    memex at \"2 hours ago\" --window 15
+    memex at \"2024-06-01 14:30\" --json
+
+Accepts clock times (3pm, 15:30, noon), days (today, yesterday, monday, last
+friday, 2024-06-01), parts of the day (morning, afternoon, evening, last night),
+\"<n> minutes/hours/days ago\", RFC 3339 and unix timestamps. Sessions with
+records in the window are listed nearest to the moment first.")]
+    At {
+        /// When to look, e.g. \"3pm yesterday\"
+        when: String,
+        /// Minutes either side of the moment [default: 60, or the part of the day or whole day the phrase names]
+        #[arg(long, value_name = "MINUTES")]
+        window: Option<u64>,
+        /// Maximum number of sessions
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Print a session's conversation tree as a Mermaid or Graphviz graph
+    #[command(after_help = "\
+EXAMPLES:
+    memex graph 5f1c2a3e-... --format mermaid
+    memex graph 5f1c2a3e-... --format dot | dot -Tsvg > session.svg
+
+Nodes are conversation events labelled with their role and the start of their
+text. Branches appear where one event has several children (retries, edits);
+dashed edges follow compaction and summary boundaries.")]
+    Graph {
+        /// Session ID (from search results or TUI)
+        session_id: String,
+        /// Output format
+        #[arg(long, value_enum, default_value = "mermaid")]
+        format: GraphFormat,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// List recent sessions with their inferred titles
+    #[command(after_help = "\
+EXAMPLES:
+    memex sessions
+    memex sessions --source claude --project memex
+    memex sessions --since 2026-07-01 --limit 50 --json
+    memex sessions --topic tantivy
+    memex sessions --outcome rework --project memex
+    memex sessions --template '{{ session_id }}  {{ title | default(\"(untitled)\") }}'")]
+    Sessions {
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Only sessions in this topic (id, label or label term from `memex topics`)
+        #[arg(long)]
+        topic: Option<String>,
+        /// Only sessions labeled with this outcome (see `memex outcome`)
+        #[arg(long, value_enum)]
+        outcome: Option<Outcome>,
+        /// Only include sessions active on or after this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        since: Option<String>,
+        /// Maximum number of sessions to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Emit newline-delimited JSON
+        #[arg(long)]
+        json: bool,
+        /// Render each session through a template: the template itself, a
+        /// name from `[templates]` in config.toml, or a template file
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        template: Option<String>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// List the shell commands agents ran, most frequent first
+    #[command(after_help = "\
+EXAMPLES:
+    memex cmds --project memex
+    memex cmds cargo --since 2026-06-01
+    memex cmds --source codex --limit 100 --json
+
+Commands come from bash and exec tool calls of every agent. Search them
+alongside everything else with `cmd:`, e.g. memex search \"cmd:docker timeout\".")]
+    Cmds {
+        /// Only commands containing these words
+        words: Option<String>,
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Only count commands run on or after this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        since: Option<String>,
+        /// Maximum number of commands to list
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        /// Emit newline-delimited JSON
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// List every session that touched or discussed a file
+    #[command(after_help = "\
+EXAMPLES:
+    memex files src/index.rs
+    memex files Cargo.toml --project memex --since 2025-01-01
+    memex files README.md --json
+
+The path matches any file with that tail, so index.rs finds src/index.rs in
+every repo. Files come from paths in messages, file arguments of tool calls
+and patches; sessions that edited the file are marked. Search within them
+with `file:`, e.g. memex search \"file:src/index.rs panic\".")]
+    Files {
+        /// Path, or trailing part of a path, of the file
+        path: String,
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Only sessions that mentioned it on or after this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        since: Option<String>,
+        /// Maximum number of sessions to list
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        /// Emit newline-delimited JSON
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// List the errors agents hit in tool output, most frequent first
+    #[command(after_help = "\
+EXAMPLES:
+    memex errors --project memex --since 2026-09-01
+    memex errors \"borrow of moved value\"
+    memex errors error:panic --source codex
+    memex errors 3fa2b1c0d9e8
+
+Errors come from tool results: Rust compiler errors, panics, Python tracebacks,
+exceptions, `error:`/`fatal:` lines and non-zero exit codes. Each has a
+fingerprint that ignores paths, line numbers and ids, so the same error groups
+across sessions and repos. Give a fingerprint to list the sessions that hit it
+with what the agent said next, usually the fix. Search for it with `error:`,
+e.g. memex search \"error:3fa2b1c0d9e8\".")]
+    Errors {
+        /// Words the error message contains, `error:` filters, or a
+        /// fingerprint
+        query: Option<String>,
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Only errors hit on or after this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        since: Option<String>,
+        /// Maximum number of errors (or sessions, for a fingerprint) to list
+        #[arg(long, default_value_t = 30)]
+        limit: usize,
+        /// Emit newline-delimited JSON
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Pick a session in fzf or skim and print its ID
+    #[command(after_help = "\
+EXAMPLES:
+    memex pick
+    memex pick \"flaky login test\" --project memex
+    memex pick --source codex --print | fzf
+
+Wrap it in a shell function to resume a session by picking it:
+    cr() { local id; id=$(memex pick \"$@\" --source claude) && claude --resume \"$id\"; }
+
+Lines are `session_id<TAB>title<TAB>project`, newest first, or best match first
+when a query is given. Without --print they go to fzf (or sk) with the ID
+hidden, and the chosen session's ID is printed; exits 1 when nothing is picked.")]
+    Pick {
+        /// Only sessions matching this search
+        query: Option<String>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Only include sessions active on or after this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        since: Option<String>,
+        /// Maximum number of sessions offered
+        #[arg(long, default_value_t = 500)]
+        limit: usize,
+        /// Print the lines instead of opening a picker
+        #[arg(long)]
+        print: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Follow new messages from every agent as they are indexed
+    #[command(after_help = "\
+EXAMPLES:
+    memex tail
+    memex tail --source codex --project memex
+    memex tail --lines 0 --interval 5
+
+New messages appear once they are indexed, so keep the watcher running:
+    memex index-service enable --continuous")]
+    Tail {
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Number of recently indexed messages to print before following
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: usize,
+        /// Seconds between index checks
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Cluster sessions into topics by their embeddings
+    #[command(after_help = "\
+EXAMPLES:
+    memex topics
+    memex topics --k 12 --project memex
+    memex topics --limit 0 --json
+    memex sessions --topic tantivy
+
+Each session is placed by the mean embedding of its messages (k-means) and
+each topic is labelled by the terms that set its sessions apart (TF-IDF).
+Needs embeddings. The result is saved, so `memex sessions --topic` and the
+TUI (topic:<name> in the query, the topic list column) can use it until the
+next run.")]
+    Topics {
+        /// Number of topics [default: sqrt(sessions / 2), 2 to 30]
+        #[arg(long)]
+        k: Option<usize>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Sessions listed per topic (0 for all)
+        #[arg(long, default_value_t = 5)]
+        limit: usize,
+        /// Emit one JSON object per topic
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Outline what the agents decided and learned about a project
+    #[command(after_help = "\
+EXAMPLES:
+    memex knowledge --project memex
+    memex knowledge --project memex --min-sessions 2 --output docs/agent-knowledge.md
+    memex knowledge --project memex --rebuild
+
+Reads the project's assistant messages for sentences stating a decision
+(\"went with\", \"decided to\", \"instead of\") or a fact (\"lives in\",
+\"defaults to\", \"requires\"), merges the same statement made in several
+sessions, and prints a Markdown outline with a section per file mentioned.
+Statements are merged by embedding similarity when embeddings are on, by
+shared words otherwise. They are saved, so the next run only reads records
+indexed since; --rebuild reads everything again.")]
+    Knowledge {
+        /// Project to outline
+        #[arg(long)]
+        project: String,
+        /// Write the outline to this file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Only statements made in at least this many sessions
+        #[arg(long, default_value_t = 1)]
+        min_sessions: usize,
+        /// Statements listed per section, those made most often (0 for all)
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Forget saved statements and read every record again
+        #[arg(long)]
+        rebuild: bool,
+        /// Merge statements by shared words even when embeddings are on
+        #[arg(long)]
+        no_embeddings: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+/// Keeping the data directory in shape; flattened into [`Commands`].
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum MaintainCommand {
+    /// List indexed projects, most frequently and recently used first
+    #[command(after_help = "\
+EXAMPLES:
+    memex projects
+    memex projects --source codex
+
+Output is one project per line, suitable for shell completion of --project.")]
+    Projects {
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Compact the vector store and merge index and vector segments
+    #[command(after_help = "\
+EXAMPLES:
+    memex vacuum
+    memex vacuum --root /tmp/memex
+
+Drops vectors whose documents were deleted, merges vector store and search
+index segments, then reports how much disk space was reclaimed. Set
+`auto_vacuum_threshold` in config.toml to vacuum automatically after
+`memex index`.")]
+    Vacuum {
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Move old months of the index into shards of their own
+    #[command(after_help = "\
+EXAMPLES:
+    memex shards split --older-than 3
+    memex shards list
+    memex shards list --json
+
+Each month moved out gets a shard in ~/.memex/index-shards/YYYY-MM. Searches
+and every other read fan out over the index and its shards and merge the
+results, scored with the term statistics of the whole index, while ingest
+only commits to and merges the small live index. Vectors are not sharded. Set
+`shard_after_months` in config.toml to split after every `memex index`.
+Shards are plain directories: move one out of index-shards to archive that
+month, and back to search it again.")]
+    Shards {
+        #[command(subcommand)]
+        action: ShardsCommand,
+    },
+    /// Delete sessions older than the retention policy allows
+    #[command(after_help = "\
+EXAMPLES:
+    memex prune --dry-run
+    memex prune
+    memex prune --last
+
+Set `retain_days` in config.toml (and `retain_projects` for per-project
+overrides, where 0 keeps a project forever) to enable retention. A session is
+pruned once its newest message is older than that, together with its vectors
+and analytics. Sessions listed in `retain_pinned` and sessions with a note are
+kept. The index service prunes once a day; agent logs are never touched, so a
+full `memex reindex` brings pruned sessions back until the next prune.")]
+    Prune {
+        /// Show what would be pruned without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Show what the index service pruned last
+        #[arg(long, conflicts_with = "dry_run")]
+        last: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Check the index and vector store, or rebuild a damaged index
+    #[command(after_help = "\
+EXAMPLES:
+    memex repair                        # Check index and vector checksums
+    memex repair --rebuild-index        # Rebuild the index after confirming
+    memex repair --rebuild-index --yes
+
+Without flags, reports damaged or missing index files and whether the vector
+store still matches its checksums. --rebuild-index reads back every record it
+still can into a fresh index and moves the damaged one to index.damaged. When
+records were lost, sources are ingested again (without embeddings) and
+vectors whose checksums validate are carried over to the new records; run
+`memex embed` to fill any gaps.")]
+    Repair {
+        /// Rebuild the full-text index from its readable records and sources
+        #[arg(long)]
+        rebuild_index: bool,
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long, requires = "rebuild_index")]
+        yes: bool,
+        #[command(flatten)]
+        index: IndexArgs,
+    },
+    /// Snapshot the whole data directory, or restore a snapshot
+    #[command(after_help = "\
+EXAMPLES:
+    memex backup create
+    memex backup create --dir /Volumes/external/memex
+    memex backup list
+    memex backup restore latest
+    memex backup restore memex-20261016-101500 --yes
+
+A backup is one zstd-compressed tar archive of the index, vectors, state and
+config.toml, written to backup_dir from config.toml (default
+~/.memex/backups). Its manifest holds a SHA-256 per file; restore unpacks and
+checks every file before swapping the data directory in, so a damaged archive
+leaves the current data as it was. Sessions newer than the backup are picked
+up again by the next `memex index`.")]
+    Backup {
+        #[command(subcommand)]
+        action: BackupCommand,
+    },
+    /// Move the data directory to the XDG layout or another directory
+    #[command(after_help = "\
+EXAMPLES:
+    memex migrate-home --dry-run
+    memex migrate-home
+    memex migrate-home --to /srv/memex   # then set MEMEX_HOME=/srv/memex
+
+Without --to, the store moves to $XDG_DATA_HOME/memex (default
+~/.local/share/memex), with state in $XDG_STATE_HOME/memex and config.toml in
+$XDG_CONFIG_HOME/memex. memex finds it there once ~/.memex is gone. Nothing is
+moved over an existing directory. Re-run `memex index-service enable` afterwards
+if you use the background service, so its logs move too.")]
+    MigrateHome {
+        /// Store to move [default: MEMEX_HOME or ~/.memex]
+        #[arg(long)]
+        from: Option<PathBuf>,
+        /// Directory to move everything into [default: the XDG layout]
+        #[arg(long)]
+        to: Option<PathBuf>,
+        /// List the moves without making them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Adding to, labelling and removing sessions; flattened into [`Commands`].
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum AnnotateCommand {
+    /// Write a message from your own agent or script into the index
+    #[command(after_help = "\
+EXAMPLES:
+    echo \"deploy the staging cluster\" | memex append --session deploy-42 --project infra -
+    memex append --session deploy-42 --role assistant \"rolled out v1.8 to staging\"
+    my-agent --last-tool-output | memex append --session deploy-42 --role tool_result --tool kubectl -
+
+Text comes from the argument, or from stdin when it is `-` or left out. The
+message is searchable as soon as the command returns, from the external
+source. Each session's messages are also kept in a log under
+~/.memex/imports/append, so they survive `memex reindex`. The same is
+available to Rust code as `memex::append::append_message`.")]
+    Append {
+        /// Session the message belongs to; new ids start a new session
+        #[arg(long)]
+        session: String,
+        /// Role: user, assistant, tool_use, tool_result, summary, ...
+        #[arg(long, default_value = "user")]
+        role: String,
+        /// Project [default: name of the current directory]
+        #[arg(long)]
+        project: Option<String>,
+        /// Tool name, for tool_use and tool_result messages
+        #[arg(long)]
+        tool: Option<String>,
+        /// Message text; `-` or omitted reads stdin
+        text: Option<String>,
+        /// Embed the message for semantic search
+        #[arg(long)]
+        embeddings: bool,
+        /// Skip embedding generation (overrides config default)
+        #[arg(long)]
+        no_embeddings: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Attach a note to a session, or show its note
+    #[command(after_help = "\
+EXAMPLES:
+    memex note 3f2c9d1e-7a4b-4c1d-9e8f-0a1b2c3d4e5f \"where we fixed the flaky login test\"
+    memex note 3f2c9d1e-7a4b-4c1d-9e8f-0a1b2c3d4e5f
+    memex note 3f2c9d1e-7a4b-4c1d-9e8f-0a1b2c3d4e5f --clear
+
+A session has one note; setting it again replaces it. Notes are searchable
+(`memex search flaky --role note`) and survive `memex reindex`.")]
+    Note {
+        /// Session ID to annotate
+        session_id: String,
+        /// Note text; omit to print the current note
+        text: Option<String>,
+        /// Remove the session's note
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Label how a session turned out, or show its label
+    #[command(after_help = "\
+EXAMPLES:
+    memex outcome 3f2c9d1e-7a4b-4c1d-9e8f-0a1b2c3d4e5f success
+    memex outcome 3f2c9d1e-7a4b-4c1d-9e8f-0a1b2c3d4e5f
+    memex outcome 3f2c9d1e-7a4b-4c1d-9e8f-0a1b2c3d4e5f --clear
+
+Outcomes are success, abandoned or rework; in the TUI `u` cycles through them.
+List labeled sessions with `memex sessions --outcome rework` and success rates
+with `memex outcomes`. Labels survive `memex reindex`.")]
+    Outcome {
+        /// Session ID to label
+        session_id: String,
+        /// Outcome to set; omit to print the current one
+        #[arg(value_enum)]
+        outcome: Option<Outcome>,
+        /// Remove the session's label
+        #[arg(long, conflicts_with = "outcome")]
+        clear: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Success rates of labeled sessions per project
+    #[command(after_help = "\
+EXAMPLES:
+    memex outcomes
+    memex outcomes --project memex --by week
+    memex outcomes --since 2026-07-01 --by month --json
+
+Counts sessions labeled with `memex outcome` (or `u` in the TUI) per project;
+the success rate is successes over all labeled sessions. --by splits each
+project by the week or month of the session's last message.")]
+    Outcomes {
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Only sessions active on or after this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        since: Option<String>,
+        /// Also break each project down by week or month
+        #[arg(long, value_enum)]
+        by: Option<OutcomePeriod>,
+        /// Emit JSON
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Remove a session from the index (kept in the trash; see `memex restore`)
+    #[command(after_help = "\
+EXAMPLES:
+    memex forget 3f2c9d1e-7a4b-4c1d-9e8f-0a1b2c3d4e5f
+
+Agent logs are left alone, so messages the agent appends to the session later
+are indexed again. The removed records can be restored until
+`trash_retention_days` (default 30) passes or `memex trash empty` runs.")]
+    Forget {
+        /// Session ID to remove
+        session_id: String,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Put a forgotten session back into the index
+    Restore {
+        /// Session ID to restore (the most recently trashed copy)
+        session_id: String,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// List or empty trashed sessions
+    Trash {
+        #[command(subcommand)]
+        action: TrashCommand,
+    },
+    /// Give projects friendlier names
+    #[command(after_help = "\
+EXAMPLES:
+    memex project rename -Users-nico-dev-memex memex
+    memex project rename ~/dev/memex memex
+    memex project list")]
+    Project {
+        #[command(subcommand)]
+        action: ProjectCommand,
+    },
+}
+
+/// Single records and the state of the index; flattened into [`Commands`].
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum InspectCommand {
+    /// Display a single document by its internal ID
+    #[command(after_help = "\
+With --pager, renders the whole session the way the TUI preview does, in
+color, through $PAGER (less -R when unset).
+
+EXAMPLES:
+    memex show 4821 --verbose
+    memex show 3f2a9c1e-7b4d-4e2a-9f61-0c8d5e7a1b23 --pager
+    memex show 4821 --pager --tools
+    memex show 4821 --template '[{{ role }}] {{ text }}'")]
+    Show {
+        /// Document ID (from search results); with --pager, a session ID or
+        /// the ID of any document in the session
+        id: String,
+        /// Pretty-print JSON output
+        #[arg(short, long, conflicts_with = "pager")]
+        verbose: bool,
+        /// Page the formatted session transcript
+        #[arg(long)]
+        pager: bool,
+        /// Include tool calls and results in the transcript
+        #[arg(long, requires = "pager")]
+        tools: bool,
+        /// Render the document through a template: the template itself, a
+        /// name from `[templates]` in config.toml, or a template file
+        #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["verbose", "pager"])]
+        template: Option<String>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Show whether the background index service's last run succeeded
+    Status {
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// List source file lines that failed to parse and were skipped
+    #[command(after_help = "\
+EXAMPLES:
+    memex ingest-errors
+    memex ingest-errors --source codex --json
+    memex ingest-errors --clear
+
+A malformed line never stops the rest of its file from being indexed. Lines
+stay listed until the file is read from the start again, e.g. with
+`memex reindex --path <file>` after fixing it; --clear forgets them all.")]
+    IngestErrors {
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Emit one JSON object per file
+        #[arg(long, conflicts_with = "clear")]
+        json: bool,
+        /// Forget every recorded error
+        #[arg(long)]
+        clear: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Show which files the next index reads or skips, and why
+    #[command(after_help = "\
+EXAMPLES:
+    memex scan                            # Files the next `memex index` would read
+    memex scan --no-codex --include-agents
+    memex scan --explain ~/.claude/projects/-Users-me-app/0b7c.jsonl
+
+A file is skipped when its size and mtime match its entry in the ingest
+state (ingest.json), read on from the recorded offset when it grew, and read
+again from the start when it shrank. Searches that index first skip scanning
+altogether until scan_cache_ttl has passed since the last scan.")]
+    Scan {
+        #[command(flatten)]
+        index: IndexArgs,
+        /// Explain why this file was or wasn't ingested
+        #[arg(long, value_name = "PATH")]
+        explain: Option<PathBuf>,
+    },
+    /// Show index statistics (document count, vector count, storage paths)
+    #[command(after_help = "\
+With --by, counts records and session starts per hour of day, weekday, day or
+month in local time instead, charted by records.
+
+EXAMPLES:
+    memex stats
+    memex stats --by hour
+    memex stats --by weekday --source claude --since 2026-01-01
+    memex stats --by month --json
+    memex stats export --anonymize --since 2026-01-01 --markdown")]
+    #[command(args_conflicts_with_subcommands = true)]
+    Stats {
+        #[command(subcommand)]
+        action: Option<StatsCommand>,
+        /// Chart activity by hour, weekday, day or month
+        #[arg(long, value_enum)]
+        by: Option<Bucket>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long, requires = "by")]
+        source: Option<SourceFilter>,
+        /// Only count activity on or after this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP", requires = "by")]
+        since: Option<String>,
+        /// Only count activity on or before this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP", requires = "by")]
+        until: Option<String>,
+        /// Output the buckets as JSON
+        #[arg(long, requires = "by")]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+/// Sending sessions and records elsewhere; flattened into [`Commands`].
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum SharingCommand {
+    /// Share a session via agentexport, or write it to a redacted file
+    #[command(after_help = "\
+EXAMPLES:
+    memex share abc123              # Share session abc123
+    memex share abc123 --title \"Bug fix session\"  # Share with custom title
+    memex share abc123 -o bug.html --collapse-tools  # Self-contained HTML file
+    memex share abc123 --format markdown > bug.md
+
+Files written with --output or --format have API keys, tokens, passwords,
+private keys and your home directory redacted. Review them before sharing.
+
+REQUIREMENTS:
+    Publishing requires agentexport: brew install nicosuave/tap/agentexport")]
+    Share {
+        /// Session ID (from search results or TUI)
+        session_id: String,
+        /// Title for the share (optional)
+        #[arg(long)]
+        title: Option<String>,
+        /// Write a redacted transcript to this file instead of publishing
+        /// (format from the extension: .html, or Markdown otherwise)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Transcript format; without --output it is printed to stdout
+        #[arg(long, value_enum)]
+        format: Option<ShareFormat>,
+        /// Fold tool calls and results into expandable blocks
+        #[arg(long)]
+        collapse_tools: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Transfer an indexed session into another agent backend
+    #[command(after_help = "\
+EXAMPLES:
+    memex transfer abc123
+    memex transfer abc123 --to pi
+    memex transfer abc123 --to opencode
+    memex transfer abc123 --mode strict --turns 80
+    memex transfer abc123 --source pi --to codex
+    memex transfer abc123 --dry-run")]
+    Transfer {
+        /// Session ID (from search results or TUI)
+        session_id: String,
+        /// Filter by source when a session id appears in multiple backends
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Target backend to import into
+        #[arg(long, value_enum, default_value = "codex")]
+        to: TransferTarget,
+        /// Compact imports text turns; strict includes tool activity as text notes
+        #[arg(long, value_enum, default_value = "compact")]
+        mode: TransferMode,
+        /// Limit imported turns (Pi defaults to 60 and caps at 400)
+        #[arg(long)]
+        turns: Option<usize>,
+        /// Generate the intermediate transcript without importing into the target
+        #[arg(long)]
+        dry_run: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Upload newly indexed records to the team server
+    #[command(after_help = "\
+EXAMPLES:
+    memex push
+    memex push --url http://memex.internal:7377 --dry-run
+    memex search \"flaky login test\" --remote
+
+Records have API keys, tokens, passwords, private keys and your home directory
+redacted before they leave the machine; notes and attachments are never sent.
+Only records indexed since the last push go up; set `team_projects` in
+config.toml to share just some projects.")]
+    Push {
+        /// Team server [default: `team_url` in config.toml or MEMEX_TEAM_URL]
+        #[arg(long)]
+        url: Option<String>,
+        /// Send every record again instead of only new ones (the server
+        /// drops the ones it has)
+        #[arg(long)]
+        all: bool,
+        /// Count what would be sent without sending it
+        #[arg(long)]
+        dry_run: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Run a team server that receives `memex push` and answers `search --remote`
+    #[command(after_help = "\
+EXAMPLES:
+    MEMEX_TEAM_TOKEN=shared-secret memex serve --listen 0.0.0.0:7377 --root /srv/memex-team
+
+Pushed records are appended to <root>/team/records.jsonl and indexed from it;
+the server never changes or deletes them. Set MEMEX_TEAM_TOKEN (or `team_token`
+in config.toml) on the server and every client to require a shared token; without
+one the server only listens on loopback addresses. Plain HTTP: put it behind a TLS proxy or keep it on a trusted network.")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7377")]
+        listen: String,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Send records to Elasticsearch or OpenSearch with the bulk API
+    #[command(after_help = "\
+EXAMPLES:
+    memex export --to elasticsearch --url https://es.internal:9200
+    memex export --to opensearch --url https://search.internal:9200 --index agents
+    memex export --to elasticsearch --project memex --output memex.ndjson
+
+Records are redacted like `memex push` and keep their doc id as `_id`, so
+exporting one again overwrites it. Only records indexed since the last export
+to that URL and index are sent; set `elasticsearch_sync = true` in config.toml
+to export after every `memex index`. Deleted or forgotten sessions are not
+removed from the cluster.")]
+    Export {
+        /// Kind of cluster
+        #[arg(long)]
+        to: ExportTarget,
+        /// Cluster URL [default: `elasticsearch_url` in config.toml or
+        /// MEMEX_ELASTICSEARCH_URL]
+        #[arg(long)]
+        url: Option<String>,
+        /// Index to write to [default: `elasticsearch_index` or memex]
+        #[arg(long)]
+        index: Option<String>,
+        /// Only this project
+        #[arg(long)]
+        project: Option<String>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Send every record again instead of only new ones
+        #[arg(long)]
+        all: bool,
+        /// Write the bulk request body to this file ("-" for stdout) instead
+        /// of sending it
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Count what would be sent without sending it
+        #[arg(long)]
+        dry_run: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+/// Clusters `memex export` writes to. Both speak the same bulk API.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "kebab-case")]
+enum ExportTarget {
+    Elasticsearch,
+    Opensearch,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "kebab-case")]
+enum TransferTarget {
+    Codex,
+    Claude,
+    Copilot,
+    Cursor,
+    Opencode,
+    Pi,
+}
+
+impl From<TransferTarget> for CoreTransferTarget {
+    fn from(value: TransferTarget) -> Self {
+        match value {
+            TransferTarget::Codex => CoreTransferTarget::Codex,
+            TransferTarget::Claude => CoreTransferTarget::Claude,
+            TransferTarget::Copilot => CoreTransferTarget::Copilot,
+            TransferTarget::Cursor => CoreTransferTarget::Cursor,
+            TransferTarget::Opencode => CoreTransferTarget::Opencode,
+            TransferTarget::Pi => CoreTransferTarget::Pi,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "kebab-case")]
+enum TransferMode {
+    Compact,
+    Strict,
+}
+
+impl From<TransferMode> for CoreTransferMode {
+    fn from(value: TransferMode) -> Self {
+        match value {
+            TransferMode::Compact => CoreTransferMode::Compact,
+            TransferMode::Strict => CoreTransferMode::Strict,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ModelsCommand {
+    /// Show every embedding model and whether it is downloaded
+    List {
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Download an embedding model now
+    Pull {
+        /// minilm, bge, nomic, gemma or potion [default: the configured model]
+        model: Option<String>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BenchCommand {
+    /// Report recall@k and MRR for lexical, semantic and hybrid search
+    #[command(after_help = "\
+EXAMPLES:
+    memex bench search --queries queries.toml
+    memex bench search --queries queries.toml --k 5 --mode lexical --mode hybrid --json
+
+QUERIES FILE:
+    [[queries]]
+    query = \"flaky login test\"
+    sessions = [\"3f2c9d1e-7a4b-4c1d-9e8f-0a1b2c3d4e5f\"]
+
+    [[queries]]
+    query = \"rotate the tantivy index\"
+    docs = [48213]
+    project = \"memex\"
+
+A `.json` file with the same fields under a top-level `queries` array works too.
+
+With `sessions`, results are judged per session (duplicates of a session that
+already ranked are skipped); with `docs`, per record. Recall@k is the share of
+the listed results in the top k; MRR averages 1/rank of the first one found.
+Semantic and hybrid modes need embeddings (`memex embed`) and are skipped
+without them. The scoring profile (`--profile`) sets weights and fuzziness.")]
+    Search {
+        /// TOML (or .json) file of labelled queries
+        #[arg(long)]
+        queries: PathBuf,
+        /// Cutoff for recall and MRR
+        #[arg(long, default_value_t = 10)]
+        k: usize,
+        /// Modes to run [default: all]
+        #[arg(long, value_enum)]
+        mode: Vec<BenchMode>,
+        /// Scoring profile [default: scoring_profile from config.toml]
+        #[arg(long)]
+        profile: Option<String>,
+        /// Emit one JSON object per mode
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BenchMode {
+    Lexical,
+    Semantic,
+    Hybrid,
+}
+
+impl BenchMode {
+    fn label(self) -> &'static str {
+        match self {
+            BenchMode::Lexical => "lexical",
+            BenchMode::Semantic => "semantic",
+            BenchMode::Hybrid => "hybrid",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ShardsCommand {
+    /// Show each month shard and its record count
+    List {
+        /// Emit JSON
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Move records older than some months into month shards
+    Split {
+        /// Months kept in the live index, counting the current one as 0
+        /// [default: shard_after_months or 3]
+        #[arg(long)]
+        older_than: Option<u32>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupCommand {
+    /// Write a backup archive
+    Create {
+        /// Directory to write to [default: backup_dir or ~/.memex/backups]
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Show backups, newest first
+    List {
+        /// Directory to look in [default: backup_dir or ~/.memex/backups]
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Emit JSON
+        #[arg(long)]
+        json: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Replace the data directory with a backup
+    Restore {
+        /// Backup name from `memex backup list`, a path to an archive, or
+        /// `latest`
+        backup: String,
+        /// Directory to look in [default: backup_dir or ~/.memex/backups]
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    /// Show trashed sessions, newest first
+    List {
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Permanently delete everything in the trash
+    Empty {
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum EmbeddingsCommand {
+    /// Dump every embedded record with its vector for analysis elsewhere
+    Export {
+        /// File to write
+        #[arg(short, long)]
+        output: PathBuf,
+        /// File format [default: from the extension, Parquet unless .npy]
+        #[arg(long, value_enum)]
+        format: Option<EmbeddingsFormat>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Filter by project name
+        #[arg(long)]
+        project: Option<String>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EmbeddingsFormat {
+    Parquet,
+    Npy,
+}
+
+#[derive(Subcommand)]
+enum StatsCommand {
+    /// Write a usage report without conversation text, to share
+    #[command(after_help = "\
+EXAMPLES:
+    memex stats export > usage.json
+    memex stats export --anonymize --since 2026-01-01 --markdown
+    memex stats export --anonymize --salt team-2026 -o usage.json
+
+The report has session and message counts by source and project, token totals
+and the model mix (with `token_usage = true`). Titles, paths, session IDs and
+message text are never included. --anonymize rounds every count into a range
+such as 50-100 and replaces project names with salted hashes; pass the same
+--salt to keep the names stable between reports.")]
+    Export {
+        /// Round counts into ranges and hash project names
+        #[arg(long)]
+        anonymize: bool,
+        /// Salt for project name hashes [default: random per report]
+        #[arg(long, requires = "anonymize")]
+        salt: Option<String>,
+        /// Filter by source: claude, codex, cursor, opencode, pi, or copilot
+        #[arg(long)]
+        source: Option<SourceFilter>,
+        /// Only sessions active on or after this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        since: Option<String>,
+        /// Only sessions started on or before this date/timestamp
+        #[arg(long, value_name = "DATE_OR_TIMESTAMP")]
+        until: Option<String>,
+        /// Write Markdown tables instead of JSON
+        #[arg(long)]
+        markdown: bool,
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectCommand {
+    /// Show a project under a new name in search output, filters and the TUI.
+    /// Renaming it back to its original name removes the alias.
+    Rename {
+        /// Current name: as stored, as shown, or an earlier alias
+        old: String,
+        new: String,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+    /// Show renamed projects
+    List {
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IndexServiceCommand {
+    /// Enable automatic background indexing (launchd on macOS, systemd on Linux)
+    Enable {
+        #[command(flatten)]
+        index: IndexArgs,
+        /// Service label/name [default: com.memex.index (macOS) or memex-index (Linux)]
+        #[arg(long)]
+        label: Option<String>,
+        /// Run as a long-lived process instead of periodic execution
+        #[arg(long)]
+        continuous: bool,
+        /// Seconds between index checks in continuous mode [default: 30]
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..), value_name = "SECONDS")]
+        poll_interval: Option<u64>,
+        /// Seconds between invocations in interval mode [default: 3600]
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..), value_name = "SECONDS")]
+        interval: Option<u64>,
+        /// Path for stdout log file [default: ~/.memex/index-service.log] (macOS only)
+        #[arg(long)]
+        stdout: Option<PathBuf>,
+        /// Path for stderr log file [default: ~/.memex/index-service.err.log] (macOS only)
+        #[arg(long)]
+        stderr: Option<PathBuf>,
+        /// Path to write launchd plist (macOS only) [default: ~/.memex/index-service.plist]
+        #[arg(long)]
+        plist: Option<PathBuf>,
+        /// Path to systemd user directory (Linux only) [default: ~/.config/systemd/user]
+        #[arg(long)]
+        systemd_dir: Option<PathBuf>,
+    },
+    /// Disable and remove the background indexing service
+    Disable {
+        /// Service label/name [default: com.memex.index (macOS) or memex-index (Linux)]
+        #[arg(long)]
+        label: Option<String>,
+        /// Path to launchd plist (macOS only) [default: ~/.memex/index-service.plist]
+        #[arg(long)]
+        plist: Option<PathBuf>,
+        /// Path to systemd user directory (Linux only) [default: ~/.config/systemd/user]
+        #[arg(long)]
+        systemd_dir: Option<PathBuf>,
+        /// Path to memex data directory [default: ~/.memex]
+        #[arg(long)]
+        root: Option<PathBuf>,
+    },
+}
+
+pub fn run() -> Result<()> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if cli.offline {
+        // Read by the config and embedding runtime wherever they are loaded;
+        // set before any thread starts.
+        unsafe { std::env::set_var(MEMEX_OFFLINE_ENV, "1") };
+    }
+    // Bare `memex` opens the TUI home screen.
+    let command = cli.command.unwrap_or(Commands::Tui {
+        limit: None,
+        context: None,
+        tail: None,
+        message_chars: None,
+        no_semantic: false,
+        root: None,
+    });
+    // The config that applies is the one under the command's own --root.
+    let offline = Paths::new(command_root(&matches)).is_ok_and(|paths| network_disabled(&paths));
+    let should_check = !matches!(command, Commands::Tui { .. } | Commands::Update { .. });
+    if should_check && !offline {
+        check_for_update_async(None);
+    }
+    match command {
+        Commands::Index {
+            index,
+            watch,
+            watch_interval,
+            service,
+        } => {
+            if watch {
+                run_index_loop(&index, watch_interval, service)?;
+            } else {
+                run_index_once(&index, service)?;
+            }
+        }
+        Commands::Ingest(command) => run_ingest_command(command)?,
+        Commands::Search {
+            query,
+            queries,
+            project,
+            role,
+            tool,
+            session,
+            source,
+            semantic,
+            hybrid,
+            no_semantic,
+            min_score,
+            profile,
+            recency_weight,
+            recency_half_life_days,
+            since,
+            until,
+            as_of,
+            limit,
+            top_n_per_session,
+            unique_session,
+            json_array,
+            fields,
+            template,
+            sort,
+            verbose,
+            explain,
+            remote,
+            root,
+        } => {
+            run_search(
+                query.into_iter().chain(queries).collect(),
+                project,
+                role,
+                tool,
+                session,
+                source,
+                semantic,
+                hybrid,
+                no_semantic,
+                min_score,
+                profile,
+                recency_weight,
+                recency_half_life_days,
+                since,
+                until,
+                as_of,
+                limit,
+                top_n_per_session,
+                unique_session,
+                json_array,
+                fields,
+                template,
+                sort,
+                verbose,
+                explain,
+                remote,
+                root,
+            )?;
+        }
+        Commands::Lookup(command) => run_lookup_command(command)?,
+        Commands::Tui {
+            limit,
+            context,
+            tail,
+            message_chars,
+            no_semantic,
+            root,
+        } => {
+            let overrides = tui::TuiOverrides {
+                result_limit: limit,
+                match_context: context,
+                tail_messages: tail,
+                message_chars,
+                lexical_only: no_semantic,
+            };
+            if offline {
+                tui::run(root, None, overrides)?;
+            } else {
+                let (update_tx, update_rx) = std::sync::mpsc::channel();
+                check_for_update_async(Some(update_tx));
+                tui::run(root, Some(update_rx), overrides)?;
+            }
+        }
+        Commands::IndexService { action } => match action {
+            IndexServiceCommand::Enable {
+                index,
+                label,
+                continuous,
+                poll_interval,
+                interval,
+                stdout,
+                stderr,
+                plist,
+                systemd_dir,
+            } => {
+                run_index_service_enable(
+                    &index,
+                    label,
+                    continuous,
+                    poll_interval,
+                    interval,
+                    stdout,
+                    stderr,
+                    plist,
+                    systemd_dir,
+                )?;
+            }
+            IndexServiceCommand::Disable {
+                label,
+                plist,
+                systemd_dir,
+                root,
+            } => {
+                run_index_service_disable(label, plist, systemd_dir, root)?;
+            }
+        },
+        Commands::Browse(command) => run_browse_command(command)?,
+        Commands::Maintain(command) => run_maintain_command(command)?,
+        Commands::Annotate(command) => run_annotate_command(command)?,
+        Commands::Inspect(command) => run_inspect_command(command)?,
+        Commands::Usage {
+            source,
+            since,
+            until,
+            json,
+            events,
+            cost,
+            root,
+        } => {
+            run_usage(source, since, until, json, events, cost, root)?;
+        }
+        Commands::AnalyticsBackfill { root } => {
+            run_analytics_backfill(root)?;
+        }
+        Commands::Setup { force } => {
+            run_setup(force)?;
+        }
+        Commands::Update { yes, root } => {
+            run_update(yes, &Paths::new(root)?)?;
+        }
+        Commands::Sharing(command) => run_sharing_command(command)?,
+    }
+    Ok(())
+}
+
+fn run_ingest_command(command: IngestCommand) -> Result<()> {
+    match command {
+        IngestCommand::Reindex { index, path } => {
+            run_reindex(&index, path.as_deref())?;
+        }
+        IngestCommand::ImportArchive {
+            dir,
+            heap_mb,
+            include_agents,
+            embeddings,
+            no_embeddings,
+            model,
+            root,
+        } => {
+            run_import_archive(
+                dir,
+                heap_mb,
+                include_agents,
+                embeddings,
+                no_embeddings,
+                model,
+                root,
+            )?;
+        }
+        IngestCommand::ImportChatgpt {
+            export,
+            embeddings,
+            no_embeddings,
+            model,
+            root,
+        } => {
+            run_import_chatgpt(&export, embeddings, no_embeddings, model, root)?;
+        }
+        IngestCommand::Embed { model, root } => {
+            run_embed(model, root)?;
+        }
+        IngestCommand::Models { action } => match action {
+            ModelsCommand::List { root } => run_models_list(root)?,
+            ModelsCommand::Pull { model, root } => run_models_pull(model, root)?,
+        },
+        IngestCommand::Embeddings { action } => match action {
+            EmbeddingsCommand::Export {
+                output,
+                format,
+                source,
+                project,
+                root,
+            } => run_embeddings_export(output, format, source, project, root)?,
+        },
+    }
+    Ok(())
+}
+
+fn run_lookup_command(command: LookupCommand) -> Result<()> {
+    match command {
+        LookupCommand::Asked {
+            question,
+            threshold,
+            limit,
+            project,
+            source,
+            json,
+            root,
+        } => {
+            if !run_asked(question, threshold, limit, project, source, json, root)? {
+                std::process::exit(1);
+            }
+        }
+        LookupCommand::Query { sql, json, root } => {
+            run_query(&sql, json, root)?;
+        }
+        LookupCommand::Bench { action } => match action {
+            BenchCommand::Search {
+                queries,
+                k,
+                mode,
+                profile,
+                json,
+                root,
+            } => run_bench_search(&queries, k, mode, profile, json, root)?,
+        },
+    }
+    Ok(())
+}
+
+fn run_browse_command(command: BrowseCommand) -> Result<()> {
+    match command {
+        BrowseCommand::Session {
+            session_id,
+            verbose,
+            root,
+        } => {
+            run_session(session_id, verbose, root)?;
+        }
+        BrowseCommand::History {
+            project,
+            since,
+            until,
+            no_tools,
+            limit,
+            verbose,
+            root,
+        } => {
+            run_history(project, since, until, no_tools, limit, verbose, root)?;
+        }
+        BrowseCommand::Where { target, json, root } => {
+            run_where(&target, json, root)?;
+        }
+        BrowseCommand::At {
+            when,
+            window,
+            limit,
+            json,
+            root,
+        } => {
+            run_at(&when, window, limit, json, root)?;
+        }
+        BrowseCommand::Graph {
+            session_id,
+            format,
+            root,
+        } => {
+            run_graph(session_id, format, root)?;
+        }
+        BrowseCommand::Sessions {
+            source,
+            project,
+            topic,
+            outcome,
+            since,
+            limit,
+            json,
+            template,
+            root,
+        } => {
+            run_sessions(
+                source, project, topic, outcome, since, limit, json, template, root,
+            )?;
+        }
+        BrowseCommand::Cmds {
+            words,
+            project,
+            source,
+            since,
+            limit,
+            json,
+            root,
+        } => {
+            run_cmds(words, project, source, since, limit, json, root)?;
+        }
+        BrowseCommand::Files {
+            path,
+            project,
+            source,
+            since,
+            limit,
+            json,
+            root,
+        } => {
+            run_files(path, project, source, since, limit, json, root)?;
+        }
+        BrowseCommand::Errors {
+            query,
+            project,
+            source,
+            since,
+            limit,
+            json,
+            root,
+        } => {
+            run_errors(query, project, source, since, limit, json, root)?;
+        }
+        BrowseCommand::Pick {
+            query,
+            source,
+            project,
+            since,
+            limit,
+            print,
+            root,
+        } => {
+            if !run_pick(query, source, project, since, limit, print, root)? {
+                std::process::exit(1);
+            }
+        }
+        BrowseCommand::Tail {
+            source,
+            project,
+            lines,
+            interval,
+            root,
+        } => {
+            run_tail(source, project, lines, interval, root)?;
+        }
+        BrowseCommand::Topics {
+            k,
+            source,
+            project,
+            limit,
+            json,
+            root,
+        } => {
+            run_topics(k, source, project, limit, json, root)?;
+        }
+        BrowseCommand::Knowledge {
+            project,
+            output,
+            min_sessions,
+            limit,
+            rebuild,
+            no_embeddings,
+            root,
+        } => {
+            run_knowledge(
+                project,
+                output,
+                min_sessions,
+                limit,
+                rebuild,
+                no_embeddings,
+                root,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn run_maintain_command(command: MaintainCommand) -> Result<()> {
+    match command {
+        MaintainCommand::Projects { source, root } => {
+            run_projects(source, root)?;
+        }
+        MaintainCommand::Vacuum { root } => {
+            run_vacuum(root)?;
+        }
+        MaintainCommand::Shards { action } => match action {
+            ShardsCommand::List { json, root } => run_shards_list(json, root)?,
+            ShardsCommand::Split { older_than, root } => run_shards_split(older_than, root)?,
+        },
+        MaintainCommand::Prune {
+            dry_run,
+            last,
+            root,
+        } => {
+            run_prune(dry_run, last, root)?;
+        }
+        MaintainCommand::Repair {
+            rebuild_index,
+            yes,
+            index,
+        } => {
+            run_repair(rebuild_index, yes, &index)?;
+        }
+        MaintainCommand::MigrateHome { from, to, dry_run } => run_migrate_home(from, to, dry_run)?,
+        MaintainCommand::Backup { action } => match action {
+            BackupCommand::Create { dir, root } => run_backup_create(dir, root)?,
+            BackupCommand::List { dir, json, root } => run_backup_list(dir, json, root)?,
+            BackupCommand::Restore {
+                backup,
+                dir,
+                yes,
+                root,
+            } => run_backup_restore(&backup, dir, yes, root)?,
+        },
+    }
+    Ok(())
+}
+
+fn run_annotate_command(command: AnnotateCommand) -> Result<()> {
+    match command {
+        AnnotateCommand::Append {
+            session,
+            role,
+            project,
+            tool,
+            text,
+            embeddings,
+            no_embeddings,
+            root,
+        } => {
+            run_append(
+                session,
+                role,
+                project,
+                tool,
+                text,
+                embeddings,
+                no_embeddings,
+                root,
+            )?;
+        }
+        AnnotateCommand::Note {
+            session_id,
+            text,
+            clear,
+            root,
+        } => {
+            run_note(&session_id, text, clear, root)?;
+        }
+        AnnotateCommand::Outcome {
+            session_id,
+            outcome,
+            clear,
+            root,
+        } => {
+            run_outcome(&session_id, outcome, clear, root)?;
+        }
+        AnnotateCommand::Outcomes {
+            project,
+            since,
+            by,
+            json,
+            root,
+        } => {
+            run_outcomes(project, since, by, json, root)?;
+        }
+        AnnotateCommand::Forget { session_id, root } => {
+            run_forget(&session_id, root)?;
+        }
+        AnnotateCommand::Restore { session_id, root } => {
+            run_restore(&session_id, root)?;
+        }
+        AnnotateCommand::Trash { action } => match action {
+            TrashCommand::List { root } => run_trash_list(root)?,
+            TrashCommand::Empty { root } => run_trash_empty(root)?,
+        },
+        AnnotateCommand::Project { action } => match action {
+            ProjectCommand::Rename { old, new, root } => run_project_rename(old, new, root)?,
+            ProjectCommand::List { root } => run_project_list(root)?,
+        },
+    }
+    Ok(())
+}
+
+fn run_inspect_command(command: InspectCommand) -> Result<()> {
+    match command {
+        InspectCommand::Show {
+            id,
+            verbose,
+            pager,
+            tools,
+            template,
+            root,
+        } => {
+            if pager {
+                run_show_pager(&id, tools, root)?;
+            } else {
+                let doc_id = id.parse().map_err(|_| {
+                    anyhow!("not a document ID: {id} (page a session with --pager)")
+                })?;
+                run_show(doc_id, verbose, template, root)?;
+            }
+        }
+        InspectCommand::Status { root } => {
+            if !run_status(root)? {
+                std::process::exit(1);
+            }
+        }
+        InspectCommand::IngestErrors {
+            source,
+            json,
+            clear,
+            root,
+        } => {
+            run_ingest_errors(source, json, clear, root)?;
+        }
+        InspectCommand::Scan { index, explain } => {
+            run_scan(&index, explain.as_deref())?;
+        }
+        InspectCommand::Stats {
+            action:
+                Some(StatsCommand::Export {
+                    anonymize,
+                    salt,
+                    source,
+                    since,
+                    until,
+                    markdown,
+                    output,
+                    root,
+                }),
+            ..
+        } => run_stats_export(
+            anonymize, salt, source, since, until, markdown, output, root,
+        )?,
+        InspectCommand::Stats {
+            action: None,
+            by,
+            source,
+            since,
+            until,
+            json,
+            root,
+        } => match by {
+            Some(by) => run_activity_stats(by, source, since, until, json, root)?,
+            None => run_stats(root)?,
+        },
+    }
+    Ok(())
+}
+
+fn run_sharing_command(command: SharingCommand) -> Result<()> {
+    match command {
+        SharingCommand::Share {
+            session_id,
+            title,
+            output,
+            format,
+            collapse_tools,
+            root,
+        } => {
+            if output.is_some() || format.is_some() {
+                run_share_bundle(session_id, title, output, format, collapse_tools, root)?;
+            } else {
+                run_share(session_id, title, root)?;
+            }
+        }
+        SharingCommand::Transfer {
+            session_id,
+            source,
+            to,
+            mode,
+            turns,
+            dry_run,
+            root,
+        } => {
+            run_transfer(session_id, source, to, mode, turns, dry_run, root)?;
+        }
+        SharingCommand::Push {
+            url,
+            all,
+            dry_run,
+            root,
+        } => {
+            run_push(url, all, dry_run, root)?;
+        }
+        SharingCommand::Serve { listen, root } => {
+            run_serve(&listen, root)?;
+        }
+        SharingCommand::Export {
+            to: _,
+            url,
+            index,
+            project,
+            source,
+            all,
+            output,
+            dry_run,
+            root,
+        } => {
+            let filter = ExportFilter {
+                project,
+                source,
+                all,
+            };
+            run_export(url, index, filter, output, dry_run, root)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_index_loop(index: &IndexArgs, interval_secs: u64, service: bool) -> Result<()> {
+    loop {
+        run_index_once(index, service)?;
+        std::io::stdout().flush().ok();
+        std::thread::sleep(Duration::from_secs(interval_secs));
+    }
+}
+
+/// One incremental index run. Under the index service the outcome is kept in
+/// state for `memex status` and the TUI, and a new failure raises a desktop
+/// notification, since nobody is watching the service logs. The service also
+/// skips runs while its power/idle policy says the machine is off limits.
+fn run_index_once(index: &IndexArgs, service: bool) -> Result<()> {
+    if service {
+        let paths = Paths::new(index.root.clone())?;
+        let policy = UserConfig::load(&paths)?.index_service_policy()?;
+        if let Some(reason) = policy.blocker() {
+            println!("skipping index run: {reason}");
+            return Ok(());
+        }
+    }
+    let result = run_index_args(index, false, None);
+    let result = match (result, service) {
+        (Ok(()), true) => run_scheduled_prune(index.root.clone()),
+        (result, _) => result,
+    };
+    if service && let Err(err) = report_service_run(index.root.clone(), &result) {
+        eprintln!("memex: failed to record index service status: {err:#}");
+    }
+    result
+}
+
+/// Applies the retention policy when it is set and the last prune was more
+/// than a day ago.
+fn run_scheduled_prune(root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let policy = UserConfig::load(&paths)?.retention_policy();
+    let state_path = retention_state_path(&paths.state);
+    let mut state = RetentionState::load(&state_path)?;
+    let now = now_millis();
+    if !policy.is_enabled() || !state.due(now) {
+        return Ok(());
+    }
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let report = prune(&paths, &index, &policy, now, false)?;
+    print_retention_report(&report, &paths.project_aliases()?);
+    state.last_run = now;
+    state.last_report = Some(report);
+    state.save(&state_path)
+}
+
+fn report_service_run(root: Option<PathBuf>, result: &Result<()>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let notify = record_service_run(&service_status_path(&paths.state), result)?;
+    if let Err(err) = result
+        && notify
+        && UserConfig::load(&paths)
+            .map(|config| config.index_service_notify())
+            .unwrap_or(true)
+    {
+        let detail = format!("{err:#}");
+        notify_desktop(
+            "memex indexing failed",
+            &format!(
+                "{}\nRun `memex status` for details.",
+                one_line(&detail, 200)
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// A source name in `--source` or a `--path` glob narrows the rebuild to
+/// those files; otherwise the whole index is dropped and rebuilt.
+fn run_reindex(index: &IndexArgs, path: Option<&str>) -> Result<()> {
+    let mut index = index.clone();
+    let source = index
+        .source
+        .as_deref()
+        .and_then(|source| source.to_str())
+        .and_then(|name| SourceFilter::from_str(name, true).ok());
+    if source.is_some() {
+        index.source = None;
+    }
+    let path = path
+        .map(|raw| {
+            let raw = match raw.strip_prefix("~/") {
+                Some(rest) => directories::BaseDirs::new()
+                    .map(|dirs| dirs.home_dir().join(rest).to_string_lossy().to_string())
+                    .unwrap_or_else(|| raw.to_string()),
+                None => raw.to_string(),
+            };
+            glob::Pattern::new(&raw).map_err(|err| anyhow!("invalid --path glob {raw}: {err}"))
+        })
+        .transpose()?;
+    let scope = (source.is_some() || path.is_some()).then_some(ReindexScope { source, path });
+    run_index_args(&index, true, scope)
+}
+
+fn run_index_args(index: &IndexArgs, reindex: bool, scope: Option<ReindexScope>) -> Result<()> {
+    run_index(
+        index.source.clone(),
+        index.include_agents,
+        index.codex && !index.no_codex,
+        index.opencode && !index.no_opencode,
+        index.cursor,
+        index.pi && !index.no_pi,
+        index.copilot && !index.no_copilot,
+        index.embeddings,
+        index.no_embeddings,
+        index.model.clone(),
+        index.root.clone(),
+        reindex,
+        scope,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_index(
+    source: Option<PathBuf>,
+    include_agents: bool,
+    codex: bool,
+    opencode: bool,
+    cursor: bool,
+    pi: bool,
+    copilot: bool,
+    embeddings_flag: bool,
+    no_embeddings: bool,
+    model: Option<String>,
+    root: Option<PathBuf>,
+    reindex: bool,
+    scope: Option<ReindexScope>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+
+    // Model priority: CLI flag > config file > env var > default
+    let model_choice = config.resolve_model(model)?;
+    let embed_runtime = config.resolve_embed_runtime()?;
+    let tool_content_limits = config.indexed_tool_content_limits()?;
+    let embeddings = resolve_flag(
+        config.embeddings_default(),
+        embeddings_flag,
+        no_embeddings,
+        "embeddings",
+    )?;
+    // A full reindex wipes the data directory; notes, outcome labels,
+    // imported exports and appended messages are the user's own, so carry
+    // them across.
+    let mut notes = SessionNotes::default();
+    let mut outcomes = None;
+    let imports = imports_dir(&paths);
+    let mut kept_imports = None;
+    if reindex && scope.is_none() && paths.root.exists() {
+        notes = SessionNotes::load(&notes_path(&paths.state))?;
+        outcomes = Some(SessionOutcomes::load(&outcomes_path(&paths.state))?);
+        if imports.exists() {
+            let parent = paths.root.parent().unwrap_or(Path::new("."));
+            let keep = tempfile::Builder::new()
+                .prefix(".memex-imports")
+                .tempdir_in(parent)?;
+            std::fs::rename(&imports, keep.path().join("imports"))?;
+            kept_imports = Some(keep);
+        }
+        std::fs::remove_dir_all(&paths.root)?;
+        // Kept apart from the data directory in the XDG layout.
+        if paths.state.exists() {
+            std::fs::remove_dir_all(&paths.state)?;
+        }
+    }
+    let auto_vacuum_threshold = config.auto_vacuum_threshold()?;
+    let shard_after_months = config.shard_after_months();
+    paths.ensure_dirs()?;
+    if let Some(keep) = kept_imports {
+        std::fs::rename(keep.path().join("imports"), &imports)?;
+    }
+    if let Some(outcomes) = outcomes.filter(|outcomes| !outcomes.outcomes.is_empty()) {
+        outcomes.save(&outcomes_path(&paths.state))?;
+    }
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create_for_ingest(&paths.index)?;
+
+    let opts = IngestOptions {
+        claude_source: source.unwrap_or_else(default_claude_source),
+        include_agents,
+        include_codex: codex,
+        include_opencode: opencode,
+        include_cursor: cursor,
+        include_pi: pi,
+        include_copilot: copilot,
+        embeddings,
+        backfill_embeddings: false,
+        model: model_choice,
+        embed_runtime,
+        tool_content_limits,
+        max_threads: config.max_cpu_threads()?,
+        ocr_images: config.ocr_images(),
+        embed_listener: None,
+        archive_tier: config.archive_tier()?,
+    };
+
+    let report = match &scope {
+        Some(scope) => reindex_scoped(&paths, &index, &opts, scope)?,
+        None => ingest_all(&paths, &index, &opts)?,
+    };
+    restore_notes(&paths, &index, &notes)?;
+    if report.records_embedded > 0 {
+        println!(
+            "indexed {} records, embedded {} across {} files (skipped {})",
+            report.records_added,
+            report.records_embedded,
+            report.files_scanned,
+            report.files_skipped
+        );
+    } else {
+        println!(
+            "indexed {} records across {} files (skipped {})",
+            report.records_added, report.files_scanned, report.files_skipped
+        );
+    }
+    if report.records_too_large > 0 || report.nested_transcripts > 0 {
+        println!(
+            "skipped {} records over max_indexed_record_bytes and {} nested transcripts",
+            report.records_too_large, report.nested_transcripts
+        );
+    }
+    if report.malformed_lines > 0 {
+        println!(
+            "skipped {} malformed lines; list them with `memex ingest-errors`",
+            report.malformed_lines
+        );
+    }
+    if opts.ocr_images && crate::ocr::Ocr::detect().is_none() {
+        println!("ocr_images is set but tesseract is not installed; images were not read");
+    }
+    if let Some(months) = shard_after_months {
+        let moved =
+            index.split_months(month_start_before(months), opts.tool_content_limits.code)?;
+        print_split_report(&moved);
+    }
+    if config.elasticsearch_sync() {
+        // The index is already committed; a cluster that is down only
+        // delays the export to the next run.
+        let home =
+            directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_string_lossy().into_owned());
+        let synced = elastic_target(&config, None, None).and_then(|target| {
+            let outcome = export_to_cluster(
+                &paths,
+                &index,
+                &target,
+                &ExportFilter::default(),
+                home.as_deref(),
+            )?;
+            Ok((target, outcome))
+        });
+        match synced {
+            Ok((target, outcome)) => print_export_report(&target, &outcome),
+            Err(err) => eprintln!("elasticsearch sync failed: {err:#}"),
+        }
+    }
+    if let Some(threshold) = auto_vacuum_threshold
+        && fragmentation(&paths, &index)? > threshold
+    {
+        print_vacuum_report(&vacuum(&paths, &index)?);
+    }
+    Ok(())
+}
+
+fn run_import_archive(
+    dir: PathBuf,
+    heap_mb: u64,
+    include_agents: bool,
+    embeddings_flag: bool,
+    no_embeddings: bool,
+    model: Option<String>,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let embeddings = resolve_flag(
+        config.embeddings_default(),
+        embeddings_flag,
+        no_embeddings,
+        "embeddings",
+    )?;
+    paths.ensure_dirs()?;
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create_for_ingest(&paths.index)?;
+    let opts = IngestOptions {
+        claude_source: dir.clone(),
+        include_agents,
+        include_codex: true,
+        include_opencode: false,
+        include_cursor: false,
+        include_pi: false,
+        include_copilot: false,
+        embeddings,
+        backfill_embeddings: false,
+        model: config.resolve_model(model)?,
+        embed_runtime: config.resolve_embed_runtime()?,
+        tool_content_limits: config.indexed_tool_content_limits()?,
+        max_threads: config.max_cpu_threads()?,
+        ocr_images: config.ocr_images(),
+        embed_listener: None,
+        archive_tier: config.archive_tier()?,
+    };
+    let archive = ArchiveImport {
+        dir,
+        writer_heap_bytes: usize::try_from(heap_mb * 1024 * 1024)?,
+    };
+    let started = Instant::now();
+    let report = import_archive(&paths, &index, &opts, &archive)?;
+    let embedded = if report.records_embedded > 0 {
+        format!(", embedded {}", report.records_embedded)
+    } else {
+        String::new()
+    };
+    println!(
+        "imported {} records{embedded} from {} files (skipped {} already imported) in {:.1}s",
+        report.records_added,
+        report.files_scanned - report.files_skipped,
+        report.files_skipped,
+        started.elapsed().as_secs_f64()
+    );
+    if report.records_too_large > 0 || report.nested_transcripts > 0 {
+        println!(
+            "skipped {} records over max_indexed_record_bytes and {} nested transcripts",
+            report.records_too_large, report.nested_transcripts
+        );
+    }
+    if report.malformed_lines > 0 {
+        println!(
+            "skipped {} malformed lines; list them with `memex ingest-errors`",
+            report.malformed_lines
+        );
+    }
+    Ok(())
+}
+
+fn run_import_chatgpt(
+    export: &Path,
+    embeddings_flag: bool,
+    no_embeddings: bool,
+    model: Option<String>,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    const WRITER_HEAP_BYTES: usize = 512 * 1024 * 1024;
+
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let embeddings = resolve_flag(
+        config.embeddings_default(),
+        embeddings_flag,
+        no_embeddings,
+        "embeddings",
+    )?;
+    paths.ensure_dirs()?;
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create_for_ingest(&paths.index)?;
+    let opts = IngestOptions {
+        claude_source: chatgpt_import_dir(&paths),
+        include_agents: false,
+        include_codex: false,
+        include_opencode: false,
+        include_cursor: false,
+        include_pi: false,
+        include_copilot: false,
+        embeddings,
+        backfill_embeddings: false,
+        model: config.resolve_model(model)?,
+        embed_runtime: config.resolve_embed_runtime()?,
+        tool_content_limits: config.indexed_tool_content_limits()?,
+        max_threads: config.max_cpu_threads()?,
+        ocr_images: false,
+        embed_listener: None,
+        archive_tier: config.archive_tier()?,
+    };
+    let started = Instant::now();
+    let report = import_chatgpt(&paths, &index, &opts, export, WRITER_HEAP_BYTES)?;
+    if report.files_skipped > 0 {
+        println!("{} was already imported", export.display());
+        return Ok(());
+    }
+    let embedded = if report.records_embedded > 0 {
+        format!(", embedded {}", report.records_embedded)
+    } else {
+        String::new()
+    };
+    println!(
+        "imported {} ChatGPT records{embedded} in {:.1}s",
+        report.records_added,
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+fn run_embed(model: Option<String>, root: Option<PathBuf>) -> Result<()> {
+    const BATCH_SIZE: usize = 256;
+
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+
+    // Model priority: CLI flag > config file > env var > default
+    let model_choice = config.resolve_model(model)?;
+    let embed_runtime = config.resolve_embed_runtime()?;
+
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let mut embedder = EmbedderHandle::with_model_and_runtime(model_choice, &embed_runtime)?;
+    let mut vector =
+        VectorIndex::open_or_create(&paths.vectors, embedder.dims, Some(model_choice.as_str()))?;
+    // Old records go to the archive store, if there is a tier for them.
+    let mut archive = match config.archive_tier()? {
+        Some(tier) => {
+            let embedder = EmbedderHandle::with_model_and_runtime(tier.model, &embed_runtime)?;
+            let vector = VectorIndex::open_or_create(
+                &archive_vectors_dir(&paths.vectors),
+                embedder.dims,
+                Some(tier.model.as_str()),
+            )?;
+            let now_ms = chrono::Utc::now().timestamp_millis().max(0) as u64;
+            Some((tier.cutoff_ms(now_ms), embedder, vector, Vec::new()))
+        }
+        None => None,
+    };
+
+    let progress = std::sync::Arc::new(crate::progress::Progress::new(
+        [0; crate::progress::SOURCE_COUNT],
+        [0; crate::progress::SOURCE_COUNT],
+        true,
+    ));
+    progress.set_embed_ready();
+
+    let mut embedded_counts = [0u64; crate::progress::SOURCE_COUNT];
+    let mut embedded_total = 0u64;
+    let mut batch: Vec<(u64, String, crate::types::SourceKind)> = Vec::with_capacity(BATCH_SIZE);
+
+    let flush_batch = |batch: &mut Vec<(u64, String, crate::types::SourceKind)>,
+                       embedder: &mut EmbedderHandle,
+                       vector: &mut VectorIndex,
+                       progress: &crate::progress::Progress,
+                       embedded_counts: &mut [u64; crate::progress::SOURCE_COUNT],
+                       embedded_total: &mut u64| {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let texts: Vec<&str> = batch.iter().map(|(_, text, _)| text.as_str()).collect();
+        let embeddings = embedder.embed_texts(&texts)?;
+        embedder.pause_after_batch();
+
+        for ((doc_id, _, source), vec) in batch.iter().zip(embeddings.iter()) {
+            vector.add(*doc_id, vec)?;
+            progress.sub_embed_pending(*source, 1);
+            progress.add_embedded(*source, 1);
+            embedded_counts[source.idx()] += 1;
+            *embedded_total += 1;
+        }
+        progress.embed_batch_done();
+        batch.clear();
+        Ok::<_, anyhow::Error>(())
+    };
+
+    index.for_each_record(|record| {
+        if record.text.is_empty() || !is_embedding_role(&record.role) {
+            return Ok(());
+        }
+        if vector.contains(record.doc_id)
+            || archive
+                .as_ref()
+                .is_some_and(|(_, _, archived, _)| archived.contains(record.doc_id))
+        {
+            return Ok(());
+        }
+        let text = truncate_for_embedding(record.text);
+        if !text.is_empty() {
+            progress.add_embed_total(record.source, 1);
+            progress.add_embed_pending(record.source, 1);
+            if let Some((cutoff_ms, archive_embedder, archive_vector, archive_batch)) =
+                archive.as_mut()
+                && record.ts < *cutoff_ms
+            {
+                archive_batch.push((record.doc_id, text, record.source));
+                if archive_batch.len() >= BATCH_SIZE {
+                    flush_batch(
+                        archive_batch,
+                        archive_embedder,
+                        archive_vector,
+                        &progress,
+                        &mut embedded_counts,
+                        &mut embedded_total,
+                    )?;
+                }
+                return Ok(());
+            }
+            batch.push((record.doc_id, text, record.source));
+
+            if batch.len() >= BATCH_SIZE {
+                flush_batch(
+                    &mut batch,
+                    &mut embedder,
+                    &mut vector,
+                    &progress,
+                    &mut embedded_counts,
+                    &mut embedded_total,
+                )?;
+            }
+        }
+        Ok(())
+    })?;
+
+    // Flush remaining
+    flush_batch(
+        &mut batch,
+        &mut embedder,
+        &mut vector,
+        &progress,
+        &mut embedded_counts,
+        &mut embedded_total,
+    )?;
+    if let Some((_, mut archive_embedder, mut archive_vector, mut archive_batch)) = archive {
+        flush_batch(
+            &mut archive_batch,
+            &mut archive_embedder,
+            &mut archive_vector,
+            &progress,
+            &mut embedded_counts,
+            &mut embedded_total,
+        )?;
+        archive_vector.save()?;
+    }
+
+    vector.save()?;
+    progress.finish();
+    println!(
+        "embedded {} vectors (claude {}, codex {}, history {}, opencode {}, cursor {}, pi {}, copilot {}, chatgpt {}, external {})",
+        embedded_total,
+        embedded_counts[crate::types::SourceKind::Claude.idx()],
+        embedded_counts[crate::types::SourceKind::CodexSession.idx()],
+        embedded_counts[crate::types::SourceKind::CodexHistory.idx()],
+        embedded_counts[crate::types::SourceKind::Opencode.idx()],
+        embedded_counts[crate::types::SourceKind::Cursor.idx()],
+        embedded_counts[crate::types::SourceKind::Pi.idx()],
+        embedded_counts[crate::types::SourceKind::Copilot.idx()],
+        embedded_counts[crate::types::SourceKind::ChatGPT.idx()],
+        embedded_counts[crate::types::SourceKind::APPENDED.idx()],
+    );
+
+    std::io::stdout().flush().ok();
+    std::process::exit(0);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_search(
+    queries: Vec<String>,
+    project: Option<String>,
+    role: Option<String>,
+    tool: Option<String>,
+    session: Option<String>,
+    source: Option<SourceFilter>,
+    semantic: bool,
+    hybrid: bool,
+    no_semantic: bool,
+    min_score: Option<f32>,
+    profile: Option<String>,
+    recency_weight: Option<f32>,
+    recency_half_life_days: Option<f32>,
+    since: Option<String>,
+    until: Option<String>,
+    as_of: Option<String>,
+    limit: usize,
+    top_n_per_session: Option<usize>,
+    unique_session: bool,
+    json_array: bool,
+    fields: Option<String>,
+    template: Option<String>,
+    sort: SortBy,
+    verbose: bool,
+    explain: bool,
+    remote: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    if queries.is_empty() {
+        return Err(anyhow!("give a search query, or one or more with -q"));
+    }
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let project = project.map(|project| aliases.resolve(&project));
+    let config = UserConfig::load(&paths)?;
+    let remote = if remote {
+        Some(team_remote(&config, None)?)
+    } else {
+        None
+    };
+    let model_choice = config.resolve_model(None)?;
+    let embed_runtime = config.resolve_embed_runtime()?;
+    let profile = config.resolve_scoring_profile(profile.as_deref())?;
+    let tuning = config.query_tuning()?;
+    let recency_weight = recency_weight.unwrap_or(profile.recency_weight);
+    let recency_half_life_days = recency_half_life_days.unwrap_or(profile.recency_half_life_days);
+    // The team server has its own index; nothing local to refresh.
+    let auto_index_on_search = config.auto_index_on_search_default() && remote.is_none();
+    let embeddings_default = config.embeddings_default() && !no_semantic;
+    let scan_cache_ttl = config.scan_cache_ttl();
+    if auto_index_on_search {
+        paths.ensure_dirs()?;
+    }
+    // When another process is already indexing, search its last commit instead.
+    let write_lock = if auto_index_on_search {
+        WriteLock::try_acquire(&paths.state)?
+    } else {
+        None
+    };
+    if write_lock.is_some() {
+        let tool_content_limits = config.indexed_tool_content_limits()?;
+        let index = SearchIndex::open_or_create_for_ingest(&paths.index)?;
+        let opts = IngestOptions {
+            claude_source: default_claude_source(),
+            include_agents: false,
+            include_codex: true,
+            include_opencode: true,
+            include_cursor: true,
+            include_pi: true,
+            include_copilot: true,
+            embeddings: embeddings_default,
+            backfill_embeddings: false,
+            model: model_choice,
+            embed_runtime: embed_runtime.clone(),
+            tool_content_limits,
+            max_threads: config.max_cpu_threads()?,
+            ocr_images: config.ocr_images(),
+            embed_listener: None,
+            archive_tier: config.archive_tier()?,
+        };
+        // Skip indexing if we recently scanned (within TTL)
+        let _ = ingest_if_stale(&paths, &index, &opts, scan_cache_ttl)?;
+    }
+    drop(write_lock);
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    if let Some(project) = &project {
+        let _ = record_project_visits(
+            &project_frecency_path(&paths.state),
+            [shown_project_name(project, &aliases).as_str()],
+        );
+    }
+
+    let since = parse_ts_millis(since)?;
+    let until = parse_ts_millis(until)?;
+    let as_of = parse_ts_millis(as_of)?;
+    // Each phrasing may carry its own query filters, e.g. `model:o3`.
+    let searches: Vec<QueryOptions> = queries
+        .iter()
+        .map(|raw| {
+            let (query, filters) = extract_query_filters(raw);
+            let (since, until) = (filters.since(since), filters.until(until));
+            QueryOptions {
+                query,
+                project: project.clone(),
+                role: role.clone().or(filters.role),
+                tool: tool.clone(),
+                session_id: session.clone(),
+                source,
+                since,
+                until,
+                as_of,
+                settings: filters.settings,
+                has: filters.has,
+                edited: filters.edited,
+                commands: filters.commands,
+                files: filters.files,
+                langs: filters.langs,
+                errors: filters.errors,
+                require_all_terms: profile.require_all_terms,
+                fuzzy_distance: profile.fuzzy_distance,
+                tuning: tuning.clone(),
+                limit,
+            }
+        })
+        .collect();
+    let mut terms: Vec<String> = Vec::new();
+    for options in &searches {
+        for term in text::query_terms(&options.query) {
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+    }
+    let matchers = build_matchers(&terms)?;
+    let fields = parse_fields(fields)?;
+    let template = if verbose || json_array {
+        None
+    } else {
+        output_template(&config, "search", template.as_deref())?
+    };
+    let top_n_per_session = if unique_session && top_n_per_session.is_none() {
+        Some(1)
+    } else {
+        top_n_per_session
+    };
+    let render = RenderOptions {
+        verbose,
+        terms,
+        matchers,
+        json_array: json_array && !verbose,
+        fields,
+        template,
+        sort,
+        min_score,
+        top_n_per_session,
+        limit,
+        hyperlinks: crate::hyperlink::terminal_allows(&config),
+        aliases,
+    };
+
+    // Several phrasings collapse to one result per session, so fetch more.
+    let candidate_limit = if top_n_per_session.is_some() || source.is_some() || searches.len() > 1 {
+        (limit * 5).max(limit + 10)
+    } else {
+        limit
+    };
+
+    let mut semantic_backend = None;
+    if hybrid || semantic {
+        match SemanticBackend::open(&paths, model_choice, &embed_runtime, config.archive_tier()?) {
+            Ok(backend) => semantic_backend = Some(backend),
+            Err(err) if is_missing_vector_index_error(&err) => {
+                warn_vector_index_missing(if hybrid { "hybrid" } else { "semantic" });
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    let ctx = SearchContext {
+        recency_weight,
+        recency_half_life_days,
+        keyword_weight: profile.keyword_weight,
+        semantic_weight: profile.semantic_weight,
+        explain,
+    };
+
+    // Union of every phrasing's results; a record found by several keeps its
+    // best score, and `origin` remembers which phrasing that was.
+    let mut results: Vec<(f32, crate::types::Record)> = Vec::new();
+    let mut positions: HashMap<u64, usize> = HashMap::new();
+    let mut origin: HashMap<u64, usize> = HashMap::new();
+    let mut explanations: HashMap<u64, ScoreExplanation> = HashMap::new();
+    for (idx, options) in searches.iter().enumerate() {
+        let (found, mut found_explanations) = match semantic_backend.as_mut() {
+            Some(backend) if hybrid => {
+                run_hybrid_search(&index, options, candidate_limit, backend, &ctx)?
+            }
+            Some(backend) => run_semantic_search(&index, options, candidate_limit, backend, &ctx)?,
+            None => match &remote {
+                Some(remote) => rank_lexical(remote.search(options)?, options, &ctx),
+                None => run_lexical_search(&index, options, &ctx)?,
+            },
+        };
+        for (score, record) in found {
+            let doc_id = record.doc_id;
+            match positions.get(&doc_id) {
+                Some(&pos) if results[pos].0 >= score => continue,
+                Some(&pos) => results[pos] = (score, record),
+                None => {
+                    positions.insert(doc_id, results.len());
+                    results.push((score, record));
+                }
+            }
+            origin.insert(doc_id, idx);
+            if let Some(mut explanation) = found_explanations.remove(&doc_id) {
+                if searches.len() > 1 {
+                    explanation.query = Some(options.query.clone());
+                }
+                explanations.insert(doc_id, explanation);
+            }
+        }
+    }
+    if searches.len() > 1 {
+        results = best_per_session(results);
+    }
+
+    let results = apply_post_processing(results, &render);
+    if explain {
+        // Only shown results get the per-term breakdown.
+        for (_, record) in &results {
+            if let Some(explanation) = explanations.get_mut(&record.doc_id)
+                && explanation.bm25.is_some()
+                && let Some(&idx) = origin.get(&record.doc_id)
+            {
+                explanation.terms = index.term_scores(&searches[idx], record.doc_id)?;
+            }
+        }
+    }
+    render_results(results, &render, &explanations)
+}
+
+/// Keeps each session's highest-scoring result, for searches that union
+/// several phrasings.
+fn best_per_session(results: Vec<(f32, crate::types::Record)>) -> Vec<(f32, crate::types::Record)> {
+    let mut kept: Vec<(f32, crate::types::Record)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (score, record) in results {
+        match positions.get(&record.session_id) {
+            Some(&pos) => {
+                if score > kept[pos].0 {
+                    kept[pos] = (score, record);
+                }
+            }
+            None => {
+                positions.insert(record.session_id.clone(), kept.len());
+                kept.push((score, record));
+            }
+        }
+    }
+    kept
+}
+
+/// Results of one phrasing, before post-processing, with an explanation per
+/// result when `--explain` is on.
+type Ranked = (
+    Vec<(f32, crate::types::Record)>,
+    HashMap<u64, ScoreExplanation>,
+);
+
+struct SearchContext {
+    recency_weight: f32,
+    recency_half_life_days: f32,
+    /// Hybrid search multipliers on each side's reciprocal-rank score.
+    keyword_weight: f32,
+    semantic_weight: f32,
+    explain: bool,
+}
+
+fn run_semantic_search(
+    index: &SearchIndex,
+    options: &QueryOptions,
+    limit: usize,
+    backend: &mut SemanticBackend,
+    ctx: &SearchContext,
+) -> Result<Ranked> {
+    let mut results = Vec::new();
+    let mut explanations = HashMap::new();
+    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+    for (doc_id, distance) in backend.search(index, options, limit)? {
+        if let Some(record) = index.get_by_doc_id(doc_id)?
+            && matches_filters(&record, options)
+        {
+            let base = score_from_distance(distance);
+            let score = apply_recency(
+                base,
+                record.ts,
+                now_ms,
+                ctx.recency_weight,
+                ctx.recency_half_life_days,
+            );
+            if ctx.explain {
+                let mut explanation = ScoreExplanation::new(
+                    base,
+                    record.ts,
+                    now_ms,
+                    ctx.recency_weight,
+                    ctx.recency_half_life_days,
+                );
+                // Cosine distance, so similarity is its complement.
+                explanation.similarity = Some(1.0 - distance);
+                explanations.insert(doc_id, explanation);
+            }
+            results.push((score, record));
+        }
+    }
+    Ok((results, explanations))
+}
+
+/// Nearest vectors among the records passing the search filters. With a
+/// filter set, only matching doc ids are candidates, so a narrow project or
+/// date range still fills `limit` instead of losing most of a global top-k.
+fn filtered_vector_search(
+    index: &SearchIndex,
+    vector: &VectorIndex,
+    embedding: &[f32],
+    limit: usize,
+    options: &QueryOptions,
+) -> Result<Vec<(u64, f32)>> {
+    if !options.has_filters() {
+        return vector.search(embedding, limit);
+    }
+    let allowed = index.filtered_doc_ids(options)?;
+    vector.search_among(embedding, limit, &allowed)
+}
+
+/// The vector stores semantic and hybrid search read: the main store and,
+/// with an archive tier, the archive store, each with an embedder for the
+/// model its vectors came from.
+struct SemanticBackend {
+    stores: Vec<(VectorIndex, EmbedderHandle)>,
+}
+
+impl SemanticBackend {
+    /// Fails with the missing-index error when there is no main store. An
+    /// archive store not built yet is skipped.
+    fn open(
+        paths: &Paths,
+        model: ModelChoice,
+        runtime: &EmbedRuntimeConfig,
+        archive_tier: Option<ArchiveTier>,
+    ) -> Result<Self> {
+        let vector = VectorIndex::open(&paths.vectors)?;
+        let mut stores = vec![(
+            vector,
+            EmbedderHandle::with_model_and_runtime(model, runtime)?,
+        )];
+        if let Some(tier) = archive_tier {
+            match VectorIndex::open(&archive_vectors_dir(&paths.vectors)) {
+                Ok(vector) => stores.push((
+                    vector,
+                    EmbedderHandle::with_model_and_runtime(tier.model, runtime)?,
+                )),
+                Err(err) if is_missing_vector_index_error(&err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Self { stores })
+    }
+
+    /// Nearest vectors to the query across every store, the query embedded
+    /// once per model.
+    fn search(
+        &mut self,
+        index: &SearchIndex,
+        options: &QueryOptions,
+        limit: usize,
+    ) -> Result<Vec<(u64, f32)>> {
+        let mut hits = Vec::new();
+        for (vector, embedder) in &mut self.stores {
+            let embeddings = embedder.embed_texts(&[options.query.as_str()])?;
+            let embedding = embeddings
+                .first()
+                .ok_or_else(|| anyhow!("embedding missing"))?;
+            hits.push(filtered_vector_search(
+                index, vector, embedding, limit, options,
+            )?);
+        }
+        Ok(merge_vector_hits(hits, limit))
+    }
+}
+
+/// The closest `limit` of several stores' hits. Distances from different
+/// models are compared as they are; a record in both stores keeps its
+/// closer hit.
+fn merge_vector_hits(hits: Vec<Vec<(u64, f32)>>, limit: usize) -> Vec<(u64, f32)> {
+    if hits.len() == 1 {
+        return hits.into_iter().next().unwrap_or_default();
+    }
+    let mut merged: Vec<(u64, f32)> = hits.into_iter().flatten().collect();
+    merged.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    let mut seen = HashSet::new();
+    merged.retain(|(doc_id, _)| seen.insert(*doc_id));
+    merged.truncate(limit);
+    merged
+}
+
+fn run_hybrid_search(
+    index: &SearchIndex,
+    options: &QueryOptions,
+    limit: usize,
+    backend: &mut SemanticBackend,
+    ctx: &SearchContext,
+) -> Result<Ranked> {
+    let bm25_k = (limit * 5).clamp(50, 500);
+    let vector_k = (limit * 5).clamp(50, 500);
+
+    let bm25_results = index.search(&QueryOptions {
+        limit: bm25_k,
+        ..options.clone()
+    })?;
+
+    let vector_results = backend.search(index, options, vector_k)?;
+
+    let mut records: HashMap<u64, crate::types::Record> = HashMap::new();
+    let mut scores: HashMap<u64, f32> = HashMap::new();
+    let rrf_k = 60.0;
+    let mut explanations: HashMap<u64, ScoreExplanation> = HashMap::new();
+    let fusion = FusionExplanation {
+        rrf_k,
+        keyword_weight: ctx.keyword_weight,
+        semantic_weight: ctx.semantic_weight,
+        ..FusionExplanation::default()
+    };
+
+    for (rank, (bm25, record)) in bm25_results.into_iter().enumerate() {
+        if !matches_filters(&record, options) {
+            continue;
+        }
+        let contribution = ctx.keyword_weight / (rrf_k + rank as f32 + 1.0);
+        scores
+            .entry(record.doc_id)
+            .and_modify(|v| *v += contribution)
+            .or_insert(contribution);
+        if ctx.explain {
+            let explanation = explanations.entry(record.doc_id).or_default();
+            explanation.bm25 = Some(bm25);
+            explanation
+                .fusion
+                .get_or_insert_with(|| fusion.clone())
+                .keyword_rank = Some(rank + 1);
+        }
+        records.insert(record.doc_id, record);
+    }
+
+    for (rank, (doc_id, distance)) in vector_results.into_iter().enumerate() {
+        if let Some(record) = index.get_by_doc_id(doc_id)? {
+            if !matches_filters(&record, options) {
+                continue;
+            }
+            let contribution = ctx.semantic_weight / (rrf_k + rank as f32 + 1.0);
+            scores
+                .entry(doc_id)
+                .and_modify(|v| *v += contribution)
+                .or_insert(contribution);
+            if ctx.explain {
+                let explanation = explanations.entry(doc_id).or_default();
+                explanation.similarity = Some(1.0 - distance);
+                explanation
+                    .fusion
+                    .get_or_insert_with(|| fusion.clone())
+                    .semantic_rank = Some(rank + 1);
+            }
+            records.entry(doc_id).or_insert(record);
+        }
+    }
+
+    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+    let merged: Vec<(f32, crate::types::Record)> = scores
+        .into_iter()
+        .filter_map(|(doc_id, score)| {
+            records.remove(&doc_id).map(|r| {
+                if let Some(explanation) = explanations.get_mut(&doc_id) {
+                    let ranks = std::mem::take(explanation);
+                    *explanation = ScoreExplanation {
+                        bm25: ranks.bm25,
+                        similarity: ranks.similarity,
+                        fusion: ranks.fusion,
+                        ..ScoreExplanation::new(
+                            score,
+                            r.ts,
+                            now_ms,
+                            ctx.recency_weight,
+                            ctx.recency_half_life_days,
+                        )
+                    };
+                }
+                (
+                    apply_recency(
+                        score,
+                        r.ts,
+                        now_ms,
+                        ctx.recency_weight,
+                        ctx.recency_half_life_days,
+                    ),
+                    r,
+                )
+            })
+        })
+        .collect();
+    Ok((merged, explanations))
+}
+
+fn run_models_list(root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let configured = config.resolve_model(None)?;
+    let mut stdout = std::io::stdout().lock();
+    for model in ModelChoice::ALL {
+        let marker = if model == configured { "*" } else { " " };
+        let dims = model
+            .known_dimensions()
+            .map(|dims| dims.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let (status, location) = match model.cached_snapshot()? {
+            Some(snapshot) => ("cached", snapshot.display().to_string()),
+            None => ("missing", model.repo_id()?),
+        };
+        writeln!(
+            stdout,
+            "{marker} {}  {}  {}  {location}",
+            pad_end(model.as_str(), 7),
+            pad_start(&dims, 4),
+            pad_end(status, 7)
+        )?;
+    }
+    Ok(())
+}
+
+fn run_models_pull(model: Option<String>, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let model = config.resolve_model(model)?;
+    if let Some(snapshot) = model.cached_snapshot()? {
+        println!(
+            "{} is already cached at {}",
+            model.as_str(),
+            snapshot.display()
+        );
+        return Ok(());
+    }
+    if config.offline() {
+        return Err(anyhow!(
+            "network access is disabled (offline); not downloading {}",
+            model.as_str()
+        ));
+    }
+    eprintln!("downloading {} from {}", model.as_str(), model.repo_id()?);
+    EmbedderHandle::with_model_and_runtime(model, &config.resolve_embed_runtime()?)?;
+    match model.cached_snapshot()? {
+        Some(snapshot) => println!("cached {} at {}", model.as_str(), snapshot.display()),
+        None => println!("loaded {}", model.as_str()),
+    }
+    Ok(())
+}
+
+fn run_bench_search(
+    queries: &std::path::Path,
+    k: usize,
+    modes: Vec<BenchMode>,
+    profile: Option<String>,
+    json: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    if k == 0 {
+        return Err(anyhow!("--k must be at least 1"));
+    }
+    let queries = load_queries(queries)?;
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let config = UserConfig::load(&paths)?;
+    let profile = config.resolve_scoring_profile(profile.as_deref())?;
+    let tuning = config.query_tuning()?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let mut modes = if modes.is_empty() {
+        vec![BenchMode::Lexical, BenchMode::Semantic, BenchMode::Hybrid]
+    } else {
+        modes
+    };
+    let mut semantic_backend = None;
+    if modes.iter().any(|mode| *mode != BenchMode::Lexical) {
+        match SemanticBackend::open(
+            &paths,
+            config.resolve_model(None)?,
+            &config.resolve_embed_runtime()?,
+            config.archive_tier()?,
+        ) {
+            Ok(backend) => semantic_backend = Some(backend),
+            Err(err) if is_missing_vector_index_error(&err) => {
+                eprintln!("no embeddings yet; benchmarking lexical search only");
+                modes.retain(|mode| *mode == BenchMode::Lexical);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    let ctx = SearchContext {
+        recency_weight: profile.recency_weight,
+        recency_half_life_days: profile.recency_half_life_days,
+        keyword_weight: profile.keyword_weight,
+        semantic_weight: profile.semantic_weight,
+        explain: false,
+    };
+    // Session-judged queries skip repeat hits, so rank deeper than k.
+    let candidates = (k * 5).max(k + 10);
+
+    let mut stdout = std::io::stdout().lock();
+    if !json {
+        writeln!(
+            stdout,
+            "{}  {}  {}  {}",
+            pad_end("mode", 8),
+            pad_start("queries", 7),
+            pad_start(&format!("recall@{k}"), 9),
+            pad_start("MRR", 6)
+        )?;
+    }
+    for mode in modes {
+        let mut scores = ModeScores::default();
+        for query in &queries {
+            let (text, filters) = extract_query_filters(&query.query);
+            let options = QueryOptions {
+                query: text,
+                project: query
+                    .project
+                    .as_deref()
+                    .map(|project| aliases.resolve(project)),
+                role: filters.role,
+                tool: None,
+                session_id: None,
+                source: None,
+                since: filters.after,
+                until: filters.before,
+                as_of: None,
+                settings: filters.settings,
+                has: filters.has,
+                edited: filters.edited,
+                commands: filters.commands,
+                files: filters.files,
+                langs: filters.langs,
+                errors: filters.errors,
+                require_all_terms: profile.require_all_terms,
+                fuzzy_distance: profile.fuzzy_distance,
+                tuning: tuning.clone(),
+                limit: candidates,
+            };
+            let (mut ranked, _) = match (mode, semantic_backend.as_mut()) {
+                (BenchMode::Hybrid, Some(backend)) => {
+                    run_hybrid_search(&index, &options, candidates, backend, &ctx)?
+                }
+                (BenchMode::Semantic, Some(backend)) => {
+                    run_semantic_search(&index, &options, candidates, backend, &ctx)?
+                }
+                _ => run_lexical_search(&index, &options, &ctx)?,
+            };
+            ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+            let records: Vec<crate::types::Record> =
+                ranked.into_iter().map(|(_, record)| record).collect();
+            scores.add(query, &records, k);
+        }
+        let report = scores.report(mode.label(), k);
+        if json {
+            writeln!(stdout, "{}", serde_json::to_string(&report)?)?;
+        } else {
+            writeln!(
+                stdout,
+                "{}  {}  {}  {}",
+                pad_end(&report.mode, 8),
+                pad_start(&report.queries.to_string(), 7),
+                pad_start(&format!("{:.3}", report.recall), 9),
+                pad_start(&format!("{:.3}", report.mrr), 6)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Nearest prompts considered before the similarity threshold is applied.
+const ASKED_CANDIDATES: usize = 200;
+
+#[derive(Serialize)]
+struct AskedMatch {
+    similarity: f32,
+    ts: u64,
+    session_id: String,
+    project: String,
+    source: String,
+    question: String,
+    answer: Option<String>,
+}
+
+/// Prints earlier user prompts similar to `question` with the answer each got.
+/// Returns whether any cleared `threshold`.
+#[allow(clippy::too_many_arguments)]
+fn run_asked(
+    question: String,
+    threshold: f32,
+    limit: usize,
+    project: Option<String>,
+    source: Option<SourceFilter>,
+    json: bool,
+    root: Option<PathBuf>,
+) -> Result<bool> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let project = project.map(|project| aliases.resolve(&project));
+    let config = UserConfig::load(&paths)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let vector = match VectorIndex::open(&paths.vectors) {
+        Ok(vector) => vector,
+        Err(err) if is_missing_vector_index_error(&err) => {
+            return Err(anyhow!(
+                "no embeddings to compare against; run `memex embed` first"
+            ));
+        }
+        Err(err) => return Err(err),
+    };
+    let mut embedder = EmbedderHandle::with_model_and_runtime(
+        config.resolve_model(None)?,
+        &config.resolve_embed_runtime()?,
+    )?;
+    let embeddings = embedder.embed_texts(&[question.as_str()])?;
+    let embedding = embeddings
+        .first()
+        .ok_or_else(|| anyhow!("embedding missing"))?;
+
+    let mut matches: Vec<AskedMatch> = Vec::new();
+    let mut closest = None;
+    for (doc_id, distance) in vector.search(embedding, ASKED_CANDIDATES)? {
+        let Some(record) = index.get_by_doc_id(doc_id)? else {
+            continue;
+        };
+        if record.role != "user"
+            || source.is_some_and(|source| !source.matches(record.source))
+            || project
+                .as_deref()
+                .is_some_and(|project| record.project != project)
+        {
+            continue;
+        }
+        // Cosine distance, so similarity is its complement.
+        let similarity = 1.0 - distance;
+        closest.get_or_insert(similarity);
+        if similarity < threshold || matches.len() == limit {
+            break;
+        }
+        if matches
+            .iter()
+            .any(|earlier| earlier.session_id == record.session_id)
+        {
+            continue;
+        }
+        let answer = answer_to(&index.records_by_session_id(&record.session_id)?, &record);
+        matches.push(AskedMatch {
+            similarity,
+            ts: record.ts,
+            session_id: record.session_id,
+            project: aliases.shown(&record.project),
+            source: record.source.label().to_string(),
+            question: record.text,
+            answer,
+        });
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    if json {
+        for entry in &matches {
+            writeln!(stdout, "{}", serde_json::to_string(entry)?)?;
+        }
+        return Ok(!matches.is_empty());
+    }
+    if matches.is_empty() {
+        match closest {
+            Some(closest) => writeln!(
+                stdout,
+                "Not asked before (closest earlier prompt: {closest:.2} similar)"
+            )?,
+            None => writeln!(stdout, "Not asked before")?,
+        }
+        return Ok(false);
+    }
+    for entry in &matches {
+        writeln!(
+            stdout,
+            "{:.2} similar  {}  {:<8} {}\n    {}",
+            entry.similarity,
+            format_ts(entry.ts),
+            entry.source,
+            entry.project,
+            entry.session_id
+        )?;
+        writeln!(stdout, "  Q: {}", one_line(&entry.question, 300))?;
+        match &entry.answer {
+            Some(answer) => writeln!(stdout, "  A: {}", one_line(answer, 600))?,
+            None => writeln!(stdout, "  A: (no answer recorded)")?,
+        }
+        writeln!(stdout)?;
+    }
+    Ok(true)
+}
+
+/// Assistant text between `question` and the next user prompt in its session.
+fn answer_to(session: &[crate::types::Record], question: &crate::types::Record) -> Option<String> {
+    let mut records: Vec<&crate::types::Record> = session.iter().collect();
+    records.sort_by(|a, b| {
+        a.turn_id
+            .cmp(&b.turn_id)
+            .then_with(|| a.ts.cmp(&b.ts))
+            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    });
+    let start = records
+        .iter()
+        .position(|record| record.doc_id == question.doc_id)?;
+    let answer = records[start + 1..]
+        .iter()
+        .take_while(|record| record.role != "user")
+        .filter(|record| record.role == "assistant" && !record.text.trim().is_empty())
+        .map(|record| record.text.trim())
+        .collect::<Vec<_>>()
+        .join("\n");
+    (!answer.is_empty()).then_some(answer)
+}
+
+/// Collapses whitespace and cuts `text` to `max_columns`.
+fn one_line(text: &str, max_columns: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text::width(&text) <= max_columns {
+        return text;
+    }
+    let mut cut = take_width(&text, max_columns).to_string();
+    cut.push('…');
+    cut
+}
+
+fn run_lexical_search(
+    index: &SearchIndex,
+    options: &QueryOptions,
+    ctx: &SearchContext,
+) -> Result<Ranked> {
+    Ok(rank_lexical(index.search(options)?, options, ctx))
+}
+
+/// Applies the recency boost and filters to BM25 `results`, from the local
+/// index or the team server.
+fn rank_lexical(
+    results: Vec<(f32, crate::types::Record)>,
+    options: &QueryOptions,
+    ctx: &SearchContext,
+) -> Ranked {
+    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+    let mut explanations = HashMap::new();
+    if ctx.explain {
+        for (bm25, record) in &results {
+            let mut explanation = ScoreExplanation::new(
+                *bm25,
+                record.ts,
+                now_ms,
+                ctx.recency_weight,
+                ctx.recency_half_life_days,
+            );
+            explanation.bm25 = Some(*bm25);
+            explanations.insert(record.doc_id, explanation);
+        }
+    }
+    let mut reranked = apply_recency_to_results(
+        results,
+        now_ms,
+        ctx.recency_weight,
+        ctx.recency_half_life_days,
+    );
+    reranked.retain(|(_, record)| matches_filters(record, options));
+    (reranked, explanations)
+}
+
+fn is_missing_vector_index_error(err: &anyhow::Error) -> bool {
+    err.to_string() == "vector index not found"
+}
+
+fn warn_vector_index_missing(mode: &str) {
+    eprintln!(
+        "Warning: {mode} search requested, but the local vector index is not available; falling back to lexical search. Run 'memex embed' to build embeddings."
+    );
+}
+
+fn score_from_distance(distance: f32) -> f32 {
+    1.0 / (1.0 + distance)
+}
+
+fn matches_filters(record: &crate::types::Record, options: &QueryOptions) -> bool {
+    if let Some(project) = &options.project
+        && &record.project != project
+    {
+        return false;
+    }
+    if let Some(role) = &options.role
+        && &record.role != role
+    {
+        return false;
+    }
+    if let Some(tool) = &options.tool
+        && record.tool_name.as_deref() != Some(tool.as_str())
+    {
+        return false;
+    }
+    if let Some(source) = options.source
+        && !source.matches(record.source)
+    {
+        return false;
+    }
+    if let Some(session_id) = &options.session_id
+        && &record.session_id != session_id
+    {
+        return false;
+    }
+    if let Some(since) = options.since
+        && record.ts < since
+    {
+        return false;
+    }
+    if let Some(until) = options.until
+        && record.ts > until
+    {
+        return false;
+    }
+    if let Some(as_of) = options.as_of
+        && record.ingested_at > as_of
+    {
+        return false;
+    }
+    for (wanted, actual) in [
+        (&options.settings.model, &record.settings.model),
+        (&options.settings.sandbox, &record.settings.sandbox),
+        (
+            &options.settings.approval_policy,
+            &record.settings.approval_policy,
+        ),
+    ] {
+        if wanted.is_some() && wanted != actual {
+            return false;
+        }
+    }
+    if !options.has.iter().all(|kind| {
+        !record.attachments.is_empty()
+            && (kind == ANY_ATTACHMENT
+                || record
+                    .attachments
+                    .iter()
+                    .any(|attachment| &attachment.kind == kind))
+    }) {
+        return false;
+    }
+    if !options.edited.is_empty() {
+        let edited: HashSet<String> = crate::patch::record_edits(record)
+            .iter()
+            .flat_map(|edit| edit.paths().flat_map(crate::patch::path_suffixes))
+            .collect();
+        if !options.edited.iter().all(|path| edited.contains(path)) {
+            return false;
+        }
+    }
+    if !options.commands.is_empty() {
+        let words: HashSet<String> = crate::shell_history::record_commands(record)
+            .iter()
+            .flat_map(|command| crate::shell_history::command_words(command))
+            .collect();
+        if !options.commands.iter().all(|word| words.contains(word)) {
+            return false;
+        }
+    }
+    if !options.files.is_empty() {
+        let files: HashSet<String> = crate::mentions::record_files(record)
+            .iter()
+            .flat_map(|path| crate::patch::path_suffixes(path))
+            .collect();
+        if !options.files.iter().all(|path| files.contains(path)) {
+            return false;
+        }
+    }
+    true
+}
+
+#[derive(Clone)]
+struct RenderOptions {
+    verbose: bool,
+    /// Query terms that snippets center on and highlight.
+    terms: Vec<String>,
+    matchers: Vec<regex::Regex>,
+    json_array: bool,
+    fields: Option<HashSet<String>>,
+    /// Renders each result's JSON in place of printing it.
+    template: Option<Template>,
+    sort: SortBy,
+    min_score: Option<f32>,
+    top_n_per_session: Option<usize>,
+    limit: usize,
+    /// Link session ids to their transcripts in verbose terminal output.
+    hyperlinks: bool,
+    aliases: ProjectAliases,
+}
+
+#[derive(Serialize)]
+struct MatchSpan {
+    start: usize,
+    end: usize,
+    text: String,
+    before: String,
+    after: String,
+}
+
+#[derive(Serialize)]
+struct SearchHit {
+    score: f32,
+    ts: String,
+    doc_id: u64,
+    project: String,
+    role: String,
+    session_id: String,
+    source: String,
+    source_path: String,
+    text: String,
+    snippet: String,
+    /// Byte ranges of query terms within `snippet`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    snippet_highlights: Vec<(usize, usize)>,
+    matches: Vec<MatchSpan>,
+    #[serde(flatten)]
+    links: RecordLinks,
+    #[serde(flatten)]
+    settings: AgentSettings,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Attachment>,
+    /// Files a patch in the record edits.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    edits: Vec<crate::patch::FileEdit>,
+}
+
+fn render_results(
+    results: Vec<(f32, crate::types::Record)>,
+    render: &RenderOptions,
+    explanations: &HashMap<u64, ScoreExplanation>,
+) -> Result<()> {
+    if render.verbose {
+        let color = std::io::IsTerminal::is_terminal(&std::io::stdout());
+        let links = Hyperlinks::new(color && render.hyperlinks);
+        for (score, record) in results {
+            let ts = format_ts(record.ts);
+            let snippet = text::snippet(&record.text, &render.terms, 200);
+            let text = if color {
+                snippet.marked("\x1b[1m", "\x1b[0m")
             } else {
-                run_index_args(&index, false)?;
+                snippet.text
+            };
+            let project = render.aliases.shown(&record.project);
+            println!(
+                "[{score:.3}] {} {} {} {} {} {}",
+                ts,
+                record.doc_id,
+                project,
+                record.role,
+                links.path(&record.source_path, &record.session_id),
+                text
+            );
+            if let Some(explanation) = explanations.get(&record.doc_id) {
+                for line in explanation.lines() {
+                    println!("    {line}");
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let mut output = Vec::new();
+    for (score, mut record) in results {
+        let explanation = explanations.get(&record.doc_id);
+        record.project = render.aliases.shown(&record.project);
+        let ts = format_ts(record.ts);
+        let text_ref = record.text.as_str();
+        let wants_snippet = wants_field(&render.fields, "snippet")
+            || wants_field(&render.fields, "snippet_highlights");
+        let wants_matches = wants_field(&render.fields, "matches");
+        let wants_text = wants_field(&render.fields, "text");
+        let edits = if wants_field(&render.fields, "edits") {
+            crate::patch::record_edits(&record)
+        } else {
+            Vec::new()
+        };
+        let snippet = if wants_snippet {
+            text::snippet(text_ref, &render.terms, 400)
+        } else {
+            text::Snippet::default()
+        };
+        let matches = if wants_matches {
+            collect_matches(text_ref, &render.matchers, 8)
+        } else {
+            Vec::new()
+        };
+        let text = if wants_text {
+            record.text
+        } else {
+            String::new()
+        };
+
+        let mut value = if let Some(fields) = &render.fields {
+            let mut map = serde_json::Map::new();
+            if fields.contains("score") {
+                map.insert("score".to_string(), Value::from(score));
+            }
+            if fields.contains("ts") {
+                map.insert("ts".to_string(), Value::from(ts));
+            }
+            if fields.contains("doc_id") {
+                map.insert("doc_id".to_string(), Value::from(record.doc_id));
+            }
+            if fields.contains("project") {
+                map.insert("project".to_string(), Value::from(record.project));
+            }
+            if fields.contains("role") {
+                map.insert("role".to_string(), Value::from(record.role));
+            }
+            if fields.contains("session_id") {
+                map.insert("session_id".to_string(), Value::from(record.session_id));
+            }
+            if fields.contains("source") {
+                map.insert("source".to_string(), Value::from(record.source.label()));
+            }
+            insert_optional_field(&mut map, fields, "event_id", &record.links.event_id);
+            insert_optional_field(
+                &mut map,
+                fields,
+                "parent_event_id",
+                &record.links.parent_event_id,
+            );
+            insert_optional_field(
+                &mut map,
+                fields,
+                "logical_parent_event_id",
+                &record.links.logical_parent_event_id,
+            );
+            insert_optional_field(
+                &mut map,
+                fields,
+                "parent_session_id",
+                &record.links.parent_session_id,
+            );
+            insert_optional_field(
+                &mut map,
+                fields,
+                "thread_source",
+                &record.links.thread_source,
+            );
+            insert_optional_field(
+                &mut map,
+                fields,
+                "conversation_kind",
+                &record.links.conversation_kind,
+            );
+            insert_optional_field(&mut map, fields, "thread", &record.links.thread);
+            insert_optional_field(
+                &mut map,
+                fields,
+                "parent_tool_use_id",
+                &record.links.parent_tool_use_id,
+            );
+            insert_optional_field(
+                &mut map,
+                fields,
+                "source_tool_use_id",
+                &record.links.source_tool_use_id,
+            );
+            insert_optional_field(
+                &mut map,
+                fields,
+                "source_tool_assistant_uuid",
+                &record.links.source_tool_assistant_uuid,
+            );
+            insert_optional_field(&mut map, fields, "model", &record.settings.model);
+            insert_optional_field(&mut map, fields, "sandbox", &record.settings.sandbox);
+            insert_optional_field(
+                &mut map,
+                fields,
+                "approval_policy",
+                &record.settings.approval_policy,
+            );
+            if fields.contains("attachments") && !record.attachments.is_empty() {
+                map.insert(
+                    "attachments".to_string(),
+                    serde_json::to_value(&record.attachments)?,
+                );
+            }
+            if fields.contains("edits") && !edits.is_empty() {
+                map.insert("edits".to_string(), serde_json::to_value(&edits)?);
+            }
+            if fields.contains("source_path") {
+                map.insert("source_path".to_string(), Value::from(record.source_path));
+            }
+            if fields.contains("ingested_at") {
+                map.insert("ingested_at".to_string(), Value::from(record.ingested_at));
+            }
+            if fields.contains("text") {
+                map.insert("text".to_string(), Value::from(text));
+            }
+            if fields.contains("snippet_highlights") {
+                map.insert(
+                    "snippet_highlights".to_string(),
+                    serde_json::to_value(&snippet.highlights)?,
+                );
+            }
+            if fields.contains("snippet") {
+                map.insert("snippet".to_string(), Value::from(snippet.text));
+            }
+            if fields.contains("matches") {
+                map.insert("matches".to_string(), serde_json::to_value(matches)?);
+            }
+            Value::Object(map)
+        } else {
+            serde_json::to_value(SearchHit {
+                score,
+                ts,
+                doc_id: record.doc_id,
+                project: record.project,
+                role: record.role,
+                session_id: record.session_id,
+                source: record.source.label().to_string(),
+                source_path: record.source_path,
+                text,
+                snippet: snippet.text,
+                snippet_highlights: snippet.highlights,
+                matches,
+                links: record.links,
+                settings: record.settings,
+                attachments: record.attachments,
+                edits,
+            })?
+        };
+        if let Some(explanation) = explanation
+            && wants_field(&render.fields, "explain")
+            && let Value::Object(map) = &mut value
+        {
+            map.insert("explain".to_string(), serde_json::to_value(explanation)?);
+        }
+        if render.json_array {
+            output.push(value);
+        } else if let Some(template) = &render.template {
+            template.write(&mut std::io::stdout().lock(), &value)?;
+        } else {
+            println!("{}", serde_json::to_string(&value)?);
+        }
+    }
+
+    if render.json_array {
+        println!("{}", serde_json::to_string(&output)?);
+    }
+    Ok(())
+}
+
+fn insert_optional_field(
+    map: &mut serde_json::Map<String, Value>,
+    fields: &HashSet<String>,
+    name: &str,
+    value: &Option<String>,
+) {
+    if fields.contains(name)
+        && let Some(value) = value
+    {
+        map.insert(name.to_string(), Value::from(value.clone()));
+    }
+}
+
+fn run_session(session_id: String, verbose: bool, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let mut records = index.records_by_session_id(&session_id)?;
+    records.sort_by(|a, b| {
+        a.turn_id
+            .cmp(&b.turn_id)
+            .then_with(|| a.ts.cmp(&b.ts))
+            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    });
+    if verbose {
+        for record in records {
+            let ts = format_ts(record.ts);
+            println!("{ts} {}", record.role);
+            if record.text.is_empty() {
+                println!("  <empty>");
+                continue;
+            }
+            for line in record.text.lines() {
+                println!("  {line}");
             }
         }
-        Commands::Reindex { index } => {
-            run_index_args(&index, true)?;
+        return Ok(());
+    }
+    for record in records {
+        println!("{}", serde_json::to_string(&record)?);
+    }
+    Ok(())
+}
+
+fn run_history(
+    project: String,
+    since: Option<String>,
+    until: Option<String>,
+    no_tools: bool,
+    limit: usize,
+    verbose: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    const DEFAULT_WINDOW_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let project = aliases.resolve(&project);
+    let until = parse_ts_millis(until)?.unwrap_or_else(now_millis);
+    let since = parse_ts_millis(since)?.unwrap_or(until.saturating_sub(DEFAULT_WINDOW_MS));
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let mut records = index.records_by_project(&project, since, until, limit)?;
+    if no_tools {
+        records.retain(|record| !matches!(record.role.as_str(), "tool_use" | "tool_result"));
+    }
+    if !verbose {
+        for record in records {
+            println!("{}", serde_json::to_string(&record)?);
         }
-        Commands::Embed { model, root } => {
-            run_embed(model, root)?;
+        return Ok(());
+    }
+    if records.is_empty() {
+        println!(
+            "no records in {project} between {} and {}",
+            format_ts(since),
+            format_ts(until)
+        );
+        return Ok(());
+    }
+    let mut current: Option<String> = None;
+    for record in records {
+        if current.as_deref() != Some(record.session_id.as_str()) {
+            if current.is_some() {
+                println!();
+            }
+            println!(
+                "== session {} ({})",
+                record.session_id,
+                record.source.label()
+            );
+            current = Some(record.session_id.clone());
         }
-        Commands::Search {
+        println!("{} {}", format_ts(record.ts), record.role);
+        if record.text.is_empty() {
+            println!("  <empty>");
+            continue;
+        }
+        for line in record.text.lines() {
+            println!("  {line}");
+        }
+    }
+    Ok(())
+}
+
+/// A transcript file behind a session, from `memex where`.
+#[derive(Debug, Serialize)]
+struct SessionSourceFile {
+    path: String,
+    /// `None` when the file no longer exists.
+    size: Option<u64>,
+    /// Byte range of the lines mentioning the session, end exclusive.
+    byte_start: u64,
+    byte_end: u64,
+    /// Bytes ingest has read so far.
+    indexed_bytes: Option<u64>,
+    records: usize,
+    first_turn: u32,
+    last_turn: u32,
+    first_ts: u64,
+    last_ts: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SessionSources {
+    session_id: String,
+    source: String,
+    project: String,
+    files: Vec<SessionSourceFile>,
+}
+
+fn run_where(target: &str, json: bool, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let mut records = index.records_by_session_id(target)?;
+    if records.is_empty() {
+        let (query, filters) = extract_query_filters(target);
+        let hits = index.search(&QueryOptions {
             query,
-            project,
-            role,
-            tool,
-            session,
-            source,
-            semantic,
-            hybrid,
-            min_score,
-            recency_weight,
-            recency_half_life_days,
-            since,
-            until,
-            limit,
-            top_n_per_session,
-            unique_session,
-            json_array,
-            fields,
-            sort,
-            verbose,
-            root,
-        } => {
-            run_search(
-                query,
-                project,
-                role,
-                tool,
-                session,
-                source,
-                semantic,
-                hybrid,
-                min_score,
-                recency_weight,
-                recency_half_life_days,
-                since,
-                until,
-                limit,
-                top_n_per_session,
-                unique_session,
-                json_array,
-                fields,
-                sort,
-                verbose,
-                root,
-            )?;
+            project: None,
+            role: filters.role,
+            tool: None,
+            session_id: None,
+            source: None,
+            since: filters.after,
+            until: filters.before,
+            as_of: None,
+            settings: filters.settings,
+            has: filters.has,
+            edited: filters.edited,
+            commands: filters.commands,
+            files: filters.files,
+            langs: filters.langs,
+            errors: filters.errors,
+            require_all_terms: false,
+            fuzzy_distance: 0,
+            tuning: UserConfig::load(&paths)?.query_tuning()?,
+            limit: 1,
+        })?;
+        let Some((_, hit)) = hits.into_iter().next() else {
+            return Err(anyhow!("no session or search match for '{target}'"));
+        };
+        records = index.records_by_session_id(&hit.session_id)?;
+    }
+    let first = &records[0];
+    let session_id = first.session_id.clone();
+    let mut sources = SessionSources {
+        session_id: session_id.clone(),
+        source: first.source.storage_label().to_string(),
+        project: first.project.clone(),
+        files: Vec::new(),
+    };
+
+    let ingest = IngestState::load(&paths.state.join("ingest.json"))?;
+    let mut by_path: std::collections::BTreeMap<&str, Vec<&crate::types::Record>> =
+        std::collections::BTreeMap::new();
+    for record in &records {
+        by_path
+            .entry(record.source_path.as_str())
+            .or_default()
+            .push(record);
+    }
+    for (path, records) in by_path {
+        let size = std::fs::metadata(path).ok().map(|meta| meta.len());
+        let (byte_start, byte_end) = match size {
+            Some(size) => {
+                session_byte_range(std::path::Path::new(path), &session_id)?.unwrap_or((0, size))
+            }
+            None => (0, 0),
+        };
+        sources.files.push(SessionSourceFile {
+            path: path.to_string(),
+            size,
+            byte_start,
+            byte_end,
+            indexed_bytes: ingest.files.get(path).map(|file| file.offset),
+            records: records.len(),
+            first_turn: records.iter().map(|r| r.turn_id).min().unwrap_or(0),
+            last_turn: records.iter().map(|r| r.turn_id).max().unwrap_or(0),
+            first_ts: records.iter().map(|r| r.ts).min().unwrap_or(0),
+            last_ts: records.iter().map(|r| r.ts).max().unwrap_or(0),
+        });
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&sources)?);
+        return Ok(());
+    }
+    let links = Hyperlinks::for_stdout(&UserConfig::load(&paths)?);
+    let project = aliases.shown(&sources.project);
+    println!("{} ({}, {project})", sources.session_id, sources.source);
+    for file in &sources.files {
+        println!("  {}", links.path(&file.path, &file.path));
+        let Some(size) = file.size else {
+            println!("    missing; {} records indexed from it", file.records);
+            continue;
+        };
+        let indexed = file
+            .indexed_bytes
+            .map(|bytes| format!(", indexed through byte {bytes}"))
+            .unwrap_or_default();
+        println!(
+            "    bytes {}-{} of {size}{indexed}",
+            file.byte_start, file.byte_end
+        );
+        println!(
+            "    {} records, turns {}-{}, {} to {}",
+            file.records,
+            file.first_turn,
+            file.last_turn,
+            format_ts(file.first_ts),
+            format_ts(file.last_ts)
+        );
+    }
+    Ok(())
+}
+
+/// A session with records around the moment `memex at` asked about.
+#[derive(Debug, Serialize)]
+struct ActiveSession {
+    session_id: String,
+    project: String,
+    source: String,
+    first_ts: u64,
+    last_ts: u64,
+    records: usize,
+    /// From the moment to the session's nearest record; 0 when the moment
+    /// falls inside the session.
+    distance_ms: u64,
+    /// The nearest prompt or reply, else the nearest record of any kind.
+    nearest: crate::types::Record,
+}
+
+fn run_at(
+    when: &str,
+    window: Option<u64>,
+    limit: usize,
+    json: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    const DEFAULT_WINDOW_MINUTES: i64 = 60;
+    const RECORD_LIMIT: usize = 20_000;
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let moment = crate::moment::parse_moment(when, chrono::Local::now())?;
+    let half = match window {
+        Some(minutes) => chrono::Duration::minutes(minutes as i64),
+        None => moment
+            .span
+            .unwrap_or(chrono::Duration::minutes(DEFAULT_WINDOW_MINUTES)),
+    };
+    let at = moment.at.timestamp_millis().max(0) as u64;
+    let half_ms = half.num_milliseconds().max(0) as u64;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let records = index.records_between(
+        at.saturating_sub(half_ms),
+        at.saturating_add(half_ms),
+        RECORD_LIMIT,
+    )?;
+    let sessions = active_sessions(records, at, limit);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+        return Ok(());
+    }
+    let local = |ts: u64, format: &str| {
+        chrono::DateTime::<chrono::Utc>::from_timestamp_millis(ts as i64)
+            .map(|dt| dt.with_timezone(&chrono::Local).format(format).to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    println!(
+        "around {} (±{}m)",
+        local(at, "%a %Y-%m-%d %H:%M"),
+        half.num_minutes()
+    );
+    if sessions.is_empty() {
+        println!("no sessions");
+        return Ok(());
+    }
+    for session in &sessions {
+        let when = match session.distance_ms {
+            0 => "at the time".to_string(),
+            distance if session.last_ts < at => format!("{}m before", distance / 60_000),
+            distance => format!("{}m after", distance / 60_000),
+        };
+        let project = aliases.shown(&session.project);
+        println!(
+            "{}  {project}  {}  {}-{}  {} records  {when}",
+            session.session_id,
+            session.source,
+            local(session.first_ts, "%H:%M"),
+            local(session.last_ts, "%H:%M"),
+            session.records,
+        );
+        println!(
+            "  {} {}: {}",
+            local(session.nearest.ts, "%H:%M"),
+            session.nearest.role,
+            summarize(&session.nearest.text, 100)
+        );
+    }
+    Ok(())
+}
+
+/// Groups `records` by session, nearest to `at` first.
+fn active_sessions(
+    records: Vec<crate::types::Record>,
+    at: u64,
+    limit: usize,
+) -> Vec<ActiveSession> {
+    let mut by_session: HashMap<String, Vec<crate::types::Record>> = HashMap::new();
+    for record in records {
+        by_session
+            .entry(record.session_id.clone())
+            .or_default()
+            .push(record);
+    }
+    let mut sessions: Vec<ActiveSession> = by_session
+        .into_iter()
+        .filter_map(|(session_id, records)| {
+            let first_ts = records.iter().map(|r| r.ts).min()?;
+            let last_ts = records.iter().map(|r| r.ts).max()?;
+            let distance_ms = if (first_ts..=last_ts).contains(&at) {
+                0
+            } else {
+                first_ts.abs_diff(at).min(last_ts.abs_diff(at))
+            };
+            let count = records.len();
+            let nearest = records
+                .iter()
+                .filter(|r| matches!(r.role.as_str(), "user" | "assistant") && !r.text.is_empty())
+                .min_by_key(|r| r.ts.abs_diff(at))
+                .or_else(|| records.iter().min_by_key(|r| r.ts.abs_diff(at)))?
+                .clone();
+            Some(ActiveSession {
+                session_id,
+                project: nearest.project.clone(),
+                source: nearest.source.label().to_string(),
+                first_ts,
+                last_ts,
+                records: count,
+                distance_ms,
+                nearest,
+            })
+        })
+        .collect();
+    sessions.sort_by(|a, b| {
+        a.distance_ms
+            .cmp(&b.distance_ms)
+            .then_with(|| a.nearest.ts.abs_diff(at).cmp(&b.nearest.ts.abs_diff(at)))
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    sessions.truncate(limit);
+    sessions
+}
+
+/// Byte range from the start of the first line mentioning `session_id` to
+/// the end of the last, or `None` when no line does (e.g. a file per session
+/// that never repeats its id).
+fn session_byte_range(path: &std::path::Path, session_id: &str) -> Result<Option<(u64, u64)>> {
+    use std::io::BufRead;
+
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut line = Vec::new();
+    let mut offset = 0u64;
+    let mut range: Option<(u64, u64)> = None;
+    let needle = session_id.as_bytes();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)? as u64;
+        if read == 0 {
+            break;
+        }
+        if !needle.is_empty() && line.windows(needle.len()).any(|window| window == needle) {
+            let start = range.map_or(offset, |(start, _)| start);
+            range = Some((start, offset + read));
+        }
+        offset += read;
+    }
+    Ok(range)
+}
+
+fn run_embeddings_export(
+    output: PathBuf,
+    format: Option<EmbeddingsFormat>,
+    source: Option<SourceFilter>,
+    project: Option<String>,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let project = project.map(|project| aliases.resolve(&project));
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let vectors = VectorIndex::open(&paths.vectors)
+        .map_err(|_| anyhow!("no embeddings yet; run `memex embed` first"))?;
+    let format = match format {
+        Some(EmbeddingsFormat::Parquet) => EmbeddingFormat::Parquet,
+        Some(EmbeddingsFormat::Npy) => EmbeddingFormat::Npy,
+        None => EmbeddingFormat::from_path(&output),
+    };
+
+    let mut export = EmbeddingExport::create(&output, format, vectors.dimensions())?;
+    index.for_each_record(|record| {
+        if source.is_some_and(|source| !source.matches(record.source))
+            || project
+                .as_ref()
+                .is_some_and(|project| *project != record.project)
+        {
+            return Ok(());
+        }
+        let Some(vector) = vectors.get(record.doc_id)? else {
+            return Ok(());
+        };
+        export.push(EmbeddingRow {
+            doc_id: record.doc_id,
+            session_id: record.session_id,
+            project: aliases.shown(&record.project),
+            ts: record.ts,
+            vector,
+        })
+    })?;
+    let rows = export.finish()?;
+
+    println!(
+        "wrote {rows} embeddings ({} dimensions) to {}",
+        vectors.dimensions(),
+        output.display()
+    );
+    if format == EmbeddingFormat::Npy {
+        println!("row metadata: {}", npy_metadata_path(&output).display());
+    }
+    Ok(())
+}
+
+fn run_graph(session_id: String, format: GraphFormat, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let mut records = index.records_by_session_id(&session_id)?;
+    if records.is_empty() {
+        return Err(anyhow!("session not found: {session_id}"));
+    }
+    records.sort_by(|a, b| {
+        a.turn_id
+            .cmp(&b.turn_id)
+            .then_with(|| a.ts.cmp(&b.ts))
+            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    });
+    let graph = ConversationGraph::from_records(&records);
+    let output = match format {
+        GraphFormat::Mermaid => graph.to_mermaid(),
+        GraphFormat::Dot => graph.to_dot(),
+    };
+    print!("{output}");
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SessionListEntry {
+    session_id: String,
+    source: String,
+    project: String,
+    last_ts: u64,
+    message_count: u64,
+    title: Option<String>,
+    source_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<Outcome>,
+}
+
+fn run_cmds(
+    words: Option<String>,
+    project: Option<String>,
+    source: Option<SourceFilter>,
+    since: Option<String>,
+    limit: usize,
+    json: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let records = index.records_with_commands(&QueryOptions {
+        query: String::new(),
+        project: project.map(|project| aliases.resolve(&project)),
+        role: None,
+        tool: None,
+        session_id: None,
+        source,
+        since: parse_ts_millis(since)?,
+        until: None,
+        as_of: None,
+        settings: AgentSettings::default(),
+        has: Vec::new(),
+        edited: Vec::new(),
+        commands: words
+            .as_deref()
+            .map(crate::shell_history::command_words)
+            .unwrap_or_default(),
+        files: Vec::new(),
+        langs: Vec::new(),
+        errors: Vec::new(),
+        require_all_terms: false,
+        fuzzy_distance: 0,
+        tuning: QueryTuning::default(),
+        limit,
+    })?;
+    let counts = crate::shell_history::count_commands(&records);
+    let mut stdout = std::io::stdout().lock();
+    if json {
+        for count in counts.iter().take(limit) {
+            writeln!(stdout, "{}", serde_json::to_string(count)?)?;
+        }
+        return Ok(());
+    }
+    if counts.is_empty() {
+        writeln!(stdout, "no commands found")?;
+        return Ok(());
+    }
+    let width = counts[0].count.to_string().len();
+    for count in counts.iter().take(limit) {
+        // Heredocs and multi-line scripts fold onto one line.
+        writeln!(
+            stdout,
+            "{:>width$}  {}  {}",
+            count.count,
+            format_ts(count.last_ts),
+            summarize(&count.command, 160)
+        )?;
+    }
+    Ok(())
+}
+
+fn run_errors(
+    query: Option<String>,
+    project: Option<String>,
+    source: Option<SourceFilter>,
+    since: Option<String>,
+    limit: usize,
+    json: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let (text, mut filters) = extract_query_filters(query.as_deref().unwrap_or_default());
+    let fingerprint = crate::errors::is_fingerprint(&text).then(|| text.to_lowercase());
+    if let Some(fingerprint) = &fingerprint {
+        filters.errors.push(fingerprint.clone());
+    }
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let since = filters.since(parse_ts_millis(since)?);
+    let records = index.records_with_errors(&QueryOptions {
+        query: if fingerprint.is_some() {
+            String::new()
+        } else {
+            text.clone()
+        },
+        project: project.map(|project| aliases.resolve(&project)),
+        role: filters.role,
+        tool: None,
+        session_id: None,
+        source,
+        since,
+        until: filters.before,
+        as_of: None,
+        settings: filters.settings,
+        has: filters.has,
+        edited: filters.edited,
+        commands: filters.commands,
+        files: filters.files,
+        langs: filters.langs,
+        errors: filters.errors,
+        require_all_terms: false,
+        fuzzy_distance: 0,
+        tuning: QueryTuning::default(),
+        limit,
+    })?;
+    let mut stdout = std::io::stdout().lock();
+    if let Some(fingerprint) = fingerprint {
+        return print_error_occurrences(&mut stdout, &index, records, &fingerprint, limit, json);
+    }
+
+    // The text narrowed the search to outputs containing it; keep the errors
+    // whose own message does.
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    let counts: Vec<_> = crate::errors::count_errors(&records)
+        .into_iter()
+        .filter(|count| {
+            let message = count.message.to_lowercase();
+            words.iter().all(|word| message.contains(word.as_str()))
+        })
+        .take(limit)
+        .collect();
+    if json {
+        for count in &counts {
+            writeln!(stdout, "{}", serde_json::to_string(count)?)?;
         }
-        Commands::Tui { root } => {
-            let (update_tx, update_rx) = std::sync::mpsc::channel();
-            check_for_update_async(Some(update_tx));
-            tui::run(root, Some(update_rx))?;
+        return Ok(());
+    }
+    if counts.is_empty() {
+        writeln!(stdout, "no errors found")?;
+        return Ok(());
+    }
+    let width = counts[0].count.to_string().len();
+    for count in &counts {
+        writeln!(
+            stdout,
+            "{:>width$}  {}  {}  {:<9} {}",
+            count.count,
+            format_ts(count.last_ts),
+            count.fingerprint,
+            count.kind,
+            summarize(&count.message, 120)
+        )?;
+    }
+    writeln!(
+        stdout,
+        "\nsee the sessions that hit one and what came next: memex errors <fingerprint>"
+    )?;
+    Ok(())
+}
+
+/// Each session that hit the error, newest first, with what the agent said
+/// after its last hit.
+fn print_error_occurrences(
+    out: &mut impl Write,
+    index: &SearchIndex,
+    records: Vec<crate::types::Record>,
+    fingerprint: &str,
+    limit: usize,
+    json: bool,
+) -> Result<()> {
+    let mut sessions: Vec<String> = records
+        .into_iter()
+        .map(|record| record.session_id)
+        .collect();
+    sessions.sort();
+    sessions.dedup();
+    let mut occurrences = Vec::new();
+    for session_id in sessions {
+        let mut records = index.records_by_session_id(&session_id)?;
+        records.sort_by(|a, b| {
+            a.turn_id
+                .cmp(&b.turn_id)
+                .then_with(|| a.ts.cmp(&b.ts))
+                .then_with(|| a.doc_id.cmp(&b.doc_id))
+        });
+        occurrences.extend(crate::errors::session_occurrence(&records, fingerprint));
+    }
+    occurrences.sort_by_key(|occurrence| std::cmp::Reverse(occurrence.ts));
+    occurrences.truncate(limit);
+    if json {
+        for occurrence in &occurrences {
+            writeln!(out, "{}", serde_json::to_string(occurrence)?)?;
         }
-        Commands::IndexService { action } => match action {
-            IndexServiceCommand::Enable {
-                index,
-                label,
-                continuous,
-                poll_interval,
-                interval,
-                stdout,
-                stderr,
-                plist,
-                systemd_dir,
-            } => {
-                run_index_service_enable(
-                    &index,
-                    label,
-                    continuous,
-                    poll_interval,
-                    interval,
-                    stdout,
-                    stderr,
-                    plist,
-                    systemd_dir,
-                )?;
-            }
-            IndexServiceCommand::Disable {
-                label,
-                plist,
-                systemd_dir,
-                root,
-            } => {
-                run_index_service_disable(label, plist, systemd_dir, root)?;
+        return Ok(());
+    }
+    let Some(latest) = occurrences.first() else {
+        writeln!(out, "no error {fingerprint} found")?;
+        return Ok(());
+    };
+    writeln!(out, "{}\n", summarize(&latest.message, 200))?;
+    for occurrence in &occurrences {
+        writeln!(
+            out,
+            "{}  {:<8} {}  {}  {} hit{}",
+            format_ts(occurrence.ts),
+            occurrence.source,
+            occurrence.project,
+            occurrence.session_id,
+            occurrence.hits,
+            if occurrence.hits == 1 { "" } else { "s" }
+        )?;
+        if let Some(next) = &occurrence.next {
+            writeln!(out, "  next: {}", summarize(next, 200))?;
+        }
+    }
+    Ok(())
+}
+
+fn run_files(
+    path: String,
+    project: Option<String>,
+    source: Option<SourceFilter>,
+    since: Option<String>,
+    limit: usize,
+    json: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let file = crate::mentions::normalize_file(&path);
+    if file.is_empty() {
+        return Err(anyhow!("no file path given"));
+    }
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let records = index.records_mentioning_files(&QueryOptions {
+        query: String::new(),
+        project: project.map(|project| aliases.resolve(&project)),
+        role: None,
+        tool: None,
+        session_id: None,
+        source,
+        since: parse_ts_millis(since)?,
+        until: None,
+        as_of: None,
+        settings: AgentSettings::default(),
+        has: Vec::new(),
+        edited: Vec::new(),
+        commands: Vec::new(),
+        files: vec![file.clone()],
+        langs: Vec::new(),
+        errors: Vec::new(),
+        require_all_terms: false,
+        fuzzy_distance: 0,
+        tuning: QueryTuning::default(),
+        limit,
+    })?;
+    let sessions = crate::mentions::sessions_touching(&records, &file);
+    let mut stdout = std::io::stdout().lock();
+    if json {
+        for session in sessions.iter().take(limit) {
+            writeln!(stdout, "{}", serde_json::to_string(session)?)?;
+        }
+        return Ok(());
+    }
+    if sessions.is_empty() {
+        writeln!(stdout, "no sessions mention {file}")?;
+        return Ok(());
+    }
+    for session in sessions.iter().take(limit) {
+        writeln!(
+            stdout,
+            "{}  {:<8} {}  {}  {} mention{}{}",
+            format_ts(session.last_ts),
+            session.source,
+            session.project,
+            session.session_id,
+            session.mentions,
+            if session.mentions == 1 { "" } else { "s" },
+            if session.edited { ", edited" } else { "" }
+        )?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_sessions(
+    source: Option<SourceFilter>,
+    project: Option<String>,
+    topic: Option<String>,
+    outcome: Option<Outcome>,
+    since: Option<String>,
+    limit: usize,
+    json: bool,
+    template: Option<String>,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let config = UserConfig::load(&paths)?;
+    let template = if json {
+        None
+    } else {
+        output_template(&config, "sessions", template.as_deref())?
+    };
+    let project = project.map(|project| aliases.resolve(&project));
+    let since_ms = parse_ts_millis(since)?;
+    let store = open_session_store(&paths)?;
+    let topics = Topics::load(&topics_path(&paths.state))?;
+    let in_topic: Option<HashSet<String>> = match &topic {
+        Some(name) => {
+            let topic = topics.find(name).ok_or_else(|| {
+                anyhow!("unknown topic: {name} (run `memex topics` to list them)")
+            })?;
+            Some(
+                topic
+                    .sessions
+                    .iter()
+                    .map(|session| session.session_id.clone())
+                    .collect(),
+            )
+        }
+        None => None,
+    };
+    let labels = topics.labels_by_session();
+    let outcomes = SessionOutcomes::load(&outcomes_path(&paths.state))?;
+    let rows = store.query_sessions(
+        source,
+        since_ms,
+        project.as_deref(),
+        crate::analytics::ProjectGrouping::Flat,
+        // Topics and outcomes aren't in the database, so filter after the query.
+        (in_topic.is_none() && outcome.is_none()).then_some(limit),
+    )?;
+    let rows = rows
+        .into_iter()
+        .filter(|row| {
+            in_topic
+                .as_ref()
+                .is_none_or(|sessions| sessions.contains(&row.session_id))
+                && outcome.is_none_or(|outcome| outcomes.get(&row.session_id) == Some(outcome))
+        })
+        .take(limit);
+    let links = Hyperlinks::for_stdout(&config);
+    let mut stdout = std::io::stdout().lock();
+    for row in rows {
+        let topic = labels.get(&row.session_id).cloned();
+        let outcome = outcomes.get(&row.session_id);
+        if json || template.is_some() {
+            let entry = SessionListEntry {
+                session_id: row.session_id,
+                source: row.source.label().to_string(),
+                project: row.display_project,
+                last_ts: row.last_at,
+                message_count: row.message_count,
+                title: row.title,
+                source_path: row.source_path,
+                topic,
+                outcome,
+            };
+            match &template {
+                Some(template) => template.write(&mut stdout, &serde_json::to_value(&entry)?)?,
+                None => writeln!(stdout, "{}", serde_json::to_string(&entry)?)?,
             }
-        },
-        Commands::Session {
-            session_id,
-            verbose,
-            root,
-        } => {
-            run_session(session_id, verbose, root)?;
+            continue;
         }
-        Commands::Show {
-            doc_id,
-            verbose,
-            root,
-        } => {
-            run_show(doc_id, verbose, root)?;
+        let title = match outcome {
+            Some(outcome) => format!(
+                "[{}] {}",
+                outcome.label(),
+                row.title.as_deref().unwrap_or("(untitled)")
+            ),
+            None => row.title.unwrap_or_else(|| "(untitled)".to_string()),
+        };
+        // The project opens its working directory, the id the transcript.
+        let project = match &row.cwd {
+            Some(cwd) => links.path(cwd, &row.display_project),
+            None => row.display_project,
+        };
+        writeln!(
+            stdout,
+            "{}  {:<8} {}  {}\n    {}",
+            format_ts(row.last_at),
+            row.source.label(),
+            project,
+            title,
+            links.path(&row.source_path, &row.session_id)
+        )?;
+    }
+    Ok(())
+}
+
+/// The analytics database for session listings, built from the index first
+/// when it is missing or was interrupted.
+fn open_session_store(paths: &Paths) -> Result<AnalyticsStore> {
+    let db = analytics_path(&paths.state);
+    if !AnalyticsStore::is_complete(&db) {
+        let index = SearchIndex::open_or_create(&paths.index)?;
+        if index.doc_count()? == 0 {
+            return Err(anyhow!("no sessions indexed; run `memex index` first"));
         }
-        Commands::Stats { root } => {
-            run_stats(root)?;
+        backfill_from_index(&db, &index)?;
+    }
+    Ok(AnalyticsStore::open_read_only(&db)?.with_project_aliases(paths.project_aliases()?))
+}
+
+/// Pickers tried in order when `memex pick` runs without `--print`.
+const PICKERS: [&str; 2] = ["fzf", "sk"];
+
+/// Lists sessions for a fuzzy picker and prints the chosen ID. Returns false
+/// when nothing was picked.
+fn run_pick(
+    query: Option<String>,
+    source: Option<SourceFilter>,
+    project: Option<String>,
+    since: Option<String>,
+    limit: usize,
+    print: bool,
+    root: Option<PathBuf>,
+) -> Result<bool> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let project = project.map(|project| aliases.resolve(&project));
+    let since_ms = parse_ts_millis(since)?;
+    let store = open_session_store(&paths)?;
+    let query = query.filter(|query| !query.trim().is_empty());
+    let rows = store.query_sessions(
+        source,
+        since_ms,
+        project.as_deref(),
+        crate::analytics::ProjectGrouping::Flat,
+        query.is_none().then_some(limit),
+    )?;
+    let lines: Vec<String> = match &query {
+        None => rows
+            .iter()
+            .map(|row| {
+                pick_line(
+                    &row.session_id,
+                    row.title.as_deref().unwrap_or("(untitled)"),
+                    &row.display_project,
+                )
+            })
+            .collect(),
+        Some(query) => {
+            let rows: HashMap<&str, &crate::analytics::SessionRow> = rows
+                .iter()
+                .map(|row| (row.session_id.as_str(), row))
+                .collect();
+            let (text, filters) = extract_query_filters(query);
+            let since_ms = filters.since(since_ms);
+            let index = SearchIndex::open_or_create(&paths.index)?;
+            let hits = index.search(&QueryOptions {
+                query: text,
+                project: project.clone(),
+                role: filters.role,
+                tool: None,
+                session_id: None,
+                source,
+                since: since_ms,
+                until: filters.before,
+                as_of: None,
+                settings: filters.settings,
+                has: filters.has,
+                edited: filters.edited,
+                commands: filters.commands,
+                files: filters.files,
+                langs: filters.langs,
+                errors: filters.errors,
+                require_all_terms: false,
+                fuzzy_distance: 0,
+                tuning: UserConfig::load(&paths)?.query_tuning()?,
+                limit: (limit * 5).max(limit + 10),
+            })?;
+            best_per_session(hits)
+                .into_iter()
+                .filter_map(|(_, record)| {
+                    let row = rows.get(record.session_id.as_str())?;
+                    let title = row.title.as_deref().unwrap_or(&record.text);
+                    Some(pick_line(&row.session_id, title, &row.display_project))
+                })
+                .take(limit)
+                .collect()
         }
-        Commands::Usage {
-            source,
-            since,
-            until,
-            json,
-            events,
-            cost,
-            root,
-        } => {
-            run_usage(source, since, until, json, events, cost, root)?;
+    };
+    if lines.is_empty() {
+        eprintln!("no matching sessions");
+        return Ok(false);
+    }
+    if print {
+        let mut stdout = std::io::stdout().lock();
+        for line in &lines {
+            writeln!(stdout, "{line}")?;
         }
-        Commands::AnalyticsBackfill { root } => {
-            run_analytics_backfill(root)?;
+        return Ok(true);
+    }
+
+    let picker = PICKERS
+        .iter()
+        .find_map(|name| find_in_path(name))
+        .ok_or_else(|| {
+            anyhow!("install fzf or skim to pick interactively, or pipe `memex pick --print` into a picker")
+        })?;
+    let mut child = std::process::Command::new(picker)
+        .args(["--delimiter", "\t", "--with-nth", "2..", "--no-sort"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The picker may exit before reading everything; that is a choice,
+        // not an error.
+        for line in &lines {
+            if writeln!(stdin, "{line}").is_err() {
+                break;
+            }
         }
-        Commands::Setup { force } => {
-            run_setup(force)?;
+    }
+    let output = child.wait_with_output()?;
+    let picked = String::from_utf8_lossy(&output.stdout);
+    let Some(session_id) = picked
+        .lines()
+        .next()
+        .and_then(|line| line.split('\t').next())
+        .filter(|id| !id.is_empty())
+    else {
+        return Ok(false);
+    };
+    println!("{session_id}");
+    Ok(true)
+}
+
+/// One `session_id<TAB>title<TAB>project` picker line; tabs and newlines in
+/// the title would break the columns, so it is collapsed to one line.
+fn pick_line(session_id: &str, title: &str, project: &str) -> String {
+    format!("{session_id}\t{}\t{project}", summarize(title, 120))
+}
+
+fn run_knowledge(
+    project: String,
+    output: Option<PathBuf>,
+    min_sessions: usize,
+    limit: usize,
+    rebuild: bool,
+    no_embeddings: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let config = UserConfig::load(&paths)?;
+    let project = aliases.resolve(&project);
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let model = if config.embeddings_default() && !no_embeddings {
+        Some(config.resolve_model(None)?)
+    } else {
+        None
+    };
+    let method = model.map_or_else(|| "lexical".to_string(), |model| model.as_str().to_string());
+    let path = knowledge_path(&paths.state, &project);
+    let mut knowledge = Knowledge::load(&path)?;
+    // Entries merged another way can't take new statements.
+    if rebuild || knowledge.project != project || knowledge.method != method {
+        knowledge = Knowledge {
+            project: project.clone(),
+            method,
+            ..Knowledge::default()
+        };
+    }
+    let read = match model {
+        Some(model) => {
+            let mut embedder =
+                EmbedderHandle::with_model_and_runtime(model, &config.resolve_embed_runtime()?)?;
+            let mut embed = |texts: &[&str]| embedder.embed_texts(texts);
+            refresh_knowledge(&index, &mut knowledge, Some(&mut embed))?
         }
-        Commands::Update { yes } => {
-            run_update(yes)?;
+        None => refresh_knowledge(&index, &mut knowledge, None)?,
+    };
+    knowledge.save(&path)?;
+    let outline = knowledge.outline(min_sessions, limit);
+    match output {
+        Some(output) => {
+            std::fs::write(&output, outline)?;
+            eprintln!(
+                "read {read} new statements, {} saved; wrote {}",
+                knowledge.entries.len(),
+                output.display()
+            );
         }
-        Commands::Share {
-            session_id,
-            title,
-            root,
-        } => {
-            run_share(session_id, title, root)?;
+        None => print!("{outline}"),
+    }
+    Ok(())
+}
+
+fn run_topics(
+    k: Option<usize>,
+    source: Option<SourceFilter>,
+    project: Option<String>,
+    limit: usize,
+    json: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let project = project.map(|project| aliases.resolve(&project));
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let vectors = VectorIndex::open(&paths.vectors)
+        .map_err(|_| anyhow!("no embeddings yet; run `memex embed` first"))?;
+    let options = TopicOptions { k, source, project };
+    let topics = cluster_sessions(&index, &vectors, &options)?;
+    if topics.topics.is_empty() {
+        return Err(anyhow!("no embedded sessions to cluster"));
+    }
+    topics.save(&topics_path(&paths.state))?;
+
+    let titles: HashMap<String, String> =
+        AnalyticsStore::open_read_only(analytics_path(&paths.state))
+            .and_then(|store| {
+                store.query_sessions(
+                    source,
+                    None,
+                    None,
+                    crate::analytics::ProjectGrouping::Flat,
+                    None,
+                )
+            })
+            .map(|rows| {
+                rows.into_iter()
+                    .filter_map(|row| Some((row.session_id, row.title?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+    let shown = |count: usize| if limit == 0 { count } else { limit.min(count) };
+    let mut stdout = std::io::stdout().lock();
+    for topic in &topics.topics {
+        if json {
+            let mut topic = topic.clone();
+            topic.sessions.truncate(shown(topic.sessions.len()));
+            for session in &mut topic.sessions {
+                session.project = aliases.shown(&session.project);
+            }
+            writeln!(stdout, "{}", serde_json::to_string(&topic)?)?;
+            continue;
         }
-        Commands::Transfer {
-            session_id,
-            source,
-            to,
-            mode,
-            turns,
-            dry_run,
-            root,
-        } => {
-            run_transfer(session_id, source, to, mode, turns, dry_run, root)?;
+        writeln!(
+            stdout,
+            "#{} {} ({} sessions)",
+            topic.id,
+            topic.label,
+            topic.sessions.len()
+        )?;
+        for session in topic.sessions.iter().take(shown(topic.sessions.len())) {
+            let title = titles
+                .get(&session.session_id)
+                .map(String::as_str)
+                .unwrap_or(session.session_id.as_str());
+            writeln!(
+                stdout,
+                "    {}  {} {}",
+                format_ts(session.last_ts),
+                pad_end(&aliases.shown(&session.project), 12),
+                summarize(title, 80)
+            )?;
         }
     }
     Ok(())
 }
 
-fn run_index_loop(index: &IndexArgs, interval_secs: u64) -> Result<()> {
+/// Most records printed per poll; bursts larger than this (a full reindex)
+/// only show their newest records.
+const TAIL_BATCH: usize = 500;
+
+fn run_tail(
+    source: Option<SourceFilter>,
+    project: Option<String>,
+    lines: usize,
+    interval: u64,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    let project = project.map(|project| aliases.resolve(&project));
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let color = std::io::IsTerminal::is_terminal(&std::io::stdout());
+    let matches = |record: &crate::types::Record| {
+        source.is_none_or(|source| source.matches(record.source))
+            && project
+                .as_deref()
+                .is_none_or(|project| record.project == project)
+    };
+
+    // Start from the newest record so only the requested backlog is printed.
+    let mut cursor = 0;
+    let mut backlog: Vec<_> = index
+        .records_ingested_after(0, TAIL_BATCH)?
+        .into_iter()
+        .inspect(|record| cursor = cursor.max(record.ingested_at))
+        .filter(&matches)
+        .collect();
+    let skip = backlog.len().saturating_sub(lines);
+    backlog.drain(..skip);
+    let mut stdout = std::io::stdout().lock();
+    for record in &backlog {
+        writeln!(stdout, "{}", format_tail_line(record, &aliases, color))?;
+    }
+    stdout.flush()?;
+    drop(stdout);
+    eprintln!(
+        "Following new messages (needs `memex index-service enable --continuous`); Ctrl-C to stop"
+    );
+
+    let mut generation = index.generation()?;
     loop {
-        run_index_args(index, false)?;
-        std::io::stdout().flush().ok();
-        std::thread::sleep(Duration::from_secs(interval_secs));
+        std::thread::sleep(Duration::from_secs(interval));
+        let current = index.generation()?;
+        if current == generation {
+            continue;
+        }
+        generation = current;
+        let records = index.records_ingested_after(cursor, TAIL_BATCH)?;
+        let Some(last) = records.last() else {
+            continue;
+        };
+        cursor = last.ingested_at;
+        let mut stdout = std::io::stdout().lock();
+        for record in records.iter().filter(|record| matches(record)) {
+            writeln!(stdout, "{}", format_tail_line(record, &aliases, color))?;
+        }
+        stdout.flush()?;
     }
 }
 
-fn run_index_args(index: &IndexArgs, reindex: bool) -> Result<()> {
-    run_index(
-        index.source.clone(),
-        index.include_agents,
-        index.codex && !index.no_codex,
-        index.opencode && !index.no_opencode,
-        index.cursor,
-        index.pi && !index.no_pi,
-        index.copilot && !index.no_copilot,
-        index.embeddings,
-        index.no_embeddings,
-        index.model.clone(),
-        index.root.clone(),
-        reindex,
+/// `12:04:31 memex     assistant  text…`, one line per record with the role
+/// colorized when writing to a terminal.
+fn format_tail_line(
+    record: &crate::types::Record,
+    aliases: &ProjectAliases,
+    color: bool,
+) -> String {
+    let ts = chrono::DateTime::<chrono::Utc>::from_timestamp_millis(record.ts as i64)
+        .map(|dt| {
+            dt.with_timezone(&chrono::Local)
+                .format("%H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|| "--:--:--".to_string());
+    let role = match (&record.tool_name, record.role.as_str()) {
+        (Some(tool), "tool_use") => format!("tool_use:{tool}"),
+        _ => record.role.clone(),
+    };
+    let text: String = record
+        .text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    let text = take_width(&text, 200);
+    let project = pad_end(&aliases.shown(&record.project), 12);
+    if !color {
+        return format!("{ts} {project} {role:<10} {text}");
+    }
+    let role_color = match record.role.as_str() {
+        "user" => "36",
+        "assistant" => "32",
+        _ => "33",
+    };
+    format!(
+        "\x1b[2m{ts}\x1b[0m \x1b[35m{project}\x1b[0m \x1b[{role_color}m{role:<10}\x1b[0m {text}"
     )
 }
 
+fn run_projects(source: Option<SourceFilter>, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))
+        .map_err(|_| anyhow!("no projects indexed; run `memex index` first"))?
+        .with_project_aliases(paths.project_aliases()?);
+    let mut projects = store.query_projects(source, crate::analytics::ProjectGrouping::Flat)?;
+    rank_projects(&project_frecency_path(&paths.state), &mut projects);
+    let mut stdout = std::io::stdout().lock();
+    for project in projects {
+        writeln!(stdout, "{project}")?;
+    }
+    Ok(())
+}
+
+/// Opens the trash, first dropping entries past the configured retention.
+fn open_trash(paths: &Paths) -> Result<Trash> {
+    let config = UserConfig::load(paths)?;
+    let trash = Trash::new(&paths.state);
+    trash.expire(config.trash_retention_days(), now_millis())?;
+    Ok(trash)
+}
+
 #[allow(clippy::too_many_arguments)]
-fn run_index(
-    source: Option<PathBuf>,
-    include_agents: bool,
-    codex: bool,
-    opencode: bool,
-    cursor: bool,
-    pi: bool,
-    copilot: bool,
+fn run_append(
+    session_id: String,
+    role: String,
+    project: Option<String>,
+    tool_name: Option<String>,
+    text: Option<String>,
     embeddings_flag: bool,
     no_embeddings: bool,
-    model: Option<String>,
     root: Option<PathBuf>,
-    reindex: bool,
 ) -> Result<()> {
+    let text = match text.filter(|text| text != "-") {
+        Some(text) => text,
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+    let project = match project {
+        Some(project) => project,
+        None => std::env::current_dir()?
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
     let paths = Paths::new(root)?;
     let config = UserConfig::load(&paths)?;
-
-    // Model priority: CLI flag > config file > env var > default
-    let model_choice = config.resolve_model(model)?;
-    let embed_runtime = config.resolve_embed_runtime()?;
-    let tool_content_limits = config.indexed_tool_content_limits()?;
     let embeddings = resolve_flag(
         config.embeddings_default(),
         embeddings_flag,
         no_embeddings,
         "embeddings",
     )?;
-    if reindex && paths.root.exists() {
-        std::fs::remove_dir_all(&paths.root)?;
-    }
     paths.ensure_dirs()?;
+    let _lock = WriteLock::acquire(&paths.state)?;
     let index = SearchIndex::open_or_create_for_ingest(&paths.index)?;
-
     let opts = IngestOptions {
-        claude_source: source.unwrap_or_else(default_claude_source),
-        include_agents,
-        include_codex: codex,
-        include_opencode: opencode,
-        include_cursor: cursor,
-        include_pi: pi,
-        include_copilot: copilot,
+        claude_source: append_dir(&paths),
+        include_agents: false,
+        include_codex: false,
+        include_opencode: false,
+        include_cursor: false,
+        include_pi: false,
+        include_copilot: false,
         embeddings,
         backfill_embeddings: false,
-        model: model_choice,
-        embed_runtime,
-        tool_content_limits,
+        model: config.resolve_model(None)?,
+        embed_runtime: config.resolve_embed_runtime()?,
+        tool_content_limits: config.indexed_tool_content_limits()?,
+        max_threads: config.max_cpu_threads()?,
+        ocr_images: false,
+        embed_listener: None,
+        archive_tier: config.archive_tier()?,
     };
-
-    let report = ingest_all(&paths, &index, &opts)?;
-    if report.records_embedded > 0 {
-        println!(
-            "indexed {} records, embedded {} across {} files (skipped {})",
-            report.records_added,
-            report.records_embedded,
-            report.files_scanned,
-            report.files_skipped
-        );
-    } else {
-        println!(
-            "indexed {} records across {} files (skipped {})",
-            report.records_added, report.files_scanned, report.files_skipped
-        );
-    }
+    let message = AppendedMessage {
+        session_id,
+        project,
+        role,
+        text: text.trim_end().to_string(),
+        ts: now_millis(),
+        tool_name,
+    };
+    append_message(&paths, &index, &opts, &message)?;
+    println!("appended to {}", message.session_id);
     Ok(())
 }
 
-fn run_embed(model: Option<String>, root: Option<PathBuf>) -> Result<()> {
-    const BATCH_SIZE: usize = 256;
-
+fn run_note(
+    session_id: &str,
+    text: Option<String>,
+    clear: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
     let paths = Paths::new(root)?;
-    let config = UserConfig::load(&paths)?;
-
-    // Model priority: CLI flag > config file > env var > default
-    let model_choice = config.resolve_model(model)?;
-    let embed_runtime = config.resolve_embed_runtime()?;
-
-    let index = SearchIndex::open_or_create(&paths.index)?;
-    let mut embedder = EmbedderHandle::with_model_and_runtime(model_choice, &embed_runtime)?;
-    let mut vector =
-        VectorIndex::open_or_create(&paths.vectors, embedder.dims, Some(model_choice.as_str()))?;
-
-    let progress = std::sync::Arc::new(crate::progress::Progress::new(
-        [0; crate::progress::SOURCE_COUNT],
-        [0; crate::progress::SOURCE_COUNT],
-        true,
-    ));
-    progress.set_embed_ready();
-
-    let mut embedded_counts = [0u64; crate::progress::SOURCE_COUNT];
-    let mut embedded_total = 0u64;
-    let mut batch: Vec<(u64, String, crate::types::SourceKind)> = Vec::with_capacity(BATCH_SIZE);
-
-    let flush_batch = |batch: &mut Vec<(u64, String, crate::types::SourceKind)>,
-                       embedder: &mut EmbedderHandle,
-                       vector: &mut VectorIndex,
-                       progress: &crate::progress::Progress,
-                       embedded_counts: &mut [u64; crate::progress::SOURCE_COUNT],
-                       embedded_total: &mut u64| {
-        if batch.is_empty() {
-            return Ok(());
-        }
-        let texts: Vec<&str> = batch.iter().map(|(_, text, _)| text.as_str()).collect();
-        let embeddings = embedder.embed_texts(&texts)?;
-
-        for ((doc_id, _, source), vec) in batch.iter().zip(embeddings.iter()) {
-            vector.add(*doc_id, vec)?;
-            progress.sub_embed_pending(*source, 1);
-            progress.add_embedded(*source, 1);
-            embedded_counts[source.idx()] += 1;
-            *embedded_total += 1;
-        }
-        batch.clear();
-        Ok::<_, anyhow::Error>(())
-    };
-
-    index.for_each_record(|record| {
-        if record.text.is_empty() || !is_embedding_role(&record.role) {
-            return Ok(());
-        }
-        if vector.contains(record.doc_id) {
-            return Ok(());
+    if text.is_none() && !clear {
+        let notes = SessionNotes::load(&notes_path(&paths.state))?;
+        match notes.get(session_id) {
+            Some(note) => println!("{}", note.text),
+            None => println!("no note on {session_id}"),
         }
-        let text = truncate_for_embedding(record.text);
-        if !text.is_empty() {
-            progress.add_embed_total(record.source, 1);
-            progress.add_embed_pending(record.source, 1);
-            batch.push((record.doc_id, text, record.source));
+        return Ok(());
+    }
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let text = text.unwrap_or_default();
+    match set_note(&paths, &index, session_id, &text, now_millis())? {
+        Some(_) => println!("noted {session_id}"),
+        None => println!("cleared note on {session_id}"),
+    }
+    Ok(())
+}
 
-            if batch.len() >= BATCH_SIZE {
-                flush_batch(
-                    &mut batch,
-                    &mut embedder,
-                    &mut vector,
-                    &progress,
-                    &mut embedded_counts,
-                    &mut embedded_total,
-                )?;
-            }
+fn run_outcome(
+    session_id: &str,
+    outcome: Option<Outcome>,
+    clear: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    if outcome.is_none() && !clear {
+        let outcomes = SessionOutcomes::load(&outcomes_path(&paths.state))?;
+        match outcomes.get(session_id) {
+            Some(outcome) => println!("{}", outcome.label()),
+            None => println!("no outcome on {session_id}"),
         }
-        Ok(())
-    })?;
-
-    // Flush remaining
-    flush_batch(
-        &mut batch,
-        &mut embedder,
-        &mut vector,
-        &progress,
-        &mut embedded_counts,
-        &mut embedded_total,
-    )?;
-
-    vector.save()?;
-    progress.finish();
-    println!(
-        "embedded {} vectors (claude {}, codex {}, history {}, opencode {}, cursor {}, pi {}, copilot {})",
-        embedded_total,
-        embedded_counts[crate::types::SourceKind::Claude.idx()],
-        embedded_counts[crate::types::SourceKind::CodexSession.idx()],
-        embedded_counts[crate::types::SourceKind::CodexHistory.idx()],
-        embedded_counts[crate::types::SourceKind::Opencode.idx()],
-        embedded_counts[crate::types::SourceKind::Cursor.idx()],
-        embedded_counts[crate::types::SourceKind::Pi.idx()],
-        embedded_counts[crate::types::SourceKind::Copilot.idx()],
-    );
-
-    std::io::stdout().flush().ok();
-    std::process::exit(0);
+        return Ok(());
+    }
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    match set_outcome(&paths, &index, session_id, outcome, now_millis())? {
+        Some(label) => println!("labeled {session_id} {}", label.outcome.label()),
+        None => println!("cleared outcome on {session_id}"),
+    }
+    Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
-fn run_search(
-    query: String,
+fn run_outcomes(
     project: Option<String>,
-    role: Option<String>,
-    tool: Option<String>,
-    session: Option<String>,
-    source: Option<SourceFilter>,
-    semantic: bool,
-    hybrid: bool,
-    min_score: Option<f32>,
-    recency_weight: f32,
-    recency_half_life_days: f32,
     since: Option<String>,
-    until: Option<String>,
-    limit: usize,
-    top_n_per_session: Option<usize>,
-    unique_session: bool,
-    json_array: bool,
-    fields: Option<String>,
-    sort: SortBy,
-    verbose: bool,
+    by: Option<OutcomePeriod>,
+    json: bool,
     root: Option<PathBuf>,
 ) -> Result<()> {
     let paths = Paths::new(root)?;
-    let config = UserConfig::load(&paths)?;
-    let model_choice = config.resolve_model(None)?;
-    let embed_runtime = config.resolve_embed_runtime()?;
-    let auto_index_on_search = config.auto_index_on_search_default();
-    let embeddings_default = config.embeddings_default();
-    let scan_cache_ttl = config.scan_cache_ttl();
-    if auto_index_on_search {
-        let tool_content_limits = config.indexed_tool_content_limits()?;
-        paths.ensure_dirs()?;
-        let index = SearchIndex::open_or_create_for_ingest(&paths.index)?;
-        let opts = IngestOptions {
-            claude_source: default_claude_source(),
-            include_agents: false,
-            include_codex: true,
-            include_opencode: true,
-            include_cursor: true,
-            include_pi: true,
-            include_copilot: true,
-            embeddings: embeddings_default,
-            backfill_embeddings: false,
-            model: model_choice,
-            embed_runtime: embed_runtime.clone(),
-            tool_content_limits,
-        };
-        // Skip indexing if we recently scanned (within TTL)
-        let _ = ingest_if_stale(&paths, &index, &opts, scan_cache_ttl)?;
+    let aliases = paths.project_aliases()?;
+    let since = parse_ts_millis(since)?;
+    let outcomes = SessionOutcomes::load(&outcomes_path(&paths.state))?;
+    let mut stats = outcome_stats(
+        &outcomes,
+        since,
+        by,
+        |project| shown_project_name(project, &aliases),
+        &chrono::Local,
+    );
+    if let Some(project) = &project {
+        let shown = shown_project_name(&aliases.resolve(project), &aliases);
+        stats.retain(|row| &row.project == project || row.project == shown);
     }
-    let index = SearchIndex::open_or_create(&paths.index)?;
-
-    let options = QueryOptions {
-        query,
-        project,
-        role,
-        tool,
-        session_id: session,
-        source,
-        since: parse_ts_millis(since)?,
-        until: parse_ts_millis(until)?,
-        limit,
-    };
-    let matchers = build_matchers(&options.query)?;
-    let fields = parse_fields(fields)?;
-    let top_n_per_session = if unique_session && top_n_per_session.is_none() {
-        Some(1)
-    } else {
-        top_n_per_session
-    };
-    let render = RenderOptions {
-        verbose,
-        matchers,
-        json_array: json_array && !verbose,
-        fields,
-        sort,
-        min_score,
-        top_n_per_session,
-        limit,
-    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+    if stats.is_empty() {
+        println!("no labeled sessions (label one with `memex outcome <session_id> success`)");
+        return Ok(());
+    }
+    let width = stats
+        .iter()
+        .flat_map(|project| {
+            std::iter::once(project.project.len() + 2)
+                .chain(project.periods.iter().map(|period| period.period.len() + 2))
+        })
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    let mut stdout = std::io::stdout().lock();
+    writeln!(
+        stdout,
+        "{:<width$}  {:>7}  {:>9}  {:>6}  {:>7}",
+        "project", "success", "abandoned", "rework", "rate"
+    )?;
+    for project in &stats {
+        let rows = std::iter::once((
+            project.project.clone(),
+            &project.counts,
+            project.success_rate,
+        ))
+        .chain(project.periods.iter().map(|period| {
+            (
+                format!("  {}", period.period),
+                &period.counts,
+                period.success_rate,
+            )
+        }));
+        for (label, counts, rate) in rows {
+            writeln!(
+                stdout,
+                "{label:<width$}  {:>7}  {:>9}  {:>6}  {:>6.0}%",
+                counts.success,
+                counts.abandoned,
+                counts.rework,
+                rate * 100.0
+            )?;
+        }
+    }
+    Ok(())
+}
 
-    let candidate_limit = if top_n_per_session.is_some() || options.source.is_some() {
-        (limit * 5).max(limit + 10)
+fn run_forget(session_id: &str, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let trash = open_trash(&paths)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let records = index.records_by_session_id(session_id)?;
+    if records.is_empty() {
+        return Err(anyhow!("session not found: {session_id}"));
+    }
+    // Trash first: if the index write fails the session is merely duplicated.
+    let entry = trash.put("forget", &records, now_millis())?;
+    let mut writer = index.writer()?;
+    index.delete_by_session_id(&mut writer, session_id)?;
+    writer.commit()?;
+    let db = analytics_path(&paths.state);
+    if db.exists() {
+        AnalyticsStore::open(&db)?.delete_session(session_id)?;
+    }
+    println!(
+        "forgot {} ({} records); undo with `memex restore {}`",
+        session_id, entry.records, session_id
+    );
+    Ok(())
+}
+
+fn run_restore(session_id: &str, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let trash = open_trash(&paths)?;
+    let entry = trash
+        .find(session_id)?
+        .ok_or_else(|| anyhow!("no trashed session: {session_id}"))?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    if !index.records_by_session_id(session_id)?.is_empty() {
+        return Err(anyhow!(
+            "session {session_id} is already indexed; `memex forget` it first to restore the trashed copy"
+        ));
+    }
+    let mut records = trash.load(&entry)?;
+    records.sort_by_key(|record| (record.turn_id, record.doc_id));
+    let mut writer = index.writer()?;
+    for record in &records {
+        index.add_record(&mut writer, record)?;
+    }
+    for turn in crate::turns::build_turns(&records) {
+        index.add_record(&mut writer, &turn)?;
+    }
+    writer.commit()?;
+    let mut analytics = crate::analytics::AnalyticsWriter::open(analytics_path(&paths.state))?;
+    for record in &records {
+        analytics.record(record)?;
+    }
+    analytics.flush()?;
+    trash.remove(&entry)?;
+    println!("restored {} ({} records)", session_id, records.len());
+    Ok(())
+}
+
+fn run_migrate_home(from: Option<PathBuf>, to: Option<PathBuf>, dry_run: bool) -> Result<()> {
+    let home = directories::BaseDirs::new()
+        .ok_or_else(|| anyhow!("missing home dir"))?
+        .home_dir()
+        .to_path_buf();
+    let from = Paths::with_layout(Layout::Single(match from {
+        Some(from) => from,
+        None => std::env::var_os("MEMEX_HOME")
+            .filter(|root| !root.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".memex")),
+    }));
+    if !from.root.exists() {
+        return Err(anyhow!("no memex store at {}", from.root.display()));
+    }
+    let to = Paths::with_layout(match to {
+        Some(to) => Layout::Single(to),
+        None => Layout::xdg(&home, |name| std::env::var_os(name)),
+    });
+    let lock = if dry_run {
+        None
     } else {
-        limit
+        Some(WriteLock::try_acquire(&from.state)?.ok_or_else(|| {
+            anyhow!(
+                "{}; stop it (and the index service) before moving the store",
+                busy_message(&from.state)
+            )
+        })?)
     };
-
-    if hybrid {
-        return run_hybrid_search(
-            &index,
-            &options,
-            candidate_limit,
-            &SearchContext {
-                render: &render,
-                paths: &paths,
-                model_choice,
-                embed_runtime: &embed_runtime,
-                recency_weight,
-                recency_half_life_days,
-            },
-        );
+    let moves = plan_home_migration(&from, &to)?;
+    if moves.is_empty() {
+        println!("nothing to move from {}", from.root.display());
+        return Ok(());
     }
-    if semantic {
-        return run_semantic_search(
-            &index,
-            &options,
-            candidate_limit,
-            &SearchContext {
-                render: &render,
-                paths: &paths,
-                model_choice,
-                embed_runtime: &embed_runtime,
-                recency_weight,
-                recency_half_life_days,
-            },
+    for (source, target) in &moves {
+        println!("{} -> {}", source.display(), target.display());
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let mut lock = lock;
+    for (source, target) in &moves {
+        // State holds the lock and moves last.
+        if source == &from.state {
+            lock.take();
+        }
+        move_path(source, target)?;
+    }
+    // Only empty once everything moved; anything left is kept.
+    let _ = std::fs::remove_dir(&from.root);
+    println!("moved {} entries to {}", moves.len(), to.root.display());
+    if std::env::var_os("MEMEX_HOME").is_some() {
+        println!(
+            "MEMEX_HOME is set and takes precedence; point it at {} or unset it",
+            to.root.display()
         );
     }
-    run_lexical_search(
-        &index,
-        &options,
-        &render,
-        recency_weight,
-        recency_half_life_days,
-    )
+    Ok(())
 }
 
-struct SearchContext<'a> {
-    render: &'a RenderOptions,
-    paths: &'a Paths,
-    model_choice: ModelChoice,
-    embed_runtime: &'a EmbedRuntimeConfig,
-    recency_weight: f32,
-    recency_half_life_days: f32,
+fn run_backup_create(dir: Option<PathBuf>, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let dir = dir.unwrap_or_else(|| config.backup_dir(&paths));
+    paths.ensure_dirs()?;
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let (archive, manifest) = backup::create_backup(&paths, &dir)?;
+    let compressed = std::fs::metadata(&archive)?.len();
+    println!(
+        "backed up {} files ({}) to {} ({})",
+        manifest.files.len(),
+        crate::progress::format_bytes(manifest.total_bytes()),
+        archive.display(),
+        crate::progress::format_bytes(compressed)
+    );
+    Ok(())
 }
 
-fn run_semantic_search(
-    index: &SearchIndex,
-    options: &QueryOptions,
-    limit: usize,
-    ctx: &SearchContext,
+fn run_backup_list(dir: Option<PathBuf>, json: bool, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let dir = dir.unwrap_or_else(|| config.backup_dir(&paths));
+    let backups = backup::list_backups(&dir)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&backups)?);
+        return Ok(());
+    }
+    if backups.is_empty() {
+        println!(
+            "no backups in {} (create one with `memex backup create`)",
+            dir.display()
+        );
+        return Ok(());
+    }
+    let mut stdout = std::io::stdout().lock();
+    for backup in backups {
+        writeln!(
+            stdout,
+            "{}  {:>10}  {}",
+            format_ts(backup.modified_at),
+            crate::progress::format_bytes(backup.size),
+            backup.name.trim_end_matches(backup::BACKUP_EXTENSION)
+        )?;
+    }
+    Ok(())
+}
+
+fn run_backup_restore(
+    name: &str,
+    dir: Option<PathBuf>,
+    yes: bool,
+    root: Option<PathBuf>,
 ) -> Result<()> {
-    let vector = match VectorIndex::open(&ctx.paths.vectors) {
-        Ok(vector) => vector,
-        Err(err) if is_missing_vector_index_error(&err) => {
-            warn_vector_index_missing("semantic");
-            return run_lexical_search(
-                index,
-                options,
-                ctx.render,
-                ctx.recency_weight,
-                ctx.recency_half_life_days,
-            );
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let dir = dir.unwrap_or_else(|| config.backup_dir(&paths));
+    let archive = backup::resolve_backup(&dir, name)?;
+    if !yes {
+        if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+            return Err(anyhow!("pass --yes to restore a backup non-interactively"));
         }
-        Err(err) => return Err(err),
-    };
-    let mut embedder = EmbedderHandle::with_model_and_runtime(ctx.model_choice, ctx.embed_runtime)?;
-    let embeddings = embedder.embed_texts(&[options.query.as_str()])?;
-    let embedding = embeddings
-        .first()
-        .ok_or_else(|| anyhow!("embedding missing"))?;
-    let mut results = Vec::new();
-    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
-    for (doc_id, distance) in vector.search(embedding, limit)? {
-        if let Some(record) = index.get_by_doc_id(doc_id)?
-            && matches_filters(&record, options)
-        {
-            let base = score_from_distance(distance);
-            let score = apply_recency(
-                base,
-                record.ts,
-                now_ms,
-                ctx.recency_weight,
-                ctx.recency_half_life_days,
-            );
-            results.push((score, record));
+        print!(
+            "Replace the index, vectors, state and config.toml in {} with {}? [y/N] ",
+            paths.root.display(),
+            archive.display()
+        );
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("aborted");
+            return Ok(());
         }
     }
-    let results = apply_post_processing(results, ctx.render);
-    render_results(results, ctx.render)?;
+    paths.ensure_dirs()?;
+    let lock = WriteLock::acquire(&paths.state)?;
+    let manifest = backup::restore_backup(&paths, &archive)?;
+    drop(lock);
+    println!(
+        "restored {} files ({}) from the backup taken {} by memex {}",
+        manifest.files.len(),
+        crate::progress::format_bytes(manifest.total_bytes()),
+        format_ts(manifest.created_at),
+        manifest.memex_version
+    );
+    println!("run `memex index` to pick up sessions newer than the backup");
     Ok(())
 }
 
-fn run_hybrid_search(
-    index: &SearchIndex,
-    options: &QueryOptions,
-    limit: usize,
-    ctx: &SearchContext,
-) -> Result<()> {
-    let vector = match VectorIndex::open(&ctx.paths.vectors) {
-        Ok(vector) => vector,
-        Err(err) if is_missing_vector_index_error(&err) => {
-            warn_vector_index_missing("hybrid");
-            return run_lexical_search(
-                index,
-                options,
-                ctx.render,
-                ctx.recency_weight,
-                ctx.recency_half_life_days,
-            );
-        }
-        Err(err) => return Err(err),
-    };
-    let mut embedder = EmbedderHandle::with_model_and_runtime(ctx.model_choice, ctx.embed_runtime)?;
+fn run_trash_list(root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let entries = open_trash(&paths)?.list()?;
+    if entries.is_empty() {
+        println!("trash is empty");
+        return Ok(());
+    }
+    let mut stdout = std::io::stdout().lock();
+    for entry in entries {
+        writeln!(
+            stdout,
+            "{}  {:<8} {:>6} records  {}  {}",
+            format_ts(entry.deleted_at),
+            entry.reason,
+            entry.records,
+            entry.session_id,
+            entry.project
+        )?;
+    }
+    Ok(())
+}
 
-    let bm25_k = (limit * 5).clamp(50, 500);
-    let vector_k = (limit * 5).clamp(50, 500);
+fn run_trash_empty(root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let removed = Trash::new(&paths.state).empty()?;
+    println!("deleted {removed} trashed sessions");
+    Ok(())
+}
 
-    let bm25_results = index.search(&QueryOptions {
-        limit: bm25_k,
-        ..options.clone()
-    })?;
+fn run_project_rename(old: String, new: String, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let path = project_aliases_path(&paths.state);
+    let mut aliases = ProjectAliases::load(&path)?;
+    let original = match aliases.original(&old) {
+        Some(original) => original.to_string(),
+        None => stored_project_name(&paths, &old)?,
+    };
+    aliases.rename(&original, &new)?;
+    aliases.save(&path)?;
+    if aliases.aliases.contains_key(&original) {
+        println!("{original} is now shown as {}", new.trim());
+    } else {
+        println!("{original} is shown under its original name again");
+    }
+    Ok(())
+}
 
-    let embeddings = embedder.embed_texts(&[options.query.as_str()])?;
-    let embedding = embeddings
-        .first()
-        .ok_or_else(|| anyhow!("embedding missing"))?;
-    let vector_results = vector.search(embedding, vector_k)?;
+/// Stored project name for `name`, which may be the decoded form shown in
+/// listings (e.g. `~/dev/memex` for `-Users-nico-dev-memex`).
+fn stored_project_name(paths: &Paths, name: &str) -> Result<String> {
+    let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?;
+    let stored = store.stored_project_names()?;
+    if stored.iter().any(|project| project == name) {
+        return Ok(name.to_string());
+    }
+    stored
+        .into_iter()
+        .find(|project| display_project_name(project) == name)
+        .ok_or_else(|| anyhow!("unknown project: {name} (see `memex sessions` for project names)"))
+}
 
-    let mut records: HashMap<u64, crate::types::Record> = HashMap::new();
-    let mut scores: HashMap<u64, f32> = HashMap::new();
-    let rrf_k = 60.0;
+fn run_project_list(root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let aliases = paths.project_aliases()?;
+    if aliases.aliases.is_empty() {
+        println!("no renamed projects");
+        return Ok(());
+    }
+    let mut renamed: Vec<(&String, &String)> = aliases.aliases.iter().collect();
+    renamed.sort_by(|a, b| a.1.cmp(b.1));
+    let mut stdout = std::io::stdout().lock();
+    for (original, alias) in renamed {
+        writeln!(stdout, "{alias}  <- {original}")?;
+    }
+    Ok(())
+}
 
-    for (rank, (_, record)) in bm25_results.into_iter().enumerate() {
-        if !matches_filters(&record, options) {
-            continue;
+fn run_prune(dry_run: bool, last: bool, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let state_path = retention_state_path(&paths.state);
+    if last {
+        match RetentionState::load(&state_path)?.last_report {
+            Some(report) => print_retention_report(&report, &paths.project_aliases()?),
+            None => println!("the index service has not pruned anything yet"),
         }
-        let r = rank as f32 + 1.0;
-        scores
-            .entry(record.doc_id)
-            .and_modify(|v| *v += 1.0 / (rrf_k + r))
-            .or_insert(1.0 / (rrf_k + r));
-        records.insert(record.doc_id, record);
+        return Ok(());
+    }
+    let policy = UserConfig::load(&paths)?.retention_policy();
+    if !policy.is_enabled() {
+        return Err(anyhow!(
+            "no retention policy; set retain_days or retain_projects in config.toml"
+        ));
+    }
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let report = prune(&paths, &index, &policy, now_millis(), dry_run)?;
+    print_retention_report(&report, &paths.project_aliases()?);
+    if !dry_run {
+        let mut state = RetentionState::load(&state_path)?;
+        state.last_run = report.ran_at;
+        state.last_report = Some(report);
+        state.save(&state_path)?;
     }
+    Ok(())
+}
 
-    for (rank, (doc_id, _distance)) in vector_results.into_iter().enumerate() {
-        if let Some(record) = index.get_by_doc_id(doc_id)? {
-            if !matches_filters(&record, options) {
-                continue;
+fn run_repair(rebuild_index: bool, yes: bool, args: &IndexArgs) -> Result<()> {
+    let paths = Paths::new(args.root.clone())?;
+    if !rebuild_index {
+        let check = repair::check(&paths)?;
+        if !check.index_exists {
+            println!("index: not created yet (run `memex index`)");
+        } else if check.index_problems.is_empty() {
+            println!("index: ok");
+        } else {
+            println!("index: damaged");
+            for problem in &check.index_problems {
+                println!("  {problem}");
             }
-            let r = rank as f32 + 1.0;
-            scores
-                .entry(doc_id)
-                .and_modify(|v| *v += 1.0 / (rrf_k + r))
-                .or_insert(1.0 / (rrf_k + r));
-            records.entry(doc_id).or_insert(record);
         }
+        println!(
+            "vectors: {}",
+            match (check.vectors_exist, check.vectors_valid) {
+                (false, _) => "none",
+                (true, Some(true)) => "ok",
+                (true, Some(false)) => "checksum mismatch",
+                (true, None) => "no checksums recorded yet",
+            }
+        );
+        if !check.is_healthy() {
+            println!("run `memex repair --rebuild-index` to rebuild the index");
+        }
+        return Ok(());
     }
 
-    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
-    let merged: Vec<(f32, crate::types::Record)> = scores
-        .into_iter()
-        .filter_map(|(doc_id, score)| {
-            records.remove(&doc_id).map(|r| {
-                (
-                    apply_recency(
-                        score,
-                        r.ts,
-                        now_ms,
-                        ctx.recency_weight,
-                        ctx.recency_half_life_days,
-                    ),
-                    r,
-                )
-            })
-        })
-        .collect();
-    let merged = apply_post_processing(merged, ctx.render);
-    render_results(merged, ctx.render)?;
+    if !yes {
+        if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+            return Err(anyhow!("pass --yes to rebuild the index non-interactively"));
+        }
+        print!(
+            "Rebuild the index at {}? The damaged copy is kept in index.damaged. [y/N] ",
+            paths.index.display()
+        );
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("aborted");
+            return Ok(());
+        }
+    }
+
+    let config = UserConfig::load(&paths)?;
+    paths.ensure_dirs()?;
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let opts = IngestOptions {
+        claude_source: args.source.clone().unwrap_or_else(default_claude_source),
+        include_agents: args.include_agents,
+        include_codex: args.codex && !args.no_codex,
+        include_opencode: args.opencode && !args.no_opencode,
+        include_cursor: args.cursor,
+        include_pi: args.pi && !args.no_pi,
+        include_copilot: args.copilot && !args.no_copilot,
+        // Vectors are carried over; `memex embed` fills the rest.
+        embeddings: false,
+        backfill_embeddings: false,
+        model: config.resolve_model(args.model.clone())?,
+        embed_runtime: config.resolve_embed_runtime()?,
+        tool_content_limits: config.indexed_tool_content_limits()?,
+        max_threads: config.max_cpu_threads()?,
+        ocr_images: config.ocr_images(),
+        embed_listener: None,
+        archive_tier: config.archive_tier()?,
+    };
+    let report = repair::rebuild_index(&paths, |index| ingest_all(&paths, index, &opts).map(drop))?;
+
+    let salvage = &report.salvage;
+    if salvage.is_complete() {
+        println!(
+            "rebuilt the index from all {} readable records",
+            crate::progress::format_count(salvage.records as u64)
+        );
+    } else {
+        if salvage.readable {
+            println!(
+                "{} segments were unreadable; kept {} readable records and re-ingested sources",
+                salvage.lost_segments,
+                crate::progress::format_count(salvage.records as u64)
+            );
+        } else {
+            println!("index metadata was unreadable; re-ingested sources");
+        }
+        println!(
+            "rebuilt the index with {} records ({} filled in from sources)",
+            crate::progress::format_count(report.records as u64),
+            crate::progress::format_count(report.filled as u64)
+        );
+    }
+    match report.vectors {
+        VectorRepair::Absent => {}
+        VectorRepair::Discarded => println!(
+            "vectors failed their checksums and were removed; run `memex embed` to rebuild them"
+        ),
+        VectorRepair::Kept {
+            vectors,
+            dropped,
+            verified,
+        } => {
+            let checked = if verified {
+                "checksums ok"
+            } else {
+                "no checksums to verify"
+            };
+            println!(
+                "kept {} vectors ({checked})",
+                crate::progress::format_count(vectors as u64)
+            );
+            if dropped > 0 {
+                println!(
+                    "dropped {} vectors with no matching record; run `memex embed` to fill the gaps",
+                    crate::progress::format_count(dropped as u64)
+                );
+            }
+        }
+    }
+    println!("damaged index moved to {}", report.damaged_dir.display());
     Ok(())
 }
 
-fn run_lexical_search(
-    index: &SearchIndex,
-    options: &QueryOptions,
-    render: &RenderOptions,
-    recency_weight: f32,
-    recency_half_life_days: f32,
-) -> Result<()> {
-    let results = index.search(options)?;
-    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
-    let mut reranked =
-        apply_recency_to_results(results, now_ms, recency_weight, recency_half_life_days);
-    reranked.retain(|(_, record)| matches_filters(record, options));
-    let reranked = apply_post_processing(reranked, render);
-    render_results(reranked, render)?;
-    Ok(())
+fn print_retention_report(report: &RetentionReport, aliases: &ProjectAliases) {
+    let verb = if report.dry_run {
+        "would prune"
+    } else {
+        "pruned"
+    };
+    println!(
+        "{}: {verb} {} sessions ({} records, {} vectors), kept {} pinned or noted",
+        format_ts(report.ran_at),
+        report.sessions.len(),
+        crate::progress::format_count(report.records as u64),
+        crate::progress::format_count(report.vectors as u64),
+        report.exempt
+    );
+    for (project, records) in report.by_project() {
+        let project = aliases.shown(project);
+        println!("  {project}: {records} records");
+    }
+    if report.dry_run {
+        for session in &report.sessions {
+            println!(
+                "  {}  {}  last message {}",
+                session.session_id,
+                aliases.shown(&session.project),
+                format_ts(session.last_ts)
+            );
+        }
+    }
 }
 
-fn is_missing_vector_index_error(err: &anyhow::Error) -> bool {
-    err.to_string() == "vector index not found"
+fn run_vacuum(root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    print_vacuum_report(&vacuum(&paths, &index)?);
+    Ok(())
 }
 
-fn warn_vector_index_missing(mode: &str) {
-    eprintln!(
-        "Warning: {mode} search requested, but the local vector index is not available; falling back to lexical search. Run 'memex embed' to build embeddings."
-    );
+struct VacuumReport {
+    segments_before: usize,
+    segments_after: usize,
+    deleted_docs_purged: u64,
+    vectors_removed: usize,
+    vector_segments_before: usize,
+    vector_segments_after: usize,
+    bytes_before: u64,
+    bytes_after: u64,
 }
 
-fn score_from_distance(distance: f32) -> f32 {
-    1.0 / (1.0 + distance)
+fn vacuum(paths: &Paths, index: &SearchIndex) -> Result<VacuumReport> {
+    let bytes_before = dir_size(&paths.index) + dir_size(&paths.vectors);
+    let stats = index.compact()?;
+    let mut vectors_removed = 0;
+    let (mut vector_segments_before, mut vector_segments_after) = (0, 0);
+    let dirs = vector_store_dirs(&paths.vectors);
+    if !dirs.is_empty() {
+        let live = index.live_doc_ids()?;
+        for dir in dirs {
+            let mut vector = VectorIndex::open(&dir)?;
+            vector_segments_before += vector.segment_count();
+            let removed = vector.compact(&live)?;
+            let merged = vector.merge()?;
+            if removed > 0 || merged > 0 {
+                vector.save()?;
+            }
+            vectors_removed += removed;
+            vector_segments_after += vector.segment_count();
+        }
+    }
+    Ok(VacuumReport {
+        segments_before: stats.segments,
+        segments_after: index.segment_stats()?.segments,
+        deleted_docs_purged: stats.deleted_docs,
+        vectors_removed,
+        vector_segments_before,
+        vector_segments_after,
+        bytes_before,
+        bytes_after: dir_size(&paths.index) + dir_size(&paths.vectors),
+    })
 }
 
-fn apply_recency(score: f32, ts: u64, now_ms: u64, weight: f32, half_life_days: f32) -> f32 {
-    if score <= 0.0 || weight <= 0.0 || half_life_days <= 0.0 || ts == 0 {
-        return score;
+/// Larger of the deleted-document share of the index and the orphaned-vector
+/// share of each vector store.
+fn fragmentation(paths: &Paths, index: &SearchIndex) -> Result<f64> {
+    let mut ratio = index.segment_stats()?.deleted_ratio();
+    for dir in vector_store_dirs(&paths.vectors) {
+        let vector = VectorIndex::open(&dir)?;
+        if vector.doc_id_count() == 0 {
+            continue;
+        }
+        let orphans = vector.orphan_count(&index.live_doc_ids()?);
+        ratio = ratio.max(orphans as f64 / vector.doc_id_count() as f64);
     }
-    let age_ms = now_ms.saturating_sub(ts);
-    let age_days = age_ms as f32 / (1000.0 * 60.0 * 60.0 * 24.0);
-    let decay = (-std::f32::consts::LN_2 * age_days / half_life_days).exp();
-    score * (1.0 + weight * decay)
+    Ok(ratio)
 }
 
-fn matches_filters(record: &crate::types::Record, options: &QueryOptions) -> bool {
-    if let Some(project) = &options.project
-        && &record.project != project
-    {
-        return false;
-    }
-    if let Some(role) = &options.role
-        && &record.role != role
-    {
-        return false;
-    }
-    if let Some(tool) = &options.tool
-        && record.tool_name.as_deref() != Some(tool.as_str())
-    {
-        return false;
-    }
-    if let Some(source) = options.source
-        && !source.matches(record.source)
-    {
-        return false;
+fn print_vacuum_report(report: &VacuumReport) {
+    println!(
+        "index: {} segments -> {}, purged {} deleted documents",
+        report.segments_before,
+        report.segments_after,
+        crate::progress::format_count(report.deleted_docs_purged)
+    );
+    println!(
+        "vectors: {} segments -> {}, removed {} orphaned vectors",
+        report.vector_segments_before,
+        report.vector_segments_after,
+        crate::progress::format_count(report.vectors_removed as u64)
+    );
+    println!(
+        "reclaimed {} ({} -> {})",
+        crate::progress::format_bytes(report.bytes_before.saturating_sub(report.bytes_after)),
+        crate::progress::format_bytes(report.bytes_before),
+        crate::progress::format_bytes(report.bytes_after)
+    );
+}
+
+fn run_shards_list(json: bool, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let shards = index.shards()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&shards)?);
+        return Ok(());
     }
-    if let Some(session_id) = &options.session_id
-        && &record.session_id != session_id
-    {
-        return false;
+    if shards.is_empty() {
+        println!("no shards (create them with `memex shards split`)");
+        return Ok(());
     }
-    if let Some(since) = options.since
-        && record.ts < since
-    {
-        return false;
+    let mut stdout = std::io::stdout().lock();
+    for shard in shards {
+        writeln!(
+            stdout,
+            "{}  {:>10} records  {:>10}",
+            shard.month,
+            crate::progress::format_count(shard.records as u64),
+            crate::progress::format_bytes(dir_size(&shard.dir))
+        )?;
     }
-    if let Some(until) = options.until
-        && record.ts > until
-    {
-        return false;
+    Ok(())
+}
+
+fn run_shards_split(older_than: Option<u32>, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let months = older_than
+        .or(config.shard_after_months())
+        .unwrap_or(DEFAULT_SHARD_AFTER_MONTHS);
+    let index_code = config.indexed_tool_content_limits()?.code;
+    let _lock = WriteLock::acquire(&paths.state)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let moved = index.split_months(month_start_before(months), index_code)?;
+    if moved.is_empty() {
+        println!("nothing older than {months} months left to move");
     }
-    true
+    print_split_report(&moved);
+    Ok(())
 }
 
-#[derive(Clone)]
-struct RenderOptions {
-    verbose: bool,
-    matchers: Vec<regex::Regex>,
-    json_array: bool,
-    fields: Option<HashSet<String>>,
-    sort: SortBy,
-    min_score: Option<f32>,
-    top_n_per_session: Option<usize>,
-    limit: usize,
+/// Months `memex shards split` keeps in the live index when neither
+/// `--older-than` nor `shard_after_months` says.
+const DEFAULT_SHARD_AFTER_MONTHS: u32 = 3;
+
+/// Unix millis at the start (UTC) of the month `months` before this one.
+fn month_start_before(months: u32) -> u64 {
+    use chrono::Datelike;
+    let today = chrono::Utc::now().date_naive();
+    let first = today.with_day(1).unwrap_or(today);
+    first
+        .checked_sub_months(chrono::Months::new(months))
+        .unwrap_or(first)
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc()
+        .timestamp_millis()
+        .max(0) as u64
 }
 
-#[derive(Serialize)]
-struct MatchSpan {
-    start: usize,
-    end: usize,
-    text: String,
-    before: String,
-    after: String,
+fn print_split_report(moved: &[(String, usize)]) {
+    for (month, records) in moved {
+        println!(
+            "shard {month}: moved {} records",
+            crate::progress::format_count(*records as u64)
+        );
+    }
 }
 
-#[derive(Serialize)]
-struct SearchHit {
-    score: f32,
-    ts: String,
-    doc_id: u64,
-    project: String,
-    role: String,
-    session_id: String,
-    source: String,
-    source_path: String,
-    text: String,
-    snippet: String,
-    matches: Vec<MatchSpan>,
-    #[serde(flatten)]
-    links: RecordLinks,
+fn dir_size(dir: &std::path::Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
 }
 
-fn render_results(results: Vec<(f32, crate::types::Record)>, render: &RenderOptions) -> Result<()> {
-    if render.verbose {
-        for (score, record) in results {
-            let ts = format_ts(record.ts);
-            let text = summarize(&record.text, 200);
-            println!(
-                "[{score:.3}] {} {} {} {} {} {}",
-                ts, record.doc_id, record.project, record.role, record.session_id, text
-            );
-        }
+fn run_show(
+    doc_id: u64,
+    verbose: bool,
+    template: Option<String>,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let record = index
+        .get_by_doc_id(doc_id)?
+        .ok_or_else(|| anyhow!("doc_id not found"))?;
+    if verbose {
+        println!("{}", serde_json::to_string_pretty(&record)?);
         return Ok(());
     }
-
-    let mut output = Vec::new();
-    for (score, record) in results {
-        let ts = format_ts(record.ts);
-        let text_ref = record.text.as_str();
-        let wants_snippet = wants_field(&render.fields, "snippet");
-        let wants_matches = wants_field(&render.fields, "matches");
-        let wants_text = wants_field(&render.fields, "text");
-        let snippet = if wants_snippet {
-            summarize(text_ref, 400)
-        } else {
-            String::new()
-        };
-        let matches = if wants_matches {
-            collect_matches(text_ref, &render.matchers, 8)
-        } else {
-            Vec::new()
-        };
-        let text = if wants_text {
-            record.text
-        } else {
-            String::new()
-        };
-
-        let value = if let Some(fields) = &render.fields {
-            let mut map = serde_json::Map::new();
-            if fields.contains("score") {
-                map.insert("score".to_string(), Value::from(score));
-            }
-            if fields.contains("ts") {
-                map.insert("ts".to_string(), Value::from(ts));
-            }
-            if fields.contains("doc_id") {
-                map.insert("doc_id".to_string(), Value::from(record.doc_id));
-            }
-            if fields.contains("project") {
-                map.insert("project".to_string(), Value::from(record.project));
-            }
-            if fields.contains("role") {
-                map.insert("role".to_string(), Value::from(record.role));
-            }
-            if fields.contains("session_id") {
-                map.insert("session_id".to_string(), Value::from(record.session_id));
-            }
-            if fields.contains("source") {
-                map.insert("source".to_string(), Value::from(record.source.label()));
-            }
-            insert_optional_field(&mut map, fields, "event_id", &record.links.event_id);
-            insert_optional_field(
-                &mut map,
-                fields,
-                "parent_event_id",
-                &record.links.parent_event_id,
-            );
-            insert_optional_field(
-                &mut map,
-                fields,
-                "logical_parent_event_id",
-                &record.links.logical_parent_event_id,
-            );
-            insert_optional_field(
-                &mut map,
-                fields,
-                "parent_session_id",
-                &record.links.parent_session_id,
-            );
-            insert_optional_field(
-                &mut map,
-                fields,
-                "thread_source",
-                &record.links.thread_source,
-            );
-            insert_optional_field(
-                &mut map,
-                fields,
-                "conversation_kind",
-                &record.links.conversation_kind,
-            );
-            insert_optional_field(
-                &mut map,
-                fields,
-                "parent_tool_use_id",
-                &record.links.parent_tool_use_id,
-            );
-            insert_optional_field(
-                &mut map,
-                fields,
-                "source_tool_use_id",
-                &record.links.source_tool_use_id,
-            );
-            insert_optional_field(
-                &mut map,
-                fields,
-                "source_tool_assistant_uuid",
-                &record.links.source_tool_assistant_uuid,
-            );
-            if fields.contains("source_path") {
-                map.insert("source_path".to_string(), Value::from(record.source_path));
-            }
-            if fields.contains("text") {
-                map.insert("text".to_string(), Value::from(text));
-            }
-            if fields.contains("snippet") {
-                map.insert("snippet".to_string(), Value::from(snippet));
-            }
-            if fields.contains("matches") {
-                map.insert("matches".to_string(), serde_json::to_value(matches)?);
-            }
-            Value::Object(map)
-        } else {
-            serde_json::to_value(SearchHit {
-                score,
-                ts,
-                doc_id: record.doc_id,
-                project: record.project,
-                role: record.role,
-                session_id: record.session_id,
-                source: record.source.label().to_string(),
-                source_path: record.source_path,
-                text,
-                snippet,
-                matches,
-                links: record.links,
-            })?
-        };
-        if render.json_array {
-            output.push(value);
-        } else {
-            println!("{}", serde_json::to_string(&value)?);
-        }
+    if let Some(template) = output_template(&config, "show", template.as_deref())? {
+        return template.write(
+            &mut std::io::stdout().lock(),
+            &serde_json::to_value(&record)?,
+        );
     }
+    println!("{}", serde_json::to_string(&record)?);
+    Ok(())
+}
 
-    if render.json_array {
-        println!("{}", serde_json::to_string(&output)?);
+/// Pages `id`'s session, where `id` is a session ID or a document in it.
+fn run_show_pager(id: &str, show_tools: bool, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let session_id = match id.parse::<u64>() {
+        Ok(doc_id) => match index.get_by_doc_id(doc_id)? {
+            Some(record) => record.session_id,
+            None => id.to_string(),
+        },
+        Err(_) => id.to_string(),
+    };
+    let interactive = std::io::IsTerminal::is_terminal(&std::io::stdout());
+    let lines = tui::session_transcript(&index, &config, &session_id, show_tools, interactive)?;
+    let mut text = lines.join("\n");
+    text.push('\n');
+    if !interactive {
+        std::io::stdout().lock().write_all(text.as_bytes())?;
+        return Ok(());
+    }
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less -R".to_string());
+    let mut command = shell_command(&pager);
+    // Quit when it fits on one screen and keep the colors.
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let mut child = command
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow!("failed to start pager `{pager}`: {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The reader quitting early closes the pipe; that is not an error.
+        match stdin.write_all(text.as_bytes()) {
+            Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
     }
+    child.wait()?;
     Ok(())
 }
 
-fn insert_optional_field(
-    map: &mut serde_json::Map<String, Value>,
-    fields: &HashSet<String>,
-    name: &str,
-    value: &Option<String>,
-) {
-    if fields.contains(name)
-        && let Some(value) = value
-    {
-        map.insert(name.to_string(), Value::from(value.clone()));
+/// Prints the last background index outcome. Returns false when it failed.
+fn run_status(root: Option<PathBuf>) -> Result<bool> {
+    let paths = Paths::new(root)?;
+    let errors = IngestErrors::load(&ingest_errors_path(&paths.state))?;
+    if !errors.files.is_empty() {
+        println!(
+            "malformed lines: {} in {} files (see `memex ingest-errors`)",
+            errors.line_count(),
+            errors.files.len()
+        );
+    }
+    let path = service_status_path(&paths.state);
+    if !path.exists() {
+        println!("index service: no runs recorded (enable with `memex index-service enable`)");
+        return Ok(true);
+    }
+    let status = ServiceStatus::load(&path)?;
+    let secs = |ts: u64| format_ts(ts.saturating_mul(1000));
+    match &status.error {
+        None => println!("index service: ok"),
+        Some(_) if status.failures > 1 => println!(
+            "index service: failing ({} consecutive runs)",
+            status.failures
+        ),
+        Some(_) => println!("index service: failing"),
+    }
+    println!("last run: {}", secs(status.last_run));
+    println!(
+        "last success: {}",
+        status
+            .last_success
+            .map(secs)
+            .unwrap_or_else(|| "never".to_string())
+    );
+    if let Some(error) = &status.error {
+        println!("error: {error}");
     }
+    Ok(!status.failed())
 }
 
-fn run_session(session_id: String, verbose: bool, root: Option<PathBuf>) -> Result<()> {
+fn run_ingest_errors(
+    source: Option<SourceFilter>,
+    json: bool,
+    clear: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
     let paths = Paths::new(root)?;
-    let index = SearchIndex::open_or_create(&paths.index)?;
-    let mut records = index.records_by_session_id(&session_id)?;
-    records.sort_by(|a, b| {
-        a.turn_id
-            .cmp(&b.turn_id)
-            .then_with(|| a.ts.cmp(&b.ts))
-            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    let path = ingest_errors_path(&paths.state);
+    if clear {
+        let count = IngestErrors::load(&path)?.line_count();
+        IngestErrors::default().save(&path)?;
+        println!("cleared {count} malformed lines");
+        return Ok(());
+    }
+    let errors = IngestErrors::load(&path)?;
+    let files = errors.files.iter().filter(|(_, file)| {
+        source.is_none_or(|filter| {
+            crate::types::SourceKind::from_label(&file.source)
+                .is_some_and(|kind| filter.matches(kind))
+        })
     });
-    if verbose {
-        for record in records {
-            let ts = format_ts(record.ts);
-            println!("{ts} {}", record.role);
-            if record.text.is_empty() {
-                println!("  <empty>");
-                continue;
-            }
-            for line in record.text.lines() {
-                println!("  {line}");
-            }
+    let mut stdout = std::io::stdout().lock();
+    let mut any = false;
+    for (file_path, file) in files {
+        any = true;
+        if json {
+            let entry = serde_json::json!({
+                "path": file_path,
+                "source": file.source,
+                "found_at": file.found_at,
+                "total": file.total,
+                "lines": file.lines,
+            });
+            writeln!(stdout, "{entry}")?;
+            continue;
+        }
+        writeln!(
+            stdout,
+            "{file_path} ({}, {} malformed line{})",
+            file.source,
+            file.total,
+            if file.total == 1 { "" } else { "s" }
+        )?;
+        for line in &file.lines {
+            writeln!(
+                stdout,
+                "  line {}: {}",
+                line.line,
+                summarize(&line.error, 160)
+            )?;
+        }
+        if file.total > file.lines.len() {
+            writeln!(stdout, "  ... and {} more", file.total - file.lines.len())?;
         }
-        return Ok(());
     }
-    for record in records {
-        println!("{}", serde_json::to_string(&record)?);
+    if !any && !json {
+        writeln!(stdout, "no malformed lines")?;
     }
     Ok(())
 }
 
-fn run_show(doc_id: u64, verbose: bool, root: Option<PathBuf>) -> Result<()> {
-    let paths = Paths::new(root)?;
-    let index = SearchIndex::open_or_create(&paths.index)?;
-    let record = index
-        .get_by_doc_id(doc_id)?
-        .ok_or_else(|| anyhow!("doc_id not found"))?;
-    if verbose {
-        println!("{}", serde_json::to_string_pretty(&record)?);
+fn run_scan(args: &IndexArgs, explain: Option<&Path>) -> Result<()> {
+    let paths = Paths::new(args.root.clone())?;
+    let config = UserConfig::load(&paths)?;
+    let opts = IngestOptions {
+        claude_source: args.source.clone().unwrap_or_else(default_claude_source),
+        include_agents: args.include_agents,
+        include_codex: args.codex && !args.no_codex,
+        include_opencode: args.opencode && !args.no_opencode,
+        include_cursor: args.cursor,
+        include_pi: args.pi && !args.no_pi,
+        include_copilot: args.copilot && !args.no_copilot,
+        embeddings: false,
+        backfill_embeddings: false,
+        model: config.resolve_model(args.model.clone())?,
+        embed_runtime: config.resolve_embed_runtime()?,
+        tool_content_limits: config.indexed_tool_content_limits()?,
+        max_threads: config.max_cpu_threads()?,
+        ocr_images: config.ocr_images(),
+        embed_listener: None,
+        archive_tier: None,
+    };
+    let mut stdout = std::io::stdout().lock();
+    if let Some(path) = explain {
+        let explanation = explain_scan(&paths, &opts, path, config.scan_cache_ttl())?;
+        print_scan_explanation(&mut stdout, &explanation, config.scan_cache_ttl())?;
         return Ok(());
     }
-    println!("{}", serde_json::to_string(&record)?);
+
+    let decisions = plan_scan(&paths, &opts)?;
+    let read: Vec<&FileDecision> = decisions
+        .iter()
+        .filter(|file| !file.decision.skipped())
+        .collect();
+    writeln!(
+        stdout,
+        "would read {} of {} files ({} unchanged)",
+        crate::progress::format_count(read.len() as u64),
+        crate::progress::format_count(decisions.len() as u64),
+        crate::progress::format_count((decisions.len() - read.len()) as u64)
+    )?;
+    for file in read {
+        writeln!(
+            stdout,
+            "  {:<10} {:<8} {}",
+            file.decision.label(),
+            file.source.label(),
+            file.path.display()
+        )?;
+    }
+    Ok(())
+}
+
+fn print_scan_explanation(
+    out: &mut impl Write,
+    explanation: &ScanExplanation,
+    ttl_seconds: u64,
+) -> Result<()> {
+    writeln!(out, "{}", explanation.path.display())?;
+    match (&explanation.decision, &explanation.unlisted) {
+        (Some(file), _) => {
+            writeln!(out, "  source: {}", file.source.label())?;
+            writeln!(
+                out,
+                "  decision: {}",
+                if file.decision.skipped() {
+                    format!("skipped ({})", file.decision.label())
+                } else {
+                    format!("read ({})", file.decision.label())
+                }
+            )?;
+            writeln!(out, "  why: {}", file.reason())?;
+        }
+        (None, Some(reason)) => writeln!(out, "  not scanned: {reason}")?,
+        (None, None) => {}
+    }
+    match &explanation.state {
+        Some(state) => {
+            writeln!(
+                out,
+                "  ingest.json: size {}, mtime {}, offset {}, turn {}",
+                state.size, state.mtime, state.offset, state.turn_id
+            )?;
+            if explanation.decision.is_none() {
+                writeln!(
+                    out,
+                    "  its records from an earlier ingest stay indexed until `memex reindex`"
+                )?;
+            }
+        }
+        None => writeln!(out, "  ingest.json: no entry")?,
+    }
+    if explanation.malformed_lines > 0 {
+        writeln!(
+            out,
+            "  malformed lines skipped: {} (see `memex ingest-errors`)",
+            explanation.malformed_lines
+        )?;
+    }
+    if explanation.last_scan_ts == 0 {
+        writeln!(out, "  scan_cache.json: no scan recorded yet")?;
+    } else {
+        let at = chrono::DateTime::from_timestamp(explanation.last_scan_ts as i64, 0)
+            .unwrap_or_default()
+            .format("%Y-%m-%d %H:%M:%S UTC");
+        writeln!(out, "  scan_cache.json: last full scan at {at}")?;
+        if explanation.scan_cache_fresh {
+            writeln!(
+                out,
+                "  searches that index first skip scanning until scan_cache_ttl ({ttl_seconds}s) \
+                 has passed; `memex index` always scans"
+            )?;
+        }
+    }
     Ok(())
 }
 
@@ -1405,11 +6762,133 @@ fn run_stats(root: Option<PathBuf>) -> Result<()> {
     let paths = Paths::new(root)?;
     let index = SearchIndex::open_or_create(&paths.index)?;
     println!("index: {}", paths.index.display());
+    println!("schema: v{}", crate::index::SCHEMA_VERSION);
     println!("documents: {}", index.doc_count()?);
+    let store = index.store_stats()?;
+    println!(
+        "stored records: {} ({})",
+        crate::progress::format_bytes(store.bytes),
+        store.compression
+    );
     print_vector_stats(&paths.vectors)?;
     Ok(())
 }
 
+fn run_activity_stats(
+    by: Bucket,
+    source: Option<SourceFilter>,
+    since: Option<String>,
+    until: Option<String>,
+    json: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let since = parse_ts_millis(since)?;
+    let until = parse_ts_millis(until)?;
+    let store = open_session_store(&paths)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let records = index.matching_timestamps(&QueryOptions {
+        query: String::new(),
+        project: None,
+        role: None,
+        tool: None,
+        session_id: None,
+        source,
+        since,
+        until,
+        as_of: None,
+        settings: AgentSettings::default(),
+        has: Vec::new(),
+        edited: Vec::new(),
+        commands: Vec::new(),
+        files: Vec::new(),
+        langs: Vec::new(),
+        errors: Vec::new(),
+        require_all_terms: false,
+        fuzzy_distance: 0,
+        tuning: QueryTuning::default(),
+        limit: 0,
+    })?;
+    let session_starts = store.query_session_starts(source, since, until)?;
+    let report = bucket_activity(by, &records, &session_starts, &chrono::Local);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if report.rows.is_empty() {
+        println!("no activity in range");
+    } else {
+        print!("{}", render_chart(&report));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_stats_export(
+    anonymize: bool,
+    salt: Option<String>,
+    source: Option<SourceFilter>,
+    since: Option<String>,
+    until: Option<String>,
+    markdown: bool,
+    output: Option<PathBuf>,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let since_ms = parse_ts_millis(since.clone())?;
+    let until_ms = parse_ts_millis(until.clone())?;
+    let store = open_session_store(&paths)?;
+    let sessions: Vec<_> = store
+        .query_sessions(
+            source,
+            since_ms,
+            None,
+            crate::analytics::ProjectGrouping::Repository,
+            None,
+        )?
+        .into_iter()
+        .filter(|row| until_ms.is_none_or(|until| row.started_at <= until))
+        .collect();
+    let usage = if config.token_usage_enabled() {
+        Some(scan_usage(&UsageQuery {
+            source,
+            project: None,
+            project_grouping: crate::analytics::ProjectGrouping::Repository,
+            session_keys: None,
+            since_ms,
+            until_ms,
+            cost_mode: CostMode::Auto,
+            include_events: true,
+            cache_path: Some(paths.state.join("usage-cache.sqlite3")),
+            memo_ttl_ms: 0,
+        })?)
+    } else {
+        None
+    };
+    let options = crate::stats_export::ExportOptions {
+        anonymize,
+        salt: salt.unwrap_or_else(crate::stats_export::random_salt),
+        since,
+        until,
+    };
+    let export = crate::stats_export::build_export(&sessions, usage.as_ref(), &options);
+    let mut report = if markdown {
+        crate::stats_export::render_markdown(&export)
+    } else {
+        serde_json::to_string_pretty(&export)?
+    };
+    if !report.ends_with('\n') {
+        report.push('\n');
+    }
+    match output {
+        Some(path) => {
+            std::fs::write(&path, report)?;
+            eprintln!("wrote {}", path.display());
+        }
+        None => print!("{report}"),
+    }
+    Ok(())
+}
+
 fn run_usage(
     source: Option<SourceFilter>,
     since: Option<String>,
@@ -1424,7 +6903,7 @@ fn run_usage(
     if !config.token_usage_enabled() {
         return Err(anyhow!(
             "token usage tracking is disabled; set `token_usage = true` in {}",
-            paths.root.join("config.toml").display()
+            paths.config.display()
         ));
     }
     let query = UsageQuery {
@@ -1596,7 +7075,7 @@ fn print_usage_table(report: &crate::usage::UsageReport) {
     let mut widths = [0usize; 10];
     for row in &table {
         for (width, cell) in widths.iter_mut().zip(row) {
-            *width = (*width).max(cell.len());
+            *width = (*width).max(text::width(cell));
         }
     }
     for row in &table {
@@ -1606,9 +7085,9 @@ fn print_usage_table(report: &crate::usage::UsageReport) {
                 line.push_str("  ");
             }
             if index == 0 {
-                line.push_str(&format!("{cell:<width$}"));
+                line.push_str(&pad_end(cell, *width));
             } else {
-                line.push_str(&format!("{cell:>width$}"));
+                line.push_str(&pad_start(cell, *width));
             }
         }
         println!("{}", line.trim_end());
@@ -1655,9 +7134,61 @@ fn format_usd(value: f64) -> String {
     }
 }
 
+/// Widest a text cell gets in `memex query` table output.
+const QUERY_CELL_MAX_CHARS: usize = 80;
+
+fn run_query(sql: &str, json: bool, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let query = RecordQuery::open(&index, Some(&analytics_path(&paths.state)))?;
+    let result = query.run(sql)?;
+    let mut stdout = std::io::stdout().lock();
+    if json {
+        for row in result.rows {
+            let object: serde_json::Map<String, Value> =
+                result.columns.iter().cloned().zip(row).collect();
+            writeln!(stdout, "{}", Value::Object(object))?;
+        }
+        return Ok(());
+    }
+    let mut table = vec![result.columns.clone()];
+    table.extend(result.rows.iter().map(|row| {
+        row.iter()
+            .map(|value| match value {
+                Value::Null => "NULL".to_string(),
+                Value::String(text) => one_line(text, QUERY_CELL_MAX_CHARS),
+                other => other.to_string(),
+            })
+            .collect()
+    }));
+    let mut widths = vec![0usize; result.columns.len()];
+    for row in &table {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(text::width(cell));
+        }
+    }
+    for (row_index, row) in table.iter().enumerate() {
+        let mut line = String::new();
+        for (index, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            if index > 0 {
+                line.push_str("  ");
+            }
+            let numeric = row_index > 0 && result.rows[row_index - 1][index].is_number();
+            if numeric {
+                line.push_str(&pad_start(cell, *width));
+            } else {
+                line.push_str(&pad_end(cell, *width));
+            }
+        }
+        writeln!(stdout, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
 fn run_analytics_backfill(root: Option<PathBuf>) -> Result<()> {
     let paths = Paths::new(root)?;
     paths.ensure_dirs()?;
+    let _lock = WriteLock::acquire(&paths.state)?;
     let index = SearchIndex::open_or_create(&paths.index)?;
     let db = analytics_path(&paths.state);
     backfill_from_index(&db, &index)?;
@@ -1670,27 +7201,27 @@ fn run_analytics_backfill(root: Option<PathBuf>) -> Result<()> {
 
 fn print_vector_stats(vectors_dir: &std::path::Path) -> Result<()> {
     println!("{}", vector_stats_line(vectors_dir)?);
+    let archive = archive_vectors_dir(vectors_dir);
+    if vector_store_exists(&archive) {
+        println!("archive {}", vector_stats_line(&archive)?);
+    }
     Ok(())
 }
 
 fn vector_stats_line(vectors_dir: &std::path::Path) -> Result<String> {
-    if !vectors_dir.join("usearch.index").exists() {
+    let Some(stored) = StoredVectors::read(vectors_dir)? else {
         return Ok("vectors: none".to_string());
-    }
+    };
     let vector = VectorIndex::open(vectors_dir)?;
-    let index_path = vectors_dir.join("usearch.index");
-    let ids_path = vectors_dir.join("doc_ids.bin");
-    let index_bytes = std::fs::metadata(&index_path).map(|m| m.len()).unwrap_or(0);
-    let ids_bytes = std::fs::metadata(&ids_path).map(|m| m.len()).unwrap_or(0);
     let model = vector.model().unwrap_or("unknown");
     Ok(format!(
-        "vectors: {} (dims {}, model {}, ids {}, usearch.index {}, doc_ids.bin {})",
+        "vectors: {} (dims {}, model {}, ids {}, segments {}, {} bytes)",
         vector.len(),
         vector.dimensions(),
         model,
         vector.doc_id_count(),
-        index_bytes,
-        ids_bytes
+        stored.segments,
+        stored.bytes
     ))
 }
 
@@ -1927,62 +7458,377 @@ fn run_setup(force: bool) -> Result<()> {
     Ok(())
 }
 
-fn run_share(session_id: String, title: Option<String>, root: Option<PathBuf>) -> Result<()> {
-    // Check if agentexport is installed
-    let agentexport_path = find_in_path("agentexport");
-    if agentexport_path.is_none() {
+fn run_share(session_id: String, title: Option<String>, root: Option<PathBuf>) -> Result<()> {
+    // Check if agentexport is installed
+    let agentexport_path = find_in_path("agentexport");
+    if agentexport_path.is_none() {
+        return Err(anyhow!(
+            "agentexport not found in PATH. Install it with: brew install nicosuave/tap/agentexport"
+        ));
+    }
+
+    // Open index and find session
+    let paths = Paths::new(root)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let records = index.records_by_session_id(&session_id)?;
+
+    if records.is_empty() {
+        return Err(anyhow!("session not found: {session_id}"));
+    }
+
+    // Get source info from first record
+    let record = &records[0];
+    let tool = match record.source {
+        crate::types::SourceKind::Claude => "claude",
+        crate::types::SourceKind::CodexSession | crate::types::SourceKind::CodexHistory => "codex",
+        crate::types::SourceKind::Opencode => "opencode",
+        crate::types::SourceKind::Cursor => "cursor",
+        crate::types::SourceKind::Pi => "pi",
+        crate::types::SourceKind::Copilot => "copilot",
+        crate::types::SourceKind::ChatGPT | crate::types::SourceKind::External(_) => {
+            return Err(anyhow!(
+                "sharing is not supported for imported or external sources"
+            ));
+        }
+    };
+    let source_path = &record.source_path;
+
+    // Build agentexport command
+    let mut cmd = std::process::Command::new("agentexport");
+    cmd.args(["publish", "--tool", tool, "--transcript", source_path]);
+    if let Some(t) = &title {
+        cmd.args(["--title", t]);
+    }
+
+    // Run command and capture output
+    let output = cmd.output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("agentexport failed: {stderr}"));
+    }
+
+    // Print the share URL (agentexport prints URL to stdout)
+    let url = String::from_utf8_lossy(&output.stdout);
+    let url = url.trim();
+    if url.is_empty() {
+        return Err(anyhow!("agentexport returned no URL"));
+    }
+
+    println!("{url}");
+    Ok(())
+}
+
+fn run_share_bundle(
+    session_id: String,
+    title: Option<String>,
+    output: Option<PathBuf>,
+    format: Option<ShareFormat>,
+    collapse_tools: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let mut records = index.records_by_session_id(&session_id)?;
+    if records.is_empty() {
+        return Err(anyhow!("session not found: {session_id}"));
+    }
+    records.sort_by(|a, b| {
+        a.turn_id
+            .cmp(&b.turn_id)
+            .then_with(|| a.ts.cmp(&b.ts))
+            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    });
+    let format = format.unwrap_or_else(|| {
+        let html = output
+            .as_ref()
+            .and_then(|path| path.extension())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"));
+        if html {
+            ShareFormat::Html
+        } else {
+            ShareFormat::Markdown
+        }
+    });
+    let options = BundleOptions {
+        title,
+        collapse_tools,
+        home: directories::BaseDirs::new()
+            .map(|dirs| dirs.home_dir().to_string_lossy().into_owned()),
+    };
+    let bundle = match format {
+        ShareFormat::Html => crate::bundle::to_html(&records, &options),
+        ShareFormat::Markdown => crate::bundle::to_markdown(&records, &options),
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(&path, bundle)?;
+            eprintln!(
+                "wrote {} (secrets redacted; review before sharing)",
+                path.display()
+            );
+        }
+        None => print!("{bundle}"),
+    }
+    Ok(())
+}
+
+/// The configured team server, or `url` when given.
+fn team_remote(config: &UserConfig, url: Option<String>) -> Result<TeamRemote> {
+    if config.offline() {
         return Err(anyhow!(
-            "agentexport not found in PATH. Install it with: brew install nicosuave/tap/agentexport"
+            "network access is disabled (offline); not contacting the team server"
         ));
     }
+    let url = url.or_else(|| config.team_url()).ok_or_else(|| {
+        anyhow!("no team server: set `team_url` in config.toml or MEMEX_TEAM_URL")
+    })?;
+    Ok(TeamRemote::new(&url, config.team_token()))
+}
 
-    // Open index and find session
+fn run_push(url: Option<String>, all: bool, dry_run: bool, root: Option<PathBuf>) -> Result<()> {
     let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let remote = team_remote(&config, url)?;
     let index = SearchIndex::open_or_create(&paths.index)?;
-    let records = index.records_by_session_id(&session_id)?;
-
+    let state_path = push_state_path(&paths.state);
+    let mut state = PushState::load(&state_path)?;
+    let after = if all {
+        RecordCursor::default()
+    } else {
+        state
+            .pushed_through
+            .get(remote.url())
+            .copied()
+            .unwrap_or_default()
+    };
+    let projects = config.team_projects.clone().unwrap_or_default();
+    let records = records_to_push(&index, after, &projects)?;
     if records.is_empty() {
-        return Err(anyhow!("session not found: {session_id}"));
+        println!("nothing new to push to {}", remote.url());
+        return Ok(());
+    }
+    let sessions = records
+        .iter()
+        .map(|record| record.session_id.as_str())
+        .collect::<HashSet<_>>()
+        .len();
+    if dry_run {
+        println!(
+            "would push {} records from {sessions} sessions to {}",
+            records.len(),
+            remote.url()
+        );
+        return Ok(());
     }
 
-    // Get source info from first record
-    let record = &records[0];
-    let tool = match record.source {
-        crate::types::SourceKind::Claude => "claude",
-        crate::types::SourceKind::CodexSession | crate::types::SourceKind::CodexHistory => "codex",
-        crate::types::SourceKind::Opencode => "opencode",
-        crate::types::SourceKind::Cursor => "cursor",
-        crate::types::SourceKind::Pi => "pi",
-        crate::types::SourceKind::Copilot => "copilot",
-    };
-    let source_path = &record.source_path;
+    let home =
+        directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_string_lossy().into_owned());
+    let author = config.team_name();
+    let mut total = PushResponse::default();
+    for batch in records.chunks(PUSH_BATCH) {
+        let shared = batch
+            .iter()
+            .map(|record| sanitize(record, home.as_deref()))
+            .collect();
+        let response = remote.push(&author, shared)?;
+        total.accepted += response.accepted;
+        total.duplicates += response.duplicates;
+        // Saved per batch so an interrupted push resumes where it stopped.
+        if let Some(last) = batch.last() {
+            state
+                .pushed_through
+                .insert(remote.url().to_string(), RecordCursor::of(last));
+            state.save(&state_path)?;
+        }
+    }
+    println!(
+        "pushed {} records from {sessions} sessions to {} as {author} ({} already there)",
+        total.accepted,
+        remote.url(),
+        total.duplicates
+    );
+    Ok(())
+}
 
-    // Build agentexport command
-    let mut cmd = std::process::Command::new("agentexport");
-    cmd.args(["publish", "--tool", tool, "--transcript", source_path]);
-    if let Some(t) = &title {
-        cmd.args(["--title", t]);
+fn elastic_target(
+    config: &UserConfig,
+    url: Option<String>,
+    index: Option<String>,
+) -> Result<ElasticTarget> {
+    if config.offline() {
+        return Err(anyhow!(
+            "network access is disabled (offline); not contacting the cluster"
+        ));
     }
+    let url = url.or_else(|| config.elasticsearch_url()).ok_or_else(|| {
+        anyhow!("no cluster: pass --url, or set `elasticsearch_url` in config.toml or MEMEX_ELASTICSEARCH_URL")
+    })?;
+    let index = index.unwrap_or_else(|| config.elasticsearch_index());
+    Ok(ElasticTarget::new(
+        &url,
+        &index,
+        config.elasticsearch_auth(),
+    ))
+}
 
-    // Run command and capture output
-    let output = cmd.output()?;
+/// Which records `memex export` sends.
+#[derive(Default)]
+struct ExportFilter {
+    project: Option<String>,
+    source: Option<SourceFilter>,
+    all: bool,
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow!("agentexport failed: {stderr}"));
-    }
+/// Records after `after` that `filter` lets through, oldest first. Notes
+/// and turns stay local.
+fn export_records(
+    index: &SearchIndex,
+    after: RecordCursor,
+    filter: &ExportFilter,
+) -> Result<Vec<crate::types::Record>> {
+    let mut records = Vec::new();
+    index.for_each_record(|record| {
+        if after.precedes(&record)
+            && record.role != NOTE_ROLE
+            && record.role != TURN_ROLE
+            && filter
+                .project
+                .as_ref()
+                .is_none_or(|project| &record.project == project)
+            && filter
+                .source
+                .is_none_or(|source| source.matches(record.source))
+        {
+            records.push(record);
+        }
+        Ok(())
+    })?;
+    records.sort_by_key(RecordCursor::of);
+    Ok(records)
+}
 
-    // Print the share URL (agentexport prints URL to stdout)
-    let url = String::from_utf8_lossy(&output.stdout);
-    let url = url.trim();
-    if url.is_empty() {
-        return Err(anyhow!("agentexport returned no URL"));
+fn run_export(
+    url: Option<String>,
+    index_name: Option<String>,
+    filter: ExportFilter,
+    output: Option<PathBuf>,
+    dry_run: bool,
+    root: Option<PathBuf>,
+) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+    let home =
+        directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_string_lossy().into_owned());
+    if let Some(output) = output {
+        let records = export_records(&index, RecordCursor::default(), &filter)?;
+        let index_name = index_name.unwrap_or_else(|| config.elasticsearch_index());
+        let body = crate::elastic::bulk_body(&index_name, &records, home.as_deref())?;
+        if output == Path::new("-") {
+            std::io::stdout().write_all(body.as_bytes())?;
+        } else {
+            std::fs::write(&output, body)?;
+            eprintln!("wrote {} records to {}", records.len(), output.display());
+        }
+        return Ok(());
     }
 
-    println!("{url}");
+    let target = elastic_target(&config, url, index_name)?;
+    if dry_run {
+        let after = if filter.all {
+            RecordCursor::default()
+        } else {
+            ExportState::load(&export_state_path(&paths.state))?
+                .exported_through
+                .get(&target.key())
+                .copied()
+                .unwrap_or_default()
+        };
+        let records = export_records(&index, after, &filter)?;
+        println!(
+            "would export {} records to {} index {}",
+            records.len(),
+            target.url(),
+            target.index()
+        );
+        return Ok(());
+    }
+    let outcome = export_to_cluster(&paths, &index, &target, &filter, home.as_deref())?;
+    print_export_report(&target, &outcome);
+    if outcome.failed > 0 {
+        return Err(anyhow!(
+            "{} records were rejected; the next export sends them again",
+            outcome.failed
+        ));
+    }
     Ok(())
 }
 
+/// Sends records indexed since the last export to `target` in bulk
+/// batches. The cursor moves after each batch the cluster took whole, so an
+/// interrupted or partly rejected export resumes where it stopped.
+fn export_to_cluster(
+    paths: &Paths,
+    index: &SearchIndex,
+    target: &ElasticTarget,
+    filter: &ExportFilter,
+    home: Option<&str>,
+) -> Result<BulkOutcome> {
+    let state_path = export_state_path(&paths.state);
+    let mut state = ExportState::load(&state_path)?;
+    let after = if filter.all {
+        RecordCursor::default()
+    } else {
+        state
+            .exported_through
+            .get(&target.key())
+            .copied()
+            .unwrap_or_default()
+    };
+    let records = export_records(index, after, filter)?;
+    let mut total = BulkOutcome::default();
+    for batch in records.chunks(BULK_BATCH) {
+        let outcome = target.send(batch, home)?;
+        total.indexed += outcome.indexed;
+        total.failed += outcome.failed;
+        if total.first_error.is_none() {
+            total.first_error = outcome.first_error;
+        }
+        if outcome.failed > 0 {
+            break;
+        }
+        if let Some(last) = batch.last() {
+            state
+                .exported_through
+                .insert(target.key(), RecordCursor::of(last));
+            state.save(&state_path)?;
+        }
+    }
+    Ok(total)
+}
+
+fn print_export_report(target: &ElasticTarget, outcome: &BulkOutcome) {
+    println!(
+        "exported {} records to {} index {}",
+        outcome.indexed,
+        target.url(),
+        target.index()
+    );
+    if let Some(error) = &outcome.first_error {
+        println!("{} records rejected, first: {error}", outcome.failed);
+    }
+}
+
+fn run_serve(listen: &str, root: Option<PathBuf>) -> Result<()> {
+    let paths = Paths::new(root)?;
+    let config = UserConfig::load(&paths)?;
+    let store = TeamStore::open(&paths)?;
+    let listener = std::net::TcpListener::bind(listen)
+        .map_err(|err| anyhow!("failed to listen on {listen}: {err}"))?;
+    crate::team::serve(listener, store, config.team_token())
+}
+
 fn run_transfer(
     session_id: String,
     source: Option<SourceFilter>,
@@ -2006,11 +7852,16 @@ fn run_transfer(
         },
     )?;
 
-    println!("generated: {}", result.generated_path.display());
+    let links = Hyperlinks::for_stdout(&UserConfig::load(&paths)?);
+    let generated = result.generated_path.display().to_string();
+    println!("generated: {}", links.path(&generated, &generated));
     println!("source: {}", result.source.label());
     println!("session: {}", result.session_id);
     println!("messages: {}", result.message_count);
-    println!("source_path: {}", result.source_path);
+    println!(
+        "source_path: {}",
+        links.path(&result.source_path, &result.source_path)
+    );
     if let Some(thread_id) = result.thread_id {
         println!("codex_thread: {thread_id}");
     }
@@ -2020,17 +7871,6 @@ fn run_transfer(
     Ok(())
 }
 
-fn find_in_path(binary: &str) -> Option<PathBuf> {
-    let path_var = std::env::var_os("PATH")?;
-    for dir in std::env::split_paths(&path_var) {
-        let candidate = dir.join(binary);
-        if candidate.is_file() && is_executable(&candidate) {
-            return Some(candidate);
-        }
-    }
-    None
-}
-
 fn pi_agent_root() -> PathBuf {
     if let Some(root) = std::env::var_os("PI_CODING_AGENT_DIR") {
         return PathBuf::from(root);
@@ -2041,19 +7881,6 @@ fn pi_agent_root() -> PathBuf {
     home.join(".pi").join("agent")
 }
 
-#[cfg(unix)]
-fn is_executable(path: &std::path::Path) -> bool {
-    use std::os::unix::fs::PermissionsExt;
-    std::fs::metadata(path)
-        .map(|meta| meta.permissions().mode() & 0o111 != 0)
-        .unwrap_or(false)
-}
-
-#[cfg(not(unix))]
-fn is_executable(path: &std::path::Path) -> bool {
-    path.is_file()
-}
-
 #[allow(clippy::too_many_arguments)]
 fn run_index_service_enable(
     index: &IndexArgs,
@@ -2145,8 +7972,10 @@ fn disable_auto_index_on_search_by_default(paths: &Paths, config: &UserConfig) -
         return Ok(());
     }
 
-    std::fs::create_dir_all(&paths.root)?;
-    let path = paths.root.join("config.toml");
+    let path = paths.config.clone();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
     let mut contents = if path.exists() {
         std::fs::read_to_string(&path)?
     } else {
@@ -2585,6 +8414,7 @@ fn build_index_command_args(
     if index.no_embeddings {
         args.push("--no-embeddings".to_string());
     }
+    args.push("--service".to_string());
     if continuous {
         args.push("--watch".to_string());
         args.push("--watch-interval".to_string());
@@ -2801,67 +8631,21 @@ fn build_systemd_timer(interval: u64) -> String {
 }
 
 fn parse_ts_millis(value: Option<String>) -> Result<Option<u64>> {
-    let Some(value) = value else {
-        return Ok(None);
-    };
-    if value.chars().all(|c| c.is_ascii_digit()) {
-        let num: u64 = value.parse()?;
-        if num > 10_000_000_000 {
-            return Ok(Some(num));
-        }
-        return Ok(Some(num * 1000));
-    }
-    if let Ok(date) = chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
-        let midnight = date
-            .and_hms_opt(0, 0, 0)
-            .ok_or_else(|| anyhow!("invalid date: {value}"))?
-            .and_utc();
-        return Ok(Some(midnight.timestamp_millis() as u64));
-    }
-    let dt = chrono::DateTime::parse_from_rfc3339(&value)
-        .map_err(|_| anyhow!("invalid timestamp: {value}"))?;
-    Ok(Some(dt.timestamp_millis() as u64))
+    value
+        .map(|value| crate::moment::parse_bound_ms(&value, chrono::Local::now()))
+        .transpose()
 }
 
-fn summarize(text: &str, max: usize) -> String {
-    if max == 0 {
-        return String::new();
-    }
-    let mut out = String::new();
-    let mut count = 0usize;
-    let mut last_space = false;
-    let mut truncated = false;
-    for ch in text.chars() {
-        if count >= max {
-            truncated = true;
-            break;
-        }
-        if ch.is_whitespace() {
-            if out.is_empty() || last_space {
-                continue;
-            }
-            out.push(' ');
-            last_space = true;
-            count += 1;
-            continue;
-        }
-        out.push(ch);
-        last_space = false;
-        count += 1;
-    }
-    if truncated && max >= 3 {
-        let keep = max.saturating_sub(3);
-        let mut short = String::new();
-        for (i, ch) in out.chars().enumerate() {
-            if i >= keep {
-                break;
-            }
-            short.push(ch);
-        }
-        short.push_str("...");
-        return short.trim().to_string();
-    }
-    out.trim().to_string()
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ShareFormat {
+    Html,
+    Markdown,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Mermaid,
+    Dot,
 }
 
 #[derive(Clone, Copy, ValueEnum)]
@@ -2964,22 +8748,10 @@ fn format_ts(ts: u64) -> String {
     dt.to_rfc3339_opts(SecondsFormat::Secs, true)
 }
 
-fn build_matchers(query: &str) -> Result<Vec<regex::Regex>> {
-    let mut terms = Vec::new();
-    let mut seen = std::collections::HashSet::new();
-    for part in query.split_whitespace() {
-        let cleaned = part.trim_matches(|c: char| !c.is_alphanumeric());
-        if cleaned.len() < 2 {
-            continue;
-        }
-        let key = cleaned.to_lowercase();
-        if seen.insert(key.clone()) {
-            terms.push(key);
-        }
-    }
+fn build_matchers(terms: &[String]) -> Result<Vec<regex::Regex>> {
     let mut out = Vec::new();
     for term in terms {
-        let re = RegexBuilder::new(&regex::escape(&term))
+        let re = RegexBuilder::new(&regex::escape(term))
             .case_insensitive(true)
             .build()?;
         out.push(re);
@@ -2999,8 +8771,8 @@ fn collect_matches(text: &str, matchers: &[regex::Regex], max: usize) -> Vec<Mat
             }
             let start = m.start();
             let end = m.end();
-            let before = take_last_chars(&text[..start], 40);
-            let after = take_first_chars(&text[end..], 40);
+            let before = take_width_end(&text[..start], 40).to_string();
+            let after = take_width(&text[end..], 40).to_string();
             out.push(MatchSpan {
                 start,
                 end,
@@ -3013,18 +8785,6 @@ fn collect_matches(text: &str, matchers: &[regex::Regex], max: usize) -> Vec<Mat
     out
 }
 
-fn take_last_chars(text: &str, max: usize) -> String {
-    let mut out = Vec::new();
-    for ch in text.chars().rev().take(max) {
-        out.push(ch);
-    }
-    out.into_iter().rev().collect()
-}
-
-fn take_first_chars(text: &str, max: usize) -> String {
-    text.chars().take(max).collect()
-}
-
 fn is_embedding_role(role: &str) -> bool {
     role == "user" || role == "assistant"
 }
@@ -3067,7 +8827,34 @@ fn is_homebrew_install() -> bool {
         .unwrap_or(false)
 }
 
-fn run_update(skip_confirm: bool) -> Result<()> {
+/// Whether `offline = true` is set in the default config. Read before
+/// dispatch so background update checks never start.
+fn network_disabled(paths: &Paths) -> bool {
+    UserConfig::load(paths)
+        .map(|config| config.offline())
+        .unwrap_or(false)
+}
+
+/// The `--root` given to the invoked subcommand, at whatever depth of nested
+/// subcommands it sits.
+fn command_root(matches: &ArgMatches) -> Option<PathBuf> {
+    let mut root = None;
+    let mut current = Some(matches);
+    while let Some(matches) = current {
+        if let Ok(Some(value)) = matches.try_get_one::<PathBuf>("root") {
+            root = Some(value.clone());
+        }
+        current = matches.subcommand().map(|(_, sub)| sub);
+    }
+    root
+}
+
+fn run_update(skip_confirm: bool, paths: &Paths) -> Result<()> {
+    if network_disabled(paths) {
+        return Err(anyhow!(
+            "network access is disabled (`offline = true` in config.toml); not checking for updates"
+        ));
+    }
     if is_homebrew_install() {
         println!("memex was installed via Homebrew.");
         println!("Run 'brew upgrade memex' to update.");
@@ -3118,6 +8905,17 @@ fn run_update(skip_confirm: bool) -> Result<()> {
         return Err(anyhow!("Failed to download release"));
     }
 
+    let output = std::process::Command::new("curl")
+        .args(["-fsSL", &format!("{url}.sha256")])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to download release checksum; refusing to install an unverified binary"
+        ));
+    }
+    verify_sha256(&archive_path, &String::from_utf8_lossy(&output.stdout))?;
+    println!("Checksum verified.");
+
     // Extract
     let status = std::process::Command::new("tar")
         .args(["-xzf"])
@@ -3134,25 +8932,7 @@ fn run_update(skip_confirm: bool) -> Result<()> {
         return Err(anyhow!("Binary not found in release archive"));
     }
 
-    // Replace current binary
-    let current_exe = std::env::current_exe()?;
-    let backup = current_exe.with_extension("old");
-
-    // Move current to backup, move new to current
-    if backup.exists() {
-        std::fs::remove_file(&backup)?;
-    }
-    std::fs::rename(&current_exe, &backup)?;
-    std::fs::copy(&new_binary, &current_exe)?;
-
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        std::fs::set_permissions(&current_exe, std::fs::Permissions::from_mode(0o755))?;
-    }
-
-    // Remove backup
-    let _ = std::fs::remove_file(&backup);
+    replace_executable(&new_binary, &std::env::current_exe()?)?;
 
     println!("Updated memex to v{latest}");
     println!();
@@ -3160,6 +8940,48 @@ fn run_update(skip_confirm: bool) -> Result<()> {
     Ok(())
 }
 
+/// Checks `path` against a `sha256sum`/`shasum -a 256` style line
+/// (`<hex digest>  <file name>`).
+fn verify_sha256(path: &std::path::Path, checksum_file: &str) -> Result<()> {
+    use sha2::{Digest, Sha256};
+
+    let expected = checksum_file
+        .split_whitespace()
+        .next()
+        .filter(|digest| digest.len() == 64 && digest.chars().all(|ch| ch.is_ascii_hexdigit()))
+        .ok_or_else(|| anyhow!("Malformed release checksum"))?;
+    let actual = format!("{:x}", Sha256::digest(std::fs::read(path)?));
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(anyhow!(
+            "Checksum mismatch for downloaded release (expected {expected}, got {actual})"
+        ));
+    }
+    Ok(())
+}
+
+/// Stages `new_binary` next to `current_exe` and renames it into place, so the
+/// executable is never missing or half-written if the update is interrupted.
+fn replace_executable(new_binary: &std::path::Path, current_exe: &std::path::Path) -> Result<()> {
+    let dir = current_exe
+        .parent()
+        .ok_or_else(|| anyhow!("Cannot determine install directory"))?;
+    let staged = dir.join(format!(".memex-update-{}", std::process::id()));
+    let result = (|| -> Result<()> {
+        std::fs::copy(new_binary, &staged)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+        }
+        std::fs::rename(&staged, current_exe)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&staged);
+    }
+    result
+}
+
 fn fetch_latest_version() -> Result<String> {
     let output = std::process::Command::new("curl")
         .args([
@@ -3267,6 +9089,30 @@ mod tests {
     use crate::vector::VectorIndex;
     use tempfile::TempDir;
 
+    #[test]
+    fn pick_lines_keep_three_tab_separated_columns() {
+        assert_eq!(
+            pick_line("s1", "fix the\tflaky\n\nlogin test", "memex"),
+            "s1\tfix the flaky login test\tmemex"
+        );
+    }
+
+    #[test]
+    fn session_byte_range_spans_lines_mentioning_the_session() {
+        let tmp = TempDir::new().expect("tempdir");
+        let path = tmp.path().join("log.jsonl");
+        std::fs::write(
+            &path,
+            "{\"s\":\"a\"}\n{\"s\":\"b\"}\n{\"s\":\"c\"}\n{\"s\":\"b\"}\n",
+        )
+        .expect("write");
+        assert_eq!(
+            session_byte_range(&path, "b").expect("range"),
+            Some((10, 40))
+        );
+        assert_eq!(session_byte_range(&path, "z").expect("range"), None);
+    }
+
     #[test]
     fn build_index_command_args_preserves_disabled_sources() {
         let index = IndexArgs {
@@ -3339,9 +9185,8 @@ mod tests {
 
         let line = vector_stats_line(tmp.path()).unwrap();
 
-        assert!(line.starts_with("vectors: 1 (dims 64, model bge, ids 1,"));
-        assert!(line.contains("usearch.index"));
-        assert!(line.contains("doc_ids.bin"));
+        assert!(line.starts_with("vectors: 1 (dims 64, model bge, ids 1, segments 1,"));
+        assert!(!line.contains("usearch.index"));
         assert!(!line.contains("vectors.f32"));
         assert!(!line.contains("doc_ids.u64"));
     }
@@ -3353,6 +9198,47 @@ mod tests {
         assert_eq!(vector_stats_line(tmp.path()).unwrap(), "vectors: none");
     }
 
+    #[test]
+    fn vector_hits_from_both_tiers_merge_by_distance() {
+        let recent = vec![(1, 0.10), (2, 0.40), (3, 0.55)];
+        let archived = vec![(7, 0.05), (2, 0.30), (8, 0.60)];
+        assert_eq!(
+            merge_vector_hits(vec![recent.clone(), archived], 4),
+            vec![(7, 0.05), (1, 0.10), (2, 0.30), (3, 0.55)]
+        );
+        assert_eq!(merge_vector_hits(vec![recent.clone()], 3), recent);
+
+        let tier = ArchiveTier {
+            after_months: 6,
+            model: ModelChoice::Potion,
+        };
+        // 2026-07-15 -> 2026-01-15, both at midnight UTC.
+        assert_eq!(tier.cutoff_ms(1_784_073_600_000), 1_768_435_200_000);
+    }
+
+    #[test]
+    fn offline_config_is_read_from_the_commands_root() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let root = |args: &[&str]| {
+            command_root(&Cli::command().try_get_matches_from(args).expect("parse"))
+        };
+        let dir = tmp.path().to_str().expect("utf-8 path");
+        assert_eq!(
+            root(&["memex", "search", "auth", "--root", dir]).as_deref(),
+            Some(tmp.path())
+        );
+        assert_eq!(
+            root(&["memex", "shards", "list", "--root", dir]).as_deref(),
+            Some(tmp.path())
+        );
+        assert_eq!(root(&["memex", "update"]), None);
+
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        assert!(!network_disabled(&paths));
+        std::fs::write(tmp.path().join("config.toml"), "offline = true\n").expect("write config");
+        assert!(network_disabled(&paths));
+    }
+
     #[test]
     fn index_args_accept_negative_source_flags() {
         let cli = Cli::try_parse_from([
@@ -3424,4 +9310,190 @@ mod tests {
         ));
         assert!(service.contains("ExecStart=/usr/bin/memex index --no-pi\n"));
     }
+
+    #[test]
+    fn verify_sha256_accepts_matching_checksum_line() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("memex.tar.gz");
+        std::fs::write(&path, b"hello").unwrap();
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+        verify_sha256(
+            &path,
+            &format!("{digest}  memex-1.0.0-linux-x86_64.tar.gz\n"),
+        )
+        .unwrap();
+        verify_sha256(&path, &digest.to_uppercase()).unwrap();
+
+        let mismatch = verify_sha256(&path, &"0".repeat(64)).unwrap_err();
+        assert!(mismatch.to_string().contains("Checksum mismatch"));
+        let malformed = verify_sha256(&path, "<html>not found</html>").unwrap_err();
+        assert!(malformed.to_string().contains("Malformed"));
+    }
+
+    #[test]
+    fn replace_executable_swaps_file_without_leaving_staged_copy() {
+        let tmp = TempDir::new().unwrap();
+        let current = tmp.path().join("bin").join("memex");
+        std::fs::create_dir_all(current.parent().unwrap()).unwrap();
+        std::fs::write(&current, b"old").unwrap();
+        let new_binary = tmp.path().join("memex-new");
+        std::fs::write(&new_binary, b"new").unwrap();
+
+        replace_executable(&new_binary, &current).unwrap();
+
+        assert_eq!(std::fs::read(&current).unwrap(), b"new");
+        let entries: Vec<_> = std::fs::read_dir(current.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec![std::ffi::OsString::from("memex")]);
+    }
+
+    fn turn(doc_id: u64, role: &str, text: &str) -> crate::types::Record {
+        crate::types::Record {
+            source: crate::types::SourceKind::Claude,
+            doc_id,
+            ts: doc_id,
+            project: "memex".to_string(),
+            session_id: "s1".to_string(),
+            turn_id: doc_id as u32,
+            role: role.to_string(),
+            text: text.to_string(),
+            tool_name: None,
+            tool_input: None,
+            tool_output: None,
+            links: RecordLinks::default(),
+            settings: AgentSettings::default(),
+            attachments: Vec::new(),
+            source_path: String::new(),
+            ingested_at: 0,
+        }
+    }
+
+    #[test]
+    fn active_sessions_are_ordered_by_distance_to_the_moment() {
+        let at = |doc_id: u64, session_id: &str, role: &str, ts: u64| {
+            let mut record = turn(doc_id, role, "text");
+            record.session_id = session_id.to_string();
+            record.ts = ts;
+            record
+        };
+        let records = vec![
+            at(1, "before", "user", 1_000),
+            at(2, "during", "user", 4_000),
+            at(3, "during", "tool_result", 5_100),
+            at(4, "during", "assistant", 6_500),
+            at(5, "after", "assistant", 5_500),
+        ];
+        let sessions = active_sessions(records, 5_000, 10);
+        let order: Vec<&str> = sessions.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(order, ["during", "after", "before"]);
+        assert_eq!(sessions[0].distance_ms, 0);
+        assert_eq!(sessions[0].records, 3);
+        // Prompts and replies are preferred over a closer tool result.
+        assert_eq!(sessions[0].nearest.doc_id, 2);
+        assert_eq!(sessions[2].distance_ms, 4_000);
+        assert_eq!(active_sessions(Vec::new(), 5_000, 10).len(), 0);
+    }
+
+    #[test]
+    fn answer_to_collects_assistant_text_until_the_next_prompt() {
+        let session = vec![
+            turn(4, "user", "and then?"),
+            turn(2, "tool_result", "ls output"),
+            turn(1, "user", "how do I rotate a tantivy index"),
+            turn(
+                3,
+                "assistant",
+                "Write into a fresh directory and swap it in.",
+            ),
+            turn(5, "assistant", "unrelated"),
+            turn(2, "assistant", "Let me check."),
+        ];
+
+        assert_eq!(
+            answer_to(&session, &session[2]).as_deref(),
+            Some("Let me check.\nWrite into a fresh directory and swap it in.")
+        );
+        assert_eq!(
+            answer_to(&session, &session[0]).as_deref(),
+            Some("unrelated")
+        );
+        assert_eq!(answer_to(&session[..1], &session[2]), None);
+    }
+
+    #[test]
+    fn repeated_queries_merge_to_the_best_result_per_session() {
+        let cli = Cli::try_parse_from([
+            "memex",
+            "search",
+            "-q",
+            "tantivy segment merge",
+            "--query",
+            "index compaction",
+        ])
+        .expect("parse queries");
+        let Some(Commands::Search { query, queries, .. }) = cli.command else {
+            panic!("expected search command");
+        };
+        assert_eq!(query, None);
+        assert_eq!(queries, vec!["tantivy segment merge", "index compaction"]);
+
+        let mut other = turn(3, "assistant", "merge policy");
+        other.session_id = "s2".to_string();
+        let kept = best_per_session(vec![
+            (1.0, turn(1, "user", "segment merge")),
+            (2.0, other),
+            (3.0, turn(2, "assistant", "compaction")),
+        ]);
+        let kept: Vec<(f32, u64)> = kept
+            .iter()
+            .map(|(score, record)| (*score, record.doc_id))
+            .collect();
+        assert_eq!(kept, vec![(3.0, 2), (2.0, 3)]);
+    }
+
+    #[test]
+    fn an_interrupted_export_resumes_inside_one_index_run() {
+        let tmp = TempDir::new().expect("tempdir");
+        let index = SearchIndex::open_or_create(tmp.path()).expect("index");
+        let mut writer = index.writer().expect("writer");
+        // One `memex index` run: every record shares its ingest time.
+        for doc_id in 1..=4 {
+            let record = crate::types::Record {
+                ingested_at: 100,
+                ..turn(doc_id, "user", "deploy notes")
+            };
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+        let filter = ExportFilter::default();
+
+        let all = export_records(&index, RecordCursor::default(), &filter).expect("records");
+        // The first batch was taken, then the export was cut short.
+        let mut state = ExportState::default();
+        state
+            .exported_through
+            .insert("cluster".to_string(), RecordCursor::of(&all[1]));
+        let path = export_state_path(tmp.path());
+        state.save(&path).expect("save");
+        let after = ExportState::load(&path).expect("load").exported_through["cluster"];
+        let rest: Vec<u64> = export_records(&index, after, &filter)
+            .expect("rest")
+            .iter()
+            .map(|record| record.doc_id)
+            .collect();
+        assert_eq!(rest, [3, 4]);
+
+        // A cursor saved as a bare ingest time covers that whole run.
+        let old: ExportState =
+            serde_json::from_str(r#"{"exported_through":{"cluster":100}}"#).expect("old state");
+        let after = old.exported_through["cluster"];
+        assert!(
+            export_records(&index, after, &filter)
+                .expect("none")
+                .is_empty()
+        );
+    }
 }
@@ -1,33 +1,134 @@
 use crate::embed::{EmbedRuntimeConfig, ExecutionProviderChoice, ModelChoice};
-use anyhow::{Result, anyhow};
+use crate::lang::Translator;
+use crate::power::{DEFAULT_IDLE_CPU_PERCENT, IndexPolicy};
+use crate::scoring::{DEFAULT_PROFILE, ScoringProfile, ScoringProfileConfig};
+use crate::types::SourceFilter;
+use crate::vector::ArchiveTier;
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
 use directories::BaseDirs;
-use serde::Deserialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+/// Where memex keeps its files. `root` holds the index, vectors and
+/// everything else memex builds; state and config.toml live in it too,
+/// except in the XDG layout.
 #[derive(Debug, Clone)]
 pub struct Paths {
     pub root: PathBuf,
     pub index: PathBuf,
     pub vectors: PathBuf,
     pub state: PathBuf,
+    /// config.toml.
+    pub config: PathBuf,
+}
+
+/// How a memex home is laid out on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    /// Everything under one directory: `~/.memex`, `$MEMEX_HOME` or `--root`.
+    Single(PathBuf),
+    /// Data under `$XDG_DATA_HOME/memex`, state under `$XDG_STATE_HOME/memex`
+    /// and config.toml under `$XDG_CONFIG_HOME/memex`.
+    Xdg {
+        data: PathBuf,
+        state: PathBuf,
+        config: PathBuf,
+    },
+}
+
+impl Layout {
+    /// The layout used without `--root`: `$MEMEX_HOME`, else an existing
+    /// `~/.memex`, else XDG when an `XDG_*_HOME` variable is set or an XDG
+    /// store already exists, else `~/.memex`.
+    pub fn from_env() -> Result<Self> {
+        let base = BaseDirs::new().ok_or_else(|| anyhow!("missing home dir"))?;
+        Ok(Self::resolve(base.home_dir(), |name| {
+            std::env::var_os(name)
+        }))
+    }
+
+    fn resolve(home: &Path, var: impl Fn(&str) -> Option<OsString>) -> Self {
+        if let Some(root) = var("MEMEX_HOME").filter(|root| !root.is_empty()) {
+            return Layout::Single(PathBuf::from(root));
+        }
+        let legacy = home.join(".memex");
+        if legacy.exists() {
+            return Layout::Single(legacy);
+        }
+        let xdg_set = ["XDG_DATA_HOME", "XDG_STATE_HOME", "XDG_CONFIG_HOME"]
+            .iter()
+            .any(|name| var(name).is_some_and(|value| !value.is_empty()));
+        let xdg = Self::xdg(home, &var);
+        match &xdg {
+            Layout::Xdg { data, .. } if xdg_set || data.exists() => xdg,
+            _ => Layout::Single(legacy),
+        }
+    }
+
+    /// The XDG layout, with the spec's defaults for unset variables.
+    pub fn xdg(home: &Path, var: impl Fn(&str) -> Option<OsString>) -> Self {
+        let dir = |name: &str, default: &str| {
+            var(name)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+                .unwrap_or_else(|| home.join(default))
+                .join("memex")
+        };
+        Layout::Xdg {
+            data: dir("XDG_DATA_HOME", ".local/share"),
+            state: dir("XDG_STATE_HOME", ".local/state"),
+            config: dir("XDG_CONFIG_HOME", ".config"),
+        }
+    }
 }
 
 impl Paths {
     pub fn new(root_override: Option<PathBuf>) -> Result<Self> {
-        let root = match root_override {
-            Some(path) => path,
-            None => {
-                let base = BaseDirs::new().ok_or_else(|| anyhow!("missing home dir"))?;
-                base.home_dir().join(".memex")
-            }
+        let layout = match root_override {
+            Some(path) => Layout::Single(path),
+            None => Layout::from_env()?,
         };
+        Ok(Self::with_layout(layout))
+    }
 
-        Ok(Self {
+    pub fn with_layout(layout: Layout) -> Self {
+        let (root, state, config) = match layout {
+            Layout::Single(root) => (root.clone(), root.join("state"), root.join("config.toml")),
+            Layout::Xdg {
+                data,
+                state,
+                config,
+            } => (data, state, config.join("config.toml")),
+        };
+        Self {
             index: root.join("index"),
             vectors: root.join("vectors"),
-            state: root.join("state"),
+            state,
+            config,
             root,
-        })
+        }
+    }
+
+    /// Where `name`, a directory or file named as if under a single root
+    /// (`state`, `config.toml`, `index`, ...), lives in this layout.
+    pub fn entry(&self, name: &str) -> PathBuf {
+        match name {
+            "state" => self.state.clone(),
+            "config.toml" => self.config.clone(),
+            _ => self.root.join(name),
+        }
+    }
+
+    /// Project renames made with `memex project rename` in this data
+    /// directory.
+    pub fn project_aliases(&self) -> Result<crate::state::ProjectAliases> {
+        let path = crate::state::project_aliases_path(&self.state);
+        crate::state::ProjectAliases::load(&path)
+            .with_context(|| format!("failed to read {}", path.display()))
     }
 
     pub fn ensure_dirs(&self) -> Result<()> {
@@ -38,7 +139,98 @@ impl Paths {
     }
 }
 
+/// Moves that take the store at `from` to the layout of `to`: each entry of
+/// the data directory to where `to` keeps it, plus state and config.toml
+/// when `from` keeps them elsewhere. State comes last, since the write lock
+/// lives in it.
+pub fn plan_home_migration(from: &Paths, to: &Paths) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut moves = Vec::new();
+    let mut names: Vec<String> = match std::fs::read_dir(&from.root) {
+        Ok(entries) => entries
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<_>>()?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+    names.sort();
+    for name in names {
+        let source = from.root.join(&name);
+        if from.entry(&name) == source {
+            moves.push((source, to.entry(&name)));
+        }
+    }
+    if !from.config.starts_with(&from.root) && from.config.exists() {
+        moves.push((from.config.clone(), to.config.clone()));
+    }
+    if !from.state.starts_with(&from.root) && from.state.exists() {
+        moves.push((from.state.clone(), to.state.clone()));
+    }
+    moves.retain(|(source, target)| source != target);
+    if let Some(pos) = moves.iter().position(|(source, _)| source == &from.state) {
+        let state = moves.remove(pos);
+        moves.push(state);
+    }
+    if let Some((_, target)) = moves.iter().find(|(_, target)| target.exists()) {
+        return Err(anyhow!(
+            "{} already exists; move it away or pick another destination",
+            target.display()
+        ));
+    }
+    Ok(moves)
+}
+
+/// Renames `source` to `target`, copying and then deleting it when the two
+/// are on different file systems.
+pub fn move_path(source: &Path, target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::rename(source, target) {
+        Ok(()) => return Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {}
+        Err(err) => {
+            return Err(anyhow!(
+                "failed to move {} to {}: {err}",
+                source.display(),
+                target.display()
+            ));
+        }
+    }
+    if source.is_dir() {
+        for entry in walkdir::WalkDir::new(source) {
+            let entry = entry?;
+            let dest = target.join(entry.path().strip_prefix(source)?);
+            if entry.file_type().is_dir() {
+                std::fs::create_dir_all(&dest)?;
+            } else {
+                std::fs::copy(entry.path(), &dest)?;
+            }
+        }
+        std::fs::remove_dir_all(source)?;
+    } else {
+        std::fs::copy(source, target)?;
+        std::fs::remove_file(source)?;
+    }
+    Ok(())
+}
+
+/// Claude transcripts live under `<config dir>/projects`; the config dir is
+/// `~/.claude` (`%USERPROFILE%\.claude` on Windows) unless `CLAUDE_CONFIG_DIR`
+/// points elsewhere. Only the first entry of a comma-separated list is used.
 pub fn default_claude_source() -> PathBuf {
+    if let Some(config) = std::env::var_os("CLAUDE_CONFIG_DIR")
+        && let Some(first) = config
+            .to_string_lossy()
+            .split(',')
+            .map(str::trim)
+            .find(|part| !part.is_empty())
+    {
+        let path = PathBuf::from(first);
+        if path.file_name().and_then(|n| n.to_str()) == Some("projects") {
+            return path;
+        }
+        return path.join("projects");
+    }
     let home = directories::BaseDirs::new()
         .map(|b| b.home_dir().to_path_buf())
         .unwrap_or_else(|| PathBuf::from("/"));
@@ -53,6 +245,12 @@ const MIN_INDEXED_TOOL_CONTENT_BYTES: usize = 1024;
 pub struct IndexedToolContentLimits {
     pub input_bytes: usize,
     pub output_bytes: usize,
+    /// Also index code found in tool content for `code:` queries.
+    pub code: bool,
+    /// Records larger than this are skipped rather than truncated.
+    pub record_bytes: Option<usize>,
+    /// Skip records that look like agent transcripts themselves.
+    pub skip_nested_transcripts: bool,
 }
 
 impl Default for IndexedToolContentLimits {
@@ -60,16 +258,22 @@ impl Default for IndexedToolContentLimits {
         Self {
             input_bytes: DEFAULT_MAX_INDEXED_TOOL_INPUT_BYTES,
             output_bytes: DEFAULT_MAX_INDEXED_TOOL_OUTPUT_BYTES,
+            code: false,
+            record_bytes: None,
+            skip_nested_transcripts: true,
         }
     }
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct UserConfig {
     pub embeddings: Option<bool>,
     pub auto_index_on_search: Option<bool>,
     /// Reconstruct token usage from local agent logs (disabled by default).
     pub token_usage: Option<bool>,
+    /// Disable network access: no background update checks, `memex update`
+    /// refuses to run, and embedding models are never downloaded.
+    pub offline: Option<bool>,
     /// Embedding model: minilm, bge, nomic, gemma (default), potion
     pub model: Option<String>,
     /// Execution provider: auto, cpu, coreml, cuda
@@ -82,6 +286,19 @@ pub struct UserConfig {
     pub cudnn_library_paths: Option<Vec<PathBuf>>,
     /// Embedding runtime compute units on macOS: ane, gpu, cpu, all
     pub compute_units: Option<String>,
+    /// Texts per embedding inference batch. Default depends on the model.
+    pub embedding_batch_size: Option<usize>,
+    /// ONNX Runtime threads per embedding inference. Default: all cores.
+    pub embedding_threads: Option<usize>,
+    /// Milliseconds to pause after each embedding batch while indexing, so
+    /// long backfills leave the CPU alone in between. Default: 0.
+    pub embedding_batch_pause_ms: Option<u64>,
+    /// Threads indexing may use: caps the parsing pool and, through
+    /// `embedding_threads`, ONNX Runtime's threads for embedding. Default: all
+    /// cores.
+    pub max_cpu_threads: Option<usize>,
+    /// Maximum tokens embedded per text. Default depends on the model.
+    pub embedding_max_length: Option<usize>,
     /// Scan cache TTL in seconds. If a scan was done within this time,
     /// skip re-scanning on search. Default: 3600 seconds (1 hour).
     pub scan_cache_ttl: Option<u64>,
@@ -89,6 +306,30 @@ pub struct UserConfig {
     pub max_indexed_tool_input_bytes: Option<usize>,
     /// Maximum indexed bytes for tool-call output.
     pub max_indexed_tool_output_bytes: Option<usize>,
+    /// Records larger than this (text plus tool input and output) are
+    /// skipped instead of indexed. Default: no limit.
+    pub max_indexed_record_bytes: Option<usize>,
+    /// Skip records that are themselves agent transcripts, e.g. a session
+    /// file read or pasted while working on memex. Default: true.
+    pub skip_nested_transcripts: Option<bool>,
+    /// Index code blocks from tool input and output (file reads, diffs,
+    /// writes) for `code:` queries. Default: false.
+    pub index_tool_code: Option<bool>,
+    /// OCR pasted images at ingest with tesseract, if installed, so their
+    /// text is searchable. Default: false.
+    pub ocr_images: Option<bool>,
+    /// Vacuum after `memex index` when the share of deleted index documents
+    /// or orphaned vectors exceeds this ratio (0-1). Disabled when unset.
+    pub auto_vacuum_threshold: Option<f64>,
+    /// After `memex index`, move records older than this many months into
+    /// month shards of the index. Disabled when unset.
+    pub shard_after_months: Option<u32>,
+    /// Embed records older than this many months with
+    /// `archive_embedding_model` into a store of their own. Disabled when
+    /// unset.
+    pub archive_embeddings_after_months: Option<u32>,
+    /// Model for the archive store. Default: potion.
+    pub archive_embedding_model: Option<String>,
     /// Background index service mode: "interval" or "continuous".
     pub index_service_mode: Option<String>,
     /// Run background index service continuously (legacy).
@@ -109,6 +350,36 @@ pub struct UserConfig {
     pub index_service_plist: Option<PathBuf>,
     /// Background index service systemd user directory (Linux).
     pub index_service_systemd_dir: Option<PathBuf>,
+    /// Desktop notification when a background index run fails. Default: true.
+    pub index_service_notify: Option<bool>,
+    /// Background index service only runs on AC power. Default: false.
+    pub index_service_ac_only: Option<bool>,
+    /// Background index service only runs while the machine is idle.
+    /// Default: false.
+    pub index_service_idle_only: Option<bool>,
+    /// CPU usage (percent) below which the machine counts as idle. Default: 20.
+    pub index_service_idle_cpu_percent: Option<f32>,
+    /// Days a forgotten session stays in the trash before it is deleted for
+    /// good. Default: 30.
+    pub trash_retention_days: Option<u64>,
+    /// Days a session is kept after its newest message; the index service
+    /// and `memex prune` delete older ones. Default: keep everything.
+    pub retain_days: Option<u64>,
+    /// Per-project `retain_days`, by project name or alias. 0 keeps a
+    /// project forever.
+    pub retain_projects: Option<HashMap<String, u64>>,
+    /// Session ids retention never prunes. Sessions with a note are kept too.
+    pub retain_pinned: Option<Vec<String>>,
+    /// Directory `memex backup` writes archives to and restores from.
+    /// Default: `backups` in the data directory.
+    pub backup_dir: Option<PathBuf>,
+    /// Format of context blocks exported from the TUI basket: "markdown" or
+    /// "xml". Default: markdown.
+    pub context_format: Option<String>,
+    /// Output templates for `memex search`, `show` and `sessions` by name,
+    /// picked with `--template NAME`. One named after a command is that
+    /// command's default output.
+    pub templates: Option<HashMap<String, String>>,
     /// Resume command template for Claude sessions.
     pub claude_resume_cmd: Option<String>,
     /// Resume command template for Codex sessions.
@@ -121,11 +392,160 @@ pub struct UserConfig {
     pub pi_resume_cmd: Option<String>,
     /// Resume command template for GitHub Copilot CLI sessions.
     pub copilot_resume_cmd: Option<String>,
+    /// Per source (claude, codex, opencode, cursor, pi, copilot): change into
+    /// the session's working directory before resuming. Default: true.
+    pub resume_cd: Option<HashMap<String, bool>>,
+    /// Scoring profile used when `--profile` is not given. Default: "default".
+    pub scoring_profile: Option<String>,
+    /// Custom scoring profiles, or overrides for the built-in ones.
+    pub scoring_profiles: Option<HashMap<String, ScoringProfileConfig>>,
+    /// Words left out of keyword queries, e.g. ["the", "how", "to"].
+    pub stopwords: Option<Vec<String>>,
+    /// Keyword query words shorter than this are left out. Default: 1.
+    pub min_term_length: Option<usize>,
+    /// Keyword score multipliers by record role (user, assistant, tool_use,
+    /// tool_result, note), e.g. { user = 2.0, tool_result = 0.5 }.
+    pub role_boosts: Option<HashMap<String, f32>>,
+    /// Optional TUI session list columns: "messages", "duration", "topic".
+    /// Default: messages and duration.
+    pub session_list_columns: Option<Vec<String>>,
+    /// Milliseconds between messages when the TUI replay view autoplays.
+    /// Default: 1500.
+    pub replay_interval_ms: Option<u64>,
+    /// Milliseconds the TUI waits after the last keystroke in the query box
+    /// before searching. Default: 250.
+    pub search_debounce_ms: Option<u64>,
+    /// Make session files and project directories clickable (OSC 8 links)
+    /// in terminal output and the TUI footer. Default: true.
+    pub hyperlinks: Option<bool>,
+    /// Highlight fenced code blocks in the TUI preview. Default: false.
+    pub highlight_code: Option<bool>,
+    /// Command the TUI preview runs (with `T`) on messages in another
+    /// language: it reads the message on stdin and prints the translation.
+    /// `{from}` and `{to}` in it become language codes. Default: none.
+    pub translate_command: Option<String>,
+    /// Language `translate_command` translates into. Default: "en".
+    pub translate_to: Option<String>,
+    /// Most sessions the TUI lists for a query. Default: 200.
+    pub tui_result_limit: Option<usize>,
+    /// Newest messages the TUI preview shows when there is no query.
+    /// Default: 10.
+    pub preview_tail_messages: Option<usize>,
+    /// Messages the TUI preview shows before and after each match.
+    /// Default: the match's whole exchange, from its prompt to the next.
+    pub preview_match_context: Option<usize>,
+    /// Characters of a message the TUI preview shows before cutting it off.
+    /// Default: 4000.
+    pub preview_message_chars: Option<usize>,
+    /// Show the query's matches above the session's full history in the TUI
+    /// preview, instead of one or the other. Default: false.
+    pub preview_split: Option<bool>,
+    /// Count only messages holding every query term as matches in the TUI
+    /// preview, instead of any one of them. Default: false.
+    pub preview_all_terms: Option<bool>,
+    /// Start the TUI filtered to the project of the git repo it is launched
+    /// from. Default: false.
+    pub tui_project_from_cwd: Option<bool>,
+    /// Source the TUI starts filtered to ("claude", "codex", ...).
+    /// Default: all.
+    pub tui_source: Option<String>,
+    /// Order of TUI search results: "relevance" or "recent".
+    /// Default: relevance.
+    pub tui_sort: Option<String>,
+    /// TUI startup defaults by the name of the git repo it is launched from;
+    /// an entry also turns on `tui_project_from_cwd` for that repo.
+    pub tui_projects: Option<HashMap<String, TuiProjectDefaults>>,
+    /// Team server `memex push` and `memex search --remote` talk to, e.g.
+    /// "http://memex.internal:7377".
+    pub team_url: Option<String>,
+    /// Shared secret the team server expects as a bearer token.
+    pub team_token: Option<String>,
+    /// Name your pushed records are attributed to. Default: $USER.
+    pub team_name: Option<String>,
+    /// Projects `memex push` shares. Default: all of them.
+    pub team_projects: Option<Vec<String>>,
+    /// Elasticsearch or OpenSearch cluster `memex export --to elasticsearch`
+    /// sends records to, e.g. "https://es.internal:9200".
+    pub elasticsearch_url: Option<String>,
+    /// Index records are written to. Default: "memex".
+    pub elasticsearch_index: Option<String>,
+    /// Elasticsearch API key, sent as `Authorization: ApiKey <key>`.
+    pub elasticsearch_api_key: Option<String>,
+    /// "user:password" for basic auth, e.g. on OpenSearch.
+    pub elasticsearch_user: Option<String>,
+    /// Export new records after every `memex index`, so the background
+    /// service keeps the cluster in sync. Default: false.
+    pub elasticsearch_sync: Option<bool>,
+}
+
+/// A `tui_projects` entry.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TuiProjectDefaults {
+    /// Project name in the index, when it differs from the repo name.
+    pub project: Option<String>,
+    pub source: Option<String>,
+    pub sort: Option<String>,
+}
+
+/// Order of the TUI session list for a query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionSort {
+    /// Best match first.
+    #[default]
+    Relevance,
+    /// Most recent activity first.
+    Recent,
+}
+
+impl SessionSort {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "relevance" => Ok(Self::Relevance),
+            "recent" => Ok(Self::Recent),
+            other => Err(anyhow!(
+                "unknown sort '{other}' (expected relevance or recent)"
+            )),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Relevance => "relevance",
+            Self::Recent => "recent",
+        }
+    }
+}
+
+/// Filters and order the TUI starts with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TuiStartup {
+    pub project: Option<String>,
+    pub source: Option<SourceFilter>,
+    pub sort: SessionSort,
+}
+
+/// Optional columns shown in the TUI session list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionListColumns {
+    pub messages: bool,
+    pub duration: bool,
+    /// Topic label from `memex topics`.
+    pub topic: bool,
+}
+
+impl Default for SessionListColumns {
+    fn default() -> Self {
+        Self {
+            messages: true,
+            duration: true,
+            topic: false,
+        }
+    }
 }
 
 impl UserConfig {
     pub fn load(paths: &Paths) -> Result<Self> {
-        let path = paths.root.join("config.toml");
+        let path = &paths.config;
         if !path.exists() {
             return Ok(Self::default());
         }
@@ -224,14 +644,258 @@ impl UserConfig {
             cuda_device_id: self.resolve_cuda_device_id()?,
             cuda_library_paths: self.resolve_cuda_library_paths()?,
             cudnn_library_paths: self.resolve_cudnn_library_paths()?,
+            batch_size: self.embedding_batch_size,
+            threads: match (self.embedding_threads, self.max_cpu_threads) {
+                (Some(threads), Some(max)) => Some(threads.min(max)),
+                (threads, max) => threads.or(max),
+            },
+            max_length: self.embedding_max_length,
+            batch_pause_ms: self.embedding_batch_pause_ms,
+            offline: self.offline(),
         })
     }
 
+    pub fn max_cpu_threads(&self) -> Result<Option<usize>> {
+        match self.max_cpu_threads {
+            Some(0) => Err(anyhow!("max_cpu_threads must be greater than 0")),
+            value => Ok(value),
+        }
+    }
+
     pub fn apply_embed_runtime_env(&self) -> Result<()> {
         self.resolve_embed_runtime()?.apply_env()?;
         Ok(())
     }
 
+    /// Whether resume commands for `source` should first `cd` into the
+    /// session's working directory.
+    pub fn resume_cd(&self, source: &str) -> bool {
+        self.resume_cd
+            .as_ref()
+            .and_then(|sources| sources.get(source).copied())
+            .unwrap_or(true)
+    }
+
+    /// Resolves the scoring profile named on the command line, falling back to
+    /// the configured default.
+    pub fn resolve_scoring_profile(&self, cli_profile: Option<&str>) -> Result<ScoringProfile> {
+        let name = cli_profile
+            .or(self.scoring_profile.as_deref())
+            .unwrap_or(DEFAULT_PROFILE);
+        crate::scoring::resolve_profile(name, self.scoring_profiles.as_ref())
+    }
+
+    pub fn scoring_profile_names(&self) -> Vec<String> {
+        crate::scoring::profile_names(self.scoring_profiles.as_ref())
+    }
+
+    pub fn session_list_columns(&self) -> Result<SessionListColumns> {
+        let Some(names) = &self.session_list_columns else {
+            return Ok(SessionListColumns::default());
+        };
+        let mut columns = SessionListColumns {
+            messages: false,
+            duration: false,
+            topic: false,
+        };
+        for name in names {
+            match name.as_str() {
+                "messages" => columns.messages = true,
+                "duration" => columns.duration = true,
+                "topic" => columns.topic = true,
+                other => {
+                    return Err(anyhow!(
+                        "unknown session_list_columns entry '{other}' (expected messages, duration or topic)"
+                    ));
+                }
+            }
+        }
+        Ok(columns)
+    }
+
+    /// config.toml keys whose values differ in `other`, for reporting what a
+    /// reload changed.
+    pub fn changed_keys(&self, other: &Self) -> Vec<String> {
+        let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+            (serde_json::to_value(self), serde_json::to_value(other))
+        else {
+            return Vec::new();
+        };
+        after
+            .into_iter()
+            .filter(|(key, value)| before.get(key) != Some(value))
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    pub fn backup_dir(&self, paths: &Paths) -> PathBuf {
+        self.backup_dir
+            .clone()
+            .unwrap_or_else(|| paths.root.join("backups"))
+    }
+
+    pub fn ocr_images(&self) -> bool {
+        self.ocr_images.unwrap_or(false)
+    }
+
+    /// `offline = true` here, `--offline`, or `MEMEX_OFFLINE`.
+    pub fn offline(&self) -> bool {
+        self.offline.unwrap_or(false) || crate::embed::offline_from_env()
+    }
+
+    /// Startup state for a TUI launched inside the git repo named `repo`.
+    pub fn tui_startup(&self, repo: Option<&str>) -> Result<TuiStartup> {
+        let entry = repo.and_then(|repo| self.tui_projects.as_ref()?.get(repo));
+        let project = match (repo, entry) {
+            (Some(repo), Some(entry)) => {
+                Some(entry.project.clone().unwrap_or_else(|| repo.to_string()))
+            }
+            (Some(repo), None) if self.tui_project_from_cwd.unwrap_or(false) => {
+                Some(repo.to_string())
+            }
+            _ => None,
+        };
+        let source = entry
+            .and_then(|entry| entry.source.as_deref())
+            .or(self.tui_source.as_deref())
+            .filter(|source| *source != "all")
+            .map(|source| {
+                SourceFilter::from_str(source, true).map_err(|_| {
+                    anyhow!(
+                        "unknown source '{source}' (expected all, claude, codex, opencode, cursor, pi or copilot)"
+                    )
+                })
+            })
+            .transpose()?;
+        let sort = match entry
+            .and_then(|entry| entry.sort.as_deref())
+            .or(self.tui_sort.as_deref())
+        {
+            Some(sort) => SessionSort::parse(sort)?,
+            None => SessionSort::default(),
+        };
+        Ok(TuiStartup {
+            project,
+            source,
+            sort,
+        })
+    }
+
+    pub fn replay_interval(&self) -> Duration {
+        Duration::from_millis(self.replay_interval_ms.unwrap_or(1500))
+    }
+
+    pub fn search_debounce(&self) -> Duration {
+        Duration::from_millis(self.search_debounce_ms.unwrap_or(250))
+    }
+
+    pub fn hyperlinks(&self) -> bool {
+        self.hyperlinks.unwrap_or(true)
+    }
+
+    pub fn highlight_code(&self) -> bool {
+        self.highlight_code.unwrap_or(false)
+    }
+
+    /// The preview's translator, when `translate_command` is set.
+    pub fn translator(&self) -> Option<Translator> {
+        let command = self.translate_command.as_deref()?.trim();
+        if command.is_empty() {
+            return None;
+        }
+        Some(Translator {
+            command: command.to_string(),
+            to: self
+                .translate_to
+                .as_deref()
+                .unwrap_or("en")
+                .to_ascii_lowercase(),
+        })
+    }
+
+    pub fn tui_result_limit(&self) -> usize {
+        self.tui_result_limit.unwrap_or(200).max(1)
+    }
+
+    pub fn preview_tail_messages(&self) -> usize {
+        self.preview_tail_messages.unwrap_or(10)
+    }
+
+    /// `None` shows each match's whole exchange.
+    pub fn preview_match_context(&self) -> Option<usize> {
+        self.preview_match_context
+    }
+
+    pub fn preview_message_chars(&self) -> usize {
+        self.preview_message_chars.unwrap_or(4000).max(1)
+    }
+
+    pub fn preview_split(&self) -> bool {
+        self.preview_split.unwrap_or(false)
+    }
+
+    pub fn preview_all_terms(&self) -> bool {
+        self.preview_all_terms.unwrap_or(false)
+    }
+
+    pub fn team_url(&self) -> Option<String> {
+        self.team_url
+            .clone()
+            .or_else(|| std::env::var("MEMEX_TEAM_URL").ok())
+            .filter(|url| !url.trim().is_empty())
+    }
+
+    pub fn team_token(&self) -> Option<String> {
+        self.team_token
+            .clone()
+            .or_else(|| std::env::var("MEMEX_TEAM_TOKEN").ok())
+            .filter(|token| !token.is_empty())
+    }
+
+    pub fn elasticsearch_url(&self) -> Option<String> {
+        self.elasticsearch_url
+            .clone()
+            .or_else(|| std::env::var("MEMEX_ELASTICSEARCH_URL").ok())
+            .filter(|url| !url.trim().is_empty())
+    }
+
+    pub fn elasticsearch_index(&self) -> String {
+        self.elasticsearch_index
+            .clone()
+            .filter(|index| !index.trim().is_empty())
+            .unwrap_or_else(|| crate::elastic::DEFAULT_INDEX.to_string())
+    }
+
+    /// The API key, else the basic auth user, from config.toml or
+    /// MEMEX_ELASTICSEARCH_API_KEY.
+    pub fn elasticsearch_auth(&self) -> Option<crate::elastic::ElasticAuth> {
+        use crate::elastic::ElasticAuth;
+        self.elasticsearch_api_key
+            .clone()
+            .or_else(|| std::env::var("MEMEX_ELASTICSEARCH_API_KEY").ok())
+            .filter(|key| !key.is_empty())
+            .map(ElasticAuth::ApiKey)
+            .or_else(|| {
+                self.elasticsearch_user
+                    .clone()
+                    .filter(|user| !user.is_empty())
+                    .map(ElasticAuth::Basic)
+            })
+    }
+
+    pub fn elasticsearch_sync(&self) -> bool {
+        self.elasticsearch_sync.unwrap_or(false)
+    }
+
+    pub fn team_name(&self) -> String {
+        self.team_name
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| "anonymous".to_string())
+    }
+
     pub fn scan_cache_ttl(&self) -> u64 {
         self.scan_cache_ttl.unwrap_or(3600)
     }
@@ -248,9 +912,42 @@ impl UserConfig {
                 DEFAULT_MAX_INDEXED_TOOL_OUTPUT_BYTES,
                 "max_indexed_tool_output_bytes",
             )?,
+            code: self.index_tool_code.unwrap_or(false),
+            record_bytes: self
+                .max_indexed_record_bytes
+                .map(|value| indexed_tool_content_limit(Some(value), 0, "max_indexed_record_bytes"))
+                .transpose()?,
+            skip_nested_transcripts: self.skip_nested_transcripts.unwrap_or(true),
         })
     }
 
+    pub fn auto_vacuum_threshold(&self) -> Result<Option<f64>> {
+        match self.auto_vacuum_threshold {
+            Some(value) if !(value > 0.0 && value <= 1.0) => Err(anyhow!(
+                "auto_vacuum_threshold must be greater than 0 and at most 1"
+            )),
+            value => Ok(value),
+        }
+    }
+
+    pub fn shard_after_months(&self) -> Option<u32> {
+        self.shard_after_months
+    }
+
+    pub fn archive_tier(&self) -> Result<Option<ArchiveTier>> {
+        let Some(after_months) = self.archive_embeddings_after_months else {
+            return Ok(None);
+        };
+        let model = match self.archive_embedding_model.as_deref() {
+            Some(model) => ModelChoice::parse(model)?,
+            None => ModelChoice::Potion,
+        };
+        Ok(Some(ArchiveTier {
+            after_months,
+            model,
+        }))
+    }
+
     pub fn index_service_mode(&self) -> Option<&str> {
         self.index_service_mode.as_deref()
     }
@@ -266,6 +963,70 @@ impl UserConfig {
     pub fn index_service_poll_interval(&self) -> u64 {
         self.index_service_poll_interval.unwrap_or(30)
     }
+
+    pub fn index_service_notify(&self) -> bool {
+        self.index_service_notify.unwrap_or(true)
+    }
+
+    pub fn index_service_policy(&self) -> Result<IndexPolicy> {
+        let idle_cpu_percent = self
+            .index_service_idle_cpu_percent
+            .unwrap_or(DEFAULT_IDLE_CPU_PERCENT);
+        if !(idle_cpu_percent > 0.0 && idle_cpu_percent <= 100.0) {
+            return Err(anyhow!(
+                "index_service_idle_cpu_percent must be greater than 0 and at most 100"
+            ));
+        }
+        Ok(IndexPolicy {
+            ac_only: self.index_service_ac_only.unwrap_or(false),
+            idle_only: self.index_service_idle_only.unwrap_or(false),
+            idle_cpu_percent,
+        })
+    }
+
+    pub fn trash_retention_days(&self) -> u64 {
+        self.trash_retention_days
+            .unwrap_or(crate::trash::DEFAULT_TRASH_RETENTION_DAYS)
+    }
+
+    pub fn retention_policy(&self) -> crate::retention::RetentionPolicy {
+        crate::retention::RetentionPolicy {
+            days: self.retain_days,
+            projects: self.retain_projects.clone().unwrap_or_default(),
+            pinned: self.retain_pinned.iter().flatten().cloned().collect(),
+        }
+    }
+
+    pub fn query_tuning(&self) -> Result<crate::index::QueryTuning> {
+        let role_boosts = self.role_boosts.clone().unwrap_or_default();
+        if let Some((role, _)) = role_boosts
+            .iter()
+            .find(|(_, boost)| !boost.is_finite() || **boost < 0.0)
+        {
+            return Err(anyhow!("role_boosts.{role} must be a non-negative number"));
+        }
+        Ok(crate::index::QueryTuning {
+            stopwords: self
+                .stopwords
+                .iter()
+                .flatten()
+                .map(|word| word.to_lowercase())
+                .collect(),
+            min_term_length: self.min_term_length.unwrap_or(1),
+            role_boosts,
+        })
+    }
+
+    pub fn context_format(&self) -> crate::context::ContextFormat {
+        self.context_format
+            .as_deref()
+            .and_then(crate::context::ContextFormat::parse)
+            .unwrap_or_default()
+    }
+
+    pub fn template(&self, name: &str) -> Option<&str> {
+        self.templates.as_ref()?.get(name).map(String::as_str)
+    }
 }
 
 fn indexed_tool_content_limit(value: Option<usize>, default: usize, key: &str) -> Result<usize> {
@@ -283,6 +1044,126 @@ mod tests {
     use super::*;
     use crate::test_support::{EnvVarGuard, env_lock};
 
+    #[test]
+    fn home_layout_prefers_memex_home_then_legacy_then_xdg() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let home = tmp.path();
+        let vars = |pairs: &[(&str, &str)]| {
+            let pairs: HashMap<String, OsString> = pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), OsString::from(value)))
+                .collect();
+            move |name: &str| pairs.get(name).cloned()
+        };
+        let xdg_data = home.join("data");
+        let xdg_data = xdg_data.to_str().unwrap();
+
+        assert_eq!(
+            Layout::resolve(home, vars(&[])),
+            Layout::Single(home.join(".memex"))
+        );
+        assert_eq!(
+            Layout::resolve(home, vars(&[("XDG_DATA_HOME", xdg_data)])),
+            Layout::Xdg {
+                data: home.join("data/memex"),
+                state: home.join(".local/state/memex"),
+                config: home.join(".config/memex"),
+            }
+        );
+        // Relative values are ignored, as the spec says.
+        assert_eq!(
+            Layout::xdg(home, vars(&[("XDG_STATE_HOME", "state")])),
+            Layout::Xdg {
+                data: home.join(".local/share/memex"),
+                state: home.join(".local/state/memex"),
+                config: home.join(".config/memex"),
+            }
+        );
+        // A migrated store is found without the variables.
+        std::fs::create_dir_all(home.join(".local/share/memex")).unwrap();
+        assert!(matches!(
+            Layout::resolve(home, vars(&[])),
+            Layout::Xdg { .. }
+        ));
+        std::fs::create_dir_all(home.join(".memex")).unwrap();
+        assert_eq!(
+            Layout::resolve(home, vars(&[("XDG_DATA_HOME", xdg_data)])),
+            Layout::Single(home.join(".memex"))
+        );
+        assert_eq!(
+            Layout::resolve(home, vars(&[("MEMEX_HOME", "/srv/memex")])),
+            Layout::Single(PathBuf::from("/srv/memex"))
+        );
+    }
+
+    #[test]
+    fn unreadable_project_aliases_are_reported() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::with_layout(Layout::Single(tmp.path().to_path_buf()));
+        assert!(
+            paths
+                .project_aliases()
+                .expect("missing file")
+                .aliases
+                .is_empty()
+        );
+        std::fs::create_dir_all(&paths.state).unwrap();
+        std::fs::write(crate::state::project_aliases_path(&paths.state), "{").unwrap();
+        let err = format!("{:#}", paths.project_aliases().unwrap_err());
+        assert!(err.contains("project_aliases.json"), "{err}");
+    }
+
+    #[test]
+    fn home_migration_moves_a_single_root_into_the_xdg_layout() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let home = tmp.path();
+        let from = Paths::with_layout(Layout::Single(home.join(".memex")));
+        let to = Paths::with_layout(Layout::xdg(home, |_| None));
+        std::fs::create_dir_all(&from.index).unwrap();
+        std::fs::write(from.index.join("meta.json"), "{}").unwrap();
+        std::fs::create_dir_all(&from.state).unwrap();
+        std::fs::write(from.state.join("ingest.json"), "{}").unwrap();
+        std::fs::write(&from.config, "embeddings = true\n").unwrap();
+
+        let moves = plan_home_migration(&from, &to).unwrap();
+        assert_eq!(
+            moves,
+            [
+                (from.config.clone(), to.config.clone()),
+                (from.index.clone(), to.index.clone()),
+                (from.state.clone(), to.state.clone()),
+            ]
+        );
+        for (source, target) in &moves {
+            move_path(source, target).unwrap();
+        }
+        assert!(to.index.join("meta.json").is_file());
+        assert!(to.state.join("ingest.json").is_file());
+        assert_eq!(
+            std::fs::read_to_string(&to.config).unwrap(),
+            "embeddings = true\n"
+        );
+        assert!(plan_home_migration(&from, &to).unwrap().is_empty());
+
+        // Never over an existing store.
+        std::fs::create_dir_all(&from.index).unwrap();
+        let err = plan_home_migration(&from, &to).unwrap_err().to_string();
+        assert!(err.contains("already exists"), "{err}");
+    }
+
+    #[test]
+    fn claude_source_honors_claude_config_dir() {
+        let _lock = env_lock();
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("claude");
+        let _env = EnvVarGuard::set_os(&[("CLAUDE_CONFIG_DIR", Some(config_dir.as_os_str()))]);
+        assert_eq!(default_claude_source(), config_dir.join("projects"));
+
+        let projects = config_dir.join("projects");
+        let _env = EnvVarGuard::set_os(&[("CLAUDE_CONFIG_DIR", Some(projects.as_os_str()))]);
+        assert_eq!(default_claude_source(), projects);
+    }
+
     #[test]
     fn token_usage_is_disabled_by_default() {
         assert!(!UserConfig::default().token_usage_enabled());
@@ -298,6 +1179,140 @@ mod tests {
         assert!(config.token_usage_enabled());
     }
 
+    #[test]
+    fn max_cpu_threads_caps_embedding_threads() {
+        let threads = |embedding_threads, max_cpu_threads| {
+            UserConfig {
+                embedding_threads,
+                max_cpu_threads,
+                ..UserConfig::default()
+            }
+            .resolve_embed_runtime()
+            .expect("embed runtime")
+            .threads
+        };
+        assert_eq!(threads(None, None), None);
+        assert_eq!(threads(None, Some(2)), Some(2));
+        assert_eq!(threads(Some(8), Some(2)), Some(2));
+        assert_eq!(threads(Some(1), Some(2)), Some(1));
+    }
+
+    #[test]
+    fn changed_keys_lists_only_settings_that_differ() {
+        let before: UserConfig = toml::from_str(
+            "claude_resume_cmd = \"claude -r {session_id}\"\ncontext_format = \"xml\"\n",
+        )
+        .unwrap();
+        let after: UserConfig = toml::from_str(
+            "claude_resume_cmd = \"claude --resume {session_id}\"\ncontext_format = \"xml\"\nsession_list_columns = [\"topic\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            before.changed_keys(&after),
+            vec!["claude_resume_cmd", "session_list_columns"]
+        );
+        assert!(after.changed_keys(&after.clone()).is_empty());
+    }
+
+    #[test]
+    fn query_tuning_lowercases_stopwords_and_rejects_negative_boosts() {
+        let config: UserConfig =
+            toml::from_str("stopwords = [\"The\"]\nrole_boosts = { tool_result = 0.5 }\n").unwrap();
+        let tuning = config.query_tuning().unwrap();
+        assert!(tuning.stopwords.contains("the"));
+        assert_eq!(tuning.min_term_length, 1);
+        assert_eq!(tuning.role_boosts.get("tool_result"), Some(&0.5));
+
+        let config: UserConfig = toml::from_str("role_boosts = { user = -1.0 }\n").unwrap();
+        assert!(config.query_tuning().is_err());
+    }
+
+    #[test]
+    fn tui_startup_prefers_the_repo_entry_over_global_defaults() {
+        let config: UserConfig = toml::from_str(
+            "tui_source = \"codex\"\n[tui_projects.memex]\nproject = \"memex-app\"\nsort = \"recent\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.tui_startup(Some("memex")).unwrap(),
+            TuiStartup {
+                project: Some("memex-app".to_string()),
+                source: Some(SourceFilter::Codex),
+                sort: SessionSort::Recent,
+            }
+        );
+        // Other repos only get a project filter with tui_project_from_cwd.
+        assert_eq!(config.tui_startup(Some("other")).unwrap().project, None);
+        let config = UserConfig {
+            tui_project_from_cwd: Some(true),
+            ..config
+        };
+        assert_eq!(
+            config
+                .tui_startup(Some("other"))
+                .unwrap()
+                .project
+                .as_deref(),
+            Some("other")
+        );
+        assert_eq!(config.tui_startup(None).unwrap().project, None);
+
+        let config: UserConfig = toml::from_str("tui_sort = \"oldest\"\n").unwrap();
+        assert!(config.tui_startup(None).is_err());
+    }
+
+    #[test]
+    fn session_list_columns_default_to_all_and_reject_unknown_names() {
+        let config = UserConfig::default();
+        assert_eq!(
+            config.session_list_columns().unwrap(),
+            SessionListColumns::default()
+        );
+
+        let config = UserConfig {
+            session_list_columns: Some(vec!["duration".to_string(), "topic".to_string()]),
+            ..UserConfig::default()
+        };
+        assert_eq!(
+            config.session_list_columns().unwrap(),
+            SessionListColumns {
+                messages: false,
+                duration: true,
+                topic: true,
+            }
+        );
+
+        let config = UserConfig {
+            session_list_columns: Some(vec!["tokens".to_string()]),
+            ..UserConfig::default()
+        };
+        assert!(config.session_list_columns().is_err());
+    }
+
+    #[test]
+    fn auto_vacuum_threshold_is_validated() {
+        assert_eq!(
+            UserConfig::default()
+                .auto_vacuum_threshold()
+                .expect("unset"),
+            None
+        );
+
+        let config = UserConfig {
+            auto_vacuum_threshold: Some(0.25),
+            ..UserConfig::default()
+        };
+        assert_eq!(config.auto_vacuum_threshold().expect("valid"), Some(0.25));
+
+        for invalid in [0.0, -0.5, 1.5] {
+            let config = UserConfig {
+                auto_vacuum_threshold: Some(invalid),
+                ..UserConfig::default()
+            };
+            assert!(config.auto_vacuum_threshold().is_err());
+        }
+    }
+
     #[test]
     fn indexed_tool_content_limits_use_defaults() {
         assert_eq!(
@@ -323,6 +1338,9 @@ mod tests {
             IndexedToolContentLimits {
                 input_bytes: 96 * 1024,
                 output_bytes: 384 * 1024,
+                code: false,
+                record_bytes: None,
+                skip_nested_transcripts: true,
             }
         );
     }
@@ -0,0 +1,119 @@
+use crate::bundle::{escape_html, record_body};
+use crate::types::Record;
+use chrono::SecondsFormat;
+use std::fmt::Write as _;
+
+/// How a basket of collected messages is written out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextFormat {
+    #[default]
+    Markdown,
+    Xml,
+}
+
+impl ContextFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Some(Self::Markdown),
+            "xml" => Some(Self::Xml),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Xml => "xml",
+        }
+    }
+}
+
+fn format_time(ts: u64) -> String {
+    chrono::DateTime::<chrono::Utc>::from_timestamp_millis(ts as i64)
+        .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+/// Renders messages picked from any number of sessions as one block to paste
+/// into a new agent session, oldest first.
+pub fn render(records: &[Record], format: ContextFormat) -> String {
+    let mut records: Vec<&Record> = records.iter().collect();
+    records.sort_by(|a, b| a.ts.cmp(&b.ts).then_with(|| a.doc_id.cmp(&b.doc_id)));
+    match format {
+        ContextFormat::Markdown => to_markdown(&records),
+        ContextFormat::Xml => to_xml(&records),
+    }
+}
+
+fn to_markdown(records: &[&Record]) -> String {
+    let mut out = String::from("# Context from earlier sessions\n\n");
+    for record in records {
+        let _ = writeln!(
+            out,
+            "## {} · {} · {}\n_{} session {}_\n\n{}\n",
+            record.role,
+            record.project,
+            format_time(record.ts),
+            record.source.label(),
+            record.session_id,
+            record_body(record).trim()
+        );
+    }
+    out
+}
+
+fn to_xml(records: &[&Record]) -> String {
+    let mut out = String::from("<context>\n");
+    for record in records {
+        let _ = writeln!(
+            out,
+            "<message role=\"{}\" source=\"{}\" project=\"{}\" session=\"{}\" ts=\"{}\">\n{}\n</message>",
+            escape_html(&record.role),
+            escape_html(record.source.label()),
+            escape_html(&record.project),
+            escape_html(&record.session_id),
+            format_time(record.ts),
+            escape_html(record_body(record).trim())
+        );
+    }
+    out.push_str("</context>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_record;
+
+    #[test]
+    fn orders_messages_across_sessions_by_time() {
+        let records = [
+            Record {
+                ts: 2_000,
+                role: "assistant".to_string(),
+                text: "use a <Mutex>".to_string(),
+                ..test_record(2, "later")
+            },
+            Record {
+                ts: 1_000,
+                role: "assistant".to_string(),
+                text: "the lock is global".to_string(),
+                ..test_record(1, "earlier")
+            },
+        ];
+
+        let markdown = render(&records, ContextFormat::Markdown);
+        let first = markdown
+            .find("the lock is global")
+            .expect("earlier message");
+        let second = markdown.find("use a <Mutex>").expect("later message");
+        assert!(first < second);
+        assert!(markdown.contains("_claude session earlier_"));
+
+        let xml = render(&records, ContextFormat::Xml);
+        assert!(xml.starts_with("<context>\n<message role=\"assistant\""));
+        assert!(xml.contains("session=\"earlier\" ts=\"1970-01-01T00:00:01Z\""));
+        assert!(xml.contains("use a &lt;Mutex&gt;"));
+        assert_eq!(ContextFormat::parse("XML"), Some(ContextFormat::Xml));
+    }
+}
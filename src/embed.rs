@@ -2,6 +2,7 @@ use anyhow::{Result, anyhow};
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
 use model2vec_rs::model::StaticModel;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[cfg(feature = "cuda")]
 use std::path::Path;
@@ -13,6 +14,12 @@ const MEMEX_CUDA_DEVICE_ID_ENV: &str = "MEMEX_CUDA_DEVICE_ID";
 const MEMEX_COMPUTE_UNITS_ENV: &str = "MEMEX_COMPUTE_UNITS";
 const MEMEX_CUDA_LIBRARY_PATHS_ENV: &str = "MEMEX_CUDA_LIBRARY_PATHS";
 const MEMEX_CUDNN_LIBRARY_PATHS_ENV: &str = "MEMEX_CUDNN_LIBRARY_PATHS";
+/// Set (to anything but `0` or `false`) to forbid network access, like
+/// `offline = true` in config.toml or `--offline`.
+pub const MEMEX_OFFLINE_ENV: &str = "MEMEX_OFFLINE";
+
+/// Hugging Face repo the potion model is loaded from.
+const POTION_REPO: &str = "minishlab/potion-base-8M";
 
 #[cfg(all(feature = "cuda", windows))]
 const CUDA_DYLIBS: &[&str] = &[
@@ -71,6 +78,14 @@ pub enum ModelChoice {
 }
 
 impl ModelChoice {
+    pub const ALL: [ModelChoice; 5] = [
+        ModelChoice::MiniLM,
+        ModelChoice::BGESmall,
+        ModelChoice::Nomic,
+        ModelChoice::Gemma,
+        ModelChoice::Potion,
+    ];
+
     fn fastembed_config(self) -> Option<(EmbeddingModel, usize)> {
         match self {
             ModelChoice::MiniLM => Some((EmbeddingModel::AllMiniLML6V2, 384)),
@@ -110,6 +125,82 @@ impl ModelChoice {
     pub fn known_dimensions(self) -> Option<usize> {
         self.fastembed_config().map(|(_, dimensions)| dimensions)
     }
+
+    /// Texts per inference batch when `embedding_batch_size` is unset. Larger
+    /// models get smaller batches to keep peak memory reasonable.
+    pub fn default_batch_size(self) -> usize {
+        match self {
+            ModelChoice::MiniLM | ModelChoice::BGESmall => 256,
+            ModelChoice::Nomic => 64,
+            ModelChoice::Gemma => 32,
+            ModelChoice::Potion => 1024,
+        }
+    }
+
+    /// Tokens kept per text when `embedding_max_length` is unset.
+    pub fn default_max_length(self) -> usize {
+        match self {
+            ModelChoice::MiniLM => 256,
+            _ => 512,
+        }
+    }
+
+    /// Hugging Face repo the model downloads from.
+    pub fn repo_id(self) -> Result<String> {
+        match self.fastembed_config() {
+            Some((model, _)) => Ok(TextEmbedding::get_model_info(&model)?.model_code.clone()),
+            None => Ok(POTION_REPO.to_string()),
+        }
+    }
+
+    fn weights_file(self) -> Result<String> {
+        match self.fastembed_config() {
+            Some((model, _)) => Ok(TextEmbedding::get_model_info(&model)?.model_file.clone()),
+            None => Ok("model.safetensors".to_string()),
+        }
+    }
+
+    /// Hub cache the model is read from and downloaded into, resolved the way
+    /// the embedding libraries do: fastembed uses `HF_HOME`, else
+    /// `FASTEMBED_CACHE_DIR`, else `.fastembed_cache` in the working
+    /// directory; potion uses `HF_HOME/hub`, else `~/.cache/huggingface/hub`.
+    pub fn cache_root(self) -> PathBuf {
+        let hf_home = std::env::var_os("HF_HOME").map(PathBuf::from);
+        match (self.fastembed_config(), hf_home) {
+            (Some(_), Some(home)) => home,
+            (Some(_), None) => PathBuf::from(fastembed::get_cache_dir()),
+            (None, Some(home)) => home.join("hub"),
+            (None, None) => directories::BaseDirs::new()
+                .map(|dirs| dirs.home_dir().join(".cache/huggingface/hub"))
+                .unwrap_or_else(|| PathBuf::from(".cache/huggingface/hub")),
+        }
+    }
+
+    /// Directory holding the downloaded model, or `None` when it would have
+    /// to be fetched first.
+    pub fn cached_snapshot(self) -> Result<Option<PathBuf>> {
+        Ok(hub_snapshot(
+            &self.cache_root(),
+            &self.repo_id()?,
+            &self.weights_file()?,
+        ))
+    }
+}
+
+/// Snapshot of `repo` in a Hugging Face hub cache at `root` whose `main`
+/// revision contains `file`.
+fn hub_snapshot(root: &std::path::Path, repo: &str, file: &str) -> Option<PathBuf> {
+    let repo_dir = root.join(format!("models--{}", repo.replace('/', "--")));
+    let revision = std::fs::read_to_string(repo_dir.join("refs").join("main")).ok()?;
+    let snapshot = repo_dir.join("snapshots").join(revision.trim());
+    snapshot.join(file).is_file().then_some(snapshot)
+}
+
+/// Whether `MEMEX_OFFLINE` is set to a true value.
+pub fn offline_from_env() -> bool {
+    std::env::var(MEMEX_OFFLINE_ENV)
+        .map(|value| !matches!(value.trim().to_lowercase().as_str(), "" | "0" | "false"))
+        .unwrap_or(false)
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -169,6 +260,17 @@ pub struct EmbedRuntimeConfig {
     pub cuda_device_id: Option<i32>,
     pub cuda_library_paths: Vec<PathBuf>,
     pub cudnn_library_paths: Vec<PathBuf>,
+    /// Overrides [`ModelChoice::default_batch_size`].
+    pub batch_size: Option<usize>,
+    /// ONNX Runtime intra-op threads per model; all cores when unset.
+    pub threads: Option<usize>,
+    /// Overrides [`ModelChoice::default_max_length`].
+    pub max_length: Option<usize>,
+    /// Pause after each indexing batch; see
+    /// [`EmbedderHandle::pause_after_batch`].
+    pub batch_pause_ms: Option<u64>,
+    /// Fail instead of downloading a model that is not cached yet.
+    pub offline: bool,
 }
 
 impl EmbedRuntimeConfig {
@@ -179,6 +281,8 @@ impl EmbedRuntimeConfig {
             cuda_device_id: resolve_cuda_device_id_from_env()?,
             cuda_library_paths: resolve_library_paths_from_env(MEMEX_CUDA_LIBRARY_PATHS_ENV),
             cudnn_library_paths: resolve_library_paths_from_env(MEMEX_CUDNN_LIBRARY_PATHS_ENV),
+            offline: offline_from_env(),
+            ..Self::default()
         })
     }
 
@@ -476,9 +580,15 @@ fn normalize_existing_dirs(dirs: Vec<PathBuf>) -> Vec<PathBuf> {
 fn init_options_for_model(
     model_type: EmbeddingModel,
     runtime: &EmbedRuntimeConfig,
+    max_length: usize,
 ) -> Result<InitOptions> {
     let effective_provider = runtime.execution_provider.effective();
-    let opts = InitOptions::new(model_type).with_show_download_progress(false);
+    let mut opts = InitOptions::new(model_type)
+        .with_show_download_progress(false)
+        .with_max_length(max_length);
+    if let Some(threads) = runtime.threads {
+        opts = opts.with_intra_threads(threads);
+    }
 
     match effective_provider {
         ExecutionProviderChoice::Auto => unreachable!("auto should resolve to a concrete provider"),
@@ -562,6 +672,9 @@ enum EmbedBackend {
 pub struct EmbedderHandle {
     backend: EmbedBackend,
     pub dims: usize,
+    batch_size: usize,
+    max_length: usize,
+    batch_pause: Duration,
 }
 
 impl EmbedderHandle {
@@ -574,10 +687,23 @@ impl EmbedderHandle {
         choice: ModelChoice,
         runtime: &EmbedRuntimeConfig,
     ) -> Result<Self> {
+        let batch_size = positive_setting(runtime.batch_size, "embedding_batch_size")?
+            .unwrap_or_else(|| choice.default_batch_size());
+        let max_length = positive_setting(runtime.max_length, "embedding_max_length")?
+            .unwrap_or_else(|| choice.default_max_length());
+        positive_setting(runtime.threads, "embedding_threads")?;
+        let batch_pause = Duration::from_millis(runtime.batch_pause_ms.unwrap_or(0));
+        if runtime.offline && choice.cached_snapshot()?.is_none() {
+            let name = choice.as_str();
+            return Err(anyhow!(
+                "embedding model {name} is not downloaded and network access is disabled \
+                 (offline); run `memex models pull {name}` while online"
+            ));
+        }
         if let Some((model_type, dims)) = choice.fastembed_config() {
             let requested_provider = runtime.execution_provider;
             let effective_provider = requested_provider.effective();
-            let opts = init_options_for_model(model_type, runtime)?;
+            let opts = init_options_for_model(model_type, runtime, max_length)?;
             let model = TextEmbedding::try_new(opts).map_err(|err| match effective_provider {
                 ExecutionProviderChoice::Cuda => anyhow!(
                     "failed to initialize CUDA execution provider: {err}. Ensure the binary was \
@@ -594,9 +720,12 @@ impl EmbedderHandle {
             Ok(Self {
                 backend: EmbedBackend::Fastembed(model),
                 dims,
+                batch_size,
+                max_length,
+                batch_pause,
             })
         } else {
-            let model = StaticModel::from_pretrained("minishlab/potion-base-8M", None, None, None)?;
+            let model = StaticModel::from_pretrained(POTION_REPO, None, None, None)?;
             let dims = model
                 .encode(&[String::from("dimension_check")])
                 .first()
@@ -605,24 +734,49 @@ impl EmbedderHandle {
             Ok(Self {
                 backend: EmbedBackend::Model2Vec(model),
                 dims,
+                batch_size,
+                max_length,
+                batch_pause,
             })
         }
     }
 
+    /// Texts embedded per inference batch; callers buffer this many before
+    /// flushing.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Paces bulk embedding: indexing calls this after every batch so a long
+    /// backfill doesn't keep every core busy. A no-op unless
+    /// `embedding_batch_pause_ms` is set.
+    pub fn pause_after_batch(&self) {
+        if !self.batch_pause.is_zero() {
+            std::thread::sleep(self.batch_pause);
+        }
+    }
+
     pub fn embed_texts(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
         if texts.is_empty() {
             return Ok(Vec::new());
         }
         match &mut self.backend {
-            EmbedBackend::Fastembed(model) => Ok(model.embed(texts, None)?),
+            EmbedBackend::Fastembed(model) => Ok(model.embed(texts, Some(self.batch_size))?),
             EmbedBackend::Model2Vec(model) => {
                 let input: Vec<String> = texts.iter().map(|t| t.to_string()).collect();
-                Ok(model.encode_with_args(&input, Some(512), 64))
+                Ok(model.encode_with_args(&input, Some(self.max_length), self.batch_size))
             }
         }
     }
 }
 
+fn positive_setting(value: Option<usize>, name: &str) -> Result<Option<usize>> {
+    match value {
+        Some(0) => Err(anyhow!("{name} must be greater than 0")),
+        other => Ok(other),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -753,6 +907,84 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hub_snapshot_needs_the_main_revision_and_weights() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let repo = tmp.path().join("models--minishlab--potion-base-8M");
+        assert!(hub_snapshot(tmp.path(), POTION_REPO, "model.safetensors").is_none());
+        std::fs::create_dir_all(repo.join("refs")).expect("refs");
+        std::fs::write(repo.join("refs/main"), "abc123\n").expect("ref");
+        let snapshot = repo.join("snapshots/abc123");
+        std::fs::create_dir_all(&snapshot).expect("snapshot");
+        assert!(hub_snapshot(tmp.path(), POTION_REPO, "model.safetensors").is_none());
+        std::fs::write(snapshot.join("model.safetensors"), b"weights").expect("weights");
+        assert_eq!(
+            hub_snapshot(tmp.path(), POTION_REPO, "model.safetensors"),
+            Some(snapshot)
+        );
+    }
+
+    #[test]
+    fn offline_refuses_models_that_are_not_cached() {
+        let _guard = env_lock();
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let _env = EnvVarGuard::set_os(&[("HF_HOME", Some(tmp.path().as_os_str()))]);
+        let runtime = EmbedRuntimeConfig {
+            offline: true,
+            ..EmbedRuntimeConfig::default()
+        };
+        let err = EmbedderHandle::with_model_and_runtime(ModelChoice::Potion, &runtime)
+            .err()
+            .expect("uncached model should fail offline");
+        assert!(err.to_string().contains("memex models pull potion"));
+    }
+
+    #[test]
+    fn zero_embedding_settings_are_rejected() {
+        for runtime in [
+            EmbedRuntimeConfig {
+                batch_size: Some(0),
+                ..EmbedRuntimeConfig::default()
+            },
+            EmbedRuntimeConfig {
+                threads: Some(0),
+                ..EmbedRuntimeConfig::default()
+            },
+            EmbedRuntimeConfig {
+                max_length: Some(0),
+                ..EmbedRuntimeConfig::default()
+            },
+        ] {
+            let err = EmbedderHandle::with_model_and_runtime(ModelChoice::Potion, &runtime)
+                .err()
+                .expect("zero setting should fail");
+            assert!(err.to_string().contains("must be greater than 0"));
+        }
+    }
+
+    #[test]
+    fn embedding_threads_cap_onnx_runtime_threads() {
+        let runtime = EmbedRuntimeConfig {
+            threads: Some(3),
+            execution_provider: ExecutionProviderChoice::Cpu,
+            ..EmbedRuntimeConfig::default()
+        };
+        let opts = init_options_for_model(EmbeddingModel::AllMiniLML6V2, &runtime, 256)
+            .expect("init options");
+        assert_eq!(opts.intra_threads, Some(3));
+
+        let opts = init_options_for_model(
+            EmbeddingModel::AllMiniLML6V2,
+            &EmbedRuntimeConfig {
+                execution_provider: ExecutionProviderChoice::Cpu,
+                ..EmbedRuntimeConfig::default()
+            },
+            256,
+        )
+        .expect("init options");
+        assert_eq!(opts.intra_threads, None);
+    }
+
     #[test]
     fn test_parse_potion_model() {
         let choice = ModelChoice::parse("potion").expect("parse potion");
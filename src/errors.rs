@@ -0,0 +1,366 @@
+//! Errors in tool output: compiler errors, panics, tracebacks, exceptions
+//! and failed commands. Each gets a fingerprint that ignores paths, line
+//! numbers and other details that change between runs, so `memex errors` can
+//! group the same error across sessions and `error:` queries find it again.
+
+use crate::types::Record;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+/// Hex characters of a fingerprint.
+const FINGERPRINT_LEN: usize = 12;
+/// Errors taken from one tool result; later ones are usually fallout.
+const MAX_ERRORS_PER_RECORD: usize = 5;
+/// Characters of an error message kept for display.
+const MESSAGE_CHARS: usize = 200;
+
+static RUST_ERROR: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^error(\[E\d{4}\])?: (.+)$").expect("rust error regex"));
+static PANIC_INLINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"panicked at '(.+)', \S+:\d+:\d+").expect("inline panic regex"));
+static PANIC_AT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"panicked at \S+:\d+:\d+:$").expect("panic regex"));
+static GO_PANIC: Lazy<Regex> = Lazy::new(|| Regex::new(r"^panic: (.+)$").expect("go panic regex"));
+static EXCEPTION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:Uncaught )?((?:[A-Za-z_][\w]*\.)*[A-Z]\w*(?:Error|Exception))(?:: (.*))?$")
+        .expect("exception regex")
+});
+static FATAL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?:fatal|FATAL|npm ERR!|ERROR):? (.+)$").expect("fatal regex"));
+static EXIT_CODE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^(?:error: )?(?:exit code|exit status|process exited with code)[: ]\s*(-?\d+)")
+        .expect("exit code regex")
+});
+
+static PATH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:[A-Za-z]:)?(?:[\w.~-]*[/\\])+([\w.-]+)").expect("path regex"));
+static LINE_COL: Lazy<Regex> = Lazy::new(|| Regex::new(r":\d+(?::\d+)?").expect("line regex"));
+static HEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\b(?:0x[0-9a-fA-F]+|[0-9a-f]{8,}(?:-[0-9a-f]{4,})*)\b").expect("hex regex")
+});
+static NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b\d+(?:\.\d+)?\b").expect("number regex"));
+
+/// One error found in a tool result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorEvent {
+    /// `rust`, `panic`, `python`, `exception`, `error` or `exit`.
+    pub kind: &'static str,
+    pub message: String,
+    pub fingerprint: String,
+}
+
+impl ErrorEvent {
+    fn new(kind: &'static str, message: &str) -> Self {
+        let message = message.trim();
+        Self {
+            kind,
+            fingerprint: fingerprint(kind, message),
+            message: message.chars().take(MESSAGE_CHARS).collect(),
+        }
+    }
+}
+
+/// Errors in a tool result record; other records have none.
+pub fn record_errors(record: &Record) -> Vec<ErrorEvent> {
+    if record.role != "tool_result" {
+        return Vec::new();
+    }
+    detect_errors(&record.text)
+}
+
+/// Errors in tool output, first one first, each fingerprint once. A
+/// non-zero exit code counts only when nothing more specific was found.
+pub fn detect_errors(text: &str) -> Vec<ErrorEvent> {
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let mut events = Vec::new();
+    let mut exit = None;
+    let mut at = 0;
+    while at < lines.len() {
+        let line = lines[at];
+        let trimmed = line.trim_start();
+        at += 1;
+        if trimmed.starts_with("Traceback (most recent call last)") {
+            // The exception is the first unindented line after the frames.
+            if let Some(offset) = lines[at..]
+                .iter()
+                .position(|line| !line.is_empty() && !line.starts_with([' ', '\t']))
+            {
+                events.push(ErrorEvent::new("python", lines[at + offset]));
+                at += offset + 1;
+            }
+            continue;
+        }
+        if let Some(caps) = PANIC_INLINE.captures(trimmed) {
+            events.push(ErrorEvent::new("panic", &caps[1]));
+        } else if PANIC_AT.is_match(trimmed) {
+            if let Some(message) = lines.get(at).filter(|line| !line.trim().is_empty()) {
+                events.push(ErrorEvent::new("panic", message));
+                at += 1;
+            }
+        } else if let Some(caps) = GO_PANIC.captures(trimmed) {
+            events.push(ErrorEvent::new("panic", &caps[1]));
+        } else if let Some(caps) = RUST_ERROR.captures(trimmed) {
+            let message = &caps[2];
+            // Cargo's summary of the errors above.
+            if message.starts_with("could not compile") || message.starts_with("aborting due to") {
+                continue;
+            }
+            match caps.get(1) {
+                Some(code) => events.push(ErrorEvent::new(
+                    "rust",
+                    &format!("error{}: {message}", code.as_str()),
+                )),
+                None => events.push(ErrorEvent::new("error", message)),
+            }
+        } else if let Some(caps) = EXCEPTION.captures(trimmed) {
+            events.push(ErrorEvent::new(
+                "exception",
+                caps.get(0).map_or("", |m| m.as_str()),
+            ));
+        } else if let Some(caps) = FATAL.captures(trimmed) {
+            events.push(ErrorEvent::new("error", &caps[1]));
+        } else if let Some(caps) = EXIT_CODE.captures(trimmed)
+            && exit.is_none()
+            && caps[1].parse::<i64>().is_ok_and(|code| code != 0)
+        {
+            exit = Some(caps[1].to_string());
+        }
+    }
+    if events.is_empty()
+        && let Some(code) = exit
+    {
+        let last = lines
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty() && !is_exit_noise(line));
+        let message = match last {
+            Some(last) => format!("exit code {code}: {last}"),
+            None => format!("exit code {code}"),
+        };
+        events.push(ErrorEvent::new("exit", &message));
+    }
+    let mut seen = HashSet::new();
+    events.retain(|event| seen.insert(event.fingerprint.clone()));
+    events.truncate(MAX_ERRORS_PER_RECORD);
+    events
+}
+
+/// Lines runners add around a command's output, such as Codex's
+/// `Exit code: 1` and `Wall time: 0.2 seconds`.
+fn is_exit_noise(line: &str) -> bool {
+    EXIT_CODE.is_match(line)
+        || line.starts_with("Wall time")
+        || line == "Output:"
+        || line.starts_with("$ ")
+}
+
+/// Stable id of an error: a hash of its kind and message with paths cut to
+/// file names and line numbers, addresses, ids and other numbers blanked.
+pub fn fingerprint(kind: &str, message: &str) -> String {
+    let normalized = normalize(message);
+    let digest = Sha256::digest(format!("{kind}\0{normalized}").as_bytes());
+    format!("{digest:x}")[..FINGERPRINT_LEN].to_string()
+}
+
+fn normalize(message: &str) -> String {
+    let message = PATH.replace_all(message, "$1");
+    let message = LINE_COL.replace_all(&message, "");
+    let message = HEX.replace_all(&message, "_");
+    let message = NUMBER.replace_all(&message, "N");
+    message.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether `text` looks like a fingerprint rather than words to search for.
+pub fn is_fingerprint(text: &str) -> bool {
+    text.len() == FINGERPRINT_LEN && text.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// An error with how often and where it happened.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorCount {
+    pub fingerprint: String,
+    pub kind: String,
+    /// The message as it last appeared.
+    pub message: String,
+    pub count: usize,
+    pub sessions: usize,
+    pub first_ts: u64,
+    pub last_ts: u64,
+}
+
+/// Errors in `records`, most frequent first, then most recent.
+pub fn count_errors(records: &[Record]) -> Vec<ErrorCount> {
+    let mut counts: HashMap<String, (ErrorCount, HashSet<&str>)> = HashMap::new();
+    for record in records {
+        for event in record_errors(record) {
+            let (count, sessions) = counts.entry(event.fingerprint.clone()).or_insert_with(|| {
+                (
+                    ErrorCount {
+                        fingerprint: event.fingerprint.clone(),
+                        kind: event.kind.to_string(),
+                        message: event.message.clone(),
+                        count: 0,
+                        sessions: 0,
+                        first_ts: record.ts,
+                        last_ts: record.ts,
+                    },
+                    HashSet::new(),
+                )
+            });
+            count.count += 1;
+            count.first_ts = count.first_ts.min(record.ts);
+            if record.ts >= count.last_ts {
+                count.last_ts = record.ts;
+                count.message = event.message;
+            }
+            sessions.insert(record.session_id.as_str());
+        }
+    }
+    let mut counts: Vec<ErrorCount> = counts
+        .into_values()
+        .map(|(mut count, sessions)| {
+            count.sessions = sessions.len();
+            count
+        })
+        .collect();
+    counts.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.last_ts.cmp(&a.last_ts))
+            .then_with(|| a.fingerprint.cmp(&b.fingerprint))
+    });
+    counts
+}
+
+/// The last time a session hit an error, and what the agent said next.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorOccurrence {
+    pub session_id: String,
+    pub project: String,
+    pub source: String,
+    pub ts: u64,
+    pub doc_id: u64,
+    pub message: String,
+    /// Times the session hit it.
+    pub hits: usize,
+    /// The first assistant message after the last hit: usually the fix, or
+    /// why there wasn't one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+/// Where `session`, one session's records in order, hit the error with
+/// `fingerprint`.
+pub fn session_occurrence(session: &[Record], fingerprint: &str) -> Option<ErrorOccurrence> {
+    let mut hits = 0;
+    let mut last = None;
+    for (at, record) in session.iter().enumerate() {
+        if let Some(event) = record_errors(record)
+            .into_iter()
+            .find(|event| event.fingerprint == fingerprint)
+        {
+            hits += 1;
+            last = Some((at, event));
+        }
+    }
+    let (at, event) = last?;
+    let record = &session[at];
+    let next = session[at + 1..]
+        .iter()
+        .find(|record| record.role == "assistant" && !record.text.trim().is_empty())
+        .map(|record| record.text.trim().to_string());
+    Some(ErrorOccurrence {
+        session_id: record.session_id.clone(),
+        project: record.project.clone(),
+        source: record.source.label().to_string(),
+        ts: record.ts,
+        doc_id: record.doc_id,
+        message: event.message,
+        hits,
+        next,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds_and_messages(text: &str) -> Vec<(&'static str, String)> {
+        detect_errors(text)
+            .into_iter()
+            .map(|event| (event.kind, event.message))
+            .collect()
+    }
+
+    #[test]
+    fn finds_compiler_errors_panics_tracebacks_and_failed_commands() {
+        let cargo = "   Compiling memex v0.1.0\n\
+            error[E0382]: borrow of moved value: `records`\n  --> src/index.rs:120:9\n\
+            error: aborting due to 1 previous error\n\
+            error: could not compile `memex` (lib) due to 1 previous error\n";
+        assert_eq!(
+            kinds_and_messages(cargo),
+            [(
+                "rust",
+                "error[E0382]: borrow of moved value: `records`".to_string()
+            )]
+        );
+
+        let panic = "running 3 tests\nthread 'tests::ingest' panicked at src/ingest.rs:88:5:\n\
+            called `Option::unwrap()` on a `None` value\n";
+        assert_eq!(
+            kinds_and_messages(panic),
+            [(
+                "panic",
+                "called `Option::unwrap()` on a `None` value".to_string()
+            )]
+        );
+
+        let python = "Traceback (most recent call last):\n  File \"/home/ana/app.py\", line 3, in <module>\n    \
+            main()\nKeyError: 'user_id'\nexit code: 1\n";
+        assert_eq!(
+            kinds_and_messages(python),
+            [("python", "KeyError: 'user_id'".to_string())]
+        );
+
+        let node = "file:///srv/app/index.js:4\nTypeError: Cannot read properties of undefined (reading 'id')\n";
+        assert_eq!(
+            kinds_and_messages(node),
+            [(
+                "exception",
+                "TypeError: Cannot read properties of undefined (reading 'id')".to_string()
+            )]
+        );
+
+        let codex = "Exit code: 2\nWall time: 0.4 seconds\nOutput:\nmake: *** [test] Error 2\n";
+        assert_eq!(
+            kinds_and_messages(codex),
+            [("exit", "exit code 2: make: *** [test] Error 2".to_string())]
+        );
+        assert!(detect_errors("Exit code: 0\nOutput:\nok\n").is_empty());
+        assert!(detect_errors("all 12 tests passed").is_empty());
+    }
+
+    #[test]
+    fn fingerprints_ignore_paths_line_numbers_and_ids() {
+        let a = "thread 'main' panicked at /home/ana/wt-1/src/main.rs:10:5:\n\
+            index out of bounds: the len is 3 but the index is 7 (0x7ffd1234)\n";
+        let b = "thread 'main' panicked at /Users/bo/memex/src/main.rs:42:9:\n\
+            index out of bounds: the len is 4 but the index is 9 (0x7ffd9999)\n";
+        assert_eq!(
+            detect_errors(a)[0].fingerprint,
+            detect_errors(b)[0].fingerprint
+        );
+        assert_ne!(
+            fingerprint("rust", "error[E0382]: borrow of moved value: `x`"),
+            fingerprint("rust", "error[E0499]: borrow of moved value: `x`")
+        );
+        let id = fingerprint("python", "KeyError: 'user_id'");
+        assert!(is_fingerprint(&id));
+        assert!(!is_fingerprint("KeyError"));
+    }
+}
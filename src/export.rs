@@ -0,0 +1,300 @@
+use anyhow::{Context, Result, anyhow};
+use parquet::data_type::{ByteArray, ByteArrayType, FloatType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Rows buffered before a Parquet row group is written, so an export of a
+/// large history never holds every vector in memory.
+const ROW_GROUP_ROWS: usize = 8192;
+
+/// Bytes reserved for the `.npy` header. The row count is only known at the
+/// end, so the header is written last over this fixed-size slot.
+const NPY_HEADER_LEN: usize = 128;
+
+const PARQUET_SCHEMA: &str = "
+message embedding {
+    required int64 doc_id (INTEGER(64,false));
+    required binary session_id (UTF8);
+    required binary project (UTF8);
+    required int64 ts (TIMESTAMP(MILLIS,true));
+    required group vector (LIST) {
+        repeated group list {
+            required float element;
+        }
+    }
+}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddingFormat {
+    /// One file with the metadata columns and a list column of floats.
+    Parquet,
+    /// A float32 matrix, plus a JSONL file of metadata in the same row order.
+    Npy,
+}
+
+impl EmbeddingFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("npy") => Self::Npy,
+            _ => Self::Parquet,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingRow {
+    pub doc_id: u64,
+    pub session_id: String,
+    pub project: String,
+    pub ts: u64,
+    #[serde(skip)]
+    pub vector: Vec<f32>,
+}
+
+/// Where `.npy` exports put the metadata for each matrix row.
+pub fn npy_metadata_path(path: &Path) -> PathBuf {
+    path.with_extension("jsonl")
+}
+
+/// Streams embedding rows to disk in a format notebooks load directly
+/// (`pandas.read_parquet`, `numpy.load`).
+pub struct EmbeddingExport {
+    dims: usize,
+    rows: usize,
+    sink: Sink,
+}
+
+enum Sink {
+    Parquet {
+        writer: SerializedFileWriter<File>,
+        pending: Vec<EmbeddingRow>,
+    },
+    Npy {
+        matrix: BufWriter<File>,
+        metadata: BufWriter<File>,
+    },
+}
+
+impl EmbeddingExport {
+    pub fn create(path: &Path, format: EmbeddingFormat, dims: usize) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("create {}", path.display()))?;
+        let sink = match format {
+            EmbeddingFormat::Parquet => {
+                let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+                let properties = Arc::new(WriterProperties::builder().build());
+                Sink::Parquet {
+                    writer: SerializedFileWriter::new(file, schema, properties)?,
+                    pending: Vec::with_capacity(ROW_GROUP_ROWS),
+                }
+            }
+            EmbeddingFormat::Npy => {
+                let mut matrix = BufWriter::new(file);
+                matrix.write_all(&[b' '; NPY_HEADER_LEN])?;
+                let metadata_path = npy_metadata_path(path);
+                let metadata = File::create(&metadata_path)
+                    .with_context(|| format!("create {}", metadata_path.display()))?;
+                Sink::Npy {
+                    matrix,
+                    metadata: BufWriter::new(metadata),
+                }
+            }
+        };
+        Ok(Self {
+            dims,
+            rows: 0,
+            sink,
+        })
+    }
+
+    pub fn push(&mut self, row: EmbeddingRow) -> Result<()> {
+        if row.vector.len() != self.dims {
+            return Err(anyhow!(
+                "embedding dimensions mismatch for doc {}: expected {}, got {}",
+                row.doc_id,
+                self.dims,
+                row.vector.len()
+            ));
+        }
+        match &mut self.sink {
+            Sink::Parquet { writer, pending } => {
+                pending.push(row);
+                if pending.len() >= ROW_GROUP_ROWS {
+                    write_row_group(writer, pending)?;
+                }
+            }
+            Sink::Npy { matrix, metadata } => {
+                for value in &row.vector {
+                    matrix.write_all(&value.to_le_bytes())?;
+                }
+                serde_json::to_writer(&mut *metadata, &row)?;
+                metadata.write_all(b"\n")?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Flushes everything and returns the number of rows written.
+    pub fn finish(self) -> Result<usize> {
+        match self.sink {
+            Sink::Parquet {
+                mut writer,
+                mut pending,
+            } => {
+                if !pending.is_empty() {
+                    write_row_group(&mut writer, &mut pending)?;
+                }
+                writer.close()?;
+            }
+            Sink::Npy { matrix, metadata } => {
+                let mut file = matrix.into_inner().map_err(|err| err.into_error())?;
+                file.seek(SeekFrom::Start(0))?;
+                file.write_all(&npy_header(self.rows, self.dims))?;
+                metadata.into_inner().map_err(|err| err.into_error())?;
+            }
+        }
+        Ok(self.rows)
+    }
+}
+
+fn write_row_group(
+    writer: &mut SerializedFileWriter<File>,
+    rows: &mut Vec<EmbeddingRow>,
+) -> Result<()> {
+    let mut row_group = writer.next_row_group()?;
+    let mut column = 0;
+    while let Some(mut column_writer) = row_group.next_column()? {
+        match column {
+            0 => {
+                let values: Vec<i64> = rows.iter().map(|row| row.doc_id as i64).collect();
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&values, None, None)?;
+            }
+            1 | 2 => {
+                let values: Vec<ByteArray> = rows
+                    .iter()
+                    .map(|row| {
+                        let value = if column == 1 {
+                            &row.session_id
+                        } else {
+                            &row.project
+                        };
+                        ByteArray::from(value.as_str())
+                    })
+                    .collect();
+                column_writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)?;
+            }
+            3 => {
+                let values: Vec<i64> = rows.iter().map(|row| row.ts as i64).collect();
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&values, None, None)?;
+            }
+            _ => {
+                // Every element is defined (level 1); a repetition level of 0
+                // starts the next row's list.
+                let values: Vec<f32> = rows
+                    .iter()
+                    .flat_map(|row| row.vector.iter().copied())
+                    .collect();
+                let definition = vec![1i16; values.len()];
+                let repetition: Vec<i16> = rows
+                    .iter()
+                    .flat_map(|row| (0..row.vector.len()).map(|i| i16::from(i > 0)))
+                    .collect();
+                column_writer.typed::<FloatType>().write_batch(
+                    &values,
+                    Some(&definition),
+                    Some(&repetition),
+                )?;
+            }
+        }
+        column_writer.close()?;
+        column += 1;
+    }
+    row_group.close()?;
+    rows.clear();
+    Ok(())
+}
+
+/// NPY 1.0 header for a little-endian float32 `rows x dims` matrix, padded
+/// with spaces to the reserved length.
+fn npy_header(rows: usize, dims: usize) -> Vec<u8> {
+    let dict = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({rows}, {dims}), }}");
+    let mut header = Vec::with_capacity(NPY_HEADER_LEN);
+    header.extend_from_slice(b"\x93NUMPY\x01\x00");
+    header.extend_from_slice(&((NPY_HEADER_LEN - 10) as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header.resize(NPY_HEADER_LEN - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::{ListAccessor, RowAccessor};
+
+    fn row(doc_id: u64, vector: Vec<f32>) -> EmbeddingRow {
+        EmbeddingRow {
+            doc_id,
+            session_id: format!("session-{doc_id}"),
+            project: "memex".to_string(),
+            ts: 1_700_000_000_000 + doc_id,
+            vector,
+        }
+    }
+
+    #[test]
+    fn exports_rows_as_parquet_and_npy() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+
+        let parquet_path = tmp.path().join("embeddings.parquet");
+        let mut export = EmbeddingExport::create(&parquet_path, EmbeddingFormat::Parquet, 3)
+            .expect("create parquet");
+        export.push(row(1, vec![0.1, 0.2, 0.3])).expect("push");
+        export.push(row(2, vec![0.4, 0.5, 0.6])).expect("push");
+        assert!(export.push(row(3, vec![1.0])).is_err());
+        assert_eq!(export.finish().expect("finish"), 2);
+
+        let reader =
+            SerializedFileReader::new(File::open(&parquet_path).expect("open")).expect("reader");
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .expect("rows")
+            .map(|row| row.expect("row"))
+            .collect();
+        assert_eq!(rows[1].get_string(1).expect("session"), "session-2");
+        let vector = rows[1].get_list(4).expect("vector");
+        assert_eq!(vector.len(), 3);
+        assert_eq!(vector.get_float(2).expect("element"), 0.6);
+
+        let npy_path = tmp.path().join("embeddings.npy");
+        assert_eq!(EmbeddingFormat::from_path(&npy_path), EmbeddingFormat::Npy);
+        let mut export =
+            EmbeddingExport::create(&npy_path, EmbeddingFormat::Npy, 2).expect("create npy");
+        export.push(row(7, vec![1.0, -2.0])).expect("push");
+        assert_eq!(export.finish().expect("finish"), 1);
+
+        let bytes = std::fs::read(&npy_path).expect("read npy");
+        assert_eq!(bytes.len(), NPY_HEADER_LEN + 2 * 4);
+        let header = String::from_utf8_lossy(&bytes[10..NPY_HEADER_LEN]);
+        assert!(header.contains("'shape': (1, 2)"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(&bytes[NPY_HEADER_LEN + 4..], &(-2.0f32).to_le_bytes());
+        let metadata =
+            std::fs::read_to_string(npy_metadata_path(&npy_path)).expect("read metadata");
+        assert!(metadata.starts_with("{\"doc_id\":7,\"session_id\":\"session-7\""));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::types::Record;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+/// Characters of message text kept in each node label.
+const LABEL_CHARS: usize = 60;
+
+/// One conversation event. Records that share an `event_id` (an assistant
+/// turn with text plus tool calls) collapse into a single node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    pub event_id: Option<String>,
+    pub roles: Vec<String>,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    /// Follows `logical_parent_event_id` (compaction or summary boundaries)
+    /// rather than a direct parent link.
+    pub logical: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct ConversationGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl ConversationGraph {
+    /// Builds the event tree for one session. `records` should be in
+    /// conversation order. Events without links (sources that do not record
+    /// parents) are chained to the previous node so they still read top to
+    /// bottom; linked events whose parent is not indexed become roots.
+    pub fn from_records(records: &[Record]) -> Self {
+        let mut graph = Self::default();
+        let mut by_event: HashMap<&str, usize> = HashMap::new();
+        let mut parents: Vec<(usize, Option<&str>, bool)> = Vec::new();
+        let mut previous: Option<usize> = None;
+        for record in records {
+            let event_id = record.links.event_id.as_deref();
+            if let Some(event_id) = event_id
+                && let Some(&node) = by_event.get(event_id)
+            {
+                graph.nodes[node].absorb(record);
+                continue;
+            }
+            let node = graph.nodes.len();
+            graph.nodes.push(GraphNode::new(record));
+            if let Some(event_id) = event_id {
+                by_event.insert(event_id, node);
+            }
+            match (
+                record.links.parent_event_id.as_deref(),
+                record.links.logical_parent_event_id.as_deref(),
+            ) {
+                (Some(parent), _) => parents.push((node, Some(parent), false)),
+                (None, Some(parent)) => parents.push((node, Some(parent), true)),
+                (None, None) if event_id.is_none() => {
+                    if let Some(prev) = previous {
+                        graph.edges.push(GraphEdge {
+                            from: prev,
+                            to: node,
+                            logical: false,
+                        });
+                    }
+                }
+                (None, None) => {}
+            }
+            previous = Some(node);
+        }
+        for (node, parent, logical) in parents {
+            if let Some(&from) = parent.and_then(|parent| by_event.get(parent))
+                && from != node
+            {
+                graph.edges.push(GraphEdge {
+                    from,
+                    to: node,
+                    logical,
+                });
+            }
+        }
+        graph.edges.sort_by_key(|edge| (edge.to, edge.from));
+        graph
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart TD\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let label = format!("{}: {}", node.roles.join("+"), node.label);
+            let _ = writeln!(out, "    n{idx}[\"{}\"]", mermaid_escape(&label));
+        }
+        for edge in &self.edges {
+            let arrow = if edge.logical { "-.->" } else { "-->" };
+            let _ = writeln!(out, "    n{} {arrow} n{}", edge.from, edge.to);
+        }
+        out
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph conversation {\n    node [shape=box];\n");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let label = format!("{}: {}", node.roles.join("+"), node.label);
+            let _ = writeln!(out, "    n{idx} [label=\"{}\"];", dot_escape(&label));
+        }
+        for edge in &self.edges {
+            let style = if edge.logical { " [style=dashed]" } else { "" };
+            let _ = writeln!(out, "    n{} -> n{}{style};", edge.from, edge.to);
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl GraphNode {
+    fn new(record: &Record) -> Self {
+        Self {
+            event_id: record.links.event_id.clone(),
+            roles: vec![record.role.clone()],
+            label: label_text(&record.text),
+        }
+    }
+
+    fn absorb(&mut self, record: &Record) {
+        if !self.roles.contains(&record.role) {
+            self.roles.push(record.role.clone());
+        }
+        if self.label.is_empty() {
+            self.label = label_text(&record.text);
+        }
+    }
+}
+
+/// First `LABEL_CHARS` characters with whitespace collapsed.
+fn label_text(text: &str) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() <= LABEL_CHARS {
+        return collapsed;
+    }
+    let mut out: String = collapsed.chars().take(LABEL_CHARS - 1).collect();
+    out.push('…');
+    out
+}
+
+/// Mermaid labels are quoted; quotes and markup characters become entities.
+fn mermaid_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("#quot;"),
+            '<' => out.push_str("#lt;"),
+            '>' => out.push_str("#gt;"),
+            '#' => out.push_str("#35;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{RecordLinks, test_record};
+
+    #[test]
+    fn branches_share_a_parent_and_blocks_share_a_node() {
+        let records = vec![
+            Record {
+                text: "fix the \"parser\"".to_string(),
+                links: RecordLinks {
+                    event_id: Some("a".to_string()),
+                    ..RecordLinks::default()
+                },
+                ..test_record(0, "s1")
+            },
+            Record {
+                role: "assistant".to_string(),
+                text: "first try".to_string(),
+                links: RecordLinks {
+                    event_id: Some("b".to_string()),
+                    parent_event_id: Some("a".to_string()),
+                    ..RecordLinks::default()
+                },
+                ..test_record(0, "s1")
+            },
+            Record {
+                role: "tool_use".to_string(),
+                text: String::new(),
+                links: RecordLinks {
+                    event_id: Some("b".to_string()),
+                    parent_event_id: Some("a".to_string()),
+                    ..RecordLinks::default()
+                },
+                ..test_record(0, "s1")
+            },
+            Record {
+                role: "assistant".to_string(),
+                text: "retry".to_string(),
+                links: RecordLinks {
+                    event_id: Some("c".to_string()),
+                    parent_event_id: Some("a".to_string()),
+                    ..RecordLinks::default()
+                },
+                ..test_record(0, "s1")
+            },
+            Record {
+                text: "thanks".to_string(),
+                links: RecordLinks {
+                    event_id: Some("d".to_string()),
+                    parent_event_id: Some("c".to_string()),
+                    ..RecordLinks::default()
+                },
+                ..test_record(0, "s1")
+            },
+        ];
+        let graph = ConversationGraph::from_records(&records);
+
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.nodes[1].roles, vec!["assistant", "tool_use"]);
+        let edges: Vec<(usize, usize)> = graph.edges.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(edges, vec![(0, 1), (0, 2), (2, 3)]);
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("n0[\"user: fix the #quot;parser#quot;\"]"));
+        assert!(mermaid.contains("n0 --> n2"));
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("n0 [label=\"user: fix the \\\"parser\\\"\"];"));
+        assert!(dot.contains("n2 -> n3;"));
+    }
+
+    #[test]
+    fn unlinked_records_form_a_chain() {
+        let records = vec![
+            test_record(0, "s1"),
+            Record {
+                role: "assistant".to_string(),
+                text: "hi".to_string(),
+                ..test_record(0, "s1")
+            },
+            Record {
+                text: "bye".to_string(),
+                ..test_record(0, "s1")
+            },
+        ];
+        let graph = ConversationGraph::from_records(&records);
+        let edges: Vec<(usize, usize)> = graph.edges.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(edges, vec![(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn labels_are_collapsed_and_truncated() {
+        let long = "word ".repeat(40);
+        let label = label_text(&long);
+        assert_eq!(label.chars().count(), LABEL_CHARS);
+        assert!(label.ends_with('…'));
+        assert_eq!(label_text("a\n\n  b"), "a b");
+    }
+}
@@ -0,0 +1,189 @@
+//! Startup checks of the index and vector store, so the TUI can say what is
+//! wrong and how to fix it instead of quietly returning odd results.
+
+use crate::config::{Paths, UserConfig};
+use crate::index::SearchIndex;
+use crate::state::{
+    IngestErrors, ScanCache, ServiceStatus, ingest_errors_path, service_status_path,
+};
+use crate::vector::StoredVectors;
+use anyhow::Result;
+
+/// Share of user and assistant messages that must have vectors before
+/// semantic search is considered healthy.
+const MIN_EMBED_COVERAGE: f64 = 0.9;
+
+/// Messages without vectors that are always tolerated, e.g. ones written
+/// since the last embedding run.
+const UNEMBEDDED_SLACK: usize = 50;
+
+/// A scan older than this counts as stale when nothing refreshes the index
+/// on open.
+const STALE_SCAN_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthIssue {
+    pub problem: String,
+    /// Command or key that fixes it.
+    pub fix: String,
+}
+
+impl HealthIssue {
+    pub fn new(problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            problem: problem.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+/// Checks, in order of how much they break search: vectors built by another
+/// model, embedding coverage, the background index service's last run, how
+/// long ago sources were scanned and source lines that failed to parse.
+/// `now` is in Unix seconds.
+pub fn check_health(
+    paths: &Paths,
+    config: &UserConfig,
+    index: &SearchIndex,
+    now: u64,
+) -> Result<Vec<HealthIssue>> {
+    let mut issues = Vec::new();
+    let model = config.resolve_model(None)?;
+    let stored = StoredVectors::read(&paths.vectors)?;
+    match &stored {
+        Some(stored)
+            if stored.model.as_deref() != Some(model.as_str())
+                || model
+                    .known_dimensions()
+                    .is_some_and(|dims| dims != stored.dimensions) =>
+        {
+            issues.push(HealthIssue::new(
+                format!(
+                    "vectors were built with {} ({} dims) but config uses {}",
+                    stored.model.as_deref().unwrap_or("an unknown model"),
+                    stored.dimensions,
+                    model.as_str()
+                ),
+                "memex embed",
+            ));
+        }
+        Some(stored) if config.embeddings_default() => {
+            let embeddable = index.count_roles(&["user", "assistant"])?;
+            let missing = embeddable.saturating_sub(stored.count);
+            if missing > UNEMBEDDED_SLACK
+                && (stored.count as f64) < embeddable as f64 * MIN_EMBED_COVERAGE
+            {
+                issues.push(HealthIssue::new(
+                    format!(
+                        "only {}% of messages have embeddings",
+                        stored.count * 100 / embeddable.max(1)
+                    ),
+                    "memex embed",
+                ));
+            }
+        }
+        None if config.embeddings_default() && index.doc_count()? > 0 => {
+            issues.push(HealthIssue::new(
+                "embeddings are on but none have been built",
+                "memex embed",
+            ));
+        }
+        _ => {}
+    }
+
+    let status = ServiceStatus::load(&service_status_path(&paths.state))?;
+    if let Some(error) = &status.error {
+        let first_line = error.lines().next().unwrap_or_default();
+        issues.push(HealthIssue::new(
+            format!("last background index run failed: {first_line}"),
+            "memex status",
+        ));
+    }
+
+    if !config.auto_index_on_search_default() {
+        let cache = ScanCache::load(&paths.state.join("scan_cache.json"))?;
+        let age = now.saturating_sub(cache.last_scan_ts);
+        if cache.last_scan_ts > 0 && age > STALE_SCAN_SECS {
+            issues.push(HealthIssue::new(
+                format!("sources were last scanned {} days ago", age / 86_400),
+                "press i or run memex index",
+            ));
+        }
+    }
+
+    let errors = IngestErrors::load(&ingest_errors_path(&paths.state))?;
+    if !errors.files.is_empty() {
+        issues.push(HealthIssue::new(
+            format!(
+                "malformed lines skipped: {} in {} files",
+                errors.line_count(),
+                errors.files.len()
+            ),
+            "memex ingest-errors",
+        ));
+    }
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_mismatched_vectors_failed_runs_and_stale_scans() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        paths.ensure_dirs().expect("dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let config = UserConfig {
+            model: Some("minilm".to_string()),
+            auto_index_on_search: Some(false),
+            ..UserConfig::default()
+        };
+        let now = 10 * 86_400;
+        assert!(
+            check_health(&paths, &config, &index, now)
+                .expect("healthy")
+                .is_empty()
+        );
+
+        std::fs::create_dir_all(&paths.vectors).expect("vectors dir");
+        std::fs::write(
+            paths.vectors.join("meta.json"),
+            r#"{"dimensions":768,"model":"nomic","index_file":"usearch.index","ids_file":"doc_ids.bin"}"#,
+        )
+        .expect("meta");
+        ServiceStatus {
+            last_run: now - 60,
+            last_success: None,
+            error: Some("permission denied\ncaused by: ...".to_string()),
+            failures: 1,
+        }
+        .save(&service_status_path(&paths.state))
+        .expect("status");
+        ScanCache {
+            last_scan_ts: now - 3 * 86_400,
+            ..ScanCache::default()
+        }
+        .save(&paths.state.join("scan_cache.json"))
+        .expect("cache");
+        let mut errors = IngestErrors::default();
+        errors.update("/s.jsonl", "claude", true, 2, Vec::new());
+        errors
+            .save(&ingest_errors_path(&paths.state))
+            .expect("errors");
+
+        let issues = check_health(&paths, &config, &index, now).expect("issues");
+        let problems: Vec<&str> = issues.iter().map(|issue| issue.problem.as_str()).collect();
+        assert_eq!(
+            problems,
+            [
+                "vectors were built with nomic (768 dims) but config uses minilm",
+                "last background index run failed: permission denied",
+                "sources were last scanned 3 days ago",
+                "malformed lines skipped: 2 in 1 files",
+            ]
+        );
+        assert_eq!(issues[0].fix, "memex embed");
+    }
+}
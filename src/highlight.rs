@@ -0,0 +1,483 @@
+//! Lightweight syntax highlighting for fenced code in the TUI preview.
+//! Lines are split into keyword, string, number, comment and type tokens
+//! from a small per-language table; it is a coloring aid, not a parser.
+
+/// What a run of characters in a code line is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Type,
+}
+
+#[derive(Debug)]
+pub struct Lang {
+    pub name: &'static str,
+    keywords: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Capitalized identifiers are types (Rust, Go, Java, TypeScript).
+    capitalized_types: bool,
+}
+
+const RUST: Lang = Lang {
+    name: "rust",
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    capitalized_types: true,
+};
+
+const PYTHON: Lang = Lang {
+    name: "python",
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    capitalized_types: false,
+};
+
+const JAVASCRIPT: Lang = Lang {
+    name: "javascript",
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "else",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "null",
+        "of",
+        "return",
+        "static",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "type",
+        "typeof",
+        "undefined",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    capitalized_types: true,
+};
+
+const GO: Lang = Lang {
+    name: "go",
+    keywords: &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "false",
+        "for",
+        "func",
+        "go",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "nil",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "true",
+        "type",
+        "var",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '`', '\''],
+    capitalized_types: true,
+};
+
+const C_LIKE: Lang = Lang {
+    name: "c",
+    keywords: &[
+        "auto",
+        "bool",
+        "break",
+        "case",
+        "char",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "double",
+        "else",
+        "enum",
+        "extends",
+        "false",
+        "final",
+        "float",
+        "for",
+        "if",
+        "implements",
+        "import",
+        "int",
+        "long",
+        "namespace",
+        "new",
+        "null",
+        "nullptr",
+        "package",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "short",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "typedef",
+        "unsigned",
+        "using",
+        "void",
+        "while",
+    ],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    capitalized_types: true,
+};
+
+const SHELL: Lang = Lang {
+    name: "shell",
+    keywords: &[
+        "case", "do", "done", "echo", "elif", "else", "esac", "export", "fi", "for", "function",
+        "if", "in", "local", "return", "then", "while",
+    ],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    capitalized_types: false,
+};
+
+const JSON: Lang = Lang {
+    name: "json",
+    keywords: &["false", "null", "true"],
+    line_comments: &[],
+    block_comment: None,
+    quotes: &['"'],
+    capitalized_types: false,
+};
+
+const TOML_YAML: Lang = Lang {
+    name: "toml",
+    keywords: &["false", "true", "null", "yes", "no"],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    capitalized_types: false,
+};
+
+const SQL: Lang = Lang {
+    name: "sql",
+    keywords: &[
+        "and", "as", "by", "create", "delete", "desc", "from", "group", "insert", "into", "join",
+        "limit", "not", "null", "on", "or", "order", "select", "set", "table", "update", "values",
+        "where", "AND", "AS", "BY", "CREATE", "DELETE", "DESC", "FROM", "GROUP", "INSERT", "INTO",
+        "JOIN", "LIMIT", "NOT", "NULL", "ON", "OR", "ORDER", "SELECT", "SET", "TABLE", "UPDATE",
+        "VALUES", "WHERE",
+    ],
+    line_comments: &["--"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['\''],
+    capitalized_types: false,
+};
+
+/// The language named by a fence's info string (```` ```rust ````), by its
+/// usual name or file extension.
+pub fn lang_for_fence(info: &str) -> Option<&'static Lang> {
+    let name = info
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '{')
+        .next()?
+        .trim_start_matches('.')
+        .to_ascii_lowercase();
+    let lang = match name.as_str() {
+        "rust" | "rs" => &RUST,
+        "python" | "py" | "python3" => &PYTHON,
+        "javascript" | "js" | "jsx" | "typescript" | "ts" | "tsx" | "mjs" => &JAVASCRIPT,
+        "go" | "golang" => &GO,
+        "c" | "h" | "cpp" | "c++" | "cc" | "hpp" | "java" | "kotlin" | "kt" | "cs" | "csharp"
+        | "swift" => &C_LIKE,
+        "sh" | "bash" | "zsh" | "shell" | "console" | "fish" => &SHELL,
+        "json" | "jsonl" | "jsonc" => &JSON,
+        "toml" | "yaml" | "yml" | "ini" => &TOML_YAML,
+        "sql" => &SQL,
+        _ => return None,
+    };
+    Some(lang)
+}
+
+/// A language for an untagged block, from telltale starts of its lines.
+pub fn guess_lang<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<&'static Lang> {
+    for line in lines.into_iter().take(8) {
+        let line = line.trim_start();
+        let starts = |prefixes: &[&str]| prefixes.iter().any(|prefix| line.starts_with(prefix));
+        if starts(&["fn ", "pub fn ", "use ", "impl ", "let mut ", "#[derive"]) {
+            return Some(&RUST);
+        }
+        if starts(&["func ", "package "]) {
+            return Some(&GO);
+        }
+        // `import x from "y"` is JavaScript, `from x import y` Python.
+        if starts(&["function ", "const ", "export ", "import {"])
+            || starts(&["import "]) && line.contains(" from ")
+        {
+            return Some(&JAVASCRIPT);
+        }
+        if starts(&["def ", "class "]) && line.ends_with(':') || starts(&["from ", "import "]) {
+            return Some(&PYTHON);
+        }
+        if starts(&["#!/bin/", "$ ", "cd ", "cargo ", "git ", "npm ", "echo "]) {
+            return Some(&SHELL);
+        }
+        if starts(&["{", "["]) && !line.starts_with("[[") {
+            return Some(&JSON);
+        }
+    }
+    None
+}
+
+/// Highlights a block line by line, carrying block comments across lines.
+#[derive(Debug)]
+pub struct Highlighter {
+    lang: &'static Lang,
+    in_block_comment: bool,
+}
+
+impl Highlighter {
+    pub fn new(lang: &'static Lang) -> Self {
+        Self {
+            lang,
+            in_block_comment: false,
+        }
+    }
+
+    /// `line` split into tokens; joining their text gives `line` back.
+    pub fn line(&mut self, line: &str) -> Vec<(TokenKind, String)> {
+        let lang = self.lang;
+        let mut tokens = Tokens::default();
+        let mut rest = line;
+        while !rest.is_empty() {
+            if self.in_block_comment {
+                let (_, close) = lang.block_comment.expect("only set for block comments");
+                let end = rest.find(close).map_or(rest.len(), |at| at + close.len());
+                self.in_block_comment = end == rest.len() && !rest.ends_with(close);
+                tokens.push(TokenKind::Comment, &rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+            if lang
+                .line_comments
+                .iter()
+                .any(|start| rest.starts_with(start))
+            {
+                tokens.push(TokenKind::Comment, rest);
+                break;
+            }
+            if let Some((open, _)) = lang.block_comment
+                && rest.starts_with(open)
+            {
+                self.in_block_comment = true;
+                tokens.push(TokenKind::Comment, open);
+                rest = &rest[open.len()..];
+                continue;
+            }
+            let first = rest.chars().next().expect("rest is not empty");
+            if lang.quotes.contains(&first) {
+                let end = string_end(rest, first);
+                tokens.push(TokenKind::String, &rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+            let word_end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if word_end == 0 {
+                tokens.push(TokenKind::Plain, &rest[..first.len_utf8()]);
+                rest = &rest[first.len_utf8()..];
+                continue;
+            }
+            let mut word_end = word_end;
+            let kind = if first.is_ascii_digit() {
+                // Decimals: `1.5`, but not `x.0` field access or `0..10`.
+                if rest[word_end..].starts_with('.')
+                    && rest[word_end + 1..].starts_with(|c: char| c.is_ascii_digit())
+                {
+                    word_end += 1 + rest[word_end + 1..]
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len() - word_end - 1);
+                }
+                TokenKind::Number
+            } else if lang.keywords.contains(&&rest[..word_end]) {
+                TokenKind::Keyword
+            } else if lang.capitalized_types && first.is_uppercase() {
+                TokenKind::Type
+            } else {
+                TokenKind::Plain
+            };
+            tokens.push(kind, &rest[..word_end]);
+            rest = &rest[word_end..];
+        }
+        tokens.0
+    }
+}
+
+/// Byte offset just past the string starting at `text`, or the end of the
+/// line when it isn't closed there.
+fn string_end(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (at, c) in text.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return at + c.len_utf8();
+        }
+    }
+    text.len()
+}
+
+/// Tokens with adjacent runs of the same kind merged.
+#[derive(Default)]
+struct Tokens(Vec<(TokenKind, String)>);
+
+impl Tokens {
+    fn push(&mut self, kind: TokenKind, text: &str) {
+        match self.0.last_mut() {
+            Some((last, run)) if *last == kind => run.push_str(text),
+            _ => self.0.push((kind, text.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(lang: &'static Lang, line: &str) -> Vec<(TokenKind, String)> {
+        Highlighter::new(lang).line(line)
+    }
+
+    #[test]
+    fn highlights_keywords_strings_numbers_and_comments() {
+        let line = r#"let name: String = "a \"b\""; // 1.5 retries"#;
+        let tokens = kinds(&RUST, line);
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|(_, text)| text.as_str())
+                .collect::<String>(),
+            line
+        );
+        assert_eq!(tokens[0], (TokenKind::Keyword, "let".to_string()));
+        assert!(tokens.contains(&(TokenKind::Type, "String".to_string())));
+        assert!(tokens.contains(&(TokenKind::String, r#""a \"b\"""#.to_string())));
+        assert_eq!(
+            tokens.last(),
+            Some(&(TokenKind::Comment, "// 1.5 retries".to_string()))
+        );
+        assert!(kinds(&PYTHON, "x = 1.5").contains(&(TokenKind::Number, "1.5".to_string())));
+        assert!(kinds(&RUST, "0..10").contains(&(TokenKind::Number, "0".to_string())));
+        assert_eq!(kinds(&PYTHON, "# note")[0].0, TokenKind::Comment);
+
+        let mut block = Highlighter::new(&GO);
+        assert_eq!(
+            block.line("x /* open").last().unwrap().0,
+            TokenKind::Comment
+        );
+        assert_eq!(
+            block.line("still */ y")[0],
+            (TokenKind::Comment, "still */".to_string())
+        );
+        assert_eq!(block.line("return")[0].0, TokenKind::Keyword);
+    }
+
+    #[test]
+    fn finds_languages_from_fences_and_content() {
+        assert_eq!(lang_for_fence("rs").map(|lang| lang.name), Some("rust"));
+        assert_eq!(
+            lang_for_fence("tsx title=\"a\"").map(|lang| lang.name),
+            Some("javascript")
+        );
+        assert_eq!(
+            lang_for_fence("Python").map(|lang| lang.name),
+            Some("python")
+        );
+        assert!(lang_for_fence("brainfuck").is_none());
+        assert!(lang_for_fence("").is_none());
+
+        assert_eq!(
+            guess_lang(["", "fn main() {"]).map(|lang| lang.name),
+            Some("rust")
+        );
+        assert_eq!(
+            guess_lang(["def run(x):"]).map(|lang| lang.name),
+            Some("python")
+        );
+        assert_eq!(
+            guess_lang(["$ cargo test"]).map(|lang| lang.name),
+            Some("shell")
+        );
+        assert!(guess_lang(["just some prose"]).is_none());
+    }
+}
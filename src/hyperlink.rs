@@ -0,0 +1,94 @@
+//! OSC 8 terminal hyperlinks for session files and project directories, so
+//! terminals that support them open the path on click. Terminals without
+//! support print the text and ignore the escape codes.
+
+use crate::config::UserConfig;
+use std::io::IsTerminal;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hyperlinks {
+    enabled: bool,
+}
+
+impl Hyperlinks {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// On when stdout is a terminal, unless `hyperlinks = false` or
+    /// `TERM=dumb`.
+    pub fn for_stdout(config: &UserConfig) -> Self {
+        Self::new(std::io::stdout().is_terminal() && terminal_allows(config))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// `text` linked to the file or directory at `path`; just `text` when
+    /// links are off or `path` is not absolute.
+    pub fn path(&self, path: &str, text: &str) -> String {
+        match file_url(path).filter(|_| self.enabled) {
+            Some(url) => hyperlink(&url, text),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Whether links may go to a terminal, leaving aside whether the output is
+/// one.
+pub fn terminal_allows(config: &UserConfig) -> bool {
+    config.hyperlinks() && std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+}
+
+pub fn hyperlink(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
+/// A `file://` URL for an absolute path, percent-encoding everything but
+/// unreserved characters and separators.
+pub fn file_url(path: &str) -> Option<String> {
+    if path.is_empty() || !Path::new(path).is_absolute() {
+        return None;
+    }
+    let path = path.replace('\\', "/");
+    let mut url = String::with_capacity(path.len() + 8);
+    url.push_str("file://");
+    // Windows drive paths ("C:/...") need the third slash too.
+    if !path.starts_with('/') {
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_absolute_paths_only_when_enabled() {
+        assert_eq!(
+            file_url("/home/me/my project/a#1.jsonl").as_deref(),
+            Some("file:///home/me/my%20project/a%231.jsonl")
+        );
+        assert_eq!(file_url("relative/path"), None);
+        assert_eq!(file_url(""), None);
+
+        let on = Hyperlinks::new(true);
+        assert_eq!(
+            on.path("/tmp/s.jsonl", "s"),
+            "\x1b]8;;file:///tmp/s.jsonl\x1b\\s\x1b]8;;\x1b\\"
+        );
+        assert_eq!(on.path("s.jsonl", "s"), "s");
+        assert_eq!(Hyperlinks::new(false).path("/tmp/s.jsonl", "s"), "s");
+    }
+}
@@ -1,15 +1,33 @@
-use crate::types::{Record, RecordLinks};
+use crate::scoring::TermScore;
+use crate::tokenizer::CODE_TOKENIZER;
+use crate::types::{AgentSettings, Record, RecordLinks};
 use anyhow::{Result, anyhow};
+use indicatif::{ProgressBar, ProgressStyle};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Bound;
-use std::path::Path;
-use tantivy::collector::TopDocs;
-use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, RangeQuery, TermQuery};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tantivy::collector::{Count, DocSetCollector, TopDocs};
+use tantivy::directory::Directory;
+use tantivy::indexer::NoMergePolicy;
+use tantivy::query::{
+    AllQuery, Bm25StatisticsProvider, BooleanQuery, BoostQuery, ConstScoreQuery, EnableScoring,
+    Occur, Query, RangeQuery, TermQuery,
+};
 use tantivy::schema::Value;
 use tantivy::schema::{
-    FAST, Field, INDEXED, IndexRecordOption, STORED, STRING, Schema, SchemaBuilder, TEXT,
-    TextFieldIndexing, TextOptions,
+    FAST, Field, FieldType, INDEXED, IndexRecordOption, STORED, STRING, Schema, SchemaBuilder,
+    TEXT, TextFieldIndexing, TextOptions,
+};
+use tantivy::store::{Compressor, ZstdCompressor};
+use tantivy::{
+    DocAddress, Index, IndexReader, IndexSettings, IndexWriter, Order, ReloadPolicy, Searcher,
+    SegmentComponent, SegmentReader, TantivyDocument, Term,
 };
-use tantivy::{Index, IndexReader, IndexWriter, Order, TantivyDocument, Term};
 
 #[derive(Clone)]
 pub struct IndexFields {
@@ -30,19 +48,82 @@ pub struct IndexFields {
     pub parent_session_id: Field,
     pub thread_source: Field,
     pub conversation_kind: Field,
+    pub thread: Field,
     pub parent_tool_use_id: Field,
     pub source_tool_use_id: Field,
     pub source_tool_assistant_uuid: Field,
     pub source_path: Field,
+    pub ingested_at: Field,
+    pub model: Field,
+    pub sandbox: Field,
+    pub approval_policy: Field,
+    pub attachments: Field,
+    pub attachment_kind: Field,
+    pub code: Field,
+    pub edited_path: Field,
+    pub command: Field,
+    pub file: Field,
+    pub lang: Field,
+    pub error: Field,
 }
 
 #[derive(Clone)]
 pub struct SearchIndex {
     pub index: Index,
     pub fields: IndexFields,
+    /// Where [`SearchIndex::split_months`] moves old months to; every read
+    /// searches the shards found there along with this index.
+    shards_dir: PathBuf,
+    shards: Arc<Mutex<Vec<MonthShard>>>,
+}
+
+/// One month of records moved out of the live index. Shards are written
+/// only by a split and by deletes, so their readers reload by hand when
+/// `meta.json` changes.
+struct MonthShard {
+    month: String,
+    dir: PathBuf,
+    index: SearchIndex,
+    reader: IndexReader,
+    modified: Option<SystemTime>,
+}
+
+impl MonthShard {
+    fn open(dir: PathBuf, month: String) -> Result<Self> {
+        let index = SearchIndex::open_or_create(&dir)?;
+        let reader = index
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        Ok(Self {
+            modified: meta_modified(&dir),
+            month,
+            dir,
+            index,
+            reader,
+        })
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let modified = meta_modified(&self.dir);
+        if modified != self.modified {
+            self.reader.reload()?;
+            self.modified = modified;
+        }
+        Ok(())
+    }
+}
+
+/// A month shard and how many records it holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShardInfo {
+    pub month: String,
+    pub records: usize,
+    pub dir: PathBuf,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryOptions {
     pub query: String,
     pub project: Option<String>,
@@ -52,9 +133,234 @@ pub struct QueryOptions {
     pub source: Option<crate::types::SourceFilter>,
     pub since: Option<u64>,
     pub until: Option<u64>,
+    /// Only consider records ingested at or before this time (unix millis).
+    pub as_of: Option<u64>,
+    /// Exact-match filters on the agent settings a record was written under.
+    pub settings: AgentSettings,
+    /// `has:` filters: attachment kinds (`image`, `document`) or `attachment`.
+    pub has: Vec<String>,
+    /// `edited:` filters: paths, or path tails, of files a record's patch
+    /// edited.
+    #[serde(default)]
+    pub edited: Vec<String>,
+    /// `cmd:` filters: words the shell commands a record ran must contain.
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// `file:` filters: paths, or path tails, of files a record mentions,
+    /// reads or edits.
+    #[serde(default)]
+    pub files: Vec<String>,
+    /// `lang:` filters: language codes (`de`, `en`) records must be written
+    /// in.
+    #[serde(default)]
+    pub langs: Vec<String>,
+    /// `error:` filters: fingerprints or kinds (`panic`, `rust`) of errors in
+    /// a tool result.
+    #[serde(default)]
+    pub errors: Vec<String>,
+    /// Require every query term instead of any (from the scoring profile).
+    pub require_all_terms: bool,
+    /// Edit distance allowed when matching query terms.
+    pub fuzzy_distance: u8,
+    /// Stopwords, minimum term length and role boosts from config.toml.
+    #[serde(default)]
+    pub tuning: QueryTuning,
     pub limit: usize,
 }
 
+impl QueryOptions {
+    /// Whether any metadata filter narrows the records a query can match.
+    pub fn has_filters(&self) -> bool {
+        self.project.is_some()
+            || self.role.is_some()
+            || self.tool.is_some()
+            || self.session_id.is_some()
+            || self.source.is_some()
+            || self.since.is_some()
+            || self.until.is_some()
+            || self.as_of.is_some()
+            || !self.settings.is_empty()
+            || !self.has.is_empty()
+            || !self.edited.is_empty()
+            || !self.commands.is_empty()
+            || !self.files.is_empty()
+            || !self.langs.is_empty()
+            || !self.errors.is_empty()
+    }
+}
+
+/// How keyword queries are built, from `stopwords`, `min_term_length` and
+/// `role_boosts` in config.toml.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryTuning {
+    /// Lowercase words dropped from queries.
+    pub stopwords: HashSet<String>,
+    /// Query words with fewer characters are dropped.
+    pub min_term_length: usize,
+    /// Score multipliers by record role; roles left out count 1.0.
+    pub role_boosts: HashMap<String, f32>,
+}
+
+impl QueryTuning {
+    /// `query` without stopwords and words shorter than `min_term_length`.
+    /// Phrases, `field:` terms, `+`/`-` terms and queries with boolean
+    /// operators are left as written, and a query that would end up empty is
+    /// returned unchanged.
+    pub fn filter_query(&self, query: &str) -> String {
+        if self.stopwords.is_empty() && self.min_term_length <= 1 {
+            return query.to_string();
+        }
+        let parts: Vec<&str> = query.split_whitespace().collect();
+        if parts
+            .iter()
+            .any(|part| matches!(*part, "AND" | "OR" | "NOT"))
+        {
+            return query.to_string();
+        }
+        let mut kept = Vec::with_capacity(parts.len());
+        let mut in_phrase = false;
+        for part in parts {
+            let quoted = in_phrase || part.contains('"');
+            if part.matches('"').count() % 2 == 1 {
+                in_phrase = !in_phrase;
+            }
+            let word = part.trim_end_matches([',', '.', ';', '?', '!']);
+            let droppable = !quoted && !word.is_empty() && word.chars().all(char::is_alphanumeric);
+            if droppable
+                && (word.chars().count() < self.min_term_length
+                    || self.stopwords.contains(&word.to_lowercase()))
+            {
+                continue;
+            }
+            kept.push(part);
+        }
+        if kept.is_empty() {
+            query.to_string()
+        } else {
+            kept.join(" ")
+        }
+    }
+}
+
+/// Query prefixes that filter on record metadata instead of matching text.
+const FILTER_PREFIXES: [&str; 13] = [
+    "model:",
+    "sandbox:",
+    "approval:",
+    "approval_policy:",
+    "has:",
+    "edited:",
+    "cmd:",
+    "file:",
+    "lang:",
+    "error:",
+    "type:",
+    "after:",
+    "before:",
+];
+
+/// Kind indexed for every record with attachments, so `has:attachment`
+/// matches any of them.
+pub const ANY_ATTACHMENT: &str = "attachment";
+
+/// Filters written inline in a query, e.g. `model:o3`, `has:image`,
+/// `edited:src/main.rs`, `cmd:cargo`, `file:README.md`, `lang:de`,
+/// `error:panic`, `type:todo` or `after:last-monday`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryFilters {
+    pub settings: AgentSettings,
+    pub has: Vec<String>,
+    pub edited: Vec<String>,
+    pub commands: Vec<String>,
+    pub files: Vec<String>,
+    pub langs: Vec<String>,
+    pub errors: Vec<String>,
+    /// `type:` filter: the role records must have, e.g. `todo` or `plan`.
+    pub role: Option<String>,
+    /// `after:` and `before:` bounds in Unix millis, e.g. from
+    /// `after:yesterday` or `before:"2 weeks ago"`.
+    pub after: Option<u64>,
+    pub before: Option<u64>,
+}
+
+impl QueryFilters {
+    /// The later of `since` and the query's `after:` bound.
+    pub fn since(&self, since: Option<u64>) -> Option<u64> {
+        since.max(self.after)
+    }
+
+    /// The earlier of `until` and the query's `before:` bound.
+    pub fn until(&self, until: Option<u64>) -> Option<u64> {
+        until.into_iter().chain(self.before).min()
+    }
+}
+
+/// Pulls `model:o3`, `sandbox:read-only`, `approval:never`, `has:image`,
+/// `edited:path`, `cmd:word`, `file:path`, `lang:de`, `error:panic`,
+/// `type:todo`, `after:yesterday` and `before:"2 weeks ago"` terms out of a
+/// query, returning the remaining text and the filters. A date the filters
+/// can't read stays in the text.
+pub fn extract_query_filters(query: &str) -> (String, QueryFilters) {
+    let mut filters = QueryFilters::default();
+    let mut rest = Vec::new();
+    for part in quoted_parts(query) {
+        let Some(prefix) = FILTER_PREFIXES
+            .iter()
+            .find(|prefix| part.len() > prefix.len() && part.starts_with(*prefix))
+        else {
+            rest.push(part);
+            continue;
+        };
+        let value = part[prefix.len()..].trim_matches('"').to_string();
+        match *prefix {
+            "after:" | "before:" => {
+                let Ok(bound) = crate::moment::parse_bound_ms(&value, chrono::Local::now()) else {
+                    rest.push(part);
+                    continue;
+                };
+                if *prefix == "after:" {
+                    filters.after = Some(bound);
+                } else {
+                    filters.before = Some(bound);
+                }
+            }
+            "model:" => filters.settings.model = Some(value),
+            "sandbox:" => filters.settings.sandbox = Some(value),
+            "has:" => filters.has.push(value.to_lowercase()),
+            "edited:" => filters.edited.push(crate::patch::normalize_path(&value)),
+            "cmd:" => filters
+                .commands
+                .extend(crate::shell_history::command_words(&value)),
+            "file:" => filters.files.push(crate::mentions::normalize_file(&value)),
+            "lang:" => filters.langs.push(value.to_lowercase()),
+            "error:" => filters.errors.push(value.to_lowercase()),
+            "type:" => filters.role = Some(value.to_lowercase()),
+            _ => filters.settings.approval_policy = Some(value),
+        }
+    }
+    (rest.join(" "), filters)
+}
+
+/// Whitespace-separated words of a query, with a quoted run like
+/// `before:"2 weeks ago"` kept as one.
+fn quoted_parts(query: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut open = false;
+    for word in query.split_whitespace() {
+        match parts.last_mut() {
+            Some(last) if open => {
+                last.push(' ');
+                last.push_str(word);
+            }
+            _ => parts.push(word.to_string()),
+        }
+        if word.matches('"').count() % 2 == 1 {
+            open = !open;
+        }
+    }
+    parts
+}
+
 impl SearchIndex {
     pub fn open_or_create(dir: &Path) -> Result<Self> {
         Self::open_or_create_with_policy(dir, StaleSchemaPolicy::Error)
@@ -69,21 +375,44 @@ impl SearchIndex {
         stale_schema_policy: StaleSchemaPolicy,
     ) -> Result<Self> {
         let meta_path = dir.join("meta.json");
-        if meta_path.exists() {
-            let index = Index::open_in_dir(dir)?;
-            if !schema_is_current(&index.schema()) {
-                return match stale_schema_policy {
-                    StaleSchemaPolicy::Error => Err(stale_schema_error(dir)),
-                    StaleSchemaPolicy::Recreate => {
-                        drop(index);
-                        recreate_index_dir(dir)
-                    }
-                };
+        if !meta_path.exists() {
+            return create_index_in_dir(dir);
+        }
+        let index = Index::open_in_dir(dir).map_err(|err| damaged_index_error(dir, err))?;
+        if let Some(path) = missing_segment_files(&index).first() {
+            return Err(damaged_index_error(
+                dir,
+                format!("{} is missing", path.display()),
+            ));
+        }
+        register_tokenizers(&index);
+        let version = read_schema_version(dir, &index.schema());
+        match plan_migration(version, MIGRATIONS) {
+            MigrationPlan::Current => {
+                let fields =
+                    load_fields(index.schema()).map_err(|err| damaged_index_error(dir, err))?;
+                Ok(Self::from_parts(dir, index, fields))
             }
-            let fields = load_fields(index.schema())?;
-            Ok(Self { index, fields })
-        } else {
-            create_index_in_dir(dir)
+            MigrationPlan::CopyStored(fixups) => {
+                drop(index);
+                upgrade_in_place(dir, &fixups)
+            }
+            MigrationPlan::Reingest => match stale_schema_policy {
+                StaleSchemaPolicy::Error => Err(stale_schema_error(dir, version)),
+                StaleSchemaPolicy::Recreate => {
+                    drop(index);
+                    recreate_index_dir(dir)
+                }
+            },
+        }
+    }
+
+    fn from_parts(dir: &Path, index: Index, fields: IndexFields) -> Self {
+        Self {
+            index,
+            fields,
+            shards_dir: shards_dir(dir),
+            shards: Arc::default(),
         }
     }
 
@@ -91,16 +420,98 @@ impl SearchIndex {
         Ok(self.index.writer(256_000_000)?)
     }
 
+    /// A writer for bulk imports: `heap_bytes` of indexing memory and no
+    /// merges until the caller merges once at the end.
+    pub fn bulk_writer(&self, heap_bytes: usize) -> Result<IndexWriter> {
+        let writer = self.index.writer(heap_bytes)?;
+        writer.set_merge_policy(Box::new(NoMergePolicy));
+        Ok(writer)
+    }
+
     pub fn reader(&self) -> Result<IndexReader> {
         Ok(self.index.reader()?)
     }
 
-    pub fn delete_by_source_path(&self, writer: &mut IndexWriter, path: &str) {
+    /// Searchers over this index and then each month shard, oldest first.
+    fn searchers(&self) -> Result<Vec<Searcher>> {
+        let mut searchers = vec![self.reader()?.searcher()];
+        searchers.extend(self.with_shards(|shard| Ok(shard.reader.searcher()))?);
+        Ok(searchers)
+    }
+
+    /// Runs `f` on every month shard, oldest first, opening shards a split
+    /// added since the last call and reloading ones written to.
+    fn with_shards<T>(&self, mut f: impl FnMut(&mut MonthShard) -> Result<T>) -> Result<Vec<T>> {
+        let months = shard_months(&self.shards_dir);
+        let mut shards = self
+            .shards
+            .lock()
+            .map_err(|_| anyhow!("index shards lock poisoned"))?;
+        shards.retain(|shard| months.contains(&shard.month));
+        let mut out = Vec::with_capacity(months.len());
+        for month in months {
+            let at = match shards.iter().position(|shard| shard.month == month) {
+                Some(at) => at,
+                None => {
+                    let dir = self.shards_dir.join(&month);
+                    shards.push(MonthShard::open(dir, month)?);
+                    shards.len() - 1
+                }
+            };
+            let shard = &mut shards[at];
+            shard.reload()?;
+            out.push(f(shard)?);
+        }
+        Ok(out)
+    }
+
+    /// Deletes `term` from every month shard holding it, committing each, so
+    /// a session file indexed again doesn't keep its copy in a shard.
+    fn delete_in_shards(&self, term: Term) -> Result<()> {
+        let query = TermQuery::new(term.clone(), IndexRecordOption::Basic);
+        self.with_shards(|shard| {
+            if shard.reader.searcher().search(&query, &Count)? == 0 {
+                return Ok(());
+            }
+            let mut writer = shard.index.writer()?;
+            writer.delete_term(term.clone());
+            writer.commit()?;
+            drop(writer);
+            shard.reload()
+        })?;
+        Ok(())
+    }
+
+    pub fn delete_by_source_path(&self, writer: &mut IndexWriter, path: &str) -> Result<()> {
         let term = Term::from_field_text(self.fields.source_path, path);
-        writer.delete_term(term);
+        writer.delete_term(term.clone());
+        self.delete_in_shards(term)
+    }
+
+    pub fn delete_by_session_id(&self, writer: &mut IndexWriter, session_id: &str) -> Result<()> {
+        let term = Term::from_field_text(self.fields.session_id, session_id);
+        writer.delete_term(term.clone());
+        self.delete_in_shards(term)
+    }
+
+    pub fn delete_by_doc_id(&self, writer: &mut IndexWriter, doc_id: u64) -> Result<()> {
+        let term = Term::from_field_u64(self.fields.doc_id, doc_id);
+        writer.delete_term(term.clone());
+        self.delete_in_shards(term)
     }
 
     pub fn add_record(&self, writer: &mut IndexWriter, record: &Record) -> Result<()> {
+        self.add_record_with_code(writer, record, false)
+    }
+
+    /// Adds `record`, and with `index_code` also the code found in its tool
+    /// input and output to the `code` field, searched with `code:`.
+    pub fn add_record_with_code(
+        &self,
+        writer: &mut IndexWriter,
+        record: &Record,
+        index_code: bool,
+    ) -> Result<()> {
         let mut doc = TantivyDocument::default();
         doc.add_u64(self.fields.doc_id, record.doc_id);
         doc.add_u64(self.fields.ts, record.ts);
@@ -147,6 +558,7 @@ impl SearchIndex {
             self.fields.conversation_kind,
             &record.links.conversation_kind,
         );
+        add_optional_text(&mut doc, self.fields.thread, &record.links.thread);
         add_optional_text(
             &mut doc,
             self.fields.parent_tool_use_id,
@@ -163,128 +575,1257 @@ impl SearchIndex {
             &record.links.source_tool_assistant_uuid,
         );
         doc.add_text(self.fields.source_path, &record.source_path);
+        let ingested_at = if record.ingested_at > 0 {
+            record.ingested_at
+        } else {
+            chrono::Utc::now().timestamp_millis().max(0) as u64
+        };
+        doc.add_u64(self.fields.ingested_at, ingested_at);
+        add_optional_text(&mut doc, self.fields.model, &record.settings.model);
+        add_optional_text(&mut doc, self.fields.sandbox, &record.settings.sandbox);
+        add_optional_text(
+            &mut doc,
+            self.fields.approval_policy,
+            &record.settings.approval_policy,
+        );
+        if !record.attachments.is_empty() {
+            doc.add_text(
+                self.fields.attachments,
+                serde_json::to_string(&record.attachments)?,
+            );
+            doc.add_text(self.fields.attachment_kind, ANY_ATTACHMENT);
+            let kinds: HashSet<&str> = record
+                .attachments
+                .iter()
+                .map(|attachment| attachment.kind.as_str())
+                .collect();
+            for kind in kinds {
+                doc.add_text(self.fields.attachment_kind, kind);
+            }
+            // Extra values of `text` are searched but never read back.
+            for text in record.attachments.iter().filter_map(|a| a.text.as_ref()) {
+                doc.add_text(self.fields.text, text);
+            }
+        }
+        if index_code {
+            for code in record_code(record) {
+                doc.add_text(self.fields.code, code);
+            }
+        }
+        add_edited_paths(
+            &self.fields,
+            &mut doc,
+            record.tool_name.as_deref(),
+            record.tool_input.as_deref(),
+        );
+        add_commands(
+            &self.fields,
+            &mut doc,
+            record.tool_name.as_deref(),
+            record.tool_input.as_deref(),
+        );
+        add_files(&self.fields, &mut doc, record);
+        if let Some(lang) = crate::lang::record_language(record) {
+            doc.add_text(self.fields.lang, lang);
+        }
+        add_errors(&self.fields, &mut doc, record);
         writer.add_document(doc)?;
         Ok(())
     }
 
     pub fn get_by_doc_id(&self, doc_id: u64) -> Result<Option<Record>> {
-        let reader = self.reader()?;
-        let searcher = reader.searcher();
         let term = Term::from_field_u64(self.fields.doc_id, doc_id);
         let query = TermQuery::new(term, IndexRecordOption::Basic);
-        let top = searcher.search(&query, &TopDocs::with_limit(1))?;
-        let Some((_, addr)) = top.first() else {
-            return Ok(None);
-        };
-        let doc = searcher.doc::<TantivyDocument>(*addr)?;
-        Ok(Some(record_from_doc(&self.fields, &doc)))
+        for searcher in self.searchers()? {
+            let top = searcher.search(&query, &TopDocs::with_limit(1))?;
+            if let Some((_, addr)) = top.first() {
+                let doc = searcher.doc::<TantivyDocument>(*addr)?;
+                return Ok(Some(record_from_doc(&self.fields, &doc)));
+            }
+        }
+        Ok(None)
     }
 
     pub fn search(&self, options: &QueryOptions) -> Result<Vec<(f32, Record)>> {
-        let reader = self.reader()?;
-        let searcher = reader.searcher();
+        Ok(self.search_timed(options)?.0)
+    }
+
+    /// [`Self::search`], with the time each step took.
+    pub fn search_timed(
+        &self,
+        options: &QueryOptions,
+    ) -> Result<(Vec<(f32, Record)>, SearchPhases)> {
+        let mut phases = SearchPhases::default();
+        let started = Instant::now();
+        let searchers = self.searchers()?;
+        phases.open = started.elapsed();
+        let started = Instant::now();
         let query = build_query(&self.fields, options, &self.index)?;
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(options.limit))?;
+        let collector = TopDocs::with_limit(options.limit);
+        let statistics = ShardStatistics(&searchers);
+        let mut top_docs = Vec::new();
+        for (at, searcher) in searchers.iter().enumerate() {
+            for (score, addr) in
+                searcher.search_with_statistics_provider(&query, &collector, &statistics)?
+            {
+                top_docs.push((score, at, addr));
+            }
+        }
+        top_docs.sort_by(|a, b| b.0.total_cmp(&a.0));
+        top_docs.truncate(options.limit);
+        phases.query = started.elapsed();
+        let started = Instant::now();
         let mut results = Vec::with_capacity(top_docs.len());
-        for (score, addr) in top_docs {
-            let doc = searcher.doc::<TantivyDocument>(addr)?;
+        for (score, at, addr) in top_docs {
+            let doc = searchers[at].doc::<TantivyDocument>(addr)?;
             results.push((score, record_from_doc(&self.fields, &doc)));
         }
-        Ok(results)
+        phases.load = started.elapsed();
+        Ok((results, phases))
+    }
+
+    /// How many records `options` matches, however many its limit would
+    /// return.
+    pub fn count(&self, options: &QueryOptions) -> Result<usize> {
+        let query = build_query(&self.fields, options, &self.index)?;
+        let mut count = 0;
+        for searcher in self.searchers()? {
+            count += searcher.search(&query, &Count)?;
+        }
+        Ok(count)
+    }
+
+    /// BM25 contribution of each query term to the record `doc_id`, highest
+    /// first. Terms the record doesn't contain are left out; fuzzy matches
+    /// aren't broken down.
+    pub fn term_scores(&self, options: &QueryOptions, doc_id: u64) -> Result<Vec<TermScore>> {
+        if options.query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let id_query = TermQuery::new(
+            Term::from_field_u64(self.fields.doc_id, doc_id),
+            IndexRecordOption::Basic,
+        );
+        let searchers = self.searchers()?;
+        let mut found = None;
+        for searcher in &searchers {
+            if let Some((_, addr)) = searcher.search(&id_query, &TopDocs::with_limit(1))?.first() {
+                found = Some((searcher, *addr));
+                break;
+            }
+        }
+        let Some((searcher, addr)) = found else {
+            return Ok(Vec::new());
+        };
+        let statistics = ShardStatistics(&searchers);
+        let query = build_query(&self.fields, options, &self.index)?;
+        let mut terms: Vec<Term> = Vec::new();
+        query.query_terms(&mut |term, _| {
+            let searched = term.field() == self.fields.text || term.field() == self.fields.code;
+            if searched && !terms.contains(term) {
+                terms.push(term.clone());
+            }
+        });
+        let mut scores = Vec::new();
+        for term in terms {
+            let Some(text) = term.value().as_str().map(str::to_string) else {
+                continue;
+            };
+            let label = if term.field() == self.fields.code {
+                format!("code:{text}")
+            } else {
+                text
+            };
+            let term_query = TermQuery::new(term, IndexRecordOption::WithFreqs);
+            let weight = term_query.weight(EnableScoring::enabled_from_statistics_provider(
+                &statistics,
+                searcher,
+            ))?;
+            // Explaining a document the term doesn't match is an error.
+            if let Ok(explanation) =
+                weight.explain(searcher.segment_reader(addr.segment_ord), addr.doc_id)
+            {
+                scores.push(TermScore {
+                    term: label,
+                    score: explanation.value(),
+                });
+            }
+        }
+        scores.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(scores)
     }
 
+    /// The session's transcript. Notes the user attached to it, its todo
+    /// lists and plans, and its turns are left out.
     pub fn records_by_session_id(&self, session_id: &str) -> Result<Vec<Record>> {
-        let reader = self.reader()?;
-        let searcher = reader.searcher();
-        let term = Term::from_field_text(self.fields.session_id, session_id);
-        let query = TermQuery::new(term, IndexRecordOption::Basic);
-        let limit = searcher.num_docs() as usize;
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
-        let mut records = Vec::with_capacity(top_docs.len());
-        for (_score, addr) in top_docs {
-            let doc = searcher.doc::<TantivyDocument>(addr)?;
-            records.push(record_from_doc(&self.fields, &doc));
+        let mut records = self.records_by_term(self.fields.session_id, session_id)?;
+        records.retain(|record| !crate::types::is_side_role(&record.role));
+        Ok(records)
+    }
+
+    /// The session's records with `role`, like its todo items, which its
+    /// transcript leaves out.
+    pub fn session_records_with_role(&self, session_id: &str, role: &str) -> Result<Vec<Record>> {
+        let mut records = self.records_by_term(self.fields.session_id, session_id)?;
+        records.retain(|record| record.role == role);
+        Ok(records)
+    }
+
+    /// Records from child conversations (subagents, forks) spawned by `session_id`.
+    pub fn records_by_parent_session_id(&self, session_id: &str) -> Result<Vec<Record>> {
+        self.records_by_term(self.fields.parent_session_id, session_id)
+    }
+
+    /// Every session's records in `project` with `since <= ts <= until`,
+    /// interleaved oldest first; the newest `limit` when there are more.
+    /// Notes, todo items and plans are left out.
+    pub fn records_by_project(
+        &self,
+        project: &str,
+        since: u64,
+        until: u64,
+        limit: usize,
+    ) -> Result<Vec<Record>> {
+        let project = Term::from_field_text(self.fields.project, project);
+        self.records_in_range(
+            Some(Box::new(TermQuery::new(project, IndexRecordOption::Basic))),
+            since,
+            until,
+            limit,
+        )
+    }
+
+    /// Records of every project written between `since` and `until`
+    /// (inclusive), oldest first, keeping the newest `limit`. Notes, todo
+    /// items and plans are left out.
+    pub fn records_between(&self, since: u64, until: u64, limit: usize) -> Result<Vec<Record>> {
+        self.records_in_range(None, since, until, limit)
+    }
+
+    fn records_in_range(
+        &self,
+        filter: Option<Box<dyn Query>>,
+        since: u64,
+        until: u64,
+        limit: usize,
+    ) -> Result<Vec<Record>> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(
+            Occur::Must,
+            Box::new(RangeQuery::new_u64_bounds(
+                "ts".to_string(),
+                Bound::Included(since),
+                Bound::Included(until),
+            )),
+        )];
+        clauses.extend(filter.map(|filter| (Occur::Must, filter)));
+        let query = BooleanQuery::new(clauses);
+        let mut records = self.newest_records(&query, "ts", limit)?;
+        records.retain(|record| {
+            !crate::types::is_side_role(&record.role) && record.role != crate::types::TURN_ROLE
+        });
+        records.sort_by(|a, b| {
+            a.ts.cmp(&b.ts)
+                .then_with(|| a.session_id.cmp(&b.session_id))
+                .then_with(|| a.turn_id.cmp(&b.turn_id))
+                .then_with(|| a.doc_id.cmp(&b.doc_id))
+        });
+        Ok(records)
+    }
+
+    /// Records indexed from the session file at `path`, in no particular
+    /// order.
+    pub fn records_by_source_path(&self, path: &str) -> Result<Vec<Record>> {
+        self.records_by_term(self.fields.source_path, path)
+    }
+
+    /// Ingestion times of the records currently indexed from `path`, keyed by
+    /// `(turn_id, ts, role)`, so a re-ingested file keeps the original times
+    /// for records it already had.
+    pub fn ingested_at_by_source_path(
+        &self,
+        path: &str,
+    ) -> Result<HashMap<(u32, u64, String), u64>> {
+        Ok(self
+            .records_by_term(self.fields.source_path, path)?
+            .into_iter()
+            .filter(|record| record.ingested_at > 0)
+            .map(|record| ((record.turn_id, record.ts, record.role), record.ingested_at))
+            .collect())
+    }
+
+    /// Records with `value` in `field`; turns are left out.
+    fn records_by_term(&self, field: Field, value: &str) -> Result<Vec<Record>> {
+        let term = Term::from_field_text(field, value);
+        let query = self.without_turns(Box::new(TermQuery::new(term, IndexRecordOption::Basic)));
+        let mut records = Vec::new();
+        for searcher in self.searchers()? {
+            let limit = (searcher.num_docs() as usize).max(1);
+            for (_score, addr) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+                let doc = searcher.doc::<TantivyDocument>(addr)?;
+                records.push(record_from_doc(&self.fields, &doc));
+            }
         }
         Ok(records)
     }
 
-    pub fn recent_records(&self, limit: usize) -> Result<Vec<Record>> {
-        let reader = self.reader()?;
-        let searcher = reader.searcher();
-        let query = AllQuery;
+    /// Every record matching `query`, in no particular order.
+    fn matching_records(&self, query: &dyn Query) -> Result<Vec<Record>> {
+        let mut records = Vec::new();
+        for searcher in self.searchers()? {
+            for addr in searcher.search(query, &DocSetCollector)? {
+                let doc = searcher.doc::<TantivyDocument>(addr)?;
+                records.push(record_from_doc(&self.fields, &doc));
+            }
+        }
+        Ok(records)
+    }
+
+    /// The `limit` records matching `query` with the highest value of the
+    /// u64 fast field `field`, highest first, across every shard.
+    fn newest_records(&self, query: &dyn Query, field: &str, limit: usize) -> Result<Vec<Record>> {
+        let searchers = self.searchers()?;
         let collector =
-            TopDocs::with_limit(limit.max(1)).order_by_fast_field::<u64>("ts", Order::Desc);
-        let top_docs: Vec<(u64, tantivy::DocAddress)> = searcher.search(&query, &collector)?;
+            TopDocs::with_limit(limit.max(1)).order_by_fast_field::<u64>(field, Order::Desc);
+        let mut top_docs: Vec<(u64, usize, DocAddress)> = Vec::new();
+        for (at, searcher) in searchers.iter().enumerate() {
+            for (value, addr) in searcher.search(query, &collector)? {
+                top_docs.push((value, at, addr));
+            }
+        }
+        top_docs.sort_by_key(|(value, _, _)| std::cmp::Reverse(*value));
+        top_docs.truncate(limit.max(1));
         let mut records = Vec::with_capacity(top_docs.len());
-        for (_ts, addr) in top_docs {
-            let doc = searcher.doc::<TantivyDocument>(addr)?;
+        for (_value, at, addr) in top_docs {
+            let doc = searchers[at].doc::<TantivyDocument>(addr)?;
             records.push(record_from_doc(&self.fields, &doc));
         }
         Ok(records)
     }
 
+    pub fn recent_records(&self, limit: usize) -> Result<Vec<Record>> {
+        let query = self.without_turns(Box::new(AllQuery));
+        self.newest_records(&query, "ts", limit)
+    }
+
+    /// The newest `limit` records memex indexed after `after` (unix millis),
+    /// oldest first.
+    pub fn records_ingested_after(&self, after: u64, limit: usize) -> Result<Vec<Record>> {
+        let query = self.without_turns(Box::new(RangeQuery::new_u64_bounds(
+            "ingested_at".to_string(),
+            Bound::Excluded(after),
+            Bound::Unbounded,
+        )));
+        let mut records = self.newest_records(&query, "ingested_at", limit)?;
+        records.sort_by(|a, b| a.ingested_at.cmp(&b.ingested_at).then(a.ts.cmp(&b.ts)));
+        Ok(records)
+    }
+
+    /// Opstamp of the last commit. Every commit, from this process or the
+    /// index service, bumps it, so results keyed on it go stale on their own.
+    pub fn generation(&self) -> Result<u64> {
+        Ok(self.index.load_metas()?.opstamp)
+    }
+
     pub fn doc_count(&self) -> Result<usize> {
-        let reader = self.reader()?;
-        Ok(reader.searcher().num_docs() as usize)
+        Ok(self
+            .searchers()?
+            .iter()
+            .map(|searcher| searcher.num_docs() as usize)
+            .sum())
+    }
+
+    /// Records written under any of `roles`.
+    pub fn count_roles(&self, roles: &[&str]) -> Result<usize> {
+        let clauses: Vec<(Occur, Box<dyn Query>)> = roles
+            .iter()
+            .map(|role| {
+                let term = Term::from_field_text(self.fields.role, role);
+                (
+                    Occur::Should,
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>,
+                )
+            })
+            .collect();
+        let query = BooleanQuery::new(clauses);
+        let mut count = 0;
+        for searcher in self.searchers()? {
+            count += searcher.search(&query, &Count)?;
+        }
+        Ok(count)
+    }
+
+    /// Size of the stored records on disk and how they are compressed.
+    pub fn store_stats(&self) -> Result<StoreStats> {
+        let mut bytes = 0;
+        for searcher in self.searchers()? {
+            bytes += searcher
+                .space_usage()?
+                .segments()
+                .iter()
+                .map(|segment| segment.store().total().get_bytes())
+                .sum::<u64>();
+        }
+        Ok(StoreStats {
+            bytes,
+            compression: compressor_label(&self.index.settings().docstore_compression),
+        })
     }
 
+    /// Calls `f` with every record, turns aside since they repeat the
+    /// records they are built from.
     pub fn for_each_record<F>(&self, mut f: F) -> Result<()>
     where
         F: FnMut(Record) -> Result<()>,
     {
+        for searcher in self.searchers()? {
+            for segment_reader in searcher.segment_readers() {
+                let store = segment_reader.get_store_reader(0)?;
+                for doc in store.iter::<TantivyDocument>(segment_reader.alive_bitset()) {
+                    let doc = doc?;
+                    let record = record_from_doc(&self.fields, &doc);
+                    if record.role != crate::types::TURN_ROLE {
+                        f(record)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `query` with turns left out.
+    fn without_turns(&self, query: Box<dyn Query>) -> BooleanQuery {
+        let turn = Term::from_field_text(self.fields.role, crate::types::TURN_ROLE);
+        BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (
+                Occur::MustNot,
+                Box::new(TermQuery::new(turn, IndexRecordOption::Basic)),
+            ),
+        ])
+    }
+
+    pub fn live_doc_ids(&self) -> Result<HashSet<u64>> {
+        let mut ids = HashSet::new();
+        for searcher in self.searchers()? {
+            for segment_reader in searcher.segment_readers() {
+                let column = segment_reader.fast_fields().u64("doc_id")?;
+                for doc in segment_reader.doc_ids_alive() {
+                    if let Some(doc_id) = column.first(doc) {
+                        ids.insert(doc_id);
+                    }
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Every record that ran a shell command and passes the filters in
+    /// `options`; its text and `limit` are ignored.
+    pub fn records_with_commands(&self, options: &QueryOptions) -> Result<Vec<Record>> {
+        let filters = QueryOptions {
+            query: String::new(),
+            ..options.clone()
+        };
+        // An open range over the command words matches any record with one.
+        let query = BooleanQuery::new(vec![
+            (
+                Occur::Must,
+                build_query(&self.fields, &filters, &self.index)?,
+            ),
+            (
+                Occur::Must,
+                Box::new(RangeQuery::new_str_bounds(
+                    "command".to_string(),
+                    Bound::Unbounded,
+                    Bound::Unbounded,
+                )),
+            ),
+        ]);
+        self.matching_records(&query)
+    }
+
+    /// Every tool result with an error that passes the filters in `options`,
+    /// including any `error:` ones; `limit` is ignored. Text in `options`
+    /// narrows them to results containing it.
+    pub fn records_with_errors(&self, options: &QueryOptions) -> Result<Vec<Record>> {
+        let query = BooleanQuery::new(vec![
+            (
+                Occur::Must,
+                build_query(&self.fields, options, &self.index)?,
+            ),
+            (
+                Occur::Must,
+                Box::new(RangeQuery::new_str_bounds(
+                    "error".to_string(),
+                    Bound::Unbounded,
+                    Bound::Unbounded,
+                )),
+            ),
+        ]);
+        self.matching_records(&query)
+    }
+
+    /// Every record that mentions a file matching the `file:` filters in
+    /// `options` and passes its other filters; text and `limit` are ignored.
+    pub fn records_mentioning_files(&self, options: &QueryOptions) -> Result<Vec<Record>> {
+        if options.files.is_empty() {
+            return Ok(Vec::new());
+        }
+        let filters = QueryOptions {
+            query: String::new(),
+            ..options.clone()
+        };
+        self.matching_records(build_query(&self.fields, &filters, &self.index)?.as_ref())
+    }
+
+    /// Timestamps of every record matching `options`; `limit` is ignored.
+    pub fn matching_timestamps(&self, options: &QueryOptions) -> Result<Vec<u64>> {
+        let query = build_query(&self.fields, options, &self.index)?;
+        self.matching_fast_values(query.as_ref(), "ts")
+    }
+
+    /// The u64 fast field `field` of every record matching `query`.
+    fn matching_fast_values(&self, query: &dyn Query, field: &str) -> Result<Vec<u64>> {
+        let mut values = Vec::new();
+        for searcher in self.searchers()? {
+            let addrs = searcher.search(query, &DocSetCollector)?;
+            let columns = searcher
+                .segment_readers()
+                .iter()
+                .map(|segment_reader| segment_reader.fast_fields().u64(field))
+                .collect::<tantivy::Result<Vec<_>>>()?;
+            values.extend(
+                addrs
+                    .into_iter()
+                    .filter_map(|addr| columns[addr.segment_ord as usize].first(addr.doc_id)),
+            );
+        }
+        Ok(values)
+    }
+
+    /// Doc ids of every record passing the filters in `options`, whatever its
+    /// text, to restrict vector search before scoring.
+    pub fn filtered_doc_ids(&self, options: &QueryOptions) -> Result<HashSet<u64>> {
+        let filters = QueryOptions {
+            query: String::new(),
+            ..options.clone()
+        };
+        let query = build_query(&self.fields, &filters, &self.index)?;
+        Ok(self
+            .matching_fast_values(query.as_ref(), "doc_id")?
+            .into_iter()
+            .collect())
+    }
+
+    /// Segments of the live index; month shards are left out, since they
+    /// are never merged again.
+    pub fn segment_stats(&self) -> Result<SegmentStats> {
         let reader = self.reader()?;
         let searcher = reader.searcher();
+        let mut stats = SegmentStats::default();
         for segment_reader in searcher.segment_readers() {
-            let store = segment_reader.get_store_reader(0)?;
-            for doc in store.iter::<TantivyDocument>(segment_reader.alive_bitset()) {
-                let doc = doc?;
+            stats.segments += 1;
+            stats.alive_docs += segment_reader.num_docs() as u64;
+            stats.deleted_docs += segment_reader.num_deleted_docs() as u64;
+        }
+        Ok(stats)
+    }
+
+    /// Merges every searchable segment into one, purging deleted documents,
+    /// then removes segment files that are no longer referenced.
+    pub fn compact(&self) -> Result<SegmentStats> {
+        let before = self.segment_stats()?;
+        let segment_ids = self.index.searchable_segment_ids()?;
+        if segment_ids.is_empty() || (segment_ids.len() == 1 && before.deleted_docs == 0) {
+            return Ok(before);
+        }
+        let mut writer = self.writer()?;
+        writer.merge(&segment_ids).wait()?;
+        writer.garbage_collect_files().wait()?;
+        writer.wait_merging_threads()?;
+        Ok(before)
+    }
+
+    /// The month shards next to this index, oldest first.
+    pub fn shards(&self) -> Result<Vec<ShardInfo>> {
+        self.with_shards(|shard| {
+            Ok(ShardInfo {
+                month: shard.month.clone(),
+                records: shard.reader.searcher().num_docs() as usize,
+                dir: shard.dir.clone(),
+            })
+        })
+    }
+
+    /// Moves every record written before `before` (unix millis) into a
+    /// shard for its month, then compacts what is left. Notes stay, since
+    /// they are edited in place. `index_code` is ingest's setting for the
+    /// `code` field, which isn't stored and so can't be copied. Returns the
+    /// records moved per month.
+    pub fn split_months(&self, before: u64, index_code: bool) -> Result<Vec<(String, usize)>> {
+        let note = Term::from_field_text(self.fields.role, crate::types::NOTE_ROLE);
+        let query = BooleanQuery::new(vec![
+            (
+                Occur::Must,
+                Box::new(RangeQuery::new_u64_bounds(
+                    "ts".to_string(),
+                    Bound::Unbounded,
+                    Bound::Excluded(before),
+                )),
+            ),
+            (
+                Occur::MustNot,
+                Box::new(TermQuery::new(note, IndexRecordOption::Basic)),
+            ),
+        ]);
+        let searcher = self.reader()?.searcher();
+        let columns = searcher
+            .segment_readers()
+            .iter()
+            .map(|segment_reader| segment_reader.fast_fields().u64("ts"))
+            .collect::<tantivy::Result<Vec<_>>>()?;
+        let mut by_month: BTreeMap<String, Vec<DocAddress>> = BTreeMap::new();
+        for addr in searcher.search(&query, &DocSetCollector)? {
+            let ts = columns[addr.segment_ord as usize]
+                .first(addr.doc_id)
+                .unwrap_or(0);
+            by_month.entry(month_of(ts)).or_default().push(addr);
+        }
+        let mut moved = Vec::with_capacity(by_month.len());
+        for (month, addrs) in by_month {
+            let dir = self.shards_dir.join(&month);
+            std::fs::create_dir_all(&dir)?;
+            let shard = SearchIndex::open_or_create(&dir)?;
+            let mut writer = shard.writer()?;
+            for addr in &addrs {
+                let doc = searcher.doc::<TantivyDocument>(*addr)?;
                 let record = record_from_doc(&self.fields, &doc);
-                f(record)?;
+                // A split cut short and run again would otherwise add the
+                // records it already copied a second time.
+                writer.delete_term(Term::from_field_u64(shard.fields.doc_id, record.doc_id));
+                shard.add_record_with_code(&mut writer, &record, index_code)?;
             }
+            writer.commit()?;
+            moved.push((month, addrs.len()));
         }
-        Ok(())
+        if !moved.is_empty() {
+            let mut writer = self.writer()?;
+            writer.delete_query(Box::new(query))?;
+            writer.commit()?;
+            drop(writer);
+            self.compact()?;
+        }
+        Ok(moved)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum StaleSchemaPolicy {
-    Error,
-    Recreate,
+/// BM25 statistics summed over the live index and its month shards, so a
+/// record scores the same as it would before its month was moved out.
+struct ShardStatistics<'a>(&'a [Searcher]);
+
+impl Bm25StatisticsProvider for ShardStatistics<'_> {
+    fn total_num_tokens(&self, field: Field) -> tantivy::Result<u64> {
+        self.0
+            .iter()
+            .map(|searcher| Bm25StatisticsProvider::total_num_tokens(searcher, field))
+            .sum()
+    }
+
+    fn total_num_docs(&self) -> tantivy::Result<u64> {
+        self.0
+            .iter()
+            .map(Bm25StatisticsProvider::total_num_docs)
+            .sum()
+    }
+
+    fn doc_freq(&self, term: &Term) -> tantivy::Result<u64> {
+        self.0.iter().map(|searcher| searcher.doc_freq(term)).sum()
+    }
 }
 
-fn stale_schema_error(dir: &Path) -> anyhow::Error {
-    anyhow!(
-        "index schema at {} is stale; run `memex index` or `memex reindex` to rebuild it",
-        dir.display()
-    )
+/// Where the month shards of the index at `dir` live: `index-shards` next
+/// to `index`, one directory per `YYYY-MM`.
+pub fn shards_dir(dir: &Path) -> PathBuf {
+    let mut name = dir.file_name().unwrap_or_default().to_os_string();
+    name.push("-shards");
+    dir.with_file_name(name)
 }
 
-fn recreate_index_dir(dir: &Path) -> Result<SearchIndex> {
-    std::fs::remove_dir_all(dir)?;
-    std::fs::create_dir_all(dir)?;
-    create_index_in_dir(dir)
+/// Months with a shard in `shards_dir`, oldest first. Directories not named
+/// like a month, or without an index in them, are left alone.
+fn shard_months(shards_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(shards_dir) else {
+        return Vec::new();
+    };
+    let mut months: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("meta.json").exists())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            name.len() == 7
+                && chrono::NaiveDate::parse_from_str(&format!("{name}-01"), "%Y-%m-%d").is_ok()
+        })
+        .collect();
+    months.sort();
+    months
 }
 
-fn create_index_in_dir(dir: &Path) -> Result<SearchIndex> {
-    let schema = build_schema()?;
-    let index = Index::create_in_dir(dir, schema.clone())?;
-    let fields = load_fields(schema)?;
-    Ok(SearchIndex { index, fields })
+/// `YYYY-MM` of `ts` (unix millis) in UTC.
+fn month_of(ts: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ts as i64)
+        .unwrap_or_default()
+        .format("%Y-%m")
+        .to_string()
 }
 
-fn build_schema() -> Result<Schema> {
-    let mut builder = SchemaBuilder::default();
+fn meta_modified(dir: &Path) -> Option<SystemTime> {
+    std::fs::metadata(dir.join("meta.json"))
+        .and_then(|meta| meta.modified())
+        .ok()
+}
 
-    builder.add_u64_field("doc_id", INDEXED | STORED | FAST);
-    builder.add_u64_field("ts", INDEXED | STORED | FAST);
-    builder.add_text_field("project", STRING | STORED);
-    builder.add_text_field("session_id", STRING | STORED);
-    builder.add_u64_field("turn_id", INDEXED | STORED | FAST);
-    builder.add_text_field("role", STRING | STORED);
+/// Time spent in each step of [`SearchIndex::search_timed`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchPhases {
+    /// Opening a reader on the current segments.
+    pub open: Duration,
+    /// Parsing the query and collecting the top documents.
+    pub query: Duration,
+    /// Reading the stored records of the hits.
+    pub load: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SegmentStats {
+    pub segments: usize,
+    pub alive_docs: u64,
+    pub deleted_docs: u64,
+}
+
+impl SegmentStats {
+    /// Share of stored documents that are deleted but still on disk.
+    pub fn deleted_ratio(&self) -> f64 {
+        let total = self.alive_docs + self.deleted_docs;
+        if total == 0 {
+            return 0.0;
+        }
+        self.deleted_docs as f64 / total as f64
+    }
+}
+
+/// Doc store size, from `store_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreStats {
+    pub bytes: u64,
+    pub compression: &'static str,
+}
+
+fn compressor_label(compressor: &Compressor) -> &'static str {
+    match compressor {
+        Compressor::None => "none",
+        Compressor::Lz4 => "lz4",
+        Compressor::Zstd(_) => "zstd",
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StaleSchemaPolicy {
+    Error,
+    Recreate,
+}
+
+fn stale_schema_error(dir: &Path, version: u32) -> anyhow::Error {
+    let found = if version > SCHEMA_VERSION {
+        format!("v{version}, written by a newer memex")
+    } else {
+        format!("v{version}")
+    };
+    anyhow!(
+        "index schema at {} is {found} but this memex uses v{SCHEMA_VERSION}; \
+         run `memex index` or `memex reindex` to rebuild it",
+        dir.display()
+    )
+}
+
+fn damaged_index_error(dir: &Path, err: impl std::fmt::Display) -> anyhow::Error {
+    anyhow!(
+        "index at {} looks damaged ({err}); run `memex repair` to check it \
+         and `memex repair --rebuild-index` to rebuild it",
+        dir.display()
+    )
+}
+
+/// Files the index metadata lists for its segments that aren't on disk.
+fn missing_segment_files(index: &Index) -> Vec<PathBuf> {
+    let Ok(metas) = index.searchable_segment_metas() else {
+        return Vec::new();
+    };
+    let mut missing = Vec::new();
+    for meta in metas {
+        let mut components = vec![
+            SegmentComponent::Postings,
+            SegmentComponent::Terms,
+            SegmentComponent::FastFields,
+            SegmentComponent::FieldNorms,
+            SegmentComponent::Store,
+        ];
+        if meta.has_deletes() {
+            components.push(SegmentComponent::Delete);
+        }
+        for component in components {
+            let path = meta.relative_path(component);
+            if !index.directory().exists(&path).unwrap_or(false) {
+                missing.push(path);
+            }
+        }
+    }
+    missing
+}
+
+/// Everything wrong with the index at `dir`: unreadable metadata, missing
+/// segment files and files failing their checksum. Empty when it is intact.
+/// Reads every file, so it is only run on request.
+pub fn verify_index(dir: &Path) -> Vec<String> {
+    let index = match Index::open_in_dir(dir) {
+        Ok(index) => index,
+        Err(err) => return vec![format!("cannot open index: {err}")],
+    };
+    if let Err(err) = load_fields(index.schema()) {
+        return vec![err.to_string()];
+    }
+    let mut problems: Vec<String> = missing_segment_files(&index)
+        .iter()
+        .map(|path| format!("{} is missing", path.display()))
+        .collect();
+    match index.validate_checksum() {
+        Ok(damaged) => {
+            let mut damaged: Vec<PathBuf> = damaged.into_iter().collect();
+            damaged.sort();
+            problems.extend(
+                damaged
+                    .iter()
+                    .map(|path| format!("{} fails its checksum", path.display())),
+            );
+        }
+        Err(err) => problems.push(format!("cannot verify checksums: {err}")),
+    }
+    problems
+}
+
+/// What `salvage_records` read back from a damaged index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Salvage {
+    pub records: usize,
+    /// Segments whose stored records were partly or wholly unreadable.
+    pub lost_segments: usize,
+    /// False when the index metadata itself is unreadable, so nothing was.
+    pub readable: bool,
+}
+
+impl Salvage {
+    pub fn is_complete(&self) -> bool {
+        self.readable && self.lost_segments == 0
+    }
+}
+
+/// Reads every stored record still readable from the index at `dir`, one
+/// segment at a time so a damaged segment doesn't cost the others. Only the
+/// doc store is read; postings and fast fields are rebuilt from the records.
+pub fn salvage_records<F>(dir: &Path, mut f: F) -> Result<Salvage>
+where
+    F: FnMut(Record) -> Result<()>,
+{
+    let mut salvage = Salvage::default();
+    let Ok(index) = Index::open_in_dir(dir) else {
+        return Ok(salvage);
+    };
+    let Ok(fields) = load_fields(index.schema()) else {
+        return Ok(salvage);
+    };
+    let Ok(segments) = index.searchable_segments() else {
+        return Ok(salvage);
+    };
+    salvage.readable = true;
+    for segment in segments {
+        let Ok(reader) = SegmentReader::open(&segment) else {
+            salvage.lost_segments += 1;
+            continue;
+        };
+        let Ok(store) = reader.get_store_reader(0) else {
+            salvage.lost_segments += 1;
+            continue;
+        };
+        let mut intact = true;
+        for doc in store.iter::<TantivyDocument>(reader.alive_bitset()) {
+            match doc {
+                Ok(doc) => {
+                    f(record_from_doc(&fields, &doc))?;
+                    salvage.records += 1;
+                }
+                Err(_) => intact = false,
+            }
+        }
+        if !intact {
+            salvage.lost_segments += 1;
+        }
+    }
+    Ok(salvage)
+}
+
+fn recreate_index_dir(dir: &Path) -> Result<SearchIndex> {
+    std::fs::remove_dir_all(dir)?;
+    std::fs::create_dir_all(dir)?;
+    create_index_in_dir(dir)
+}
+
+/// zstd level for stored records. Tool output compresses well, and records
+/// are read a page of results at a time, so decompression cost is small.
+const DOCSTORE_ZSTD_LEVEL: i32 = 9;
+
+/// Records compressed together; larger blocks compress better but each
+/// fetch decompresses a whole block.
+const DOCSTORE_BLOCK_SIZE: usize = 64 * 1024;
+
+fn index_settings() -> IndexSettings {
+    IndexSettings {
+        docstore_compression: Compressor::Zstd(ZstdCompressor {
+            compression_level: Some(DOCSTORE_ZSTD_LEVEL),
+        }),
+        docstore_blocksize: DOCSTORE_BLOCK_SIZE,
+        ..IndexSettings::default()
+    }
+}
+
+fn create_index_in_dir(dir: &Path) -> Result<SearchIndex> {
+    let schema = build_schema()?;
+    let index = Index::builder()
+        .schema(schema.clone())
+        .settings(index_settings())
+        .create_in_dir(dir)?;
+    register_tokenizers(&index);
+    write_schema_version(dir)?;
+    let fields = load_fields(schema)?;
+    Ok(SearchIndex::from_parts(dir, index, fields))
+}
+
+/// Version of the index layout. Bump it with every change to `build_schema`
+/// or to how records are written, and add the step from the previous version
+/// to [`MIGRATIONS`].
+pub const SCHEMA_VERSION: u32 = 9;
+
+/// Holds [`SCHEMA_VERSION`] next to tantivy's `meta.json`.
+const SCHEMA_VERSION_FILE: &str = "memex_schema_version";
+
+/// Fills in indexed-only fields that a [`Migration::CopyStored`] step can't
+/// carry over, from the stored fields of the copied document.
+type Fixup = fn(&IndexFields, &mut TantivyDocument);
+
+#[derive(Clone, Copy)]
+enum Migration {
+    /// Stored fields carry over into the new schema; added fields start empty
+    /// until their source file is next re-read.
+    CopyStored(Option<Fixup>),
+    /// The new layout can't be derived from what is stored, so the index is
+    /// emptied and rebuilt from the session files on the next ingest.
+    Reingest,
+}
+
+/// `MIGRATIONS[n]` upgrades an index from version `n` to `n + 1`. Version 0
+/// covers every index from before versioning that isn't at version 1.
+const MIGRATIONS: &[Migration] = &[
+    Migration::Reingest,
+    // v2: attachment metadata.
+    Migration::CopyStored(None),
+    // v3: code from tool content, filled as files are re-read with
+    // `index_tool_code` on.
+    Migration::CopyStored(None),
+    // v4: stored records compressed with zstd instead of lz4.
+    Migration::CopyStored(None),
+    // v5: paths edited by patches, for `edited:` queries.
+    Migration::CopyStored(Some(fill_edited_paths)),
+    // v6: words of shell commands tools ran, for `cmd:` queries.
+    Migration::CopyStored(Some(fill_commands)),
+    // v7: files records mention, for `file:` queries and `memex files`.
+    Migration::CopyStored(Some(fill_files)),
+    // v8: the language a record is written in, for `lang:` queries.
+    Migration::CopyStored(Some(fill_lang)),
+    // v9: errors in tool results, for `error:` queries and `memex errors`.
+    Migration::CopyStored(Some(fill_errors)),
+];
+
+/// Indexes every tail of each path the record's patch edits, so `edited:`
+/// matches on any trailing part of the path.
+fn add_edited_paths(
+    fields: &IndexFields,
+    doc: &mut TantivyDocument,
+    tool_name: Option<&str>,
+    tool_input: Option<&str>,
+) {
+    let Some(patch) = tool_input.and_then(|input| crate::patch::patch_text(tool_name, input))
+    else {
+        return;
+    };
+    let mut seen = HashSet::new();
+    for edit in crate::patch::parse_patch(&patch) {
+        for path in edit.paths() {
+            for suffix in crate::patch::path_suffixes(path) {
+                if seen.insert(suffix.clone()) {
+                    doc.add_text(fields.edited_path, suffix);
+                }
+            }
+        }
+    }
+}
+
+/// Indexes the words of each shell command the record's tool call ran.
+fn add_commands(
+    fields: &IndexFields,
+    doc: &mut TantivyDocument,
+    tool_name: Option<&str>,
+    tool_input: Option<&str>,
+) {
+    let mut seen = HashSet::new();
+    for command in crate::shell_history::tool_commands(tool_name, tool_input) {
+        for word in crate::shell_history::command_words(&command) {
+            if seen.insert(word.clone()) {
+                doc.add_text(fields.command, word);
+            }
+        }
+    }
+}
+
+/// Indexes every tail of each path the record mentions, like edited paths.
+fn add_files(fields: &IndexFields, doc: &mut TantivyDocument, record: &Record) {
+    let mut seen = HashSet::new();
+    for path in crate::mentions::record_files(record) {
+        for suffix in crate::patch::path_suffixes(&path) {
+            if seen.insert(suffix.clone()) {
+                doc.add_text(fields.file, suffix);
+            }
+        }
+    }
+}
+
+fn stored_tool_call(
+    fields: &IndexFields,
+    doc: &TantivyDocument,
+) -> (Option<String>, Option<String>) {
+    let get = |field: Field| {
+        doc.get_first(field)
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    };
+    (get(fields.tool_name), get(fields.tool_input))
+}
+
+/// Does nothing once the paths are there, so chained upgrades that refill
+/// them don't index them twice.
+fn fill_edited_paths(fields: &IndexFields, doc: &mut TantivyDocument) {
+    if doc.get_first(fields.edited_path).is_some() {
+        return;
+    }
+    let (tool_name, tool_input) = stored_tool_call(fields, doc);
+    add_edited_paths(fields, doc, tool_name.as_deref(), tool_input.as_deref());
+}
+
+/// Edited paths aren't stored, so copying the index drops them; they are
+/// refilled along with the commands, which are skipped once there.
+fn fill_commands(fields: &IndexFields, doc: &mut TantivyDocument) {
+    fill_edited_paths(fields, doc);
+    if doc.get_first(fields.command).is_some() {
+        return;
+    }
+    let (tool_name, tool_input) = stored_tool_call(fields, doc);
+    add_commands(fields, doc, tool_name.as_deref(), tool_input.as_deref());
+}
+
+/// Mentioned files come from stored text and tool input; edited paths and
+/// commands are refilled first for the same reason as in [`fill_commands`].
+fn fill_files(fields: &IndexFields, doc: &mut TantivyDocument) {
+    fill_commands(fields, doc);
+    if doc.get_first(fields.file).is_some() {
+        return;
+    }
+    let record = record_from_doc(fields, doc);
+    add_files(fields, doc, &record);
+}
+
+/// Detects the language from stored text, after refilling the unstored
+/// fields before it.
+fn fill_lang(fields: &IndexFields, doc: &mut TantivyDocument) {
+    fill_files(fields, doc);
+    if doc.get_first(fields.lang).is_some() {
+        return;
+    }
+    let record = record_from_doc(fields, doc);
+    if let Some(lang) = crate::lang::record_language(&record) {
+        doc.add_text(fields.lang, lang);
+    }
+}
+
+/// Indexes the kind and fingerprint of each error in a tool result.
+fn add_errors(fields: &IndexFields, doc: &mut TantivyDocument, record: &Record) {
+    let mut seen = HashSet::new();
+    for event in crate::errors::record_errors(record) {
+        if seen.insert(event.kind) {
+            doc.add_text(fields.error, event.kind);
+        }
+        doc.add_text(fields.error, event.fingerprint);
+    }
+}
+
+/// Finds errors in stored tool output, after refilling the unstored fields
+/// before them.
+fn fill_errors(fields: &IndexFields, doc: &mut TantivyDocument) {
+    fill_lang(fields, doc);
+    if doc.get_first(fields.error).is_some() {
+        return;
+    }
+    let record = record_from_doc(fields, doc);
+    add_errors(fields, doc, &record);
+}
+
+enum MigrationPlan {
+    Current,
+    CopyStored(Vec<Fixup>),
+    Reingest,
+}
+
+/// Chains the steps from `version` to the newest; any `Reingest` on the way
+/// wins since the records would be thrown away anyway. An index from a newer
+/// memex can't be read and is rebuilt too.
+fn plan_migration(version: u32, migrations: &[Migration]) -> MigrationPlan {
+    let latest = migrations.len() as u32;
+    if version == latest {
+        return MigrationPlan::Current;
+    }
+    if version > latest {
+        return MigrationPlan::Reingest;
+    }
+    let mut fixups = Vec::new();
+    for step in &migrations[version as usize..] {
+        match step {
+            Migration::CopyStored(fixup) => fixups.extend(*fixup),
+            Migration::Reingest => return MigrationPlan::Reingest,
+        }
+    }
+    MigrationPlan::CopyStored(fixups)
+}
+
+/// Version recorded at creation, or for indexes from before versioning,
+/// the newest version whose fields they already have.
+fn read_schema_version(dir: &Path, schema: &Schema) -> u32 {
+    if let Some(version) = std::fs::read_to_string(dir.join(SCHEMA_VERSION_FILE))
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+    {
+        return version;
+    }
+    if !text_uses_code_tokenizer(schema) || !schema_has_fields(schema, &V1_FIELDS) {
+        0
+    } else if !schema_has_fields(schema, &V2_FIELDS) {
+        1
+    } else if !schema_has_fields(schema, &V3_FIELDS) {
+        2
+    } else {
+        3
+    }
+}
+
+fn write_schema_version(dir: &Path) -> Result<()> {
+    std::fs::write(dir.join(SCHEMA_VERSION_FILE), SCHEMA_VERSION.to_string())?;
+    Ok(())
+}
+
+/// Copies every live document into a fresh index with the current schema,
+/// then swaps it in for the old one.
+fn upgrade_in_place(dir: &Path, fixups: &[Fixup]) -> Result<SearchIndex> {
+    let old = Index::open_in_dir(dir)?;
+    register_tokenizers(&old);
+    let old_schema = old.schema();
+    let mut staging = dir.as_os_str().to_owned();
+    staging.push(".migrating");
+    let staging = std::path::PathBuf::from(staging);
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(&staging)?;
+    let upgraded = create_index_in_dir(&staging)?;
+    let new_schema = upgraded.index.schema();
+
+    let reader = old.reader()?;
+    let searcher = reader.searcher();
+    let store_before = searcher
+        .space_usage()
+        .map(|usage| {
+            usage
+                .segments()
+                .iter()
+                .map(|segment| segment.store().total().get_bytes())
+                .sum::<u64>()
+        })
+        .ok();
+    let progress = ProgressBar::new(searcher.num_docs());
+    progress.set_style(
+        ProgressStyle::with_template("  upgrading index {bar:30.cyan} {pos}/{len}")
+            .unwrap()
+            .progress_chars("█▉▊▋▌▍▎▏ "),
+    );
+    let mut writer = upgraded.writer()?;
+    for segment_reader in searcher.segment_readers() {
+        let store = segment_reader.get_store_reader(0)?;
+        for old_doc in store.iter::<TantivyDocument>(segment_reader.alive_bitset()) {
+            let old_doc = old_doc?;
+            let mut doc = TantivyDocument::default();
+            for field_value in old_doc.field_values() {
+                let name = old_schema.get_field_name(field_value.field());
+                if let Ok(field) = new_schema.get_field(name) {
+                    doc.add_field_value(field, field_value.value().clone());
+                }
+            }
+            for fixup in fixups {
+                fixup(&upgraded.fields, &mut doc);
+            }
+            writer.add_document(doc)?;
+            progress.inc(1);
+        }
+    }
+    writer.commit()?;
+    writer.wait_merging_threads()?;
+    if let (Some(before), Ok(after)) = (store_before, upgraded.store_stats()) {
+        progress.println(format!(
+            "  stored records: {} -> {} ({})",
+            crate::progress::format_bytes(before),
+            crate::progress::format_bytes(after.bytes),
+            after.compression
+        ));
+    }
+    progress.finish_and_clear();
+    drop(reader);
+    drop(old);
+    drop(upgraded);
+
+    std::fs::remove_dir_all(dir)?;
+    std::fs::rename(&staging, dir)?;
+    let index = Index::open_in_dir(dir)?;
+    register_tokenizers(&index);
+    let fields = load_fields(index.schema())?;
+    Ok(SearchIndex::from_parts(dir, index, fields))
+}
+
+fn build_schema() -> Result<Schema> {
+    let mut builder = SchemaBuilder::default();
+
+    builder.add_u64_field("doc_id", INDEXED | STORED | FAST);
+    builder.add_u64_field("ts", INDEXED | STORED | FAST);
+    builder.add_text_field("project", STRING | STORED);
+    builder.add_text_field("session_id", STRING | STORED);
+    builder.add_u64_field("turn_id", INDEXED | STORED | FAST);
+    builder.add_text_field("role", STRING | STORED);
     builder.add_text_field("source", STRING | STORED);
 
     let text_indexing = TextFieldIndexing::default()
-        .set_tokenizer("default")
+        .set_tokenizer(CODE_TOKENIZER)
         .set_index_option(IndexRecordOption::WithFreqsAndPositions);
     let text_options = TextOptions::default()
         .set_indexing_options(text_indexing)
@@ -300,40 +1841,91 @@ fn build_schema() -> Result<Schema> {
     builder.add_text_field("parent_session_id", STRING | STORED);
     builder.add_text_field("thread_source", STRING | STORED);
     builder.add_text_field("conversation_kind", STRING | STORED);
+    builder.add_text_field("thread", STRING | STORED);
     builder.add_text_field("parent_tool_use_id", STRING | STORED);
     builder.add_text_field("source_tool_use_id", STRING | STORED);
     builder.add_text_field("source_tool_assistant_uuid", STRING | STORED);
     builder.add_text_field("source_path", STRING | STORED);
+    builder.add_u64_field("ingested_at", INDEXED | STORED | FAST);
+    builder.add_text_field("model", STRING | STORED);
+    builder.add_text_field("sandbox", STRING | STORED);
+    builder.add_text_field("approval_policy", STRING | STORED);
+    builder.add_text_field("attachments", STORED);
+    builder.add_text_field("attachment_kind", STRING);
+
+    let code_indexing = TextFieldIndexing::default()
+        .set_tokenizer(CODE_TOKENIZER)
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    builder.add_text_field(
+        "code",
+        TextOptions::default().set_indexing_options(code_indexing),
+    );
+    builder.add_text_field("edited_path", STRING);
+    builder.add_text_field("command", STRING);
+    builder.add_text_field("file", STRING);
+    builder.add_text_field("lang", STRING);
+    builder.add_text_field("error", STRING);
 
     Ok(builder.build())
 }
 
-fn schema_is_current(schema: &Schema) -> bool {
-    [
-        "doc_id",
-        "ts",
-        "project",
-        "session_id",
-        "turn_id",
-        "role",
-        "source",
-        "text",
-        "tool_name",
-        "tool_input",
-        "tool_output",
-        "event_id",
-        "parent_event_id",
-        "logical_parent_event_id",
-        "parent_session_id",
-        "thread_source",
-        "conversation_kind",
-        "parent_tool_use_id",
-        "source_tool_use_id",
-        "source_tool_assistant_uuid",
-        "source_path",
-    ]
-    .into_iter()
-    .all(|field| schema.get_field(field).is_ok())
+fn register_tokenizers(index: &Index) {
+    index
+        .tokenizers()
+        .register(CODE_TOKENIZER, crate::tokenizer::code_analyzer());
+}
+
+/// Fields of the first versioned schema.
+const V1_FIELDS: [&str; 26] = [
+    "doc_id",
+    "ts",
+    "project",
+    "session_id",
+    "turn_id",
+    "role",
+    "source",
+    "text",
+    "tool_name",
+    "tool_input",
+    "tool_output",
+    "event_id",
+    "parent_event_id",
+    "logical_parent_event_id",
+    "parent_session_id",
+    "thread_source",
+    "conversation_kind",
+    "thread",
+    "parent_tool_use_id",
+    "source_tool_use_id",
+    "source_tool_assistant_uuid",
+    "source_path",
+    "ingested_at",
+    "model",
+    "sandbox",
+    "approval_policy",
+];
+
+/// Fields added in v2.
+const V2_FIELDS: [&str; 2] = ["attachments", "attachment_kind"];
+
+/// Fields added in v3.
+const V3_FIELDS: [&str; 1] = ["code"];
+
+/// Indexes built with the old default tokenizer predate versioning.
+fn text_uses_code_tokenizer(schema: &Schema) -> bool {
+    let text_tokenizer = schema.get_field("text").ok().and_then(|field| {
+        match schema.get_field_entry(field).field_type() {
+            FieldType::Str(options) => options
+                .get_indexing_options()
+                .map(|indexing| indexing.tokenizer() == CODE_TOKENIZER),
+            _ => None,
+        }
+    });
+    text_tokenizer == Some(true)
+}
+
+fn schema_has_fields(schema: &Schema, fields: &[&str]) -> bool {
+    fields.iter().all(|field| schema.get_field(field).is_ok())
 }
 
 fn load_fields(schema: Schema) -> Result<IndexFields> {
@@ -360,13 +1952,166 @@ fn load_fields(schema: Schema) -> Result<IndexFields> {
         parent_session_id: get("parent_session_id")?,
         thread_source: get("thread_source")?,
         conversation_kind: get("conversation_kind")?,
+        thread: get("thread")?,
         parent_tool_use_id: get("parent_tool_use_id")?,
         source_tool_use_id: get("source_tool_use_id")?,
         source_tool_assistant_uuid: get("source_tool_assistant_uuid")?,
         source_path: get("source_path")?,
+        ingested_at: get("ingested_at")?,
+        model: get("model")?,
+        sandbox: get("sandbox")?,
+        approval_policy: get("approval_policy")?,
+        attachments: get("attachments")?,
+        attachment_kind: get("attachment_kind")?,
+        code: get("code")?,
+        edited_path: get("edited_path")?,
+        command: get("command")?,
+        file: get("file")?,
+        lang: get("lang")?,
+        error: get("error")?,
     })
 }
 
+/// Fenced blocks (```` ``` ````) anywhere in a tool payload.
+static FENCED_CODE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)```[^\n]*\n(.*?)```").expect("fence regex"));
+
+/// Line-number gutters from file reads: `    12→` (Claude) or `    12\t` (`cat -n`).
+static LINE_GUTTER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^ *\d+(?:→|\t)").expect("gutter regex"));
+
+/// Fragments that are rare in prose but common in source.
+const CODE_HINTS: [&str; 14] = [
+    "{",
+    "}",
+    ";",
+    "=>",
+    "->",
+    "::",
+    "()",
+    "fn ",
+    "def ",
+    "function ",
+    "class ",
+    "import ",
+    "return ",
+    "const ",
+];
+
+/// Code in a record's tool input and output. JSON inputs (e.g. a write or
+/// edit call) are searched string by string so escaped newlines don't glue
+/// identifiers together.
+fn record_code(record: &Record) -> Vec<String> {
+    let mut texts = Vec::new();
+    if let Some(input) = &record.tool_input {
+        match serde_json::from_str::<serde_json::Value>(input) {
+            Ok(value) => collect_json_strings(&value, &mut texts),
+            Err(_) => texts.push(input.clone()),
+        }
+    }
+    if let Some(output) = &record.tool_output {
+        texts.push(output.clone());
+    }
+    if record.role == "tool_result" && record.tool_output.is_none() {
+        texts.push(record.text.clone());
+    }
+    texts.iter().filter_map(|text| extract_code(text)).collect()
+}
+
+fn collect_json_strings(value: &serde_json::Value, out: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(text) if text.contains('\n') => out.push(text.clone()),
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_json_strings(item, out);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for item in map.values() {
+                collect_json_strings(item, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The code in `text`: its fenced blocks when it has any, otherwise the whole
+/// text when at least a third of its lines look like source (file reads,
+/// diffs). Line-number gutters and diff markers are dropped.
+pub fn extract_code(text: &str) -> Option<String> {
+    let fenced: Vec<&str> = FENCED_CODE
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(1).map(|block| block.as_str()))
+        .collect();
+    if !fenced.is_empty() {
+        return Some(fenced.join("\n"));
+    }
+    let text = LINE_GUTTER.replace_all(text, "");
+    let is_diff = text.lines().any(|line| line.starts_with("@@"));
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| {
+            !(is_diff
+                && (line.starts_with("@@") || line.starts_with("+++") || line.starts_with("---")))
+        })
+        .map(|line| match line.as_bytes().first() {
+            Some(b'+' | b'-') if is_diff => &line[1..],
+            _ => line,
+        })
+        .collect();
+    if lines.len() < 2 {
+        return None;
+    }
+    let hinted = lines
+        .iter()
+        .filter(|line| CODE_HINTS.iter().any(|hint| line.contains(hint)))
+        .count();
+    (hinted * 3 >= lines.len()).then(|| lines.join("\n"))
+}
+
+/// Multiplies the text score of each record by its role's boost: one branch
+/// per boosted role restricted to that role, and one for every other role.
+fn boost_by_role(
+    fields: &IndexFields,
+    text_query: Box<dyn Query>,
+    boosts: &HashMap<String, f32>,
+) -> Box<dyn Query> {
+    let mut boosted: Vec<(&String, f32)> = boosts
+        .iter()
+        .map(|(role, boost)| (role, *boost))
+        .filter(|(_, boost)| *boost != 1.0)
+        .collect();
+    if boosted.is_empty() {
+        return text_query;
+    }
+    boosted.sort_by(|a, b| a.0.cmp(b.0));
+    let role = |role: &str| -> Box<dyn Query> {
+        Box::new(ConstScoreQuery::new(
+            Box::new(TermQuery::new(
+                Term::from_field_text(fields.role, role),
+                IndexRecordOption::Basic,
+            )),
+            0.0,
+        ))
+    };
+    let mut branches: Vec<(Occur, Box<dyn Query>)> = Vec::with_capacity(boosted.len() + 1);
+    for (name, boost) in &boosted {
+        let branch = BooleanQuery::new(vec![
+            (Occur::Must, text_query.box_clone()),
+            (Occur::Must, role(name)),
+        ]);
+        branches.push((
+            Occur::Should,
+            Box::new(BoostQuery::new(Box::new(branch), *boost)),
+        ));
+    }
+    let mut rest = vec![(Occur::Must, text_query)];
+    rest.extend(boosted.iter().map(|(name, _)| (Occur::MustNot, role(name))));
+    branches.push((Occur::Should, Box::new(BooleanQuery::new(rest))));
+    Box::new(BooleanQuery::new(branches))
+}
+
 fn build_query(
     fields: &IndexFields,
     options: &QueryOptions,
@@ -377,27 +2122,43 @@ fn build_query(
     if options.query.trim().is_empty() {
         clauses.push((Occur::Must, Box::new(AllQuery)));
     } else {
-        let parser = tantivy::query::QueryParser::for_index(index, vec![fields.text]);
-        let text_query = parser.parse_query(&options.query)?;
-        clauses.push((Occur::Must, text_query));
-    }
-
-    if let Some(project) = &options.project {
-        let term = Term::from_field_text(fields.project, project);
+        let mut parser = tantivy::query::QueryParser::for_index(index, vec![fields.text]);
+        if options.require_all_terms {
+            parser.set_conjunction_by_default();
+        }
+        if options.fuzzy_distance > 0 {
+            parser.set_field_fuzzy(fields.text, false, options.fuzzy_distance, true);
+        }
+        let text_query = parser.parse_query(&options.tuning.filter_query(&options.query))?;
         clauses.push((
             Occur::Must,
-            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            boost_by_role(fields, text_query, &options.tuning.role_boosts),
         ));
     }
 
-    if let Some(role) = &options.role {
-        let term = Term::from_field_text(fields.role, role);
+    if let Some(project) = &options.project {
+        let term = Term::from_field_text(fields.project, project);
         clauses.push((
             Occur::Must,
             Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
         ));
     }
 
+    // Turns repeat the records they are built from, so they are only
+    // searched when asked for by role.
+    let role = options.role.as_deref().unwrap_or(crate::types::TURN_ROLE);
+    clauses.push((
+        if options.role.is_some() {
+            Occur::Must
+        } else {
+            Occur::MustNot
+        },
+        Box::new(TermQuery::new(
+            Term::from_field_text(fields.role, role),
+            IndexRecordOption::Basic,
+        )),
+    ));
+
     if let Some(tool) = &options.tool {
         let term = Term::from_field_text(fields.tool_name, tool);
         clauses.push((
@@ -444,6 +2205,77 @@ fn build_query(
         clauses.push((Occur::Must, Box::new(range)));
     }
 
+    for (field, value) in [
+        (fields.model, &options.settings.model),
+        (fields.sandbox, &options.settings.sandbox),
+        (fields.approval_policy, &options.settings.approval_policy),
+    ] {
+        if let Some(value) = value {
+            let term = Term::from_field_text(field, value);
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+    }
+
+    for kind in &options.has {
+        let term = Term::from_field_text(fields.attachment_kind, kind);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+
+    for path in &options.edited {
+        let term = Term::from_field_text(fields.edited_path, path);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+
+    for word in &options.commands {
+        let term = Term::from_field_text(fields.command, word);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+
+    for path in &options.files {
+        let term = Term::from_field_text(fields.file, path);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+
+    for lang in &options.langs {
+        let term = Term::from_field_text(fields.lang, lang);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+
+    for error in &options.errors {
+        let term = Term::from_field_text(fields.error, error);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+
+    if let Some(as_of) = options.as_of {
+        let range = RangeQuery::new_u64_bounds(
+            "ingested_at".to_string(),
+            Bound::Included(0),
+            Bound::Included(as_of),
+        );
+        clauses.push((Occur::Must, Box::new(range)));
+    }
+
     Ok(Box::new(BooleanQuery::new(clauses)))
 }
 
@@ -482,11 +2314,21 @@ fn record_from_doc(fields: &IndexFields, doc: &TantivyDocument) -> Record {
             parent_session_id: get_str(fields.parent_session_id),
             thread_source: get_str(fields.thread_source),
             conversation_kind: get_str(fields.conversation_kind),
+            thread: get_str(fields.thread),
             parent_tool_use_id: get_str(fields.parent_tool_use_id),
             source_tool_use_id: get_str(fields.source_tool_use_id),
             source_tool_assistant_uuid: get_str(fields.source_tool_assistant_uuid),
         },
+        settings: AgentSettings {
+            model: get_str(fields.model),
+            sandbox: get_str(fields.sandbox),
+            approval_policy: get_str(fields.approval_policy),
+        },
+        attachments: get_str(fields.attachments)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
         source_path,
+        ingested_at: get_u64(fields.ingested_at),
     }
 }
 
@@ -499,6 +2341,47 @@ fn add_optional_text(doc: &mut TantivyDocument, field: Field, value: &Option<Str
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::test_record;
+
+    #[test]
+    fn query_filters_are_pulled_out_of_the_query() {
+        let (rest, filters) = extract_query_filters(
+            "retry logic model:o3 approval:never sandbox:read-only has:Image",
+        );
+        assert_eq!(rest, "retry logic");
+        assert_eq!(filters.settings.model.as_deref(), Some("o3"));
+        assert_eq!(filters.settings.sandbox.as_deref(), Some("read-only"));
+        assert_eq!(filters.settings.approval_policy.as_deref(), Some("never"));
+        assert_eq!(filters.has, vec!["image"]);
+
+        let (rest, filters) = extract_query_filters("model: plain words");
+        assert_eq!(rest, "model: plain words");
+        assert_eq!(filters, QueryFilters::default());
+    }
+
+    #[test]
+    fn date_filters_take_quoted_phrases_and_narrow_the_range() {
+        let (rest, filters) = extract_query_filters(
+            r#"flaky "exact phrase" after:2024-06-01 before:"1717372800" after:someday"#,
+        );
+        assert_eq!(rest, r#"flaky "exact phrase" after:someday"#);
+        assert_eq!(filters.after, Some(1_717_200_000_000));
+        assert_eq!(filters.before, Some(1_717_372_800_000));
+        assert_eq!(filters.since(None), Some(1_717_200_000_000));
+        assert_eq!(
+            filters.since(Some(1_717_300_000_000)),
+            Some(1_717_300_000_000)
+        );
+        assert_eq!(
+            filters.until(Some(1_800_000_000_000)),
+            Some(1_717_372_800_000)
+        );
+        assert_eq!(QueryFilters::default().until(Some(5)), Some(5));
+
+        let (rest, filters) = extract_query_filters(r#"deploy before:"2 weeks ago""#);
+        assert_eq!(rest, "deploy");
+        assert!(filters.before.is_some());
+    }
 
     fn create_stale_schema_index(dir: &Path) {
         let mut builder = SchemaBuilder::default();
@@ -547,4 +2430,1312 @@ mod tests {
         assert!(tmp.path().join("meta.json").exists());
         assert!(!tmp.path().join("sentinel").exists());
     }
+
+    #[test]
+    fn every_schema_version_has_a_migration() {
+        assert_eq!(MIGRATIONS.len() as u32, SCHEMA_VERSION);
+        assert!(matches!(
+            plan_migration(SCHEMA_VERSION, MIGRATIONS),
+            MigrationPlan::Current
+        ));
+        assert!(matches!(
+            plan_migration(0, MIGRATIONS),
+            MigrationPlan::Reingest
+        ));
+        assert!(matches!(
+            plan_migration(SCHEMA_VERSION + 1, MIGRATIONS),
+            MigrationPlan::Reingest
+        ));
+
+        fn noop(_: &IndexFields, _: &mut TantivyDocument) {}
+        let steps = [
+            Migration::CopyStored(Some(noop as Fixup)),
+            Migration::CopyStored(None),
+            Migration::CopyStored(Some(noop as Fixup)),
+        ];
+        assert!(matches!(
+            plan_migration(1, &steps),
+            MigrationPlan::CopyStored(fixups) if fixups.len() == 1
+        ));
+    }
+
+    #[test]
+    fn unversioned_index_without_attachment_fields_is_upgraded_in_place() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        // A v1 index: today's schema minus the attachment fields, and no
+        // version file.
+        let v1 = tmp.path().join("v1");
+        std::fs::create_dir_all(&v1).expect("v1 dir");
+        {
+            let mut builder = SchemaBuilder::default();
+            for (_, entry) in build_schema().expect("schema").fields() {
+                if !V2_FIELDS.contains(&entry.name()) {
+                    builder.add_field(entry.clone());
+                }
+            }
+            let schema = builder.build();
+            let index = Index::create_in_dir(&v1, schema.clone()).expect("create v1");
+            register_tokenizers(&index);
+            let mut writer = index
+                .writer_with_num_threads(1, 15_000_000)
+                .expect("writer");
+            let mut doc = TantivyDocument::default();
+            doc.add_u64(schema.get_field("doc_id").unwrap(), 7);
+            doc.add_text(schema.get_field("text").unwrap(), "hello");
+            doc.add_text(schema.get_field("source_path").unwrap(), "a.jsonl");
+            writer.add_document(doc).expect("add");
+            writer.commit().expect("commit");
+        }
+
+        let index = SearchIndex::open_or_create(&v1).expect("upgrade");
+        assert_eq!(index.doc_count().expect("doc count"), 1);
+        let record = index.get_by_doc_id(7).expect("get").expect("record");
+        assert_eq!(record.text, "hello");
+        assert!(record.attachments.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(v1.join(SCHEMA_VERSION_FILE)).expect("version"),
+            SCHEMA_VERSION.to_string()
+        );
+        assert!(!tmp.path().join("v1.migrating").exists());
+    }
+
+    #[test]
+    fn lz4_index_is_recompressed_with_zstd() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let v3 = tmp.path().join("v3");
+        std::fs::create_dir_all(&v3).expect("v3 dir");
+        let text: String = (0..2000)
+            .map(|i| {
+                format!(
+                    "{{\"line\":{i},\"status\":\"ok\",\"path\":\"src/m{}.rs\"}}\n",
+                    i % 37
+                )
+            })
+            .collect();
+        {
+            let schema = build_schema().expect("schema");
+            let index = Index::create_in_dir(&v3, schema.clone()).expect("create v3");
+            register_tokenizers(&index);
+            let mut writer = index
+                .writer_with_num_threads(1, 15_000_000)
+                .expect("writer");
+            for doc_id in 0..20 {
+                let mut doc = TantivyDocument::default();
+                doc.add_u64(schema.get_field("doc_id").unwrap(), doc_id);
+                doc.add_text(schema.get_field("text").unwrap(), &text);
+                writer.add_document(doc).expect("add");
+            }
+            writer.commit().expect("commit");
+            std::fs::write(v3.join(SCHEMA_VERSION_FILE), "3").expect("version");
+            let fields = load_fields(schema).expect("fields");
+            let stats = SearchIndex::from_parts(&v3, index, fields)
+                .store_stats()
+                .expect("stats");
+            assert_eq!(stats.compression, "lz4");
+        }
+
+        let index = SearchIndex::open_or_create(&v3).expect("upgrade");
+        let stats = index.store_stats().expect("stats");
+        assert_eq!(stats.compression, "zstd");
+        assert!(stats.bytes > 0);
+        assert_eq!(
+            index.get_by_doc_id(19).expect("get").expect("record").text,
+            text
+        );
+    }
+
+    #[test]
+    fn project_records_interleave_sessions_by_time() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        for (doc_id, session_id, project, ts) in [
+            (1, "a", "memex", 10),
+            (2, "b", "memex", 20),
+            (3, "a", "memex", 30),
+            (4, "c", "other", 25),
+            (5, "b", "memex", 40),
+            (6, "a", "memex", 90),
+        ] {
+            let mut record = test_record(doc_id, "s1");
+            record.session_id = session_id.to_string();
+            record.project = project.to_string();
+            record.ts = ts;
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        let mut note = Record {
+            source_path: "memex-note:a".to_string(),
+            ..test_record(7, "s1")
+        };
+        note.role = crate::types::NOTE_ROLE.to_string();
+        note.ts = 30;
+        index.add_record(&mut writer, &note).expect("add");
+        writer.commit().expect("commit");
+
+        let ids = |since, until, limit| -> Vec<u64> {
+            index
+                .records_by_project("memex", since, until, limit)
+                .expect("records")
+                .into_iter()
+                .map(|record| record.doc_id)
+                .collect()
+        };
+        assert_eq!(ids(0, 50, 100), vec![1, 2, 3, 5]);
+        // Over the limit, the newest records are kept.
+        assert_eq!(ids(0, 100, 2), vec![5, 6]);
+    }
+
+    #[test]
+    fn turns_are_searched_only_by_role_and_kept_out_of_transcripts() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut records = Vec::new();
+        for (doc_id, role, text) in [
+            (1, "user", "why does the migration hang"),
+            (2, "assistant", "the writer lock is held twice"),
+            (3, "user", "thanks"),
+        ] {
+            let mut record = test_record(doc_id, "s1");
+            record.role = role.to_string();
+            record.text = text.to_string();
+            index.add_record(&mut writer, &record).expect("add");
+            records.push(record);
+        }
+        for turn in crate::turns::build_turns(&records) {
+            index.add_record(&mut writer, &turn).expect("add turn");
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+
+        let search = |query: &str, role: Option<&str>| -> Vec<u64> {
+            index
+                .search(&QueryOptions {
+                    query: query.to_string(),
+                    project: None,
+                    role: role.map(str::to_string),
+                    tool: None,
+                    session_id: None,
+                    source: None,
+                    since: None,
+                    until: None,
+                    as_of: None,
+                    settings: AgentSettings::default(),
+                    has: Vec::new(),
+                    edited: Vec::new(),
+                    commands: Vec::new(),
+                    files: Vec::new(),
+                    langs: Vec::new(),
+                    errors: Vec::new(),
+                    require_all_terms: true,
+                    fuzzy_distance: 0,
+                    tuning: QueryTuning::default(),
+                    limit: 10,
+                })
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record.doc_id)
+                .collect()
+        };
+        // Only the turn has both the question and the answer.
+        assert!(search("migration writer", None).is_empty());
+        let turn = crate::turns::turn_doc_id(1);
+        assert_eq!(
+            search("migration writer", Some(crate::types::TURN_ROLE)),
+            vec![turn]
+        );
+        assert_eq!(search("migration", None), vec![1]);
+
+        assert_eq!(index.records_by_session_id("s1").expect("records").len(), 3);
+        assert_eq!(index.recent_records(10).expect("recent").len(), 3);
+        let mut seen = 0;
+        index
+            .for_each_record(|_| {
+                seen += 1;
+                Ok(())
+            })
+            .expect("records");
+        assert_eq!(seen, 3);
+        assert_eq!(
+            index
+                .count_roles(&[crate::types::TURN_ROLE])
+                .expect("count"),
+            2
+        );
+
+        let mut writer = index.writer().expect("writer");
+        index.delete_by_doc_id(&mut writer, turn).expect("delete");
+        writer.commit().expect("commit");
+        assert_eq!(
+            index
+                .count_roles(&[crate::types::TURN_ROLE])
+                .expect("count"),
+            1
+        );
+    }
+
+    #[test]
+    fn lang_filter_keeps_records_in_that_language() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        for (doc_id, text) in [
+            (1, "Warum ist der Build auf main nicht grün?"),
+            (2, "Why is the build on main not green?"),
+        ] {
+            let mut record = test_record(doc_id, "s1");
+            record.text = text.to_string();
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let (query, filters) = extract_query_filters("build lang:DE");
+        assert_eq!(filters.langs, ["de"]);
+        let ids: Vec<u64> = index
+            .search(&QueryOptions {
+                query,
+                project: None,
+                role: None,
+                tool: None,
+                session_id: None,
+                source: None,
+                since: None,
+                until: None,
+                as_of: None,
+                settings: AgentSettings::default(),
+                has: Vec::new(),
+                edited: Vec::new(),
+                commands: Vec::new(),
+                files: Vec::new(),
+                langs: filters.langs,
+                errors: filters.errors,
+                require_all_terms: false,
+                fuzzy_distance: 0,
+                tuning: QueryTuning::default(),
+                limit: 10,
+            })
+            .expect("search")
+            .into_iter()
+            .map(|(_, record)| record.doc_id)
+            .collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn error_filter_finds_tool_results_with_that_error() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        for (doc_id, role, text) in [
+            (
+                1,
+                "tool_result",
+                "thread 'main' panicked at src/main.rs:3:5:\nindex out of bounds",
+            ),
+            (2, "tool_result", "test result: ok. 4 passed"),
+            (
+                3,
+                "assistant",
+                "thread 'main' panicked at src/main.rs:3:5:\nindex out of bounds",
+            ),
+        ] {
+            let mut record = test_record(doc_id, "s1");
+            record.role = role.to_string();
+            record.text = text.to_string();
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let fingerprint = crate::errors::fingerprint("panic", "index out of bounds");
+        for filter in ["error:panic".to_string(), format!("error:{fingerprint}")] {
+            let (query, filters) = extract_query_filters(&filter);
+            let options = QueryOptions {
+                query,
+                project: None,
+                role: None,
+                tool: None,
+                session_id: None,
+                source: None,
+                since: None,
+                until: None,
+                as_of: None,
+                settings: AgentSettings::default(),
+                has: Vec::new(),
+                edited: Vec::new(),
+                commands: Vec::new(),
+                files: Vec::new(),
+                langs: Vec::new(),
+                errors: filters.errors,
+                require_all_terms: false,
+                fuzzy_distance: 0,
+                tuning: QueryTuning::default(),
+                limit: 10,
+            };
+            let ids: Vec<u64> = index
+                .records_with_errors(&options)
+                .expect("errors")
+                .into_iter()
+                .map(|record| record.doc_id)
+                .collect();
+            assert_eq!(ids, vec![1], "{filter}");
+        }
+    }
+
+    #[test]
+    fn split_months_moves_old_records_into_shards_searched_with_the_index() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let dir = tmp.path().join("index");
+        std::fs::create_dir_all(&dir).expect("index dir");
+        let index = SearchIndex::open_or_create_for_ingest(&dir).expect("create index");
+        let month = |date: &str| {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .expect("date")
+                .and_hms_opt(12, 0, 0)
+                .expect("time")
+                .and_utc()
+                .timestamp_millis() as u64
+        };
+        let mut writer = index.writer().expect("writer");
+        for (doc_id, path, date, role) in [
+            (1, "a.jsonl", "2024-01-10", "user"),
+            (2, "b.jsonl", "2024-02-10", "user"),
+            (3, "c.jsonl", "2024-06-10", "user"),
+            (4, "a.jsonl", "2024-01-11", crate::types::NOTE_ROLE),
+        ] {
+            let mut record = Record {
+                source_path: path.to_string(),
+                ..test_record(doc_id, "s1")
+            };
+            record.ts = month(date);
+            record.role = role.to_string();
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+        let options = QueryOptions {
+            query: "hello".to_string(),
+            project: None,
+            role: None,
+            tool: None,
+            session_id: None,
+            source: None,
+            since: None,
+            until: None,
+            as_of: None,
+            settings: AgentSettings::default(),
+            has: Vec::new(),
+            edited: Vec::new(),
+            commands: Vec::new(),
+            files: Vec::new(),
+            langs: Vec::new(),
+            errors: Vec::new(),
+            require_all_terms: false,
+            fuzzy_distance: 0,
+            tuning: QueryTuning::default(),
+            limit: 10,
+        };
+        let scores = |index: &SearchIndex| -> HashMap<u64, f32> {
+            index
+                .search(&options)
+                .expect("search")
+                .into_iter()
+                .map(|(score, record)| (record.doc_id, score))
+                .collect()
+        };
+        let before = scores(&index);
+
+        let moved = index
+            .split_months(month("2024-06-01"), false)
+            .expect("split");
+        assert_eq!(
+            moved,
+            vec![("2024-01".to_string(), 1), ("2024-02".to_string(), 1)]
+        );
+        assert_eq!(index.reader().expect("reader").searcher().num_docs(), 2);
+        let shards = index.shards().expect("shards");
+        let months: Vec<&str> = shards.iter().map(|shard| shard.month.as_str()).collect();
+        assert_eq!(months, ["2024-01", "2024-02"]);
+        assert_eq!(shards[0].dir, tmp.path().join("index-shards/2024-01"));
+
+        assert_eq!(index.doc_count().expect("count"), 4);
+        let found = scores(&index);
+        assert_eq!(
+            found.keys().copied().collect::<HashSet<_>>(),
+            HashSet::from([1, 2, 3, 4])
+        );
+        // Shards score with the statistics of the whole index.
+        assert_eq!(found, before);
+        assert_eq!(index.records_by_session_id("s1").expect("records").len(), 3);
+        let recent: Vec<u64> = index
+            .recent_records(2)
+            .expect("recent")
+            .iter()
+            .map(|record| record.doc_id)
+            .collect();
+        assert_eq!(recent, [3, 2]);
+        assert!(index.live_doc_ids().expect("ids").contains(&1));
+
+        // Nothing is left to move, and a file indexed again drops its shard copy.
+        assert!(
+            index
+                .split_months(month("2024-06-01"), false)
+                .expect("split")
+                .is_empty()
+        );
+        let mut writer = index.writer().expect("writer");
+        index
+            .delete_by_source_path(&mut writer, "a.jsonl")
+            .expect("delete");
+        writer.commit().expect("commit");
+        assert!(index.get_by_doc_id(1).expect("get").is_none());
+        assert!(index.get_by_doc_id(2).expect("get").is_some());
+        assert_eq!(index.doc_count().expect("count"), 2);
+    }
+
+    #[test]
+    fn damaged_index_is_reported_at_open_and_salvaged_per_segment() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        for doc_id in [1, 2] {
+            index
+                .add_record(&mut writer, &test_record(doc_id, "s1"))
+                .expect("add");
+            // One segment per record.
+            writer.commit().expect("commit");
+        }
+        drop(writer);
+        drop(index);
+        assert!(verify_index(tmp.path()).is_empty());
+
+        let store = std::fs::read_dir(tmp.path())
+            .expect("read dir")
+            .map(|entry| entry.expect("entry").path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "store"))
+            .expect("segment store");
+        std::fs::remove_file(&store).expect("remove store");
+
+        let err = SearchIndex::open_or_create(tmp.path())
+            .err()
+            .expect("damaged index fails to open")
+            .to_string();
+        assert!(err.contains("memex repair --rebuild-index"), "{err}");
+        assert!(!verify_index(tmp.path()).is_empty());
+
+        let mut salvaged = Vec::new();
+        let salvage = salvage_records(tmp.path(), |record| {
+            salvaged.push(record.doc_id);
+            Ok(())
+        })
+        .expect("salvage");
+        assert_eq!(salvage.records, 1);
+        assert_eq!(salvage.lost_segments, 1);
+        assert!(!salvage.is_complete());
+        assert_eq!(salvaged.len(), 1);
+
+        std::fs::write(tmp.path().join("meta.json"), "{").expect("truncate meta");
+        let err = SearchIndex::open_or_create(tmp.path())
+            .err()
+            .expect("unreadable meta fails to open")
+            .to_string();
+        assert!(err.contains("looks damaged"), "{err}");
+        let salvage = salvage_records(tmp.path(), |_| Ok(())).expect("salvage");
+        assert!(!salvage.readable);
+    }
+
+    #[test]
+    fn text_search_matches_code_identifiers() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut camel = test_record(1, "s1");
+        camel.text = "the QueryOptions struct".to_string();
+        let mut joined = test_record(2, "s1");
+        joined.text = "vectors live in doc_ids.u64 now".to_string();
+        let mut prose = test_record(3, "s1");
+        prose.text = "query the ids and options".to_string();
+        for record in [&camel, &joined, &prose] {
+            index.add_record(&mut writer, record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let search = |query: &str| -> Vec<u64> {
+            let mut ids: Vec<u64> = index
+                .search(&QueryOptions {
+                    query: query.to_string(),
+                    project: None,
+                    role: None,
+                    tool: None,
+                    session_id: None,
+                    source: None,
+                    since: None,
+                    until: None,
+                    as_of: None,
+                    settings: AgentSettings::default(),
+                    has: Vec::new(),
+                    edited: Vec::new(),
+                    commands: Vec::new(),
+                    files: Vec::new(),
+                    langs: Vec::new(),
+                    errors: Vec::new(),
+                    require_all_terms: false,
+                    fuzzy_distance: 0,
+                    tuning: QueryTuning::default(),
+                    limit: 10,
+                })
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record.doc_id)
+                .collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        assert_eq!(search("QueryOptions"), vec![1]);
+        assert_eq!(search("queryoptions"), vec![1]);
+        assert_eq!(search("options"), vec![1, 3]);
+        assert_eq!(search("doc_ids.u64"), vec![2]);
+        assert_eq!(search("ids"), vec![2, 3]);
+    }
+
+    #[test]
+    fn code_prefix_searches_code_from_tool_content() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let file_read = "     1→fn retry_backoff(attempt: u32) -> Duration {\n     \
+                         2→    Duration::from_millis(100 << attempt)\n     3→}\n";
+        let mut read = test_record(1, "s1");
+        read.role = "tool_result".to_string();
+        read.text = String::new();
+        read.tool_output = Some(file_read.to_string());
+        let mut write = test_record(2, "s1");
+        write.role = "tool_use".to_string();
+        write.tool_input = Some(
+            r#"{"file_path":"src/net.rs","content":"pub fn jitter(ms: u64) -> u64 {\n    ms / 2\n}\n"}"#
+                .to_string(),
+        );
+        let mut prose = test_record(3, "s1");
+        prose.role = "tool_result".to_string();
+        prose.tool_output = Some("retry backoff finished without errors".to_string());
+        for record in [&read, &write, &prose] {
+            index
+                .add_record_with_code(&mut writer, record, true)
+                .expect("add");
+        }
+        let mut without_code = read.clone();
+        without_code.doc_id = 4;
+        index.add_record(&mut writer, &without_code).expect("add");
+        writer.commit().expect("commit");
+
+        let search = |query: &str| -> Vec<u64> {
+            let mut ids: Vec<u64> = index
+                .search(&QueryOptions {
+                    query: query.to_string(),
+                    project: None,
+                    role: None,
+                    tool: None,
+                    session_id: None,
+                    source: None,
+                    since: None,
+                    until: None,
+                    as_of: None,
+                    settings: AgentSettings::default(),
+                    has: Vec::new(),
+                    edited: Vec::new(),
+                    commands: Vec::new(),
+                    files: Vec::new(),
+                    langs: Vec::new(),
+                    errors: Vec::new(),
+                    require_all_terms: false,
+                    fuzzy_distance: 0,
+                    tuning: QueryTuning::default(),
+                    limit: 10,
+                })
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record.doc_id)
+                .collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        assert_eq!(search("code:retry_backoff"), vec![1]);
+        assert_eq!(search("code:backoff"), vec![1]);
+        assert_eq!(search("code:jitter"), vec![2]);
+        assert_eq!(
+            extract_code("see:\n```rust\nlet x = 1;\n```\ndone").as_deref(),
+            Some("let x = 1;\n")
+        );
+        assert_eq!(extract_code("All tests passed.\nNo warnings."), None);
+    }
+
+    #[test]
+    fn as_of_excludes_records_ingested_later() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut early = test_record(1, "s1");
+        early.ingested_at = 1_000;
+        let mut late = test_record(2, "s1");
+        late.ingested_at = 5_000;
+        let fresh = test_record(3, "s1");
+        for record in [&early, &late, &fresh] {
+            index.add_record(&mut writer, record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let search = |as_of: Option<u64>| -> Vec<u64> {
+            let mut ids: Vec<u64> = index
+                .search(&QueryOptions {
+                    query: "hello".to_string(),
+                    project: None,
+                    role: None,
+                    tool: None,
+                    session_id: None,
+                    source: None,
+                    since: None,
+                    until: None,
+                    as_of,
+                    settings: AgentSettings::default(),
+                    has: Vec::new(),
+                    edited: Vec::new(),
+                    commands: Vec::new(),
+                    files: Vec::new(),
+                    langs: Vec::new(),
+                    errors: Vec::new(),
+                    require_all_terms: false,
+                    fuzzy_distance: 0,
+                    tuning: QueryTuning::default(),
+                    limit: 10,
+                })
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record.doc_id)
+                .collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        assert_eq!(search(Some(1_000)), vec![1]);
+        assert_eq!(search(Some(5_000)), vec![1, 2]);
+        assert_eq!(search(None), vec![1, 2, 3]);
+
+        let prior = index
+            .ingested_at_by_source_path("/tmp/s1.jsonl")
+            .expect("prior");
+        assert_eq!(prior.get(&(1, 1, "user".to_string())), Some(&1_000));
+        assert_eq!(prior.get(&(2, 2, "user".to_string())), Some(&5_000));
+    }
+
+    #[test]
+    fn records_ingested_after_returns_the_newest_oldest_first() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        for (doc_id, ingested_at) in [(1, 1_000), (2, 3_000), (3, 2_000)] {
+            let mut record = test_record(doc_id, "s1");
+            record.ingested_at = ingested_at;
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let ids = |after: u64, limit: usize| -> Vec<u64> {
+            index
+                .records_ingested_after(after, limit)
+                .expect("records")
+                .into_iter()
+                .map(|record| record.doc_id)
+                .collect()
+        };
+        assert_eq!(ids(0, 10), vec![1, 3, 2]);
+        assert_eq!(ids(1_000, 10), vec![3, 2]);
+        assert_eq!(ids(0, 2), vec![3, 2]);
+        assert!(ids(3_000, 10).is_empty());
+    }
+
+    #[test]
+    fn profile_term_matching_controls_conjunction_and_fuzziness() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut both = test_record(1, "s1");
+        both.text = "parser panic".to_string();
+        let mut one = test_record(2, "s1");
+        one.text = "parser works".to_string();
+        for record in [&both, &one] {
+            index.add_record(&mut writer, record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let search = |query: &str, require_all_terms: bool, fuzzy_distance: u8| -> Vec<u64> {
+            let mut ids: Vec<u64> = index
+                .search(&QueryOptions {
+                    query: query.to_string(),
+                    project: None,
+                    role: None,
+                    tool: None,
+                    session_id: None,
+                    source: None,
+                    since: None,
+                    until: None,
+                    as_of: None,
+                    settings: AgentSettings::default(),
+                    has: Vec::new(),
+                    edited: Vec::new(),
+                    commands: Vec::new(),
+                    files: Vec::new(),
+                    langs: Vec::new(),
+                    errors: Vec::new(),
+                    require_all_terms,
+                    fuzzy_distance,
+                    tuning: QueryTuning::default(),
+                    limit: 10,
+                })
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record.doc_id)
+                .collect();
+            ids.sort_unstable();
+            ids
+        };
+
+        assert_eq!(search("parser panic", false, 0), vec![1, 2]);
+        assert_eq!(search("parser panic", true, 0), vec![1]);
+        assert!(search("parsr", false, 0).is_empty());
+        assert_eq!(search("parsr", false, 1), vec![1, 2]);
+    }
+
+    #[test]
+    fn ocr_text_of_attachments_is_searched_but_not_shown() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut pasted = test_record(1, "s1");
+        pasted.text = "[image]".to_string();
+        pasted.attachments = vec![crate::types::Attachment {
+            kind: "image".to_string(),
+            media_type: Some("image/png".to_string()),
+            filename: None,
+            size: Some(10),
+            path: None,
+            text: Some("ECONNREFUSED 127.0.0.1:5432".to_string()),
+        }];
+        index.add_record(&mut writer, &pasted).expect("add");
+        writer.commit().expect("commit");
+
+        let (query, filters) = extract_query_filters("has:image econnrefused");
+        let hits = index
+            .search(&QueryOptions {
+                query,
+                project: None,
+                role: None,
+                tool: None,
+                session_id: None,
+                source: None,
+                since: None,
+                until: None,
+                as_of: None,
+                settings: filters.settings,
+                has: filters.has,
+                edited: filters.edited,
+                commands: filters.commands,
+                files: filters.files,
+                langs: filters.langs,
+                errors: filters.errors,
+                require_all_terms: false,
+                fuzzy_distance: 0,
+                tuning: QueryTuning::default(),
+                limit: 10,
+            })
+            .expect("search");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1.text, "[image]");
+        assert_eq!(
+            hits[0].1.attachments[0].text.as_deref(),
+            Some("ECONNREFUSED 127.0.0.1:5432")
+        );
+    }
+
+    #[test]
+    fn edited_filter_matches_patched_path_tails() {
+        let patch =
+            "*** Begin Patch\n*** Update File: /repo/src/index.rs\n@@\n-a\n+b\n*** End Patch";
+        let search = |index: &SearchIndex, query: &str| {
+            let (query, filters) = extract_query_filters(query);
+            index
+                .search(&QueryOptions {
+                    query,
+                    project: None,
+                    role: None,
+                    tool: None,
+                    session_id: None,
+                    source: None,
+                    since: None,
+                    until: None,
+                    as_of: None,
+                    settings: filters.settings,
+                    has: filters.has,
+                    edited: filters.edited,
+                    commands: filters.commands,
+                    files: filters.files,
+                    langs: filters.langs,
+                    errors: filters.errors,
+                    require_all_terms: false,
+                    fuzzy_distance: 0,
+                    tuning: QueryTuning::default(),
+                    limit: 10,
+                })
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record.doc_id)
+                .collect::<Vec<_>>()
+        };
+
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut call = test_record(1, "s1");
+        call.role = "tool_use".to_string();
+        call.tool_name = Some("apply_patch".to_string());
+        call.tool_input = Some(patch.to_string());
+        index.add_record(&mut writer, &call).expect("add");
+        index
+            .add_record(&mut writer, &test_record(2, "s1"))
+            .expect("add");
+        writer.commit().expect("commit");
+        assert_eq!(search(&index, "edited:src/index.rs"), vec![1]);
+        assert_eq!(search(&index, "edited:./index.rs hello"), vec![1]);
+        assert!(search(&index, "edited:dex.rs").is_empty());
+
+        // v4 indexes get the paths filled in from stored tool input.
+        let v4 = tmp.path().join("v4");
+        std::fs::create_dir_all(&v4).expect("v4 dir");
+        {
+            let schema = build_schema().expect("schema");
+            let index = Index::create_in_dir(&v4, schema.clone()).expect("create v4");
+            register_tokenizers(&index);
+            let mut writer = index
+                .writer_with_num_threads(1, 15_000_000)
+                .expect("writer");
+            let mut doc = TantivyDocument::default();
+            doc.add_u64(schema.get_field("doc_id").unwrap(), 3);
+            doc.add_text(schema.get_field("tool_name").unwrap(), "apply_patch");
+            doc.add_text(schema.get_field("tool_input").unwrap(), patch);
+            writer.add_document(doc).expect("add");
+            writer.commit().expect("commit");
+            std::fs::write(v4.join(SCHEMA_VERSION_FILE), "4").expect("version");
+        }
+        let upgraded = SearchIndex::open_or_create(&v4).expect("upgrade");
+        assert_eq!(search(&upgraded, "edited:index.rs"), vec![3]);
+    }
+
+    #[test]
+    fn cmd_filter_matches_words_of_shell_commands() {
+        let options = |query: &str| {
+            let (query, filters) = extract_query_filters(query);
+            QueryOptions {
+                query,
+                project: None,
+                role: None,
+                tool: None,
+                session_id: None,
+                source: None,
+                since: None,
+                until: None,
+                as_of: None,
+                settings: filters.settings,
+                has: filters.has,
+                edited: filters.edited,
+                commands: filters.commands,
+                files: filters.files,
+                langs: filters.langs,
+                errors: filters.errors,
+                require_all_terms: false,
+                fuzzy_distance: 0,
+                tuning: QueryTuning::default(),
+                limit: 10,
+            }
+        };
+        let search = |index: &SearchIndex, query: &str| {
+            index
+                .search(&options(query))
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record.doc_id)
+                .collect::<Vec<_>>()
+        };
+        let bash = r#"{"command":"docker-compose up -d","description":"Start services"}"#;
+
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut call = test_record(1, "s1");
+        call.role = "tool_use".to_string();
+        call.tool_name = Some("Bash".to_string());
+        call.tool_input = Some(bash.to_string());
+        index.add_record(&mut writer, &call).expect("add");
+        index
+            .add_record(&mut writer, &test_record(2, "s1"))
+            .expect("add");
+        writer.commit().expect("commit");
+        assert_eq!(search(&index, "cmd:docker"), vec![1]);
+        assert_eq!(search(&index, "cmd:Docker-Compose hello"), vec![1]);
+        assert!(search(&index, "cmd:services").is_empty());
+        let with_commands = index.records_with_commands(&options("")).expect("records");
+        assert_eq!(with_commands.len(), 1);
+        assert_eq!(with_commands[0].doc_id, 1);
+
+        // v5 indexes get commands filled in, and keep their edited paths.
+        let v5 = tmp.path().join("v5");
+        std::fs::create_dir_all(&v5).expect("v5 dir");
+        {
+            let schema = build_schema().expect("schema");
+            let index = Index::create_in_dir(&v5, schema.clone()).expect("create v5");
+            register_tokenizers(&index);
+            let mut writer = index
+                .writer_with_num_threads(1, 15_000_000)
+                .expect("writer");
+            let mut doc = TantivyDocument::default();
+            doc.add_u64(schema.get_field("doc_id").unwrap(), 3);
+            doc.add_text(schema.get_field("tool_name").unwrap(), "Bash");
+            doc.add_text(schema.get_field("tool_input").unwrap(), bash);
+            writer.add_document(doc).expect("add");
+            let mut doc = TantivyDocument::default();
+            doc.add_u64(schema.get_field("doc_id").unwrap(), 4);
+            doc.add_text(schema.get_field("tool_name").unwrap(), "apply_patch");
+            doc.add_text(
+                schema.get_field("tool_input").unwrap(),
+                "*** Begin Patch\n*** Delete File: old.rs\n*** End Patch",
+            );
+            writer.add_document(doc).expect("add");
+            writer.commit().expect("commit");
+            std::fs::write(v5.join(SCHEMA_VERSION_FILE), "5").expect("version");
+        }
+        let upgraded = SearchIndex::open_or_create(&v5).expect("upgrade");
+        assert_eq!(search(&upgraded, "cmd:compose"), vec![3]);
+        assert_eq!(search(&upgraded, "edited:old.rs"), vec![4]);
+    }
+
+    #[test]
+    fn file_filter_matches_mentioned_paths() {
+        let options = |query: &str| {
+            let (query, filters) = extract_query_filters(query);
+            QueryOptions {
+                query,
+                project: None,
+                role: None,
+                tool: None,
+                session_id: None,
+                source: None,
+                since: None,
+                until: None,
+                as_of: None,
+                settings: filters.settings,
+                has: filters.has,
+                edited: filters.edited,
+                commands: filters.commands,
+                files: filters.files,
+                langs: filters.langs,
+                errors: filters.errors,
+                require_all_terms: false,
+                fuzzy_distance: 0,
+                tuning: QueryTuning::default(),
+                limit: 10,
+            }
+        };
+        let search = |index: &SearchIndex, query: &str| {
+            let mut ids: Vec<u64> = index
+                .search(&options(query))
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record.doc_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let read = r#"{"file_path":"/repo/src/index.rs"}"#;
+
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut mention = test_record(1, "s1");
+        mention.text = "hello, the panic is in src/index.rs:88".to_string();
+        let mut call = test_record(2, "s1");
+        call.role = "tool_use".to_string();
+        call.tool_name = Some("Read".to_string());
+        call.tool_input = Some(read.to_string());
+        call.text = read.to_string();
+        let mut listing = test_record(3, "s1");
+        listing.role = "tool_result".to_string();
+        listing.text = "hello\nsrc/index.rs\nsrc/cli.rs".to_string();
+        for record in [&mention, &call, &listing] {
+            index.add_record(&mut writer, record).expect("add");
+        }
+        writer.commit().expect("commit");
+        assert_eq!(search(&index, "file:index.rs"), vec![1, 2]);
+        assert_eq!(search(&index, "file:./src/index.rs hello"), vec![1]);
+        assert_eq!(search(&index, "file:/repo/src/index.rs"), vec![2]);
+        assert!(search(&index, "file:cli.rs").is_empty());
+        let mentioning = index
+            .records_mentioning_files(&options("file:src/index.rs"))
+            .expect("records");
+        assert_eq!(mentioning.len(), 2);
+
+        // v6 indexes get mentioned files filled in from stored text.
+        let v6 = tmp.path().join("v6");
+        std::fs::create_dir_all(&v6).expect("v6 dir");
+        {
+            let schema = build_schema().expect("schema");
+            let index = Index::create_in_dir(&v6, schema.clone()).expect("create v6");
+            register_tokenizers(&index);
+            let mut writer = index
+                .writer_with_num_threads(1, 15_000_000)
+                .expect("writer");
+            let mut doc = TantivyDocument::default();
+            doc.add_u64(schema.get_field("doc_id").unwrap(), 4);
+            doc.add_text(schema.get_field("role").unwrap(), "assistant");
+            doc.add_text(schema.get_field("text").unwrap(), "updated Cargo.toml");
+            writer.add_document(doc).expect("add");
+            let mut doc = TantivyDocument::default();
+            doc.add_u64(schema.get_field("doc_id").unwrap(), 5);
+            doc.add_text(schema.get_field("tool_name").unwrap(), "Bash");
+            doc.add_text(
+                schema.get_field("tool_input").unwrap(),
+                r#"{"command":"cargo build"}"#,
+            );
+            writer.add_document(doc).expect("add");
+            writer.commit().expect("commit");
+            std::fs::write(v6.join(SCHEMA_VERSION_FILE), "6").expect("version");
+        }
+        let upgraded = SearchIndex::open_or_create(&v6).expect("upgrade");
+        assert_eq!(search(&upgraded, "file:Cargo.toml"), vec![4]);
+        assert_eq!(search(&upgraded, "cmd:cargo"), vec![5]);
+    }
+
+    #[test]
+    fn tuning_drops_stopwords_and_boosts_roles() {
+        let mut tuning = QueryTuning {
+            stopwords: HashSet::from(["the".to_string(), "how".to_string()]),
+            min_term_length: 3,
+            role_boosts: HashMap::new(),
+        };
+        assert_eq!(
+            tuning.filter_query("How to fix the flaky deploy?"),
+            "fix flaky deploy?"
+        );
+        assert_eq!(
+            tuning.filter_query("\"the deploy\" +to code:x"),
+            "\"the deploy\" +to code:x"
+        );
+        assert_eq!(tuning.filter_query("the AND deploy"), "the AND deploy");
+        assert_eq!(tuning.filter_query("the"), "the");
+
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut user = test_record(1, "s1");
+        user.text = "why did the deploy fail".to_string();
+        let mut log = test_record(2, "s1");
+        log.role = "tool_result".to_string();
+        log.text = "deploy failed: deploy step 3 of deploy".to_string();
+        for record in [&user, &log] {
+            index.add_record(&mut writer, record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let search = |tuning: &QueryTuning| -> Vec<(f32, u64)> {
+            index
+                .search(&QueryOptions {
+                    query: "the deploy".to_string(),
+                    project: None,
+                    role: None,
+                    tool: None,
+                    session_id: None,
+                    source: None,
+                    since: None,
+                    until: None,
+                    as_of: None,
+                    settings: AgentSettings::default(),
+                    has: Vec::new(),
+                    edited: Vec::new(),
+                    commands: Vec::new(),
+                    files: Vec::new(),
+                    langs: Vec::new(),
+                    errors: Vec::new(),
+                    require_all_terms: false,
+                    fuzzy_distance: 0,
+                    tuning: tuning.clone(),
+                    limit: 10,
+                })
+                .expect("search")
+                .into_iter()
+                .map(|(score, record)| (score, record.doc_id))
+                .collect()
+        };
+
+        let plain = search(&tuning);
+        assert_eq!(plain.iter().map(|hit| hit.1).collect::<Vec<_>>(), [2, 1]);
+        tuning.role_boosts =
+            HashMap::from([("user".to_string(), 4.0), ("tool_result".to_string(), 0.5)]);
+        let boosted = search(&tuning);
+        assert_eq!(boosted.iter().map(|hit| hit.1).collect::<Vec<_>>(), [1, 2]);
+        assert!((boosted[0].0 - plain[1].0 * 4.0).abs() < 1e-4);
+        assert!((boosted[1].0 - plain[0].0 * 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn compact_merges_segments_and_purges_deleted_docs() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+
+        let mut writer = index.writer().expect("writer");
+        index
+            .add_record(&mut writer, &test_record(1, "s1"))
+            .expect("add");
+        index
+            .add_record(
+                &mut writer,
+                &Record {
+                    source_path: "b.jsonl".to_string(),
+                    ..test_record(2, "s1")
+                },
+            )
+            .expect("add");
+        writer.commit().expect("commit");
+        index
+            .add_record(
+                &mut writer,
+                &Record {
+                    source_path: "c.jsonl".to_string(),
+                    ..test_record(3, "s1")
+                },
+            )
+            .expect("add");
+        writer.commit().expect("commit");
+        index
+            .delete_by_source_path(&mut writer, "/tmp/s1.jsonl")
+            .expect("delete");
+        writer.commit().expect("commit");
+        drop(writer);
+
+        let before = index.segment_stats().expect("stats");
+        assert_eq!(before.segments, 2);
+        assert_eq!(before.alive_docs, 2);
+        assert_eq!(before.deleted_docs, 1);
+        assert_eq!(
+            index.live_doc_ids().expect("live ids"),
+            HashSet::from([2u64, 3])
+        );
+
+        index.compact().expect("compact");
+        let after = index.segment_stats().expect("stats");
+        assert_eq!(after.segments, 1);
+        assert_eq!(after.deleted_docs, 0);
+        assert_eq!(after.deleted_ratio(), 0.0);
+        assert_eq!(
+            index.live_doc_ids().expect("live ids"),
+            HashSet::from([2u64, 3])
+        );
+    }
+
+    #[test]
+    fn filtered_doc_ids_ignore_text_and_apply_metadata_filters() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let codex = |doc_id| Record {
+            source: crate::types::SourceKind::CodexSession,
+            ..test_record(doc_id, "s1")
+        };
+        let mut other_project = codex(2);
+        other_project.project = "api".to_string();
+        for record in [&codex(1), &other_project, &test_record(3, "s1"), &codex(9)] {
+            index.add_record(&mut writer, record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let mut options = QueryOptions {
+            query: "words no record contains".to_string(),
+            project: Some("memex".to_string()),
+            role: None,
+            tool: None,
+            session_id: None,
+            source: Some(crate::types::SourceFilter::Codex),
+            since: None,
+            until: Some(5),
+            as_of: None,
+            settings: AgentSettings::default(),
+            has: Vec::new(),
+            edited: Vec::new(),
+            commands: Vec::new(),
+            files: Vec::new(),
+            langs: Vec::new(),
+            errors: Vec::new(),
+            require_all_terms: false,
+            fuzzy_distance: 0,
+            tuning: QueryTuning::default(),
+            limit: 1,
+        };
+        assert!(options.has_filters());
+        assert_eq!(
+            index.filtered_doc_ids(&options).expect("filtered ids"),
+            HashSet::from([1u64])
+        );
+
+        options.project = None;
+        options.source = None;
+        options.until = None;
+        assert!(!options.has_filters());
+    }
+
+    #[test]
+    fn term_scores_break_bm25_down_by_matching_term() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("create index");
+        let mut writer = index.writer().expect("writer");
+        let mut hit = test_record(1, "s1");
+        hit.text = "retry with backoff, then retry again".to_string();
+        let mut other = test_record(2, "s1");
+        other.text = "backoff only".to_string();
+        for record in [&hit, &other] {
+            index.add_record(&mut writer, record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let options = QueryOptions {
+            query: "retry backoff timeout".to_string(),
+            project: None,
+            role: None,
+            tool: None,
+            session_id: None,
+            source: None,
+            since: None,
+            until: None,
+            as_of: None,
+            settings: AgentSettings::default(),
+            has: Vec::new(),
+            edited: Vec::new(),
+            commands: Vec::new(),
+            files: Vec::new(),
+            langs: Vec::new(),
+            errors: Vec::new(),
+            require_all_terms: false,
+            fuzzy_distance: 0,
+            tuning: QueryTuning::default(),
+            limit: 10,
+        };
+        let (bm25, _) = index
+            .search(&options)
+            .expect("search")
+            .into_iter()
+            .find(|(_, record)| record.doc_id == 1)
+            .expect("hit");
+        let terms = index.term_scores(&options, 1).expect("term scores");
+        let names: Vec<&str> = terms.iter().map(|term| term.term.as_str()).collect();
+        assert_eq!(names, vec!["retry", "backoff"]);
+        let total: f32 = terms.iter().map(|term| term.score).sum();
+        assert!((total - bm25).abs() < 1e-4);
+        assert!(index.term_scores(&options, 99).expect("missing").is_empty());
+    }
 }
@@ -1,10 +1,23 @@
-use crate::analytics::{AnalyticsStore, AnalyticsWriter, analytics_path, backfill_from_index};
+use crate::analytics::{
+    AnalyticsStore, AnalyticsWriter, analytics_path, backfill_from_index, display_project_name,
+};
+use crate::append::{AppendAdapter, append_dir};
 use crate::config::{IndexedToolContentLimits, Paths};
 use crate::embed::{EmbedRuntimeConfig, EmbedderHandle, ModelChoice};
 use crate::index::SearchIndex;
-use crate::progress::{Progress, SOURCE_COUNT};
-use crate::state::{FileState, IngestState, ScanCache};
-use crate::types::{Record, RecordLinks, SourceKind};
+use crate::ocr::Ocr;
+use crate::progress::{EmbedListener, Progress, SOURCE_COUNT};
+use crate::state::{
+    FileState, IngestErrors, IngestState, LineError, MAX_LINE_ERRORS_PER_FILE, ScanCache,
+    ingest_errors_path, project_frecency_path, record_project_visits,
+};
+use crate::todos::ClaudeTodoAdapter;
+use crate::turns::{TurnBuilder, build_turns, is_prompt, last_exchange, turn_doc_id};
+use crate::types::{
+    AgentSettings, Attachment, COMPACT_SUMMARY_ROLE, Record, RecordLinks, SUMMARY_ROLE,
+    SourceFilter, SourceKind, TURN_ROLE,
+};
+use crate::vector::{ArchiveTier, archive_vectors_dir};
 use anyhow::{Result, anyhow};
 use chrono::{DateTime, Utc};
 use crossbeam_channel::{Receiver, Sender, bounded, unbounded};
@@ -16,16 +29,30 @@ use simd_json::prelude::*;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use walkdir::WalkDir;
 
-const EMBED_BATCH_SIZE: usize = 64;
 const EMBED_MAX_CHARS: usize = 8192;
 const RETAINED_HEAD_PERCENT: usize = 75;
 const INDEX_PROGRESS_BATCH: u64 = 1;
 // Keep a small amount of parser/writer overlap without retaining an unbounded transcript backlog.
 const RECORD_CHANNEL_CAPACITY: usize = 8;
+// Entries of another transcript a record must hold to be skipped as one.
+const NESTED_TRANSCRIPT_MIN_ENTRIES: usize = 8;
+// Keys every line of a Claude or Codex session file carries; also matched
+// JSON-escaped, as in a transcript quoted inside a tool result.
+const TRANSCRIPT_ENTRY_MARKERS: &[&str] = &[
+    "\"parentUuid",
+    "\"sessionId",
+    "\"response_item",
+    "\"event_msg",
+];
+/// File in a ChatGPT data export holding every conversation.
+pub const CHATGPT_CONVERSATIONS: &str = "conversations.json";
+/// Project imported ChatGPT conversations are filed under; they have no
+/// working directory.
+pub const CHATGPT_PROJECT: &str = "chatgpt";
 const CURSOR_SUBAGENT_TURN_BASE: u32 = 1_000_000_000;
 const CURSOR_SUBAGENT_TURN_STRIDE: u32 = 50_000;
 const CURSOR_SUBAGENT_TURN_BUCKETS: u32 = 65_536;
@@ -44,6 +71,14 @@ pub struct IngestOptions {
     pub model: ModelChoice,
     pub embed_runtime: EmbedRuntimeConfig,
     pub tool_content_limits: IndexedToolContentLimits,
+    /// Threads parsing may use; all cores when unset.
+    pub max_threads: Option<usize>,
+    /// OCR pasted images with tesseract, when it is installed.
+    pub ocr_images: bool,
+    /// Gets embedding progress in place of the stderr progress bars.
+    pub embed_listener: Option<EmbedListener>,
+    /// Where old records are embedded instead of with `model`, if anywhere.
+    pub archive_tier: Option<ArchiveTier>,
 }
 
 #[derive(Debug)]
@@ -52,16 +87,166 @@ pub struct IngestReport {
     pub records_embedded: usize,
     pub files_scanned: usize,
     pub files_skipped: usize,
+    /// Records skipped for exceeding `max_indexed_record_bytes`.
+    pub records_too_large: usize,
+    /// Records skipped as transcripts of other sessions.
+    pub nested_transcripts: usize,
+    /// Source lines skipped as malformed, listed by `memex ingest-errors`.
+    pub malformed_lines: usize,
+    /// What the scan decided for each file it found.
+    pub scan: Vec<FileDecision>,
+}
+
+/// What a scan decided for one file, from its entry in the ingest state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanDecision {
+    /// No ingest state entry: read from the start.
+    New,
+    /// Same size and mtime as its entry: skipped.
+    Unchanged(FileState),
+    /// Grew since its entry: read on from the offset there.
+    Appended(FileState),
+    /// Smaller, or an older mtime, than its entry: its records are replaced.
+    Truncated(FileState),
+    /// Changed, and its source only reads whole files: its records are
+    /// replaced.
+    Rewritten(FileState),
+    /// Outside a partial reindex: skipped.
+    OutOfScope,
+    /// Inside a partial reindex: its records are replaced.
+    Reindexed,
+}
+
+impl ScanDecision {
+    fn decide(prev: Option<&FileState>, size: u64, mtime: i64, resumable: bool) -> Self {
+        match prev {
+            None => Self::New,
+            Some(prev) if size == prev.size && mtime == prev.mtime => Self::Unchanged(prev.clone()),
+            Some(prev) if size < prev.size || mtime < prev.mtime => Self::Truncated(prev.clone()),
+            Some(prev) if !resumable => Self::Rewritten(prev.clone()),
+            Some(prev) => Self::Appended(prev.clone()),
+        }
+    }
+
+    pub fn skipped(&self) -> bool {
+        matches!(self, Self::Unchanged(_) | Self::OutOfScope)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Unchanged(_) => "unchanged",
+            Self::Appended(_) => "appended",
+            Self::Truncated(_) => "truncated",
+            Self::Rewritten(_) => "rewritten",
+            Self::OutOfScope => "out of scope",
+            Self::Reindexed => "reindexed",
+        }
+    }
+
+    /// Byte offset and turn the file is read from, and whether its records
+    /// are deleted first.
+    fn read_from(&self) -> (u64, u32, bool) {
+        match self {
+            Self::Appended(prev) | Self::Unchanged(prev) => (prev.offset, prev.turn_id, false),
+            Self::New => (0, 0, false),
+            Self::Truncated(_) | Self::Rewritten(_) | Self::OutOfScope | Self::Reindexed => {
+                (0, 0, true)
+            }
+        }
+    }
+}
+
+/// A file a scan found and what it decided to do with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDecision {
+    pub path: PathBuf,
+    pub source: SourceKind,
+    pub size: u64,
+    /// Unix seconds.
+    pub mtime: i64,
+    pub decision: ScanDecision,
+}
+
+impl FileDecision {
+    /// Why the scan decided what it did, naming the ingest state it
+    /// compared against.
+    pub fn reason(&self) -> String {
+        let (size, mtime) = (self.size, self.mtime);
+        match &self.decision {
+            ScanDecision::New => {
+                "no entry in the ingest state (ingest.json): read from the start".to_string()
+            }
+            ScanDecision::Unchanged(prev) => format!(
+                "size {} and mtime {} match its ingest.json entry: skipped as unchanged",
+                prev.size, prev.mtime
+            ),
+            ScanDecision::Appended(prev) => format!(
+                "grew from {} to {size} bytes since its ingest.json entry: \
+                 read on from byte {}, turn {}",
+                prev.size, prev.offset, prev.turn_id
+            ),
+            ScanDecision::Truncated(prev) => format!(
+                "size {size} and mtime {mtime} against {} and {} in its ingest.json entry: \
+                 shrank or went back in time, so its records are replaced",
+                prev.size, prev.mtime
+            ),
+            ScanDecision::Rewritten(prev) => format!(
+                "size {size} and mtime {mtime} against {} and {} in its ingest.json entry, \
+                 and {} files are only read whole: its records are replaced",
+                prev.size,
+                prev.mtime,
+                self.source.label()
+            ),
+            ScanDecision::OutOfScope => "outside the partial reindex: skipped".to_string(),
+            ScanDecision::Reindexed => {
+                "inside the partial reindex: read again from the start".to_string()
+            }
+        }
+    }
+}
+
+/// The source files a partial `memex reindex` re-reads from the start. Their
+/// records are replaced; every other file keeps its records and embeddings.
+#[derive(Debug, Clone, Default)]
+pub struct ReindexScope {
+    pub source: Option<SourceFilter>,
+    /// Matched against the full path of each source file.
+    pub path: Option<glob::Pattern>,
+}
+
+impl ReindexScope {
+    fn matches(&self, source: SourceKind, path: &Path) -> bool {
+        self.source.is_none_or(|filter| filter.matches(source))
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|pattern| pattern.matches_path(path))
+    }
+}
+
+/// A one-shot bulk import of old transcripts from `memex import-archive`.
+#[derive(Debug, Clone)]
+pub struct ArchiveImport {
+    /// Directory searched for Claude `.jsonl` sessions and Codex
+    /// `rollout-*.jsonl` files.
+    pub dir: PathBuf,
+    /// Index writer memory budget; more means fewer, larger segments.
+    pub writer_heap_bytes: usize,
 }
 
+/// A transcript file queued for parsing, resuming where the last ingest of
+/// it stopped.
 #[derive(Debug)]
-struct FileTask {
-    path: PathBuf,
-    source: SourceKind,
-    offset: u64,
-    turn_id: u32,
-    size: u64,
-    mtime: i64,
+pub struct FileTask {
+    pub path: PathBuf,
+    pub source: SourceKind,
+    /// Byte offset already indexed; zero for new or rewritten files.
+    pub offset: u64,
+    /// Next turn id to hand out.
+    pub turn_id: u32,
+    pub size: u64,
+    pub mtime: i64,
     delete_first: bool,
 }
 
@@ -70,24 +255,121 @@ struct FileUpdate {
     path: String,
     state: FileState,
     session_id: Option<String>,
+    malformed: MalformedLines,
+}
+
+/// Lines of one file that failed to parse and were skipped. Line numbers
+/// are only counted when a line fails, from where the last count stopped,
+/// since resumed files start mid-way and bad lines are rare.
+#[derive(Debug, Default)]
+struct MalformedLines {
+    counted_to: usize,
+    lines_before: u64,
+    total: usize,
+    lines: Vec<LineError>,
+}
+
+impl MalformedLines {
+    /// Notes the line starting at byte `line_start` of `data`.
+    fn push(&mut self, data: &[u8], line_start: usize, error: impl std::fmt::Display) {
+        self.total += 1;
+        if self.lines.len() >= MAX_LINE_ERRORS_PER_FILE {
+            return;
+        }
+        self.lines_before +=
+            memchr::memchr_iter(b'\n', &data[self.counted_to..line_start]).count() as u64;
+        self.counted_to = line_start;
+        self.lines.push(LineError {
+            line: self.lines_before + 1,
+            error: error.to_string(),
+        });
+    }
+}
+
+/// Records `RecordSender` dropped instead of queueing.
+#[derive(Debug, Default)]
+struct SkippedRecords {
+    too_large: AtomicUsize,
+    nested_transcripts: AtomicUsize,
 }
 
 #[derive(Clone)]
 struct RecordSender {
     sender: Sender<Record>,
     limits: IndexedToolContentLimits,
+    skipped: Arc<SkippedRecords>,
 }
 
 impl RecordSender {
     fn new(sender: Sender<Record>, limits: IndexedToolContentLimits) -> Self {
-        Self { sender, limits }
+        Self {
+            sender,
+            limits,
+            skipped: Arc::default(),
+        }
     }
 
     fn send(&self, mut record: Record) -> Result<()> {
+        if self
+            .limits
+            .record_bytes
+            .is_some_and(|max_bytes| record_bytes(&record) > max_bytes)
+        {
+            self.skipped.too_large.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        if self.limits.skip_nested_transcripts && is_nested_transcript(&record) {
+            self.skipped
+                .nested_transcripts
+                .fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
         limit_record_tool_content(&mut record, self.limits);
         self.sender.send(record)?;
         Ok(())
     }
+
+    /// Records skipped so far: too large, and nested transcripts.
+    fn skipped(&self) -> (usize, usize) {
+        (
+            self.skipped.too_large.load(Ordering::Relaxed),
+            self.skipped.nested_transcripts.load(Ordering::Relaxed),
+        )
+    }
+}
+
+fn record_bytes(record: &Record) -> usize {
+    record.text.len()
+        + record.tool_input.as_ref().map_or(0, String::len)
+        + record.tool_output.as_ref().map_or(0, String::len)
+}
+
+/// True when the record's text or tool content is mostly lines of another
+/// agent transcript, like a session file read or pasted into this one.
+fn is_nested_transcript(record: &Record) -> bool {
+    [
+        Some(&record.text),
+        record.tool_input.as_ref(),
+        record.tool_output.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|text| looks_like_transcript(text))
+}
+
+fn looks_like_transcript(text: &str) -> bool {
+    if text.len() < NESTED_TRANSCRIPT_MIN_ENTRIES * 32 {
+        return false;
+    }
+    let entries = TRANSCRIPT_ENTRY_MARKERS
+        .iter()
+        .map(|marker| text.matches(marker).count())
+        .max()
+        .unwrap_or(0);
+    // An escaped transcript can be a single line; otherwise most lines
+    // must be entries, so prose that mentions the keys is kept.
+    entries >= NESTED_TRANSCRIPT_MIN_ENTRIES
+        && entries * 2 >= text.lines().filter(|line| !line.trim().is_empty()).count()
 }
 
 struct WriterContext {
@@ -95,16 +377,396 @@ struct WriterContext {
     do_backfill_embeddings: bool,
     vector_dir: PathBuf,
     analytics_path: PathBuf,
+    frecency_path: PathBuf,
     progress: Arc<Progress>,
     model: ModelChoice,
     embed_runtime: EmbedRuntimeConfig,
     tool_content_limits: IndexedToolContentLimits,
+    /// Set for bulk imports: the writer heap, with merges deferred to one at
+    /// the end and embeddings on a pipeline thread.
+    bulk_heap_bytes: Option<usize>,
+    archive_tier: Option<ArchiveTier>,
 }
 
 fn record_channel() -> (Sender<Record>, Receiver<Record>) {
     bounded(RECORD_CHANNEL_CAPACITY)
 }
 
+/// One agent's history on disk: where its transcripts live and how to turn
+/// them into records. The built-in sources are adapters too; downstream
+/// crates add their own with [`register_adapter`].
+pub trait SourceAdapter: Send + Sync {
+    /// Source every record from this adapter is tagged with; adapters outside
+    /// memex use [`SourceKind::External`] with a name of their own, which
+    /// labels their records wherever a source is shown.
+    fn kind(&self) -> SourceKind;
+
+    /// Whether `options` turn this source on.
+    fn enabled(&self, _options: &IngestOptions) -> bool {
+        true
+    }
+
+    /// Transcript files to consider. Unchanged files are skipped afterwards
+    /// by size and mtime.
+    fn scan(&self, options: &IngestOptions) -> Result<Vec<PathBuf>>;
+
+    /// Parses `task.path` from `task.offset`, sending each record and then
+    /// [`ParseContext::finish_file`].
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()>;
+
+    /// Session id of a transcript when its path alone determines it.
+    fn session_id(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    /// Project of a transcript when its path alone determines it.
+    fn project(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    /// Whether a grown file is read on from where the last ingest stopped.
+    /// Sources whose files are rewritten whole return false, so any change
+    /// reindexes the file.
+    fn resumable(&self) -> bool {
+        true
+    }
+}
+
+static EXTERNAL_ADAPTERS: Mutex<Vec<Arc<dyn SourceAdapter>>> = Mutex::new(Vec::new());
+
+/// Adds `adapter` to every later ingest in this process, after the built-in
+/// sources. Its kind must be [`SourceKind::External`] with a name no other
+/// source uses.
+pub fn register_adapter(adapter: Arc<dyn SourceAdapter>) -> Result<()> {
+    let kind = adapter.kind();
+    let SourceKind::External(name) = kind else {
+        return Err(anyhow!(
+            "{} is a built-in source; adapters use SourceKind::External",
+            kind.label()
+        ));
+    };
+    if kind == SourceKind::APPENDED || SourceKind::from_label(name) != Some(kind) {
+        return Err(anyhow!("source name {name:?} is reserved"));
+    }
+    let mut adapters = EXTERNAL_ADAPTERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if adapters.iter().any(|adapter| adapter.kind() == kind) {
+        return Err(anyhow!("an adapter named {name:?} is already registered"));
+    }
+    adapters.push(adapter);
+    Ok(())
+}
+
+/// Built-in adapters in scan order, followed by registered ones.
+fn source_adapters(paths: &Paths) -> Vec<Arc<dyn SourceAdapter>> {
+    let mut adapters: Vec<Arc<dyn SourceAdapter>> = vec![
+        Arc::new(ClaudeAdapter),
+        Arc::new(ClaudeTodoAdapter::default()),
+        Arc::new(CodexSessionAdapter),
+        Arc::new(CodexHistoryAdapter),
+        Arc::new(OpencodeAdapter),
+        Arc::new(CursorAdapter),
+        Arc::new(PiAdapter),
+        Arc::new(CopilotAdapter),
+        Arc::new(ChatGptAdapter {
+            dir: chatgpt_import_dir(paths),
+        }),
+        Arc::new(AppendAdapter {
+            dir: append_dir(paths),
+        }),
+    ];
+    adapters.extend(
+        EXTERNAL_ADAPTERS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .cloned(),
+    );
+    adapters
+}
+
+/// What a [`SourceAdapter`] reports into while parsing one file.
+pub struct ParseContext<'a> {
+    records: &'a RecordSender,
+    updates: &'a Sender<FileUpdate>,
+    next_doc_id: &'a AtomicU64,
+    progress: &'a Arc<Progress>,
+    /// Session ids reported by adapters during the scan.
+    session_ids: &'a HashSet<String>,
+    opencode_session_links: &'a HashMap<String, SessionLinks>,
+    ocr: Option<&'a Ocr>,
+}
+
+impl ParseContext<'_> {
+    /// Unique id for the next record.
+    pub fn next_doc_id(&self) -> u64 {
+        self.next_doc_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub fn send(&self, record: Record) -> Result<()> {
+        // A summary titles its session and is never indexed.
+        if record.role != SUMMARY_ROLE {
+            self.progress.add_produced(record.source, 1);
+        }
+        self.records.send(record)
+    }
+
+    pub fn add_parsed_bytes(&self, source: SourceKind, bytes: u64) {
+        self.progress.add_parsed_bytes(source, bytes);
+    }
+
+    /// Marks `task` done, recording how far it was read so the next ingest
+    /// resumes from `offset` with `turn_id`.
+    pub fn finish_file(&self, task: &FileTask, offset: u64, turn_id: u32) -> Result<()> {
+        self.progress.add_files_done(task.source, 1);
+        self.updates.send(FileUpdate {
+            path: task.path.to_string_lossy().to_string(),
+            state: FileState {
+                size: task.size,
+                mtime: task.mtime,
+                offset,
+                turn_id,
+            },
+            session_id: None,
+            malformed: MalformedLines::default(),
+        })?;
+        Ok(())
+    }
+}
+
+struct ClaudeAdapter;
+
+impl SourceAdapter for ClaudeAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Claude
+    }
+
+    fn scan(&self, options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        if !options.claude_source.exists() {
+            return Ok(Vec::new());
+        }
+        collect_claude_files(&options.claude_source, options.include_agents)
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        parse_claude_file(
+            task,
+            ctx.records,
+            ctx.updates,
+            ctx.next_doc_id,
+            ctx.progress,
+            ctx.ocr,
+        )
+    }
+
+    fn project(&self, path: &Path) -> Option<String> {
+        Some(project_from_claude_path(path))
+    }
+}
+
+struct CodexSessionAdapter;
+
+impl SourceAdapter for CodexSessionAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::CodexSession
+    }
+
+    fn enabled(&self, options: &IngestOptions) -> bool {
+        options.include_codex
+    }
+
+    fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        collect_codex_session_files()
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        parse_codex_session(
+            task,
+            ctx.records,
+            ctx.updates,
+            ctx.next_doc_id,
+            ctx.progress,
+        )
+    }
+
+    fn session_id(&self, path: &Path) -> Option<String> {
+        session_id_from_filename(path)
+    }
+}
+
+/// `~/.codex/history.jsonl`: prompts from sessions whose rollout files are
+/// gone, so entries for any scanned session are skipped.
+struct CodexHistoryAdapter;
+
+impl SourceAdapter for CodexHistoryAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::CodexHistory
+    }
+
+    fn enabled(&self, options: &IngestOptions) -> bool {
+        options.include_codex
+    }
+
+    fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        let history_path = codex_history_path();
+        Ok(if history_path.exists() {
+            vec![history_path]
+        } else {
+            Vec::new()
+        })
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        parse_codex_history(
+            task,
+            ctx.records,
+            ctx.updates,
+            ctx.next_doc_id,
+            ctx.session_ids,
+            ctx.progress,
+        )
+    }
+}
+
+struct OpencodeAdapter;
+
+impl SourceAdapter for OpencodeAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Opencode
+    }
+
+    fn enabled(&self, options: &IngestOptions) -> bool {
+        options.include_opencode
+    }
+
+    fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        collect_opencode_files()
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        parse_opencode_file(
+            task,
+            ctx.records,
+            ctx.updates,
+            ctx.next_doc_id,
+            ctx.progress,
+            ctx.opencode_session_links,
+        )
+    }
+}
+
+struct CursorAdapter;
+
+impl SourceAdapter for CursorAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Cursor
+    }
+
+    fn enabled(&self, options: &IngestOptions) -> bool {
+        options.include_cursor
+    }
+
+    fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        collect_cursor_files()
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        parse_cursor_file(
+            task,
+            ctx.records,
+            ctx.updates,
+            ctx.next_doc_id,
+            ctx.progress,
+        )
+    }
+}
+
+struct PiAdapter;
+
+impl SourceAdapter for PiAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Pi
+    }
+
+    fn enabled(&self, options: &IngestOptions) -> bool {
+        options.include_pi
+    }
+
+    fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        collect_pi_files()
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        parse_pi_file(
+            task,
+            ctx.records,
+            ctx.updates,
+            ctx.next_doc_id,
+            ctx.progress,
+        )
+    }
+}
+
+struct CopilotAdapter;
+
+impl SourceAdapter for CopilotAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Copilot
+    }
+
+    fn enabled(&self, options: &IngestOptions) -> bool {
+        options.include_copilot
+    }
+
+    fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        collect_copilot_files()
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        parse_copilot_session(
+            task,
+            ctx.records,
+            ctx.updates,
+            ctx.next_doc_id,
+            ctx.progress,
+        )
+    }
+}
+
+/// Conversations from the last ChatGPT data export [`import_chatgpt`]
+/// staged in `dir`.
+struct ChatGptAdapter {
+    dir: PathBuf,
+}
+
+impl SourceAdapter for ChatGptAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::ChatGPT
+    }
+
+    fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        let path = self.dir.join(CHATGPT_CONVERSATIONS);
+        Ok(if path.exists() {
+            vec![path]
+        } else {
+            Vec::new()
+        })
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        parse_chatgpt_export(task, ctx)
+    }
+
+    fn project(&self, _path: &Path) -> Option<String> {
+        Some(CHATGPT_PROJECT.to_string())
+    }
+
+    fn resumable(&self) -> bool {
+        false
+    }
+}
+
 /// Check if scan cache is fresh and vector state is usable; if so, skip indexing entirely.
 /// Returns Ok(None) if skipped due to fresh cache, Ok(Some(report)) if indexing ran.
 pub fn ingest_if_stale(
@@ -128,6 +790,131 @@ pub fn ingest_all(
     paths: &Paths,
     index: &SearchIndex,
     options: &IngestOptions,
+) -> Result<IngestReport> {
+    ingest_files(paths, index, options, None, None, None)
+}
+
+/// Rebuilds only the files in `scope`, leaving the rest of the index alone.
+pub fn reindex_scoped(
+    paths: &Paths,
+    index: &SearchIndex,
+    options: &IngestOptions,
+    scope: &ReindexScope,
+) -> Result<IngestReport> {
+    ingest_files(paths, index, options, Some(scope), None, None)
+}
+
+/// Reads on in just `files`, all from the adapter for `kind`, resuming each
+/// where the last ingest stopped. Nothing else is scanned, so the scan cache
+/// is left alone.
+pub fn ingest_paths(
+    paths: &Paths,
+    index: &SearchIndex,
+    options: &IngestOptions,
+    kind: SourceKind,
+    files: &[PathBuf],
+) -> Result<IngestReport> {
+    ingest_files(paths, index, options, None, None, Some((kind, files)))
+}
+
+/// Imports the transcripts under `archive.dir` in one pass tuned for
+/// throughput: a larger writer heap, no segment merges or commits until the
+/// end, embeddings computed on their own thread while indexing continues,
+/// then a single commit and a merge into one segment. Files already
+/// imported and unchanged are skipped, as in a normal ingest.
+pub fn import_archive(
+    paths: &Paths,
+    index: &SearchIndex,
+    options: &IngestOptions,
+    archive: &ArchiveImport,
+) -> Result<IngestReport> {
+    if !archive.dir.is_dir() {
+        return Err(anyhow!("{} is not a directory", archive.dir.display()));
+    }
+    ingest_files(paths, index, options, None, Some(archive), None)
+}
+
+/// Files memex can't read again from anywhere else: imported exports and
+/// `memex append` logs. A full reindex keeps them.
+pub fn imports_dir(paths: &Paths) -> PathBuf {
+    paths.root.join("imports")
+}
+
+/// Where [`import_chatgpt`] keeps the imported `conversations.json`. The
+/// path is fixed so importing a newer export replaces the last one.
+pub fn chatgpt_import_dir(paths: &Paths) -> PathBuf {
+    imports_dir(paths).join("chatgpt")
+}
+
+/// Imports a ChatGPT data export: the `.zip` OpenAI sends, the directory it
+/// unpacks to, or its `conversations.json`. Each conversation becomes a
+/// session. Every export holds the whole account, so a newer one replaces
+/// what the last import indexed and an unchanged one is skipped.
+pub fn import_chatgpt(
+    paths: &Paths,
+    index: &SearchIndex,
+    options: &IngestOptions,
+    export: &Path,
+    writer_heap_bytes: usize,
+) -> Result<IngestReport> {
+    let contents = read_chatgpt_export(export)?;
+    serde_json::from_slice::<Vec<serde::de::IgnoredAny>>(&contents).map_err(|err| {
+        anyhow!(
+            "{} is not a ChatGPT conversations.json: {err}",
+            export.display()
+        )
+    })?;
+    let dir = chatgpt_import_dir(paths);
+    std::fs::create_dir_all(&dir)?;
+    let target = dir.join(CHATGPT_CONVERSATIONS);
+    // Left alone when unchanged, so the import sees the same size and mtime.
+    if std::fs::read(&target).ok().as_deref() != Some(contents.as_slice()) {
+        let staged = dir.join(format!("{CHATGPT_CONVERSATIONS}.tmp"));
+        std::fs::write(&staged, &contents)?;
+        std::fs::rename(&staged, &target)?;
+    }
+    import_archive(
+        paths,
+        index,
+        options,
+        &ArchiveImport {
+            dir,
+            writer_heap_bytes,
+        },
+    )
+}
+
+fn read_chatgpt_export(export: &Path) -> Result<Vec<u8>> {
+    if export.is_dir() {
+        let path = export.join(CHATGPT_CONVERSATIONS);
+        return std::fs::read(&path)
+            .map_err(|err| anyhow!("failed to read {}: {err}", path.display()));
+    }
+    if export.extension().is_some_and(|ext| ext == "zip") {
+        let output = std::process::Command::new("unzip")
+            .arg("-p")
+            .arg(export)
+            .arg(CHATGPT_CONVERSATIONS)
+            .output()
+            .map_err(|err| anyhow!("failed to run unzip; is it installed? {err}"))?;
+        if !output.status.success() || output.stdout.is_empty() {
+            return Err(anyhow!(
+                "no {CHATGPT_CONVERSATIONS} in {}",
+                export.display()
+            ));
+        }
+        return Ok(output.stdout);
+    }
+    std::fs::read(export).map_err(|err| anyhow!("failed to read {}: {err}", export.display()))
+}
+
+fn ingest_files(
+    paths: &Paths,
+    index: &SearchIndex,
+    options: &IngestOptions,
+    scope: Option<&ReindexScope>,
+    archive: Option<&ArchiveImport>,
+    files: Option<(SourceKind, &[PathBuf])>,
 ) -> Result<IngestReport> {
     // Apply additive analytics migrations even when the scan finds no changed files.
     drop(AnalyticsStore::open(analytics_path(&paths.state))?);
@@ -140,326 +927,74 @@ pub fn ingest_all(
             std::fs::create_dir_all(&paths.vectors)?;
         }
     }
+    if index.count_roles(&[TURN_ROLE])? == 0 && index.count_roles(&["user"])? > 0 {
+        backfill_turns(index)?;
+    }
     let next_doc_id = Arc::new(AtomicU64::new(state.next_doc_id));
 
+    let adapters = source_adapters(paths);
     let mut tasks = Vec::new();
     let mut files_scanned = 0usize;
     let mut files_skipped = 0usize;
     let mut total_bytes = 0u64;
+    let mut session_ids = HashSet::new();
 
-    if options.claude_source.exists() {
-        let claude_files = collect_claude_files(&options.claude_source, options.include_agents)?;
-        for path in claude_files {
-            let meta = path.metadata()?;
-            let size = meta.len();
-            let mtime = meta
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
-            files_scanned += 1;
-            total_bytes += size;
-            let key = path.to_string_lossy().to_string();
-            let prev = state.files.get(&key);
-            let (offset, turn_id, delete_first, skip) = match prev {
-                None => (0, 0, false, false),
-                Some(prev) => {
-                    if size < prev.size || mtime < prev.mtime {
-                        (0, 0, true, false)
-                    } else if size == prev.size && mtime == prev.mtime {
-                        (prev.offset, prev.turn_id, false, true)
-                    } else {
-                        (prev.offset, prev.turn_id, false, false)
-                    }
-                }
-            };
-            if skip {
-                files_skipped += 1;
-                continue;
-            }
-            tasks.push(FileTask {
-                path,
-                source: SourceKind::Claude,
-                offset,
-                turn_id,
-                size,
-                mtime,
-                delete_first,
-            });
-        }
-    }
-
-    let mut session_ids = HashSet::new();
-    if options.include_codex {
-        let codex_files = collect_codex_session_files()?;
-        for path in codex_files {
-            if let Some(id) = session_id_from_filename(&path) {
-                session_ids.insert(id);
-            }
-            let meta = path.metadata()?;
-            let size = meta.len();
-            let mtime = meta
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
-            files_scanned += 1;
-            total_bytes += size;
-            let key = path.to_string_lossy().to_string();
-            let prev = state.files.get(&key);
-            let (offset, turn_id, delete_first, skip) = match prev {
-                None => (0, 0, false, false),
-                Some(prev) => {
-                    if size < prev.size || mtime < prev.mtime {
-                        (0, 0, true, false)
-                    } else if size == prev.size && mtime == prev.mtime {
-                        (prev.offset, prev.turn_id, false, true)
-                    } else {
-                        (prev.offset, prev.turn_id, false, false)
-                    }
-                }
-            };
-            if skip {
-                files_skipped += 1;
-                continue;
-            }
-            tasks.push(FileTask {
-                path,
-                source: SourceKind::CodexSession,
-                offset,
-                turn_id,
-                size,
-                mtime,
-                delete_first,
-            });
-        }
-    }
-
-    if options.include_codex {
-        let history_path = codex_history_path();
-        if history_path.exists() {
-            let meta = history_path.metadata()?;
-            let size = meta.len();
-            let mtime = meta
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
-            files_scanned += 1;
-            total_bytes += size;
-            let key = history_path.to_string_lossy().to_string();
-            let prev = state.files.get(&key);
-            let (offset, turn_id, delete_first, skip) = match prev {
-                None => (0, 0, false, false),
-                Some(prev) => {
-                    if size < prev.size || mtime < prev.mtime {
-                        (0, 0, true, false)
-                    } else if size == prev.size && mtime == prev.mtime {
-                        (prev.offset, prev.turn_id, false, true)
-                    } else {
-                        (prev.offset, prev.turn_id, false, false)
-                    }
-                }
-            };
-            if skip {
-                files_skipped += 1;
-            } else {
-                tasks.push(FileTask {
-                    path: history_path,
-                    source: SourceKind::CodexHistory,
-                    offset,
-                    turn_id,
-                    size,
-                    mtime,
-                    delete_first,
-                });
-            }
-        }
-    }
-
-    if options.include_opencode {
-        let opencode_files = collect_opencode_files()?;
-        for path in opencode_files {
-            let meta = path.metadata()?;
-            let size = meta.len();
-            let mtime = meta
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
-            files_scanned += 1;
-            total_bytes += size;
-            let key = path.to_string_lossy().to_string();
-            let prev = state.files.get(&key);
-            let (offset, turn_id, delete_first, skip) = match prev {
-                None => (0, 0, false, false),
-                Some(prev) => {
-                    if size < prev.size || mtime < prev.mtime {
-                        (0, 0, true, false)
-                    } else if size == prev.size && mtime == prev.mtime {
-                        (prev.offset, prev.turn_id, false, true)
-                    } else {
-                        (prev.offset, prev.turn_id, false, false)
-                    }
-                }
-            };
-            if skip {
-                files_skipped += 1;
-                continue;
-            }
-            tasks.push(FileTask {
-                path,
-                source: SourceKind::Opencode,
-                offset,
-                turn_id,
-                size,
-                mtime,
-                delete_first,
-            });
+    let candidates = match (archive, files) {
+        (Some(archive), _) => archive_files(&adapters, &archive.dir, options.include_agents)?,
+        (None, Some((kind, files))) => {
+            let adapter_idx = adapters
+                .iter()
+                .position(|adapter| adapter.kind() == kind)
+                .ok_or_else(|| anyhow!("no adapter for {}", kind.label()))?;
+            files
+                .iter()
+                .filter(|path| path.exists())
+                .map(|path| (adapter_idx, path.clone()))
+                .collect()
         }
-    }
-
-    if options.include_cursor {
-        let cursor_files = collect_cursor_files()?;
-        for path in cursor_files {
-            let meta = path.metadata()?;
-            let size = meta.len();
-            let mtime = meta
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
-            files_scanned += 1;
-            total_bytes += size;
-            let key = path.to_string_lossy().to_string();
-            let prev = state.files.get(&key);
-            let (offset, turn_id, delete_first, skip) = match prev {
-                None => (0, 0, false, false),
-                Some(prev) => {
-                    if size < prev.size || mtime < prev.mtime {
-                        (0, 0, true, false)
-                    } else if size == prev.size && mtime == prev.mtime {
-                        (prev.offset, prev.turn_id, false, true)
-                    } else {
-                        (prev.offset, prev.turn_id, false, false)
-                    }
-                }
-            };
-            if skip {
-                files_skipped += 1;
-                continue;
-            }
-            tasks.push(FileTask {
-                path,
-                source: SourceKind::Cursor,
-                offset,
-                turn_id,
-                size,
-                mtime,
-                delete_first,
-            });
+        (None, None) => scan_candidates(&adapters, options)?,
+    };
+    let mut scan = Vec::with_capacity(candidates.len());
+    for (adapter_idx, path) in candidates {
+        let adapter = &adapters[adapter_idx];
+        if let Some(id) = adapter.session_id(&path) {
+            session_ids.insert(id);
         }
-    }
-
-    if options.include_pi {
-        let pi_files = collect_pi_files()?;
-        for path in pi_files {
-            let meta = path.metadata()?;
-            let size = meta.len();
-            let mtime = meta
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
-            files_scanned += 1;
-            total_bytes += size;
-            let key = path.to_string_lossy().to_string();
-            let prev = state.files.get(&key);
-            let (offset, turn_id, delete_first, skip) = match prev {
-                None => (0, 0, false, false),
-                Some(prev) => {
-                    if size < prev.size || mtime < prev.mtime {
-                        (0, 0, true, false)
-                    } else if size == prev.size && mtime == prev.mtime {
-                        (prev.offset, prev.turn_id, false, true)
-                    } else {
-                        (prev.offset, prev.turn_id, false, false)
-                    }
-                }
-            };
-            if skip {
-                files_skipped += 1;
-                continue;
-            }
-            tasks.push(FileTask {
-                path,
-                source: SourceKind::Pi,
-                offset,
-                turn_id,
-                size,
-                mtime,
-                delete_first,
-            });
+        let decision = decide_file(adapter.as_ref(), &path, &state, scope)?;
+        files_scanned += 1;
+        total_bytes += decision.size;
+        if decision.decision.skipped() {
+            files_skipped += 1;
+            scan.push(decision);
+            continue;
         }
-    }
-
-    if options.include_copilot {
-        let copilot_files = collect_copilot_files()?;
-        for path in copilot_files {
-            let meta = path.metadata()?;
-            let size = meta.len();
-            let mtime = meta
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
-            files_scanned += 1;
-            total_bytes += size;
-            let key = path.to_string_lossy().to_string();
-            let prev = state.files.get(&key);
-            let (offset, turn_id, delete_first, skip) = match prev {
-                None => (0, 0, false, false),
-                Some(prev) => {
-                    if size < prev.size || mtime < prev.mtime {
-                        (0, 0, true, false)
-                    } else if size == prev.size && mtime == prev.mtime {
-                        (prev.offset, prev.turn_id, false, true)
-                    } else {
-                        (prev.offset, prev.turn_id, false, false)
-                    }
-                }
-            };
-            if skip {
-                files_skipped += 1;
-                continue;
-            }
-            tasks.push(FileTask {
+        let (offset, turn_id, delete_first) = decision.decision.read_from();
+        tasks.push((
+            adapter_idx,
+            FileTask {
                 path,
-                source: SourceKind::Copilot,
+                source: adapter.kind(),
                 offset,
                 turn_id,
-                size,
-                mtime,
+                size: decision.size,
+                mtime: decision.mtime,
                 delete_first,
-            });
-        }
+            },
+        ));
+        scan.push(decision);
     }
 
-    let opencode_session_links = if tasks.iter().any(|task| task.source == SourceKind::Opencode) {
+    let opencode_session_links = if tasks
+        .iter()
+        .any(|(_, task)| task.source == SourceKind::Opencode)
+    {
         opencode_session_links_by_id()
     } else {
         HashMap::new()
     };
 
-    let totals = compute_totals(&tasks);
-    let file_totals = compute_file_totals(&tasks);
+    let totals = compute_totals(tasks.iter().map(|(_, task)| task));
+    let file_totals = compute_file_totals(tasks.iter().map(|(_, task)| task));
     let analytics_db = analytics_path(&paths.state);
     let analytics_needs_backfill =
         !AnalyticsStore::is_complete(&analytics_db) && index.doc_count()? > 0;
@@ -467,16 +1002,25 @@ pub fn ingest_all(
         if analytics_needs_backfill {
             backfill_from_index(&analytics_db, index)?;
         }
-        update_scan_cache(paths, files_scanned, total_bytes);
+        if files.is_none() {
+            update_scan_cache(paths, files_scanned, total_bytes);
+        }
         return Ok(IngestReport {
             records_added: 0,
             records_embedded: 0,
             files_scanned,
             files_skipped,
+            records_too_large: 0,
+            nested_transcripts: 0,
+            malformed_lines: 0,
+            scan,
         });
     }
 
-    let progress = Arc::new(Progress::new(totals, file_totals, options.embeddings));
+    let progress = Arc::new(
+        Progress::new(totals, file_totals, options.embeddings)
+            .with_listener(options.embed_listener.clone()),
+    );
 
     let (raw_tx_record, rx_record) = record_channel();
     let tx_record = RecordSender::new(raw_tx_record, options.tool_content_limits);
@@ -484,6 +1028,7 @@ pub fn ingest_all(
 
     let delete_paths: Vec<String> = tasks
         .iter()
+        .map(|(_, task)| task)
         .filter(|t| t.delete_first)
         .map(|t| t.path.to_string_lossy().to_string())
         .collect();
@@ -494,50 +1039,42 @@ pub fn ingest_all(
         do_backfill_embeddings: options.backfill_embeddings,
         vector_dir: paths.vectors.clone(),
         analytics_path: analytics_db.clone(),
+        frecency_path: project_frecency_path(&paths.state),
         progress: progress.clone(),
         model: options.model,
         embed_runtime: options.embed_runtime.clone(),
         tool_content_limits: options.tool_content_limits,
+        bulk_heap_bytes: archive.map(|archive| archive.writer_heap_bytes),
+        archive_tier: options.archive_tier,
     };
     let writer_handle =
         std::thread::spawn(move || writer_loop(writer_index, rx_record, delete_paths, writer_ctx));
 
-    let tasks_arc = Arc::new(tasks);
-    tasks_arc.par_iter().try_for_each(|task| -> Result<()> {
-        match task.source {
-            SourceKind::Claude => {
-                parse_claude_file(task, &tx_record, &tx_update, &next_doc_id, &progress)?
-            }
-            SourceKind::CodexSession => {
-                parse_codex_session(task, &tx_record, &tx_update, &next_doc_id, &progress)?
-            }
-            SourceKind::CodexHistory => parse_codex_history(
-                task,
-                &tx_record,
-                &tx_update,
-                &next_doc_id,
-                &session_ids,
-                &progress,
-            )?,
-            SourceKind::Opencode => parse_opencode_file(
-                task,
-                &tx_record,
-                &tx_update,
-                &next_doc_id,
-                &progress,
-                &opencode_session_links,
-            )?,
-            SourceKind::Cursor => {
-                parse_cursor_file(task, &tx_record, &tx_update, &next_doc_id, &progress)?
-            }
-            SourceKind::Pi => parse_pi_file(task, &tx_record, &tx_update, &next_doc_id, &progress)?,
-            SourceKind::Copilot => {
-                parse_copilot_session(task, &tx_record, &tx_update, &next_doc_id, &progress)?
-            }
-        }
-        Ok(())
-    })?;
+    let ocr = options.ocr_images.then(Ocr::detect).flatten();
+    let ctx = ParseContext {
+        records: &tx_record,
+        updates: &tx_update,
+        next_doc_id: &next_doc_id,
+        progress: &progress,
+        session_ids: &session_ids,
+        opencode_session_links: &opencode_session_links,
+        ocr: ocr.as_ref(),
+    };
+    let parse_all = || {
+        tasks
+            .par_iter()
+            .try_for_each(|(adapter_idx, task)| adapters[*adapter_idx].parse(task, &ctx))
+    };
+    match options.max_threads {
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|idx| format!("memex-parse-{idx}"))
+            .build()?
+            .install(parse_all)?,
+        None => parse_all()?,
+    }
 
+    let (records_too_large, nested_transcripts) = tx_record.skipped();
     drop(tx_record);
     drop(tx_update);
 
@@ -552,10 +1089,39 @@ pub fn ingest_all(
         AnalyticsStore::open(&analytics_db)?.mark_complete()?;
     }
 
+    let sources: HashMap<String, (SourceKind, bool)> = tasks
+        .iter()
+        .map(|(_, task)| {
+            (
+                task.path.to_string_lossy().to_string(),
+                (task.source, task.offset == 0),
+            )
+        })
+        .collect();
+    let errors_path = ingest_errors_path(&paths.state);
+    let mut errors = IngestErrors::load(&errors_path).unwrap_or_default();
+    let errors_before = errors.clone();
+    let mut malformed_lines = 0;
     let mut updated_files = HashMap::new();
     while let Ok(update) = rx_update.recv() {
         updated_files.insert(update.path.clone(), update.state.clone());
         let _ = update.session_id;
+        let (source, from_start) = sources
+            .get(&update.path)
+            .map_or((None, false), |(source, from_start)| {
+                (Some(*source), *from_start)
+            });
+        malformed_lines += update.malformed.total;
+        errors.update(
+            &update.path,
+            source.map_or("", SourceKind::label),
+            from_start,
+            update.malformed.total,
+            update.malformed.lines,
+        );
+    }
+    if errors.files != errors_before.files {
+        errors.save(&errors_path)?;
     }
 
     for (path, update) in updated_files {
@@ -564,16 +1130,210 @@ pub fn ingest_all(
     state.next_doc_id = next_doc_id.load(Ordering::SeqCst);
     state.save(&state_path)?;
 
-    update_scan_cache(paths, files_scanned, total_bytes);
+    if files.is_none() {
+        update_scan_cache(paths, files_scanned, total_bytes);
+    }
 
     Ok(IngestReport {
         records_added,
         records_embedded,
         files_scanned,
         files_skipped,
+        records_too_large,
+        nested_transcripts,
+        malformed_lines,
+        scan,
+    })
+}
+
+/// Every file the enabled sources list, with the adapter that listed it.
+fn scan_candidates(
+    adapters: &[Arc<dyn SourceAdapter>],
+    options: &IngestOptions,
+) -> Result<Vec<(usize, PathBuf)>> {
+    let mut candidates = Vec::new();
+    for (adapter_idx, adapter) in adapters.iter().enumerate() {
+        if adapter.enabled(options) {
+            let paths = adapter.scan(options)?;
+            candidates.extend(paths.into_iter().map(|path| (adapter_idx, path)));
+        }
+    }
+    Ok(candidates)
+}
+
+fn decide_file(
+    adapter: &dyn SourceAdapter,
+    path: &Path,
+    state: &IngestState,
+    scope: Option<&ReindexScope>,
+) -> Result<FileDecision> {
+    let meta = path.metadata()?;
+    let size = meta.len();
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let decision = match scope {
+        Some(scope) if scope.matches(adapter.kind(), path) => ScanDecision::Reindexed,
+        Some(_) => ScanDecision::OutOfScope,
+        None => ScanDecision::decide(
+            state.files.get(path.to_string_lossy().as_ref()),
+            size,
+            mtime,
+            adapter.resumable(),
+        ),
+    };
+    Ok(FileDecision {
+        path: path.to_path_buf(),
+        source: adapter.kind(),
+        size,
+        mtime,
+        decision,
+    })
+}
+
+/// What `memex index` would decide for every file, without reading any.
+pub fn plan_scan(paths: &Paths, options: &IngestOptions) -> Result<Vec<FileDecision>> {
+    let state = IngestState::load(&paths.state.join("ingest.json"))?;
+    let adapters = source_adapters(paths);
+    scan_candidates(&adapters, options)?
+        .into_iter()
+        .map(|(adapter_idx, path)| decide_file(adapters[adapter_idx].as_ref(), &path, &state, None))
+        .collect()
+}
+
+/// Everything `memex scan --explain` reports about one path.
+#[derive(Debug)]
+pub struct ScanExplanation {
+    pub path: PathBuf,
+    pub exists: bool,
+    /// The file as a scan finds it; `None` when no enabled source lists it.
+    pub decision: Option<FileDecision>,
+    /// Why no source lists it, when none does.
+    pub unlisted: Option<String>,
+    /// Its entry in the ingest state (ingest.json), listed or not.
+    pub state: Option<FileState>,
+    /// Malformed lines recorded for it (`memex ingest-errors`).
+    pub malformed_lines: usize,
+    /// When the last full scan ran (scan_cache.json), in Unix seconds.
+    pub last_scan_ts: u64,
+    /// Whether that scan is recent enough that searches which index first
+    /// skip scanning altogether.
+    pub scan_cache_fresh: bool,
+}
+
+/// Why `path` was or wasn't ingested, as `memex index` with `options`
+/// would scan it now.
+pub fn explain_scan(
+    paths: &Paths,
+    options: &IngestOptions,
+    path: &Path,
+    ttl_seconds: u64,
+) -> Result<ScanExplanation> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let state = IngestState::load(&paths.state.join("ingest.json"))?;
+    let adapters = source_adapters(paths);
+    let listed = scan_candidates(&adapters, options)?
+        .into_iter()
+        .find(|(_, candidate)| {
+            candidate == path
+                || (candidate.file_name() == canonical.file_name()
+                    && candidate
+                        .canonicalize()
+                        .is_ok_and(|found| found == canonical))
+        });
+    let decision = match &listed {
+        Some((adapter_idx, found)) => Some(decide_file(
+            adapters[*adapter_idx].as_ref(),
+            found,
+            &state,
+            None,
+        )?),
+        None => None,
+    };
+    let keys = match &listed {
+        Some((_, found)) => vec![found.to_string_lossy().to_string()],
+        None => vec![
+            path.to_string_lossy().to_string(),
+            canonical.to_string_lossy().to_string(),
+        ],
+    };
+    let errors = IngestErrors::load(&ingest_errors_path(&paths.state))?;
+    let cache = ScanCache::load(&paths.state.join("scan_cache.json"))?;
+    Ok(ScanExplanation {
+        path: listed.map_or(canonical.clone(), |(_, found)| found),
+        exists: canonical.is_file(),
+        unlisted: decision
+            .is_none()
+            .then(|| unlisted_reason(&adapters, options, &canonical)),
+        decision,
+        state: keys.iter().find_map(|key| state.files.get(key).cloned()),
+        malformed_lines: keys
+            .iter()
+            .find_map(|key| errors.files.get(key))
+            .map_or(0, |file| file.total),
+        last_scan_ts: cache.last_scan_ts,
+        scan_cache_fresh: cache.is_fresh(ttl_seconds),
     })
 }
 
+/// Why no enabled source lists `path`, going by where it lives.
+fn unlisted_reason(
+    adapters: &[Arc<dyn SourceAdapter>],
+    options: &IngestOptions,
+    path: &Path,
+) -> String {
+    if !path.exists() {
+        return "it does not exist".to_string();
+    }
+    if !path.is_file() {
+        return "it is not a file".to_string();
+    }
+    let claude_source = options
+        .claude_source
+        .canonicalize()
+        .unwrap_or_else(|_| options.claude_source.clone());
+    if path.starts_with(&claude_source) {
+        if path.extension().is_none_or(|ext| ext != "jsonl") {
+            return format!(
+                "only .jsonl files under {} are Claude sessions",
+                claude_source.display()
+            );
+        }
+        if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("agent-"))
+            && !options.include_agents
+        {
+            return "Claude subagent transcripts are skipped without --include-agents".to_string();
+        }
+    }
+    let kind = SourceKind::from_path(&path.to_string_lossy());
+    if kind != SourceKind::Claude
+        && let Some(adapter) = adapters.iter().find(|adapter| adapter.kind() == kind)
+    {
+        if !adapter.enabled(options) {
+            return format!(
+                "it looks like a {} file, and that source is off",
+                kind.label()
+            );
+        }
+        return format!(
+            "it looks like a {} file, but the {} scan does not list it: not a name or \
+             format that source reads",
+            kind.label(),
+            kind.label()
+        );
+    }
+    format!(
+        "it is not under a directory memex reads (Claude sessions come from {})",
+        claude_source.display()
+    )
+}
+
 fn update_scan_cache(paths: &Paths, files_scanned: usize, total_bytes: u64) {
     let cache_path = paths.state.join("scan_cache.json");
     let mut cache = ScanCache::load(&cache_path).unwrap_or_default();
@@ -612,7 +1372,7 @@ fn can_skip_noop_index(
     let Some(dimensions) = options.model.known_dimensions() else {
         return Ok(false);
     };
-    if !paths.vectors.join("usearch.index").exists() {
+    if !crate::vector::vector_store_exists(&paths.vectors) {
         return Ok(false);
     }
     let vector_index = crate::vector::VectorIndex::open(&paths.vectors)?;
@@ -621,16 +1381,37 @@ fn can_skip_noop_index(
     {
         return Ok(false);
     }
-    vector_index_covers_embeddable_records(index, &vector_index)
+    let archive_index = match options.archive_tier {
+        Some(tier) => {
+            let dir = archive_vectors_dir(&paths.vectors);
+            if !crate::vector::vector_store_exists(&dir) {
+                return Ok(false);
+            }
+            let archive_index = crate::vector::VectorIndex::open(&dir)?;
+            if archive_index.model() != Some(tier.model.as_str()) {
+                return Ok(false);
+            }
+            Some(archive_index)
+        }
+        None => None,
+    };
+    vector_index_covers_embeddable_records(index, &vector_index, archive_index.as_ref())
 }
 
+/// Whether every record worth embedding has a vector in the main store or,
+/// with an archive tier, the archive store. A record keeps the store it was
+/// first embedded in, so either counts.
 fn vector_index_covers_embeddable_records(
     index: &SearchIndex,
     vector_index: &crate::vector::VectorIndex,
+    archive_index: Option<&crate::vector::VectorIndex>,
 ) -> Result<bool> {
     let mut covers_all = true;
     index.for_each_record(|record| {
-        if record_needs_embedding(&record) && !vector_index.contains(record.doc_id) {
+        if record_needs_embedding(&record)
+            && !vector_index.contains(record.doc_id)
+            && !archive_index.is_some_and(|archive| archive.contains(record.doc_id))
+        {
             covers_all = false;
         }
         Ok(())
@@ -638,6 +1419,31 @@ fn vector_index_covers_embeddable_records(
     Ok(covers_all)
 }
 
+/// Indexes the turns of every session file already in the index, which
+/// predates turns. Files ingested later build theirs as they are read.
+fn backfill_turns(index: &SearchIndex) -> Result<()> {
+    let mut by_path: HashMap<String, Vec<Record>> = HashMap::new();
+    index.for_each_record(|mut record| {
+        // Turns only need the text.
+        record.tool_input = None;
+        record.tool_output = None;
+        by_path
+            .entry(record.source_path.clone())
+            .or_default()
+            .push(record);
+        Ok(())
+    })?;
+    let mut writer = index.writer()?;
+    for records in by_path.values_mut() {
+        records.sort_by_key(|record| (record.turn_id, record.doc_id));
+        for turn in build_turns(records) {
+            index.add_record(&mut writer, &turn)?;
+        }
+    }
+    writer.commit()?;
+    Ok(())
+}
+
 fn record_needs_embedding(record: &Record) -> bool {
     is_embedding_role(&record.role) && !record.text.is_empty()
 }
@@ -653,17 +1459,31 @@ fn writer_loop(
         do_backfill_embeddings,
         vector_dir,
         analytics_path,
+        frecency_path,
         progress,
         model,
         embed_runtime,
         tool_content_limits,
+        bulk_heap_bytes,
+        archive_tier,
     } = ctx;
-    let mut writer = index.writer()?;
+    let mut writer = match bulk_heap_bytes {
+        Some(heap) => index.bulk_writer(heap)?,
+        None => index.writer()?,
+    };
     let mut analytics = AnalyticsWriter::open(&analytics_path)?;
+    // Changed files are re-added in full; records they already had keep their
+    // original ingestion time so `search --as-of` still sees them.
+    let mut prior_ingested_at = HashMap::new();
+    let deleted_paths: HashSet<String> = delete_paths.iter().cloned().collect();
     for path in delete_paths {
-        index.delete_by_source_path(&mut writer, &path);
+        for (key, ingested_at) in index.ingested_at_by_source_path(&path)? {
+            prior_ingested_at.insert((path.clone(), key), ingested_at);
+        }
+        index.delete_by_source_path(&mut writer, &path)?;
         analytics.delete_source_path(&path)?;
     }
+    let ingested_at = Utc::now().timestamp_millis().max(0) as u64;
 
     let mut count = 0usize;
     let mut embedded_count = 0usize;
@@ -671,7 +1491,37 @@ fn writer_loop(
     let mut embedder: Option<EmbedderHandle> = None;
     let mut embed_buffer: Vec<(u64, String, SourceKind)> = Vec::new();
     let mut index_pending = [0u64; SOURCE_COUNT];
-    if embeddings {
+    let mut ingested_projects: HashSet<String> = HashSet::new();
+    let mut pipeline = None;
+    let mut archive_pipeline = None;
+    let mut archive: Option<ArchiveEmbedding> = None;
+    let archive_cutoff = archive_tier.map(|tier| tier.cutoff_ms(ingested_at));
+    let mut turns = TurnBuilder::default();
+    if embeddings && bulk_heap_bytes.is_some() {
+        // With an archive tier both pipelines embed at once, so they split the
+        // thread cap between them.
+        let pipeline_runtime = match (archive_tier, embed_runtime.threads) {
+            (Some(_), Some(threads)) => EmbedRuntimeConfig {
+                threads: Some((threads / 2).max(1)),
+                ..embed_runtime.clone()
+            },
+            _ => embed_runtime.clone(),
+        };
+        pipeline = Some(EmbedPipeline::start(
+            model,
+            &pipeline_runtime,
+            &vector_dir,
+            &progress,
+        )?);
+        if let Some(tier) = archive_tier {
+            archive_pipeline = Some(EmbedPipeline::start(
+                tier.model,
+                &pipeline_runtime,
+                &archive_vectors_dir(&vector_dir),
+                &progress,
+            )?);
+        }
+    } else if embeddings {
         let handle = EmbedderHandle::with_model_and_runtime(model, &embed_runtime)?;
         let dims = handle.dims;
         vector_index = Some(crate::vector::VectorIndex::open_or_create(
@@ -680,23 +1530,72 @@ fn writer_loop(
             Some(model.as_str()),
         )?);
         embedder = Some(handle);
+        if let (Some(tier), Some(cutoff_ms)) = (archive_tier, archive_cutoff) {
+            archive = Some(ArchiveEmbedding::open(
+                tier,
+                cutoff_ms,
+                &embed_runtime,
+                &vector_dir,
+            )?);
+        }
         progress.set_embed_ready();
     }
 
     for mut record in rx.iter() {
+        if record.role == SUMMARY_ROLE {
+            analytics.record(&record)?;
+            continue;
+        }
         // Parsers apply the limit before queueing; enforce it here as a defensive boundary too.
         limit_record_tool_content(&mut record, tool_content_limits);
+        let key = (
+            record.source_path.clone(),
+            (record.turn_id, record.ts, record.role.clone()),
+        );
+        record.ingested_at = prior_ingested_at.get(&key).copied().unwrap_or(ingested_at);
         analytics.record(&record)?;
-        index.add_record(&mut writer, &record)?;
+        index.add_record_with_code(&mut writer, &record, tool_content_limits.code)?;
+        if !turns.has_file(&record.source_path)
+            && !is_prompt(&record)
+            && !deleted_paths.contains(&record.source_path)
+        {
+            // A file read from where the last ingest stopped continues the
+            // exchange it ended on; that turn is rebuilt with the new records.
+            let exchange = last_exchange(index.records_by_source_path(&record.source_path)?);
+            if let Some(prompt) = exchange.first() {
+                index.delete_by_doc_id(&mut writer, turn_doc_id(prompt.doc_id))?;
+            }
+            turns.resume(&record.source_path, exchange);
+        }
+        if let Some(turn) = turns.push(&record) {
+            index.add_record(&mut writer, &turn)?;
+        }
+        if !ingested_projects.contains(&record.project) {
+            ingested_projects.insert(record.project.clone());
+        }
         let source_idx = record.source.idx();
         index_pending[source_idx] += 1;
         if index_pending[source_idx] >= INDEX_PROGRESS_BATCH {
             progress.add_indexed(record.source, index_pending[source_idx]);
             index_pending[source_idx] = 0;
         }
-        if embeddings && is_embedding_role(&record.role) && !record.text.is_empty() {
+        let archived = archive_cutoff.is_some_and(|cutoff| record.ts < cutoff);
+        if let Some(pipeline) = pipeline.as_mut()
+            && is_embedding_role(&record.role)
+            && !record.text.is_empty()
+        {
             let text = truncate_for_embedding(std::mem::take(&mut record.text));
-            if let Some(vindex) = vector_index.as_ref()
+            match archive_pipeline.as_mut().filter(|_| archived) {
+                Some(archive_pipeline) => {
+                    archive_pipeline.push(record.doc_id, text, record.source)?
+                }
+                None => pipeline.push(record.doc_id, text, record.source)?,
+            }
+        } else if embeddings && is_embedding_role(&record.role) && !record.text.is_empty() {
+            let text = truncate_for_embedding(std::mem::take(&mut record.text));
+            if let Some(archive) = archive.as_mut().filter(|_| archived) {
+                embedded_count += archive.push(record.doc_id, text, record.source, &progress)?;
+            } else if let Some(vindex) = vector_index.as_ref()
                 && !vindex.contains(record.doc_id)
             {
                 progress.add_embed_total(record.source, 1);
@@ -704,7 +1603,7 @@ fn writer_loop(
                 embed_buffer.push((record.doc_id, text, record.source));
             }
             if let Some(emb) = embedder.as_mut()
-                && embed_buffer.len() >= EMBED_BATCH_SIZE
+                && embed_buffer.len() >= emb.batch_size()
             {
                 embedded_count += flush_embeddings(
                     &mut embed_buffer,
@@ -726,9 +1625,30 @@ fn writer_loop(
         }
     }
 
+    for turn in turns.finish() {
+        index.add_record(&mut writer, &turn)?;
+    }
     analytics.flush()?;
     writer.commit()?;
-    if embeddings {
+    if bulk_heap_bytes.is_some() {
+        let segment_ids = index.index.searchable_segment_ids()?;
+        if segment_ids.len() > 1 {
+            writer.merge(&segment_ids).wait()?;
+            writer.garbage_collect_files().wait()?;
+        }
+    }
+    // Fresh activity in a project counts as a visit for picker ranking.
+    let projects: Vec<String> = ingested_projects
+        .iter()
+        .map(|project| display_project_name(project))
+        .collect();
+    let _ = record_project_visits(&frecency_path, projects.iter().map(String::as_str));
+    if let Some(mut pipeline) = pipeline {
+        embedded_count += pipeline.finish()?;
+        if let Some(mut archive_pipeline) = archive_pipeline {
+            embedded_count += archive_pipeline.finish()?;
+        }
+    } else if embeddings {
         if !embed_buffer.is_empty() {
             embedded_count += flush_embeddings(
                 &mut embed_buffer,
@@ -737,24 +1657,64 @@ fn writer_loop(
                 &progress,
             )?;
         }
+        if let Some(archive) = archive.as_mut() {
+            embedded_count += archive.flush(&progress)?;
+        }
 
         let needs_vector_backfill = match vector_index.as_ref() {
             Some(vindex) => {
-                vindex.needs_backfill() || !vector_index_covers_embeddable_records(&index, vindex)?
+                vindex.needs_backfill()
+                    || archive
+                        .as_ref()
+                        .is_some_and(|archive| archive.vectors.needs_backfill())
+                    || !vector_index_covers_embeddable_records(
+                        &index,
+                        vindex,
+                        archive.as_ref().map(|archive| &archive.vectors),
+                    )?
             }
             None => false,
         };
         if do_backfill_embeddings || needs_vector_backfill {
-            embedded_count += backfill_embeddings(
-                &index,
-                embedder.as_mut().unwrap(),
-                vector_index.as_mut().unwrap(),
-                &progress,
-            )?;
+            let vindex = vector_index.as_mut().unwrap();
+            match archive.as_mut() {
+                // Records missing from both stores go to the one their age
+                // picks; records already in either stay where they are.
+                Some(archive) => {
+                    let cutoff_ms = archive.cutoff_ms;
+                    embedded_count += backfill_embeddings(
+                        &index,
+                        embedder.as_mut().unwrap(),
+                        vindex,
+                        &progress,
+                        |record| record.ts >= cutoff_ms && !archive.vectors.contains(record.doc_id),
+                    )?;
+                    embedded_count += backfill_embeddings(
+                        &index,
+                        &mut archive.embedder,
+                        &mut archive.vectors,
+                        &progress,
+                        |record| record.ts < cutoff_ms && !vindex.contains(record.doc_id),
+                    )?;
+                }
+                None => {
+                    embedded_count += backfill_embeddings(
+                        &index,
+                        embedder.as_mut().unwrap(),
+                        vindex,
+                        &progress,
+                        |_| true,
+                    )?;
+                }
+            }
         }
         if let Some(vindex) = vector_index.as_mut() {
             vindex.save()?;
         }
+        if let Some(mut archive) = archive.take() {
+            archive.vectors.save()?;
+            std::mem::forget(archive.embedder);
+        }
         if let Some(handle) = embedder.take() {
             std::mem::forget(handle);
         }
@@ -762,11 +1722,172 @@ fn writer_loop(
     Ok((count, embedded_count))
 }
 
+/// The archive tier's embedder and store, outside bulk imports: records
+/// older than `cutoff_ms` are embedded here instead of the main store.
+struct ArchiveEmbedding {
+    cutoff_ms: u64,
+    embedder: EmbedderHandle,
+    vectors: crate::vector::VectorIndex,
+    buffer: Vec<(u64, String, SourceKind)>,
+}
+
+impl ArchiveEmbedding {
+    fn open(
+        tier: ArchiveTier,
+        cutoff_ms: u64,
+        runtime: &EmbedRuntimeConfig,
+        vector_dir: &Path,
+    ) -> Result<Self> {
+        let embedder = EmbedderHandle::with_model_and_runtime(tier.model, runtime)?;
+        let vectors = crate::vector::VectorIndex::open_or_create(
+            &archive_vectors_dir(vector_dir),
+            embedder.dims,
+            Some(tier.model.as_str()),
+        )?;
+        Ok(Self {
+            cutoff_ms,
+            embedder,
+            vectors,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Queues a text, embedding the queue once it fills a batch. Returns how
+    /// many vectors that added.
+    fn push(
+        &mut self,
+        doc_id: u64,
+        text: String,
+        source: SourceKind,
+        progress: &Arc<Progress>,
+    ) -> Result<usize> {
+        if !self.vectors.contains(doc_id) {
+            progress.add_embed_total(source, 1);
+            progress.add_embed_pending(source, 1);
+            self.buffer.push((doc_id, text, source));
+        }
+        if self.buffer.len() >= self.embedder.batch_size() {
+            return self.flush(progress);
+        }
+        Ok(0)
+    }
+
+    fn flush(&mut self, progress: &Arc<Progress>) -> Result<usize> {
+        flush_embeddings(
+            &mut self.buffer,
+            &mut self.embedder,
+            &mut self.vectors,
+            progress,
+        )
+    }
+}
+
+/// Texts queued on the embedding thread before the writer waits for it.
+const EMBED_PIPELINE_BATCHES: usize = 4;
+
+/// Embeds on a thread of its own during bulk imports, so indexing never
+/// waits on the model. The thread owns the embedder and vector store and
+/// saves the store when the writer is done.
+struct EmbedPipeline {
+    buffer: Vec<(u64, String, SourceKind)>,
+    batch_size: usize,
+    batches: Option<Sender<Vec<(u64, String, SourceKind)>>>,
+    worker: Option<std::thread::JoinHandle<Result<usize>>>,
+    progress: Arc<Progress>,
+}
+
+impl EmbedPipeline {
+    fn start(
+        model: ModelChoice,
+        runtime: &EmbedRuntimeConfig,
+        vector_dir: &Path,
+        progress: &Arc<Progress>,
+    ) -> Result<Self> {
+        let (batches, rx) = bounded::<Vec<(u64, String, SourceKind)>>(EMBED_PIPELINE_BATCHES);
+        let runtime_for_worker = runtime.clone();
+        let vector_dir = vector_dir.to_path_buf();
+        let worker_progress = progress.clone();
+        let worker = std::thread::Builder::new()
+            .name("memex-embed-pipeline".to_string())
+            .spawn(move || -> Result<usize> {
+                let mut embedder =
+                    EmbedderHandle::with_model_and_runtime(model, &runtime_for_worker)?;
+                let mut vectors = crate::vector::VectorIndex::open_or_create(
+                    &vector_dir,
+                    embedder.dims,
+                    Some(model.as_str()),
+                )?;
+                worker_progress.set_embed_ready();
+                let mut count = 0;
+                for mut batch in rx.iter() {
+                    count += flush_embeddings(
+                        &mut batch,
+                        &mut embedder,
+                        &mut vectors,
+                        &worker_progress,
+                    )?;
+                }
+                vectors.save()?;
+                std::mem::forget(embedder);
+                Ok(count)
+            })?;
+        Ok(Self {
+            buffer: Vec::new(),
+            batch_size: runtime
+                .batch_size
+                .filter(|&size| size > 0)
+                .unwrap_or_else(|| model.default_batch_size()),
+            batches: Some(batches),
+            worker: Some(worker),
+            progress: progress.clone(),
+        })
+    }
+
+    fn push(&mut self, doc_id: u64, text: String, source: SourceKind) -> Result<()> {
+        self.progress.add_embed_total(source, 1);
+        self.progress.add_embed_pending(source, 1);
+        self.buffer.push((doc_id, text, source));
+        if self.buffer.len() >= self.batch_size {
+            let batch = std::mem::take(&mut self.buffer);
+            let sent = self
+                .batches
+                .as_ref()
+                .is_some_and(|batches| batches.send(batch).is_ok());
+            if !sent {
+                // The thread only stops early on an error; report it.
+                return Err(self
+                    .finish()
+                    .err()
+                    .unwrap_or_else(|| anyhow!("embedding thread stopped early")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends what is left, waits for the thread and returns how many
+    /// vectors it added.
+    fn finish(&mut self) -> Result<usize> {
+        if let Some(batches) = self.batches.take()
+            && !self.buffer.is_empty()
+        {
+            let _ = batches.send(std::mem::take(&mut self.buffer));
+        }
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .map_err(|_| anyhow!("embedding thread panicked"))?,
+            None => Ok(0),
+        }
+    }
+}
+
+/// Embeds the records `wanted` picks that `vector_index` has no vector for.
 fn backfill_embeddings(
     index: &SearchIndex,
     embedder: &mut EmbedderHandle,
     vector_index: &mut crate::vector::VectorIndex,
     progress: &Arc<Progress>,
+    wanted: impl Fn(&Record) -> bool,
 ) -> Result<usize> {
     use std::cell::Cell;
     let embedded_count = Cell::new(0usize);
@@ -775,6 +1896,7 @@ fn backfill_embeddings(
         if record.text.is_empty()
             || !is_embedding_role(&record.role)
             || vector_index.contains(record.doc_id)
+            || !wanted(&record)
         {
             return Ok(());
         }
@@ -785,7 +1907,7 @@ fn backfill_embeddings(
             truncate_for_embedding(record.text),
             record.source,
         ));
-        if embed_buffer.len() >= EMBED_BATCH_SIZE {
+        if embed_buffer.len() >= embedder.batch_size() {
             let n = flush_embeddings(&mut embed_buffer, embedder, vector_index, progress)?;
             embedded_count.set(embedded_count.get() + n);
         }
@@ -798,6 +1920,47 @@ fn backfill_embeddings(
     Ok(embedded_count.get())
 }
 
+/// Transcripts under an archive directory with the adapter that reads each:
+/// Codex `rollout-*.jsonl` files and, for every other `.jsonl`, Claude.
+fn archive_files(
+    adapters: &[Arc<dyn SourceAdapter>],
+    dir: &Path,
+    include_agents: bool,
+) -> Result<Vec<(usize, PathBuf)>> {
+    let adapter = |kind: SourceKind| {
+        adapters
+            .iter()
+            .position(|adapter| adapter.kind() == kind)
+            .ok_or_else(|| anyhow!("no adapter for {}", kind.label()))
+    };
+    let (claude, codex, chatgpt) = (
+        adapter(SourceKind::Claude)?,
+        adapter(SourceKind::CodexSession)?,
+        adapter(SourceKind::ChatGPT)?,
+    );
+    let mut files: Vec<(usize, PathBuf)> = collect_claude_files(dir, include_agents)?
+        .into_iter()
+        .map(|path| {
+            let is_rollout = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("rollout-"));
+            (if is_rollout { codex } else { claude }, path)
+        })
+        .collect();
+    files.extend(
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry.file_type().is_file() && entry.file_name() == CHATGPT_CONVERSATIONS
+            })
+            .map(|entry| (chatgpt, entry.into_path())),
+    );
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(files)
+}
+
 fn collect_claude_files(source: &Path, include_agents: bool) -> Result<Vec<PathBuf>> {
     let mut files = Vec::new();
     for entry in WalkDir::new(source).into_iter().filter_map(Result::ok) {
@@ -933,6 +2096,15 @@ fn collect_copilot_files_from_root(root: &Path) -> Result<Vec<PathBuf>> {
 }
 
 fn codex_root() -> PathBuf {
+    if let Some(codex_home) = std::env::var_os("CODEX_HOME")
+        && let Some(first) = codex_home
+            .to_string_lossy()
+            .split(',')
+            .map(str::trim)
+            .find(|part| !part.is_empty())
+    {
+        return PathBuf::from(first);
+    }
     let home = directories::BaseDirs::new()
         .map(|b| b.home_dir().to_path_buf())
         .unwrap_or_else(|| PathBuf::from("/"));
@@ -1149,6 +2321,8 @@ struct CodexSessionMeta {
     session_id: String,
     project: String,
     links: SessionLinks,
+    /// From the latest `turn_context`; applies to the records that follow.
+    settings: AgentSettings,
 }
 
 fn codex_session_meta_from_path(path: &Path) -> CodexSessionMeta {
@@ -1159,6 +2333,7 @@ fn codex_session_meta_from_path(path: &Path) -> CodexSessionMeta {
             conversation_kind: Some("main".to_string()),
             ..SessionLinks::default()
         },
+        settings: AgentSettings::default(),
     }
 }
 
@@ -1186,15 +2361,39 @@ fn read_codex_session_meta_until(path: &Path, limit: u64) -> Result<CodexSession
             Ok(v) => v,
             Err(_) => continue,
         };
-        if value.get("type").and_then(|v| v.as_str()) == Some("session_meta")
-            && let Some(payload) = value.get("payload").and_then(|v| v.as_object())
-        {
-            apply_codex_session_meta(payload, &mut meta);
+        let Some(payload) = value.get("payload").and_then(|v| v.as_object()) else {
+            continue;
+        };
+        match value.get("type").and_then(|v| v.as_str()) {
+            Some("session_meta") => apply_codex_session_meta(payload, &mut meta),
+            Some("turn_context") => apply_codex_turn_context(payload, &mut meta.settings),
+            _ => {}
         }
     }
     Ok(meta)
 }
 
+/// `turn_context` entries record the model, sandbox and approval policy in
+/// effect for the following turn. `sandbox_policy` is either a bare mode
+/// string or an object tagged with `mode`/`type`.
+fn apply_codex_turn_context(payload: &simd_json::borrowed::Object, settings: &mut AgentSettings) {
+    if let Some(model) = opt_str(payload, "model") {
+        settings.model = Some(model);
+    }
+    if let Some(approval) = opt_str(payload, "approval_policy") {
+        settings.approval_policy = Some(approval);
+    }
+    let sandbox = payload.get("sandbox_policy").and_then(|policy| {
+        policy.as_str().map(str::to_string).or_else(|| {
+            let policy = policy.as_object()?;
+            opt_str(policy, "mode").or_else(|| opt_str(policy, "type"))
+        })
+    });
+    if let Some(sandbox) = sandbox {
+        settings.sandbox = Some(sandbox);
+    }
+}
+
 fn apply_codex_session_meta(payload: &simd_json::borrowed::Object, meta: &mut CodexSessionMeta) {
     if let Some(id) = payload.get("id").and_then(|v| v.as_str()) {
         meta.session_id = id.to_string();
@@ -1256,6 +2455,7 @@ fn parse_claude_file(
     tx_update: &Sender<FileUpdate>,
     next_doc_id: &AtomicU64,
     progress: &Arc<Progress>,
+    ocr: Option<&Ocr>,
 ) -> Result<()> {
     let file = File::open(&task.path)?;
     let mmap = unsafe { Mmap::map(&file)? };
@@ -1276,9 +2476,17 @@ fn parse_claude_file(
             .any(|component| component.as_os_str().to_str() == Some("subagents"));
     let source_path = task.path.to_string_lossy().to_string();
     let mut tool_id_to_name: HashMap<String, String> = HashMap::new();
+    // Summary lines carry no timestamp and usually precede the messages, so
+    // they are held until the first timestamped entry dates them.
+    let mut pending_summaries: Vec<String> = Vec::new();
+    let mut last_ts = 0u64;
+    // Inline sidechains inherit the thread of their parent event so a whole
+    // branch shares one id, rooted at its first sidechain event.
+    let mut thread_by_event: HashMap<String, String> = HashMap::new();
 
     let mut buf = Vec::new();
     let mut parsed_bytes = 0u64;
+    let mut malformed = MalformedLines::default();
     while start < mmap.len() {
         let slice = &mmap[start..];
         let rel = memchr(b'\n', slice).unwrap_or(slice.len());
@@ -1297,19 +2505,63 @@ fn parse_claude_file(
         buf.extend_from_slice(line);
         let value: BorrowedValue = match simd_json::to_borrowed_value(&mut buf) {
             Ok(v) => v,
-            Err(_) => continue,
+            Err(err) => {
+                malformed.push(&mmap, start - advanced, err);
+                continue;
+            }
         };
         let obj = match value.as_object() {
             Some(o) => o,
             None => continue,
         };
         let entry_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
-        if entry_type != "user" && entry_type != "assistant" {
-            continue;
-        }
-        let entry_uuid = opt_str(obj, "uuid");
-        let entry_parent_uuid = opt_str(obj, "parentUuid");
-        let is_sidechain = obj
+        if entry_type == "summary" {
+            let summary = obj
+                .get("summary")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .trim();
+            if !summary.is_empty() {
+                pending_summaries.push(summary.to_string());
+            }
+        }
+        if let Some(ts) = obj
+            .get("timestamp")
+            .and_then(|v| v.as_str())
+            .and_then(parse_iso_millis)
+        {
+            last_ts = ts;
+        }
+        if last_ts > 0 {
+            // A summary only titles the session: it takes no doc id or turn
+            // and the writer keeps it out of the index.
+            for summary in pending_summaries.drain(..) {
+                tx_record.send(Record {
+                    source: SourceKind::Claude,
+                    doc_id: 0,
+                    ts: last_ts,
+                    project: project.clone(),
+                    session_id: session_id.clone(),
+                    turn_id,
+                    role: SUMMARY_ROLE.to_string(),
+                    text: summary,
+                    tool_name: None,
+                    tool_input: None,
+                    tool_output: None,
+                    links: RecordLinks::default(),
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
+                    source_path: source_path.clone(),
+                    ingested_at: 0,
+                })?;
+            }
+        }
+        if entry_type != "user" && entry_type != "assistant" {
+            continue;
+        }
+        let entry_uuid = opt_str(obj, "uuid");
+        let entry_parent_uuid = opt_str(obj, "parentUuid");
+        let is_sidechain = obj
             .get("isSidechain")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
@@ -1327,6 +2579,22 @@ fn parse_claude_file(
         } else {
             None
         };
+        let thread = if is_agent_file {
+            Some(opt_str(obj, "agentId").unwrap_or_else(|| session_id.clone()))
+        } else if is_sidechain {
+            entry_parent_uuid
+                .as_ref()
+                .and_then(|parent| thread_by_event.get(parent))
+                .cloned()
+                .or_else(|| entry_uuid.clone())
+        } else {
+            None
+        };
+        if let (Some(uuid), Some(thread)) = (&entry_uuid, &thread)
+            && is_sidechain
+        {
+            thread_by_event.insert(uuid.clone(), thread.clone());
+        }
         let entry_links = RecordLinks {
             event_id: entry_uuid.clone(),
             parent_event_id: entry_parent_uuid,
@@ -1334,6 +2602,7 @@ fn parse_claude_file(
             parent_session_id: is_agent_file.then(|| opt_str(obj, "sessionId")).flatten(),
             thread_source,
             conversation_kind: Some(conversation_kind.to_string()),
+            thread,
             parent_tool_use_id: opt_str(obj, "parentToolUseID"),
             source_tool_use_id: opt_str(obj, "sourceToolUseID"),
             source_tool_assistant_uuid: opt_str(obj, "sourceToolAssistantUUID"),
@@ -1349,6 +2618,7 @@ fn parse_claude_file(
         };
         let content = message.get("content");
         let mut text_parts = Vec::new();
+        let mut attachments = Vec::new();
         if let Some(content) = content {
             if let Some(text) = content.as_str() {
                 text_parts.push(text);
@@ -1363,6 +2633,8 @@ fn parse_claude_file(
                         if let Some(text) = block_obj.get("text").and_then(|v| v.as_str()) {
                             text_parts.push(text);
                         }
+                    } else if block_type == "image" || block_type == "document" {
+                        attachments.push(claude_attachment(block_type, block_obj, ocr));
                     } else if block_type == "tool_use" {
                         let tool_name = block_obj
                             .get("name")
@@ -1394,7 +2666,10 @@ fn parse_claude_file(
                             tool_input,
                             tool_output: None,
                             links,
+                            settings: AgentSettings::default(),
+                            attachments: Vec::new(),
                             source_path: source_path.clone(),
+                            ingested_at: 0,
                         };
                         progress.add_produced(SourceKind::Claude, 1);
                         tx_record.send(record)?;
@@ -1451,7 +2726,10 @@ fn parse_claude_file(
                         tool_input: None,
                         tool_output,
                         links,
+                        settings: AgentSettings::default(),
+                        attachments: Vec::new(),
                         source_path: source_path.clone(),
+                        ingested_at: 0,
                     };
                     progress.add_produced(SourceKind::Claude, 1);
                     tx_record.send(record)?;
@@ -1460,7 +2738,21 @@ fn parse_claude_file(
             }
         }
 
-        let text = text_parts.join(" ").trim().to_string();
+        let mut text = text_parts.join(" ").trim().to_string();
+        if text.is_empty() {
+            text = attachment_placeholder(&attachments);
+        }
+        // After a compaction the summary comes back as a user message.
+        let role = if entry_type == "user"
+            && obj
+                .get("isCompactSummary")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+        {
+            COMPACT_SUMMARY_ROLE
+        } else {
+            entry_type
+        };
         if !text.is_empty() {
             let record = Record {
                 source: SourceKind::Claude,
@@ -1469,13 +2761,16 @@ fn parse_claude_file(
                 project: project.clone(),
                 session_id: session_id.clone(),
                 turn_id,
-                role: entry_type.to_string(),
+                role: role.to_string(),
                 text,
                 tool_name: None,
                 tool_input: None,
                 tool_output: None,
                 links: entry_links,
+                settings: AgentSettings::default(),
+                attachments,
                 source_path: source_path.clone(),
+                ingested_at: 0,
             };
             progress.add_produced(SourceKind::Claude, 1);
             tx_record.send(record)?;
@@ -1497,10 +2792,76 @@ fn parse_claude_file(
         path: source_path,
         state,
         session_id: Some(session_id),
+        malformed,
     })?;
     Ok(())
 }
 
+/// Metadata for a Claude `image` or `document` content block. Inline base64
+/// data is measured but not kept; with `ocr`, images are read for text.
+fn claude_attachment(
+    kind: &str,
+    block: &simd_json::borrowed::Object,
+    ocr: Option<&Ocr>,
+) -> Attachment {
+    let source = block.get("source").and_then(|v| v.as_object());
+    let source_str = |key: &str| source.and_then(|source| opt_str(source, key));
+    let data = source
+        .and_then(|source| source.get("data"))
+        .and_then(|v| v.as_str());
+    let size = data.map(base64_decoded_len);
+    let path = source_str("path")
+        .or_else(|| source_str("file_path"))
+        .or_else(|| opt_str(block, "file_path"));
+    let filename = opt_str(block, "title")
+        .or_else(|| opt_str(block, "name"))
+        .or_else(|| {
+            path.clone()
+                .or_else(|| source_str("url"))
+                .and_then(|location| {
+                    location
+                        .rsplit(['/', '\\'])
+                        .next()
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_string)
+                })
+        });
+    let path = path.filter(|path| Path::new(path).exists());
+    let text = ocr
+        .filter(|_| kind == "image")
+        .and_then(|ocr| match (data, &path) {
+            (Some(data), _) => ocr.image_base64(data),
+            (None, Some(path)) => ocr.image_file(Path::new(path)),
+            (None, None) => None,
+        });
+    Attachment {
+        kind: kind.to_string(),
+        media_type: source_str("media_type"),
+        filename,
+        size,
+        path,
+        text,
+    }
+}
+
+fn base64_decoded_len(data: &str) -> u64 {
+    let padding = data.bytes().rev().take_while(|b| *b == b'=').count();
+    ((data.len() / 4 * 3).saturating_sub(padding)) as u64
+}
+
+/// Stands in for the text of a message that only carried attachments, so the
+/// message is still indexed and shown.
+fn attachment_placeholder(attachments: &[Attachment]) -> String {
+    attachments
+        .iter()
+        .map(|attachment| match &attachment.filename {
+            Some(name) => format!("[{}: {name}]", attachment.kind),
+            None => format!("[{}]", attachment.kind),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 fn parse_codex_session(
     task: &FileTask,
     tx_record: &RecordSender,
@@ -1519,6 +2880,7 @@ fn parse_codex_session(
 
     let mut buf = Vec::new();
     let mut parsed_bytes = 0u64;
+    let mut malformed = MalformedLines::default();
     while start < mmap.len() {
         let slice = &mmap[start..];
         let rel = memchr(b'\n', slice).unwrap_or(slice.len());
@@ -1537,7 +2899,10 @@ fn parse_codex_session(
         buf.extend_from_slice(line);
         let value: BorrowedValue = match simd_json::to_borrowed_value(&mut buf) {
             Ok(v) => v,
-            Err(_) => continue,
+            Err(err) => {
+                malformed.push(&mmap, start - advanced, err);
+                continue;
+            }
         };
         let obj = match value.as_object() {
             Some(o) => o,
@@ -1555,6 +2920,12 @@ fn parse_codex_session(
             }
             continue;
         }
+        if entry_type == "turn_context" {
+            if let Some(payload) = obj.get("payload").and_then(|v| v.as_object()) {
+                apply_codex_turn_context(payload, &mut meta.settings);
+            }
+            continue;
+        }
         if entry_type != "response_item" {
             continue;
         }
@@ -1602,18 +2973,24 @@ fn parse_codex_session(
                 tool_input: None,
                 tool_output: None,
                 links: base_links,
+                settings: meta.settings.clone(),
+                attachments: Vec::new(),
                 source_path: source_path.clone(),
+                ingested_at: 0,
             };
             progress.add_produced(SourceKind::CodexSession, 1);
             tx_record.send(record)?;
             turn_id += 1;
-        } else if payload_type == "function_call" {
+        } else if payload_type == "function_call" || payload_type == "custom_tool_call" {
             let tool_name = payload
                 .get("name")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string());
+            // Freeform tools like apply_patch carry raw `input` instead of
+            // JSON `arguments`.
             let tool_input = payload
                 .get("arguments")
+                .or_else(|| payload.get("input"))
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string());
             if let Some(call_id) = payload.get("call_id").and_then(|v| v.as_str())
@@ -1639,12 +3016,17 @@ fn parse_codex_session(
                 tool_input,
                 tool_output: None,
                 links,
+                settings: meta.settings.clone(),
+                attachments: Vec::new(),
                 source_path: source_path.clone(),
+                ingested_at: 0,
             };
             progress.add_produced(SourceKind::CodexSession, 1);
             tx_record.send(record)?;
             turn_id += 1;
-        } else if payload_type == "function_call_output" {
+        } else if payload_type == "function_call_output"
+            || payload_type == "custom_tool_call_output"
+        {
             let call_id = payload
                 .get("call_id")
                 .and_then(|v| v.as_str())
@@ -1676,7 +3058,10 @@ fn parse_codex_session(
                 tool_input: None,
                 tool_output,
                 links,
+                settings: meta.settings.clone(),
+                attachments: Vec::new(),
                 source_path: source_path.clone(),
+                ingested_at: 0,
             };
             progress.add_produced(SourceKind::CodexSession, 1);
             tx_record.send(record)?;
@@ -1698,6 +3083,7 @@ fn parse_codex_session(
         path: source_path,
         state,
         session_id: Some(meta.session_id),
+        malformed,
     })?;
     Ok(())
 }
@@ -1718,6 +3104,7 @@ fn parse_codex_history(
 
     let mut buf = Vec::new();
     let mut parsed_bytes = 0u64;
+    let mut malformed = MalformedLines::default();
     while start < mmap.len() {
         let slice = &mmap[start..];
         let rel = memchr(b'\n', slice).unwrap_or(slice.len());
@@ -1736,7 +3123,10 @@ fn parse_codex_history(
         buf.extend_from_slice(line);
         let value: BorrowedValue = match simd_json::to_borrowed_value(&mut buf) {
             Ok(v) => v,
-            Err(_) => continue,
+            Err(err) => {
+                malformed.push(&mmap, start - advanced, err);
+                continue;
+            }
         };
         let obj = match value.as_object() {
             Some(o) => o,
@@ -1772,7 +3162,10 @@ fn parse_codex_history(
                 conversation_kind: Some("main".to_string()),
                 ..RecordLinks::default()
             },
+            settings: AgentSettings::default(),
+            attachments: Vec::new(),
             source_path: source_path.clone(),
+            ingested_at: 0,
         };
         progress.add_produced(SourceKind::CodexHistory, 1);
         tx_record.send(record)?;
@@ -1793,6 +3186,7 @@ fn parse_codex_history(
         path: source_path,
         state,
         session_id: None,
+        malformed,
     })?;
     Ok(())
 }
@@ -1900,7 +3294,10 @@ fn parse_opencode_file(
             tool_input: None,
             tool_output: None,
             links,
+            settings: AgentSettings::default(),
+            attachments: Vec::new(),
             source_path: session_dir.to_string_lossy().to_string(),
+            ingested_at: 0,
         };
         progress.add_produced(SourceKind::Opencode, 1);
         tx_record.send(record)?;
@@ -1918,6 +3315,7 @@ fn parse_opencode_file(
         path: session_dir.to_string_lossy().to_string(),
         state,
         session_id: Some(session_id),
+        malformed: MalformedLines::default(),
     })?;
     Ok(())
 }
@@ -1941,6 +3339,7 @@ fn parse_cursor_file(
 
     let mut buf = Vec::new();
     let mut parsed_bytes = 0u64;
+    let mut malformed = MalformedLines::default();
     while start < mmap.len() {
         let slice = &mmap[start..];
         let rel = memchr(b'\n', slice).unwrap_or(slice.len());
@@ -1959,7 +3358,10 @@ fn parse_cursor_file(
         buf.extend_from_slice(line);
         let value: BorrowedValue = match simd_json::to_borrowed_value(&mut buf) {
             Ok(v) => v,
-            Err(_) => continue,
+            Err(err) => {
+                malformed.push(&mmap, start - advanced, err);
+                continue;
+            }
         };
         let obj = match value.as_object() {
             Some(o) => o,
@@ -2007,7 +3409,10 @@ fn parse_cursor_file(
                             tool_input,
                             tool_output: None,
                             links: cursor_record_links(&task.path, &session_id, turn_id),
+                            settings: AgentSettings::default(),
+                            attachments: Vec::new(),
                             source_path: source_path.clone(),
+                            ingested_at: 0,
                         };
                         progress.add_produced(SourceKind::Cursor, 1);
                         tx_record.send(record)?;
@@ -2036,7 +3441,10 @@ fn parse_cursor_file(
                             tool_input: None,
                             tool_output,
                             links: cursor_record_links(&task.path, &session_id, turn_id),
+                            settings: AgentSettings::default(),
+                            attachments: Vec::new(),
                             source_path: source_path.clone(),
+                            ingested_at: 0,
                         };
                         progress.add_produced(SourceKind::Cursor, 1);
                         tx_record.send(record)?;
@@ -2061,7 +3469,10 @@ fn parse_cursor_file(
                 tool_input: None,
                 tool_output: None,
                 links: cursor_record_links(&task.path, &session_id, turn_id),
+                settings: AgentSettings::default(),
+                attachments: Vec::new(),
                 source_path: source_path.clone(),
+                ingested_at: 0,
             };
             progress.add_produced(SourceKind::Cursor, 1);
             tx_record.send(record)?;
@@ -2083,6 +3494,7 @@ fn parse_cursor_file(
         path: source_path,
         state,
         session_id: Some(session_id),
+        malformed,
     })?;
     Ok(())
 }
@@ -2120,6 +3532,7 @@ fn parse_pi_file(
         }
     }
     let mut parsed_bytes = 0u64;
+    let mut malformed = MalformedLines::default();
     while start < mmap.len() {
         let slice = &mmap[start..];
         let rel = memchr(b'\n', slice).unwrap_or(slice.len());
@@ -2138,7 +3551,10 @@ fn parse_pi_file(
         buf.extend_from_slice(line);
         let value: BorrowedValue = match simd_json::to_borrowed_value(&mut buf) {
             Ok(v) => v,
-            Err(_) => continue,
+            Err(err) => {
+                malformed.push(&mmap, start - advanced, err);
+                continue;
+            }
         };
         let obj = match value.as_object() {
             Some(o) => o,
@@ -2184,7 +3600,10 @@ fn parse_pi_file(
                 tool_input: None,
                 tool_output: None,
                 links: base_links,
+                settings: AgentSettings::default(),
+                attachments: Vec::new(),
                 source_path: source_path.clone(),
+                ingested_at: 0,
             };
             progress.add_produced(SourceKind::Pi, 1);
             tx_record.send(record)?;
@@ -2216,7 +3635,10 @@ fn parse_pi_file(
                 tool_input: None,
                 tool_output: None,
                 links: base_links,
+                settings: AgentSettings::default(),
+                attachments: Vec::new(),
                 source_path: source_path.clone(),
+                ingested_at: 0,
             };
             progress.add_produced(SourceKind::Pi, 1);
             tx_record.send(record)?;
@@ -2297,7 +3719,10 @@ fn parse_pi_file(
                             tool_input,
                             tool_output: None,
                             links,
+                            settings: AgentSettings::default(),
+                            attachments: Vec::new(),
                             source_path: source_path.clone(),
+                            ingested_at: 0,
                         };
                         progress.add_produced(SourceKind::Pi, 1);
                         tx_record.send(record)?;
@@ -2322,7 +3747,10 @@ fn parse_pi_file(
                     tool_input: None,
                     tool_output: None,
                     links: base_links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Pi, 1);
                 tx_record.send(record)?;
@@ -2360,7 +3788,10 @@ fn parse_pi_file(
                     tool_input: None,
                     tool_output,
                     links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Pi, 1);
                 tx_record.send(record)?;
@@ -2410,7 +3841,10 @@ fn parse_pi_file(
                         Some(output)
                     },
                     links: base_links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Pi, 1);
                 tx_record.send(record)?;
@@ -2434,7 +3868,10 @@ fn parse_pi_file(
                     tool_input: None,
                     tool_output: None,
                     links: base_links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Pi, 1);
                 tx_record.send(record)?;
@@ -2458,6 +3895,7 @@ fn parse_pi_file(
         path: source_path,
         state,
         session_id: Some(session_id),
+        malformed,
     })?;
     Ok(())
 }
@@ -2468,7 +3906,7 @@ fn parse_iso_millis(input: &str) -> Option<u64> {
         .map(|dt| dt.with_timezone(&Utc).timestamp_millis() as u64)
 }
 
-fn project_from_claude_path(path: &Path) -> String {
+pub(crate) fn project_from_claude_path(path: &Path) -> String {
     let Some(parent) = path
         .parent()
         .and_then(|p| p.file_name())
@@ -2542,6 +3980,7 @@ fn parse_copilot_session(
     let mut call_id_to_name: HashMap<String, String> = HashMap::new();
 
     let mut parsed_bytes = 0u64;
+    let mut malformed = MalformedLines::default();
     while start < mmap.len() {
         let slice = &mmap[start..];
         let rel = memchr(b'\n', slice).unwrap_or(slice.len());
@@ -2558,7 +3997,10 @@ fn parse_copilot_session(
         }
         let value: serde_json::Value = match serde_json::from_slice(line) {
             Ok(v) => v,
-            Err(_) => continue,
+            Err(err) => {
+                malformed.push(&mmap, start - advanced, err);
+                continue;
+            }
         };
         let Some(obj) = value.as_object() else {
             continue;
@@ -2614,7 +4056,10 @@ fn parse_copilot_session(
                     tool_input: None,
                     tool_output: None,
                     links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Copilot, 1);
                 tx_record.send(record)?;
@@ -2642,7 +4087,10 @@ fn parse_copilot_session(
                     tool_input: None,
                     tool_output: None,
                     links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Copilot, 1);
                 tx_record.send(record)?;
@@ -2681,7 +4129,10 @@ fn parse_copilot_session(
                     tool_input,
                     tool_output: None,
                     links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Copilot, 1);
                 tx_record.send(record)?;
@@ -2721,7 +4172,10 @@ fn parse_copilot_session(
                     tool_input: None,
                     tool_output,
                     links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Copilot, 1);
                 tx_record.send(record)?;
@@ -2749,7 +4203,10 @@ fn parse_copilot_session(
                     tool_input: None,
                     tool_output: None,
                     links,
+                    settings: AgentSettings::default(),
+                    attachments: Vec::new(),
                     source_path: source_path.clone(),
+                    ingested_at: 0,
                 };
                 progress.add_produced(SourceKind::Copilot, 1);
                 tx_record.send(record)?;
@@ -2773,10 +4230,210 @@ fn parse_copilot_session(
         path: source_path,
         state,
         session_id: Some(session_id),
+        malformed,
     })?;
     Ok(())
 }
 
+/// Reads a ChatGPT export's `conversations.json` whole; it is never resumed
+/// (see [`ChatGptAdapter`]).
+fn parse_chatgpt_export(task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+    let contents = std::fs::read(&task.path)?;
+    let conversations: Vec<serde_json::Value> =
+        serde_json::from_slice(&contents).map_err(|err| {
+            anyhow!(
+                "{} is not a ChatGPT conversations.json: {err}",
+                task.path.display()
+            )
+        })?;
+    let source_path = task.path.to_string_lossy().to_string();
+    for conversation in &conversations {
+        for mut record in chatgpt_conversation_records(conversation, &source_path) {
+            if record.role != SUMMARY_ROLE {
+                record.doc_id = ctx.next_doc_id();
+            }
+            ctx.send(record)?;
+        }
+    }
+    ctx.add_parsed_bytes(SourceKind::ChatGPT, task.size);
+    ctx.finish_file(task, task.size, 0)
+}
+
+/// Records of one exported conversation, doc ids unset: its title as a
+/// summary, then the messages on the branch last shown in ChatGPT, from the
+/// root to `current_node`. System, hidden and empty messages are skipped;
+/// messages the assistant sends to a tool (code, browsing) become tool calls.
+fn chatgpt_conversation_records(
+    conversation: &serde_json::Value,
+    source_path: &str,
+) -> Vec<Record> {
+    let Some(session_id) = conversation
+        .get("conversation_id")
+        .or_else(|| conversation.get("id"))
+        .and_then(|v| v.as_str())
+    else {
+        return Vec::new();
+    };
+    let Some(mapping) = conversation.get("mapping").and_then(|v| v.as_object()) else {
+        return Vec::new();
+    };
+    let mut branch = Vec::new();
+    let mut node_id = conversation.get("current_node").and_then(|v| v.as_str());
+    while let Some(id) = node_id
+        && let Some(node) = mapping.get(id)
+        && branch.len() < mapping.len()
+    {
+        branch.push(node);
+        node_id = node.get("parent").and_then(|v| v.as_str());
+    }
+    branch.reverse();
+    if branch.is_empty() {
+        branch = mapping.values().collect();
+        branch.sort_by_key(|node| chatgpt_millis(node.pointer("/message/create_time")));
+    }
+
+    let base = Record {
+        source: SourceKind::ChatGPT,
+        doc_id: 0,
+        ts: chatgpt_millis(conversation.get("create_time")).unwrap_or(0),
+        project: CHATGPT_PROJECT.to_string(),
+        session_id: session_id.to_string(),
+        turn_id: 0,
+        role: String::new(),
+        text: String::new(),
+        tool_name: None,
+        tool_input: None,
+        tool_output: None,
+        links: RecordLinks::default(),
+        settings: AgentSettings::default(),
+        attachments: Vec::new(),
+        source_path: source_path.to_string(),
+        ingested_at: 0,
+    };
+    let mut records = Vec::new();
+    let mut ts = base.ts;
+    let mut parent_event_id = None;
+    for message in branch.iter().filter_map(|node| node.get("message")) {
+        if message
+            .pointer("/metadata/is_visually_hidden_from_conversation")
+            .and_then(|v| v.as_bool())
+            == Some(true)
+        {
+            continue;
+        }
+        let recipient = message
+            .get("recipient")
+            .and_then(|v| v.as_str())
+            .unwrap_or("all");
+        let (role, tool_name) = match message.pointer("/author/role").and_then(|v| v.as_str()) {
+            Some("user") => ("user", None),
+            Some("assistant") if recipient != "all" => ("tool_use", Some(recipient.to_string())),
+            Some("assistant") => ("assistant", None),
+            Some("tool") => (
+                "tool_result",
+                message
+                    .pointer("/author/name")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string),
+            ),
+            _ => continue,
+        };
+        let content = message.get("content").unwrap_or(&serde_json::Value::Null);
+        let text = content
+            .get("parts")
+            .and_then(text_from_json)
+            .or_else(|| {
+                ["text", "result"].iter().find_map(|key| {
+                    content
+                        .get(*key)
+                        .and_then(|v| v.as_str())
+                        .map(|text| text.trim().to_string())
+                })
+            })
+            .unwrap_or_default();
+        let attachments = chatgpt_attachments(content);
+        if text.is_empty() && attachments.is_empty() {
+            continue;
+        }
+        ts = chatgpt_millis(message.get("create_time")).unwrap_or(ts);
+        let event_id = message
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        records.push(Record {
+            ts,
+            turn_id: records.len() as u32,
+            role: role.to_string(),
+            tool_input: (role == "tool_use").then(|| text.clone()),
+            tool_output: (role == "tool_result").then(|| text.clone()),
+            text,
+            tool_name,
+            links: RecordLinks {
+                event_id: event_id.clone(),
+                parent_event_id: parent_event_id.take(),
+                ..RecordLinks::default()
+            },
+            settings: AgentSettings {
+                model: message
+                    .pointer("/metadata/model_slug")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string),
+                ..AgentSettings::default()
+            },
+            attachments,
+            ..base.clone()
+        });
+        parent_event_id = event_id;
+    }
+    if let Some(title) = conversation
+        .get("title")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|title| !title.is_empty() && !records.is_empty())
+    {
+        records.insert(
+            0,
+            Record {
+                ts: records[0].ts,
+                role: SUMMARY_ROLE.to_string(),
+                text: title.to_string(),
+                ..base
+            },
+        );
+    }
+    records
+}
+
+/// A ChatGPT timestamp, seconds since the epoch as a float, in millis.
+fn chatgpt_millis(value: Option<&serde_json::Value>) -> Option<u64> {
+    value
+        .and_then(|v| v.as_f64())
+        .filter(|secs| *secs > 0.0)
+        .map(|secs| (secs * 1000.0) as u64)
+}
+
+/// Images pasted into a ChatGPT message. The export keeps them as separate
+/// files, so only their presence is recorded.
+fn chatgpt_attachments(content: &serde_json::Value) -> Vec<Attachment> {
+    content
+        .get("parts")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|part| {
+            part.get("content_type").and_then(|v| v.as_str()) == Some("image_asset_pointer")
+        })
+        .map(|_| Attachment {
+            kind: "image".to_string(),
+            media_type: None,
+            filename: None,
+            size: None,
+            path: None,
+            text: None,
+        })
+        .collect()
+}
+
 fn read_copilot_workspace(events_path: &Path) -> CopilotWorkspace {
     let Some(dir) = events_path.parent() else {
         return CopilotWorkspace::default();
@@ -3334,9 +4991,10 @@ fn flush_embeddings(
         return Ok(0);
     }
 
-    // Batch embed all texts at once (ONNX Runtime handles internal parallelism)
+    // Batch embed all texts at once (the embedder splits them into its configured batch size)
     let texts: Vec<&str> = items.iter().map(|(_, text, _)| text.as_str()).collect();
     let embeddings = embedder.embed_texts(&texts)?;
+    embedder.pause_after_batch();
 
     // Add embeddings to index
     let mut count = 0;
@@ -3346,10 +5004,11 @@ fn flush_embeddings(
         progress.add_embedded(*source, 1);
         count += 1;
     }
+    progress.embed_batch_done();
     Ok(count)
 }
 
-fn compute_totals(tasks: &[FileTask]) -> [u64; SOURCE_COUNT] {
+fn compute_totals<'a>(tasks: impl Iterator<Item = &'a FileTask>) -> [u64; SOURCE_COUNT] {
     let mut totals = [0u64; SOURCE_COUNT];
     for task in tasks {
         let remaining = task.size.saturating_sub(task.offset);
@@ -3358,7 +5017,7 @@ fn compute_totals(tasks: &[FileTask]) -> [u64; SOURCE_COUNT] {
     totals
 }
 
-fn compute_file_totals(tasks: &[FileTask]) -> [u64; SOURCE_COUNT] {
+fn compute_file_totals<'a>(tasks: impl Iterator<Item = &'a FileTask>) -> [u64; SOURCE_COUNT] {
     let mut totals = [0u64; SOURCE_COUNT];
     for task in tasks {
         totals[task.source.idx()] += 1;
@@ -3454,7 +5113,7 @@ mod tests {
     use super::*;
     use crate::config::{IndexedToolContentLimits, Paths};
     use crate::embed::{EmbedRuntimeConfig, ModelChoice};
-    use crate::index::SearchIndex;
+    use crate::index::{QueryTuning, SearchIndex};
     use crate::test_support::{EnvVarGuard, env_lock};
     use crate::vector::VectorIndex;
     use std::fs;
@@ -3474,6 +5133,10 @@ mod tests {
             model,
             embed_runtime: EmbedRuntimeConfig::default(),
             tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
         }
     }
 
@@ -3516,11 +5179,8 @@ mod tests {
             turn_id: doc_id as u32,
             role: role.to_string(),
             text: text.to_string(),
-            tool_name: None,
-            tool_input: None,
-            tool_output: None,
-            links: RecordLinks::default(),
             source_path: format!("source-{doc_id}.jsonl"),
+            ..Default::default()
         }
     }
 
@@ -3546,6 +5206,8 @@ mod tests {
         let limits = IndexedToolContentLimits {
             input_bytes: 1024,
             output_bytes: 2048,
+            code: false,
+            ..IndexedToolContentLimits::default()
         };
         let plain_text = format!("plain-begin{}plain-end", "w".repeat(4096));
         let plain = record(1, "assistant", &plain_text);
@@ -3611,6 +5273,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn record_sender_skips_oversized_records_and_nested_transcripts() {
+        let transcript: String = (0..20)
+            .map(|i| {
+                format!(
+                    "{{\"parentUuid\":\"p{i}\",\"sessionId\":\"s\",\"type\":\"user\",\"message\":{{\"content\":\"hi\"}}}}\n"
+                )
+            })
+            .collect();
+        let pasted = record(1, "user", &format!("look at this log:\n{transcript}"));
+        let mut read = record(2, "tool_result", "ok");
+        read.tool_output = Some(transcript.clone());
+        // Quoted inside another transcript's JSON, on a single line.
+        let escaped = record(
+            3,
+            "tool_result",
+            &serde_json::to_string(&transcript).unwrap(),
+        );
+        let prose = record(
+            4,
+            "assistant",
+            &format!(
+                "{}\"sessionId\" and \"parentUuid\" link entries.",
+                "x".repeat(400)
+            ),
+        );
+        let large = record(5, "assistant", &"y".repeat(4096));
+
+        let limits = IndexedToolContentLimits {
+            record_bytes: Some(3072),
+            ..IndexedToolContentLimits::default()
+        };
+        let (raw_tx, rx) = unbounded();
+        let tx = RecordSender::new(raw_tx, limits);
+        for record in [pasted, read, escaped, prose, large] {
+            tx.send(record).expect("send");
+        }
+        assert_eq!(tx.skipped(), (1, 3));
+        drop(tx);
+        let kept: Vec<u64> = rx.iter().map(|record| record.doc_id).collect();
+        assert_eq!(kept, vec![4]);
+
+        let limits = IndexedToolContentLimits {
+            skip_nested_transcripts: false,
+            ..IndexedToolContentLimits::default()
+        };
+        let (raw_tx, rx) = unbounded();
+        let tx = RecordSender::new(raw_tx, limits);
+        tx.send(record(6, "user", &transcript)).expect("send");
+        drop(tx);
+        assert_eq!(rx.iter().count(), 1);
+    }
+
     fn assert_truncated_content(content: &str, max_bytes: usize, prefix: &str, suffix: &str) {
         assert!(content.len() <= max_bytes);
         assert!(content.starts_with(prefix));
@@ -3631,165 +5346,815 @@ mod tests {
     }
 
     #[test]
-    fn cursor_session_id_uses_agent_transcripts_session_directory() {
-        let path = Path::new(
-            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
-             11111111-1111-1111-1111-111111111111/\
-             11111111-1111-1111-1111-111111111111.jsonl",
-        );
+    fn cursor_session_id_uses_agent_transcripts_session_directory() {
+        let path = Path::new(
+            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
+             11111111-1111-1111-1111-111111111111/\
+             11111111-1111-1111-1111-111111111111.jsonl",
+        );
+
+        assert_eq!(
+            cursor_session_id_from_path(path),
+            "11111111-1111-1111-1111-111111111111"
+        );
+    }
+
+    #[test]
+    fn cursor_session_id_strips_direct_transcript_extension() {
+        let path = Path::new(
+            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
+             11111111-1111-1111-1111-111111111111.jsonl",
+        );
+
+        assert_eq!(
+            cursor_session_id_from_path(path),
+            "11111111-1111-1111-1111-111111111111"
+        );
+    }
+
+    #[test]
+    fn cursor_session_id_uses_parent_session_for_subagent_transcripts() {
+        let path = Path::new(
+            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
+             11111111-1111-1111-1111-111111111111/subagents/\
+             22222222-2222-2222-2222-222222222222.jsonl",
+        );
+
+        assert_eq!(
+            cursor_session_id_from_path(path),
+            "11111111-1111-1111-1111-111111111111"
+        );
+    }
+
+    #[test]
+    fn cursor_parent_transcripts_start_at_cached_turn_id() {
+        let path = Path::new(
+            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
+             11111111-1111-1111-1111-111111111111/\
+             11111111-1111-1111-1111-111111111111.jsonl",
+        );
+
+        assert_eq!(cursor_initial_turn_id(path, 0), 0);
+        assert_eq!(cursor_initial_turn_id(path, 42), 42);
+    }
+
+    #[test]
+    fn cursor_subagent_transcripts_use_reserved_turn_range() {
+        let path = Path::new(
+            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
+             11111111-1111-1111-1111-111111111111/subagents/\
+             22222222-2222-2222-2222-222222222222.jsonl",
+        );
+
+        let initial = cursor_initial_turn_id(path, 0);
+        assert!(initial >= CURSOR_SUBAGENT_TURN_BASE);
+        assert_eq!(cursor_initial_turn_id(path, initial + 3), initial + 3);
+    }
+
+    #[test]
+    fn cursor_record_links_mark_subagent_parent_session() {
+        let path = Path::new(
+            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
+             11111111-1111-1111-1111-111111111111/subagents/\
+             22222222-2222-2222-2222-222222222222.jsonl",
+        );
+
+        let links = cursor_record_links(path, "11111111-1111-1111-1111-111111111111", 42);
+
+        assert_eq!(
+            links.event_id.as_deref(),
+            Some("22222222-2222-2222-2222-222222222222:42")
+        );
+        assert_eq!(
+            links.parent_session_id.as_deref(),
+            Some("11111111-1111-1111-1111-111111111111")
+        );
+        assert_eq!(links.thread_source.as_deref(), Some("subagent"));
+        assert_eq!(links.conversation_kind.as_deref(), Some("subagent"));
+    }
+
+    #[test]
+    fn opencode_session_links_preserve_parent_id() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let project = tmp.path().join("project");
+        fs::create_dir_all(&project).expect("create opencode project");
+        fs::write(
+            project.join("ses_child.json"),
+            r#"{"id":"ses_child","parentID":"ses_parent","projectID":"global"}"#,
+        )
+        .expect("write opencode session");
+
+        let links = opencode_session_links_from_root(tmp.path(), "ses_child");
+
+        assert_eq!(links.parent_session_id.as_deref(), Some("ses_parent"));
+        assert_eq!(links.thread_source.as_deref(), Some("fork"));
+        assert_eq!(links.conversation_kind.as_deref(), Some("fork"));
+    }
+
+    #[test]
+    fn opencode_session_links_by_id_caches_metadata_tree() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let project = tmp.path().join("project");
+        fs::create_dir_all(&project).expect("create opencode project");
+        fs::write(
+            project.join("ses_child.json"),
+            r#"{"id":"ses_child","parentID":"ses_parent","projectID":"global"}"#,
+        )
+        .expect("write child session");
+        fs::write(
+            project.join("ses_main.json"),
+            r#"{"id":"ses_main","projectID":"global"}"#,
+        )
+        .expect("write main session");
+
+        let links_by_id = opencode_session_links_by_id_from_root(tmp.path());
+        let child_links = links_by_id.get("ses_child").expect("child links");
+        let main_links = links_by_id.get("ses_main").expect("main links");
+
+        assert_eq!(links_by_id.len(), 2);
+        assert_eq!(child_links.parent_session_id.as_deref(), Some("ses_parent"));
+        assert_eq!(child_links.thread_source.as_deref(), Some("fork"));
+        assert_eq!(child_links.conversation_kind.as_deref(), Some("fork"));
+        assert_eq!(main_links.parent_session_id, None);
+        assert_eq!(main_links.thread_source, None);
+        assert_eq!(main_links.conversation_kind.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn codex_session_meta_preserves_fork_and_subagent_links() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let path = tmp
+            .path()
+            .join("rollout-2026-05-22T13-17-11-019e5155-b507-7d83-8c3d-9ecee5f93f12.jsonl");
+        fs::write(
+            &path,
+            r#"{"timestamp":"2026-05-22T20:17:12.595Z","type":"session_meta","payload":{"id":"019e5155-b507-7d83-8c3d-9ecee5f93f12","forked_from_id":"019e5117-c673-7660-b218-af0489416e0f","cwd":"/tmp/project","source":{"subagent":{"thread_spawn":{"parent_thread_id":"019e5117-c673-7660-b218-af0489416e0f","depth":1}}},"thread_source":"subagent"}}"#
+                .to_string()
+                + "\n",
+        )
+        .expect("write codex session");
+
+        let meta = read_codex_session_meta_until(&path, fs::metadata(&path).unwrap().len())
+            .expect("read codex meta");
+
+        assert_eq!(meta.session_id, "019e5155-b507-7d83-8c3d-9ecee5f93f12");
+        assert_eq!(meta.project, "project");
+        assert_eq!(
+            meta.links.parent_session_id.as_deref(),
+            Some("019e5117-c673-7660-b218-af0489416e0f")
+        );
+        assert_eq!(meta.links.thread_source.as_deref(), Some("subagent"));
+        assert_eq!(meta.links.conversation_kind.as_deref(), Some("subagent"));
+    }
+
+    #[test]
+    fn codex_turn_context_sets_agent_settings() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let path = tmp.path().join("rollout-settings.jsonl");
+        fs::write(
+            &path,
+            r#"{"timestamp":"2026-05-22T20:17:12.595Z","type":"session_meta","payload":{"id":"sess-settings","cwd":"/tmp/project"}}
+{"timestamp":"2026-05-22T20:17:13.000Z","type":"turn_context","payload":{"cwd":"/tmp/project","model":"gpt-5","approval_policy":"on-request","sandbox_policy":{"mode":"workspace-write","network_access":false}}}
+{"timestamp":"2026-05-22T20:18:13.000Z","type":"turn_context","payload":{"cwd":"/tmp/project","model":"o3","approval_policy":"never","sandbox_policy":"read-only"}}
+"#,
+        )
+        .expect("write codex session");
+
+        let meta = read_codex_session_meta_until(&path, fs::metadata(&path).unwrap().len())
+            .expect("read codex meta");
+
+        assert_eq!(
+            meta.settings,
+            AgentSettings {
+                model: Some("o3".to_string()),
+                sandbox: Some("read-only".to_string()),
+                approval_policy: Some("never".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn ingest_claude_compact_summaries_get_their_own_role() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let claude_root = tmp.path().join("claude-projects");
+        let project_root = claude_root.join("-Users-nico-Code-memex");
+        fs::create_dir_all(&project_root).expect("create claude project");
+        fs::write(
+            project_root.join("sess-compact.jsonl"),
+            r#"{"type":"user","uuid":"u1","parentUuid":null,"sessionId":"sess-compact","timestamp":"2026-03-11T01:23:43.844Z","isCompactSummary":true,"message":{"content":"This session is being continued from a previous conversation. Summary: login test retries"}}
+{"type":"user","uuid":"u2","parentUuid":"u1","sessionId":"sess-compact","timestamp":"2026-03-11T01:24:00.000Z","message":{"content":"now make the retries configurable"}}
+"#,
+        )
+        .expect("write claude fixture");
+
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = IngestOptions {
+            claude_source: claude_root,
+            include_agents: false,
+            include_codex: false,
+            include_opencode: false,
+            include_cursor: false,
+            include_pi: false,
+            include_copilot: false,
+            embeddings: false,
+            backfill_embeddings: false,
+            model: ModelChoice::default(),
+            embed_runtime: EmbedRuntimeConfig::default(),
+            tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
+        };
+        ingest_all(&paths, &index, &options).expect("ingest");
+
+        let mut records = index
+            .records_by_session_id("sess-compact")
+            .expect("records by session");
+        records.sort_by_key(|record| record.turn_id);
+        assert_eq!(records[0].role, COMPACT_SUMMARY_ROLE);
+        assert_eq!(records[1].role, "user");
+
+        // The title comes from the user's own first message.
+        let store = AnalyticsStore::open(analytics_path(&paths.state)).expect("analytics");
+        let rows = store
+            .query_sessions(
+                None,
+                None,
+                None,
+                crate::analytics::ProjectGrouping::Flat,
+                None,
+            )
+            .expect("sessions");
+        assert_eq!(
+            rows[0].title.as_deref(),
+            Some("now make the retries configurable")
+        );
+    }
+
+    #[test]
+    fn import_archive_reads_claude_and_codex_files_from_one_dir() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let archive_dir = tmp.path().join("old-laptop");
+        let nested = archive_dir.join("2023/projects/-Users-nico-Code-api");
+        fs::create_dir_all(&nested).expect("create archive dir");
+        fs::write(
+            nested.join("sess-old.jsonl"),
+            r#"{"type":"user","uuid":"u1","parentUuid":null,"sessionId":"sess-old","timestamp":"2023-04-02T10:00:00.000Z","message":{"content":"why is the archive flaky"}}
+"#,
+        )
+        .expect("write claude fixture");
+        fs::write(
+            archive_dir.join("rollout-2023-05-01T09-00-00-codex-old.jsonl"),
+            r#"{"timestamp":"2023-05-01T09:00:00.000Z","type":"session_meta","payload":{"id":"codex-old","cwd":"/tmp/api"}}
+{"timestamp":"2023-05-01T09:00:01.000Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"bump the archive retention"}]}}
+"#,
+        )
+        .expect("write codex fixture");
+
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = ingest_options(false, ModelChoice::default());
+        let archive = ArchiveImport {
+            dir: archive_dir.clone(),
+            writer_heap_bytes: 64 * 1024 * 1024,
+        };
+        let report = import_archive(&paths, &index, &options, &archive).expect("import");
+        assert_eq!(report.files_scanned, 2);
+        assert_eq!(report.records_added, 2);
+
+        let claude = index.records_by_session_id("sess-old").expect("claude");
+        assert_eq!(claude[0].source, SourceKind::Claude);
+        let codex = index.records_by_session_id("codex-old").expect("codex");
+        assert_eq!(codex[0].source, SourceKind::CodexSession);
+
+        // Unchanged files are skipped when an import is rerun.
+        let report = import_archive(&paths, &index, &options, &archive).expect("reimport");
+        assert_eq!(report.records_added, 0);
+        assert_eq!(report.files_skipped, 2);
+    }
+
+    #[test]
+    fn import_chatgpt_maps_conversations_to_sessions() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let export = tmp.path().join("conversations.json");
+        // "u1" was edited into "u1b"; the export keeps both branches and
+        // current_node points at the one last shown.
+        let conversations = |reply: &str| {
+            serde_json::json!([{
+                "title": "Regex for semver",
+                "create_time": 1_700_000_000.0,
+                "conversation_id": "conv-1",
+                "current_node": "a2",
+                "mapping": {
+                    "root": {"id": "root", "message": null, "parent": null},
+                    "s0": {"id": "s0", "parent": "root", "message": {
+                        "id": "s0", "author": {"role": "system"},
+                        "content": {"content_type": "text", "parts": [""]},
+                        "metadata": {"is_visually_hidden_from_conversation": true}}},
+                    "u1": {"id": "u1", "parent": "s0", "message": {
+                        "id": "u1", "author": {"role": "user"}, "create_time": 1_700_000_001.0,
+                        "content": {"content_type": "text", "parts": ["old question"]}}},
+                    "u1b": {"id": "u1b", "parent": "s0", "message": {
+                        "id": "u1b", "author": {"role": "user"}, "create_time": 1_700_000_002.5,
+                        "content": {"content_type": "text", "parts": ["write a regex for semver"]}}},
+                    "c1": {"id": "c1", "parent": "u1b", "message": {
+                        "id": "c1", "author": {"role": "assistant"}, "recipient": "python",
+                        "create_time": 1_700_000_003.0,
+                        "content": {"content_type": "code", "language": "python", "text": "import re"}}},
+                    "t1": {"id": "t1", "parent": "c1", "message": {
+                        "id": "t1", "author": {"role": "tool", "name": "python"},
+                        "content": {"content_type": "execution_output", "text": "ok"}}},
+                    "a2": {"id": "a2", "parent": "t1", "message": {
+                        "id": "a2", "author": {"role": "assistant"}, "recipient": "all",
+                        "create_time": 1_700_000_004.0,
+                        "metadata": {"model_slug": "gpt-4o"},
+                        "content": {"content_type": "text", "parts": [reply]}}}
+                }
+            }])
+            .to_string()
+        };
+        fs::write(&export, conversations("use ^\\d+\\.\\d+\\.\\d+$")).expect("write export");
+
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = ingest_options(false, ModelChoice::default());
+        let report =
+            import_chatgpt(&paths, &index, &options, &export, 64 * 1024 * 1024).expect("import");
+        assert_eq!(report.records_added, 4);
+
+        let mut records = index.records_by_session_id("conv-1").expect("records");
+        records.sort_by_key(|record| record.turn_id);
+        let roles: Vec<&str> = records.iter().map(|record| record.role.as_str()).collect();
+        assert_eq!(roles, ["user", "tool_use", "tool_result", "assistant"]);
+        assert!(records.iter().all(|r| r.source == SourceKind::ChatGPT));
+        assert_eq!(records[0].text, "write a regex for semver");
+        assert_eq!(records[0].ts, 1_700_000_002_500);
+        assert_eq!(records[1].tool_name.as_deref(), Some("python"));
+        assert_eq!(records[2].ts, 1_700_000_003_000);
+        assert_eq!(records[3].project, CHATGPT_PROJECT);
+        assert_eq!(records[3].settings.model.as_deref(), Some("gpt-4o"));
+        let store = AnalyticsStore::open(analytics_path(&paths.state)).expect("analytics");
+        let rows = store
+            .query_sessions(
+                None,
+                None,
+                None,
+                crate::analytics::ProjectGrouping::Flat,
+                None,
+            )
+            .expect("sessions");
+        assert_eq!(rows[0].title.as_deref(), Some("Regex for semver"));
+
+        let report =
+            import_chatgpt(&paths, &index, &options, &export, 64 * 1024 * 1024).expect("reimport");
+        assert_eq!(report.records_added, 0);
+        assert_eq!(report.files_skipped, 1);
+
+        // A newer export replaces the conversations instead of duplicating them.
+        fs::write(&export, conversations("try the semver crate")).expect("rewrite export");
+        import_chatgpt(&paths, &index, &options, &export, 64 * 1024 * 1024).expect("update");
+        let records = index.records_by_session_id("conv-1").expect("records");
+        assert_eq!(records.len(), 4);
+        assert!(records.iter().any(|r| r.text == "try the semver crate"));
+
+        fs::write(&export, "{}").expect("write bad export");
+        assert!(import_chatgpt(&paths, &index, &options, &export, 64 * 1024 * 1024).is_err());
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped_and_reported_with_line_numbers() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let claude_root = tmp.path().join("claude-projects");
+        let project_root = claude_root.join("-Users-nico-Code-memex");
+        fs::create_dir_all(&project_root).expect("create claude project");
+        let user = |uuid: &str| {
+            format!(
+                r#"{{"type":"user","uuid":"{uuid}","parentUuid":null,"sessionId":"sess-bad","timestamp":"2026-03-11T01:23:43.844Z","message":{{"content":"message {uuid}"}}}}"#
+            )
+        };
+        let file = project_root.join("sess-bad.jsonl");
+        fs::write(
+            &file,
+            format!(
+                "{}\n{{\"type\":\"user\",\"uuid\":\n{}\n",
+                user("u1"),
+                user("u2")
+            ),
+        )
+        .expect("write claude fixture");
+
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = IngestOptions {
+            claude_source: claude_root,
+            include_agents: false,
+            include_codex: false,
+            include_opencode: false,
+            include_cursor: false,
+            include_pi: false,
+            include_copilot: false,
+            embeddings: false,
+            backfill_embeddings: false,
+            model: ModelChoice::default(),
+            embed_runtime: EmbedRuntimeConfig::default(),
+            tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
+        };
+        let report = ingest_all(&paths, &index, &options).expect("ingest");
+        assert_eq!(report.records_added, 2);
+        assert_eq!(report.malformed_lines, 1);
+        let key = file.to_string_lossy().to_string();
+        let errors_path = ingest_errors_path(&paths.state);
+        let errors = IngestErrors::load(&errors_path).expect("errors");
+        let lines: Vec<u64> = errors.files[&key].lines.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [2]);
+        assert_eq!(errors.files[&key].source, "claude");
+
+        // Appended lines are read from where the last ingest stopped but
+        // still numbered from the top of the file.
+        let mut contents = fs::read_to_string(&file).expect("read");
+        contents.push_str(&format!("not json\n{}\n", user("u3")));
+        fs::write(&file, contents).expect("append");
+        let report = ingest_all(&paths, &index, &options).expect("resume");
+        assert_eq!(report.records_added, 1);
+        let errors = IngestErrors::load(&errors_path).expect("errors");
+        let lines: Vec<u64> = errors.files[&key].lines.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [2, 4]);
+
+        fs::write(&file, format!("{}\n", user("u1"))).expect("rewrite");
+        ingest_all(&paths, &index, &options).expect("reread");
+        let errors = IngestErrors::load(&errors_path).expect("errors");
+        assert!(errors.files.is_empty());
+    }
+
+    #[test]
+    fn scan_decisions_explain_why_files_are_read_or_skipped() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let claude_root = tmp.path().join("claude-projects");
+        let project_root = claude_root.join("-Users-nico-Code-memex");
+        fs::create_dir_all(&project_root).expect("create claude project");
+        let line = |uuid: &str| {
+            format!(
+                r#"{{"type":"user","uuid":"{uuid}","parentUuid":null,"sessionId":"sess-scan","timestamp":"2026-03-11T01:23:43.844Z","message":{{"content":"message {uuid}"}}}}"#
+            ) + "\n"
+        };
+        let file = project_root.join("sess-scan.jsonl");
+        let agent = project_root.join("agent-1234.jsonl");
+        fs::write(&file, line("u1")).expect("write session");
+        fs::write(&agent, line("a1")).expect("write agent session");
 
-        assert_eq!(
-            cursor_session_id_from_path(path),
-            "11111111-1111-1111-1111-111111111111"
-        );
-    }
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = IngestOptions {
+            claude_source: claude_root,
+            ..ingest_options(false, ModelChoice::default())
+        };
+        let planned = plan_scan(&paths, &options).expect("plan");
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].decision, ScanDecision::New);
 
-    #[test]
-    fn cursor_session_id_strips_direct_transcript_extension() {
-        let path = Path::new(
-            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
-             11111111-1111-1111-1111-111111111111.jsonl",
-        );
+        let report = ingest_all(&paths, &index, &options).expect("ingest");
+        assert_eq!(report.scan.len(), 1);
+        assert_eq!(report.scan[0].decision, ScanDecision::New);
 
-        assert_eq!(
-            cursor_session_id_from_path(path),
-            "11111111-1111-1111-1111-111111111111"
-        );
-    }
+        let explained = explain_scan(&paths, &options, &file, 3600).expect("explain");
+        let decision = explained.decision.expect("listed");
+        let ScanDecision::Unchanged(state) = &decision.decision else {
+            panic!("expected unchanged, got {:?}", decision.decision);
+        };
+        assert_eq!(state.size, line("u1").len() as u64);
+        assert!(decision.reason().contains("ingest.json"));
+        assert_eq!(explained.state.as_ref(), Some(state));
+        assert!(explained.scan_cache_fresh);
 
-    #[test]
-    fn cursor_session_id_uses_parent_session_for_subagent_transcripts() {
-        let path = Path::new(
-            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
-             11111111-1111-1111-1111-111111111111/subagents/\
-             22222222-2222-2222-2222-222222222222.jsonl",
-        );
+        fs::write(&file, line("u1") + &line("u2")).expect("append");
+        let explained = explain_scan(&paths, &options, &file, 3600).expect("explain");
+        let decision = explained.decision.expect("listed");
+        assert!(matches!(decision.decision, ScanDecision::Appended(_)));
+        assert!(decision.reason().contains("read on from byte"));
 
-        assert_eq!(
-            cursor_session_id_from_path(path),
-            "11111111-1111-1111-1111-111111111111"
-        );
+        let explained = explain_scan(&paths, &options, &agent, 3600).expect("explain agent");
+        assert!(explained.decision.is_none());
+        assert!(explained.unlisted.unwrap().contains("--include-agents"));
+        let missing = project_root.join("gone.jsonl");
+        let explained = explain_scan(&paths, &options, &missing, 3600).expect("explain missing");
+        assert_eq!(explained.unlisted.as_deref(), Some("it does not exist"));
     }
 
     #[test]
-    fn cursor_parent_transcripts_start_at_cached_turn_id() {
-        let path = Path::new(
-            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
-             11111111-1111-1111-1111-111111111111/\
-             11111111-1111-1111-1111-111111111111.jsonl",
-        );
+    fn ingest_claude_summary_lines_title_the_session() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let claude_root = tmp.path().join("claude-projects");
+        let project_root = claude_root.join("-Users-nico-Code-memex");
+        fs::create_dir_all(&project_root).expect("create claude project");
+        fs::write(
+            project_root.join("sess-title.jsonl"),
+            r#"{"type":"summary","summary":"Fix flaky login test","leafUuid":"a0"}
+{"type":"user","uuid":"u1","parentUuid":null,"sessionId":"sess-title","timestamp":"2026-03-11T01:23:43.844Z","message":{"content":"why does the login test fail?"}}
+"#,
+        )
+        .expect("write claude fixture");
 
-        assert_eq!(cursor_initial_turn_id(path, 0), 0);
-        assert_eq!(cursor_initial_turn_id(path, 42), 42);
-    }
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = IngestOptions {
+            claude_source: claude_root,
+            include_agents: false,
+            include_codex: false,
+            include_opencode: false,
+            include_cursor: false,
+            include_pi: false,
+            include_copilot: false,
+            embeddings: false,
+            backfill_embeddings: false,
+            model: ModelChoice::default(),
+            embed_runtime: EmbedRuntimeConfig::default(),
+            tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
+        };
+        ingest_all(&paths, &index, &options).expect("ingest");
 
-    #[test]
-    fn cursor_subagent_transcripts_use_reserved_turn_range() {
-        let path = Path::new(
-            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
-             11111111-1111-1111-1111-111111111111/subagents/\
-             22222222-2222-2222-2222-222222222222.jsonl",
-        );
+        let records = index
+            .records_by_session_id("sess-title")
+            .expect("records by session");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].role, "user");
+        assert_eq!(records[0].turn_id, 0);
+
+        let store = AnalyticsStore::open(analytics_path(&paths.state)).expect("analytics");
+        let rows = store
+            .query_sessions(
+                None,
+                None,
+                None,
+                crate::analytics::ProjectGrouping::Flat,
+                None,
+            )
+            .expect("sessions");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].title.as_deref(), Some("Fix flaky login test"));
+        assert_eq!(rows[0].message_count, 1);
+    }
+
+    /// Reads `role: text` lines from `*.log` files under `root`. Only enabled
+    /// for ingests whose Claude source is `root`, since the registry is
+    /// shared by every test in the process.
+    struct LogAdapter {
+        root: PathBuf,
+    }
+
+    impl SourceAdapter for LogAdapter {
+        fn kind(&self) -> SourceKind {
+            SourceKind::External("inhouse-log")
+        }
 
-        let initial = cursor_initial_turn_id(path, 0);
-        assert!(initial >= CURSOR_SUBAGENT_TURN_BASE);
-        assert_eq!(cursor_initial_turn_id(path, initial + 3), initial + 3);
-    }
+        fn enabled(&self, options: &IngestOptions) -> bool {
+            options.claude_source == self.root
+        }
 
-    #[test]
-    fn cursor_record_links_mark_subagent_parent_session() {
-        let path = Path::new(
-            "/Users/nico/.cursor/projects/-Users-nico-Code-memex/agent-transcripts/\
-             11111111-1111-1111-1111-111111111111/subagents/\
-             22222222-2222-2222-2222-222222222222.jsonl",
-        );
+        fn scan(&self, _options: &IngestOptions) -> Result<Vec<PathBuf>> {
+            Ok(fs::read_dir(&self.root)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+                .collect())
+        }
 
-        let links = cursor_record_links(path, "11111111-1111-1111-1111-111111111111", 42);
+        fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+            let contents = fs::read_to_string(&task.path)?;
+            let session_id = self.session_id(&task.path).unwrap_or_default();
+            let mut turn_id = task.turn_id;
+            for line in contents[task.offset as usize..].lines() {
+                let Some((role, text)) = line.split_once(": ") else {
+                    continue;
+                };
+                ctx.send(Record {
+                    source: self.kind(),
+                    doc_id: ctx.next_doc_id(),
+                    ts: 1_000 + turn_id as u64,
+                    project: self.project(&task.path).unwrap_or_default(),
+                    session_id: session_id.clone(),
+                    turn_id,
+                    role: role.to_string(),
+                    text: text.to_string(),
+                    source_path: task.path.to_string_lossy().to_string(),
+                    ..Default::default()
+                })?;
+                turn_id += 1;
+            }
+            ctx.add_parsed_bytes(self.kind(), task.size - task.offset);
+            ctx.finish_file(task, task.size, turn_id)
+        }
 
-        assert_eq!(
-            links.event_id.as_deref(),
-            Some("22222222-2222-2222-2222-222222222222:42")
-        );
-        assert_eq!(
-            links.parent_session_id.as_deref(),
-            Some("11111111-1111-1111-1111-111111111111")
-        );
-        assert_eq!(links.thread_source.as_deref(), Some("subagent"));
-        assert_eq!(links.conversation_kind.as_deref(), Some("subagent"));
+        fn session_id(&self, path: &Path) -> Option<String> {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        }
+
+        fn project(&self, _path: &Path) -> Option<String> {
+            Some("inhouse".to_string())
+        }
     }
 
     #[test]
-    fn opencode_session_links_preserve_parent_id() {
+    fn registered_adapters_are_ingested_alongside_builtin_sources() {
         let tmp = tempfile::tempdir().expect("tempdir");
-        let project = tmp.path().join("project");
-        fs::create_dir_all(&project).expect("create opencode project");
+        let root = tmp.path().join("inhouse-agent");
+        fs::create_dir_all(&root).expect("create adapter root");
         fs::write(
-            project.join("ses_child.json"),
-            r#"{"id":"ses_child","parentID":"ses_parent","projectID":"global"}"#,
+            root.join("sess-log.log"),
+            "user: rotate the tantivy index\nassistant: done\n",
         )
-        .expect("write opencode session");
+        .expect("write log fixture");
+        register_adapter(Arc::new(LogAdapter { root: root.clone() })).expect("register adapter");
+        let err = register_adapter(Arc::new(LogAdapter { root: root.clone() }))
+            .expect_err("duplicate name");
+        assert!(err.to_string().contains("already registered"));
 
-        let links = opencode_session_links_from_root(tmp.path(), "ses_child");
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = IngestOptions {
+            claude_source: root,
+            include_agents: false,
+            include_codex: false,
+            include_opencode: false,
+            include_cursor: false,
+            include_pi: false,
+            include_copilot: false,
+            embeddings: false,
+            backfill_embeddings: false,
+            model: ModelChoice::default(),
+            embed_runtime: EmbedRuntimeConfig::default(),
+            tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
+        };
+        let report = ingest_all(&paths, &index, &options).expect("ingest");
+        assert_eq!(report.records_added, 2);
 
-        assert_eq!(links.parent_session_id.as_deref(), Some("ses_parent"));
-        assert_eq!(links.thread_source.as_deref(), Some("fork"));
-        assert_eq!(links.conversation_kind.as_deref(), Some("fork"));
+        let mut records = index
+            .records_by_session_id("sess-log")
+            .expect("records by session");
+        records.sort_by_key(|record| record.turn_id);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].source, SourceKind::External("inhouse-log"));
+        assert_eq!(records[0].source.label(), "inhouse-log");
+        assert_eq!(records[0].project, "inhouse");
+        assert_eq!(records[1].text, "done");
+
+        let report = ingest_all(&paths, &index, &options).expect("reingest");
+        assert_eq!(report.records_added, 0);
+        assert_eq!(report.files_skipped, 1);
     }
 
     #[test]
-    fn opencode_session_links_by_id_caches_metadata_tree() {
+    fn scoped_reindex_rereads_only_matching_files() {
         let tmp = tempfile::tempdir().expect("tempdir");
-        let project = tmp.path().join("project");
-        fs::create_dir_all(&project).expect("create opencode project");
-        fs::write(
-            project.join("ses_child.json"),
-            r#"{"id":"ses_child","parentID":"ses_parent","projectID":"global"}"#,
-        )
-        .expect("write child session");
-        fs::write(
-            project.join("ses_main.json"),
-            r#"{"id":"ses_main","projectID":"global"}"#,
-        )
-        .expect("write main session");
+        let claude_root = tmp.path().join("claude-projects");
+        let project_root = claude_root.join("-Users-nico-Code-memex");
+        fs::create_dir_all(&project_root).expect("create claude project");
+        for (session, text) in [("sess-a", "rotate the index"), ("sess-b", "fix the tui")] {
+            fs::write(
+                project_root.join(format!("{session}.jsonl")),
+                format!(
+                    r#"{{"type":"user","uuid":"u1","parentUuid":null,"sessionId":"{session}","timestamp":"2026-03-11T01:23:43.844Z","message":{{"content":"{text}"}}}}
+"#
+                ),
+            )
+            .expect("write claude fixture");
+        }
 
-        let links_by_id = opencode_session_links_by_id_from_root(tmp.path());
-        let child_links = links_by_id.get("ses_child").expect("child links");
-        let main_links = links_by_id.get("ses_main").expect("main links");
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let mut options = ingest_options(false, ModelChoice::default());
+        options.claude_source = claude_root;
+        ingest_all(&paths, &index, &options).expect("ingest");
+        let doc_id = |session: &str| {
+            let records = index.records_by_session_id(session).expect("records");
+            assert_eq!(records.len(), 1);
+            records[0].doc_id
+        };
+        let (a, b) = (doc_id("sess-a"), doc_id("sess-b"));
 
-        assert_eq!(links_by_id.len(), 2);
-        assert_eq!(child_links.parent_session_id.as_deref(), Some("ses_parent"));
-        assert_eq!(child_links.thread_source.as_deref(), Some("fork"));
-        assert_eq!(child_links.conversation_kind.as_deref(), Some("fork"));
-        assert_eq!(main_links.parent_session_id, None);
-        assert_eq!(main_links.thread_source, None);
-        assert_eq!(main_links.conversation_kind.as_deref(), Some("main"));
+        let scope = ReindexScope {
+            source: None,
+            path: Some(glob::Pattern::new("**/sess-a.jsonl").expect("glob")),
+        };
+        let report = reindex_scoped(&paths, &index, &options, &scope).expect("reindex");
+        assert_eq!(report.records_added, 1);
+        assert_eq!(report.files_skipped, 1);
+        assert_ne!(doc_id("sess-a"), a);
+        assert_eq!(doc_id("sess-b"), b);
+
+        let scope = ReindexScope {
+            source: Some(SourceFilter::Codex),
+            path: None,
+        };
+        let report = reindex_scoped(&paths, &index, &options, &scope).expect("reindex");
+        assert_eq!(report.records_added, 0);
+        assert_eq!(doc_id("sess-b"), b);
     }
 
     #[test]
-    fn codex_session_meta_preserves_fork_and_subagent_links() {
+    fn ingest_claude_records_attachment_metadata() {
         let tmp = tempfile::tempdir().expect("tempdir");
-        let path = tmp
-            .path()
-            .join("rollout-2026-05-22T13-17-11-019e5155-b507-7d83-8c3d-9ecee5f93f12.jsonl");
+        let claude_root = tmp.path().join("claude-projects");
+        let project_root = claude_root.join("-Users-nico-Code-memex");
+        fs::create_dir_all(&project_root).expect("create claude project");
         fs::write(
-            &path,
-            r#"{"timestamp":"2026-05-22T20:17:12.595Z","type":"session_meta","payload":{"id":"019e5155-b507-7d83-8c3d-9ecee5f93f12","forked_from_id":"019e5117-c673-7660-b218-af0489416e0f","cwd":"/tmp/project","source":{"subagent":{"thread_spawn":{"parent_thread_id":"019e5117-c673-7660-b218-af0489416e0f","depth":1}}},"thread_source":"subagent"}}"#
-                .to_string()
-                + "\n",
+            project_root.join("sess-attach.jsonl"),
+            r#"{"type":"user","uuid":"u1","parentUuid":null,"sessionId":"sess-attach","timestamp":"2026-03-11T01:23:43.844Z","message":{"content":[{"type":"text","text":"why is this layout broken"},{"type":"image","source":{"type":"base64","media_type":"image/png","data":"aGVsbG8="}}]}}
+{"type":"user","uuid":"u2","parentUuid":"u1","sessionId":"sess-attach","timestamp":"2026-03-11T01:24:43.844Z","message":{"content":[{"type":"document","title":"spec.pdf","source":{"type":"base64","media_type":"application/pdf","data":"AAAA"}}]}}
+"#,
         )
-        .expect("write codex session");
+        .expect("write claude fixture");
 
-        let meta = read_codex_session_meta_until(&path, fs::metadata(&path).unwrap().len())
-            .expect("read codex meta");
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("ensure dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let options = IngestOptions {
+            claude_source: claude_root,
+            include_agents: false,
+            include_codex: false,
+            include_opencode: false,
+            include_cursor: false,
+            include_pi: false,
+            include_copilot: false,
+            embeddings: false,
+            backfill_embeddings: false,
+            model: ModelChoice::default(),
+            embed_runtime: EmbedRuntimeConfig::default(),
+            tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
+        };
+        ingest_all(&paths, &index, &options).expect("ingest");
 
-        assert_eq!(meta.session_id, "019e5155-b507-7d83-8c3d-9ecee5f93f12");
-        assert_eq!(meta.project, "project");
+        let mut records = index
+            .records_by_session_id("sess-attach")
+            .expect("records by session");
+        records.sort_by_key(|record| record.turn_id);
+        assert_eq!(records.len(), 2);
         assert_eq!(
-            meta.links.parent_session_id.as_deref(),
-            Some("019e5117-c673-7660-b218-af0489416e0f")
+            records[0].attachments,
+            vec![Attachment {
+                kind: "image".to_string(),
+                media_type: Some("image/png".to_string()),
+                filename: None,
+                size: Some(5),
+                path: None,
+                text: None,
+            }]
         );
-        assert_eq!(meta.links.thread_source.as_deref(), Some("subagent"));
-        assert_eq!(meta.links.conversation_kind.as_deref(), Some("subagent"));
+        assert_eq!(records[1].text, "[document: spec.pdf]");
+        assert_eq!(records[1].attachments[0].size, Some(3));
+
+        let (query, filters) = crate::index::extract_query_filters("has:image");
+        let hits = index
+            .search(&crate::index::QueryOptions {
+                query,
+                project: None,
+                role: None,
+                tool: None,
+                session_id: None,
+                source: None,
+                since: None,
+                until: None,
+                as_of: None,
+                settings: filters.settings,
+                has: filters.has,
+                edited: filters.edited,
+                commands: filters.commands,
+                files: filters.files,
+                langs: filters.langs,
+                errors: filters.errors,
+                require_all_terms: false,
+                fuzzy_distance: 0,
+                tuning: QueryTuning::default(),
+                limit: 10,
+            })
+            .expect("search");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1.text, "why is this layout broken");
     }
 
     #[test]
@@ -3824,6 +6189,10 @@ mod tests {
             model: ModelChoice::default(),
             embed_runtime: EmbedRuntimeConfig::default(),
             tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
         };
 
         let report = ingest_all(&paths, &index, &options).expect("ingest");
@@ -3838,7 +6207,9 @@ mod tests {
         assert_eq!(records[0].role, "user");
         assert_eq!(records[0].links.event_id.as_deref(), Some("u1"));
         assert_eq!(records[0].links.conversation_kind.as_deref(), Some("main"));
+        assert_eq!(records[0].links.thread, None);
         assert_eq!(records[1].role, "tool_use");
+        assert_eq!(records[1].links.thread.as_deref(), Some("a1"));
         assert_eq!(records[1].links.event_id.as_deref(), Some("tool-claude"));
         assert_eq!(records[1].links.parent_event_id.as_deref(), Some("a1"));
         assert_eq!(
@@ -3867,6 +6238,7 @@ mod tests {
             Some("sidechain")
         );
         assert_eq!(records[3].role, "tool_result");
+        assert_eq!(records[3].links.thread.as_deref(), Some("a1"));
         assert_eq!(
             records[3].links.event_id.as_deref(),
             Some("r1:tool_result:tool-claude")
@@ -4085,6 +6457,22 @@ mod tests {
         assert_eq!(files, vec![session]);
     }
 
+    #[test]
+    fn codex_session_roots_honor_codex_home() {
+        let _guard = env_lock();
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let codex_home = tmp.path().join("codex");
+        let _env = EnvVarGuard::set_os(&[("CODEX_HOME", Some(codex_home.as_os_str()))]);
+
+        assert_eq!(
+            codex_session_roots(),
+            vec![
+                codex_home.join("sessions"),
+                codex_home.join("archived_sessions"),
+            ]
+        );
+    }
+
     #[test]
     fn pi_sessions_root_honors_session_dir_override() {
         let _guard = env_lock();
@@ -4193,6 +6581,10 @@ mod tests {
             model: ModelChoice::default(),
             embed_runtime: EmbedRuntimeConfig::default(),
             tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
         };
 
         let report = ingest_all(&paths, &index, &options).expect("ingest");
@@ -4374,11 +6766,11 @@ mod tests {
         let tx_record = RecordSender::new(raw_tx_record, IndexedToolContentLimits::default());
         let (tx_update, rx_update) = unbounded();
         let next_doc_id = AtomicU64::new(1);
-        let progress = Arc::new(Progress::new(
-            [0, 0, 0, 0, 0, 0, meta.len()],
-            [0, 0, 0, 0, 0, 0, 1],
-            false,
-        ));
+        let mut totals = [0; SOURCE_COUNT];
+        let mut file_totals = [0; SOURCE_COUNT];
+        totals[SourceKind::Copilot.idx()] = meta.len();
+        file_totals[SourceKind::Copilot.idx()] = 1;
+        let progress = Arc::new(Progress::new(totals, file_totals, false));
 
         parse_copilot_session(&task, &tx_record, &tx_update, &next_doc_id, &progress)
             .expect("parse copilot session");
@@ -4435,13 +6827,8 @@ mod tests {
                 ts: 1_780_291_200_000,
                 project: "memex".to_string(),
                 session_id: "11111111-1111-4111-8111-111111111111".to_string(),
-                turn_id: 0,
                 role: "user".to_string(),
                 text: "Find the parser".to_string(),
-                tool_name: None,
-                tool_input: None,
-                tool_output: None,
-                links: RecordLinks::default(),
                 source_path: tmp
                     .path()
                     .join(
@@ -4449,6 +6836,7 @@ mod tests {
                     )
                     .to_string_lossy()
                     .to_string(),
+                ..Default::default()
             })
             .expect("send record");
         drop(tx_record);
@@ -4459,10 +6847,13 @@ mod tests {
             do_backfill_embeddings: false,
             vector_dir,
             analytics_path: tmp.path().join("state").join("analytics.sqlite"),
+            frecency_path: tmp.path().join("state").join("projects.json"),
             progress,
             model: ModelChoice::default(),
             embed_runtime: EmbedRuntimeConfig::default(),
             tool_content_limits: IndexedToolContentLimits::default(),
+            bulk_heap_bytes: None,
+            archive_tier: None,
         };
 
         let (records_added, records_embedded) =
@@ -0,0 +1,630 @@
+//! What the agents decided and learned about a project, mined from its
+//! assistant messages for `memex knowledge`. Sentences stating a decision
+//! ("we went with", "decided to") or a fact ("lives in", "defaults to") are
+//! kept, the same statement made in several sessions is merged into one
+//! entry, and the entries become an outline grouped by the file each one
+//! mentions. Entries are saved, so a refresh only reads records indexed
+//! since the last one.
+
+use crate::index::SearchIndex;
+use crate::mentions::{normalize_file, text_files};
+use crate::topics::terms;
+use crate::types::Record;
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MIN_STATEMENT_CHARS: usize = 25;
+const MAX_STATEMENT_CHARS: usize = 280;
+/// Cosine similarity from which two statements say the same thing.
+const SIMILAR_EMBEDDING: f32 = 0.88;
+/// Share of their words two statements need in common to be merged when
+/// there are no embeddings.
+const SIMILAR_TERMS: f32 = 0.6;
+/// Words a statement must share with an entry to be compared with it.
+const MIN_SHARED_TERMS: usize = 2;
+/// Statements embedded per call.
+const EMBED_BATCH: usize = 256;
+
+/// Openings of the assistant narrating its next step rather than stating
+/// anything.
+const NARRATION: &[&str] = &[
+    "let me",
+    "now let",
+    "let's",
+    "i'll",
+    "i will",
+    "i'm going to",
+    "i need to",
+    "now i",
+    "next, i",
+    "first, i",
+    "i can see",
+    "i see",
+    "looking at",
+    "perfect",
+    "great",
+    "excellent",
+    "done",
+    "sure",
+    "okay",
+];
+
+static DECISION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)\b(?:decided|decision|chose|opted|settled on|went with|going with|go with|switched to|switching to|instead of|rather than|from now on|going forward)\b",
+    )
+    .expect("decision regex")
+});
+static FACT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)\b(?:uses|is stored|are stored|lives in|live in|is defined in|are defined in|is configured|are configured|defaults to|depends on|requires|is responsible for|is handled by|is used for|runs on|expects|must|always|never)\b",
+    )
+    .expect("fact regex")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementKind {
+    Decision,
+    Fact,
+}
+
+/// A sentence of an assistant message that states a decision or a fact.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub text: String,
+    /// First file the sentence mentions.
+    pub area: Option<String>,
+    pub session_id: String,
+    pub ts: u64,
+}
+
+/// Statements merged into one: the newest wording, and every session that
+/// made it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeEntry {
+    pub kind: StatementKind,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub area: Option<String>,
+    pub sessions: Vec<String>,
+    pub first_ts: u64,
+    pub last_ts: u64,
+    /// Statements merged in, weighting `embedding` as more join.
+    pub statements: usize,
+    /// Mean embedding of the merged statements; empty without embeddings.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embedding: Vec<f32>,
+}
+
+/// A project's merged statements, saved between refreshes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Knowledge {
+    pub project: String,
+    /// Embedding model statements were merged by, or "lexical".
+    pub method: String,
+    /// Newest `ingested_at` read, so a refresh reads only later records.
+    pub indexed_through: u64,
+    pub updated_at: u64,
+    pub entries: Vec<KnowledgeEntry>,
+}
+
+/// Embeds statement texts for merging, one vector per text.
+pub type Embed<'a> = &'a mut dyn FnMut(&[&str]) -> Result<Vec<Vec<f32>>>;
+
+impl Knowledge {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let knowledge = serde_json::from_str(&data)?;
+        Ok(knowledge)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Folds `statements`, oldest first, into the entries: each joins the
+    /// entry saying the same thing, else starts its own. With `embeddings`,
+    /// one per statement, sameness is cosine similarity; without, shared
+    /// words.
+    pub fn merge(&mut self, statements: Vec<Statement>, embeddings: Option<Vec<Vec<f32>>>) {
+        let mut exact: HashMap<(StatementKind, String), usize> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| ((entry.kind, normalized_text(&entry.text)), idx))
+            .collect();
+        let mut postings: HashMap<String, Vec<usize>> = HashMap::new();
+        for (idx, entry) in self.entries.iter().enumerate() {
+            for term in terms(&entry.text).collect::<HashSet<_>>() {
+                postings.entry(term).or_default().push(idx);
+            }
+        }
+        for (at, statement) in statements.into_iter().enumerate() {
+            let embedding = embeddings
+                .as_ref()
+                .and_then(|embeddings| embeddings.get(at))
+                .map(|embedding| normalized(embedding.clone()));
+            let statement_terms: HashSet<String> = terms(&statement.text).collect();
+            let key = (statement.kind, normalized_text(&statement.text));
+            let found = exact.get(&key).copied().or_else(|| {
+                self.similar(
+                    statement.kind,
+                    &statement_terms,
+                    embedding.as_deref(),
+                    &postings,
+                )
+            });
+            let idx = match found {
+                Some(idx) => {
+                    let entry = &mut self.entries[idx];
+                    if !entry.sessions.contains(&statement.session_id) {
+                        entry.sessions.push(statement.session_id);
+                    }
+                    entry.first_ts = entry.first_ts.min(statement.ts);
+                    if statement.ts >= entry.last_ts {
+                        entry.last_ts = statement.ts;
+                        entry.text = statement.text;
+                        entry.area = statement.area.or(entry.area.take());
+                    }
+                    if let Some(embedding) = embedding
+                        && entry.embedding.len() == embedding.len()
+                    {
+                        let weight = entry.statements as f32;
+                        for (mean, value) in entry.embedding.iter_mut().zip(&embedding) {
+                            *mean = (*mean * weight + value) / (weight + 1.0);
+                        }
+                        entry.embedding = normalized(std::mem::take(&mut entry.embedding));
+                    }
+                    entry.statements += 1;
+                    idx
+                }
+                None => {
+                    self.entries.push(KnowledgeEntry {
+                        kind: statement.kind,
+                        text: statement.text,
+                        area: statement.area,
+                        sessions: vec![statement.session_id],
+                        first_ts: statement.ts,
+                        last_ts: statement.ts,
+                        statements: 1,
+                        embedding: embedding.unwrap_or_default(),
+                    });
+                    self.entries.len() - 1
+                }
+            };
+            exact.insert(key, idx);
+            for term in statement_terms {
+                let list = postings.entry(term).or_default();
+                if !list.contains(&idx) {
+                    list.push(idx);
+                }
+            }
+        }
+    }
+
+    /// The entry of `kind` closest to a statement, among those sharing
+    /// enough of its words, when it is close enough to merge.
+    fn similar(
+        &self,
+        kind: StatementKind,
+        statement_terms: &HashSet<String>,
+        embedding: Option<&[f32]>,
+        postings: &HashMap<String, Vec<usize>>,
+    ) -> Option<usize> {
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for term in statement_terms {
+            for &idx in postings.get(term).into_iter().flatten() {
+                *shared.entry(idx).or_default() += 1;
+            }
+        }
+        shared
+            .into_iter()
+            .filter(|&(idx, count)| count >= MIN_SHARED_TERMS && self.entries[idx].kind == kind)
+            .filter_map(|(idx, _)| {
+                let entry = &self.entries[idx];
+                let (score, threshold) = match embedding {
+                    Some(embedding) if entry.embedding.len() == embedding.len() => {
+                        (dot(embedding, &entry.embedding), SIMILAR_EMBEDDING)
+                    }
+                    _ => (
+                        jaccard(statement_terms, &terms(&entry.text).collect()),
+                        SIMILAR_TERMS,
+                    ),
+                };
+                (score >= threshold).then_some((idx, score))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(idx, _)| idx)
+    }
+
+    /// The entries as a Markdown outline: a section per file, the most
+    /// discussed first and statements about no file last, decisions before
+    /// facts. Only entries made in at least `min_sessions` sessions, and at
+    /// most `limit` per section (0 for all), those made most often.
+    pub fn outline(&self, min_sessions: usize, limit: usize) -> String {
+        let kept: Vec<&KnowledgeEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.sessions.len() >= min_sessions)
+            .collect();
+        let sessions: HashSet<&str> = kept
+            .iter()
+            .flat_map(|entry| entry.sessions.iter().map(String::as_str))
+            .collect();
+        let mut areas: HashMap<Option<&str>, Vec<&KnowledgeEntry>> = HashMap::new();
+        for entry in &kept {
+            areas.entry(entry.area.as_deref()).or_default().push(entry);
+        }
+        let mut areas: Vec<(Option<&str>, Vec<&KnowledgeEntry>)> = areas.into_iter().collect();
+        let weight = |entries: &[&KnowledgeEntry]| -> usize {
+            entries.iter().map(|entry| entry.sessions.len()).sum()
+        };
+        areas.sort_by(|a, b| {
+            a.0.is_none()
+                .cmp(&b.0.is_none())
+                .then(weight(&b.1).cmp(&weight(&a.1)))
+                .then(a.0.cmp(&b.0))
+        });
+
+        let mut out = format!("# What the agents know about {}\n\n", self.project);
+        out.push_str(&format!(
+            "{} statements from {} sessions, refreshed {}.\n",
+            kept.len(),
+            sessions.len(),
+            date(self.updated_at)
+        ));
+        if kept.is_empty() {
+            out.push_str(
+                "\nNo decisions or facts found in the project's assistant messages yet.\n",
+            );
+        }
+        for (area, mut entries) in areas {
+            entries.sort_by(|a, b| {
+                b.sessions
+                    .len()
+                    .cmp(&a.sessions.len())
+                    .then(b.last_ts.cmp(&a.last_ts))
+            });
+            if limit > 0 {
+                entries.truncate(limit);
+            }
+            entries.sort_by_key(|entry| entry.kind);
+            out.push_str(&format!("\n## {}\n\n", area.unwrap_or("General")));
+            for entry in entries {
+                let label = match entry.kind {
+                    StatementKind::Decision => "**Decision:** ",
+                    StatementKind::Fact => "",
+                };
+                let count = entry.sessions.len();
+                out.push_str(&format!(
+                    "- {label}{} _({count} {}, latest {})_\n",
+                    entry.text,
+                    if count == 1 { "session" } else { "sessions" },
+                    date(entry.last_ts)
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// Where `project`'s merged statements are saved.
+pub fn knowledge_path(state_dir: &Path, project: &str) -> PathBuf {
+    let name: String = project
+        .chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || matches!(ch, '-' | '_' | '.') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    state_dir.join("knowledge").join(format!("{name}.json"))
+}
+
+/// Reads `knowledge.project`'s assistant messages indexed since the last
+/// refresh into its entries. Returns how many statements were read.
+pub fn refresh(
+    index: &SearchIndex,
+    knowledge: &mut Knowledge,
+    embed: Option<Embed<'_>>,
+) -> Result<usize> {
+    let project = knowledge.project.clone();
+    let after = knowledge.indexed_through;
+    let mut through = after;
+    let mut statements = Vec::new();
+    index.for_each_record(|record| {
+        if record.project != project || record.ingested_at <= after {
+            return Ok(());
+        }
+        through = through.max(record.ingested_at);
+        statements.extend(extract_statements(&record));
+        Ok(())
+    })?;
+    statements.sort_by_key(|statement| statement.ts);
+    let embeddings = match embed {
+        Some(embed) => {
+            let mut embeddings = Vec::with_capacity(statements.len());
+            for chunk in statements.chunks(EMBED_BATCH) {
+                let texts: Vec<&str> = chunk
+                    .iter()
+                    .map(|statement| statement.text.as_str())
+                    .collect();
+                embeddings.extend(embed(&texts)?);
+            }
+            Some(embeddings)
+        }
+        None => None,
+    };
+    let read = statements.len();
+    knowledge.merge(statements, embeddings);
+    knowledge.indexed_through = through;
+    knowledge.updated_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    Ok(read)
+}
+
+/// Decisions and facts an assistant message states, leaving out code
+/// blocks, tables, headings and quotes.
+pub fn extract_statements(record: &Record) -> Vec<Statement> {
+    if record.role != "assistant" {
+        return Vec::new();
+    }
+    let mut statements = Vec::new();
+    let mut in_code = false;
+    for line in record.text.lines() {
+        let line = line.trim();
+        if line.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || line.starts_with(['|', '#', '>']) {
+            continue;
+        }
+        for sentence in sentences(strip_list_marker(line)) {
+            let Some((kind, text)) = classify(sentence) else {
+                continue;
+            };
+            let area = text_files(&text).next().map(normalize_file);
+            statements.push(Statement {
+                kind,
+                area,
+                text,
+                session_id: record.session_id.clone(),
+                ts: record.ts,
+            });
+        }
+    }
+    statements
+}
+
+fn strip_list_marker(line: &str) -> &str {
+    if let Some(rest) = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))
+    {
+        return rest.trim_start();
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    match line[digits..]
+        .strip_prefix(". ")
+        .or_else(|| line[digits..].strip_prefix(") "))
+    {
+        Some(rest) if digits > 0 => rest.trim_start(),
+        _ => line,
+    }
+}
+
+/// `line` cut after each `.`, `!` or `?` that ends a word.
+fn sentences(line: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((at, ch)) = chars.next() {
+        if matches!(ch, '.' | '!' | '?')
+            && chars.peek().is_none_or(|(_, next)| next.is_whitespace())
+        {
+            let end = at + ch.len_utf8();
+            out.push(line[start..end].trim());
+            start = end;
+        }
+    }
+    out.push(line[start..].trim());
+    out.retain(|sentence| !sentence.is_empty());
+    out
+}
+
+/// Whether `sentence` states a decision or a fact, and its text without
+/// Markdown emphasis.
+fn classify(sentence: &str) -> Option<(StatementKind, String)> {
+    let text = sentence.replace("**", "").replace("__", "");
+    let text = text.trim();
+    let chars = text.chars().count();
+    if !(MIN_STATEMENT_CHARS..=MAX_STATEMENT_CHARS).contains(&chars) || text.ends_with(['?', ':']) {
+        return None;
+    }
+    let decision = DECISION.is_match(text);
+    let lower = text.to_lowercase();
+    if !decision && NARRATION.iter().any(|opening| lower.starts_with(opening)) {
+        return None;
+    }
+    let kind = if decision {
+        StatementKind::Decision
+    } else if FACT.is_match(text) {
+        StatementKind::Fact
+    } else {
+        return None;
+    };
+    Some((kind, text.to_string()))
+}
+
+/// Lowercased words, for telling restatements of the same text apart from
+/// new statements.
+fn normalized_text(text: &str) -> String {
+    text.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = dot(&vector, &vector).sqrt();
+    if norm > 0.0 {
+        for value in &mut vector {
+            *value /= norm;
+        }
+    }
+    vector
+}
+
+fn date(ts: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ts as i64)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SourceKind;
+
+    fn assistant(doc_id: u64, session_id: &str, ts: u64, ingested_at: u64, text: &str) -> Record {
+        Record {
+            source: SourceKind::Claude,
+            doc_id,
+            ts,
+            project: "memex".to_string(),
+            session_id: session_id.to_string(),
+            turn_id: doc_id as u32,
+            role: "assistant".to_string(),
+            text: text.to_string(),
+            source_path: format!("{session_id}.jsonl"),
+            ingested_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mines_decisions_and_facts_and_merges_them_across_refreshes() {
+        let statements = extract_statements(&assistant(
+            1,
+            "s1",
+            1_000,
+            1,
+            "Let me check the index first.\n\
+             We went with tantivy instead of sqlite FTS for the search index.\n\
+             ```rust\nlet always = true; // must never run\n```\n\
+             - The embedding cache lives in `src/vector.rs` next to the index.\n\
+             | table | always |\n\
+             Does this look right?",
+        ));
+        let kinds: Vec<(StatementKind, Option<&str>)> = statements
+            .iter()
+            .map(|statement| (statement.kind, statement.area.as_deref()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (StatementKind::Decision, None),
+                (StatementKind::Fact, Some("src/vector.rs")),
+            ]
+        );
+
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create_for_ingest(tmp.path()).expect("index");
+        let mut writer = index.writer().expect("writer");
+        for record in [
+            assistant(
+                1,
+                "s1",
+                1_000,
+                10,
+                "We went with tantivy instead of sqlite FTS for the search index.",
+            ),
+            assistant(
+                2,
+                "s2",
+                2_000,
+                20,
+                "As before, we went with tantivy instead of sqlite FTS for the index.",
+            ),
+        ] {
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+
+        let mut knowledge = Knowledge {
+            project: "memex".to_string(),
+            method: "lexical".to_string(),
+            ..Knowledge::default()
+        };
+        assert_eq!(refresh(&index, &mut knowledge, None).expect("refresh"), 2);
+        assert_eq!(knowledge.entries.len(), 1);
+        assert_eq!(knowledge.entries[0].sessions, ["s1", "s2"]);
+        assert_eq!(knowledge.indexed_through, 20);
+
+        index
+            .add_record(
+                &mut writer,
+                &assistant(
+                    3,
+                    "s3",
+                    3_000,
+                    30,
+                    "The scan cache defaults to a five minute TTL in config.rs.",
+                ),
+            )
+            .expect("add");
+        writer.commit().expect("commit");
+        assert_eq!(refresh(&index, &mut knowledge, None).expect("refresh"), 1);
+        assert_eq!(knowledge.entries.len(), 2);
+
+        let outline = knowledge.outline(1, 0);
+        assert!(outline.starts_with("# What the agents know about memex\n"));
+        assert!(outline.contains("2 statements from 3 sessions"));
+        let config = outline.find("## config.rs").expect("file section");
+        let general = outline.find("## General").expect("general section");
+        assert!(config < general);
+        assert!(outline.contains("- **Decision:** As before, we went with tantivy"));
+        assert!(outline.contains("_(2 sessions, latest 1970-01-01)_"));
+        assert!(!knowledge.outline(2, 0).contains("scan cache"));
+
+        let path = knowledge_path(tmp.path(), "-Users-nico/memex");
+        knowledge.save(&path).expect("save");
+        assert!(path.ends_with("knowledge/-Users-nico_memex.json"));
+        assert_eq!(Knowledge::load(&path).expect("load").entries.len(), 2);
+    }
+}
@@ -0,0 +1,252 @@
+//! Language of a record's text, indexed for `lang:` queries, and the
+//! translation command the TUI preview runs on messages in other languages.
+
+use crate::shell::shell_command;
+use crate::types::Record;
+use anyhow::{Result, anyhow};
+use std::io::Write;
+use std::process::Stdio;
+
+/// Words counted before a Latin-script text is given a language.
+const MIN_WORDS: usize = 4;
+
+/// Common short words of each Latin-script language detected; ties and
+/// texts with too few of them get no language.
+const STOPWORDS: [(&str, &[&str]); 7] = [
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "to", "of", "in", "that", "it", "for", "with", "you",
+            "this", "not", "be", "have", "on", "was", "what", "can", "do", "why", "how",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ich", "ein", "eine", "zu", "mit", "den",
+            "dem", "auf", "es", "sich", "auch", "wie", "wir", "bitte", "noch", "kannst", "du",
+            "für", "dass", "wenn", "aber", "oder", "sind", "hat", "haben", "werden", "warum",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "un", "une", "des", "pas", "que", "qui", "pour",
+            "dans", "ce", "je", "vous", "nous", "avec", "sur", "du", "il", "elle", "mais",
+            "pourquoi",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "es", "una", "que", "no", "en", "por", "para", "con", "se",
+            "lo", "como", "pero", "está", "del", "al", "qué", "puedes",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "gli", "e", "è", "un", "che", "di", "non", "per", "con", "sono", "questo",
+            "come", "ma", "della", "perché", "puoi",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "niet", "dat", "van", "ik", "je", "op", "met", "voor",
+            "zijn", "maar", "ook", "er", "wat", "naar", "waarom",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "e", "é", "um", "uma", "que", "não", "em", "por", "para", "com", "se",
+            "como", "mas", "do", "da", "você", "porque",
+        ],
+    ),
+];
+
+/// ISO 639-1 code of the language most of `text` is written in, or `None`
+/// when it is too short, mostly code, or unclear. Fenced code blocks are
+/// skipped.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut prose = String::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if !in_fence {
+            prose.push_str(line);
+            prose.push('\n');
+        }
+    }
+    if let Some(lang) = script_language(&prose) {
+        return Some(lang);
+    }
+
+    let words: Vec<String> = prose
+        .split(|ch: char| !ch.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() < MIN_WORDS {
+        return None;
+    }
+    let mut scores: Vec<(&'static str, usize)> = STOPWORDS
+        .iter()
+        .map(|(lang, stopwords)| {
+            let hits = words
+                .iter()
+                .filter(|word| stopwords.contains(&word.as_str()))
+                .count();
+            (*lang, hits)
+        })
+        .collect();
+    scores.sort_by_key(|(_, hits)| std::cmp::Reverse(*hits));
+    let (lang, best) = scores[0];
+    let runner_up = scores[1].1;
+    (best >= 2 && best > runner_up && best * 8 >= words.len()).then_some(lang)
+}
+
+/// Language of a text mostly written in a non-Latin script.
+fn script_language(text: &str) -> Option<&'static str> {
+    let mut letters = 0usize;
+    let mut counts: [usize; 9] = [0; 9];
+    const LANGS: [&str; 9] = ["ru", "el", "ar", "he", "ko", "ja", "zh", "th", "hi"];
+    for ch in text.chars().filter(|ch| ch.is_alphabetic()) {
+        letters += 1;
+        let slot = match ch as u32 {
+            0x0400..=0x04FF => 0,
+            0x0370..=0x03FF => 1,
+            0x0600..=0x06FF => 2,
+            0x0590..=0x05FF => 3,
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => 4,
+            0x3040..=0x30FF => 5,
+            0x4E00..=0x9FFF => 6,
+            0x0E00..=0x0E7F => 7,
+            0x0900..=0x097F => 8,
+            _ => continue,
+        };
+        counts[slot] += 1;
+    }
+    // Kana marks Japanese even when most characters are kanji.
+    let cjk = counts[5] + counts[6];
+    if counts[5] > 0 && cjk * 2 > letters {
+        return Some("ja");
+    }
+    let (slot, &count) = counts.iter().enumerate().max_by_key(|(_, count)| **count)?;
+    (count > 0 && count * 2 > letters).then_some(LANGS[slot])
+}
+
+/// Language indexed for `record`: its messages and notes, not tool calls
+/// and output, which are mostly code.
+pub fn record_language(record: &Record) -> Option<&'static str> {
+    if record.role.starts_with("tool_") {
+        return None;
+    }
+    detect_language(&record.text)
+}
+
+/// The `translate_command` from config.toml and the language it translates
+/// into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translator {
+    pub command: String,
+    pub to: String,
+}
+
+impl Translator {
+    /// Whether a message in `lang` needs translating.
+    pub fn wants(&self, lang: &str) -> bool {
+        !lang.eq_ignore_ascii_case(&self.to)
+    }
+
+    /// Runs the command with `text` on stdin and returns what it prints;
+    /// `{from}` and `{to}` in the command become the language codes.
+    pub fn translate(&self, text: &str, from: &str) -> Result<String> {
+        let command = self
+            .command
+            .replace("{from}", from)
+            .replace("{to}", &self.to);
+        let mut child = shell_command(&command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| anyhow!("failed to run translate_command: {err}"))?;
+        let mut stdin = child.stdin.take().expect("piped stdin");
+        let input = text.to_string();
+        // Written from another thread so a command that prints as it reads
+        // can't fill the pipe and block both sides.
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output()?;
+        let _ = writer.join();
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().next().unwrap_or_default();
+            return Err(anyhow!(
+                "translate_command exited with {}: {reason}",
+                output.status
+            ));
+        }
+        let translated = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if translated.is_empty() {
+            return Err(anyhow!("translate_command printed nothing"));
+        }
+        Ok(translated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_mixed_german_and_english_messages() {
+        assert_eq!(
+            detect_language("Kannst du bitte prüfen, warum der Test nicht durchläuft?"),
+            Some("de")
+        );
+        assert_eq!(
+            detect_language("Can you check why the test is failing on main?"),
+            Some("en")
+        );
+        assert_eq!(
+            detect_language("Pourquoi le build est cassé sur la branche principale ?"),
+            Some("fr")
+        );
+        assert_eq!(detect_language("なぜテストが失敗するのですか"), Some("ja"));
+        assert_eq!(detect_language("Почему тест падает?"), Some("ru"));
+        // Too short, or only code.
+        assert_eq!(detect_language("ok"), None);
+        assert_eq!(
+            detect_language("```rust\nfn main() { let x = 1; }\n```"),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn translator_pipes_text_through_the_command() {
+        let translator = Translator {
+            command: "printf '[{from}>{to}] '; cat".to_string(),
+            to: "en".to_string(),
+        };
+        assert!(translator.wants("de"));
+        assert!(!translator.wants("EN"));
+        assert_eq!(
+            translator
+                .translate("Hallo Welt\n", "de")
+                .expect("translate"),
+            "[de>en] Hallo Welt"
+        );
+        let failing = Translator {
+            command: "echo no model >&2; exit 3".to_string(),
+            to: "en".to_string(),
+        };
+        let err = failing.translate("Hallo", "de").unwrap_err().to_string();
+        assert!(err.contains("no model"), "{err}");
+    }
+}
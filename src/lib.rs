@@ -1,13 +1,51 @@
+pub mod activity;
 pub mod analytics;
+pub mod append;
+pub mod backup;
+pub mod bench;
+pub mod bundle;
 pub mod cli;
 pub mod config;
+pub mod context;
+pub mod elastic;
 pub mod embed;
+pub mod errors;
+pub mod export;
+pub mod graph;
+pub mod health;
+pub mod highlight;
+pub mod hyperlink;
 pub mod index;
 pub mod ingest;
+pub mod knowledge;
+pub mod lang;
+pub mod lock;
+pub mod mentions;
+pub mod moment;
+pub mod notes;
+pub mod ocr;
+pub mod outcomes;
+pub mod patch;
+pub mod power;
 pub mod progress;
+pub mod query;
+pub mod repair;
+pub mod retention;
+pub mod scoring;
+pub mod shell;
+pub mod shell_history;
 pub mod state;
+pub mod stats_export;
+pub mod team;
+pub mod template;
+pub mod text;
+pub mod todos;
+pub mod tokenizer;
+pub mod topics;
 pub mod transfer;
+pub mod trash;
 pub mod tui;
+pub mod turns;
 pub mod types;
 pub mod usage;
 pub mod vector;
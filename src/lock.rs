@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Exclusive lock for anything that writes the data directory: the Tantivy
+/// index, vectors, analytics and ingest state.
+///
+/// Tantivy's own writer lock only covers the index and is taken after a scan
+/// has already run, so two ingests could both scan, race on `ingest.json`, and
+/// one would fail late. Readers never take this lock: Tantivy searchers are
+/// snapshots and vector segments are listed by a manifest that is replaced
+/// atomically, so a read-only process keeps working while another one writes.
+/// The lock is released when the value is dropped or the process exits.
+#[derive(Debug)]
+pub struct WriteLock {
+    _file: File,
+}
+
+impl WriteLock {
+    /// Takes the lock, or returns `None` when another process holds it.
+    pub fn try_acquire(state_dir: &Path) -> Result<Option<Self>> {
+        let mut file = open_lock_file(state_dir)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(err)) => {
+                return Err(err).context("lock memex data directory");
+            }
+        }
+        record_holder(&mut file);
+        Ok(Some(Self { _file: file }))
+    }
+
+    /// Takes the lock, waiting for the current holder to finish first.
+    pub fn acquire(state_dir: &Path) -> Result<Self> {
+        if let Some(lock) = Self::try_acquire(state_dir)? {
+            return Ok(lock);
+        }
+        eprintln!("{}; waiting for it to finish", busy_message(state_dir));
+        let mut file = open_lock_file(state_dir)?;
+        file.lock().context("lock memex data directory")?;
+        record_holder(&mut file);
+        Ok(Self { _file: file })
+    }
+}
+
+pub fn write_lock_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("write.lock")
+}
+
+/// Process id of the current (or last) lock holder, if it left one.
+pub fn lock_holder(state_dir: &Path) -> Option<u32> {
+    fs::read_to_string(write_lock_path(state_dir))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Human-readable explanation for a busy lock.
+pub fn busy_message(state_dir: &Path) -> String {
+    match lock_holder(state_dir) {
+        Some(pid) => format!("another memex process (pid {pid}) is updating the index"),
+        None => "another memex process is updating the index".to_string(),
+    }
+}
+
+fn open_lock_file(state_dir: &Path) -> Result<File> {
+    fs::create_dir_all(state_dir)?;
+    let path = write_lock_path(state_dir);
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("open {}", path.display()))
+}
+
+/// Best effort: the pid only feeds status messages.
+fn record_holder(file: &mut File) {
+    let _ = file.set_len(0);
+    let _ = file.seek(SeekFrom::Start(0));
+    let _ = write!(file, "{}", std::process::id());
+    let _ = file.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_writer_sees_the_lock_until_the_first_drops_it() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let first = WriteLock::try_acquire(tmp.path())
+            .expect("lock")
+            .expect("free");
+        assert!(WriteLock::try_acquire(tmp.path()).expect("lock").is_none());
+        // Windows locks are mandatory, so the pid can't be read while held.
+        if cfg!(unix) {
+            assert_eq!(lock_holder(tmp.path()), Some(std::process::id()));
+            assert!(busy_message(tmp.path()).contains(&std::process::id().to_string()));
+        }
+
+        drop(first);
+        assert!(WriteLock::try_acquire(tmp.path()).expect("lock").is_some());
+    }
+}
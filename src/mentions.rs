@@ -0,0 +1,269 @@
+//! File paths records mention: paths in user, assistant and tool call text,
+//! file arguments of tool calls and files that patches edit. They back
+//! `file:` queries and `memex files`.
+
+use crate::patch::{normalize_path, record_edits};
+use crate::types::Record;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Paths indexed per record at most; past this a record is a file listing,
+/// not a mention.
+const MAX_FILES_PER_RECORD: usize = 64;
+
+/// Tool input keys whose value names a file.
+const FILE_KEYS: [&str; 8] = [
+    "file_path",
+    "filePath",
+    "notebook_path",
+    "path",
+    "paths",
+    "filename",
+    "target_file",
+    "file",
+];
+
+/// Extensions that make a bare name like `main.rs` a file; anything with a
+/// `/` only needs some extension.
+const FILE_EXTENSIONS: [&str; 52] = [
+    "rs", "py", "js", "mjs", "cjs", "ts", "tsx", "jsx", "go", "java", "kt", "swift", "c", "h",
+    "cc", "cpp", "hpp", "cs", "rb", "php", "sh", "zsh", "bash", "fish", "md", "mdx", "txt", "toml",
+    "yaml", "yml", "json", "jsonl", "lock", "sql", "html", "css", "scss", "vue", "svelte", "xml",
+    "ini", "cfg", "conf", "proto", "lua", "ex", "exs", "scala", "dart", "tf", "nix", "zig",
+];
+
+const EXTENSIONLESS_FILES: [&str; 5] =
+    ["Makefile", "Dockerfile", "Justfile", "Rakefile", "Gemfile"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileSession {
+    pub session_id: String,
+    pub project: String,
+    pub source: String,
+    pub first_ts: u64,
+    pub last_ts: u64,
+    /// Records in the session that mention the file.
+    pub mentions: usize,
+    /// Whether a patch in the session edited it.
+    pub edited: bool,
+}
+
+/// Normalized paths `record` mentions, in the order first seen. Tool results
+/// are left out: their directory listings and search output would drown
+/// the files anyone actually talked about.
+pub fn record_files(record: &Record) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    let mut push = |path: &str| {
+        let path = normalize_file(path);
+        if !path.is_empty() && files.len() < MAX_FILES_PER_RECORD && seen.insert(path.clone()) {
+            files.push(path);
+        }
+    };
+    for edit in record_edits(record) {
+        for path in edit.paths() {
+            push(path);
+        }
+    }
+    if let Some(input) = record.tool_input.as_deref()
+        && let Ok(value) = serde_json::from_str::<Value>(input)
+    {
+        for path in tool_input_files(&value) {
+            push(path);
+        }
+    }
+    if record.role != "tool_result" {
+        for path in text_files(&record.text) {
+            push(path);
+        }
+    }
+    files
+}
+
+/// The form `file:` terms are indexed and matched in: [`normalize_path`]
+/// without a leading `~/` or a trailing `:line:col`.
+pub fn normalize_file(path: &str) -> String {
+    let path = path.trim();
+    let path = path.strip_prefix("~/").unwrap_or(path);
+    normalize_path(strip_line_suffix(path))
+}
+
+fn tool_input_files(value: &Value) -> Vec<&str> {
+    let Value::Object(map) = value else {
+        return Vec::new();
+    };
+    FILE_KEYS
+        .iter()
+        .filter_map(|key| map.get(*key))
+        .flat_map(|value| match value {
+            Value::String(path) => vec![path.as_str()],
+            Value::Array(paths) => paths.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Words of `text` that look like file paths.
+pub(crate) fn text_files(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|ch: char| ch.is_whitespace() || "\"'`()[]{}<>,;|=".contains(ch))
+        .map(|word| word.trim_end_matches(['.', ':', '!', '?']))
+        .filter(|word| looks_like_file(strip_line_suffix(word)))
+}
+
+/// `src/main.rs:42:7` without the line and column.
+fn strip_line_suffix(path: &str) -> &str {
+    let mut path = path;
+    for _ in 0..2 {
+        match path.rsplit_once(':') {
+            Some((head, tail))
+                if !head.is_empty()
+                    && !tail.is_empty()
+                    && tail.chars().all(|ch| ch.is_ascii_digit()) =>
+            {
+                path = head
+            }
+            _ => break,
+        }
+    }
+    path
+}
+
+fn looks_like_file(word: &str) -> bool {
+    if word.len() < 3 || word.contains("://") || word.starts_with('-') || word.contains('*') {
+        return false;
+    }
+    let name = word.rsplit(['/', '\\']).next().unwrap_or(word);
+    if EXTENSIONLESS_FILES.contains(&name) {
+        return true;
+    }
+    let Some((stem, extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+    let extension_ok = !extension.is_empty()
+        && extension.len() <= 10
+        && extension.chars().all(|ch| ch.is_ascii_alphanumeric())
+        && extension.chars().any(|ch| ch.is_ascii_alphabetic());
+    if !extension_ok || (stem.is_empty() && !name.starts_with('.')) {
+        return false;
+    }
+    word.contains('/') || FILE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+}
+
+/// Sessions among `records` that mention `path` (normalized), most recent
+/// first.
+pub fn sessions_touching(records: &[Record], path: &str) -> Vec<FileSession> {
+    let path = normalize_file(path);
+    let mut sessions: HashMap<&str, FileSession> = HashMap::new();
+    for record in records {
+        let edited = record_edits(record).iter().any(|edit| {
+            edit.paths()
+                .any(|edited| crate::patch::path_suffixes(edited).contains(&path))
+        });
+        let session = sessions
+            .entry(record.session_id.as_str())
+            .or_insert_with(|| FileSession {
+                session_id: record.session_id.clone(),
+                project: record.project.clone(),
+                source: record.source.label().to_string(),
+                first_ts: record.ts,
+                last_ts: record.ts,
+                mentions: 0,
+                edited: false,
+            });
+        session.first_ts = session.first_ts.min(record.ts);
+        session.last_ts = session.last_ts.max(record.ts);
+        session.mentions += 1;
+        session.edited |= edited;
+    }
+    let mut sessions: Vec<FileSession> = sessions.into_values().collect();
+    sessions.sort_by(|a, b| {
+        b.last_ts
+            .cmp(&a.last_ts)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_record;
+
+    #[test]
+    fn finds_paths_in_text_and_tool_arguments() {
+        let message = Record {
+            role: "assistant".to_string(),
+            text: "The bug is in `src/index.rs:120:4` (see ./README.md). Also main.rs, \
+             e.g. v1.2 and https://example.com/a.html; the Makefile too."
+                .to_string(),
+            ..test_record(1, "s1")
+        };
+        assert_eq!(
+            record_files(&message),
+            ["src/index.rs", "README.md", "main.rs", "Makefile"]
+        );
+
+        let mut read = Record {
+            role: "tool_use".to_string(),
+            text: "".to_string(),
+            ..test_record(1, "s1")
+        };
+        read.tool_name = Some("Read".to_string());
+        read.tool_input = Some(r#"{"file_path":"/Users/me/repo/src/tui.rs"}"#.to_string());
+        read.text = read.tool_input.clone().unwrap();
+        assert_eq!(record_files(&read), ["Users/me/repo/src/tui.rs"]);
+
+        let output = Record {
+            role: "tool_result".to_string(),
+            text: "src/a.rs\nsrc/b.rs".to_string(),
+            ..test_record(1, "s1")
+        };
+        assert!(record_files(&output).is_empty());
+        assert_eq!(
+            normalize_file("~/dev/memex/Cargo.toml:3"),
+            "dev/memex/Cargo.toml"
+        );
+    }
+
+    #[test]
+    fn groups_mentions_by_session_and_flags_edits() {
+        let mut mention = Record {
+            text: "why does src/lib.rs panic".to_string(),
+            ..test_record(1, "s1")
+        };
+        mention.ts = 10;
+        let mut edit = Record {
+            role: "tool_use".to_string(),
+            text: "".to_string(),
+            ..test_record(1, "s1")
+        };
+        edit.session_id = "s2".to_string();
+        edit.ts = 20;
+        edit.tool_name = Some("apply_patch".to_string());
+        edit.tool_input = Some(
+            "*** Begin Patch\n*** Update File: /repo/src/lib.rs\n@@\n-a\n+b\n*** End Patch"
+                .to_string(),
+        );
+        let mut later = Record {
+            role: "assistant".to_string(),
+            text: "fixed lib.rs".to_string(),
+            ..test_record(1, "s1")
+        };
+        later.ts = 15;
+
+        let sessions = sessions_touching(&[mention, edit, later], "./src/lib.rs");
+        let summary: Vec<(&str, usize, bool, u64)> = sessions
+            .iter()
+            .map(|session| {
+                (
+                    session.session_id.as_str(),
+                    session.mentions,
+                    session.edited,
+                    session.last_ts,
+                )
+            })
+            .collect();
+        assert_eq!(summary, [("s2", 1, true, 20), ("s1", 2, false, 15)]);
+    }
+}
@@ -0,0 +1,405 @@
+//! Parses the moment `memex at` looks around, and the `--since`/`--until`
+//! bounds and `after:`/`before:` filters of searches: timestamps, dates and
+//! everyday phrases like "3pm yesterday", "last friday morning", "last week"
+//! or "2 hours ago".
+
+use anyhow::{Result, anyhow};
+use chrono::{
+    DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveTime, TimeZone, Weekday,
+};
+
+/// A parsed moment and how precisely the phrase pinned it down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Moment {
+    pub at: DateTime<Local>,
+    /// Half-width of the span the phrase covers: a whole day for
+    /// "yesterday", a few hours for "this morning", `None` for a clock time.
+    pub span: Option<Duration>,
+}
+
+pub fn parse_moment(input: &str, now: DateTime<Local>) -> Result<Moment> {
+    let input = input.trim();
+    if let Some(at) = parse_exact(input)? {
+        return Ok(Moment { at, span: None });
+    }
+    let lowered = input.to_lowercase().replace(',', " ");
+    let tokens: Vec<&str> = lowered.split_whitespace().collect();
+    if tokens.is_empty() {
+        return Err(anyhow!("no time given"));
+    }
+    if tokens == ["now"] {
+        return Ok(Moment {
+            at: now,
+            span: None,
+        });
+    }
+    if let [which @ ("this" | "last"), unit] = tokens.as_slice()
+        && let Some((start, next)) = calendar_span(now.date_naive(), unit, *which == "last")
+    {
+        let (start, next) = (local(start, NaiveTime::MIN)?, local(next, NaiveTime::MIN)?);
+        let half = (next - start) / 2;
+        return Ok(Moment {
+            at: start + half,
+            span: Some(half),
+        });
+    }
+    if tokens.last() == Some(&"ago") {
+        let ago = parse_ago(&tokens[..tokens.len() - 1])
+            .ok_or_else(|| not_understood(input, "expected e.g. \"2 hours ago\""))?;
+        return Ok(Moment {
+            at: now - ago,
+            span: None,
+        });
+    }
+
+    let today = now.date_naive();
+    let mut date: Option<NaiveDate> = None;
+    let mut time: Option<NaiveTime> = None;
+    let mut span: Option<Duration> = None;
+    let mut last = false;
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        i += 1;
+        if matches!(
+            token,
+            "at" | "on" | "in" | "the" | "around" | "about" | "this"
+        ) {
+            continue;
+        }
+        if token == "last" {
+            last = true;
+            continue;
+        }
+        if let Some((day, token_span)) = part_of_day(token) {
+            if token == "night" && last {
+                date = Some(today - Duration::days(1));
+            } else {
+                // "morning" is this morning even before it has begun.
+                date.get_or_insert(today);
+            }
+            time = Some(day);
+            span = Some(token_span);
+            last = false;
+            continue;
+        }
+        if let Some(weekday) = parse_weekday(token) {
+            date = Some(previous_weekday(today, weekday, last));
+            last = false;
+            continue;
+        }
+        match token {
+            "today" => date = Some(today),
+            "yesterday" => date = Some(today - Duration::days(1)),
+            _ => {
+                if let Ok(day) = NaiveDate::parse_from_str(token, "%Y-%m-%d") {
+                    date = Some(day);
+                    continue;
+                }
+                // "3 pm" as well as "3pm".
+                let meridiem = tokens
+                    .get(i)
+                    .copied()
+                    .filter(|next| matches!(*next, "am" | "pm" | "a.m." | "p.m."));
+                let clock = match meridiem {
+                    Some(meridiem) => format!("{token}{meridiem}"),
+                    None => token.to_string(),
+                };
+                let Some(parsed) = parse_clock(&clock) else {
+                    return Err(not_understood(input, &format!("unknown word '{token}'")));
+                };
+                if meridiem.is_some() {
+                    i += 1;
+                }
+                time = Some(parsed);
+                span = None;
+            }
+        }
+    }
+
+    let (at, span) = match (date, time) {
+        (None, None) => return Err(not_understood(input, "no date or time in it")),
+        (Some(date), None) => (local(date, noon())?, Some(Duration::hours(12))),
+        (Some(date), Some(time)) => (local(date, time)?, span),
+        // A bare time later than now means that time yesterday.
+        (None, Some(time)) => match local(today, time)? {
+            at if at > now => (local(today - Duration::days(1), time)?, span),
+            at => (at, span),
+        },
+    };
+    Ok(Moment { at, span })
+}
+
+/// Unix millis for a `--since`/`--until` value or an `after:`/`before:`
+/// filter. Anything [`parse_moment`] reads counts from the start of the span
+/// it covers, so "yesterday" starts at midnight and "last week" on Monday;
+/// words may be joined by hyphens, as in `after:last-monday`. A bare ISO
+/// date is midnight UTC, as it always was for these flags.
+pub fn parse_bound_ms(input: &str, now: DateTime<Local>) -> Result<u64> {
+    let input = input.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        let midnight = date.and_time(NaiveTime::MIN).and_utc();
+        return Ok(midnight.timestamp_millis().max(0) as u64);
+    }
+    let moment = parse_moment(input, now)
+        .or_else(|err| parse_moment(&input.replace('-', " "), now).map_err(|_| err))?;
+    let start = moment.at - moment.span.unwrap_or_default();
+    Ok(start.timestamp_millis().max(0) as u64)
+}
+
+/// Current Unix time in milliseconds; 0 if the clock is before 1970.
+pub fn now_millis() -> u64 {
+    chrono::Utc::now().timestamp_millis().max(0) as u64
+}
+
+/// First day of the week (from Monday), month or year `today` falls in, or
+/// of the one before for "last", and the first day of the one after it.
+fn calendar_span(today: NaiveDate, unit: &str, last: bool) -> Option<(NaiveDate, NaiveDate)> {
+    let (start, next) = match unit {
+        "week" => {
+            let start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+            (start, start + Duration::weeks(1))
+        }
+        "month" => {
+            let start = today.with_day(1)?;
+            (start, start.checked_add_months(Months::new(1))?)
+        }
+        "year" => (
+            NaiveDate::from_ymd_opt(today.year(), 1, 1)?,
+            NaiveDate::from_ymd_opt(today.year() + 1, 1, 1)?,
+        ),
+        _ => return None,
+    };
+    if !last {
+        return Some((start, next));
+    }
+    let previous = match unit {
+        "week" => start - Duration::weeks(1),
+        "month" => start.checked_sub_months(Months::new(1))?,
+        _ => NaiveDate::from_ymd_opt(today.year() - 1, 1, 1)?,
+    };
+    Some((previous, start))
+}
+
+/// Unix seconds or millis, RFC 3339, or an ISO date with a clock time.
+fn parse_exact(input: &str) -> Result<Option<DateTime<Local>>> {
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_digit()) {
+        let value: i64 = input.parse()?;
+        let millis = if value > 10_000_000_000 {
+            value
+        } else {
+            value * 1000
+        };
+        return Ok(DateTime::from_timestamp_millis(millis).map(|dt| dt.with_timezone(&Local)));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(Some(dt.with_timezone(&Local)));
+    }
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(naive) = chrono::NaiveDateTime::parse_from_str(input, format) {
+            return local(naive.date(), naive.time()).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// "2 hours", "an hour", "90m", "3 days", "2 weeks"; a month is 30 days and
+/// a year 365.
+fn parse_ago(tokens: &[&str]) -> Option<Duration> {
+    let (amount, unit) = match tokens {
+        [amount, unit] => {
+            let amount: i32 = match *amount {
+                "a" | "an" => 1,
+                amount => amount.parse().ok()?,
+            };
+            (amount, *unit)
+        }
+        [compact] => {
+            let split = compact.find(|c: char| !c.is_ascii_digit())?;
+            (compact[..split].parse().ok()?, &compact[split..])
+        }
+        _ => return None,
+    };
+    let unit = match unit.trim_end_matches('s') {
+        "m" | "min" | "minute" => Duration::minutes(1),
+        "h" | "hr" | "hour" => Duration::hours(1),
+        "d" | "day" => Duration::days(1),
+        "w" | "week" => Duration::weeks(1),
+        "mo" | "month" => Duration::days(30),
+        "y" | "yr" | "year" => Duration::days(365),
+        _ => return None,
+    };
+    Some(unit * amount)
+}
+
+/// "3pm", "3:30pm", "15:00", "15:00:30", "noon", "midnight".
+fn parse_clock(token: &str) -> Option<NaiveTime> {
+    match token {
+        "noon" | "midday" => return Some(noon()),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+    let token = token.replace('.', "");
+    let (digits, offset) = if let Some(digits) = token.strip_suffix("am") {
+        (digits, Some(0))
+    } else if let Some(digits) = token.strip_suffix("pm") {
+        (digits, Some(12))
+    } else {
+        (token.as_str(), None)
+    };
+    let mut parts = digits.split(':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = parts.next().map_or(Some(0), |m| m.parse().ok())?;
+    let second: u32 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let hour = match offset {
+        // A bare number is only a time with minutes, so "3" isn't one.
+        None if !digits.contains(':') => return None,
+        None => hour,
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(offset) => hour % 12 + offset,
+    };
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+/// Middle of a named part of the day, and half its length.
+fn part_of_day(token: &str) -> Option<(NaiveTime, Duration)> {
+    let (hour, half) = match token {
+        "morning" => (9, 3),
+        "afternoon" => (15, 3),
+        "evening" => (19, 2),
+        "night" | "tonight" => (22, 2),
+        _ => return None,
+    };
+    Some((NaiveTime::from_hms_opt(hour, 0, 0)?, Duration::hours(half)))
+}
+
+fn parse_weekday(token: &str) -> Option<Weekday> {
+    let weekday = match token.get(..3)? {
+        "mon" => Weekday::Mon,
+        "tue" => Weekday::Tue,
+        "wed" => Weekday::Wed,
+        "thu" => Weekday::Thu,
+        "fri" => Weekday::Fri,
+        "sat" => Weekday::Sat,
+        "sun" => Weekday::Sun,
+        _ => return None,
+    };
+    // Full names or their usual abbreviations, not any word that starts alike.
+    let full = [
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+        "sunday",
+    ][weekday.num_days_from_monday() as usize];
+    (full.starts_with(token) || token == "tues" || token == "thur" || token == "thurs")
+        .then_some(weekday)
+}
+
+/// The most recent `weekday` on or before `today`, or strictly before it
+/// for "last".
+fn previous_weekday(today: NaiveDate, weekday: Weekday, last: bool) -> NaiveDate {
+    let mut back =
+        (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    if back == 0 && last {
+        back = 7;
+    }
+    today - Duration::days(back as i64)
+}
+
+fn noon() -> NaiveTime {
+    NaiveTime::from_hms_opt(12, 0, 0).expect("noon is a valid time")
+}
+
+fn local(date: NaiveDate, time: NaiveTime) -> Result<DateTime<Local>> {
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .ok_or_else(|| anyhow!("{date} {time} does not exist in the local time zone"))
+}
+
+fn not_understood(input: &str, reason: &str) -> anyhow::Error {
+    anyhow!(
+        "could not read '{input}' as a time ({reason}); try \"3pm yesterday\", \
+         \"last friday morning\", \"last week\", \"2 hours ago\" or \"2024-06-01 14:30\""
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> DateTime<Local> {
+        local(
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            NaiveTime::parse_from_str(time, "%H:%M").unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_everyday_phrases_relative_to_now() {
+        // A Wednesday afternoon.
+        let now = at("2024-06-05", "16:00");
+        let parse = |input: &str| parse_moment(input, now).unwrap();
+
+        assert_eq!(parse("3pm yesterday").at, at("2024-06-04", "15:00"));
+        assert_eq!(parse("yesterday at 3:30 pm").at, at("2024-06-04", "15:30"));
+        assert_eq!(parse("10:15").at, at("2024-06-05", "10:15"));
+        // Later than now, so yesterday's.
+        assert_eq!(parse("11pm").at, at("2024-06-04", "23:00"));
+        assert_eq!(parse("2 hours ago").at, at("2024-06-05", "14:00"));
+        assert_eq!(parse("an hour ago").at, at("2024-06-05", "15:00"));
+        assert_eq!(parse("90m ago").at, at("2024-06-05", "14:30"));
+        assert_eq!(parse("monday").at, at("2024-06-03", "12:00"));
+        assert_eq!(parse("monday").span, Some(Duration::hours(12)));
+        assert_eq!(parse("wed 9am").at, at("2024-06-05", "09:00"));
+        assert_eq!(parse("last wednesday").at, at("2024-05-29", "12:00"));
+        let morning = parse("last friday morning");
+        assert_eq!(morning.at, at("2024-05-31", "09:00"));
+        assert_eq!(morning.span, Some(Duration::hours(3)));
+        assert_eq!(parse("last night").at, at("2024-06-04", "22:00"));
+        assert_eq!(parse("2024-06-01 14:30").at, at("2024-06-01", "14:30"));
+        assert_eq!(parse("2024-06-01 noon").at, at("2024-06-01", "12:00"));
+        assert_eq!(parse("now").at, now);
+        // Today's noon is still ahead, but a named day is never moved.
+        let monday_morning = at("2024-06-03", "10:00");
+        assert_eq!(
+            parse_moment("monday", monday_morning).unwrap().at,
+            at("2024-06-03", "12:00")
+        );
+
+        assert!(parse_moment("25:00", now).is_err());
+        assert!(parse_moment("13pm", now).is_err());
+        assert!(parse_moment("sometime", now).is_err());
+        assert!(parse_moment("month ago", now).is_err());
+    }
+
+    #[test]
+    fn bounds_start_where_the_phrase_does() {
+        let now = at("2024-06-05", "16:00");
+        let bound = |input: &str| parse_bound_ms(input, now).unwrap();
+        let ms = |date: &str, time: &str| at(date, time).timestamp_millis() as u64;
+
+        assert_eq!(bound("yesterday"), ms("2024-06-04", "00:00"));
+        assert_eq!(bound("last-monday"), ms("2024-06-03", "00:00"));
+        assert_eq!(bound("this week"), ms("2024-06-03", "00:00"));
+        assert_eq!(bound("last week"), ms("2024-05-27", "00:00"));
+        assert_eq!(bound("last month"), ms("2024-05-01", "00:00"));
+        assert_eq!(bound("this year"), ms("2024-01-01", "00:00"));
+        assert_eq!(bound("2 weeks ago"), ms("2024-05-22", "16:00"));
+        assert_eq!(bound("2-days-ago"), ms("2024-06-03", "16:00"));
+        assert_eq!(bound("2024-06-01"), 1_717_200_000_000);
+        assert_eq!(bound("1717200000"), 1_717_200_000_000);
+        assert_eq!(
+            parse_moment("last week", now).unwrap().span,
+            Some(Duration::days(7) / 2)
+        );
+        assert!(parse_bound_ms("last fortnight", now).is_err());
+    }
+}
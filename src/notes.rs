@@ -0,0 +1,277 @@
+use crate::config::Paths;
+use crate::index::SearchIndex;
+use crate::state::IngestState;
+use crate::types::{AgentSettings, NOTE_ROLE, Record, RecordLinks, SourceKind};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Note records are keyed by this plus the session id in `source_path`, so
+/// re-ingesting the transcript never deletes them.
+const NOTE_SOURCE_PREFIX: &str = "memex-note:";
+
+/// What the user wrote about a session, with enough of the session to index
+/// the note again after a full reindex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionNote {
+    pub text: String,
+    pub project: String,
+    /// Storage label of the session's source.
+    pub source: String,
+    /// The session's transcript, for resuming from a note-only search hit.
+    pub source_path: String,
+    /// Time of the session's last message, so the note sorts with it.
+    pub session_ts: u64,
+    pub updated_at: u64,
+}
+
+impl SessionNote {
+    pub fn source_kind(&self) -> SourceKind {
+        SourceKind::from_label(&self.source)
+            .unwrap_or_else(|| SourceKind::from_path(&self.source_path))
+    }
+}
+
+/// One note per session, keyed by session id. The index holds a searchable
+/// copy of each; this file is the source of truth.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionNotes {
+    pub notes: BTreeMap<String, SessionNote>,
+}
+
+impl SessionNotes {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, session_id: &str) -> Option<&SessionNote> {
+        self.notes.get(session_id)
+    }
+}
+
+pub fn notes_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("notes.json")
+}
+
+pub fn is_note_source_path(path: &str) -> bool {
+    path.starts_with(NOTE_SOURCE_PREFIX)
+}
+
+/// Sets the note on `session_id`, replacing any earlier one; blank text
+/// removes it. Returns the stored note. Callers hold the write lock.
+pub fn set_note(
+    paths: &Paths,
+    index: &SearchIndex,
+    session_id: &str,
+    text: &str,
+    now_ms: u64,
+) -> Result<Option<SessionNote>> {
+    let path = notes_path(&paths.state);
+    let mut notes = SessionNotes::load(&path)?;
+    let text = text.trim();
+    let note = if text.is_empty() {
+        notes.notes.remove(session_id);
+        None
+    } else {
+        let records = index.records_by_session_id(session_id)?;
+        let note = match records.iter().max_by_key(|record| record.ts) {
+            Some(last) => SessionNote {
+                text: text.to_string(),
+                project: last.project.clone(),
+                source: last.source.storage_label().to_string(),
+                source_path: last.source_path.clone(),
+                session_ts: last.ts,
+                updated_at: now_ms,
+            },
+            None => SessionNote {
+                text: text.to_string(),
+                updated_at: now_ms,
+                ..notes
+                    .get(session_id)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("session not found: {session_id}"))?
+            },
+        };
+        notes.notes.insert(session_id.to_string(), note.clone());
+        Some(note)
+    };
+
+    let mut writer = index.writer()?;
+    index.delete_by_source_path(&mut writer, &note_source_path(session_id))?;
+    if let Some(note) = &note {
+        let doc_id = allocate_doc_ids(paths, 1)?;
+        index.add_record(&mut writer, &note_record(session_id, note, doc_id))?;
+    }
+    writer.commit()?;
+    notes.save(&path)?;
+    Ok(note)
+}
+
+/// Writes `notes` back after a full reindex wiped the data directory, and
+/// indexes each one again.
+pub fn restore_notes(paths: &Paths, index: &SearchIndex, notes: &SessionNotes) -> Result<()> {
+    if notes.notes.is_empty() {
+        return Ok(());
+    }
+    notes.save(&notes_path(&paths.state))?;
+    let first = allocate_doc_ids(paths, notes.notes.len() as u64)?;
+    let mut writer = index.writer()?;
+    for (doc_id, (session_id, note)) in (first..).zip(&notes.notes) {
+        index.delete_by_source_path(&mut writer, &note_source_path(session_id))?;
+        index.add_record(&mut writer, &note_record(session_id, note, doc_id))?;
+    }
+    writer.commit()?;
+    Ok(())
+}
+
+fn note_source_path(session_id: &str) -> String {
+    format!("{NOTE_SOURCE_PREFIX}{session_id}")
+}
+
+fn note_record(session_id: &str, note: &SessionNote, doc_id: u64) -> Record {
+    Record {
+        source: note.source_kind(),
+        doc_id,
+        ts: note.session_ts,
+        project: note.project.clone(),
+        session_id: session_id.to_string(),
+        turn_id: 0,
+        role: NOTE_ROLE.to_string(),
+        text: note.text.clone(),
+        tool_name: None,
+        tool_input: None,
+        tool_output: None,
+        links: RecordLinks::default(),
+        settings: AgentSettings::default(),
+        attachments: Vec::new(),
+        source_path: note_source_path(session_id),
+        ingested_at: note.updated_at,
+    }
+}
+
+/// Takes `count` doc ids from the ingest counter so notes never collide with
+/// transcript records.
+fn allocate_doc_ids(paths: &Paths, count: u64) -> Result<u64> {
+    let state_path = paths.state.join("ingest.json");
+    let mut state = IngestState::load(&state_path)?;
+    let first = state.next_doc_id;
+    state.next_doc_id += count;
+    state.save(&state_path)?;
+    Ok(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_record;
+
+    #[test]
+    fn notes_are_indexed_replaced_and_kept_out_of_the_transcript() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        paths.ensure_dirs().expect("dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let mut writer = index.writer().expect("writer");
+        index
+            .add_record(
+                &mut writer,
+                &Record {
+                    ts: 100,
+                    text: "the test fails on ci".to_string(),
+                    ..test_record(1, "s1")
+                },
+            )
+            .expect("add");
+        index
+            .add_record(
+                &mut writer,
+                &Record {
+                    ts: 200,
+                    text: "retry the request".to_string(),
+                    ..test_record(2, "s1")
+                },
+            )
+            .expect("add");
+        writer.commit().expect("commit");
+        drop(writer);
+        IngestState {
+            next_doc_id: 3,
+            ..IngestState::default()
+        }
+        .save(&paths.state.join("ingest.json"))
+        .expect("ingest state");
+
+        assert!(set_note(&paths, &index, "missing", "hi", 1).is_err());
+        let note = set_note(&paths, &index, "s1", "  where we fixed the flaky test ", 5)
+            .expect("set note")
+            .expect("note");
+        assert_eq!(note.text, "where we fixed the flaky test");
+        assert_eq!(note.session_ts, 200);
+        assert_eq!(note.source_path, "/tmp/s1.jsonl");
+        set_note(
+            &paths,
+            &index,
+            "s1",
+            "where we fixed the flaky login test",
+            6,
+        )
+        .expect("replace note");
+
+        let hits = |query: &str| -> Vec<Record> {
+            index
+                .search(&crate::index::QueryOptions {
+                    query: query.to_string(),
+                    project: None,
+                    role: Some(NOTE_ROLE.to_string()),
+                    tool: None,
+                    session_id: None,
+                    source: None,
+                    since: None,
+                    until: None,
+                    as_of: None,
+                    settings: AgentSettings::default(),
+                    has: Vec::new(),
+                    edited: Vec::new(),
+                    commands: Vec::new(),
+                    files: Vec::new(),
+                    langs: Vec::new(),
+                    errors: Vec::new(),
+                    require_all_terms: false,
+                    fuzzy_distance: 0,
+                    tuning: crate::index::QueryTuning::default(),
+                    limit: 10,
+                })
+                .expect("search")
+                .into_iter()
+                .map(|(_, record)| record)
+                .collect()
+        };
+        let found = hits("flaky");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].session_id, "s1");
+        assert_eq!(found[0].doc_id, 4);
+        assert_eq!(index.records_by_session_id("s1").expect("records").len(), 2);
+
+        let notes = SessionNotes::load(&notes_path(&paths.state)).expect("load");
+        assert_eq!(notes.get("s1").map(|note| note.updated_at), Some(6));
+
+        set_note(&paths, &index, "s1", "   ", 7).expect("clear note");
+        assert!(hits("flaky").is_empty());
+        let notes = SessionNotes::load(&notes_path(&paths.state)).expect("load");
+        assert!(notes.get("s1").is_none());
+    }
+}
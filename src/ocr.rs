@@ -0,0 +1,145 @@
+//! Optional OCR of pasted images at ingest, through the `tesseract` CLI, so
+//! text in error screenshots is searchable. Turned on with `ocr_images`.
+
+use crate::shell::find_in_path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Images larger than this are not read.
+const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// OCR text kept per image.
+const MAX_TEXT_BYTES: usize = 16 * 1024;
+
+/// Output with fewer letters and digits than this is treated as noise.
+const MIN_TEXT_CHARS: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Ocr {
+    binary: PathBuf,
+}
+
+impl Ocr {
+    /// `tesseract` from PATH, if installed.
+    pub fn detect() -> Option<Self> {
+        find_in_path("tesseract").map(|binary| Self { binary })
+    }
+
+    /// Text in the image file at `path`.
+    pub fn image_file(&self, path: &Path) -> Option<String> {
+        let size = std::fs::metadata(path).ok()?.len();
+        if size == 0 || size > MAX_IMAGE_BYTES {
+            return None;
+        }
+        self.run(path.as_os_str(), None)
+    }
+
+    /// Text in an image inlined in a transcript as base64.
+    pub fn image_base64(&self, data: &str) -> Option<String> {
+        if data.len() as u64 > MAX_IMAGE_BYTES / 3 * 4 {
+            return None;
+        }
+        let bytes = decode_base64(data)?;
+        self.run("-".as_ref(), Some(&bytes))
+    }
+
+    fn run(&self, input: &std::ffi::OsStr, stdin: Option<&[u8]>) -> Option<String> {
+        let mut child = Command::new(&self.binary)
+            .arg(input)
+            .arg("stdout")
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        if let Some(bytes) = stdin {
+            // tesseract reads the whole image before writing anything.
+            let mut pipe = child.stdin.take()?;
+            if pipe.write_all(bytes).is_err() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+        let output = child.wait_with_output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        clean_text(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// OCR output with blank lines and runs of spaces collapsed, capped at
+/// `MAX_TEXT_BYTES`; `None` when it has no real text.
+pub fn clean_text(raw: &str) -> Option<String> {
+    let lines: Vec<String> = raw
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect();
+    let mut text = lines.join("\n");
+    if text.chars().filter(|c| c.is_alphanumeric()).count() < MIN_TEXT_CHARS {
+        return None;
+    }
+    if text.len() > MAX_TEXT_BYTES {
+        let mut end = MAX_TEXT_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    Some(text)
+}
+
+/// Standard base64, padding optional; whitespace is skipped.
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 3);
+    let mut buf = 0u32;
+    let mut bits = 0u32;
+    for byte in data.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            b' ' | b'\n' | b'\r' | b'\t' => continue,
+            _ => return None,
+        };
+        buf = (buf << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buf >> bits) as u8);
+            buf &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_base64_and_cleans_ocr_output() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVs\nbG8").unwrap(), b"hello");
+        assert_eq!(decode_base64("AAAA").unwrap(), [0, 0, 0]);
+        assert!(decode_base64("not*base64").is_none());
+
+        assert_eq!(
+            clean_text("  Error:   connection\n\n refused \n\x0c").as_deref(),
+            Some("Error: connection\nrefused")
+        );
+        assert_eq!(clean_text(" | \n ~ \n"), None);
+        let long = "é".repeat(MAX_TEXT_BYTES);
+        assert!(clean_text(&long).unwrap().len() <= MAX_TEXT_BYTES);
+    }
+}
@@ -0,0 +1,313 @@
+//! Outcome labels the user puts on sessions: whether the agent's work landed,
+//! was dropped or had to be redone. They filter `memex sessions` and feed
+//! per-project success rates in `memex outcomes`.
+
+use crate::config::Paths;
+use crate::index::SearchIndex;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, TimeZone};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// The work landed
+    Success,
+    /// The work was dropped
+    Abandoned,
+    /// The work had to be redone
+    Rework,
+}
+
+impl Outcome {
+    pub fn label(self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Abandoned => "abandoned",
+            Outcome::Rework => "rework",
+        }
+    }
+
+    /// The label after `current` when cycling through them, ending unlabeled.
+    pub fn cycle(current: Option<Outcome>) -> Option<Outcome> {
+        match current {
+            None => Some(Outcome::Success),
+            Some(Outcome::Success) => Some(Outcome::Abandoned),
+            Some(Outcome::Abandoned) => Some(Outcome::Rework),
+            Some(Outcome::Rework) => None,
+        }
+    }
+}
+
+/// A session's label, with the session's project and time so stats don't
+/// need the index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutcomeLabel {
+    pub outcome: Outcome,
+    pub project: String,
+    /// Time of the session's last message.
+    pub session_ts: u64,
+    pub labeled_at: u64,
+}
+
+/// One label per session, keyed by session id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionOutcomes {
+    pub outcomes: BTreeMap<String, OutcomeLabel>,
+}
+
+impl SessionOutcomes {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, session_id: &str) -> Option<Outcome> {
+        self.outcomes.get(session_id).map(|label| label.outcome)
+    }
+}
+
+pub fn outcomes_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("outcomes.json")
+}
+
+/// Labels `session_id` with `outcome`, replacing any earlier label; `None`
+/// removes it. Returns the stored label.
+pub fn set_outcome(
+    paths: &Paths,
+    index: &SearchIndex,
+    session_id: &str,
+    outcome: Option<Outcome>,
+    now_ms: u64,
+) -> Result<Option<OutcomeLabel>> {
+    let path = outcomes_path(&paths.state);
+    let mut outcomes = SessionOutcomes::load(&path)?;
+    let label = match outcome {
+        None => {
+            outcomes.outcomes.remove(session_id);
+            None
+        }
+        Some(outcome) => {
+            let records = index.records_by_session_id(session_id)?;
+            let label = match records.iter().max_by_key(|record| record.ts) {
+                Some(last) => OutcomeLabel {
+                    outcome,
+                    project: last.project.clone(),
+                    session_ts: last.ts,
+                    labeled_at: now_ms,
+                },
+                // Pruned or forgotten since it was labeled; keep what we had.
+                None => OutcomeLabel {
+                    outcome,
+                    labeled_at: now_ms,
+                    ..outcomes
+                        .outcomes
+                        .get(session_id)
+                        .cloned()
+                        .ok_or_else(|| anyhow!("session not found: {session_id}"))?
+                },
+            };
+            outcomes
+                .outcomes
+                .insert(session_id.to_string(), label.clone());
+            Some(label)
+        }
+    };
+    outcomes.save(&path)?;
+    Ok(label)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutcomePeriod {
+    /// ISO week, e.g. 2026-W41
+    Week,
+    /// Calendar month
+    Month,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct OutcomeCounts {
+    pub success: u64,
+    pub abandoned: u64,
+    pub rework: u64,
+}
+
+impl OutcomeCounts {
+    fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Success => self.success += 1,
+            Outcome::Abandoned => self.abandoned += 1,
+            Outcome::Rework => self.rework += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.success + self.abandoned + self.rework
+    }
+
+    /// Share of labeled sessions that succeeded, 0 to 1.
+    pub fn success_rate(&self) -> f64 {
+        self.success as f64 / self.total().max(1) as f64
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectOutcomes {
+    pub project: String,
+    #[serde(flatten)]
+    pub counts: OutcomeCounts,
+    pub success_rate: f64,
+    /// Counts per week or month, oldest first; empty without a period.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub periods: Vec<PeriodOutcomes>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodOutcomes {
+    pub period: String,
+    #[serde(flatten)]
+    pub counts: OutcomeCounts,
+    pub success_rate: f64,
+}
+
+/// Labeled sessions counted per project (as `project_name` shows them), most
+/// labeled first, and per `period` of the session's time in `tz` when given.
+pub fn outcome_stats<Tz: TimeZone>(
+    outcomes: &SessionOutcomes,
+    since: Option<u64>,
+    period: Option<OutcomePeriod>,
+    project_name: impl Fn(&str) -> String,
+    tz: &Tz,
+) -> Vec<ProjectOutcomes> {
+    let mut projects: BTreeMap<String, (OutcomeCounts, BTreeMap<String, OutcomeCounts>)> =
+        BTreeMap::new();
+    for label in outcomes.outcomes.values() {
+        if since.is_some_and(|since| label.session_ts < since) {
+            continue;
+        }
+        let (counts, periods) = projects.entry(project_name(&label.project)).or_default();
+        counts.add(label.outcome);
+        if let Some(period) = period
+            && let Some(key) = period_key(period, label.session_ts, tz)
+        {
+            periods.entry(key).or_default().add(label.outcome);
+        }
+    }
+    let mut stats: Vec<ProjectOutcomes> = projects
+        .into_iter()
+        .map(|(project, (counts, periods))| ProjectOutcomes {
+            project,
+            counts,
+            success_rate: counts.success_rate(),
+            periods: periods
+                .into_iter()
+                .map(|(period, counts)| PeriodOutcomes {
+                    period,
+                    counts,
+                    success_rate: counts.success_rate(),
+                })
+                .collect(),
+        })
+        .collect();
+    stats.sort_by_key(|project| std::cmp::Reverse(project.counts.total()));
+    stats
+}
+
+fn period_key<Tz: TimeZone>(period: OutcomePeriod, ts: u64, tz: &Tz) -> Option<String> {
+    let at = DateTime::from_timestamp_millis(ts as i64)?.with_timezone(tz);
+    Some(match period {
+        OutcomePeriod::Week => {
+            let week = at.iso_week();
+            format!("{}-W{:02}", week.year(), week.week())
+        }
+        OutcomePeriod::Month => format!("{}-{:02}", at.year(), at.month()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+    fn label(outcome: Outcome, project: &str, session_ts: u64) -> OutcomeLabel {
+        OutcomeLabel {
+            outcome,
+            project: project.to_string(),
+            session_ts,
+            labeled_at: session_ts,
+        }
+    }
+
+    #[test]
+    fn success_rate_per_project_and_month() {
+        // 2026-09-30 and 2026-10-01 (UTC).
+        let september = 1_790_726_400_000;
+        let october = september + DAY_MS;
+        let mut outcomes = SessionOutcomes::default();
+        for (id, outcome, project, ts) in [
+            ("a", Outcome::Success, "memex", september),
+            ("b", Outcome::Rework, "memex", september),
+            ("c", Outcome::Success, "memex", october),
+            ("d", Outcome::Success, "memex", october),
+            ("e", Outcome::Abandoned, "-Users-me-dev-site", october),
+        ] {
+            outcomes
+                .outcomes
+                .insert(id.to_string(), label(outcome, project, ts));
+        }
+
+        let stats = outcome_stats(
+            &outcomes,
+            None,
+            Some(OutcomePeriod::Month),
+            |project| project.trim_start_matches("-Users-me-dev-").to_string(),
+            &Utc,
+        );
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].project, "memex");
+        assert_eq!(stats[0].success_rate, 0.75);
+        let months: Vec<(&str, u64, f64)> = stats[0]
+            .periods
+            .iter()
+            .map(|period| {
+                (
+                    period.period.as_str(),
+                    period.counts.total(),
+                    period.success_rate,
+                )
+            })
+            .collect();
+        assert_eq!(months, [("2026-09", 2, 0.5), ("2026-10", 2, 1.0)]);
+        assert_eq!(stats[1].project, "site");
+        assert_eq!(stats[1].counts.abandoned, 1);
+
+        let recent = outcome_stats(&outcomes, Some(october), None, str::to_string, &Utc);
+        assert_eq!(recent[0].counts.total(), 2);
+        assert!(recent[0].periods.is_empty());
+        assert_eq!(
+            period_key(OutcomePeriod::Week, october, &Utc).as_deref(),
+            Some("2026-W40")
+        );
+        assert_eq!(Outcome::cycle(Some(Outcome::Rework)), None);
+    }
+}
@@ -0,0 +1,318 @@
+//! File edits in patches agents apply: Codex `apply_patch` input
+//! (`*** Begin Patch` ... `*** End Patch`) and unified diffs. Parsed edits
+//! back `edited:path` queries, search output and diff previews.
+
+use crate::types::Record;
+use serde::Serialize;
+use serde_json::Value;
+
+const BEGIN_PATCH: &str = "*** Begin Patch";
+const END_PATCH: &str = "*** End Patch";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditKind {
+    Add,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileEdit {
+    pub path: String,
+    pub kind: EditKind,
+    /// New path when the patch also renames the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub move_to: Option<String>,
+    pub hunks: Vec<Hunk>,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Hunk {
+    /// Text after `@@`: line ranges in unified diffs, a context line or
+    /// nothing in Codex patches.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub header: String,
+    /// Body lines with their ` `, `+` or `-` marker.
+    pub lines: Vec<String>,
+}
+
+impl FileEdit {
+    fn new(path: &str, kind: EditKind) -> Self {
+        Self {
+            path: path.trim().to_string(),
+            kind,
+            move_to: None,
+            hunks: Vec::new(),
+            additions: 0,
+            deletions: 0,
+        }
+    }
+
+    /// The path before and, for renames, after the edit.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.path.as_str()).chain(self.move_to.as_deref())
+    }
+}
+
+/// Edits in a Codex patch or unified diff, in the order they appear. Lines
+/// outside any file section are skipped.
+pub fn parse_patch(text: &str) -> Vec<FileEdit> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut edits: Vec<FileEdit> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        if let Some(edit) = codex_file_header(line) {
+            edits.push(edit);
+            continue;
+        }
+        if let Some(to) = line.strip_prefix("*** Move to:") {
+            if let Some(edit) = edits.last_mut() {
+                edit.move_to = Some(to.trim().to_string());
+            }
+            continue;
+        }
+        if line.starts_with("*** ") {
+            continue;
+        }
+        // `--- ` is a header only right before `+++ `; elsewhere it is a
+        // removed line that started with `-- `.
+        if let Some(old) = line.strip_prefix("--- ")
+            && let Some(new) = lines.get(i).and_then(|next| next.strip_prefix("+++ "))
+        {
+            i += 1;
+            let edit = match (diff_path(old), diff_path(new)) {
+                (None, Some(new)) => FileEdit::new(new, EditKind::Add),
+                (Some(old), None) => FileEdit::new(old, EditKind::Delete),
+                (Some(old), Some(new)) => {
+                    let mut edit = FileEdit::new(old, EditKind::Update);
+                    if new != old {
+                        edit.move_to = Some(new.to_string());
+                    }
+                    edit
+                }
+                (None, None) => continue,
+            };
+            edits.push(edit);
+            continue;
+        }
+        let Some(edit) = edits.last_mut() else {
+            continue;
+        };
+        if let Some(header) = line.strip_prefix("@@") {
+            edit.hunks.push(Hunk {
+                header: header.trim().to_string(),
+                lines: Vec::new(),
+            });
+            continue;
+        }
+        match line.chars().next() {
+            Some('+') => edit.additions += 1,
+            Some('-') => edit.deletions += 1,
+            Some(' ') | None => {}
+            // `diff --git`, `index ...`, `\ No newline at end of file`.
+            _ => continue,
+        }
+        // Added files have no `@@` line before their contents.
+        if edit.hunks.is_empty() {
+            edit.hunks.push(Hunk::default());
+        }
+        if let Some(hunk) = edit.hunks.last_mut() {
+            hunk.lines.push(line.to_string());
+        }
+    }
+    edits
+}
+
+fn codex_file_header(line: &str) -> Option<FileEdit> {
+    for (prefix, kind) in [
+        ("*** Add File:", EditKind::Add),
+        ("*** Update File:", EditKind::Update),
+        ("*** Delete File:", EditKind::Delete),
+    ] {
+        if let Some(path) = line.strip_prefix(prefix) {
+            return Some(FileEdit::new(path, kind));
+        }
+    }
+    None
+}
+
+/// Path from a `---`/`+++` line without its `a/`/`b/` prefix or trailing
+/// timestamp; `None` for `/dev/null`.
+fn diff_path(raw: &str) -> Option<&str> {
+    let path = raw.split('\t').next().unwrap_or(raw).trim();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    Some(
+        path.strip_prefix("a/")
+            .or_else(|| path.strip_prefix("b/"))
+            .unwrap_or(path),
+    )
+}
+
+/// The patch in a tool call's input: raw `apply_patch` input, a patch inside
+/// JSON arguments (`{"command": ["apply_patch", "*** Begin Patch..."]}`) or
+/// a shell heredoc, or a bare unified diff given to `apply_patch`.
+pub fn patch_text(tool_name: Option<&str>, tool_input: &str) -> Option<String> {
+    let input = tool_input.trim();
+    if input.starts_with(BEGIN_PATCH) {
+        return Some(input.to_string());
+    }
+    if let Ok(value) = serde_json::from_str::<Value>(input) {
+        return find_patch_in_json(&value);
+    }
+    if let Some(patch) = slice_patch(input) {
+        return Some(patch.to_string());
+    }
+    let is_diff =
+        input.starts_with("--- ") || input.starts_with("diff ") || input.contains("\n+++ ");
+    (tool_name == Some("apply_patch") && is_diff).then(|| input.to_string())
+}
+
+fn find_patch_in_json(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => slice_patch(text).map(str::to_string),
+        Value::Array(items) => items.iter().find_map(find_patch_in_json),
+        Value::Object(map) => map.values().find_map(find_patch_in_json),
+        _ => None,
+    }
+}
+
+/// From `*** Begin Patch` through `*** End Patch`, or to the end when the
+/// end marker is missing.
+fn slice_patch(text: &str) -> Option<&str> {
+    let start = text.find(BEGIN_PATCH)?;
+    let rest = &text[start..];
+    let end = rest
+        .find(END_PATCH)
+        .map_or(rest.len(), |end| end + END_PATCH.len());
+    Some(&rest[..end])
+}
+
+/// Edits made by a tool call record; empty for anything else.
+pub fn record_edits(record: &Record) -> Vec<FileEdit> {
+    record
+        .tool_input
+        .as_deref()
+        .and_then(|input| patch_text(record.tool_name.as_deref(), input))
+        .map(|patch| parse_patch(&patch))
+        .unwrap_or_default()
+}
+
+/// `path` with `\` as `/` and without a leading `./` or `/`, the form
+/// `edited:` terms are indexed and matched in.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.trim().replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+    path.trim_start_matches('/').to_string()
+}
+
+/// Every tail of `path` on a `/` boundary, so `edited:src/main.rs` and
+/// `edited:main.rs` both match an edit to `/repo/src/main.rs`.
+pub fn path_suffixes(path: &str) -> Vec<String> {
+    let path = normalize_path(path);
+    if path.is_empty() {
+        return Vec::new();
+    }
+    let mut suffixes = vec![path.clone()];
+    for (at, _) in path.match_indices('/') {
+        let tail = &path[at + 1..];
+        if !tail.is_empty() {
+            suffixes.push(tail.to_string());
+        }
+    }
+    suffixes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_codex_patches_and_unified_diffs() {
+        let codex = "*** Begin Patch\n\
+*** Add File: docs/new.md\n\
++# New\n\
++text\n\
+*** Update File: src/lib.rs\n\
+*** Move to: src/core.rs\n\
+@@ fn main() {\n \
+ let a = 1;\n\
+-let b = 2;\n\
++let b = 3;\n\
+*** End of File\n\
+*** Delete File: old.txt\n\
+*** End Patch";
+        let edits = parse_patch(codex);
+        assert_eq!(edits.len(), 3);
+        assert_eq!(edits[0].kind, EditKind::Add);
+        assert_eq!(edits[0].path, "docs/new.md");
+        assert_eq!((edits[0].additions, edits[0].deletions), (2, 0));
+        assert_eq!(edits[0].hunks[0].lines, ["+# New", "+text"]);
+        assert_eq!(edits[1].move_to.as_deref(), Some("src/core.rs"));
+        assert_eq!(edits[1].hunks[0].header, "fn main() {");
+        assert_eq!((edits[1].additions, edits[1].deletions), (1, 1));
+        assert_eq!(edits[2].kind, EditKind::Delete);
+
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n\
+index 1..2 100644\n\
+--- a/src/a.rs\n\
++++ b/src/a.rs\n\
+@@ -1,2 +1,2 @@\n \
+ keep\n\
+--- removed comment line\n\
++added\n\
+\\ No newline at end of file\n\
+--- /dev/null\n\
++++ b/src/b.rs\n\
+@@ -0,0 +1 @@\n\
++new\n";
+        let edits = parse_patch(diff);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].path, "src/a.rs");
+        assert_eq!(edits[0].move_to, None);
+        assert_eq!((edits[0].additions, edits[0].deletions), (1, 1));
+        assert_eq!(edits[0].hunks[0].header, "-1,2 +1,2 @@");
+        assert_eq!(edits[1].kind, EditKind::Add);
+        assert_eq!(edits[1].path, "src/b.rs");
+    }
+
+    #[test]
+    fn finds_patches_in_tool_input() {
+        let patch = "*** Begin Patch\n*** Delete File: a.txt\n*** End Patch";
+        assert_eq!(
+            patch_text(Some("apply_patch"), patch).as_deref(),
+            Some(patch)
+        );
+        let args = serde_json::json!({ "command": ["apply_patch", patch], "workdir": "/repo" });
+        assert_eq!(
+            patch_text(Some("shell"), &args.to_string()).as_deref(),
+            Some(patch)
+        );
+        let heredoc = format!("apply_patch <<'EOF'\n{patch}\nEOF");
+        assert_eq!(patch_text(Some("exec"), &heredoc).as_deref(), Some(patch));
+        let diff = "--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b";
+        assert!(patch_text(Some("apply_patch"), diff).is_some());
+        assert!(patch_text(Some("shell"), diff).is_none());
+        assert!(patch_text(Some("shell"), r#"{"command":["ls"]}"#).is_none());
+    }
+
+    #[test]
+    fn path_suffixes_cover_every_tail() {
+        assert_eq!(
+            path_suffixes("/repo/src/main.rs"),
+            ["repo/src/main.rs", "src/main.rs", "main.rs"]
+        );
+        assert_eq!(path_suffixes(".\\src\\a.rs"), ["src/a.rs", "a.rs"]);
+        assert_eq!(normalize_path("./src/a.rs"), "src/a.rs");
+        assert!(path_suffixes("").is_empty());
+    }
+}
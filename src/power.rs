@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::time::Duration;
+use sysinfo::System;
+
+pub const DEFAULT_IDLE_CPU_PERCENT: f32 = 20.0;
+
+/// How long CPU usage is sampled before deciding whether the machine is idle.
+const CPU_SAMPLE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// No battery, or the platform can't tell; treated as AC.
+    Unknown,
+}
+
+/// When the index service may run; an always-on desktop leaves both off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexPolicy {
+    pub ac_only: bool,
+    pub idle_only: bool,
+    /// Machine-wide CPU usage below which the machine counts as idle.
+    pub idle_cpu_percent: f32,
+}
+
+impl Default for IndexPolicy {
+    fn default() -> Self {
+        Self {
+            ac_only: false,
+            idle_only: false,
+            idle_cpu_percent: DEFAULT_IDLE_CPU_PERCENT,
+        }
+    }
+}
+
+impl IndexPolicy {
+    /// Why an index run should wait, or `None` when it may go ahead.
+    pub fn blocker(&self) -> Option<String> {
+        if self.ac_only && power_source() == PowerSource::Battery {
+            return Some("running on battery".to_string());
+        }
+        if self.idle_only {
+            let usage = cpu_usage_percent();
+            if usage >= self.idle_cpu_percent {
+                return Some(format!(
+                    "machine is busy ({usage:.0}% CPU, idle below {:.0}%)",
+                    self.idle_cpu_percent
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Machine-wide CPU usage, sampled over about a second.
+pub fn cpu_usage_percent() -> f32 {
+    let mut system = System::new();
+    system.refresh_cpu_usage();
+    std::thread::sleep(CPU_SAMPLE.max(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL));
+    system.refresh_cpu_usage();
+    system.global_cpu_info().cpu_usage()
+}
+
+/// sysinfo doesn't report power, so this asks the platform directly.
+pub fn power_source() -> PowerSource {
+    if cfg!(target_os = "macos") {
+        return std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| power_from_pmset(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or(PowerSource::Unknown);
+    }
+    if cfg!(target_os = "linux") {
+        return power_from_sysfs(Path::new("/sys/class/power_supply"));
+    }
+    PowerSource::Unknown
+}
+
+/// `pmset -g batt` starts with e.g. `Now drawing from 'Battery Power'`.
+fn power_from_pmset(output: &str) -> PowerSource {
+    let first = output.lines().next().unwrap_or_default();
+    if first.contains("'AC Power'") {
+        PowerSource::Ac
+    } else if first.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+/// Each supply directory has a `type` (Mains, Battery, USB, ...) and chargers
+/// an `online` flag. On battery only when a battery exists and no charger is
+/// online.
+fn power_from_sysfs(dir: &Path) -> PowerSource {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return PowerSource::Unknown;
+    };
+    let read = |path: &Path, name: &str| {
+        std::fs::read_to_string(path.join(name))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut has_battery = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match read(&path, "type").as_str() {
+            "Battery" => has_battery = true,
+            "Mains" | "USB" | "USB_C" | "USB_PD" if read(&path, "online") == "1" => {
+                return PowerSource::Ac;
+            }
+            _ => {}
+        }
+    }
+    if has_battery {
+        PowerSource::Battery
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_source_reads_pmset_and_sysfs() {
+        assert_eq!(
+            power_from_pmset("Now drawing from 'Battery Power'\n -InternalBattery-0\t80%"),
+            PowerSource::Battery
+        );
+        assert_eq!(
+            power_from_pmset("Now drawing from 'AC Power'\n"),
+            PowerSource::Ac
+        );
+
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let supply = |name: &str, kind: &str, online: Option<&str>| {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir_all(&dir).expect("supply dir");
+            std::fs::write(dir.join("type"), format!("{kind}\n")).expect("type");
+            if let Some(online) = online {
+                std::fs::write(dir.join("online"), format!("{online}\n")).expect("online");
+            }
+        };
+        assert_eq!(power_from_sysfs(tmp.path()), PowerSource::Unknown);
+        supply("BAT0", "Battery", None);
+        supply("AC", "Mains", Some("0"));
+        assert_eq!(power_from_sysfs(tmp.path()), PowerSource::Battery);
+        supply("AC", "Mains", Some("1"));
+        assert_eq!(power_from_sysfs(tmp.path()), PowerSource::Ac);
+    }
+}
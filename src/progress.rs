@@ -1,13 +1,14 @@
 use crate::types::SourceKind;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::fmt;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
 pub const SOURCE_COUNT: usize = SourceKind::COUNT;
 const SOURCES: [SourceKind; SOURCE_COUNT] = SourceKind::ALL;
 
 pub struct Progress {
-    #[allow(dead_code)] // Kept alive to coordinate progress bars.
     multi: MultiProgress,
     headers: Vec<ProgressBar>,
     parse: Vec<ProgressBar>,
@@ -19,6 +20,85 @@ pub struct Progress {
     embed_total: [AtomicU64; SOURCE_COUNT],
     embed_pending: [AtomicU64; SOURCE_COUNT],
     embeddings_enabled: bool,
+    /// When the first vector was added, for the rate and ETA.
+    embed_started: OnceLock<Instant>,
+    /// Milliseconds from `embed_started` to the latest vector, so the final
+    /// average leaves out the commit after embedding ends.
+    embed_elapsed_ms: AtomicU64,
+    listener: Option<EmbedListener>,
+}
+
+/// How far embedding has got across every source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbedStatus {
+    pub embedded: u64,
+    pub total: u64,
+    /// Since the first vector was added.
+    pub elapsed: Duration,
+    /// Set on the last status of a run, sent by [`Progress::finish`].
+    pub finished: bool,
+}
+
+impl EmbedStatus {
+    /// Records embedded per second so far; `None` until there is a rate to
+    /// go by.
+    pub fn rate(&self) -> Option<f64> {
+        if self.embedded == 0 || self.elapsed < Duration::from_secs(1) {
+            return None;
+        }
+        Some(self.embedded as f64 / self.elapsed.as_secs_f64())
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        remaining_time(self.embedded, self.total, self.elapsed)
+    }
+
+    /// "embedded 1,200 / 5,000, 40/s, eta 1m35s" while running.
+    pub fn describe(&self) -> String {
+        let mut out = format!(
+            "embedded {} / {}",
+            format_count(self.embedded),
+            format_count(self.total)
+        );
+        if let Some(rate) = self.rate() {
+            out.push_str(&format!(", {}", format_rate(rate)));
+        }
+        if let Some(eta) = self.eta() {
+            out.push_str(&format!(", eta {}", format_eta(eta)));
+        }
+        out
+    }
+
+    /// "embedded 5,000 in 2m05s (avg 40/s)", the line printed when a run
+    /// ends.
+    pub fn summary(&self) -> String {
+        let mut out = format!(
+            "embedded {} in {}",
+            format_count(self.embedded),
+            format_eta(self.elapsed)
+        );
+        if let Some(rate) = self.rate() {
+            out.push_str(&format!(" (avg {})", format_rate(rate)));
+        }
+        out
+    }
+}
+
+/// Called with an [`EmbedStatus`] after each embedded batch and once more
+/// when the run finishes; how the TUI shows embedding while it indexes.
+#[derive(Clone)]
+pub struct EmbedListener(Arc<dyn Fn(EmbedStatus) + Send + Sync>);
+
+impl EmbedListener {
+    pub fn new(listener: impl Fn(EmbedStatus) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(listener))
+    }
+}
+
+impl fmt::Debug for EmbedListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EmbedListener")
+    }
 }
 
 impl Progress {
@@ -82,7 +162,20 @@ impl Progress {
             embed_total: std::array::from_fn(|_| AtomicU64::new(0)),
             embed_pending: std::array::from_fn(|_| AtomicU64::new(0)),
             embeddings_enabled: embeddings,
+            embed_started: OnceLock::new(),
+            embed_elapsed_ms: AtomicU64::new(0),
+            listener: None,
+        }
+    }
+
+    /// Sends embedding progress to `listener` instead of drawing bars on
+    /// stderr, for callers with a screen of their own.
+    pub fn with_listener(mut self, listener: Option<EmbedListener>) -> Self {
+        if listener.is_some() {
+            self.multi.set_draw_target(ProgressDrawTarget::hidden());
         }
+        self.listener = listener;
+        self
     }
 
     pub fn add_parsed_bytes(&self, source: SourceKind, bytes: u64) {
@@ -155,8 +248,17 @@ impl Progress {
         let pending = self.embed_pending[idx].load(Ordering::Relaxed);
         let msg = if total > 0 {
             if pending > 0 {
+                let status = self.embed_status();
+                let rate = status
+                    .and_then(|status| status.rate())
+                    .map(|rate| format!(", {}", format_rate(rate)))
+                    .unwrap_or_default();
+                let eta = status
+                    .and_then(|status| status.eta())
+                    .map(|eta| format!(", eta {}", format_eta(eta)))
+                    .unwrap_or_default();
                 format!(
-                    "embedded {} / {} ({} queued)",
+                    "embedded {} / {} ({} queued{rate}{eta})",
                     format_count(embedded),
                     format_count(total),
                     format_count(pending)
@@ -174,7 +276,32 @@ impl Progress {
         self.embed[idx].set_message(msg);
     }
 
+    /// Embedding across every source, once the first vector is in.
+    pub fn embed_status(&self) -> Option<EmbedStatus> {
+        let started = self.embed_started.get()?;
+        Some(EmbedStatus {
+            embedded: self.embed.iter().map(ProgressBar::position).sum(),
+            total: self
+                .embed_total
+                .iter()
+                .map(|total| total.load(Ordering::Relaxed))
+                .sum(),
+            elapsed: started.elapsed(),
+            finished: false,
+        })
+    }
+
+    /// Reports a finished batch to the listener.
+    pub fn embed_batch_done(&self) {
+        if let (Some(listener), Some(status)) = (&self.listener, self.embed_status()) {
+            (listener.0)(status);
+        }
+    }
+
     pub fn add_embedded(&self, source: SourceKind, count: u64) {
+        let started = *self.embed_started.get_or_init(Instant::now);
+        self.embed_elapsed_ms
+            .store(started.elapsed().as_millis() as u64, Ordering::Relaxed);
         let idx = source.idx();
         self.embed[idx].inc(count);
         let embedded = self.embed[idx].position();
@@ -202,7 +329,17 @@ impl Progress {
         }
     }
 
+    /// Finishes every bar and, when anything was embedded, prints the
+    /// average throughput and sends the last status to the listener.
     pub fn finish(&self) {
+        if let Some(mut status) = self.embed_status().filter(|status| status.embedded > 0) {
+            status.elapsed = Duration::from_millis(self.embed_elapsed_ms.load(Ordering::Relaxed));
+            status.finished = true;
+            let _ = self.multi.println(status.summary());
+            if let Some(listener) = &self.listener {
+                (listener.0)(status);
+            }
+        }
         for source in SOURCES {
             let idx = source.idx();
             self.headers[idx].finish();
@@ -245,6 +382,8 @@ fn progress_label(source: SourceKind) -> &'static str {
         SourceKind::Cursor => "cursor",
         SourceKind::Pi => "pi",
         SourceKind::Copilot => "copilot",
+        SourceKind::ChatGPT => "chatgpt",
+        SourceKind::External(name) => name,
     }
 }
 
@@ -263,7 +402,38 @@ pub(crate) fn format_count(value: u64) -> String {
     out.chars().rev().collect()
 }
 
-fn format_bytes(bytes: u64) -> String {
+/// Time to finish `total` items at the rate `done` took `elapsed`; `None`
+/// until there is a rate to go by.
+fn remaining_time(done: u64, total: u64, elapsed: Duration) -> Option<Duration> {
+    if done == 0 || elapsed < Duration::from_secs(1) {
+        return None;
+    }
+    let left = total.saturating_sub(done);
+    Some(elapsed.mul_f64(left as f64 / done as f64))
+}
+
+/// "8.5/s" below ten a second, else "1,234/s".
+pub(crate) fn format_rate(per_sec: f64) -> String {
+    if per_sec < 10.0 {
+        format!("{per_sec:.1}/s")
+    } else {
+        format!("{}/s", format_count(per_sec.round() as u64))
+    }
+}
+
+/// "45s", "3m20s", "1h05m".
+pub(crate) fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+pub(crate) fn format_bytes(bytes: u64) -> String {
     const KB: f64 = 1024.0;
     const MB: f64 = KB * 1024.0;
     const GB: f64 = MB * 1024.0;
@@ -278,3 +448,48 @@ fn format_bytes(bytes: u64) -> String {
         format!("{bytes} B")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_follows_the_rate_so_far() {
+        let eta = remaining_time(100, 400, Duration::from_secs(10));
+        assert_eq!(eta, Some(Duration::from_secs(30)));
+        assert_eq!(remaining_time(0, 400, Duration::from_secs(10)), None);
+        assert_eq!(remaining_time(10, 400, Duration::from_millis(200)), None);
+        assert_eq!(format_eta(Duration::from_secs(45)), "45s");
+        assert_eq!(format_eta(Duration::from_secs(200)), "3m20s");
+        assert_eq!(format_eta(Duration::from_secs(3900)), "1h05m");
+    }
+
+    #[test]
+    fn embed_status_reports_throughput() {
+        let running = EmbedStatus {
+            embedded: 1200,
+            total: 5000,
+            elapsed: Duration::from_secs(30),
+            finished: false,
+        };
+        assert_eq!(running.rate(), Some(40.0));
+        assert_eq!(
+            running.describe(),
+            "embedded 1,200 / 5,000, 40/s, eta 1m35s"
+        );
+        let done = EmbedStatus {
+            embedded: 18,
+            total: 18,
+            elapsed: Duration::from_secs(4),
+            finished: true,
+        };
+        assert_eq!(done.summary(), "embedded 18 in 4s (avg 4.5/s)");
+        let instant = EmbedStatus {
+            elapsed: Duration::from_millis(300),
+            ..done
+        };
+        assert_eq!(instant.rate(), None);
+        assert_eq!(instant.summary(), "embedded 18 in 0s");
+        assert_eq!(format_rate(12_345.4), "12,345/s");
+    }
+}
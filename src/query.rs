@@ -0,0 +1,203 @@
+use crate::index::SearchIndex;
+use crate::types::Record;
+use anyhow::{Context, Result, anyhow};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, params};
+use serde_json::Value;
+use std::path::Path;
+
+/// Columns of the `records` table, in the order `memex query` documents them.
+const RECORDS_TABLE: &str = r#"
+    CREATE TABLE records (
+        doc_id INTEGER PRIMARY KEY,
+        ts INTEGER NOT NULL,
+        ingested_at INTEGER NOT NULL,
+        source TEXT NOT NULL,
+        project TEXT NOT NULL,
+        session_id TEXT NOT NULL,
+        turn_id INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        text TEXT NOT NULL,
+        tool_name TEXT,
+        tool_input TEXT,
+        tool_output TEXT,
+        model TEXT,
+        parent_session_id TEXT,
+        attachments INTEGER NOT NULL,
+        source_path TEXT NOT NULL
+    );
+"#;
+
+/// Rows returned by a query, with values already converted to JSON.
+#[derive(Debug, Default)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// An in-memory SQLite copy of the record store for ad-hoc, read-only SQL.
+///
+/// Records live in Tantivy, so they are loaded into a `records` table on
+/// open. The analytics database, when present, is attached so `sessions`
+/// can be joined too.
+pub struct RecordQuery {
+    conn: Connection,
+}
+
+impl RecordQuery {
+    pub fn open(index: &SearchIndex, analytics: Option<&Path>) -> Result<Self> {
+        let mut query = Self::empty()?;
+        let tx = query.conn.transaction()?;
+        {
+            let mut insert = tx.prepare(INSERT_RECORD)?;
+            index
+                .for_each_record(|record| insert_record(&mut insert, &record))
+                .context("load records for query")?;
+        }
+        tx.commit()?;
+        if let Some(path) = analytics.filter(|path| path.exists()) {
+            query.conn.execute(
+                "ATTACH DATABASE ?1 AS analytics",
+                params![path.to_string_lossy()],
+            )?;
+        }
+        query.seal()
+    }
+
+    pub fn from_records(records: impl IntoIterator<Item = Record>) -> Result<Self> {
+        let mut query = Self::empty()?;
+        let tx = query.conn.transaction()?;
+        {
+            let mut insert = tx.prepare(INSERT_RECORD)?;
+            for record in records {
+                insert_record(&mut insert, &record)?;
+            }
+        }
+        tx.commit()?;
+        query.seal()
+    }
+
+    fn empty() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(RECORDS_TABLE)?;
+        Ok(Self { conn })
+    }
+
+    /// Bars writes from here on, including to the attached analytics file.
+    fn seal(self) -> Result<Self> {
+        self.conn.pragma_update(None, "query_only", true)?;
+        Ok(self)
+    }
+
+    /// Runs a read-only statement. Anything after the first statement is
+    /// ignored.
+    pub fn run(&self, sql: &str) -> Result<QueryResult> {
+        let mut stmt = self.conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(anyhow!(
+                "only read-only statements (SELECT, WITH) are allowed"
+            ));
+        }
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let mut rows = stmt.query([])?;
+        let mut result = QueryResult {
+            columns,
+            rows: Vec::new(),
+        };
+        while let Some(row) = rows.next()? {
+            let values = (0..result.columns.len())
+                .map(|idx| row.get_ref(idx).map(json_value))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            result.rows.push(values);
+        }
+        Ok(result)
+    }
+}
+
+const INSERT_RECORD: &str = "INSERT OR REPLACE INTO records VALUES \
+    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)";
+
+fn insert_record(insert: &mut rusqlite::Statement<'_>, record: &Record) -> Result<()> {
+    insert.execute(params![
+        record.doc_id as i64,
+        record.ts as i64,
+        record.ingested_at as i64,
+        record.source.storage_label(),
+        record.project,
+        record.session_id,
+        record.turn_id,
+        record.role,
+        record.text,
+        record.tool_name,
+        record.tool_input,
+        record.tool_output,
+        record.settings.model,
+        record.links.parent_session_id,
+        record.attachments.len() as i64,
+        record.source_path,
+    ])?;
+    Ok(())
+}
+
+fn json_value(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(value) => Value::from(value),
+        ValueRef::Real(value) => Value::from(value),
+        ValueRef::Text(text) => Value::from(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(blob) => Value::from(format!("<{} bytes>", blob.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_record;
+
+    #[test]
+    fn aggregates_records_with_sql() {
+        let query = RecordQuery::from_records([
+            test_record(1, "s-memex"),
+            Record {
+                role: "assistant".to_string(),
+                ..test_record(2, "s-memex")
+            },
+            test_record(3, "s-memex"),
+            Record {
+                project: "blog".to_string(),
+                ..test_record(4, "s-blog")
+            },
+        ])
+        .expect("load records");
+
+        let result = query
+            .run(
+                "SELECT project, count(*) AS prompts FROM records \
+                 WHERE role = 'user' GROUP BY project ORDER BY prompts DESC",
+            )
+            .expect("run query");
+
+        assert_eq!(result.columns, ["project", "prompts"]);
+        assert_eq!(
+            result.rows,
+            [
+                vec![Value::from("memex"), Value::from(2)],
+                vec![Value::from("blog"), Value::from(1)],
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_writes() {
+        let query = RecordQuery::from_records([test_record(1, "s-memex")]).expect("load records");
+
+        assert!(query.run("DELETE FROM records").is_err());
+        assert_eq!(
+            query
+                .run("SELECT count(*) FROM records")
+                .expect("count")
+                .rows,
+            [vec![Value::from(1)]]
+        );
+    }
+}
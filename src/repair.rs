@@ -0,0 +1,369 @@
+//! Recovery from a damaged search index. `memex repair` reports what is
+//! wrong; `memex repair --rebuild-index` rebuilds the index from the records
+//! still readable in it, re-ingesting sources to fill in what was lost, and
+//! keeps the vector store when its checksums still validate.
+
+use crate::config::Paths;
+use crate::index::{Salvage, SearchIndex, salvage_records, verify_index};
+use crate::notes::{SessionNotes, notes_path, restore_notes};
+use crate::state::IngestState;
+use crate::types::Record;
+use crate::vector::{VectorIndex, remove_vector_store, vector_store_exists, verify_checksums};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// Health of the index and vector store, from `check`.
+#[derive(Debug, Clone, Default)]
+pub struct RepairCheck {
+    /// False when there is no index yet.
+    pub index_exists: bool,
+    /// Problems `verify_index` found; empty when the index is intact.
+    pub index_problems: Vec<String>,
+    pub vectors_exist: bool,
+    /// `None` when the vector store predates checksums.
+    pub vectors_valid: Option<bool>,
+}
+
+impl RepairCheck {
+    pub fn is_healthy(&self) -> bool {
+        self.index_problems.is_empty() && self.vectors_valid != Some(false)
+    }
+}
+
+/// What happened to the vector store during a rebuild.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorRepair {
+    /// There was no vector store.
+    Absent,
+    /// Its checksums failed, so it was removed; the next embedding run
+    /// backfills it.
+    Discarded,
+    Kept {
+        vectors: usize,
+        /// Vectors dropped because their records were lost.
+        dropped: usize,
+        /// False when the store predates checksums and couldn't be checked.
+        verified: bool,
+    },
+}
+
+/// What `rebuild_index` did.
+#[derive(Debug, Clone)]
+pub struct RebuildReport {
+    pub salvage: Salvage,
+    /// True when records were lost and sources were ingested again.
+    pub reingested: bool,
+    /// Records the re-ingest added that the salvage didn't have.
+    pub filled: usize,
+    /// Records in the rebuilt index.
+    pub records: usize,
+    pub vectors: VectorRepair,
+    /// Where the damaged index was moved.
+    pub damaged_dir: PathBuf,
+}
+
+pub fn check(paths: &Paths) -> Result<RepairCheck> {
+    let index_exists = paths.index.join("meta.json").exists();
+    let vectors_exist = vector_store_exists(&paths.vectors);
+    Ok(RepairCheck {
+        index_exists,
+        index_problems: if index_exists {
+            verify_index(&paths.index)
+        } else {
+            Vec::new()
+        },
+        vectors_exist,
+        vectors_valid: if vectors_exist {
+            verify_checksums(&paths.vectors)?
+        } else {
+            None
+        },
+    })
+}
+
+/// Identifies a record across re-ingests, which assign new doc ids.
+type RecordKey = (String, u32, String, u64);
+
+fn record_key(record: &Record) -> RecordKey {
+    (
+        record.source_path.clone(),
+        record.turn_id,
+        record.role.clone(),
+        record.ts,
+    )
+}
+
+/// Rebuilds the index at `paths.index` into a fresh one, moving the damaged
+/// copy to `index.damaged`. Every record that could be read back keeps its
+/// doc id, so its vector stays valid. When some were lost the ingest state is
+/// reset and the gaps are filled from a re-ingest (see [`fill_gaps`]);
+/// vectors of lost records are dropped. Callers hold the write lock.
+pub fn rebuild_index<F>(paths: &Paths, reingest: F) -> Result<RebuildReport>
+where
+    F: FnOnce(&SearchIndex) -> Result<()>,
+{
+    let staging = paths.root.join("index.rebuild");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    let rebuilt = SearchIndex::open_or_create_for_ingest(&staging)?;
+    let mut salvaged: HashSet<RecordKey> = HashSet::new();
+    let mut writer = rebuilt.writer()?;
+    let salvage = salvage_records(&paths.index, |record| {
+        salvaged.insert(record_key(&record));
+        rebuilt.add_record(&mut writer, &record)
+    })?;
+    // Kept even when records were lost: their source files may be gone, so
+    // this can be the only copy left.
+    writer.commit()?;
+    drop(writer);
+    drop(rebuilt);
+
+    let damaged_dir = paths.root.join("index.damaged");
+    if damaged_dir.exists() {
+        fs::remove_dir_all(&damaged_dir)?;
+    }
+    if paths.index.exists() {
+        fs::rename(&paths.index, &damaged_dir)?;
+    }
+    fs::rename(&staging, &paths.index)?;
+    let index = SearchIndex::open_or_create(&paths.index)?;
+
+    let reingested = !salvage.is_complete();
+    let mut filled = 0;
+    if reingested {
+        // Keep next_doc_id so new doc ids never collide with the old ones
+        // the vector store is still keyed by.
+        let state_path = paths.state.join("ingest.json");
+        let mut state = IngestState::load(&state_path)?;
+        state.files.clear();
+        state.save(&state_path)?;
+        filled = fill_gaps(paths, &index, &salvaged, reingest)?;
+    }
+
+    let vectors = repair_vectors(paths, &index, reingested)?;
+    Ok(RebuildReport {
+        salvage,
+        reingested,
+        filled,
+        records: index.doc_count()?,
+        vectors,
+        damaged_dir,
+    })
+}
+
+/// Ingests every source again into a scratch index, since which ones lost
+/// records is unknown, and adds to `index` the records `salvaged` doesn't
+/// already hold. Returns how many were added.
+fn fill_gaps<F>(
+    paths: &Paths,
+    index: &SearchIndex,
+    salvaged: &HashSet<RecordKey>,
+    reingest: F,
+) -> Result<usize>
+where
+    F: FnOnce(&SearchIndex) -> Result<()>,
+{
+    let scratch_dir = paths.root.join("index.reingest");
+    if scratch_dir.exists() {
+        fs::remove_dir_all(&scratch_dir)?;
+    }
+    fs::create_dir_all(&scratch_dir)?;
+    let scratch = SearchIndex::open_or_create(&scratch_dir)?;
+    reingest(&scratch)?;
+    restore_notes(
+        paths,
+        &scratch,
+        &SessionNotes::load(&notes_path(&paths.state))?,
+    )?;
+    let mut writer = index.writer()?;
+    let mut filled = 0;
+    scratch.for_each_record(|record| {
+        if salvaged.contains(&record_key(&record)) {
+            return Ok(());
+        }
+        filled += 1;
+        index.add_record(&mut writer, &record)
+    })?;
+    writer.commit()?;
+    drop(writer);
+    drop(scratch);
+    fs::remove_dir_all(&scratch_dir)?;
+    Ok(filled)
+}
+
+/// Keeps the vector store if its checksums validate. After a re-ingest,
+/// vectors of records that were lost are dropped: the copies filled back in
+/// have new doc ids.
+fn repair_vectors(paths: &Paths, index: &SearchIndex, reingested: bool) -> Result<VectorRepair> {
+    if !vector_store_exists(&paths.vectors) {
+        return Ok(VectorRepair::Absent);
+    }
+    let verified = match verify_checksums(&paths.vectors)? {
+        Some(false) => {
+            remove_vector_store(&paths.vectors)?;
+            return Ok(VectorRepair::Discarded);
+        }
+        Some(true) => true,
+        None => false,
+    };
+    let mut vector = VectorIndex::open(&paths.vectors)?;
+    let before = vector.doc_id_count();
+    if !reingested {
+        return Ok(VectorRepair::Kept {
+            vectors: before,
+            dropped: 0,
+            verified,
+        });
+    }
+    let mut doc_ids = HashMap::new();
+    index.for_each_record(|record| {
+        doc_ids.insert(record.doc_id, record.doc_id);
+        Ok(())
+    })?;
+    let vectors = vector.remap(&doc_ids)?;
+    vector.save()?;
+    Ok(VectorRepair::Kept {
+        vectors,
+        dropped: before - vectors,
+        verified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_record;
+
+    fn index_with_two_segments(paths: &Paths) {
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let mut writer = index.writer().expect("writer");
+        for (doc_id, session_id) in [(1, "a"), (2, "b")] {
+            index
+                .add_record(&mut writer, &test_record(doc_id, session_id))
+                .expect("add");
+            writer.commit().expect("commit");
+        }
+    }
+
+    #[test]
+    fn rebuild_keeps_doc_ids_when_every_record_is_readable() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        paths.ensure_dirs().expect("dirs");
+        index_with_two_segments(&paths);
+        assert!(check(&paths).expect("check").is_healthy());
+
+        let report = rebuild_index(&paths, |_| panic!("nothing to re-ingest")).expect("rebuild");
+        assert!(report.salvage.is_complete());
+        assert!(!report.reingested);
+        assert_eq!(report.records, 2);
+        assert_eq!(report.vectors, VectorRepair::Absent);
+        assert!(report.damaged_dir.join("meta.json").exists());
+        let index = SearchIndex::open_or_create(&paths.index).expect("reopen");
+        assert_eq!(index.records_by_session_id("b").expect("b")[0].doc_id, 2);
+    }
+
+    /// Deletes the stored records of one of the two segments and returns
+    /// the session still readable.
+    fn lose_a_segment(paths: &Paths) -> String {
+        let store = fs::read_dir(&paths.index)
+            .expect("read dir")
+            .map(|entry| entry.expect("entry").path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "store"))
+            .expect("segment store");
+        fs::remove_file(store).expect("remove store");
+        let mut survivors = Vec::new();
+        salvage_records(&paths.index, |record| {
+            survivors.push(record.session_id);
+            Ok(())
+        })
+        .expect("salvage");
+        assert_eq!(survivors.len(), 1);
+        survivors.remove(0)
+    }
+
+    #[test]
+    fn rebuild_reingests_when_a_segment_is_lost() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        paths.ensure_dirs().expect("dirs");
+        index_with_two_segments(&paths);
+        let mut state = IngestState {
+            next_doc_id: 3,
+            ..IngestState::default()
+        };
+        state.files.insert(
+            "/tmp/a.jsonl".to_string(),
+            crate::state::FileState {
+                size: 10,
+                mtime: 0,
+                offset: 10,
+                turn_id: 2,
+            },
+        );
+        state.save(&paths.state.join("ingest.json")).expect("state");
+        let survivor = lose_a_segment(&paths);
+
+        let check = check(&paths).expect("check");
+        assert!(!check.is_healthy());
+        let report = rebuild_index(&paths, |index| {
+            let state = IngestState::load(&paths.state.join("ingest.json"))?;
+            assert!(state.files.is_empty());
+            assert_eq!(state.next_doc_id, 3);
+            let mut writer = index.writer()?;
+            for (doc_id, session_id) in [(3, "a"), (4, "b")] {
+                let mut record = test_record(doc_id, session_id);
+                record.turn_id -= 2;
+                record.ts -= 2;
+                index.add_record(&mut writer, &record)?;
+            }
+            writer.commit()?;
+            Ok(())
+        })
+        .expect("rebuild");
+        assert_eq!(report.salvage.lost_segments, 1);
+        assert!(report.reingested);
+        // The re-ingested copy of the salvaged record is left out.
+        assert_eq!(report.filled, 1);
+        assert_eq!(report.records, 2);
+        let index = SearchIndex::open_or_create(&paths.index).expect("reopen");
+        let kept = index.records_by_session_id(&survivor).expect("survivor");
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].doc_id <= 2, "salvaged record keeps its doc id");
+    }
+
+    #[test]
+    fn rebuild_keeps_salvaged_records_whose_source_is_gone() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        paths.ensure_dirs().expect("dirs");
+        index_with_two_segments(&paths);
+        let survivor = lose_a_segment(&paths);
+        let lost = if survivor == "a" { "b" } else { "a" };
+
+        // Only the lost session's source file is still on disk.
+        let report = rebuild_index(&paths, |index| {
+            let mut writer = index.writer()?;
+            let mut record = test_record(3, lost);
+            record.turn_id -= 2;
+            record.ts -= 2;
+            index.add_record(&mut writer, &record)?;
+            writer.commit()?;
+            Ok(())
+        })
+        .expect("rebuild");
+        assert_eq!(report.filled, 1);
+        assert_eq!(report.records, 2);
+        let index = SearchIndex::open_or_create(&paths.index).expect("reopen");
+        assert_eq!(
+            index.records_by_session_id(&survivor).expect("kept").len(),
+            1
+        );
+        assert_eq!(index.records_by_session_id(lost).expect("filled").len(), 1);
+        assert!(!paths.root.join("index.reingest").exists());
+    }
+}
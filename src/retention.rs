@@ -0,0 +1,329 @@
+//! Retention policy: sessions whose newest message is older than the
+//! configured number of days are pruned from the index, the vector store and
+//! analytics. Pinned sessions and sessions with a note are always kept.
+
+use crate::analytics::{AnalyticsStore, analytics_path};
+use crate::config::Paths;
+use crate::index::SearchIndex;
+use crate::notes::{SessionNotes, notes_path};
+use crate::state::ProjectAliases;
+use crate::vector::{VectorIndex, vector_store_dirs};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// The index service prunes at most this often.
+pub const PRUNE_INTERVAL_MS: u64 = DAY_MS;
+
+/// How long sessions are kept, from `retain_days`, `retain_projects` and
+/// `retain_pinned` in config.toml.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Days kept when the project has no override; `None` keeps everything.
+    pub days: Option<u64>,
+    /// Per-project days, by indexed name or alias. 0 keeps the project forever.
+    pub projects: HashMap<String, u64>,
+    /// Session ids never pruned.
+    pub pinned: HashSet<String>,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.days.is_some() || self.projects.values().any(|days| *days > 0)
+    }
+
+    /// Days `project` keeps its sessions, or `None` to keep them forever.
+    pub fn days_for(&self, project: &str, aliases: &ProjectAliases) -> Option<u64> {
+        let days = self
+            .projects
+            .get(project)
+            .or_else(|| {
+                aliases
+                    .alias(project)
+                    .and_then(|alias| self.projects.get(alias))
+            })
+            .copied()
+            .or(self.days)?;
+        (days > 0).then_some(days)
+    }
+}
+
+/// A session past its project's retention.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpiredSession {
+    pub session_id: String,
+    pub project: String,
+    pub records: usize,
+    /// Unix millis of the session's newest message.
+    pub last_ts: u64,
+}
+
+/// What one prune removed, or would remove with `dry_run`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionReport {
+    /// Unix millis the prune ran.
+    pub ran_at: u64,
+    pub dry_run: bool,
+    pub sessions: Vec<ExpiredSession>,
+    pub records: usize,
+    pub vectors: usize,
+    /// Expired sessions kept because they are pinned or have a note.
+    pub exempt: usize,
+}
+
+impl RetentionReport {
+    /// Pruned record counts by project.
+    pub fn by_project(&self) -> BTreeMap<&str, usize> {
+        let mut out = BTreeMap::new();
+        for session in &self.sessions {
+            *out.entry(session.project.as_str()).or_default() += session.records;
+        }
+        out
+    }
+}
+
+/// When the index service last pruned, and what it removed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionState {
+    pub last_run: u64,
+    pub last_report: Option<RetentionReport>,
+}
+
+impl RetentionState {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn due(&self, now_ms: u64) -> bool {
+        now_ms.saturating_sub(self.last_run) >= PRUNE_INTERVAL_MS
+    }
+}
+
+pub fn retention_state_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("retention.json")
+}
+
+/// Sessions `policy` expires as of `now_ms`, oldest first, and how many
+/// expired sessions are exempt.
+pub fn expired_sessions(
+    index: &SearchIndex,
+    policy: &RetentionPolicy,
+    aliases: &ProjectAliases,
+    exempt: &HashSet<String>,
+    now_ms: u64,
+) -> Result<(Vec<ExpiredSession>, usize)> {
+    let mut sessions: HashMap<String, ExpiredSession> = HashMap::new();
+    index.for_each_record(|record| {
+        let entry = sessions
+            .entry(record.session_id.clone())
+            .or_insert_with(|| ExpiredSession {
+                session_id: record.session_id,
+                project: record.project,
+                records: 0,
+                last_ts: 0,
+            });
+        entry.records += 1;
+        entry.last_ts = entry.last_ts.max(record.ts);
+        Ok(())
+    })?;
+    let mut expired = Vec::new();
+    let mut exempted = 0;
+    for session in sessions.into_values() {
+        let Some(days) = policy.days_for(&session.project, aliases) else {
+            continue;
+        };
+        if session.last_ts >= now_ms.saturating_sub(days.saturating_mul(DAY_MS)) {
+            continue;
+        }
+        if exempt.contains(&session.session_id) {
+            exempted += 1;
+        } else {
+            expired.push(session);
+        }
+    }
+    expired.sort_by(|a, b| {
+        a.last_ts
+            .cmp(&b.last_ts)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    Ok((expired, exempted))
+}
+
+/// Deletes every session `policy` expires, unless `dry_run`. Vectors of the
+/// deleted records are compacted away straight after, since nothing else
+/// would reclaim them. Callers hold the write lock.
+pub fn prune(
+    paths: &Paths,
+    index: &SearchIndex,
+    policy: &RetentionPolicy,
+    now_ms: u64,
+    dry_run: bool,
+) -> Result<RetentionReport> {
+    let mut exempt = policy.pinned.clone();
+    exempt.extend(
+        SessionNotes::load(&notes_path(&paths.state))?
+            .notes
+            .into_keys(),
+    );
+    let (sessions, exempted) =
+        expired_sessions(index, policy, &paths.project_aliases()?, &exempt, now_ms)?;
+    let mut report = RetentionReport {
+        ran_at: now_ms,
+        dry_run,
+        records: sessions.iter().map(|session| session.records).sum(),
+        sessions,
+        vectors: 0,
+        exempt: exempted,
+    };
+    if dry_run || report.sessions.is_empty() {
+        return Ok(report);
+    }
+
+    let mut writer = index.writer()?;
+    for session in &report.sessions {
+        index.delete_by_session_id(&mut writer, &session.session_id)?;
+    }
+    writer.commit()?;
+    let db = analytics_path(&paths.state);
+    if db.exists() {
+        let analytics = AnalyticsStore::open(&db)?;
+        for session in &report.sessions {
+            analytics.delete_session(&session.session_id)?;
+        }
+    }
+    for dir in vector_store_dirs(&paths.vectors) {
+        let mut vector = VectorIndex::open(&dir)?;
+        let removed = vector.compact(&index.live_doc_ids()?)?;
+        if removed > 0 {
+            vector.save()?;
+        }
+        report.vectors += removed;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Record, test_record};
+
+    #[test]
+    fn prunes_expired_sessions_per_project_and_keeps_exempt_ones() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        paths.ensure_dirs().expect("dirs");
+        let index = SearchIndex::open_or_create(&paths.index).expect("index");
+        let now = 100 * DAY_MS;
+        let mut writer = index.writer().expect("writer");
+        for record in [
+            // Old, but its newest message is recent.
+            Record {
+                ts: DAY_MS,
+                ..test_record(1, "active")
+            },
+            Record {
+                ts: 99 * DAY_MS,
+                ..test_record(2, "active")
+            },
+            Record {
+                ts: 50 * DAY_MS,
+                ..test_record(3, "stale")
+            },
+            Record {
+                ts: 51 * DAY_MS,
+                ..test_record(4, "stale")
+            },
+            Record {
+                ts: DAY_MS,
+                ..test_record(5, "pinned")
+            },
+            Record {
+                ts: DAY_MS,
+                ..test_record(6, "noted")
+            },
+            // Kept forever by its override.
+            Record {
+                project: "notes".to_string(),
+                ts: DAY_MS,
+                ..test_record(7, "archive")
+            },
+            // Shorter override.
+            Record {
+                project: "scratch".to_string(),
+                ts: 95 * DAY_MS,
+                ..test_record(8, "scratch")
+            },
+        ] {
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+        let mut notes = SessionNotes::default();
+        notes.notes.insert(
+            "noted".to_string(),
+            crate::notes::SessionNote {
+                text: "keep me".to_string(),
+                project: "memex".to_string(),
+                source: "claude".to_string(),
+                source_path: "/tmp/noted.jsonl".to_string(),
+                session_ts: DAY_MS,
+                updated_at: 0,
+            },
+        );
+        notes.save(&notes_path(&paths.state)).expect("notes");
+
+        let policy = RetentionPolicy {
+            days: Some(30),
+            projects: HashMap::from([("notes".to_string(), 0), ("scratch".to_string(), 3)]),
+            pinned: HashSet::from(["pinned".to_string()]),
+        };
+        assert!(policy.is_enabled());
+        assert!(!RetentionPolicy::default().is_enabled());
+
+        let dry = prune(&paths, &index, &policy, now, true).expect("dry run");
+        let pruned: Vec<&str> = dry
+            .sessions
+            .iter()
+            .map(|session| session.session_id.as_str())
+            .collect();
+        assert_eq!(pruned, ["stale", "scratch"]);
+        assert_eq!(dry.records, 3);
+        assert_eq!(dry.exempt, 2);
+        assert_eq!(index.doc_count().expect("count"), 8);
+
+        let report = prune(&paths, &index, &policy, now, false).expect("prune");
+        assert_eq!(
+            report.by_project(),
+            BTreeMap::from([("memex", 2), ("scratch", 1)])
+        );
+        assert!(
+            index
+                .records_by_session_id("stale")
+                .expect("stale")
+                .is_empty()
+        );
+        assert_eq!(index.doc_count().expect("count"), 5);
+
+        let mut state = RetentionState::default();
+        assert!(state.due(now));
+        state.last_run = now;
+        assert!(!state.due(now + DAY_MS - 1));
+        assert!(state.due(now + DAY_MS));
+    }
+}
@@ -0,0 +1,373 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Built-in profiles, in the order the TUI cycles through them.
+pub const BUILTIN_PROFILES: [&str; 4] = [DEFAULT_PROFILE, "precision", "recall", "recent"];
+
+/// Fuzzy matching beyond two edits is not supported by tantivy.
+const MAX_FUZZY_DISTANCE: u8 = 2;
+
+/// Ranking knobs picked together by name, e.g. `precision` while debugging an
+/// exact error or `recall` while researching a topic.
+///
+/// Tantivy fixes BM25's k1 and b, so the lexical side is tuned through how
+/// query terms match instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoringProfile {
+    /// Every query term must match instead of any of them.
+    pub require_all_terms: bool,
+    /// Edit distance allowed when matching query terms; 0 matches exactly.
+    pub fuzzy_distance: u8,
+    /// Multiplier on the keyword rank contribution in hybrid search.
+    pub keyword_weight: f32,
+    /// Multiplier on the semantic rank contribution in hybrid search.
+    pub semantic_weight: f32,
+    /// Boost for recent results (0 = none).
+    pub recency_weight: f32,
+    /// Half-life in days for the recency boost.
+    pub recency_half_life_days: f32,
+}
+
+impl Default for ScoringProfile {
+    fn default() -> Self {
+        Self {
+            require_all_terms: false,
+            fuzzy_distance: 0,
+            keyword_weight: 1.0,
+            semantic_weight: 1.0,
+            recency_weight: 1.0,
+            recency_half_life_days: 30.0,
+        }
+    }
+}
+
+impl ScoringProfile {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let base = Self::default();
+        match name {
+            DEFAULT_PROFILE => Some(base),
+            "precision" => Some(Self {
+                require_all_terms: true,
+                keyword_weight: 1.5,
+                semantic_weight: 0.5,
+                recency_weight: 0.5,
+                ..base
+            }),
+            "recall" => Some(Self {
+                fuzzy_distance: 1,
+                keyword_weight: 0.75,
+                semantic_weight: 1.5,
+                recency_weight: 0.25,
+                recency_half_life_days: 90.0,
+                ..base
+            }),
+            "recent" => Some(Self {
+                recency_weight: 4.0,
+                recency_half_life_days: 7.0,
+                ..base
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Boosts `score` by up to `weight` for records newer than `half_life_days`,
+/// decaying exponentially with age.
+pub fn apply_recency(score: f32, ts: u64, now_ms: u64, weight: f32, half_life_days: f32) -> f32 {
+    if score <= 0.0 {
+        return score;
+    }
+    score * recency_boost(ts, now_ms, weight, half_life_days)
+}
+
+/// Factor `apply_recency` multiplies a positive score by; 1 when the boost is
+/// off or the record has no timestamp.
+pub fn recency_boost(ts: u64, now_ms: u64, weight: f32, half_life_days: f32) -> f32 {
+    if weight <= 0.0 || half_life_days <= 0.0 || ts == 0 {
+        return 1.0;
+    }
+    let age_ms = now_ms.saturating_sub(ts);
+    let age_days = age_ms as f32 / (1000.0 * 60.0 * 60.0 * 24.0);
+    let decay = (-std::f32::consts::LN_2 * age_days / half_life_days).exp();
+    1.0 + weight * decay
+}
+
+/// Why a search result got its score, for `memex search --explain`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScoreExplanation {
+    /// Phrasing that found the record, when several were searched at once.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// BM25 score of the keyword match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bm25: Option<f32>,
+    /// BM25 contribution of each query term the record contains, highest
+    /// first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub terms: Vec<TermScore>,
+    /// Cosine similarity between the query and the record embedding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+    /// Reciprocal-rank fusion inputs, for hybrid search.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fusion: Option<FusionExplanation>,
+    /// Score before the recency boost.
+    pub base: f32,
+    /// Factor the recency boost multiplied `base` by.
+    pub recency_boost: f32,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TermScore {
+    pub term: String,
+    pub score: f32,
+}
+
+/// How hybrid search fused the keyword and semantic rankings: each side adds
+/// `weight / (rrf_k + rank)` for the ranks it placed the record at.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FusionExplanation {
+    pub rrf_k: f32,
+    pub keyword_weight: f32,
+    pub semantic_weight: f32,
+    /// 1-based rank among keyword matches, if the record was one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword_rank: Option<usize>,
+    /// 1-based rank among nearest embeddings, if the record was one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub semantic_rank: Option<usize>,
+}
+
+impl FusionExplanation {
+    fn contribution(&self, weight: f32, rank: Option<usize>) -> f32 {
+        rank.map_or(0.0, |rank| weight / (self.rrf_k + rank as f32))
+    }
+}
+
+impl ScoreExplanation {
+    /// Starts an explanation for a result scored `base` before its recency
+    /// boost.
+    pub fn new(base: f32, ts: u64, now_ms: u64, weight: f32, half_life_days: f32) -> Self {
+        Self {
+            base,
+            recency_boost: recency_boost(ts, now_ms, weight, half_life_days),
+            score: apply_recency(base, ts, now_ms, weight, half_life_days),
+            ..Self::default()
+        }
+    }
+
+    /// One line per scoring stage, for plain-text output.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(query) = &self.query {
+            lines.push(format!("matched {query:?}"));
+        }
+        if let Some(bm25) = self.bm25 {
+            let terms = self
+                .terms
+                .iter()
+                .map(|term| format!("{} {:.3}", term.term, term.score))
+                .collect::<Vec<_>>();
+            if terms.is_empty() {
+                lines.push(format!("bm25 {bm25:.3}"));
+            } else {
+                lines.push(format!("bm25 {bm25:.3}: {}", terms.join(", ")));
+            }
+        }
+        if let Some(similarity) = self.similarity {
+            lines.push(format!("similarity {similarity:.3}"));
+        }
+        if let Some(fusion) = &self.fusion {
+            let side = |name: &str, weight: f32, rank: Option<usize>| match rank {
+                Some(rank) => format!(
+                    "{name} #{rank} {weight}/({}+{rank}) = {:.4}",
+                    fusion.rrf_k,
+                    fusion.contribution(weight, Some(rank))
+                ),
+                None => format!("{name} unranked"),
+            };
+            lines.push(format!(
+                "fusion {} + {} = {:.4}",
+                side("keyword", fusion.keyword_weight, fusion.keyword_rank),
+                side("semantic", fusion.semantic_weight, fusion.semantic_rank),
+                self.base
+            ));
+        }
+        lines.push(format!(
+            "recency x{:.2}: {:.3} -> {:.3}",
+            self.recency_boost, self.base, self.score
+        ));
+        lines
+    }
+}
+
+/// A `[scoring_profiles.<name>]` table in config.toml. Unset keys come from
+/// the built-in profile of the same name, or from `default` for new names.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ScoringProfileConfig {
+    pub require_all_terms: Option<bool>,
+    pub fuzzy_distance: Option<u8>,
+    pub keyword_weight: Option<f32>,
+    pub semantic_weight: Option<f32>,
+    pub recency_weight: Option<f32>,
+    pub recency_half_life_days: Option<f32>,
+}
+
+impl ScoringProfileConfig {
+    fn apply(&self, base: ScoringProfile) -> ScoringProfile {
+        ScoringProfile {
+            require_all_terms: self.require_all_terms.unwrap_or(base.require_all_terms),
+            fuzzy_distance: self.fuzzy_distance.unwrap_or(base.fuzzy_distance),
+            keyword_weight: self.keyword_weight.unwrap_or(base.keyword_weight),
+            semantic_weight: self.semantic_weight.unwrap_or(base.semantic_weight),
+            recency_weight: self.recency_weight.unwrap_or(base.recency_weight),
+            recency_half_life_days: self
+                .recency_half_life_days
+                .unwrap_or(base.recency_half_life_days),
+        }
+    }
+}
+
+/// Looks up `name` among the built-in and configured profiles.
+pub fn resolve_profile(
+    name: &str,
+    configured: Option<&HashMap<String, ScoringProfileConfig>>,
+) -> Result<ScoringProfile> {
+    let builtin = ScoringProfile::builtin(name);
+    let overrides = configured.and_then(|profiles| profiles.get(name));
+    let profile = match (builtin, overrides) {
+        (Some(base), Some(overrides)) => overrides.apply(base),
+        (Some(base), None) => base,
+        (None, Some(overrides)) => overrides.apply(ScoringProfile::default()),
+        (None, None) => {
+            return Err(anyhow!(
+                "unknown scoring profile '{name}' (available: {})",
+                profile_names(configured).join(", ")
+            ));
+        }
+    };
+    validate(name, profile)
+}
+
+/// Built-in profile names followed by configured ones, sorted.
+pub fn profile_names(configured: Option<&HashMap<String, ScoringProfileConfig>>) -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN_PROFILES.iter().map(|s| s.to_string()).collect();
+    let mut custom: Vec<String> = configured
+        .into_iter()
+        .flat_map(|profiles| profiles.keys())
+        .filter(|name| ScoringProfile::builtin(name).is_none())
+        .cloned()
+        .collect();
+    custom.sort();
+    names.extend(custom);
+    names
+}
+
+fn validate(name: &str, profile: ScoringProfile) -> Result<ScoringProfile> {
+    if profile.fuzzy_distance > MAX_FUZZY_DISTANCE {
+        return Err(anyhow!(
+            "scoring profile '{name}': fuzzy_distance must be at most {MAX_FUZZY_DISTANCE}"
+        ));
+    }
+    for (key, value) in [
+        ("keyword_weight", profile.keyword_weight),
+        ("semantic_weight", profile.semantic_weight),
+        ("recency_weight", profile.recency_weight),
+        ("recency_half_life_days", profile.recency_half_life_days),
+    ] {
+        if !value.is_finite() || value < 0.0 {
+            return Err(anyhow!(
+                "scoring profile '{name}': {key} must be a non-negative number"
+            ));
+        }
+    }
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explanations_show_each_stage_of_a_hybrid_score() {
+        let day = 24 * 60 * 60 * 1000;
+        let fusion = FusionExplanation {
+            rrf_k: 60.0,
+            keyword_weight: 1.0,
+            semantic_weight: 1.0,
+            keyword_rank: Some(2),
+            semantic_rank: None,
+        };
+        let base = fusion.contribution(1.0, fusion.keyword_rank);
+        let mut explanation = ScoreExplanation::new(base, 40 * day, 70 * day, 1.0, 30.0);
+        explanation.bm25 = Some(3.5);
+        explanation.terms = vec![TermScore {
+            term: "retry".to_string(),
+            score: 3.5,
+        }];
+        explanation.fusion = Some(fusion);
+
+        assert!((explanation.recency_boost - 1.5).abs() < 1e-4);
+        assert!((explanation.score - base * 1.5).abs() < 1e-6);
+        let lines = explanation.lines();
+        assert_eq!(lines[0], "bm25 3.500: retry 3.500");
+        assert_eq!(
+            lines[1],
+            "fusion keyword #2 1/(60+2) = 0.0161 + semantic unranked = 0.0161"
+        );
+        assert!(lines[2].starts_with("recency x1.50"));
+        assert_eq!(recency_boost(0, 70 * day, 1.0, 30.0), 1.0);
+    }
+
+    #[test]
+    fn config_overrides_builtins_and_adds_profiles() {
+        let mut configured = HashMap::new();
+        configured.insert(
+            "precision".to_string(),
+            ScoringProfileConfig {
+                recency_weight: Some(0.0),
+                ..ScoringProfileConfig::default()
+            },
+        );
+        configured.insert(
+            "debugging".to_string(),
+            ScoringProfileConfig {
+                require_all_terms: Some(true),
+                ..ScoringProfileConfig::default()
+            },
+        );
+
+        let precision = resolve_profile("precision", Some(&configured)).expect("precision");
+        assert!(precision.require_all_terms);
+        assert_eq!(precision.recency_weight, 0.0);
+
+        let debugging = resolve_profile("debugging", Some(&configured)).expect("debugging");
+        assert!(debugging.require_all_terms);
+        assert_eq!(debugging.keyword_weight, 1.0);
+
+        assert_eq!(
+            profile_names(Some(&configured)),
+            vec!["default", "precision", "recall", "recent", "debugging"]
+        );
+    }
+
+    #[test]
+    fn unknown_and_invalid_profiles_are_rejected() {
+        let err = resolve_profile("fast", None).unwrap_err().to_string();
+        assert!(err.contains("available: default, precision, recall, recent"));
+
+        let mut configured = HashMap::new();
+        configured.insert(
+            "recall".to_string(),
+            ScoringProfileConfig {
+                fuzzy_distance: Some(3),
+                ..ScoringProfileConfig::default()
+            },
+        );
+        assert!(resolve_profile("recall", Some(&configured)).is_err());
+    }
+}
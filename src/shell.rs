@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Builds a command that runs `command` through the platform shell:
+/// `sh -lc` on Unix, `%COMSPEC% /C` (usually cmd.exe) on Windows.
+pub fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let comspec = std::env::var_os("COMSPEC").unwrap_or_else(|| "cmd.exe".into());
+        let mut cmd = Command::new(comspec);
+        cmd.arg("/C").arg(command);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-lc").arg(command);
+        cmd
+    }
+}
+
+/// Quotes `value` as a single argument for the shell used by [`shell_command`].
+pub fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    if value.is_empty() {
+        return "''".to_string();
+    }
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('\'');
+    for ch in value.chars() {
+        if ch == '\'' {
+            out.push_str("'\\''");
+        } else {
+            out.push(ch);
+        }
+    }
+    out.push('\'');
+    out
+}
+
+/// Prefix for commands that should run inside another directory.
+/// `cd /d` also switches drives under cmd.exe.
+pub fn change_dir_command() -> &'static str {
+    if cfg!(windows) { "cd /d" } else { "cd" }
+}
+
+/// Finds an executable on `PATH`. On Windows bare names are also tried with
+/// each `PATHEXT` extension (`.exe`, `.cmd`, ...), which is how npm-installed
+/// CLIs such as `claude.cmd` are exposed.
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    let extensions = executable_extensions();
+    for dir in std::env::split_paths(&path_var) {
+        for candidate in executable_candidates(&dir, binary, &extensions) {
+            if candidate.is_file() && is_executable(&candidate) {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+fn executable_candidates(dir: &Path, binary: &str, extensions: &[String]) -> Vec<PathBuf> {
+    let mut candidates = vec![dir.join(binary)];
+    if Path::new(binary).extension().is_none() {
+        candidates.extend(
+            extensions
+                .iter()
+                .map(|ext| dir.join(format!("{binary}{ext}"))),
+        );
+    }
+    candidates
+}
+
+#[cfg(windows)]
+fn executable_extensions() -> Vec<String> {
+    let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    pathext
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn executable_extensions() -> Vec<String> {
+    Vec::new()
+}
+
+/// Best-effort desktop notification: Notification Center via `osascript` on
+/// macOS, `notify-send` (libnotify) elsewhere. Returns whether one was shown.
+pub fn notify_desktop(title: &str, body: &str) -> bool {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("osascript");
+        cmd.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        ));
+        cmd
+    } else if let Some(notify_send) = find_in_path("notify-send") {
+        let mut cmd = Command::new(notify_send);
+        cmd.arg("--app-name=memex").arg(title).arg(body);
+        cmd
+    } else {
+        return false;
+    };
+    cmd.stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn applescript_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|meta| meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_append_extensions_to_bare_names_only() {
+        let dir = Path::new("bin");
+        let extensions = vec![".exe".to_string(), ".cmd".to_string()];
+
+        assert_eq!(
+            executable_candidates(dir, "claude", &extensions),
+            vec![
+                dir.join("claude"),
+                dir.join("claude.exe"),
+                dir.join("claude.cmd")
+            ]
+        );
+        assert_eq!(
+            executable_candidates(dir, "claude.exe", &extensions),
+            vec![dir.join("claude.exe")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_quote_escapes_single_quotes() {
+        assert_eq!(shell_quote("it's here"), "'it'\\''s here'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn shell_quote_uses_double_quotes_for_cmd() {
+        assert_eq!(
+            shell_quote(r#"C:\Users\me\say "hi""#),
+            r#""C:\Users\me\say ""hi""""#
+        );
+    }
+
+    #[test]
+    fn applescript_strings_escape_quotes_and_backslashes() {
+        assert_eq!(
+            applescript_string(r#"say "hi" \ bye"#),
+            r#""say \"hi\" \\ bye""#
+        );
+    }
+
+    #[test]
+    fn shell_command_runs_through_platform_shell() {
+        let status = shell_command("exit 3").status().expect("run shell");
+        assert_eq!(status.code(), Some(3));
+    }
+}
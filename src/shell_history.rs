@@ -0,0 +1,251 @@
+//! Shell commands agents ran through bash and exec tools, pulled out of tool
+//! call input. They back `cmd:` queries and `memex cmds`, a shell history
+//! across every agent.
+
+use crate::types::Record;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Tool names, lowercased, whose input is a shell command: Claude and
+/// Opencode `Bash`, Codex `shell`/`exec_command`/`local_shell`, Cursor
+/// `run_terminal_cmd` and the like.
+const SHELL_TOOLS: [&str; 8] = [
+    "bash",
+    "shell",
+    "exec",
+    "exec_command",
+    "local_shell",
+    "shell_command",
+    "run_terminal_cmd",
+    "run_shell_command",
+];
+
+const SHELLS: [&str; 5] = ["bash", "sh", "zsh", "dash", "fish"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommandCount {
+    pub command: String,
+    pub count: usize,
+    /// When it last ran (Unix millis).
+    pub last_ts: u64,
+}
+
+pub fn is_shell_tool(name: &str) -> bool {
+    SHELL_TOOLS.contains(&name.to_lowercase().as_str())
+}
+
+/// Commands a record's tool call ran; empty for anything else.
+pub fn record_commands(record: &Record) -> Vec<String> {
+    tool_commands(record.tool_name.as_deref(), record.tool_input.as_deref())
+}
+
+/// Commands in a shell tool's input: JSON arguments (`{"command": "ls"}`,
+/// `{"cmd": ...}` or an argv like `["bash", "-lc", "cargo test"]`) or the
+/// raw command line. Patches applied through the shell count as edits, not
+/// commands.
+pub fn tool_commands(tool_name: Option<&str>, tool_input: Option<&str>) -> Vec<String> {
+    let (Some(name), Some(input)) = (tool_name, tool_input) else {
+        return Vec::new();
+    };
+    if !is_shell_tool(name) || crate::patch::patch_text(Some(name), input).is_some() {
+        return Vec::new();
+    }
+    let input = input.trim();
+    let commands = match serde_json::from_str::<Value>(input) {
+        Ok(value) => json_commands(&value),
+        Err(_) => vec![input.to_string()],
+    };
+    commands
+        .into_iter()
+        .map(|command| command.trim().to_string())
+        .filter(|command| !command.is_empty())
+        .collect()
+}
+
+fn json_commands(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(command) => vec![command.clone()],
+        Value::Array(argv) => argv_command(argv).into_iter().collect(),
+        // Codex `local_shell` calls wrap their argv in an `action`.
+        Value::Object(map) => ["command", "cmd", "action"]
+            .iter()
+            .find_map(|key| map.get(*key))
+            .map(json_commands)
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// `["bash", "-lc", "cargo test"]` is `cargo test`; any other argv is its
+/// words, quoted where the shell would need it.
+fn argv_command(argv: &[Value]) -> Option<String> {
+    let words: Vec<&str> = argv.iter().filter_map(Value::as_str).collect();
+    if let [shell, flag, script] = words.as_slice()
+        && is_shell(shell)
+        && flag.starts_with('-')
+        && flag.ends_with('c')
+    {
+        return Some(script.to_string());
+    }
+    if words.is_empty() {
+        return None;
+    }
+    let quoted: Vec<String> = words
+        .iter()
+        .map(|word| {
+            if !word.is_empty()
+                && word
+                    .chars()
+                    .all(|ch| !ch.is_whitespace() && !"'\"$`\\|&;<>()".contains(ch))
+            {
+                word.to_string()
+            } else {
+                crate::shell::shell_quote(word)
+            }
+        })
+        .collect();
+    Some(quoted.join(" "))
+}
+
+fn is_shell(program: &str) -> bool {
+    let name = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    SHELLS.contains(&name)
+}
+
+/// Lowercase words of `command`, split on anything that isn't a letter or
+/// digit, as `cmd:` filters index and match them.
+pub fn command_words(command: &str) -> Vec<String> {
+    let mut words: Vec<String> = command
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// Distinct commands in `records`, most run first and, among equals, most
+/// recently run first.
+pub fn count_commands(records: &[Record]) -> Vec<CommandCount> {
+    let mut counts: HashMap<String, CommandCount> = HashMap::new();
+    for record in records {
+        for command in record_commands(record) {
+            let entry = counts
+                .entry(command.clone())
+                .or_insert_with(|| CommandCount {
+                    command,
+                    count: 0,
+                    last_ts: 0,
+                });
+            entry.count += 1;
+            entry.last_ts = entry.last_ts.max(record.ts);
+        }
+    }
+    let mut counts: Vec<CommandCount> = counts.into_values().collect();
+    counts.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| b.last_ts.cmp(&a.last_ts))
+            .then_with(|| a.command.cmp(&b.command))
+    });
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_record;
+
+    #[test]
+    fn pulls_commands_out_of_each_agents_tool_input() {
+        let commands = |tool: &str, input: &str| tool_commands(Some(tool), Some(input));
+        assert_eq!(
+            commands(
+                "Bash",
+                r#"{"command":"cargo test -p memex","description":"Run tests"}"#
+            ),
+            ["cargo test -p memex"]
+        );
+        assert_eq!(
+            commands(
+                "shell",
+                r#"{"command":["bash","-lc","rg TODO src"],"workdir":"/repo"}"#
+            ),
+            ["rg TODO src"]
+        );
+        assert_eq!(
+            commands("shell", r#"{"command":["git","commit","-m","fix it"]}"#),
+            ["git commit -m 'fix it'"]
+        );
+        assert_eq!(commands("exec_command", r#"{"cmd":"ls -la"}"#), ["ls -la"]);
+        assert_eq!(
+            commands(
+                "local_shell",
+                r#"{"action":{"command":["/bin/zsh","-c","make"]}}"#
+            ),
+            ["make"]
+        );
+        assert_eq!(commands("Bash", "  npm run build\n"), ["npm run build"]);
+        assert!(commands("Read", r#"{"file_path":"/repo/a.rs"}"#).is_empty());
+        assert!(
+            commands(
+                "shell",
+                r#"{"command":["apply_patch","*** Begin Patch\n*** Delete File: a\n*** End Patch"]}"#
+            )
+            .is_empty()
+        );
+        assert!(commands("Bash", r#"{"command":"  "}"#).is_empty());
+
+        assert_eq!(
+            command_words("docker-compose up -d && Docker ps"),
+            ["compose", "d", "docker", "ps", "up"]
+        );
+    }
+
+    #[test]
+    fn counts_distinct_commands_by_frequency_then_recency() {
+        let records = [
+            Record {
+                role: "tool_use".to_string(),
+                tool_name: Some("Bash".to_string()),
+                tool_input: Some(serde_json::json!({ "command": "cargo test" }).to_string()),
+                ..test_record(1, "s1")
+            },
+            Record {
+                role: "tool_use".to_string(),
+                tool_name: Some("Bash".to_string()),
+                tool_input: Some(serde_json::json!({ "command": "git status" }).to_string()),
+                ..test_record(2, "s1")
+            },
+            Record {
+                role: "tool_use".to_string(),
+                tool_name: Some("bash".to_string()),
+                tool_input: Some(serde_json::json!({ "command": "cargo test" }).to_string()),
+                ..test_record(3, "s1")
+            },
+            Record {
+                role: "tool_use".to_string(),
+                tool_name: Some("Bash".to_string()),
+                tool_input: Some(serde_json::json!({ "command": "ls" }).to_string()),
+                ..test_record(4, "s1")
+            },
+            Record {
+                role: "tool_use".to_string(),
+                tool_name: Some("Read".to_string()),
+                tool_input: Some(serde_json::json!({ "command": "/repo/a.rs" }).to_string()),
+                ..test_record(5, "s1")
+            },
+        ];
+        let counts = count_commands(&records);
+        let summary: Vec<(&str, usize, u64)> = counts
+            .iter()
+            .map(|count| (count.command.as_str(), count.count, count.last_ts))
+            .collect();
+        assert_eq!(
+            summary,
+            [("cargo test", 2, 3), ("ls", 1, 4), ("git status", 1, 2)]
+        );
+    }
+}
@@ -1,10 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FileState {
     pub size: u64,
     pub mtime: i64,
@@ -97,3 +97,560 @@ impl IngestState {
         Ok(())
     }
 }
+
+/// Visits older than the newest ones are dropped so scores track current habits.
+const PROJECT_VISIT_LIMIT: usize = 10;
+/// Repeat visits inside this window refresh the last visit instead of adding
+/// one, so a background indexer ticking every few seconds can't flood history.
+const PROJECT_VISIT_MIN_GAP_SECS: u64 = 10 * 60;
+
+/// Recently and frequently used projects (searched, filtered on, or freshly
+/// ingested), used to rank project pickers by frecency.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectFrecency {
+    /// Unix timestamps (seconds) of recent visits per project, oldest first
+    pub projects: HashMap<String, Vec<u64>>,
+}
+
+impl ProjectFrecency {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let frecency = serde_json::from_str(&data)?;
+        Ok(frecency)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn visit(&mut self, project: &str, now: u64) {
+        if project.is_empty() {
+            return;
+        }
+        let visits = self.projects.entry(project.to_string()).or_default();
+        match visits.last_mut() {
+            Some(last) if now.saturating_sub(*last) < PROJECT_VISIT_MIN_GAP_SECS => {
+                *last = (*last).max(now);
+            }
+            _ => visits.push(now),
+        }
+        if visits.len() > PROJECT_VISIT_LIMIT {
+            let excess = visits.len() - PROJECT_VISIT_LIMIT;
+            visits.drain(..excess);
+        }
+    }
+
+    /// Sum of recency-bucketed weights over the kept visits, in the style of
+    /// browser URL-bar frecency: a visit today outweighs several last quarter.
+    pub fn score(&self, project: &str, now: u64) -> u64 {
+        const DAY: u64 = 24 * 60 * 60;
+        let Some(visits) = self.projects.get(project) else {
+            return 0;
+        };
+        visits
+            .iter()
+            .map(|ts| match now.saturating_sub(*ts) {
+                age if age < DAY => 100,
+                age if age < 7 * DAY => 70,
+                age if age < 30 * DAY => 50,
+                age if age < 90 * DAY => 30,
+                _ => 10,
+            })
+            .sum()
+    }
+
+    /// Reorders `projects` by descending score. The sort is stable, so the
+    /// incoming order breaks ties and never-visited projects keep it.
+    pub fn rank(&self, projects: &mut [String], now: u64) {
+        projects.sort_by_cached_key(|project| std::cmp::Reverse(self.score(project, now)));
+    }
+}
+
+pub fn project_frecency_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("projects.json")
+}
+
+/// Records one visit per project and persists the result.
+pub fn record_project_visits<'a>(
+    path: &Path,
+    projects: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Result<()> {
+    let mut frecency = ProjectFrecency::load(path).unwrap_or_default();
+    let now = unix_now();
+    for project in projects {
+        frecency.visit(project, now);
+    }
+    frecency.save(path)
+}
+
+/// Loads frecency state and ranks `projects` with it; a missing or unreadable
+/// state file leaves the order untouched.
+pub fn rank_projects(path: &Path, projects: &mut [String]) {
+    if let Ok(frecency) = ProjectFrecency::load(path) {
+        frecency.rank(projects, unix_now());
+    }
+}
+
+/// Resume command templates saved from the TUI's resume confirmation,
+/// keyed by source label. They take precedence over config.toml.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResumeOverrides {
+    pub templates: HashMap<String, String>,
+}
+
+impl ResumeOverrides {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let overrides = serde_json::from_str(&data)?;
+        Ok(overrides)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+pub fn resume_overrides_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("resume_overrides.json")
+}
+
+/// Most queries the TUI's pinned bar holds.
+pub const MAX_PINNED_QUERIES: usize = 6;
+
+/// Queries pinned to the bar at the top of the TUI, in the order they were
+/// pinned.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PinnedQueries {
+    pub queries: Vec<String>,
+}
+
+impl PinnedQueries {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let pinned = serde_json::from_str(&data)?;
+        Ok(pinned)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Pins `query`, or unpins it when it is pinned already. Returns whether
+    /// it is pinned now.
+    pub fn toggle(&mut self, query: &str) -> anyhow::Result<bool> {
+        let query = query.trim();
+        if query.is_empty() {
+            anyhow::bail!("type a query to pin first");
+        }
+        if let Some(at) = self.queries.iter().position(|pinned| pinned == query) {
+            self.queries.remove(at);
+            return Ok(false);
+        }
+        if self.queries.len() >= MAX_PINNED_QUERIES {
+            anyhow::bail!("{MAX_PINNED_QUERIES} queries are pinned already; unpin one first");
+        }
+        self.queries.push(query.to_string());
+        Ok(true)
+    }
+}
+
+pub fn pinned_queries_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("pinned_queries.json")
+}
+
+/// Friendly names for projects, keyed by the name memex derived for them
+/// (e.g. `-Users-nico-dev-memex` -> `memex`). The index keeps the original
+/// names; aliases apply wherever a project is shown or filtered on, so a
+/// rename takes effect without reindexing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectAliases {
+    pub aliases: HashMap<String, String>,
+}
+
+impl ProjectAliases {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let aliases = serde_json::from_str(&data)?;
+        Ok(aliases)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Name `project` was renamed to, if any.
+    pub fn alias(&self, project: &str) -> Option<&str> {
+        self.aliases.get(project).map(String::as_str)
+    }
+
+    /// How `project` is listed: its alias, or the name as stored.
+    pub fn shown(&self, project: &str) -> String {
+        self.alias(project).unwrap_or(project).to_string()
+    }
+
+    /// Project name stored in the index for `name`, which may be an alias.
+    pub fn resolve(&self, name: &str) -> String {
+        self.original(name).unwrap_or(name).to_string()
+    }
+
+    /// Original project name `alias` stands for.
+    pub fn original(&self, alias: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(_, name)| name.as_str() == alias)
+            .map(|(original, _)| original.as_str())
+    }
+
+    /// Shows `original` as `alias` from now on; renaming a project back to its
+    /// original name drops the alias. Two projects can't share an alias, since
+    /// a `--project` filter has to resolve to one of them.
+    pub fn rename(&mut self, original: &str, alias: &str) -> anyhow::Result<()> {
+        let alias = alias.trim();
+        if alias.is_empty() {
+            anyhow::bail!("project name can't be empty");
+        }
+        if let Some(taken) = self.original(alias)
+            && taken != original
+        {
+            anyhow::bail!("{alias} is already the name of {taken}");
+        }
+        if alias == original {
+            self.aliases.remove(original);
+        } else {
+            self.aliases.insert(original.to_string(), alias.to_string());
+        }
+        Ok(())
+    }
+}
+
+pub fn project_aliases_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("project_aliases.json")
+}
+
+/// Outcome of the most recent background index run, written by the index
+/// service so failures surface in `memex status` and the TUI.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    /// Unix timestamp (seconds) the last run finished
+    pub last_run: u64,
+    /// Unix timestamp (seconds) of the last successful run
+    pub last_success: Option<u64>,
+    /// Error chain of the last run when it failed
+    pub error: Option<String>,
+    /// Consecutive failed runs, reset by a successful one
+    pub failures: u32,
+}
+
+impl ServiceStatus {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let status = serde_json::from_str(&data)?;
+        Ok(status)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn record_success(&mut self, now: u64) {
+        self.last_run = now;
+        self.last_success = Some(now);
+        self.error = None;
+        self.failures = 0;
+    }
+
+    /// Returns true when this failure starts a streak (or changes the error),
+    /// so a continuous service retrying every few seconds notifies once.
+    pub fn record_failure(&mut self, error: String, now: u64) -> bool {
+        let fresh = self.failures == 0 || self.error.as_deref() != Some(error.as_str());
+        self.last_run = now;
+        self.error = Some(error);
+        self.failures = self.failures.saturating_add(1);
+        fresh
+    }
+
+    pub fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+pub fn service_status_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("service_status.json")
+}
+
+/// Records the outcome of a background index run. Returns true when the
+/// failure is new and worth notifying about.
+pub fn record_service_run(path: &Path, result: &anyhow::Result<()>) -> anyhow::Result<bool> {
+    let mut status = ServiceStatus::load(path).unwrap_or_default();
+    let now = unix_now();
+    let notify = match result {
+        Ok(()) => {
+            status.record_success(now);
+            false
+        }
+        Err(err) => status.record_failure(format!("{err:#}"), now),
+    };
+    status.save(path)?;
+    Ok(notify)
+}
+
+/// Lines kept per file in [`IngestErrors`]; the rest are only counted.
+pub const MAX_LINE_ERRORS_PER_FILE: usize = 100;
+
+/// Source file lines that failed to parse, skipped by ingest and kept here
+/// for `memex ingest-errors`. A file's entry is replaced when it is read
+/// from the start again and grows as later lines are appended.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IngestErrors {
+    pub files: BTreeMap<String, FileErrors>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileErrors {
+    pub source: String,
+    /// Unix timestamp (seconds) of the ingest that last found one
+    pub found_at: u64,
+    /// Malformed lines, including any past [`MAX_LINE_ERRORS_PER_FILE`]
+    pub total: usize,
+    pub lines: Vec<LineError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineError {
+    /// 1-based line number
+    pub line: u64,
+    pub error: String,
+}
+
+impl IngestErrors {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let errors = serde_json::from_str(&data)?;
+        Ok(errors)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Merges what one parse of `path` found. A parse from the start
+    /// replaces the file's entry, dropping it when the file is now clean.
+    pub fn update(
+        &mut self,
+        path: &str,
+        source: &str,
+        from_start: bool,
+        total: usize,
+        lines: Vec<LineError>,
+    ) {
+        if from_start {
+            self.files.remove(path);
+        }
+        if total == 0 {
+            return;
+        }
+        let entry = self.files.entry(path.to_string()).or_default();
+        entry.source = source.to_string();
+        entry.found_at = unix_now();
+        entry.total += total;
+        let room = MAX_LINE_ERRORS_PER_FILE.saturating_sub(entry.lines.len());
+        entry.lines.extend(lines.into_iter().take(room));
+    }
+
+    /// Malformed lines across every file.
+    pub fn line_count(&self) -> usize {
+        self.files.values().map(|file| file.total).sum()
+    }
+}
+
+pub fn ingest_errors_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("ingest_errors.json")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ingest_errors_replace_on_reread_and_append_on_resume() {
+        let line = |line: u64| LineError {
+            line,
+            error: "EOF while parsing".to_string(),
+        };
+        let mut errors = IngestErrors::default();
+        errors.update("/a.jsonl", "claude", true, 2, vec![line(3), line(9)]);
+        errors.update("/a.jsonl", "claude", false, 1, vec![line(12)]);
+        errors.update("/b.jsonl", "codex", true, 0, Vec::new());
+        assert_eq!(errors.line_count(), 3);
+        assert_eq!(errors.files["/a.jsonl"].lines.len(), 3);
+        assert!(!errors.files.contains_key("/b.jsonl"));
+
+        let many = (1..=150).map(line).collect();
+        errors.update("/a.jsonl", "claude", true, 150, many);
+        assert_eq!(errors.files["/a.jsonl"].total, 150);
+        assert_eq!(
+            errors.files["/a.jsonl"].lines.len(),
+            MAX_LINE_ERRORS_PER_FILE
+        );
+        errors.update("/a.jsonl", "claude", true, 0, Vec::new());
+        assert!(errors.files.is_empty());
+    }
+
+    #[test]
+    fn project_aliases_rename_round_trip_and_stay_unique() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let path = project_aliases_path(tmp.path());
+        let mut aliases = ProjectAliases::load(&path).expect("load missing");
+        aliases
+            .rename("-Users-nico-dev-memex", "memex")
+            .expect("rename");
+        assert!(aliases.rename("-Users-nico-old-memex", "memex").is_err());
+        aliases.save(&path).expect("save");
+
+        let mut loaded = ProjectAliases::load(&path).expect("load");
+        assert_eq!(loaded.original("memex"), Some("-Users-nico-dev-memex"));
+        assert_eq!(loaded.shown("-Users-nico-dev-memex"), "memex");
+        assert_eq!(loaded.resolve("memex"), "-Users-nico-dev-memex");
+        assert_eq!(loaded.resolve("other"), "other");
+        loaded
+            .rename("-Users-nico-dev-memex", "-Users-nico-dev-memex")
+            .expect("rename back");
+        assert!(loaded.aliases.is_empty());
+    }
+
+    #[test]
+    fn pinned_queries_toggle_up_to_the_limit() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let path = pinned_queries_path(tmp.path());
+        let mut pinned = PinnedQueries::load(&path).expect("load missing");
+        assert!(pinned.toggle("  TODO ").expect("pin"));
+        assert!(pinned.toggle("").is_err());
+        for n in 1..MAX_PINNED_QUERIES {
+            pinned.toggle(&format!("panic {n}")).expect("pin");
+        }
+        assert!(pinned.toggle("one too many").is_err());
+        assert!(!pinned.toggle("TODO").expect("unpin"));
+        pinned.save(&path).expect("save");
+
+        let loaded = PinnedQueries::load(&path).expect("load");
+        assert_eq!(loaded.queries.len(), MAX_PINNED_QUERIES - 1);
+        assert_eq!(loaded.queries[0], "panic 1");
+    }
+
+    #[test]
+    fn frecency_ranks_recent_frequent_projects_first() {
+        let now = 1_000 * 24 * 60 * 60;
+        let mut frecency = ProjectFrecency::default();
+        frecency.visit("old", now - 200 * 24 * 60 * 60);
+        frecency.visit("old", now - 199 * 24 * 60 * 60);
+        frecency.visit("active", now - 60);
+        frecency.visit("weekly", now - 3 * 24 * 60 * 60);
+
+        let mut projects = vec![
+            "alpha".to_string(),
+            "old".to_string(),
+            "weekly".to_string(),
+            "active".to_string(),
+        ];
+        frecency.rank(&mut projects, now);
+
+        assert_eq!(projects, vec!["active", "weekly", "old", "alpha"]);
+    }
+
+    #[test]
+    fn frecency_coalesces_rapid_visits_and_caps_history() {
+        let mut frecency = ProjectFrecency::default();
+        frecency.visit("memex", 100);
+        frecency.visit("memex", 160);
+        assert_eq!(frecency.projects["memex"], vec![160]);
+
+        for visit in 1..=20 {
+            frecency.visit("memex", 160 + visit * PROJECT_VISIT_MIN_GAP_SECS);
+        }
+        assert_eq!(frecency.projects["memex"].len(), PROJECT_VISIT_LIMIT);
+        frecency.visit("", 0);
+        assert!(!frecency.projects.contains_key(""));
+    }
+
+    #[test]
+    fn project_visits_persist_across_loads() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let path = project_frecency_path(tmp.path());
+        record_project_visits(&path, ["memex", "ghostree"]).expect("record visits");
+
+        let mut projects = vec!["alpha".to_string(), "memex".to_string()];
+        rank_projects(&path, &mut projects);
+
+        assert_eq!(projects, vec!["memex", "alpha"]);
+    }
+
+    #[test]
+    fn service_status_notifies_once_per_failure_streak() {
+        let mut status = ServiceStatus::default();
+        assert!(status.record_failure("disk full".to_string(), 10));
+        assert!(!status.record_failure("disk full".to_string(), 20));
+        assert!(status.record_failure("model download failed".to_string(), 30));
+        assert_eq!(status.failures, 3);
+        assert!(status.failed());
+
+        status.record_success(40);
+        assert!(!status.failed());
+        assert_eq!(status.last_success, Some(40));
+        assert!(status.record_failure("disk full".to_string(), 50));
+    }
+}
@@ -0,0 +1,371 @@
+//! Usage reports meant to leave the machine: session and message counts,
+//! token spend and model mix, with no conversation text. Anonymized reports
+//! also replace project names with salted hashes and round every count into
+//! a range, for `memex stats export --anonymize`.
+
+use crate::analytics::SessionRow;
+use crate::usage::UsageReport;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+/// A count as reported: exact, or the `low-high` range it falls in (low
+/// inclusive, high exclusive) when anonymized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Amount {
+    Exact(u64),
+    Range(String),
+}
+
+impl std::fmt::Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Amount::Exact(value) => write!(f, "{value}"),
+            Amount::Range(range) => f.write_str(range),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    pub anonymize: bool,
+    /// Mixed into project hashes so names can't be recovered by hashing
+    /// guesses. The same salt gives the same names across reports.
+    pub salt: String,
+    /// Range covered, as shown in the report.
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsExport {
+    pub anonymized: bool,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub sessions: Amount,
+    pub messages: Amount,
+    /// Token totals are left out when token tracking is off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<Amount>,
+    /// API-equivalent cost in whole dollars.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<Amount>,
+    pub by_source: Vec<SourceUsage>,
+    pub models: Vec<ModelShare>,
+    pub projects: Vec<ProjectUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceUsage {
+    pub source: String,
+    pub sessions: Amount,
+    pub messages: Amount,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<Amount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelShare {
+    pub model: String,
+    /// Share of all tokens, in whole percent.
+    pub percent: u64,
+    pub tokens: Amount,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectUsage {
+    pub project: String,
+    pub sessions: Amount,
+    pub messages: Amount,
+}
+
+#[derive(Default)]
+struct Tally {
+    sessions: u64,
+    messages: u64,
+    tokens: Option<u64>,
+}
+
+/// Builds the report from session rows and, when token tracking is on, a
+/// usage scan with its events. Sources, models and projects come largest
+/// first.
+pub fn build_export(
+    sessions: &[SessionRow],
+    usage: Option<&UsageReport>,
+    options: &ExportOptions,
+) -> StatsExport {
+    let amount = |value: u64| {
+        if options.anonymize {
+            Amount::Range(bucket(value))
+        } else {
+            Amount::Exact(value)
+        }
+    };
+
+    let mut sources: BTreeMap<String, Tally> = BTreeMap::new();
+    let mut projects: BTreeMap<String, Tally> = BTreeMap::new();
+    for row in sessions {
+        for tally in [
+            sources.entry(row.source.label().to_string()).or_default(),
+            projects.entry(row.display_project.clone()).or_default(),
+        ] {
+            tally.sessions += 1;
+            tally.messages += row.message_count;
+        }
+    }
+    let mut models: BTreeMap<String, u64> = BTreeMap::new();
+    if let Some(usage) = usage {
+        for summary in &usage.by_source {
+            let tally = sources.entry(summary.source.clone()).or_default();
+            *tally.tokens.get_or_insert(0) += summary.total_tokens;
+        }
+        for event in &usage.details {
+            let model = event.model.as_deref().unwrap_or("unknown").to_string();
+            *models.entry(model).or_default() += event.tokens.total();
+        }
+    }
+
+    let mut by_source: Vec<(String, Tally)> = sources.into_iter().collect();
+    by_source.sort_by_key(|(_, tally)| std::cmp::Reverse(tally.sessions));
+    let mut by_project: Vec<(String, Tally)> = projects.into_iter().collect();
+    by_project.sort_by_key(|(_, tally)| std::cmp::Reverse(tally.sessions));
+    let mut by_model: Vec<(String, u64)> = models.into_iter().collect();
+    by_model.sort_by_key(|(_, tokens)| std::cmp::Reverse(*tokens));
+    let model_tokens: u64 = by_model.iter().map(|(_, tokens)| tokens).sum();
+
+    StatsExport {
+        anonymized: options.anonymize,
+        since: options.since.clone(),
+        until: options.until.clone(),
+        sessions: amount(sessions.len() as u64),
+        messages: amount(sessions.iter().map(|row| row.message_count).sum()),
+        tokens: usage.map(|usage| amount(usage.total_tokens)),
+        cost_usd: usage.map(|usage| amount(usage.known_cost_usd.round() as u64)),
+        by_source: by_source
+            .into_iter()
+            .map(|(source, tally)| SourceUsage {
+                source,
+                sessions: amount(tally.sessions),
+                messages: amount(tally.messages),
+                tokens: tally.tokens.map(amount),
+            })
+            .collect(),
+        models: by_model
+            .into_iter()
+            .map(|(model, tokens)| ModelShare {
+                model,
+                percent: (tokens * 100 + model_tokens / 2) / model_tokens.max(1),
+                tokens: amount(tokens),
+            })
+            .collect(),
+        projects: by_project
+            .into_iter()
+            .map(|(project, tally)| ProjectUsage {
+                project: if options.anonymize {
+                    salted_name(&options.salt, &project)
+                } else {
+                    project
+                },
+                sessions: amount(tally.sessions),
+                messages: amount(tally.messages),
+            })
+            .collect(),
+    }
+}
+
+/// A salt for one report, so its project names match no other report's.
+pub fn random_salt() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    let seed = format!("{nanos}:{}", std::process::id());
+    format!("{:x}", Sha256::digest(seed.as_bytes()))
+}
+
+/// `project-` and the first 8 hex digits of SHA-256 over salt and name.
+pub fn salted_name(salt: &str, name: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update([0]);
+    hasher.update(name.as_bytes());
+    let mut hex = format!("{:x}", hasher.finalize());
+    hex.truncate(8);
+    format!("project-{hex}")
+}
+
+/// The range `value` falls in, on a 1-5-10 scale: `0`, `1-5`, `5-10`,
+/// `10-50`, ..., `1k-5k`, `1M-5M`.
+pub fn bucket(value: u64) -> String {
+    if value == 0 {
+        return "0".to_string();
+    }
+    let mut power = 1u64;
+    while power <= value / 10 {
+        power *= 10;
+    }
+    let (low, high) = if value >= power * 5 {
+        (power * 5, power * 10)
+    } else {
+        (power, power * 5)
+    };
+    format!("{}-{}", compact(low), compact(high))
+}
+
+fn compact(value: u64) -> String {
+    for (unit, suffix) in [
+        (1_000_000_000_000, "T"),
+        (1_000_000_000, "B"),
+        (1_000_000, "M"),
+        (1_000, "k"),
+    ] {
+        if value >= unit && value.is_multiple_of(unit) {
+            return format!("{}{suffix}", value / unit);
+        }
+    }
+    value.to_string()
+}
+
+/// The report as Markdown tables, for pasting into an issue or chat.
+pub fn render_markdown(export: &StatsExport) -> String {
+    let mut out = String::from("# Agent usage\n\n");
+    let range = match (&export.since, &export.until) {
+        (Some(since), Some(until)) => format!("{since} to {until}"),
+        (Some(since), None) => format!("since {since}"),
+        (None, Some(until)) => format!("until {until}"),
+        (None, None) => "all time".to_string(),
+    };
+    let _ = writeln!(
+        out,
+        "{range}: {} sessions, {} messages",
+        export.sessions, export.messages
+    );
+    if let (Some(tokens), Some(cost)) = (&export.tokens, &export.cost_usd) {
+        let _ = writeln!(out, "\n{tokens} tokens, about ${cost} at API prices");
+    }
+    if export.anonymized {
+        out.push_str("\nCounts are rounded to ranges and project names are hashed.\n");
+    }
+
+    out.push_str("\n| Source | Sessions | Messages | Tokens |\n|---|---|---|---|\n");
+    for source in &export.by_source {
+        let tokens = source
+            .tokens
+            .as_ref()
+            .map_or_else(|| "-".to_string(), Amount::to_string);
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {tokens} |",
+            source.source, source.sessions, source.messages
+        );
+    }
+    if !export.models.is_empty() {
+        out.push_str("\n| Model | Share | Tokens |\n|---|---|---|\n");
+        for model in &export.models {
+            let _ = writeln!(
+                out,
+                "| {} | {}% | {} |",
+                model.model, model.percent, model.tokens
+            );
+        }
+    }
+    if !export.projects.is_empty() {
+        out.push_str("\n| Project | Sessions | Messages |\n|---|---|---|\n");
+        for project in &export.projects {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} |",
+                project.project, project.sessions, project.messages
+            );
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SourceKind;
+
+    fn session(source: SourceKind, project: &str, messages: u64) -> SessionRow {
+        SessionRow {
+            source,
+            session_id: format!("{project}-{messages}"),
+            source_path: format!("/home/me/.claude/projects/{project}/s.jsonl"),
+            project: project.to_string(),
+            display_project: project.to_string(),
+            cwd: Some(format!("/home/me/{project}")),
+            started_at: 1,
+            last_at: 2,
+            message_count: messages,
+            title: Some("fix the secret thing".to_string()),
+        }
+    }
+
+    #[test]
+    fn buckets_on_a_one_five_ten_scale() {
+        let buckets: Vec<String> = [0, 1, 4, 5, 9, 10, 49, 50, 999, 1_000, 12_345, 7_500_000]
+            .into_iter()
+            .map(bucket)
+            .collect();
+        assert_eq!(
+            buckets,
+            [
+                "0", "1-5", "1-5", "5-10", "5-10", "10-50", "10-50", "50-100", "500-1k", "1k-5k",
+                "10k-50k", "5M-10M"
+            ]
+        );
+    }
+
+    #[test]
+    fn anonymized_exports_hash_projects_and_leave_out_text() {
+        let sessions = [
+            session(SourceKind::Claude, "acme-payments", 40),
+            session(SourceKind::Claude, "acme-payments", 12),
+            session(SourceKind::CodexSession, "side-project", 3),
+        ];
+        let exact = build_export(&sessions, None, &ExportOptions::default());
+        assert_eq!(exact.sessions, Amount::Exact(3));
+        assert_eq!(exact.messages, Amount::Exact(55));
+        assert_eq!(exact.projects[0].project, "acme-payments");
+        assert!(exact.tokens.is_none());
+
+        let options = ExportOptions {
+            anonymize: true,
+            salt: "pepper".to_string(),
+            since: Some("2026-01-01".to_string()),
+            until: None,
+        };
+        let export = build_export(&sessions, None, &options);
+        let json = serde_json::to_string(&export).expect("json");
+        for secret in ["acme", "side-project", "secret", "/home/me", ".jsonl"] {
+            assert!(!json.contains(secret), "{secret} leaked into {json}");
+        }
+        assert_eq!(export.messages, Amount::Range("50-100".to_string()));
+        assert_eq!(
+            export.projects[0].project,
+            salted_name("pepper", "acme-payments")
+        );
+        assert_ne!(
+            salted_name("pepper", "acme-payments"),
+            salted_name("salt", "acme-payments")
+        );
+        let sources: Vec<(&str, &Amount)> = export
+            .by_source
+            .iter()
+            .map(|source| (source.source.as_str(), &source.sessions))
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                ("claude", &Amount::Range("1-5".to_string())),
+                ("codex", &Amount::Range("1-5".to_string()))
+            ]
+        );
+        assert!(
+            render_markdown(&export).contains("since 2026-01-01: 1-5 sessions, 50-100 messages")
+        );
+    }
+}
@@ -0,0 +1,787 @@
+//! Shared team index. `memex push` sends redacted records to a receiver run
+//! with `memex serve`, which appends them to a log and indexes them, and
+//! `memex search --remote` queries it. The receiver never edits or deletes a
+//! record once accepted.
+
+use crate::bundle::redact;
+use crate::config::Paths;
+use crate::index::{QueryOptions, SearchIndex};
+use crate::moment::now_millis;
+use crate::types::{NOTE_ROLE, Record, RecordCursor};
+use anyhow::{Context, Result, anyhow};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Records per push request.
+pub const PUSH_BATCH: usize = 500;
+/// Largest request body the receiver reads.
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+/// Most results one remote search returns.
+const MAX_SEARCH_LIMIT: usize = 1000;
+/// How long the receiver waits on a stalled client before dropping it.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+/// Connections the receiver serves at once; more wait to be accepted.
+const MAX_CONNECTIONS: usize = 16;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushRequest {
+    /// Who pushed; shown on results as the `author:` prefix of `source_path`.
+    pub author: String,
+    pub records: Vec<Record>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushResponse {
+    pub accepted: usize,
+    /// Records the receiver already had, e.g. after a local reindex.
+    pub duplicates: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub score: f32,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// Copy of `record` that is safe to share: credentials and the home directory
+/// are redacted, and attachments are dropped. The receiver assigns its own
+/// doc id and ingest time.
+pub fn sanitize(record: &Record, home: Option<&str>) -> Record {
+    let clean = |text: &str| redact(text, home).into_owned();
+    Record {
+        doc_id: 0,
+        project: clean(&record.project),
+        text: clean(&record.text),
+        tool_input: record.tool_input.as_deref().map(clean),
+        tool_output: record.tool_output.as_deref().map(clean),
+        attachments: Vec::new(),
+        source_path: clean(&record.source_path),
+        ingested_at: 0,
+        ..record.clone()
+    }
+}
+
+/// Where `memex push` got to on each server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PushState {
+    pub pushed_through: BTreeMap<String, RecordCursor>,
+}
+
+impl PushState {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub fn push_state_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("push.json")
+}
+
+/// Records after `after` to push, oldest first, leaving out notes and, with
+/// `projects`, anything outside them.
+pub fn records_to_push(
+    index: &SearchIndex,
+    after: RecordCursor,
+    projects: &[String],
+) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    index.for_each_record(|record| {
+        if after.precedes(&record)
+            && record.role != NOTE_ROLE
+            && (projects.is_empty() || projects.contains(&record.project))
+        {
+            records.push(record);
+        }
+        Ok(())
+    })?;
+    records.sort_by_key(RecordCursor::of);
+    Ok(records)
+}
+
+/// Receiver side: an append-only JSONL log of every accepted record, and a
+/// search index built from it under `<root>/team`.
+pub struct TeamStore {
+    index: SearchIndex,
+    log: PathBuf,
+    seen: HashSet<[u8; 32]>,
+    next_doc_id: u64,
+}
+
+impl TeamStore {
+    /// Opens the store, rebuilding the index from the log when the two
+    /// disagree (a deleted or half-written index).
+    pub fn open(paths: &Paths) -> Result<Self> {
+        let dir = paths.root.join("team");
+        fs::create_dir_all(dir.join("index"))?;
+        let log = dir.join("records.jsonl");
+        let index = SearchIndex::open_or_create_for_ingest(&dir.join("index"))?;
+        let mut records = Vec::new();
+        if log.exists() {
+            let reader = BufReader::new(File::open(&log)?);
+            for (line_no, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let record: Record = serde_json::from_str(&line)
+                    .with_context(|| format!("{}:{}", log.display(), line_no + 1))?;
+                records.push(record);
+            }
+        }
+        let seen = records.iter().map(record_key).collect();
+        let next_doc_id = records
+            .iter()
+            .map(|record| record.doc_id + 1)
+            .max()
+            .unwrap_or(1);
+        if index.doc_count()? != records.len() {
+            let mut writer = index.writer()?;
+            writer.delete_all_documents()?;
+            for record in &records {
+                index.add_record(&mut writer, record)?;
+            }
+            writer.commit()?;
+        }
+        Ok(Self {
+            index,
+            log,
+            seen,
+            next_doc_id,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Adds the records `author` pushed, skipping ones already stored.
+    pub fn append(
+        &mut self,
+        author: &str,
+        records: Vec<Record>,
+        now_ms: u64,
+    ) -> Result<PushResponse> {
+        let mut response = PushResponse::default();
+        let mut fresh = Vec::new();
+        for mut record in records {
+            record.source_path = format!("{author}:{}", record.source_path);
+            if !self.seen.insert(record_key(&record)) {
+                response.duplicates += 1;
+                continue;
+            }
+            record.doc_id = self.next_doc_id;
+            record.ingested_at = now_ms;
+            self.next_doc_id += 1;
+            fresh.push(record);
+        }
+        if fresh.is_empty() {
+            return Ok(response);
+        }
+        // The log is written first: it is the source of truth, and a
+        // restart re-indexes anything the index commit missed.
+        let mut log = BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.log)?,
+        );
+        for record in &fresh {
+            serde_json::to_writer(&mut log, record)?;
+            log.write_all(b"\n")?;
+        }
+        log.flush()?;
+        let mut writer = self.index.writer()?;
+        for record in &fresh {
+            self.index.add_record(&mut writer, record)?;
+        }
+        writer.commit()?;
+        response.accepted = fresh.len();
+        Ok(response)
+    }
+
+    pub fn search(&self, options: &QueryOptions) -> Result<Vec<SearchHit>> {
+        let options = QueryOptions {
+            limit: options.limit.clamp(1, MAX_SEARCH_LIMIT),
+            ..options.clone()
+        };
+        Ok(self
+            .index
+            .search(&options)?
+            .into_iter()
+            .map(|(score, record)| SearchHit { score, record })
+            .collect())
+    }
+}
+
+/// Identity of a stored record, so a re-push of the same message is dropped.
+fn record_key(record: &Record) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in [
+        record.source.storage_label(),
+        &record.source_path,
+        &record.session_id,
+        &record.role,
+        &record.text,
+        record.tool_name.as_deref().unwrap_or(""),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(record.turn_id.to_le_bytes());
+    hasher.update(record.ts.to_le_bytes());
+    hasher.finalize().into()
+}
+
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    content_length: usize,
+    body: Vec<u8>,
+}
+
+/// Reads the request line and headers, leaving the body unread.
+fn read_head(reader: &mut impl BufRead) -> Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(anyhow!("malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+    let mut content_length = 0usize;
+    let mut authorization = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("connection closed in headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(anyhow!("malformed header: {header}"));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .parse()
+                .map_err(|_| anyhow!("invalid content-length: {value}"))?;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        }
+    }
+    Ok(Request {
+        method,
+        path,
+        authorization,
+        content_length,
+        body: Vec::new(),
+    })
+}
+
+fn read_body(reader: &mut impl BufRead, request: &mut Request) -> Result<()> {
+    if request.content_length > MAX_BODY_BYTES {
+        return Err(anyhow!("request body over {MAX_BODY_BYTES} bytes"));
+    }
+    request.body = vec![0; request.content_length];
+    reader.read_exact(&mut request.body)?;
+    Ok(())
+}
+
+fn json<T: Serialize>(status: u16, value: &T) -> (u16, String) {
+    (status, serde_json::to_string(value).unwrap_or_default())
+}
+
+fn error(status: u16, message: impl Into<String>) -> (u16, String) {
+    json(
+        status,
+        &ErrorBody {
+            error: message.into(),
+        },
+    )
+}
+
+fn parse_body<T: DeserializeOwned>(request: &Request) -> Result<T, (u16, String)> {
+    serde_json::from_slice(&request.body).map_err(|err| error(400, format!("invalid body: {err}")))
+}
+
+/// Whether `authorization` carries `token`. Both sides are hashed and every
+/// byte compared, so the time taken says nothing about how much matched.
+fn token_matches(authorization: Option<&str>, token: &str) -> bool {
+    let expected = Sha256::digest(format!("Bearer {token}"));
+    let given = Sha256::digest(authorization.unwrap_or_default());
+    expected
+        .iter()
+        .zip(given.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// Status and JSON body answering the request on `reader`. With `token`,
+/// the headers are checked before the body is read, so a client without the
+/// token never gets the receiver to take in a body.
+fn answer(
+    reader: &mut impl BufRead,
+    store: &Mutex<TeamStore>,
+    token: Option<&str>,
+    now_ms: u64,
+) -> (u16, String) {
+    let mut request = match read_head(reader) {
+        Ok(request) => request,
+        Err(err) => return error(400, format!("{err:#}")),
+    };
+    if let Some(token) = token
+        && !token_matches(request.authorization.as_deref(), token)
+    {
+        return error(401, "missing or wrong team token");
+    }
+    if let Err(err) = read_body(reader, &mut request) {
+        return error(400, format!("{err:#}"));
+    }
+    route(&request, store, now_ms)
+}
+
+/// Status and JSON body answering an authorized `request`.
+fn route(request: &Request, store: &Mutex<TeamStore>, now_ms: u64) -> (u16, String) {
+    let lock = || {
+        store
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    };
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/v1/status") => {
+            return json(200, &serde_json::json!({ "records": lock().len() }));
+        }
+        ("POST", "/v1/records") => {
+            let push: PushRequest = match parse_body(request) {
+                Ok(push) => push,
+                Err(response) => return response,
+            };
+            let author = push.author.trim();
+            if author.is_empty() || author.len() > 64 || author.contains([':', '/']) {
+                return error(400, "author must be 1-64 characters without ':' or '/'");
+            }
+            lock()
+                .append(author, push.records, now_ms)
+                .map(|response| json(200, &response))
+        }
+        ("POST", "/v1/search") => {
+            let options: QueryOptions = match parse_body(request) {
+                Ok(options) => options,
+                Err(response) => return response,
+            };
+            lock().search(&options).map(|hits| json(200, &hits))
+        }
+        _ => {
+            return error(
+                404,
+                format!("no route for {} {}", request.method, request.path),
+            );
+        }
+    };
+    result.unwrap_or_else(|err| error(500, format!("{err:#}")))
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    store: &Mutex<TeamStore>,
+    token: Option<&str>,
+) -> Result<()> {
+    let (status, body) = answer(&mut BufReader::new(&stream), store, token, now_millis());
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Answers push and search requests on `listener` until the process exits,
+/// on a fixed pool of [`MAX_CONNECTIONS`] threads. With `token`, requests must send it as a
+/// bearer token; without one the listener must be on a loopback address.
+pub fn serve(listener: TcpListener, store: TeamStore, token: Option<String>) -> Result<()> {
+    let addr = listener.local_addr()?;
+    if token.is_none() && !addr.ip().is_loopback() {
+        return Err(anyhow!(
+            "refusing to serve on {addr} without a team token; set MEMEX_TEAM_TOKEN (or `team_token` in config.toml) or listen on 127.0.0.1"
+        ));
+    }
+    eprintln!(
+        "memex team server on http://{addr} with {} records{}",
+        store.len(),
+        if token.is_some() {
+            ""
+        } else {
+            " (no team token set; only local clients can reach it)"
+        }
+    );
+    let store = Mutex::new(store);
+    // Each worker accepts its own connections, so a slow client holds up one
+    // worker and the rest wait in the listen backlog.
+    std::thread::scope(|scope| {
+        for _ in 0..MAX_CONNECTIONS {
+            scope.spawn(|| accept_loop(&listener, &store, token.as_deref()));
+        }
+    });
+    Ok(())
+}
+
+fn accept_loop(listener: &TcpListener, store: &Mutex<TeamStore>, token: Option<&str>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("memex serve: accept failed: {err}");
+                continue;
+            }
+        };
+        if let Err(err) = stream
+            .set_read_timeout(Some(IO_TIMEOUT))
+            .and_then(|()| stream.set_write_timeout(Some(IO_TIMEOUT)))
+        {
+            eprintln!("memex serve: {err}");
+            continue;
+        }
+        if let Err(err) = handle_connection(stream, store, token) {
+            eprintln!("memex serve: {err:#}");
+        }
+    }
+}
+
+/// Client side: a team server reached with curl.
+pub struct TeamRemote {
+    url: String,
+    token: Option<String>,
+}
+
+impl TeamRemote {
+    pub fn new(url: &str, token: Option<String>) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn push(&self, author: &str, records: Vec<Record>) -> Result<PushResponse> {
+        self.post(
+            "/v1/records",
+            &PushRequest {
+                author: author.to_string(),
+                records,
+            },
+        )
+    }
+
+    pub fn search(&self, options: &QueryOptions) -> Result<Vec<(f32, Record)>> {
+        let hits: Vec<SearchHit> = self.post("/v1/search", options)?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| (hit.score, hit.record))
+            .collect())
+    }
+
+    fn post<T: DeserializeOwned>(&self, path: &str, body: &impl Serialize) -> Result<T> {
+        let mut command = std::process::Command::new("curl");
+        command.args([
+            "-sS",
+            "--fail-with-body",
+            "-X",
+            "POST",
+            "-H",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+        ]);
+        // The token goes through a file so it never shows up in `ps`.
+        let header_file = match &self.token {
+            Some(token) => {
+                let mut file = tempfile::NamedTempFile::new()?;
+                writeln!(file, "Authorization: Bearer {token}")?;
+                command.arg("-H").arg(format!("@{}", file.path().display()));
+                Some(file)
+            }
+            None => None,
+        };
+        let mut child = command
+            .arg(format!("{}{path}", self.url))
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("failed to run curl; is it installed?")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            serde_json::to_writer(&mut stdin, body)?;
+        }
+        let output = child.wait_with_output()?;
+        drop(header_file);
+        if !output.status.success() {
+            let reason = serde_json::from_slice::<ErrorBody>(&output.stdout)
+                .map(|body| body.error)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&output.stderr).trim().to_string());
+            return Err(anyhow!("team server {}: {reason}", self.url));
+        }
+        serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("unexpected response from {}{path}", self.url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::QueryTuning;
+    use crate::types::{AgentSettings, SourceKind, test_record};
+
+    fn request(method: &str, path: &str, auth: Option<&str>, body: String) -> String {
+        let mut raw = format!("{method} {path} HTTP/1.1\r\nHost: team\r\n");
+        if let Some(auth) = auth {
+            raw.push_str(&format!("Authorization: {auth}\r\n"));
+        }
+        raw.push_str(&format!("Content-Length: {}\r\n\r\n{body}", body.len()));
+        raw
+    }
+
+    #[test]
+    fn pushed_records_are_redacted_deduplicated_and_searchable() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        let store = Mutex::new(TeamStore::open(&paths).expect("store"));
+        let token = Some("team-secret");
+
+        let shared = sanitize(
+            &Record {
+                text: "rotate the index; OPENAI_API_KEY=sk-abcdefghijklmnopqrstuvwx".to_string(),
+                source_path: "/home/ana/.claude/projects/memex/s1.jsonl".to_string(),
+                ..test_record(1, "s1")
+            },
+            Some("/home/ana"),
+        );
+        assert!(shared.text.contains("[REDACTED"));
+        assert_eq!(shared.source_path, "~/.claude/projects/memex/s1.jsonl");
+        let push = |records: Vec<Record>| {
+            let body = serde_json::to_string(&PushRequest {
+                author: "ana".to_string(),
+                records,
+            })
+            .expect("body");
+            answer(
+                &mut request("POST", "/v1/records", Some("Bearer team-secret"), body).as_bytes(),
+                &store,
+                token,
+                77,
+            )
+        };
+        let (status, body) = push(vec![
+            shared.clone(),
+            sanitize(
+                &Record {
+                    text: "merge segments".to_string(),
+                    source_path: "/home/ana/.claude/projects/memex/s1.jsonl".to_string(),
+                    ..test_record(2, "s1")
+                },
+                None,
+            ),
+        ]);
+        assert_eq!(status, 200, "{body}");
+        let response: PushResponse = serde_json::from_str(&body).expect("response");
+        assert_eq!(
+            response,
+            PushResponse {
+                accepted: 2,
+                duplicates: 0
+            }
+        );
+        let (_, body) = push(vec![shared]);
+        let response: PushResponse = serde_json::from_str(&body).expect("response");
+        assert_eq!(
+            response,
+            PushResponse {
+                accepted: 0,
+                duplicates: 1
+            }
+        );
+
+        let unauthorized = answer(
+            &mut request("GET", "/v1/status", None, String::new()).as_bytes(),
+            &store,
+            token,
+            0,
+        );
+        assert_eq!(unauthorized.0, 401);
+        let wrong = answer(
+            &mut request(
+                "GET",
+                "/v1/status",
+                Some("Bearer team-secreT"),
+                String::new(),
+            )
+            .as_bytes(),
+            &store,
+            token,
+            0,
+        );
+        assert_eq!(wrong.0, 401);
+        // Turned away on the headers, before the body it announces is read.
+        let oversized = format!(
+            "POST /v1/records HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_BYTES + 1
+        );
+        assert_eq!(answer(&mut oversized.as_bytes(), &store, token, 0).0, 401);
+
+        let options = QueryOptions {
+            query: "rotate".to_string(),
+            project: None,
+            role: None,
+            tool: None,
+            session_id: None,
+            source: None,
+            since: None,
+            until: None,
+            as_of: None,
+            settings: AgentSettings::default(),
+            has: Vec::new(),
+            edited: Vec::new(),
+            commands: Vec::new(),
+            files: Vec::new(),
+            langs: Vec::new(),
+            errors: Vec::new(),
+            require_all_terms: false,
+            fuzzy_distance: 0,
+            tuning: QueryTuning::default(),
+            limit: 10,
+        };
+        let body = serde_json::to_string(&options).expect("query");
+        let (status, body) = answer(
+            &mut request("POST", "/v1/search", Some("Bearer team-secret"), body).as_bytes(),
+            &store,
+            token,
+            0,
+        );
+        assert_eq!(status, 200, "{body}");
+        let hits: Vec<SearchHit> = serde_json::from_str(&body).expect("hits");
+        assert_eq!(hits.len(), 1);
+        let found = hits.into_iter().next().expect("hit").record;
+        assert_eq!(found.source, SourceKind::Claude);
+        assert_eq!(found.source_path, "ana:~/.claude/projects/memex/s1.jsonl");
+        assert_eq!(found.ingested_at, 77);
+
+        // A lost index is rebuilt from the log; the duplicate check survives.
+        drop(store);
+        std::fs::remove_dir_all(tmp.path().join("team/index")).expect("remove index");
+        let mut reopened = TeamStore::open(&paths).expect("reopen");
+        assert_eq!(reopened.len(), 2);
+        assert_eq!(reopened.search(&options).expect("search").len(), 1);
+        let again = reopened
+            .append(
+                "ana",
+                vec![sanitize(
+                    &Record {
+                        text: "merge segments".to_string(),
+                        source_path: "/home/ana/.claude/projects/memex/s1.jsonl".to_string(),
+                        ..test_record(2, "s1")
+                    },
+                    None,
+                )],
+                78,
+            )
+            .expect("append");
+        assert_eq!(again.duplicates, 1);
+    }
+
+    #[test]
+    fn an_interrupted_push_resumes_inside_one_index_run() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let index = SearchIndex::open_or_create(tmp.path()).expect("index");
+        let mut writer = index.writer().expect("writer");
+        // One `memex index` run: every record shares its ingest time.
+        for doc_id in 1..=5 {
+            let record = Record {
+                ingested_at: 100,
+                role: if doc_id == 4 { NOTE_ROLE } else { "user" }.to_string(),
+                ..test_record(doc_id, "s1")
+            };
+            index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+        let doc_ids = |records: &[Record]| -> Vec<u64> {
+            records.iter().map(|record| record.doc_id).collect()
+        };
+
+        let all = records_to_push(&index, RecordCursor::default(), &[]).expect("records");
+        assert_eq!(doc_ids(&all), [1, 2, 3, 5]);
+        // The first batch went out, then the push was cut short.
+        let mut state = PushState::default();
+        state
+            .pushed_through
+            .insert("http://team".to_string(), RecordCursor::of(&all[1]));
+        let path = push_state_path(tmp.path());
+        state.save(&path).expect("save");
+        let after = PushState::load(&path).expect("load").pushed_through["http://team"];
+        let rest = records_to_push(&index, after, &[]).expect("rest");
+        assert_eq!(doc_ids(&rest), [3, 5]);
+
+        // A cursor saved as a bare ingest time covers that whole run.
+        let old: PushState =
+            serde_json::from_str(r#"{"pushed_through":{"http://team":100}}"#).expect("old state");
+        let after = old.pushed_through["http://team"];
+        assert!(
+            records_to_push(&index, after, &[])
+                .expect("none")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn serving_off_loopback_requires_a_token() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let paths = Paths::new(Some(tmp.path().to_path_buf())).expect("paths");
+        let listener = TcpListener::bind("0.0.0.0:0").expect("bind");
+        let store = TeamStore::open(&paths).expect("store");
+        let err = serve(listener, store, None).expect_err("open server refused");
+        assert!(err.to_string().contains("without a team token"), "{err}");
+    }
+}
@@ -0,0 +1,699 @@
+//! Output templates for `memex search`, `show` and `sessions`. Each result's
+//! JSON, the same object `--json` prints, is rendered through a template the
+//! user supplies. The syntax is a small subset of Jinja: `{{ field | filter }}`,
+//! `{% if %}`/`{% elif %}`/`{% else %}`, `{% for x in list %}` and
+//! `{# comments #}`, with a `-` inside a tag trimming the whitespace beside it.
+
+use crate::config::UserConfig;
+use crate::text;
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Value, json};
+use std::fs;
+use std::io::Write;
+
+/// Filters `{{ value | name(args) }}` accepts, with their least and most
+/// argument counts.
+const FILTERS: &[(&str, usize, usize)] = &[
+    ("upper", 0, 0),
+    ("lower", 0, 0),
+    ("trim", 0, 0),
+    ("length", 0, 0),
+    ("tojson", 0, 0),
+    ("first", 0, 0),
+    ("last", 0, 0),
+    ("truncate", 1, 1),
+    ("default", 1, 1),
+    ("join", 0, 1),
+    ("replace", 2, 2),
+];
+
+/// A parsed output template.
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Output(Expr),
+    If {
+        cond: Cond,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        var: String,
+        items: Expr,
+        body: Vec<Node>,
+    },
+}
+
+/// A field path or literal, passed through filters left to right.
+#[derive(Debug, Clone)]
+struct Expr {
+    base: Operand,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    /// Dotted path: a loop variable or a field of the result, then object
+    /// keys or array indexes.
+    Path(Vec<String>),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone)]
+struct Filter {
+    name: String,
+    args: Vec<Value>,
+}
+
+/// `[not] expr [== expr | != expr]`
+#[derive(Debug, Clone)]
+struct Cond {
+    negate: bool,
+    expr: Expr,
+    /// Whether the comparison is `==`, and the right-hand side.
+    compare: Option<(bool, Expr)>,
+}
+
+/// Template source cut at its tags.
+enum Piece {
+    Text(String),
+    Output(String),
+    Tag(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Literal(Value),
+    Pipe,
+    Open,
+    Close,
+    Comma,
+    Eq,
+    Ne,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut pieces = split(source)?.into_iter();
+        let (nodes, _) = parse_block(&mut pieces, &[])?;
+        Ok(Self { nodes })
+    }
+
+    pub fn render(&self, data: &Value) -> Result<String> {
+        let mut out = String::new();
+        render_nodes(&self.nodes, data, &mut Vec::new(), &mut out)?;
+        Ok(out)
+    }
+
+    /// Renders `data` to `out`, ending with a newline whether or not the
+    /// template does, so every result starts on its own line.
+    pub fn write(&self, out: &mut impl Write, data: &Value) -> Result<()> {
+        let mut rendered = self.render(data)?;
+        if !rendered.ends_with('\n') {
+            rendered.push('\n');
+        }
+        out.write_all(rendered.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The template `command` (search, show or sessions) renders its output
+/// with: the one `--template` names, else the `[templates]` entry named
+/// after the command. A name not in `[templates]` is a template itself
+/// when it holds a tag, else a file path. None when there is neither, for
+/// the command's usual output.
+pub fn output_template(
+    config: &UserConfig,
+    command: &str,
+    requested: Option<&str>,
+) -> Result<Option<Template>> {
+    let source = match requested {
+        Some(name) => match config.template(name) {
+            Some(source) => source.to_string(),
+            None if name.contains("{{") || name.contains("{%") => name.to_string(),
+            None => fs::read_to_string(name).map_err(|err| {
+                anyhow!("no template named {name} in config.toml, and no template file: {err}")
+            })?,
+        },
+        None => match config.template(command) {
+            Some(source) => source.to_string(),
+            None => return Ok(None),
+        },
+    };
+    let template =
+        Template::parse(&source).map_err(|err| anyhow!("bad {command} template: {err}"))?;
+    Ok(Some(template))
+}
+
+fn split(source: &str) -> Result<Vec<Piece>> {
+    let mut pieces = Vec::new();
+    let mut rest = source;
+    let mut trim_next = false;
+    while let Some(start) = find_open(rest) {
+        let open = &rest[start..start + 2];
+        let close = match open {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+        let inner_start = start + 2;
+        let end = rest[inner_start..]
+            .find(close)
+            .map(|end| end + inner_start)
+            .ok_or_else(|| anyhow!("{open} is never closed with {close}"))?;
+        let mut inner = &rest[inner_start..end];
+        let trim_before = inner.starts_with('-');
+        if trim_before {
+            inner = &inner[1..];
+        }
+        let trim_after = inner.ends_with('-');
+        if trim_after {
+            inner = &inner[..inner.len() - 1];
+        }
+        push_text(&mut pieces, &rest[..start], trim_next, trim_before);
+        match open {
+            "{{" => pieces.push(Piece::Output(inner.trim().to_string())),
+            "{%" => pieces.push(Piece::Tag(inner.trim().to_string())),
+            _ => {}
+        }
+        trim_next = trim_after;
+        rest = &rest[end + close.len()..];
+    }
+    push_text(&mut pieces, rest, trim_next, false);
+    Ok(pieces)
+}
+
+fn find_open(text: &str) -> Option<usize> {
+    text.match_indices('{')
+        .map(|(at, _)| at)
+        .find(|&at| matches!(text.as_bytes().get(at + 1), Some(b'{' | b'%' | b'#')))
+}
+
+fn push_text(pieces: &mut Vec<Piece>, text: &str, trim_start: bool, trim_end: bool) {
+    let mut text = text;
+    if trim_start {
+        text = text.trim_start();
+    }
+    if trim_end {
+        text = text.trim_end();
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text.to_string()));
+    }
+}
+
+/// Nodes up to the first tag whose keyword is in `ends`, returned with that
+/// tag. The last of `ends` is the closing tag a missing end reports.
+fn parse_block(
+    pieces: &mut std::vec::IntoIter<Piece>,
+    ends: &[&str],
+) -> Result<(Vec<Node>, Option<String>)> {
+    let mut nodes = Vec::new();
+    while let Some(piece) = pieces.next() {
+        match piece {
+            Piece::Text(text) => nodes.push(Node::Text(text)),
+            Piece::Output(expr) => nodes.push(Node::Output(parse_expr(&expr)?)),
+            Piece::Tag(tag) => {
+                let (keyword, rest) = tag
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((tag.as_str(), ""));
+                if ends.contains(&keyword) {
+                    return Ok((nodes, Some(tag)));
+                }
+                match keyword {
+                    "if" => nodes.push(parse_if(rest, pieces)?),
+                    "for" => {
+                        let (var, items) = rest.split_once(" in ").ok_or_else(|| {
+                            anyhow!("expected {{% for x in list %}}, got {{% {tag} %}}")
+                        })?;
+                        let var = var.trim();
+                        if !is_name(var) {
+                            bail!("bad loop variable in {{% {tag} %}}");
+                        }
+                        let items = parse_expr(items)?;
+                        let (body, _) = parse_block(pieces, &["endfor"])?;
+                        nodes.push(Node::For {
+                            var: var.to_string(),
+                            items,
+                            body,
+                        });
+                    }
+                    _ => bail!("unexpected {{% {tag} %}}"),
+                }
+            }
+        }
+    }
+    match ends.last() {
+        Some(end) => Err(anyhow!("missing {{% {end} %}}")),
+        None => Ok((nodes, None)),
+    }
+}
+
+fn parse_if(cond: &str, pieces: &mut std::vec::IntoIter<Piece>) -> Result<Node> {
+    let cond = parse_cond(cond)?;
+    let (then, end) = parse_block(pieces, &["elif", "else", "endif"])?;
+    let end = end.unwrap_or_default();
+    let (keyword, rest) = end
+        .split_once(char::is_whitespace)
+        .unwrap_or((end.as_str(), ""));
+    let otherwise = match keyword {
+        "elif" => vec![parse_if(rest, pieces)?],
+        "else" => parse_block(pieces, &["endif"])?.0,
+        _ => Vec::new(),
+    };
+    Ok(Node::If {
+        cond,
+        then,
+        otherwise,
+    })
+}
+
+fn is_name(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut at = 0;
+    while at < chars.len() {
+        let ch = chars[at];
+        let next = chars.get(at + 1).copied();
+        match ch {
+            _ if ch.is_whitespace() => at += 1,
+            '|' => {
+                tokens.push(Token::Pipe);
+                at += 1;
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                at += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                at += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                at += 1;
+            }
+            '=' | '!' if next == Some('=') => {
+                tokens.push(if ch == '=' { Token::Eq } else { Token::Ne });
+                at += 2;
+            }
+            '"' | '\'' => {
+                let mut value = String::new();
+                at += 1;
+                loop {
+                    let Some(&found) = chars.get(at) else {
+                        bail!("unterminated string in `{src}`");
+                    };
+                    at += 1;
+                    match found {
+                        _ if found == ch => break,
+                        '\\' => {
+                            let escaped = chars.get(at).copied().unwrap_or('\\');
+                            at += 1;
+                            value.push(match escaped {
+                                'n' => '\n',
+                                't' => '\t',
+                                other => other,
+                            });
+                        }
+                        other => value.push(other),
+                    }
+                }
+                tokens.push(Token::Literal(Value::String(value)));
+            }
+            _ if ch.is_ascii_digit() || (ch == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = at;
+                at += 1;
+                while chars
+                    .get(at)
+                    .is_some_and(|found| found.is_ascii_digit() || *found == '.')
+                {
+                    at += 1;
+                }
+                let number: String = chars[start..at].iter().collect();
+                let value = match number.parse::<i64>() {
+                    Ok(number) => Value::from(number),
+                    Err(_) => number
+                        .parse::<f64>()
+                        .map(Value::from)
+                        .map_err(|_| anyhow!("bad number {number} in `{src}`"))?,
+                };
+                tokens.push(Token::Literal(value));
+            }
+            _ if ch.is_alphanumeric() || ch == '_' => {
+                let start = at;
+                while chars
+                    .get(at)
+                    .is_some_and(|found| found.is_alphanumeric() || *found == '_' || *found == '.')
+                {
+                    at += 1;
+                }
+                let name: String = chars[start..at].iter().collect();
+                tokens.push(match name.as_str() {
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "none" => Token::Literal(Value::Null),
+                    _ => Token::Name(name),
+                });
+            }
+            _ => bail!("unexpected `{ch}` in `{src}`"),
+        }
+    }
+    Ok(tokens)
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<Token>>;
+
+fn parse_expr(src: &str) -> Result<Expr> {
+    let mut tokens = tokenize(src)?.into_iter().peekable();
+    let expr = expr_from(&mut tokens, src)?;
+    if tokens.next().is_some() {
+        bail!("unexpected text after `{src}`");
+    }
+    Ok(expr)
+}
+
+fn parse_cond(src: &str) -> Result<Cond> {
+    let mut tokens = tokenize(src)?.into_iter().peekable();
+    let negate = tokens.next_if_eq(&Token::Name("not".to_string())).is_some();
+    let expr = expr_from(&mut tokens, src)?;
+    let compare = match tokens.next() {
+        None => None,
+        Some(Token::Eq) => Some((true, expr_from(&mut tokens, src)?)),
+        Some(Token::Ne) => Some((false, expr_from(&mut tokens, src)?)),
+        Some(_) => bail!("expected == or != in `{src}`"),
+    };
+    if tokens.next().is_some() {
+        bail!("unexpected text after `{src}`");
+    }
+    Ok(Cond {
+        negate,
+        expr,
+        compare,
+    })
+}
+
+fn expr_from(tokens: &mut Tokens, src: &str) -> Result<Expr> {
+    let base = match tokens.next() {
+        Some(Token::Name(name)) => Operand::Path(name.split('.').map(str::to_string).collect()),
+        Some(Token::Literal(value)) => Operand::Literal(value),
+        _ => bail!("expected a field or value in `{src}`"),
+    };
+    let mut filters = Vec::new();
+    while tokens.next_if_eq(&Token::Pipe).is_some() {
+        let Some(Token::Name(name)) = tokens.next() else {
+            bail!("expected a filter name after | in `{src}`");
+        };
+        let mut args = Vec::new();
+        if tokens.next_if_eq(&Token::Open).is_some() {
+            loop {
+                match tokens.next() {
+                    Some(Token::Literal(value)) => args.push(value),
+                    Some(Token::Comma) => {}
+                    Some(Token::Close) => break,
+                    _ => bail!("filter {name} takes literal arguments, in `{src}`"),
+                }
+            }
+        }
+        let Some((_, least, most)) = FILTERS.iter().find(|(known, _, _)| *known == name) else {
+            bail!("unknown filter {name}");
+        };
+        if args.len() < *least || args.len() > *most {
+            bail!(
+                "filter {name} takes {least} to {most} arguments, got {}",
+                args.len()
+            );
+        }
+        filters.push(Filter { name, args });
+    }
+    Ok(Expr { base, filters })
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    root: &Value,
+    scope: &mut Vec<(String, Value)>,
+    out: &mut String,
+) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Output(expr) => out.push_str(&display(&eval(expr, root, scope)?)),
+            Node::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let branch = if test(cond, root, scope)? {
+                    then
+                } else {
+                    otherwise
+                };
+                render_nodes(branch, root, scope, out)?;
+            }
+            Node::For { var, items, body } => {
+                let items = match eval(items, root, scope)? {
+                    Value::Array(items) => items,
+                    Value::Null => Vec::new(),
+                    other => bail!("cannot loop over {}", display(&other)),
+                };
+                let count = items.len();
+                for (at, item) in items.into_iter().enumerate() {
+                    scope.push((var.clone(), item));
+                    scope.push((
+                        "loop".to_string(),
+                        json!({ "index": at + 1, "first": at == 0, "last": at + 1 == count }),
+                    ));
+                    let rendered = render_nodes(body, root, scope, out);
+                    scope.truncate(scope.len() - 2);
+                    rendered?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn test(cond: &Cond, root: &Value, scope: &[(String, Value)]) -> Result<bool> {
+    let value = eval(&cond.expr, root, scope)?;
+    let holds = match &cond.compare {
+        None => truthy(&value),
+        Some((equal, other)) => equals(&value, &eval(other, root, scope)?) == *equal,
+    };
+    Ok(holds != cond.negate)
+}
+
+fn eval(expr: &Expr, root: &Value, scope: &[(String, Value)]) -> Result<Value> {
+    let mut value = match &expr.base {
+        Operand::Path(path) => lookup(path, root, scope),
+        Operand::Literal(value) => value.clone(),
+    };
+    for filter in &expr.filters {
+        value = apply(filter, value)?;
+    }
+    Ok(value)
+}
+
+/// A missing field is null, rendering as nothing.
+fn lookup(path: &[String], root: &Value, scope: &[(String, Value)]) -> Value {
+    let Some((first, rest)) = path.split_first() else {
+        return Value::Null;
+    };
+    let mut value = scope
+        .iter()
+        .rev()
+        .find(|(name, _)| name == first)
+        .map(|(_, value)| value)
+        .or_else(|| root.get(first.as_str()));
+    for segment in rest {
+        value = value.and_then(|found| match found {
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|at| items.get(at)),
+            _ => found.get(segment.as_str()),
+        });
+    }
+    value.cloned().unwrap_or(Value::Null)
+}
+
+fn apply(filter: &Filter, value: Value) -> Result<Value> {
+    let arg = |at: usize| filter.args.get(at).cloned().unwrap_or(Value::Null);
+    Ok(match filter.name.as_str() {
+        "upper" => Value::String(display(&value).to_uppercase()),
+        "lower" => Value::String(display(&value).to_lowercase()),
+        "trim" => Value::String(display(&value).trim().to_string()),
+        "length" => Value::from(match &value {
+            Value::Null => 0,
+            Value::Array(items) => items.len(),
+            Value::Object(map) => map.len(),
+            other => display(other).chars().count(),
+        }),
+        "tojson" => Value::String(serde_json::to_string(&value)?),
+        "first" | "last" => {
+            let first = filter.name == "first";
+            match value {
+                Value::Array(items) => {
+                    let item = if first {
+                        items.into_iter().next()
+                    } else {
+                        items.into_iter().last()
+                    };
+                    item.unwrap_or(Value::Null)
+                }
+                other => {
+                    let text = display(&other);
+                    let ch = if first {
+                        text.chars().next()
+                    } else {
+                        text.chars().last()
+                    };
+                    ch.map(|ch| Value::String(ch.to_string()))
+                        .unwrap_or(Value::Null)
+                }
+            }
+        }
+        "truncate" => {
+            let Some(max) = arg(0).as_u64() else {
+                bail!("truncate takes a number of characters");
+            };
+            Value::String(text::truncate_end(&display(&value), max as usize))
+        }
+        "default" => {
+            if truthy(&value) {
+                value
+            } else {
+                arg(0)
+            }
+        }
+        "join" => match value {
+            Value::Array(items) => Value::String(
+                items
+                    .iter()
+                    .map(display)
+                    .collect::<Vec<_>>()
+                    .join(&display(&arg(0))),
+            ),
+            other => other,
+        },
+        "replace" => Value::String(display(&value).replace(&display(&arg(0)), &display(&arg(1)))),
+        name => bail!("unknown filter {name}"),
+    })
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64().is_some_and(|number| number != 0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64() == right.as_f64(),
+        _ => left == right,
+    }
+}
+
+/// Strings as they are, null as nothing, lists and objects as JSON.
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+        Value::Array(_) | Value::Object(_) => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_fields_filters_conditions_and_loops() {
+        let hit = json!({
+            "score": 1.5,
+            "project": "memex",
+            "role": "user",
+            "snippet": "retry the\nflaky test",
+            "settings": { "model": "o3" },
+            "matches": [{ "text": "retry" }, { "text": "flaky" }],
+            "attachments": [],
+        });
+        let template = Template::parse(concat!(
+            "{{ project | upper }} [{{ role }}] {{ snippet | replace(\"\\n\", \" \") | truncate(14) }}\n",
+            "{%- if role == \"user\" %} asked{% elif role %} other{% else %} none{% endif %}",
+            "{% if not attachments %} no-attachments{% endif %}",
+            " model={{ settings.model | default(\"?\") }} sandbox={{ sandbox | default(\"-\") }}",
+            "{# ignored #} [{% for m in matches %}{{ loop.index }}:{{ m.text }}",
+            "{%- if not loop.last %},{% endif %}{% endfor %}] {{ matches | length }}",
+        ))
+        .expect("parse");
+        assert_eq!(
+            template.render(&hit).expect("render"),
+            "MEMEX [user] retry the fla… asked no-attachments model=o3 sandbox=- [1:retry,2:flaky] 2"
+        );
+
+        let mut out = Vec::new();
+        Template::parse("{{ missing }}{{ score }}")
+            .expect("parse")
+            .write(&mut out, &hit)
+            .expect("write");
+        assert_eq!(out, b"1.5\n");
+
+        for bad in [
+            "{{ project | shout }}",
+            "{% if role %}open",
+            "{% endfor %}",
+            "{{ project",
+            "{{ snippet | truncate }}",
+        ] {
+            assert!(Template::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn picks_the_named_template_or_the_commands_default() {
+        let config = UserConfig {
+            templates: Some(
+                [
+                    ("search".to_string(), "{{ session_id }}".to_string()),
+                    ("brief".to_string(), "{{ project }}".to_string()),
+                ]
+                .into(),
+            ),
+            ..UserConfig::default()
+        };
+        let hit = json!({ "session_id": "s1", "project": "memex" });
+        let render = |command, requested| {
+            output_template(&config, command, requested)
+                .expect("template")
+                .map(|template| template.render(&hit).expect("render"))
+        };
+        assert_eq!(render("search", None).as_deref(), Some("s1"));
+        assert_eq!(render("search", Some("brief")).as_deref(), Some("memex"));
+        assert_eq!(render("sessions", None), None);
+        assert_eq!(
+            render("sessions", Some("{{ session_id }}!")).as_deref(),
+            Some("s1!")
+        );
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("hit.tmpl");
+        fs::write(&file, "{{ project }}/{{ session_id }}\n").expect("write");
+        let file = file.to_string_lossy().to_string();
+        assert_eq!(render("show", Some(&file)).as_deref(), Some("memex/s1\n"));
+        assert!(output_template(&config, "search", Some("nope")).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Terminal-width text helpers shared by the TUI and CLI output. Widths are
+//! display columns, so CJK and most emoji count two and combining marks
+//! none, and text is only ever cut between grapheme clusters.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Columns `text` takes in a terminal.
+pub fn width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// Longest prefix of whole graphemes that fits in `max` columns.
+pub fn take_width(text: &str, max: usize) -> &str {
+    let mut used = 0usize;
+    for (idx, grapheme) in text.grapheme_indices(true) {
+        used += grapheme.width();
+        if used > max {
+            return &text[..idx];
+        }
+    }
+    text
+}
+
+/// Longest suffix of whole graphemes that fits in `max` columns.
+pub fn take_width_end(text: &str, max: usize) -> &str {
+    let mut used = 0usize;
+    for (idx, grapheme) in text.grapheme_indices(true).rev() {
+        used += grapheme.width();
+        if used > max {
+            return &text[idx + grapheme.len()..];
+        }
+    }
+    text
+}
+
+/// Cuts `text` to `max` columns, ending in `…` when anything was dropped.
+pub fn truncate_end(text: &str, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    if width(text) <= max {
+        return text.to_string();
+    }
+    let mut out = take_width(text, max - 1).to_string();
+    out.push('…');
+    out
+}
+
+/// Cuts `text` to `max` columns by replacing its middle with `…`, keeping
+/// both ends (project paths, session ids).
+pub fn truncate_middle(text: &str, max: usize) -> String {
+    if width(text) <= max {
+        return text.to_string();
+    }
+    if max <= 1 {
+        return "…".to_string();
+    }
+    let keep = max - 1;
+    let head = keep / 2;
+    format!(
+        "{}…{}",
+        take_width(text, head),
+        take_width_end(text, keep - head)
+    )
+}
+
+/// Pads `text` with trailing spaces to `columns`. `format!("{:<n$}")` pads by
+/// chars, which overflows the column for wide glyphs.
+pub fn pad_end(text: &str, columns: usize) -> String {
+    let mut out = text.to_string();
+    out.extend(std::iter::repeat_n(
+        ' ',
+        columns.saturating_sub(width(text)),
+    ));
+    out
+}
+
+/// Right-aligns `text` in `columns`.
+pub fn pad_start(text: &str, columns: usize) -> String {
+    let mut out: String = std::iter::repeat_n(' ', columns.saturating_sub(width(text))).collect();
+    out.push_str(text);
+    out
+}
+
+/// Collapses whitespace and cuts `text` to `max` columns, ending in `...`
+/// when it was cut. Stops reading at the cut, so it is cheap on huge records.
+pub fn summarize(text: &str, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    let mut out = String::new();
+    let mut used = 0usize;
+    let mut pending_space = false;
+    let mut truncated = false;
+    for grapheme in text.graphemes(true) {
+        if grapheme.chars().all(char::is_whitespace) {
+            pending_space = !out.is_empty();
+            continue;
+        }
+        let columns = grapheme.width() + usize::from(pending_space);
+        if used + columns > max {
+            truncated = true;
+            break;
+        }
+        if pending_space {
+            out.push(' ');
+            pending_space = false;
+        }
+        out.push_str(grapheme);
+        used += columns;
+    }
+    if truncated && max >= 3 {
+        let mut short = take_width(&out, max - 3).to_string();
+        short.push_str("...");
+        return short;
+    }
+    out
+}
+
+/// Query words worth matching in snippets: lowercased, punctuation trimmed,
+/// single characters dropped, each once.
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for part in query.split_whitespace() {
+        let cleaned = part.trim_matches(|ch: char| !ch.is_alphanumeric());
+        if cleaned.chars().count() < 2 {
+            continue;
+        }
+        let term = cleaned.to_lowercase();
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+/// Only this much of a record is scanned for a snippet, so huge tool output
+/// stays cheap to list.
+const SNIPPET_SCAN_BYTES: usize = 32 * 1024;
+
+/// A query-centered excerpt. `text` has `…` wherever it was cut and
+/// `highlights` are the byte ranges of query terms within it, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    pub highlights: Vec<(usize, usize)>,
+}
+
+impl Snippet {
+    /// `text` with every highlight wrapped in `open` and `close`.
+    pub fn marked(&self, open: &str, close: &str) -> String {
+        let mut out = String::with_capacity(self.text.len());
+        let mut last = 0;
+        for &(start, end) in &self.highlights {
+            out.push_str(&self.text[last..start]);
+            out.push_str(open);
+            out.push_str(&self.text[start..end]);
+            out.push_str(close);
+            last = end;
+        }
+        out.push_str(&self.text[last..]);
+        out
+    }
+}
+
+/// Cuts `text` to `max` columns around the sentence that matches the most
+/// distinct `terms` (then the most occurrences), with whitespace collapsed.
+/// Without terms or a hit it is [`summarize`].
+pub fn snippet(text: &str, terms: &[String], max: usize) -> Snippet {
+    let fallback = || Snippet {
+        text: summarize(text, max),
+        highlights: Vec::new(),
+    };
+    if terms.is_empty() || max < 3 {
+        return fallback();
+    }
+    let mut scan = text.len().min(SNIPPET_SCAN_BYTES);
+    while !text.is_char_boundary(scan) {
+        scan -= 1;
+    }
+    let (flat, starts) = flatten_sentences(&text[..scan]);
+    let hits = term_hits(&flat, terms);
+    if hits.is_empty() {
+        return fallback();
+    }
+
+    let sentence_of = |pos: usize| starts.partition_point(|&start| start <= pos) - 1;
+    let mut best: Option<(usize, (usize, usize))> = None;
+    for sentence in 0..starts.len() {
+        let in_sentence: Vec<&(usize, usize, usize)> = hits
+            .iter()
+            .filter(|hit| sentence_of(hit.0) == sentence)
+            .collect();
+        let mut distinct: Vec<usize> = in_sentence.iter().map(|hit| hit.2).collect();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let score = (distinct.len(), in_sentence.len());
+        if best.is_none_or(|(_, top)| score > top) {
+            best = Some((sentence, score));
+        }
+    }
+    let Some((sentence, _)) = best else {
+        return fallback();
+    };
+    let sentence_start = starts[sentence];
+    let sentence_end = starts.get(sentence + 1).map_or(flat.len(), |next| next - 1);
+    let first_hit = hits
+        .iter()
+        .find(|hit| hit.0 >= sentence_start)
+        .map_or((sentence_start, sentence_start), |hit| (hit.0, hit.1));
+
+    // Leave room for an ellipsis on each side.
+    let budget = max - 2;
+    let fits = width(&flat[sentence_start..sentence_end]) <= budget;
+    let (mut start, mut end) = if fits {
+        let slack = budget - width(&flat[sentence_start..sentence_end]);
+        let start = sentence_start - take_width_end(&flat[..sentence_start], slack / 2).len();
+        let used = width(&flat[start..sentence_end]);
+        let end = sentence_end + take_width(&flat[sentence_end..], budget - used).len();
+        let used = width(&flat[start..end]);
+        (
+            start - take_width_end(&flat[..start], budget - used).len(),
+            end,
+        )
+    } else {
+        let start =
+            first_hit.0 - take_width_end(&flat[sentence_start..first_hit.0], budget / 3).len();
+        (start, start + take_width(&flat[start..], budget).len())
+    };
+    // Cut between words where that keeps the sentence (or the hit) whole.
+    let (keep_start, keep_end) = if fits {
+        (sentence_start, sentence_end)
+    } else {
+        first_hit
+    };
+    if start > 0
+        && !flat[..start].ends_with(' ')
+        && let Some(space) = flat[start..keep_start].find(' ')
+    {
+        start += space + 1;
+    }
+    if end < flat.len()
+        && keep_end <= end
+        && !flat[end..].starts_with(' ')
+        && let Some(space) = flat[keep_end..end].rfind(' ')
+    {
+        end = keep_end + space;
+    }
+    if start > 0 {
+        start += flat[start..end].len() - flat[start..end].trim_start().len();
+    }
+    if end < flat.len() || scan < text.len() {
+        end -= flat[start..end].len() - flat[start..end].trim_end().len();
+    }
+
+    let prefix = if start > 0 { "…" } else { "" };
+    let mut out = format!("{prefix}{}", &flat[start..end]);
+    if end < flat.len() || scan < text.len() {
+        out.push('…');
+    }
+    let highlights = hits
+        .iter()
+        .filter(|hit| hit.0 >= start && hit.1 <= end)
+        .map(|hit| (hit.0 - start + prefix.len(), hit.1 - start + prefix.len()))
+        .collect();
+    Snippet {
+        text: out,
+        highlights,
+    }
+}
+
+/// `text` with whitespace runs collapsed to one space, and the byte offset
+/// where each sentence starts: after a line break or after `.`, `!` or `?`
+/// followed by whitespace.
+fn flatten_sentences(text: &str) -> (String, Vec<usize>) {
+    let mut flat = String::with_capacity(text.len());
+    let mut starts = vec![0];
+    let mut pending_space = false;
+    let mut pending_break = false;
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !flat.is_empty() {
+                pending_space = true;
+                pending_break |= ch == '\n' || flat.ends_with(['.', '!', '?']);
+            }
+            continue;
+        }
+        if pending_space {
+            flat.push(' ');
+        }
+        if pending_break {
+            starts.push(flat.len());
+        }
+        pending_space = false;
+        pending_break = false;
+        flat.push(ch);
+    }
+    (flat, starts)
+}
+
+/// Non-overlapping `(start, end, term)` matches of `terms` in `flat`,
+/// ignoring ASCII case, in text order.
+fn term_hits(flat: &str, terms: &[String]) -> Vec<(usize, usize, usize)> {
+    let lower = flat.to_ascii_lowercase();
+    let mut hits: Vec<(usize, usize, usize)> = Vec::new();
+    for (idx, term) in terms.iter().enumerate() {
+        for (pos, found) in lower.match_indices(term.as_str()) {
+            hits.push((pos, pos + found.len(), idx));
+        }
+    }
+    // Earliest first, and the longer term where two start together.
+    hits.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    let mut kept: Vec<(usize, usize, usize)> = Vec::with_capacity(hits.len());
+    for hit in hits {
+        if kept.last().is_none_or(|last| hit.0 >= last.1) {
+            kept.push(hit);
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_fit_display_columns_and_keep_graphemes_whole() {
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(truncate_end("日本語のテキスト", 7), "日本語…");
+        assert_eq!(width(&truncate_end("日本語のテキスト", 7)), 7);
+        assert_eq!(truncate_end("hello world", 5), "hell…");
+        // A combining accent stays with its letter.
+        assert_eq!(take_width("e\u{301}tude", 1), "e\u{301}");
+        assert_eq!(take_width_end("caf\u{e9}e\u{301}", 1), "e\u{301}");
+        assert_eq!(truncate_middle("项目/memex/src", 9), "项目…/src");
+        assert_eq!(pad_end("日本", 6), "日本  ");
+        assert_eq!(pad_start("7", 3), "  7");
+
+        assert_eq!(summarize("  fix\n\n the   bug  ", 80), "fix the bug");
+        assert_eq!(summarize("fix ", 3), "fix");
+        assert_eq!(summarize("日本語のテキストです", 10), "日本語...");
+        assert_eq!(summarize("🦀🦀🦀🦀🦀🦀", 7), "🦀🦀...");
+    }
+
+    #[test]
+    fn snippets_center_on_the_best_matching_sentence() {
+        let terms = query_terms("Flaky  login, x test!");
+        assert_eq!(terms, vec!["flaky", "login", "test"]);
+
+        let text = "We started by upgrading the toolchain.\n\nThe test is flaky.  \
+                    Then the flaky login test failed again on CI. After that we \
+                    rewrote the retry loop and went home.";
+        let snip = snippet(text, &terms, 60);
+        assert!(width(&snip.text) <= 60);
+        assert!(snip.text.starts_with('…'));
+        assert!(snip.text.ends_with('…'));
+        assert!(
+            snip.text
+                .contains("Then the flaky login test failed again on CI.")
+        );
+        assert_eq!(
+            snip.marked("[", "]"),
+            "…Then the [flaky] [login] [test] failed again on CI. After…"
+        );
+
+        // A sentence longer than the window starts a little before the hit.
+        let long = format!("{} needle {}", "word ".repeat(40), "tail ".repeat(40));
+        let snip = snippet(&long, &query_terms("needle"), 30);
+        assert!(width(&snip.text) <= 30);
+        assert_eq!(snip.highlights.len(), 1);
+        assert!(snip.text.starts_with("…word"));
+
+        // No hit, or no query, is the plain summary.
+        assert_eq!(snippet("fix the bug", &terms, 80).text, "fix the bug");
+        assert!(snippet("fix the bug", &[], 80).highlights.is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+//! Claude Code's todo lists and plan documents. Claude keeps them beside its
+//! transcripts, in `todos/<session>-agent-<agent>.json` and
+//! `plans/<slug>.md`; each todo item and each plan is indexed as a record of
+//! its session, found with `type:todo` or `type:plan`, and the preview shows
+//! a session's current todo list under its header.
+
+use crate::ingest::{
+    FileTask, IngestOptions, ParseContext, SourceAdapter, project_from_claude_path,
+};
+use crate::types::{AgentSettings, PLAN_ROLE, Record, RecordLinks, SourceKind, TODO_ROLE};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// One item of a todo list, as Claude writes it. Indexed as the record's
+/// tool input so the preview can read its status back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub content: String,
+    /// `pending`, `in_progress` or `completed`.
+    pub status: String,
+    #[serde(
+        rename = "activeForm",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub active_form: Option<String>,
+}
+
+impl TodoItem {
+    pub fn done(&self) -> bool {
+        self.status == "completed"
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.status == "in_progress"
+    }
+}
+
+/// The newest todo list among a session's todo records, in order.
+pub fn current_todos(records: &[Record]) -> Vec<TodoItem> {
+    let Some(latest) = records
+        .iter()
+        .filter(|record| record.role == TODO_ROLE)
+        .max_by_key(|record| record.ts)
+    else {
+        return Vec::new();
+    };
+    let mut items: Vec<&Record> = records
+        .iter()
+        .filter(|record| record.role == TODO_ROLE && record.source_path == latest.source_path)
+        .collect();
+    items.sort_by_key(|record| record.turn_id);
+    items
+        .into_iter()
+        .filter_map(|record| serde_json::from_str(record.tool_input.as_deref()?).ok())
+        .collect()
+}
+
+/// Session a todo file belongs to: the part of its name before
+/// `-agent-`.
+fn todo_session_id(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let session = stem
+        .split_once("-agent-")
+        .map_or(stem, |(session, _)| session);
+    Some(session.to_string())
+}
+
+/// Reads the todo lists and plans next to the Claude transcripts.
+#[derive(Default)]
+pub(crate) struct ClaudeTodoAdapter {
+    /// Claude's transcript directory, set by the scan.
+    projects: OnceLock<PathBuf>,
+    /// Session id and project of each plan slug, read from the transcripts
+    /// the first time a plan is parsed.
+    plan_sessions: OnceLock<HashMap<String, (String, String)>>,
+}
+
+impl ClaudeTodoAdapter {
+    fn plan_session(&self, slug: &str) -> Option<&(String, String)> {
+        self.plan_sessions
+            .get_or_init(|| {
+                self.projects
+                    .get()
+                    .map(|projects| plan_sessions(projects))
+                    .unwrap_or_default()
+            })
+            .get(slug)
+    }
+
+    /// The session and project a todo file's session was recorded under,
+    /// from the transcript of the same name.
+    fn todo_session(&self, path: &Path) -> Option<(String, String)> {
+        let session = todo_session_id(path)?;
+        let transcript = fs::read_dir(self.projects.get()?)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join(format!("{session}.jsonl")))
+            .find(|path| path.is_file());
+        let project = transcript.map_or_else(
+            || "unknown".to_string(),
+            |path| project_from_claude_path(&path),
+        );
+        Some((session, project))
+    }
+}
+
+impl SourceAdapter for ClaudeTodoAdapter {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Claude
+    }
+
+    fn scan(&self, options: &IngestOptions) -> Result<Vec<PathBuf>> {
+        let _ = self.projects.set(options.claude_source.clone());
+        let Some(claude) = options.claude_source.parent() else {
+            return Ok(Vec::new());
+        };
+        let mut files = Vec::new();
+        for (dir, ext) in [("todos", "json"), ("plans", "md")] {
+            let Ok(entries) = fs::read_dir(claude.join(dir)) else {
+                continue;
+            };
+            files.extend(
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().is_some_and(|found| found == ext)),
+            );
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn parse(&self, task: &FileTask, ctx: &ParseContext<'_>) -> Result<()> {
+        let contents = fs::read_to_string(&task.path)?;
+        let ts = (task.mtime.max(0) as u64) * 1000;
+        let is_plan = task.path.extension().is_some_and(|ext| ext == "md");
+        let mut turn_id = 0;
+        if is_plan {
+            let slug = task
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let (session_id, project) = self
+                .plan_session(&slug)
+                .cloned()
+                .unwrap_or_else(|| (format!("plan-{slug}"), "unknown".to_string()));
+            if !contents.trim().is_empty() {
+                let mut record = side_record(PLAN_ROLE, contents.clone(), ts, task);
+                record.doc_id = ctx.next_doc_id();
+                record.session_id = session_id;
+                record.project = project;
+                ctx.send(record)?;
+                turn_id = 1;
+            }
+        } else if let Some((session_id, project)) = self.todo_session(&task.path) {
+            // A list being rewritten may not parse; it is read again once
+            // it changes.
+            let items: Vec<TodoItem> = serde_json::from_str(&contents).unwrap_or_default();
+            for item in items {
+                let mut record = side_record(TODO_ROLE, item.content.clone(), ts, task);
+                record.doc_id = ctx.next_doc_id();
+                record.turn_id = turn_id;
+                record.session_id = session_id.clone();
+                record.project = project.clone();
+                record.tool_input = Some(serde_json::to_string(&item)?);
+                ctx.send(record)?;
+                turn_id += 1;
+            }
+        }
+        ctx.add_parsed_bytes(SourceKind::Claude, contents.len() as u64);
+        ctx.finish_file(task, task.size, turn_id)
+    }
+
+    fn resumable(&self) -> bool {
+        false
+    }
+}
+
+fn side_record(role: &str, text: String, ts: u64, task: &FileTask) -> Record {
+    Record {
+        source: SourceKind::Claude,
+        doc_id: 0,
+        ts,
+        project: String::new(),
+        session_id: String::new(),
+        turn_id: 0,
+        role: role.to_string(),
+        text,
+        tool_name: None,
+        tool_input: None,
+        tool_output: None,
+        links: RecordLinks::default(),
+        settings: AgentSettings::default(),
+        attachments: Vec::new(),
+        source_path: task.path.to_string_lossy().to_string(),
+        ingested_at: 0,
+    }
+}
+
+/// Session id and project of each plan slug Claude recorded in a
+/// transcript under `projects`. A session's lines carry its slug once it
+/// has one, so the first mention in each transcript is enough.
+fn plan_sessions(projects: &Path) -> HashMap<String, (String, String)> {
+    const SLUG_KEY: &[u8] = b"\"slug\":\"";
+    let mut sessions = HashMap::new();
+    let Ok(dirs) = fs::read_dir(projects) else {
+        return sessions;
+    };
+    for dir in dirs.filter_map(|entry| entry.ok()) {
+        let Ok(files) = fs::read_dir(dir.path()) else {
+            continue;
+        };
+        for path in files.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.extension().is_none_or(|ext| ext != "jsonl") {
+                continue;
+            }
+            let Ok(bytes) = fs::read(&path) else {
+                continue;
+            };
+            let Some(at) = memchr::memmem::find(&bytes, SLUG_KEY) else {
+                continue;
+            };
+            let rest = &bytes[at + SLUG_KEY.len()..];
+            let Some(end) = memchr::memchr(b'"', rest) else {
+                continue;
+            };
+            let slug = String::from_utf8_lossy(&rest[..end]).to_string();
+            let session = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            sessions
+                .entry(slug)
+                .or_insert_with(|| (session, project_from_claude_path(&path)));
+        }
+    }
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{IndexedToolContentLimits, Paths};
+    use crate::embed::{EmbedRuntimeConfig, ModelChoice};
+    use crate::index::SearchIndex;
+    use crate::ingest::ingest_all;
+
+    #[test]
+    fn todo_lists_and_plans_are_indexed_beside_their_session() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let claude = tmp.path().join(".claude");
+        let project_dir = claude.join("projects").join("-work-memex");
+        fs::create_dir_all(&project_dir).expect("projects");
+        fs::write(
+            project_dir.join("s1.jsonl"),
+            concat!(
+                r#"{"type":"user","uuid":"u1","parentUuid":null,"sessionId":"s1","slug":"quiet-river","timestamp":"2024-05-01T10:00:00Z","message":{"role":"user","content":"plan the shard split"}}"#,
+                "\n"
+            ),
+        )
+        .expect("transcript");
+        fs::create_dir_all(claude.join("todos")).expect("todos");
+        fs::write(
+            claude.join("todos").join("s1-agent-s1.json"),
+            r#"[{"content":"split the index","status":"completed","activeForm":"Splitting the index"},
+                {"content":"migrate readers","status":"in_progress","activeForm":"Migrating readers"},
+                {"content":"drop the old index","status":"pending","activeForm":"Dropping the old index"}]"#,
+        )
+        .expect("todo list");
+        fs::create_dir_all(claude.join("plans")).expect("plans");
+        fs::write(
+            claude.join("plans").join("quiet-river.md"),
+            "# Shard split\n\n1. Split by month\n2. Move readers over\n",
+        )
+        .expect("plan");
+
+        let paths = Paths::new(Some(tmp.path().join("memex"))).expect("paths");
+        paths.ensure_dirs().expect("dirs");
+        let index = SearchIndex::open_or_create_for_ingest(&paths.index).expect("index");
+        let options = IngestOptions {
+            claude_source: claude.join("projects"),
+            include_agents: false,
+            include_codex: false,
+            include_opencode: false,
+            include_cursor: false,
+            include_pi: false,
+            include_copilot: false,
+            embeddings: false,
+            backfill_embeddings: false,
+            model: ModelChoice::default(),
+            embed_runtime: EmbedRuntimeConfig::default(),
+            tool_content_limits: IndexedToolContentLimits::default(),
+            max_threads: None,
+            ocr_images: false,
+            embed_listener: None,
+            archive_tier: None,
+        };
+        ingest_all(&paths, &index, &options).expect("ingest");
+
+        let transcript = index.records_by_session_id("s1").expect("transcript");
+        assert!(!transcript.is_empty());
+        assert!(
+            transcript
+                .iter()
+                .all(|record| record.role != TODO_ROLE && record.role != PLAN_ROLE)
+        );
+        let todos = index
+            .session_records_with_role("s1", TODO_ROLE)
+            .expect("todos");
+        assert_eq!(todos.len(), 3);
+        assert!(
+            todos
+                .iter()
+                .all(|record| record.project == transcript[0].project)
+        );
+        let current = current_todos(&todos);
+        let statuses: Vec<&str> = current.iter().map(|item| item.status.as_str()).collect();
+        assert_eq!(statuses, ["completed", "in_progress", "pending"]);
+        assert_eq!(current[1].content, "migrate readers");
+
+        let plans = index
+            .session_records_with_role("s1", PLAN_ROLE)
+            .expect("plans");
+        assert_eq!(plans.len(), 1);
+        assert!(plans[0].text.contains("Split by month"));
+    }
+}
@@ -0,0 +1,257 @@
+use tantivy::tokenizer::{
+    LowerCaser, RemoveLongFilter, TextAnalyzer, Token, TokenStream, Tokenizer,
+};
+
+/// Name the code-aware analyzer is registered under on every opened index.
+pub const CODE_TOKENIZER: &str = "code";
+
+/// Characters that glue identifier pieces together (`doc_ids.u64`,
+/// `crate::index`, `foo-bar`, `src/index.rs`). A run of words joined by these
+/// is also indexed as one term so exact identifiers still match.
+const JOINERS: [char; 5] = ['_', '.', ':', '-', '/'];
+
+pub fn code_analyzer() -> TextAnalyzer {
+    TextAnalyzer::builder(CodeTokenizer)
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+        .build()
+}
+
+/// Splits like tantivy's `SimpleTokenizer` (runs of alphanumerics), and
+/// additionally emits:
+/// - the whole punctuation-joined run (`doc_ids.u64`) at the position of its
+///   first word;
+/// - camelCase / PascalCase pieces (`QueryOptions` -> `query`, `options`), the
+///   first piece sharing the whole word's position.
+///
+/// Queries go through the same analyzer, so `QueryOptions` becomes a phrase
+/// that also needs the whole `queryoptions` term: it finds the identifier in
+/// any casing, but not prose that says `query options`. Searching the pieces
+/// (`query`, `options`) finds both.
+#[derive(Clone, Default)]
+pub struct CodeTokenizer;
+
+impl Tokenizer for CodeTokenizer {
+    type TokenStream<'a> = CodeTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> CodeTokenStream {
+        CodeTokenStream {
+            tokens: code_tokens(text),
+            index: 0,
+            token: Token::default(),
+        }
+    }
+}
+
+pub struct CodeTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+    token: Token,
+}
+
+impl TokenStream for CodeTokenStream {
+    fn advance(&mut self) -> bool {
+        let Some(token) = self.tokens.get_mut(self.index) else {
+            return false;
+        };
+        self.token = std::mem::take(token);
+        self.index += 1;
+        true
+    }
+
+    fn token(&self) -> &Token {
+        &self.token
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.token
+    }
+}
+
+fn code_tokens(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    for (start, chunk) in chunks(text) {
+        let words = words(chunk);
+        if words.len() > 1 {
+            push_token(&mut tokens, start, chunk, position);
+        }
+        for (word_start, word) in words {
+            let offset = start + word_start;
+            push_token(&mut tokens, offset, word, position);
+            let parts = camel_parts(word);
+            if parts.len() > 1 {
+                for (idx, (part_start, part)) in parts.iter().enumerate() {
+                    push_token(&mut tokens, offset + part_start, part, position + idx);
+                }
+            }
+            position += parts.len();
+        }
+    }
+    tokens
+}
+
+fn push_token(tokens: &mut Vec<Token>, offset: usize, text: &str, position: usize) {
+    tokens.push(Token {
+        offset_from: offset,
+        offset_to: offset + text.len(),
+        position,
+        text: text.to_string(),
+        position_length: 1,
+    });
+}
+
+/// Maximal runs of alphanumerics and joiners, with joiners trimmed from the
+/// ends, paired with their byte offset.
+fn chunks(text: &str) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (idx, ch) in text.char_indices() {
+        let joinable = ch.is_alphanumeric() || JOINERS.contains(&ch);
+        match (joinable, start) {
+            (true, None) => start = Some(idx),
+            (false, Some(begin)) => {
+                push_chunk(&mut out, text, begin, idx);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(begin) = start {
+        push_chunk(&mut out, text, begin, text.len());
+    }
+    out
+}
+
+fn push_chunk<'a>(out: &mut Vec<(usize, &'a str)>, text: &'a str, start: usize, end: usize) {
+    let raw = &text[start..end];
+    let leading = raw.len() - raw.trim_start_matches(JOINERS).len();
+    let chunk = raw.trim_matches(JOINERS);
+    if !chunk.is_empty() {
+        out.push((start + leading, chunk));
+    }
+}
+
+/// Alphanumeric runs inside a chunk, with offsets relative to the chunk.
+fn words(chunk: &str) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (idx, ch) in chunk.char_indices() {
+        match (ch.is_alphanumeric(), start) {
+            (true, None) => start = Some(idx),
+            (false, Some(begin)) => {
+                out.push((begin, &chunk[begin..idx]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(begin) = start {
+        out.push((begin, &chunk[begin..]));
+    }
+    out
+}
+
+/// Splits `word` at lower->upper transitions (`queryOptions`) and before the
+/// last capital of an acronym run followed by lowercase (`HTTPServer`).
+/// Digits stay attached to the preceding piece (`u64`, `Utf8Error`).
+fn camel_parts(word: &str) -> Vec<(usize, &str)> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for idx in 1..chars.len() {
+        let (offset, ch) = chars[idx];
+        let prev = chars[idx - 1].1;
+        let next = chars.get(idx + 1).map(|(_, ch)| *ch);
+        let boundary = ch.is_uppercase()
+            && (prev.is_lowercase()
+                || prev.is_numeric()
+                || (prev.is_uppercase() && next.is_some_and(char::is_lowercase)));
+        if boundary {
+            parts.push((start, &word[start..offset]));
+            start = offset;
+        }
+    }
+    parts.push((start, &word[start..]));
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(text: &str) -> Vec<(String, usize)> {
+        let mut analyzer = code_analyzer();
+        let mut stream = analyzer.token_stream(text);
+        let mut out = Vec::new();
+        while stream.advance() {
+            let token = stream.token();
+            out.push((token.text.clone(), token.position));
+        }
+        out
+    }
+
+    fn term_texts(text: &str) -> Vec<String> {
+        terms(text).into_iter().map(|(text, _)| text).collect()
+    }
+
+    #[test]
+    fn camel_case_identifiers_index_whole_and_pieces() {
+        assert_eq!(
+            terms("use QueryOptions here"),
+            vec![
+                ("use".to_string(), 0),
+                ("queryoptions".to_string(), 1),
+                ("query".to_string(), 1),
+                ("options".to_string(), 2),
+                ("here".to_string(), 3),
+            ]
+        );
+        assert_eq!(
+            term_texts("HTTPServer"),
+            vec!["httpserver", "http", "server"]
+        );
+        assert_eq!(term_texts("u64"), vec!["u64"]);
+    }
+
+    #[test]
+    fn joined_identifiers_keep_the_full_term() {
+        assert_eq!(
+            terms("see doc_ids.u64."),
+            vec![
+                ("see".to_string(), 0),
+                ("doc_ids.u64".to_string(), 1),
+                ("doc".to_string(), 1),
+                ("ids".to_string(), 2),
+                ("u64".to_string(), 3),
+            ]
+        );
+        assert_eq!(
+            term_texts("crate::index::SearchIndex"),
+            vec![
+                "crate::index::searchindex",
+                "crate",
+                "index",
+                "searchindex",
+                "search",
+                "index",
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_prose_matches_simple_tokenizer() {
+        assert_eq!(
+            terms("Hello, world! (ok)"),
+            vec![
+                ("hello".to_string(), 0),
+                ("world".to_string(), 1),
+                ("ok".to_string(), 2),
+            ]
+        );
+        assert_eq!(
+            terms("naïve café"),
+            vec![("naïve".to_string(), 0), ("café".to_string(), 1)]
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use crate::index::SearchIndex;
+use crate::types::SourceFilter;
+use crate::vector::VectorIndex;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Terms that make up a topic label.
+const LABEL_TERMS: usize = 3;
+const MAX_ITERATIONS: usize = 50;
+const MIN_TERM_LEN: usize = 3;
+const MAX_TERM_LEN: usize = 24;
+
+/// Words common to nearly every agent conversation, which would otherwise
+/// crowd out the terms that tell topics apart.
+const STOPWORDS: &[&str] = &[
+    "about", "after", "again", "all", "also", "and", "any", "are", "because", "been", "before",
+    "being", "both", "but", "can", "could", "did", "does", "doing", "done", "each", "file",
+    "files", "for", "from", "get", "got", "had", "has", "have", "here", "how", "into", "its",
+    "just", "let", "like", "make", "may", "more", "most", "need", "now", "only", "other", "our",
+    "out", "over", "please", "same", "see", "should", "some", "such", "sure", "than", "that",
+    "the", "their", "them", "then", "there", "these", "they", "this", "those", "through", "use",
+    "used", "using", "very", "want", "was", "way", "were", "what", "when", "where", "which",
+    "while", "will", "with", "would", "you", "your",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct TopicOptions {
+    /// Number of topics; derived from the session count when unset.
+    pub k: Option<usize>,
+    pub source: Option<SourceFilter>,
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicSession {
+    pub session_id: String,
+    pub project: String,
+    pub last_ts: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Topic {
+    pub id: usize,
+    pub label: String,
+    pub terms: Vec<String>,
+    /// Closest to the topic's center first.
+    pub sessions: Vec<TopicSession>,
+}
+
+/// Result of the last `memex topics` run, largest topic first. Saved so
+/// session lists can show and filter by topic without clustering again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Topics {
+    pub generated_at: u64,
+    pub topics: Vec<Topic>,
+}
+
+impl Topics {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let topics = serde_json::from_str(&data)?;
+        Ok(topics)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Topic named by its id, its full label, or one of its label terms.
+    pub fn find(&self, name: &str) -> Option<&Topic> {
+        let name = name.trim().to_lowercase();
+        if let Ok(id) = name.parse::<usize>() {
+            return self.topics.iter().find(|topic| topic.id == id);
+        }
+        self.topics
+            .iter()
+            .find(|topic| topic.label == name)
+            .or_else(|| self.topics.iter().find(|topic| topic.terms.contains(&name)))
+    }
+
+    /// Topic label of every clustered session.
+    pub fn labels_by_session(&self) -> HashMap<String, String> {
+        self.topics
+            .iter()
+            .flat_map(|topic| {
+                topic
+                    .sessions
+                    .iter()
+                    .map(|session| (session.session_id.clone(), topic.label.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Splits a `topic:<name>` token off a query, for lists that filter by the
+/// saved topics rather than the index.
+pub fn take_topic_filter(query: &str) -> (String, Option<String>) {
+    let mut topic = None;
+    let rest: Vec<&str> = query
+        .split_whitespace()
+        .filter(|token| match token.strip_prefix("topic:") {
+            Some(name) if !name.is_empty() => {
+                topic = Some(name.to_string());
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    (rest.join(" "), topic)
+}
+
+pub fn topics_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("topics.json")
+}
+
+struct SessionPoint {
+    session: TopicSession,
+    sum: Vec<f32>,
+}
+
+/// Groups sessions by the mean embedding of their messages with spherical
+/// k-means, then labels each group by the terms that are most frequent in its
+/// sessions and rare elsewhere (TF-IDF with sessions as documents).
+pub fn cluster_sessions(
+    index: &SearchIndex,
+    vectors: &VectorIndex,
+    options: &TopicOptions,
+) -> Result<Topics> {
+    let wanted = |record: &crate::types::Record| {
+        options
+            .source
+            .is_none_or(|source| source.matches(record.source))
+            && options
+                .project
+                .as_deref()
+                .is_none_or(|project| record.project == project)
+    };
+
+    let mut points: HashMap<String, SessionPoint> = HashMap::new();
+    index.for_each_record(|record| {
+        if !wanted(&record) {
+            return Ok(());
+        }
+        let Some(vector) = vectors.get(record.doc_id)? else {
+            return Ok(());
+        };
+        let point = points
+            .entry(record.session_id.clone())
+            .or_insert_with(|| SessionPoint {
+                session: TopicSession {
+                    session_id: record.session_id.clone(),
+                    project: record.project.clone(),
+                    last_ts: 0,
+                },
+                sum: vec![0.0; vector.len()],
+            });
+        point.session.last_ts = point.session.last_ts.max(record.ts);
+        for (sum, value) in point.sum.iter_mut().zip(&vector) {
+            *sum += value;
+        }
+        Ok(())
+    })?;
+
+    let mut points: Vec<SessionPoint> = points.into_values().collect();
+    points.sort_by(|a, b| a.session.session_id.cmp(&b.session.session_id));
+    let centroids: Vec<Vec<f32>> = points
+        .iter()
+        .map(|point| normalized(point.sum.clone()))
+        .collect();
+    let k = options
+        .k
+        .unwrap_or_else(|| default_topic_count(points.len()))
+        .min(points.len());
+    let (assignments, centers) = kmeans(&centroids, k);
+
+    let position: HashMap<&str, usize> = points
+        .iter()
+        .enumerate()
+        .map(|(idx, point)| (point.session.session_id.as_str(), idx))
+        .collect();
+    let mut vocabulary: HashMap<String, u32> = HashMap::new();
+    let mut session_terms: Vec<HashSet<u32>> = vec![HashSet::new(); points.len()];
+    index.for_each_record(|record| {
+        if !wanted(&record) || !matches!(record.role.as_str(), "user" | "assistant") {
+            return Ok(());
+        }
+        let Some(&idx) = position.get(record.session_id.as_str()) else {
+            return Ok(());
+        };
+        for term in terms(&record.text) {
+            let next = vocabulary.len() as u32;
+            let id = *vocabulary.entry(term).or_insert(next);
+            session_terms[idx].insert(id);
+        }
+        Ok(())
+    })?;
+    let mut words = vec![String::new(); vocabulary.len()];
+    for (word, id) in vocabulary {
+        words[id as usize] = word;
+    }
+    let labels = label_terms(&assignments, &session_terms, k);
+
+    let mut topics: Vec<Topic> = (0..k)
+        .map(|cluster| {
+            let mut members: Vec<(f32, usize)> = assignments
+                .iter()
+                .enumerate()
+                .filter(|(_, assigned)| **assigned == cluster)
+                .map(|(idx, _)| (dot(&centroids[idx], &centers[cluster]), idx))
+                .collect();
+            members.sort_by(|a, b| b.0.total_cmp(&a.0));
+            let terms: Vec<String> = labels[cluster]
+                .iter()
+                .map(|id| words[*id as usize].clone())
+                .collect();
+            Topic {
+                id: 0,
+                label: if terms.is_empty() {
+                    format!("topic-{cluster}")
+                } else {
+                    terms.join("-")
+                },
+                terms,
+                sessions: members
+                    .into_iter()
+                    .map(|(_, idx)| points[idx].session.clone())
+                    .collect(),
+            }
+        })
+        .filter(|topic| !topic.sessions.is_empty())
+        .collect();
+    topics.sort_by_key(|topic| Reverse(topic.sessions.len()));
+    for (id, topic) in topics.iter_mut().enumerate() {
+        topic.id = id;
+    }
+
+    Ok(Topics {
+        generated_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        topics,
+    })
+}
+
+/// Rule of thumb k = sqrt(n / 2), kept to a list someone would read.
+fn default_topic_count(sessions: usize) -> usize {
+    ((sessions as f64 / 2.0).sqrt().round() as usize).clamp(2, 30)
+}
+
+/// Lowercased words worth labelling a topic with.
+pub(crate) fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| (MIN_TERM_LEN..=MAX_TERM_LEN).contains(&word.len()))
+        .filter(|word| !word.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = dot(&vector, &vector).sqrt();
+    if norm > 0.0 {
+        for value in &mut vector {
+            *value /= norm;
+        }
+    }
+    vector
+}
+
+/// Spherical k-means over unit vectors. Centers start from farthest-point
+/// picks rather than random ones, so the same sessions always give the same
+/// topics. Returns each point's cluster and the final centers.
+fn kmeans(points: &[Vec<f32>], k: usize) -> (Vec<usize>, Vec<Vec<f32>>) {
+    if points.is_empty() || k == 0 {
+        return (vec![0; points.len()], Vec::new());
+    }
+    let mut centers = vec![points[0].clone()];
+    let mut nearest: Vec<f32> = points.iter().map(|p| dot(p, &centers[0])).collect();
+    while centers.len() < k {
+        let Some((far, _)) = nearest.iter().enumerate().min_by(|a, b| a.1.total_cmp(b.1)) else {
+            break;
+        };
+        let center = points[far].clone();
+        for (best, point) in nearest.iter_mut().zip(points) {
+            *best = best.max(dot(point, &center));
+        }
+        centers.push(center);
+    }
+
+    let mut assignments = vec![usize::MAX; points.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (assigned, point) in assignments.iter_mut().zip(points) {
+            let best = centers
+                .iter()
+                .enumerate()
+                .max_by(|a, b| dot(point, a.1).total_cmp(&dot(point, b.1)))
+                .map(|(idx, _)| idx)
+                .unwrap_or(0);
+            if *assigned != best {
+                *assigned = best;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+        for (cluster, center) in centers.iter_mut().enumerate() {
+            let mut sum = vec![0.0; center.len()];
+            let mut members = 0;
+            for (point, _) in points
+                .iter()
+                .zip(&assignments)
+                .filter(|(_, assigned)| **assigned == cluster)
+            {
+                for (sum, value) in sum.iter_mut().zip(point) {
+                    *sum += value;
+                }
+                members += 1;
+            }
+            // An emptied cluster keeps its old center.
+            if members > 0 {
+                *center = normalized(sum);
+            }
+        }
+    }
+    (assignments, centers)
+}
+
+/// Top terms per cluster by (share of the cluster's sessions using the term)
+/// x ln(1 + sessions / sessions using the term). The smoothing favours terms
+/// most of a cluster shares over ones a single session happens to use.
+fn label_terms(assignments: &[usize], session_terms: &[HashSet<u32>], k: usize) -> Vec<Vec<u32>> {
+    let sessions = session_terms.len() as f32;
+    let mut document_frequency: HashMap<u32, u32> = HashMap::new();
+    let mut cluster_frequency: Vec<HashMap<u32, u32>> = vec![HashMap::new(); k];
+    let mut cluster_sizes = vec![0u32; k];
+    for (terms, &cluster) in session_terms.iter().zip(assignments) {
+        cluster_sizes[cluster] += 1;
+        for &term in terms {
+            *document_frequency.entry(term).or_default() += 1;
+            *cluster_frequency[cluster].entry(term).or_default() += 1;
+        }
+    }
+    cluster_frequency
+        .into_iter()
+        .zip(cluster_sizes)
+        .map(|(counts, size)| {
+            let mut scored: Vec<(f32, u32)> = counts
+                .into_iter()
+                .map(|(term, count)| {
+                    let idf = (1.0 + sessions / document_frequency[&term] as f32).ln();
+                    (count as f32 / size.max(1) as f32 * idf, term)
+                })
+                .collect();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            scored
+                .into_iter()
+                .take(LABEL_TERMS)
+                .map(|(_, term)| term)
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmeans_separates_sessions_and_labels_them_by_distinctive_terms() {
+        let points: Vec<Vec<f32>> = [
+            [1.0, 0.1, 0.0],
+            [0.9, 0.2, 0.0],
+            [0.0, 0.1, 1.0],
+            [0.1, 0.0, 0.9],
+        ]
+        .iter()
+        .map(|point| normalized(point.to_vec()))
+        .collect();
+        let (assignments, centers) = kmeans(&points, 2);
+        assert_eq!(centers.len(), 2);
+        assert_eq!(assignments[0], assignments[1]);
+        assert_eq!(assignments[2], assignments[3]);
+        assert_ne!(assignments[0], assignments[2]);
+
+        let texts = [
+            "The tantivy segment merge is slow",
+            "merge tantivy segments after the ingest",
+            "fix the flaky login test in ci",
+            "login test fails on ci again",
+        ];
+        let mut vocabulary: HashMap<String, u32> = HashMap::new();
+        let session_terms: Vec<HashSet<u32>> = texts
+            .iter()
+            .map(|text| {
+                terms(text)
+                    .map(|term| {
+                        let next = vocabulary.len() as u32;
+                        *vocabulary.entry(term).or_insert(next)
+                    })
+                    .collect()
+            })
+            .collect();
+        let labels = label_terms(&assignments, &session_terms, 2);
+        let words = |cluster: usize| -> Vec<&str> {
+            labels[cluster]
+                .iter()
+                .map(|id| {
+                    vocabulary
+                        .iter()
+                        .find(|(_, term)| *term == id)
+                        .map(|(word, _)| word.as_str())
+                        .expect("term")
+                })
+                .collect()
+        };
+        let first = words(assignments[0]);
+        assert!(
+            first.contains(&"tantivy") && first.contains(&"merge"),
+            "{first:?}"
+        );
+        let second = words(assignments[2]);
+        assert!(
+            second.contains(&"login") && second.contains(&"test"),
+            "{second:?}"
+        );
+        assert!(!second.contains(&"the"));
+    }
+
+    #[test]
+    fn topics_are_found_by_id_label_or_term() {
+        let session = |id: &str| TopicSession {
+            session_id: id.to_string(),
+            project: "memex".to_string(),
+            last_ts: 0,
+        };
+        let topics = Topics {
+            generated_at: 0,
+            topics: vec![
+                Topic {
+                    id: 0,
+                    label: "tantivy-merge-segment".to_string(),
+                    terms: vec!["tantivy".into(), "merge".into(), "segment".into()],
+                    sessions: vec![session("a"), session("b")],
+                },
+                Topic {
+                    id: 1,
+                    label: "login-test-ci".to_string(),
+                    terms: vec!["login".into(), "test".into(), "ci".into()],
+                    sessions: vec![session("c")],
+                },
+            ],
+        };
+        assert_eq!(topics.find("1").map(|topic| topic.id), Some(1));
+        assert_eq!(topics.find("Login-Test-CI").map(|topic| topic.id), Some(1));
+        assert_eq!(topics.find("merge").map(|topic| topic.id), Some(0));
+        assert!(topics.find("unknown").is_none());
+        assert_eq!(
+            take_topic_filter("flaky topic:login retries"),
+            ("flaky retries".to_string(), Some("login".to_string()))
+        );
+        assert_eq!(
+            topics.labels_by_session().get("c").map(String::as_str),
+            Some("login-test-ci")
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use crate::index::SearchIndex;
+use crate::moment::now_millis;
+use crate::shell::{change_dir_command, shell_quote};
 use crate::types::{Record, SourceFilter, SourceKind};
 use anyhow::{Context, Result, anyhow};
 use serde::Deserialize;
@@ -1533,7 +1535,7 @@ fn resolve_cwd_from_source(records: &[Record]) -> Option<PathBuf> {
         SourceKind::Cursor => cwd_from_cursor_session(Path::new(&first.source_path)),
         SourceKind::Opencode => cwd_from_opencode_session(Path::new(&first.source_path)),
         SourceKind::Pi => cwd_from_pi_session(Path::new(&first.source_path)),
-        SourceKind::CodexHistory => None,
+        SourceKind::CodexHistory | SourceKind::ChatGPT | SourceKind::External(_) => None,
     }
     .filter(|path| path.is_dir())
 }
@@ -1844,7 +1846,8 @@ fn yaml_quote(value: &str) -> String {
 
 fn claude_resume_command(session_id: &str, cwd: &Path) -> String {
     format!(
-        "cd {} && claude --resume {session_id}",
+        "{} {} && claude --resume {session_id}",
+        change_dir_command(),
         shell_quote(&cwd.to_string_lossy())
     )
 }
@@ -1856,27 +1859,6 @@ fn pi_resume_command(session_path: &Path) -> String {
     )
 }
 
-fn shell_quote(value: &str) -> String {
-    if value.is_empty() {
-        return "''".to_string();
-    }
-    let mut out = String::with_capacity(value.len() + 2);
-    out.push('\'');
-    for ch in value.chars() {
-        if ch == '\'' {
-            out.push_str("'\\''");
-        } else {
-            out.push(ch);
-        }
-    }
-    out.push('\'');
-    out
-}
-
-fn now_millis() -> u64 {
-    u64::try_from(chrono::Utc::now().timestamp_millis()).unwrap_or(0)
-}
-
 fn claude_projects_dir() -> Result<PathBuf> {
     Ok(home_dir()?.join(".claude").join("projects"))
 }
@@ -1904,7 +1886,7 @@ fn pi_sessions_root() -> Result<PathBuf> {
 }
 
 fn memex_transfer_dir() -> Result<PathBuf> {
-    Ok(home_dir()?.join(".memex").join("transfers"))
+    Ok(crate::config::Paths::new(None)?.root.join("transfers"))
 }
 
 fn pi_agent_root() -> Result<PathBuf> {
@@ -2175,7 +2157,13 @@ mod tests {
     fn codex_generated_transcript_stays_out_of_claude_projects() {
         let _guard = env_lock();
         let dir = tempfile::tempdir().unwrap();
-        let _env = EnvVarGuard::set_os(&[("HOME", Some(dir.path().as_os_str()))]);
+        let _env = EnvVarGuard::set_os(&[
+            ("HOME", Some(dir.path().as_os_str())),
+            ("MEMEX_HOME", None),
+            ("XDG_DATA_HOME", None),
+            ("XDG_STATE_HOME", None),
+            ("XDG_CONFIG_HOME", None),
+        ]);
         let conversation = Conversation {
             source: SourceKind::Claude,
             session_id: "claude-session".to_string(),
@@ -0,0 +1,227 @@
+use crate::types::Record;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+
+/// A session removed from the index, kept so it can be restored.
+///
+/// Each entry is one JSONL file in the trash directory: this header on the
+/// first line, then one record per line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// File stem, unique per deletion
+    #[serde(skip)]
+    pub id: String,
+    pub session_id: String,
+    pub project: String,
+    /// Command that removed the session, e.g. `forget`
+    pub reason: String,
+    /// Unix millis when the session was trashed
+    pub deleted_at: u64,
+    pub records: usize,
+}
+
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            dir: state_dir.join("trash"),
+        }
+    }
+
+    /// Writes `records` (all from one session) to a new trash entry.
+    pub fn put(&self, reason: &str, records: &[Record], now_ms: u64) -> Result<TrashEntry> {
+        let first = records
+            .first()
+            .ok_or_else(|| anyhow!("nothing to move to trash"))?;
+        fs::create_dir_all(&self.dir)?;
+        let entry = TrashEntry {
+            id: format!("{now_ms}-{}", file_safe(&first.session_id)),
+            session_id: first.session_id.clone(),
+            project: first.project.clone(),
+            reason: reason.to_string(),
+            deleted_at: now_ms,
+            records: records.len(),
+        };
+        let tmp = self.dir.join(format!("{}.jsonl.tmp", entry.id));
+        let mut out = BufWriter::new(fs::File::create(&tmp)?);
+        serde_json::to_writer(&mut out, &entry)?;
+        out.write_all(b"\n")?;
+        for record in records {
+            serde_json::to_writer(&mut out, record)?;
+            out.write_all(b"\n")?;
+        }
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp, self.path(&entry))?;
+        Ok(entry)
+    }
+
+    /// Entries, newest first. Unreadable files are skipped.
+    pub fn list(&self) -> Result<Vec<TrashEntry>> {
+        let mut entries = Vec::new();
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return Ok(entries);
+        };
+        for item in dir {
+            let path = item?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let mut header = String::new();
+            if BufReader::new(fs::File::open(&path)?)
+                .read_line(&mut header)
+                .is_err()
+            {
+                continue;
+            }
+            if let Ok(mut entry) = serde_json::from_str::<TrashEntry>(&header) {
+                entry.id = id.to_string();
+                entries.push(entry);
+            }
+        }
+        entries.sort_by_key(|e| Reverse(e.deleted_at));
+        Ok(entries)
+    }
+
+    /// The most recently trashed copy of `session_id`.
+    pub fn find(&self, session_id: &str) -> Result<Option<TrashEntry>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|entry| entry.session_id == session_id))
+    }
+
+    pub fn load(&self, entry: &TrashEntry) -> Result<Vec<Record>> {
+        let reader = BufReader::new(fs::File::open(self.path(entry))?);
+        let mut records = Vec::with_capacity(entry.records);
+        for line in reader.lines().skip(1) {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(serde_json::from_str(&line)?);
+        }
+        Ok(records)
+    }
+
+    pub fn remove(&self, entry: &TrashEntry) -> Result<()> {
+        fs::remove_file(self.path(entry))?;
+        Ok(())
+    }
+
+    /// Deletes every entry. Returns how many were removed.
+    pub fn empty(&self) -> Result<usize> {
+        let entries = self.list()?;
+        for entry in &entries {
+            self.remove(entry)?;
+        }
+        Ok(entries.len())
+    }
+
+    /// Deletes entries trashed more than `retention_days` ago.
+    pub fn expire(&self, retention_days: u64, now_ms: u64) -> Result<usize> {
+        let cutoff = now_ms.saturating_sub(retention_days.saturating_mul(24 * 60 * 60 * 1000));
+        let mut removed = 0;
+        for entry in self.list()? {
+            if entry.deleted_at < cutoff {
+                self.remove(&entry)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn path(&self, entry: &TrashEntry) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", entry.id))
+    }
+}
+
+fn file_safe(value: &str) -> String {
+    value
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .take(80)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SourceKind, test_record};
+
+    #[test]
+    fn trashed_sessions_round_trip_and_expire() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let trash = Trash::new(tmp.path());
+        let day = 24 * 60 * 60 * 1000;
+        trash
+            .put(
+                "forget",
+                &[Record {
+                    source: SourceKind::CodexSession,
+                    text: "message 1".to_string(),
+                    ingested_at: 7,
+                    ..test_record(1, "old")
+                }],
+                day,
+            )
+            .expect("trash old");
+        let entry = trash
+            .put(
+                "forget",
+                &[
+                    Record {
+                        source: SourceKind::CodexSession,
+                        text: "message 2".to_string(),
+                        ingested_at: 7,
+                        ..test_record(2, "s/1")
+                    },
+                    Record {
+                        source: SourceKind::CodexSession,
+                        text: "message 3".to_string(),
+                        ingested_at: 7,
+                        ..test_record(3, "s/1")
+                    },
+                ],
+                40 * day,
+            )
+            .expect("trash new");
+
+        let listed = trash.list().expect("list");
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].session_id, "s/1");
+        assert_eq!(listed[0].records, 2);
+
+        let found = trash.find("s/1").expect("find").expect("entry");
+        assert_eq!(found.id, entry.id);
+        let records = trash.load(&found).expect("load");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].source, SourceKind::CodexSession);
+        assert_eq!(records[1].text, "message 3");
+        assert_eq!(records[1].ingested_at, 7);
+
+        assert_eq!(trash.expire(30, 40 * day).expect("expire"), 1);
+        assert!(trash.find("old").expect("find").is_none());
+        assert_eq!(trash.empty().expect("empty"), 1);
+        assert!(trash.list().expect("list").is_empty());
+    }
+}
@@ -1,9 +1,36 @@
-use crate::analytics::{AnalyticsStore, ProjectGrouping, SessionRow, analytics_path};
-use crate::config::{Paths, UserConfig, default_claude_source};
-use crate::index::{QueryOptions, SearchIndex};
+use crate::analytics::{
+    AnalyticsStore, ProjectGrouping, SessionRow, analytics_path, repository_project_for_cwd,
+};
+use crate::config::{Paths, SessionListColumns, SessionSort, UserConfig, default_claude_source};
+use crate::context;
+use crate::health::{HealthIssue, check_health};
+use crate::highlight::{Highlighter, TokenKind, guess_lang, lang_for_fence};
+use crate::hyperlink::{file_url, hyperlink, terminal_allows};
+use crate::index::{
+    QueryOptions, QueryTuning, SearchIndex, SearchPhases, SegmentStats, extract_query_filters,
+};
 use crate::ingest::{IngestOptions, ingest_if_stale};
-use crate::types::{Record, SourceFilter, SourceKind};
+use crate::lang::{Translator, record_language};
+use crate::lock::{WriteLock, busy_message};
+use crate::moment::now_millis;
+use crate::notes::{SessionNotes, is_note_source_path, notes_path, set_note};
+use crate::outcomes::{Outcome, SessionOutcomes, outcomes_path, set_outcome};
+use crate::progress::{EmbedListener, EmbedStatus, format_count, format_rate};
+use crate::scoring::{DEFAULT_PROFILE, ScoreExplanation, ScoringProfile, apply_recency};
+use crate::shell::{change_dir_command, find_in_path, shell_command, shell_quote};
+use crate::state::{
+    PinnedQueries, ProjectAliases, ResumeOverrides, ServiceStatus, pinned_queries_path,
+    project_frecency_path, rank_projects, record_project_visits, resume_overrides_path,
+    service_status_path,
+};
+use crate::text::{self, pad_end, summarize, truncate_end, truncate_middle};
+use crate::todos::{TodoItem, current_todos};
+use crate::topics::{Topics, take_topic_filter, topics_path};
+use crate::types::{
+    AgentSettings, Attachment, COMPACT_SUMMARY_ROLE, Record, SourceFilter, SourceKind, TODO_ROLE,
+};
 use crate::usage::{CostMode, UsageQuery, scan_usage_activity};
+use crate::vector::{VectorIndex, manifest_modified};
 use anyhow::Result;
 use chrono::SecondsFormat;
 use crossterm::event::{
@@ -19,13 +46,17 @@ use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap};
 use serde::Deserialize;
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::BufRead;
 #[cfg(not(unix))]
 use std::io::Stdout;
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthChar;
 
 #[cfg(unix)]
 use std::ffi::CString;
@@ -43,7 +74,18 @@ type TuiWriter = Stdout;
 enum IndexUpdate {
     Started,
     Skipped,
-    Done { added: usize, embedded: usize },
+    /// Sent after each embedded batch while the refresh runs.
+    Embedding(EmbedStatus),
+    Done {
+        added: usize,
+        embedded: usize,
+        /// Source lines skipped as malformed.
+        malformed: usize,
+        files_scanned: usize,
+        elapsed: Duration,
+    },
+    /// Another process holds the write lock; the TUI only reads.
+    ReadOnly(String),
     Error(String),
 }
 
@@ -52,6 +94,11 @@ enum SearchUpdate {
         request_id: u64,
         sessions: Vec<SessionSummary>,
     },
+    /// Where the time of a search went, sent just before its `Results`.
+    SearchTimings {
+        request_id: u64,
+        timings: SearchTimings,
+    },
     Projects {
         request_id: u64,
         projects: Vec<String>,
@@ -80,11 +127,32 @@ enum SearchUpdate {
     DetailResults {
         request_id: u64,
         lines: Vec<PreviewLine>,
+        related: Vec<SessionSummary>,
+    },
+    /// More of a preview already started by `DetailResults`.
+    DetailAppend {
+        request_id: u64,
+        lines: Vec<PreviewLine>,
+        related: Vec<SessionSummary>,
+    },
+    /// Earlier history, put in place of the preview's
+    /// [`PreviewLine::EarlierHistory`] marker.
+    DetailEarlier {
+        request_id: u64,
+        lines: Vec<PreviewLine>,
     },
     DetailError {
         request_id: u64,
         message: String,
     },
+    Heatmap {
+        request_id: u64,
+        counts: HashMap<chrono::NaiveDate, u64>,
+    },
+    HeatmapError {
+        request_id: u64,
+        message: String,
+    },
     HomeActivity {
         request_id: u64,
         points: Vec<HomeChartPoint>,
@@ -107,6 +175,14 @@ enum SearchUpdate {
         sources: Vec<SourceChoice>,
         projects: Vec<String>,
     },
+    Health {
+        issues: Vec<HealthIssue>,
+    },
+    /// Records each pinned query matches, `None` where it failed.
+    PinnedCounts {
+        request_id: u64,
+        counts: Vec<Option<usize>>,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -116,6 +192,77 @@ struct DetailRequest {
     mode: PreviewMode,
     query: String,
     show_tools: bool,
+    show_threads: bool,
+    limits: PreviewLimits,
+    /// Look up related sessions by embedding; off in lexical-only mode.
+    related: bool,
+    /// Translate messages in other languages, when turned on with `T`.
+    translate: Option<Translator>,
+    /// Build only the newest records of a full history; earlier ones are
+    /// asked for as the user scrolls up.
+    lazy_history: bool,
+    /// Build the history records before this index instead of the preview.
+    earlier: Option<usize>,
+}
+
+/// How much of a session the preview shows, from the `preview_*` config
+/// keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct PreviewLimits {
+    /// Newest messages shown when there is no query.
+    tail_messages: usize,
+    /// Messages shown before and after each match; without it, the match's
+    /// whole exchange, from the prompt to the next one.
+    match_context: Option<usize>,
+    /// Characters of a message shown before it is cut off.
+    message_chars: usize,
+    /// Only messages holding every query term count as matches.
+    all_terms: bool,
+}
+
+impl PreviewLimits {
+    fn from_config(config: &UserConfig) -> Self {
+        Self {
+            tail_messages: config.preview_tail_messages(),
+            match_context: config.preview_match_context(),
+            message_chars: config.preview_message_chars(),
+            all_terms: config.preview_all_terms(),
+        }
+    }
+}
+
+impl Default for PreviewLimits {
+    fn default() -> Self {
+        Self::from_config(&UserConfig::default())
+    }
+}
+
+/// `memex tui` flags, which take precedence over config.toml.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TuiOverrides {
+    pub result_limit: Option<usize>,
+    pub tail_messages: Option<usize>,
+    pub match_context: Option<usize>,
+    pub message_chars: Option<usize>,
+    /// Start in lexical-only mode.
+    pub lexical_only: bool,
+}
+
+impl TuiOverrides {
+    fn apply(&self, config: &mut UserConfig) {
+        if let Some(limit) = self.result_limit {
+            config.tui_result_limit = Some(limit);
+        }
+        if let Some(tail) = self.tail_messages {
+            config.preview_tail_messages = Some(tail);
+        }
+        if let Some(context) = self.match_context {
+            config.preview_match_context = Some(context);
+        }
+        if let Some(chars) = self.message_chars {
+            config.preview_message_chars = Some(chars);
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -126,6 +273,89 @@ struct SearchRequest {
     source: SourceChoice,
     since: Option<u64>,
     grouping: ProjectGrouping,
+    profile: ScoringProfile,
+    tuning: QueryTuning,
+    limit: usize,
+}
+
+/// Where the time of the last search went, for the `~` debug overlay.
+#[derive(Clone, Debug, Default)]
+struct SearchTimings {
+    query: String,
+    /// Served from the search cache without touching the index.
+    cached: bool,
+    phases: SearchPhases,
+    /// Grouping hits into sessions, or reading analytics for an empty query.
+    aggregation: Duration,
+    /// Analytics, notes, topics and outcomes added to each session.
+    enrichment: Duration,
+    total: Duration,
+    hits: usize,
+    sessions: usize,
+}
+
+/// What the last index refresh did, for the `~` debug overlay.
+#[derive(Clone, Copy, Debug)]
+struct IngestTimings {
+    files_scanned: usize,
+    added: usize,
+    embedded: usize,
+    elapsed: Duration,
+    /// Final embedding throughput, when anything was embedded.
+    embed: Option<EmbedStatus>,
+}
+
+/// Everything a query result depends on, including the index generation it
+/// was computed against.
+#[derive(Clone, Debug, PartialEq)]
+struct SearchCacheKey {
+    query: String,
+    project: String,
+    source: SourceChoice,
+    since: Option<u64>,
+    grouping: ProjectGrouping,
+    profile: ScoringProfile,
+    tuning: QueryTuning,
+    limit: usize,
+    generation: u64,
+}
+
+impl SearchCacheKey {
+    fn new(request: &SearchRequest, generation: u64) -> Self {
+        Self {
+            query: request.query.clone(),
+            project: request.project.clone(),
+            source: request.source,
+            since: request.since,
+            grouping: request.grouping,
+            profile: request.profile,
+            tuning: request.tuning.clone(),
+            limit: request.limit,
+            generation,
+        }
+    }
+}
+
+/// Most recently used first; entries from older generations fall off the end.
+#[derive(Default)]
+struct SearchCache {
+    entries: VecDeque<(SearchCacheKey, Vec<SessionSummary>)>,
+}
+
+impl SearchCache {
+    fn get(&mut self, key: &SearchCacheKey) -> Option<Vec<SessionSummary>> {
+        let pos = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(pos)?;
+        let sessions = entry.1.clone();
+        self.entries.push_front(entry);
+        Some(sessions)
+    }
+
+    fn insert(&mut self, key: SearchCacheKey, sessions: Vec<SessionSummary>) {
+        self.entries.retain(|(k, _)| k != &key);
+        self.entries.push_front((key, sessions));
+        self.entries.truncate(SEARCH_CACHE_CAPACITY);
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -144,14 +374,23 @@ enum IndexState {
     Idle,
     Loading,
     Complete,
+    ReadOnly(String),
     Error(String),
 }
 
-const RESULT_LIMIT: usize = 200;
-const DETAIL_TAIL_LINES: usize = 10;
-const MAX_MESSAGE_CHARS: usize = 4000;
+/// Recent query results kept by the search worker.
+const SEARCH_CACHE_CAPACITY: usize = 16;
+/// Records rendered per preview chunk when streaming a long history.
+const DETAIL_STREAM_RECORDS: usize = 200;
+/// Project history covers this long up to the selected session's last message.
+const PROJECT_HISTORY_WINDOW_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+/// Newest records kept in a project history.
+const PROJECT_HISTORY_LIMIT: usize = 20_000;
+const RELATED_SESSIONS_LIMIT: usize = 3;
+const RELATED_SEARCH_LIMIT: usize = 200;
+/// Tool JSON longer than this is shown as written rather than re-indented.
+const PRETTY_JSON_MAX_BYTES: usize = 4000;
 const PREVIEW_LINE_MAX_CHARS: usize = 320;
-const CONTEXT_AROUND_MATCH: usize = 1;
 const RECENT_SESSIONS_LIMIT: usize = 200;
 const RECENT_RECORDS_MULTIPLIER: usize = 50;
 const HOME_COLUMN_MIN_WIDTH: u16 = 64;
@@ -160,8 +399,17 @@ const HOME_DROPDOWN_MAX_ROWS: u16 = 8;
 // Braille cells fill bottom-up in four dot rows, giving the chart a dotted
 // texture at 4x the vertical resolution of the character grid.
 const HOME_BRAILLE: [char; 5] = [' ', '⣀', '⣤', '⣶', '⣿'];
+/// Weeks of history the heatmap loads; the screen shows as many as fit.
+const HEATMAP_WEEKS: u64 = 53;
+const HEATMAP_LABEL_WIDTH: u16 = 4;
+const HEATMAP_GLYPHS: [char; 5] = ['·', '░', '▒', '▓', '█'];
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
 const SPINNER_TICK: Duration = Duration::from_millis(80);
-const HOME_SEARCH_DEBOUNCE: Duration = Duration::from_millis(100);
+/// Threads in the pool that runs one-off loads for the home charts,
+/// projects, timeline and heatmap.
+const BACKGROUND_WORKERS: usize = 3;
+/// How often the footer re-reads the background index service's last outcome.
+const SERVICE_STATUS_POLL: Duration = Duration::from_secs(30);
 const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
 const OUTER_PAD_X: u16 = 0;
@@ -172,6 +420,10 @@ const PANEL_PAD_Y: u16 = 1;
 const PANEL_TITLE_HEIGHT: u16 = 1;
 const QUERY_BAR_HEIGHT: u16 = 1;
 const FOOTER_HEIGHT: u16 = 1;
+const HEALTH_BANNER_HEIGHT: u16 = 1;
+const PINNED_BAR_HEIGHT: u16 = 1;
+/// Narrower footers leave out the selected session's file and directory.
+const FOOTER_PATHS_MIN_WIDTH: u16 = 140;
 const PROJECT_PANEL_HEIGHT: u16 = 6;
 const SPLIT_GAP: u16 = 1;
 
@@ -184,6 +436,12 @@ const COLOR_ACCENT: Color = Color::Rgb(198, 150, 115);
 const COLOR_SELECTION_BG: Color = Color::Rgb(214, 160, 120);
 const COLOR_SELECTION_FG: Color = Color::Rgb(20, 20, 20);
 const COLOR_DIVIDER: Color = Color::Rgb(36, 36, 36);
+const COLOR_DIFF_ADD: Color = Color::Rgb(130, 180, 120);
+const COLOR_DIFF_DEL: Color = Color::Rgb(205, 110, 100);
+const COLOR_CODE_KEYWORD: Color = Color::Rgb(170, 150, 200);
+const COLOR_CODE_STRING: Color = Color::Rgb(150, 180, 120);
+const COLOR_CODE_NUMBER: Color = Color::Rgb(210, 170, 110);
+const COLOR_CODE_TYPE: Color = Color::Rgb(130, 170, 200);
 
 #[derive(Clone, Copy, Debug)]
 enum Focus {
@@ -220,6 +478,8 @@ impl Focus {
 enum PreviewMode {
     Matches,
     History,
+    /// Every session of the selected session's project, interleaved by time.
+    Project,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -228,6 +488,8 @@ enum LayoutMode {
     Split,
     List,
     Timeline,
+    /// Records per day as a contribution-style grid.
+    Heatmap,
     Detail,
 }
 
@@ -393,6 +655,7 @@ enum SourceChoice {
     Cursor,
     Pi,
     Copilot,
+    ChatGPT,
 }
 
 impl SourceChoice {
@@ -404,7 +667,8 @@ impl SourceChoice {
             SourceChoice::Opencode => SourceChoice::Cursor,
             SourceChoice::Cursor => SourceChoice::Pi,
             SourceChoice::Pi => SourceChoice::Copilot,
-            SourceChoice::Copilot => SourceChoice::All,
+            SourceChoice::Copilot => SourceChoice::ChatGPT,
+            SourceChoice::ChatGPT => SourceChoice::All,
         }
     }
 
@@ -417,6 +681,20 @@ impl SourceChoice {
             SourceChoice::Cursor => Some(SourceFilter::Cursor),
             SourceChoice::Pi => Some(SourceFilter::Pi),
             SourceChoice::Copilot => Some(SourceFilter::Copilot),
+            SourceChoice::ChatGPT => Some(SourceFilter::ChatGPT),
+        }
+    }
+
+    fn from_filter(filter: Option<SourceFilter>) -> Self {
+        match filter {
+            None => SourceChoice::All,
+            Some(SourceFilter::Claude) => SourceChoice::Claude,
+            Some(SourceFilter::Codex) => SourceChoice::Codex,
+            Some(SourceFilter::Opencode) => SourceChoice::Opencode,
+            Some(SourceFilter::Cursor) => SourceChoice::Cursor,
+            Some(SourceFilter::Pi) => SourceChoice::Pi,
+            Some(SourceFilter::Copilot) => SourceChoice::Copilot,
+            Some(SourceFilter::ChatGPT) => SourceChoice::ChatGPT,
         }
     }
 
@@ -429,6 +707,7 @@ impl SourceChoice {
             SourceChoice::Cursor => "cursor",
             SourceChoice::Pi => "pi",
             SourceChoice::Copilot => "copilot",
+            SourceChoice::ChatGPT => "chatgpt",
         }
     }
 }
@@ -440,10 +719,388 @@ struct SessionSummary {
     source: SourceKind,
     last_ts: u64,
     hit_count: usize,
+    /// Messages in the whole session; 0 when unknown.
+    message_count: usize,
+    /// First to last message; 0 when unknown.
+    duration_ms: u64,
     top_score: f32,
     snippet: String,
+    title: String,
     source_path: String,
     source_dir: String,
+    /// Label from the last `memex topics` run; empty when unclustered.
+    topic: String,
+    /// The user's note on the session; empty when none.
+    note: String,
+    outcome: Option<Outcome>,
+}
+
+/// Single-line text field with a cursor, for the popups that take input.
+#[derive(Clone, Debug, Default)]
+struct LineInput {
+    text: String,
+    /// Cursor position in chars.
+    cursor: usize,
+}
+
+impl LineInput {
+    /// Starts with the cursor at the end of `text`.
+    fn new(text: String) -> Self {
+        let cursor = text.chars().count();
+        Self { text, cursor }
+    }
+
+    fn byte_offset(&self, chars: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(chars)
+            .map(|(idx, _)| idx)
+            .unwrap_or(self.text.len())
+    }
+
+    fn insert(&mut self, ch: char) {
+        let at = self.byte_offset(self.cursor);
+        self.text.insert(at, ch);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+        self.cursor -= 1;
+        let at = self.byte_offset(self.cursor);
+        self.text.remove(at);
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.text.chars().count() {
+            let at = self.byte_offset(self.cursor);
+            self.text.remove(at);
+        }
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let len = self.text.chars().count();
+        self.cursor = self.cursor.saturating_add_signed(delta).min(len);
+    }
+
+    /// Applies an editing key; false for keys it does not handle.
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Left => self.move_cursor(-1),
+            KeyCode::Right => self.move_cursor(1),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.chars().count(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Char(ch) => self.insert(ch),
+            _ => return false,
+        }
+        true
+    }
+
+    /// The text with the char under the cursor highlighted.
+    fn line(&self, theme: &Theme) -> Line<'static> {
+        let before: String = self.text.chars().take(self.cursor).collect();
+        let mut rest = self.text.chars().skip(self.cursor);
+        let at_cursor = rest
+            .next()
+            .map(String::from)
+            .unwrap_or_else(|| " ".to_string());
+        let after: String = rest.collect();
+        Line::from(vec![
+            Span::styled(before, theme.text_bold),
+            Span::styled(at_cursor, theme.selection),
+            Span::styled(after, theme.text_bold),
+        ])
+    }
+}
+
+/// Resume command awaiting confirmation, editable in place.
+#[derive(Clone, Debug)]
+struct ResumePrompt {
+    session: SessionSummary,
+    cwd: String,
+    command: LineInput,
+}
+
+impl ResumePrompt {
+    fn new(session: SessionSummary, cwd: String, command: String) -> Self {
+        Self {
+            session,
+            cwd,
+            command: LineInput::new(command),
+        }
+    }
+}
+
+/// Note being written for a session; saved on enter.
+#[derive(Clone, Debug)]
+struct NotePrompt {
+    session_id: String,
+    text: LineInput,
+}
+
+/// What the command palette can run. Most are also bound to a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PaletteAction {
+    ToggleSort,
+    CycleScoring,
+    CycleSource,
+    FilterProject,
+    ToggleLayout,
+    TogglePreviewMode,
+    ToggleSplitPreview,
+    ToggleAllTerms,
+    ToggleTools,
+    ToggleThreads,
+    ToggleCompactions,
+    ToggleProjectDisplay,
+    ToggleProjectHistory,
+    ToggleLexicalOnly,
+    ToggleTranslate,
+    TogglePinnedQuery,
+    NextPinnedQuery,
+    Resume,
+    Note,
+    CycleOutcome,
+    Replay,
+    Share,
+    Explain,
+    ExportCollected,
+    RefreshIndex,
+    ReloadConfig,
+    EditConfig,
+    ToggleTimings,
+    GoHome,
+}
+
+/// Every palette entry in the order listed with no filter: its action,
+/// label and the key that runs it directly.
+const PALETTE_ENTRIES: &[(PaletteAction, &str, &str)] = &[
+    (PaletteAction::ToggleSort, "Change sort order", "O"),
+    (PaletteAction::CycleScoring, "Cycle scoring profile", "o"),
+    (PaletteAction::CycleSource, "Cycle source filter", "s"),
+    (PaletteAction::FilterProject, "Filter by project", "p"),
+    (PaletteAction::ToggleLayout, "Switch browse view", "v"),
+    (
+        PaletteAction::TogglePreviewMode,
+        "Toggle preview: matches or history",
+        "m",
+    ),
+    (
+        PaletteAction::ToggleSplitPreview,
+        "Toggle two-pane preview",
+        "M",
+    ),
+    (
+        PaletteAction::ToggleAllTerms,
+        "Toggle preview matching: any or all terms",
+        "A",
+    ),
+    (
+        PaletteAction::ToggleTools,
+        "Show or hide tool messages",
+        "t",
+    ),
+    (
+        PaletteAction::ToggleThreads,
+        "Show or hide subagent threads",
+        "a",
+    ),
+    (
+        PaletteAction::ToggleCompactions,
+        "Expand or fold compaction summaries",
+        "z",
+    ),
+    (
+        PaletteAction::ToggleProjectDisplay,
+        "Toggle project names",
+        "g",
+    ),
+    (
+        PaletteAction::ToggleProjectHistory,
+        "Show project history",
+        "H",
+    ),
+    (
+        PaletteAction::ToggleLexicalOnly,
+        "Toggle lexical-only mode",
+        "L",
+    ),
+    (PaletteAction::ToggleTranslate, "Toggle translations", "T"),
+    (
+        PaletteAction::TogglePinnedQuery,
+        "Pin or unpin the query",
+        "B",
+    ),
+    (
+        PaletteAction::NextPinnedQuery,
+        "Search the next pinned query",
+        "b",
+    ),
+    (PaletteAction::Resume, "Resume session", "r"),
+    (PaletteAction::Note, "Write a note on the session", "n"),
+    (
+        PaletteAction::CycleOutcome,
+        "Label the session's outcome",
+        "u",
+    ),
+    (PaletteAction::Replay, "Replay session", "P"),
+    (PaletteAction::Share, "Share session", "S"),
+    (PaletteAction::Explain, "Explain the session's rank", "e"),
+    (
+        PaletteAction::ExportCollected,
+        "Export collected messages",
+        "C",
+    ),
+    (PaletteAction::RefreshIndex, "Refresh the index", "i"),
+    (PaletteAction::ReloadConfig, "Reload config.toml", "R"),
+    (PaletteAction::EditConfig, "Edit config.toml", ""),
+    (PaletteAction::ToggleTimings, "Show search timings", "~"),
+    (PaletteAction::GoHome, "Go home", ""),
+];
+
+/// The Ctrl-P command palette: a filter over [`PALETTE_ENTRIES`] and the
+/// highlighted match.
+#[derive(Clone, Debug)]
+struct Palette {
+    filter: LineInput,
+    selected: usize,
+}
+
+impl Palette {
+    /// Entries the filter matches, best first.
+    fn matches(&self) -> Vec<(PaletteAction, &'static str, &'static str)> {
+        let filter = self.filter.text.trim().to_lowercase();
+        let mut scored: Vec<(i32, (PaletteAction, &'static str, &'static str))> = PALETTE_ENTRIES
+            .iter()
+            .filter_map(|entry| Some((fuzzy_score(entry.1, &filter)?, *entry)))
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+/// How well `label` matches `filter` when its characters appear in order,
+/// `None` when they don't. Runs of consecutive characters and characters
+/// starting a word score higher.
+fn fuzzy_score(label: &str, filter: &str) -> Option<i32> {
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut at = 0;
+    let mut last: Option<usize> = None;
+    for ch in filter.chars().filter(|ch| !ch.is_whitespace()) {
+        let found = at + label[at..].iter().position(|&c| c == ch)?;
+        score += 1;
+        if last.is_some_and(|last| last + 1 == found) {
+            score += 4;
+        }
+        if found == 0 || !label[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        last = Some(found);
+        at = found + 1;
+    }
+    Some(score)
+}
+
+/// Bounds on the autoplay step, adjusted with `+` and `-`.
+const REPLAY_MIN_INTERVAL: Duration = Duration::from_millis(100);
+const REPLAY_MAX_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A session played back one message at a time, for retros on long runs.
+/// Autoplay reveals the next message every `interval`.
+#[derive(Clone, Debug)]
+struct Replay {
+    session_id: String,
+    project: String,
+    /// The session in the order it happened.
+    records: Vec<Record>,
+    /// Messages revealed so far, at least one.
+    shown: usize,
+    autoplay: bool,
+    interval: Duration,
+    last_step: Instant,
+    /// Lines scrolled up from the newest message; 0 follows it.
+    scroll_back: usize,
+}
+
+impl Replay {
+    fn new(session: &SessionSummary, records: Vec<Record>, interval: Duration) -> Self {
+        Self {
+            session_id: session.session_id.clone(),
+            project: session.project.clone(),
+            records,
+            shown: 1,
+            autoplay: false,
+            interval: interval.clamp(REPLAY_MIN_INTERVAL, REPLAY_MAX_INTERVAL),
+            last_step: Instant::now(),
+            scroll_back: 0,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.shown >= self.records.len()
+    }
+
+    /// Moves `delta` messages forward or back; false when nothing changed.
+    fn step(&mut self, delta: isize) -> bool {
+        let next = (self.shown as isize + delta).clamp(1, self.records.len().max(1) as isize);
+        self.last_step = Instant::now();
+        self.scroll_back = 0;
+        let changed = next as usize != self.shown;
+        self.shown = next as usize;
+        changed
+    }
+
+    /// Advances autoplay when the next message is due, stopping at the end.
+    fn tick(&mut self, now: Instant) -> bool {
+        if !self.autoplay || now.duration_since(self.last_step) < self.interval {
+            return false;
+        }
+        if self.at_end() {
+            self.autoplay = false;
+            return true;
+        }
+        self.step(1)
+    }
+
+    fn toggle_autoplay(&mut self) {
+        self.autoplay = !self.autoplay;
+        self.last_step = Instant::now();
+    }
+
+    fn change_speed(&mut self, faster: bool) {
+        let interval = if faster {
+            self.interval / 2
+        } else {
+            self.interval * 2
+        };
+        self.interval = interval.clamp(REPLAY_MIN_INTERVAL, REPLAY_MAX_INTERVAL);
+    }
+
+    fn lines(&self, max_chars: usize) -> Vec<PreviewLine> {
+        let mut lines = Vec::new();
+        append_records(&mut lines, &self.records[..self.shown], max_chars);
+        lines
+    }
+
+    /// "12/340 · +42m": the position, and how far into the session the
+    /// newest revealed message was written.
+    fn position(&self) -> String {
+        let mut label = format!("{}/{}", self.shown, self.records.len());
+        let first = self.records.first().map_or(0, |record| record.ts);
+        let current = self
+            .records
+            .get(self.shown - 1)
+            .map_or(0, |record| record.ts);
+        if first > 0 && current >= first {
+            label.push_str(&format!(" · +{}", format_duration_compact(current - first)));
+        }
+        label
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -461,11 +1118,90 @@ struct AppChannels {
     search_tx: std::sync::mpsc::Sender<SearchUpdate>,
     search_rx: std::sync::mpsc::Receiver<SearchUpdate>,
     search_request_tx: std::sync::mpsc::Sender<SearchRequest>,
+    latest_search: LatestSearch,
     detail_tx: std::sync::mpsc::Sender<DetailRequest>,
 }
 
+/// Id of the newest search the UI wants. The search worker checks it
+/// between stages and drops a search once a newer one has been asked for,
+/// including one still waiting out the typing debounce.
+#[derive(Debug, Clone, Default)]
+struct LatestSearch(Arc<AtomicU64>);
+
+impl LatestSearch {
+    fn set(&self, request_id: u64) {
+        self.0.store(request_id, Ordering::Relaxed);
+    }
+
+    fn is_superseded(&self, request_id: u64) -> bool {
+        self.0.load(Ordering::Relaxed) != request_id
+    }
+}
+
+struct BackgroundTask {
+    run: Box<dyn FnOnce() + Send>,
+    /// The update sent in place of `run`'s when it panics, given the panic
+    /// message, so the request it served doesn't stay loading.
+    failed: Box<dyn FnOnce(String) -> SearchUpdate + Send>,
+}
+
+/// A fixed set of threads sharing one queue, so loads run without a thread
+/// being spawned per request. Each task sends its own `SearchUpdate`.
+///
+/// The loads are blocking calls into tantivy, SQLite and the filesystem with
+/// nothing to await, so an async runtime would only move them onto its
+/// blocking pool; plain threads do the same without pulling tokio into a
+/// crate that is otherwise synchronous. Superseded searches are dropped
+/// through [`LatestSearch`] rather than by cancelling tasks.
+struct BackgroundTasks {
+    tx: std::sync::mpsc::Sender<BackgroundTask>,
+}
+
+impl BackgroundTasks {
+    fn new(workers: usize, updates: std::sync::mpsc::Sender<SearchUpdate>) -> Self {
+        let (tx, rx) = std::sync::mpsc::channel::<BackgroundTask>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..workers {
+            let rx = Arc::clone(&rx);
+            let updates = updates.clone();
+            std::thread::spawn(move || {
+                loop {
+                    let task = match rx.lock() {
+                        Ok(rx) => rx.recv(),
+                        Err(_) => return,
+                    };
+                    let Ok(task) = task else {
+                        return;
+                    };
+                    // The worker itself stays available for the next task.
+                    if let Err(payload) =
+                        std::panic::catch_unwind(std::panic::AssertUnwindSafe(task.run))
+                    {
+                        let message = panic_payload_message(payload.as_ref());
+                        let _ = updates.send((task.failed)(message));
+                    }
+                }
+            });
+        }
+        Self { tx }
+    }
+
+    fn spawn(
+        &self,
+        run: impl FnOnce() + Send + 'static,
+        failed: impl FnOnce(String) -> SearchUpdate + Send + 'static,
+    ) {
+        let _ = self.tx.send(BackgroundTask {
+            run: Box::new(run),
+            failed: Box::new(failed),
+        });
+    }
+}
+
 struct App {
     paths: Paths,
+    /// Renamed projects, shown by their alias and resolved back for filters.
+    project_aliases: ProjectAliases,
     config: UserConfig,
     index: SearchIndex,
     focus: Focus,
@@ -481,8 +1217,14 @@ struct App {
     results: Vec<SessionSummary>,
     sessions_state: LoadState,
     sessions_since: Option<u64>,
+    /// Name of the scoring profile searches run with, and its settings.
+    scoring_profile: String,
+    profile: ScoringProfile,
+    tuning: QueryTuning,
+    sort: SessionSort,
+    session_columns: SessionListColumns,
     active_search_request: u64,
-    pending_home_search: Option<Instant>,
+    pending_search: Option<Instant>,
     selected: ListState,
     layout_mode: LayoutMode,
     detail_return_mode: LayoutMode,
@@ -496,6 +1238,9 @@ struct App {
     timeline_displayed: Option<(SourceChoice, TimelineRange, ProjectDisplayMode, String)>,
     timeline_state: LoadState,
     active_timeline_request: u64,
+    heatmap_counts: HashMap<chrono::NaiveDate, u64>,
+    heatmap_state: LoadState,
+    active_heatmap_request: u64,
     home_activity: Vec<HomeChartPoint>,
     home_result_activity: Vec<HomeChartPoint>,
     home_activity_range: TimelineRange,
@@ -520,10 +1265,55 @@ struct App {
     quick_popup: bool,
     quick_scroll: usize,
     quick_lines: Vec<PreviewLine>,
+    resume_prompt: Option<ResumePrompt>,
+    note_prompt: Option<NotePrompt>,
+    palette: Option<Palette>,
+    replay: Option<Replay>,
     preview_mode: PreviewMode,
+    /// Show matches above the full history instead of one or the other.
+    split_preview: bool,
+    /// Match only messages holding every query term in the preview.
+    all_terms: bool,
+    /// Messages of the history matching the query, listed in the upper pane
+    /// of the split preview.
+    preview_matches: Vec<PreviewMatch>,
+    preview_match_state: ListState,
     show_tools: bool,
+    show_threads: bool,
+    /// Keep embeddings out of the session: index refreshes skip them, so the
+    /// model never loads, and previews skip related sessions.
+    lexical_only: bool,
+    /// Show a translation under preview messages in another language.
+    translate: bool,
+    /// Show the `~` overlay with search and ingest timings.
+    debug_overlay: bool,
+    last_search_timings: Option<SearchTimings>,
+    last_ingest: Option<IngestTimings>,
+    /// Latest embedding progress of the running refresh, shown in the
+    /// footer.
+    embed_status: Option<EmbedStatus>,
+    /// Segment counts read when the overlay opened, not on every frame.
+    debug_segments: Option<SegmentStats>,
+    /// Show compaction summaries in full instead of one folded line.
+    expand_compactions: bool,
     find_query: String,
     detail_lines: Vec<PreviewLine>,
+    detail_related: Vec<SessionSummary>,
+    /// Messages marked with `c`, exported together with `C`.
+    basket: Vec<Record>,
+    /// Problems found by the startup health check, shown one at a time in
+    /// a banner until dismissed with ctrl-x.
+    health: Vec<HealthIssue>,
+    /// Queries pinned to the bar at the top, with how many records each
+    /// matches under the current source and project.
+    pinned: PinnedQueries,
+    pinned_counts: Vec<Option<usize>>,
+    /// Source, project and time filter the counts were taken for; cleared to
+    /// count again.
+    pinned_counts_for: Option<(SourceChoice, String, Option<u64>)>,
+    active_pinned_request: u64,
+    /// Screen cells of each pinned query, for clicks.
+    pinned_areas: Vec<Rect>,
     detail_state: LoadState,
     active_detail_request: u64,
     detail_scroll: usize,
@@ -531,10 +1321,16 @@ struct App {
     last_detail_query: Option<String>,
     last_detail_mode: PreviewMode,
     last_detail_find: Option<String>,
+    /// The request behind the preview, sent again for earlier history.
+    detail_request: Option<DetailRequest>,
+    loading_earlier: bool,
     status: String,
     last_status_at: Option<Instant>,
     update_message: Option<String>,
     index_state: IndexState,
+    /// Last background index service run failed; see `memex status`.
+    service_failed: bool,
+    service_checked_at: Option<Instant>,
     next_request_id: u64,
     spinner_frame: usize,
     last_spinner_at: Instant,
@@ -543,9 +1339,15 @@ struct App {
     search_rx: std::sync::mpsc::Receiver<SearchUpdate>,
     search_tx: std::sync::mpsc::Sender<SearchUpdate>,
     search_request_tx: std::sync::mpsc::Sender<SearchRequest>,
+    latest_search: LatestSearch,
     detail_tx: std::sync::mpsc::Sender<DetailRequest>,
+    tasks: BackgroundTasks,
     update_rx: Option<std::sync::mpsc::Receiver<String>>,
+    /// Reapplied whenever config.toml is reloaded.
+    overrides: TuiOverrides,
     querybar_area: Rect,
+    /// Footer labels to re-print as hyperlinks once the frame is drawn.
+    footer_links: Vec<ScreenLink>,
     body_area: Rect,
     list_area: Rect,
     preview_area: Rect,
@@ -562,15 +1364,114 @@ enum PreviewLine {
         source: String,
         session_id: String,
     },
+    /// Model, sandbox and approval policy the session last ran under.
+    Settings(AgentSettings),
+    /// The user's note on the session, shown above the transcript.
+    Note(String),
+    Outcome(Outcome),
+    /// An item of the session's current todo list.
+    Todo(TodoItem),
+    /// A line of the translation of the message above.
+    Translation(String),
+    /// Images and files attached to the message above.
+    Attachments(Vec<Attachment>),
     Meta {
+        doc_id: u64,
         role: String,
         ts: String,
         highlight: bool,
     },
     Text(String),
+    /// A line of a patch the record applies, colored by its marker.
+    Diff(String),
+    /// A line inside a fenced code block, split into highlighted tokens.
+    Code(Vec<(TokenKind, String)>),
+    /// A compaction summary folded to one line; the lines it stands for are
+    /// shown in its place once summaries are expanded.
+    Compaction(Vec<String>),
+    /// A run of tool messages folded to one line while tools are hidden.
+    ToolGroup(ToolGroup),
+    /// Stands for the first records of a history until scrolling up loads
+    /// them.
+    EarlierHistory(usize),
+    ThreadHeader {
+        kind: String,
+        thread: String,
+    },
+    /// A line belonging to a subagent or sidechain thread, drawn indented
+    /// under the turn that spawned it.
+    Nested(Box<PreviewLine>),
+    /// A similar session listed under the transcript, opened with its key.
+    Related {
+        key: usize,
+        label: String,
+        project: String,
+        ts: String,
+    },
     Empty,
 }
 
+/// A message of the split preview's history that matches the query.
+#[derive(Clone, Debug, PartialEq)]
+struct PreviewMatch {
+    /// Index of the message's `Meta` line in the history.
+    line: usize,
+    role: String,
+    ts: String,
+    text: String,
+}
+
+#[derive(Clone, Debug)]
+struct ToolGroup {
+    /// Tool calls in the run, or its messages when it has only results.
+    calls: usize,
+    /// The run's lines while folded; empty once expanded below the group.
+    lines: Vec<PreviewLine>,
+    /// How many lines under the group are its own while expanded.
+    expanded: usize,
+}
+
+/// Tool records met while tools are hidden, folded into one
+/// [`PreviewLine::ToolGroup`] once a conversational message ends the run.
+struct ToolRun {
+    uses: usize,
+    records: usize,
+    lines: Vec<PreviewLine>,
+    max_chars: usize,
+}
+
+impl ToolRun {
+    fn new(max_chars: usize) -> Self {
+        Self {
+            uses: 0,
+            records: 0,
+            lines: Vec::new(),
+            max_chars,
+        }
+    }
+
+    fn push(&mut self, record: &Record) {
+        if record.role == "tool_use" {
+            self.uses += 1;
+        }
+        self.records += 1;
+        append_record(&mut self.lines, record, false, self.max_chars);
+    }
+
+    fn flush(&mut self, lines: &mut Vec<PreviewLine>) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let run = std::mem::replace(self, Self::new(self.max_chars));
+        lines.push(PreviewLine::ToolGroup(ToolGroup {
+            calls: if run.uses > 0 { run.uses } else { run.records },
+            lines: run.lines,
+            expanded: 0,
+        }));
+        lines.push(PreviewLine::Empty);
+    }
+}
+
 struct Theme {
     base: Style,
     panel: Style,
@@ -705,26 +1606,55 @@ impl StdIoRedirect {
 pub fn run(
     root: Option<PathBuf>,
     update_rx: Option<std::sync::mpsc::Receiver<String>>,
+    overrides: TuiOverrides,
 ) -> Result<()> {
     let paths = Paths::new(root)?;
-    let config = UserConfig::load(&paths)?;
-    let index = if config.auto_index_on_search_default() {
+    let mut config = UserConfig::load(&paths)?;
+    overrides.apply(&mut config);
+    // Surface a bad column list at startup rather than silently ignoring it.
+    config.session_list_columns()?;
+    let repo = if config.tui_project_from_cwd.unwrap_or(false) || config.tui_projects.is_some() {
+        std::env::current_dir()
+            .ok()
+            .and_then(|cwd| repository_project_for_cwd(&cwd.to_string_lossy()))
+    } else {
+        None
+    };
+    let startup = config.tui_startup(repo.as_deref())?;
+    // Opening for ingest may rebuild a stale index, which only the lock
+    // holder may do; otherwise open the last committed snapshot read-only.
+    let startup_lock = if config.auto_index_on_search_default() {
         paths.ensure_dirs()?;
+        WriteLock::try_acquire(&paths.state)?
+    } else {
+        None
+    };
+    let index = if startup_lock.is_some() {
         SearchIndex::open_or_create_for_ingest(&paths.index)?
     } else {
         SearchIndex::open_or_create(&paths.index)?
     };
+    drop(startup_lock);
     let (index_tx, index_rx) = std::sync::mpsc::channel();
     let (search_tx, search_rx) = std::sync::mpsc::channel();
     let (search_request_tx, search_request_rx) = std::sync::mpsc::channel();
     let (detail_tx, detail_rx) = std::sync::mpsc::channel();
+    let latest_search = LatestSearch::default();
+    let project_aliases = paths.project_aliases()?;
     spawn_search_worker(
         paths.clone(),
+        project_aliases.clone(),
         index.clone(),
+        latest_search.clone(),
         search_request_rx,
         search_tx.clone(),
     );
-    spawn_detail_worker(index.clone(), detail_rx, search_tx.clone());
+    spawn_detail_worker(
+        index.clone(),
+        paths.vectors.clone(),
+        detail_rx,
+        search_tx.clone(),
+    );
 
     let mut app = App::new(
         paths,
@@ -736,28 +1666,109 @@ pub fn run(
             search_tx,
             search_rx,
             search_request_tx,
+            latest_search,
             detail_tx,
         },
     );
+    app.project_aliases = project_aliases;
     app.stdio_redirect = Some(StdIoRedirect::new()?);
     app.update_rx = update_rx;
+    app.lexical_only = overrides.lexical_only;
+    app.overrides = overrides;
+    app.project = startup.project.unwrap_or_default();
+    app.source = SourceChoice::from_filter(startup.source);
+    app.sort = startup.sort;
     app.kickoff_index_refresh(false);
     app.kickoff_search();
     app.kickoff_home_activity();
     app.kickoff_home_filters();
+    app.kickoff_health_check();
 
     let mut terminal = enter_terminal()?;
     app.suppress_stdio()?;
-    let res = run_loop(&mut terminal, &mut app);
+    let res = run_loop_restoring_on_panic(&mut terminal, &mut app);
     app.restore_stdio()?;
     exit_terminal(&mut terminal)?;
     res
 }
 
+/// A session's history as the TUI preview shows it, one string per line
+/// with ANSI colors when `color` is set, for `memex show --pager`. Threads
+/// are nested and compacted context is written out; a hidden tool run stays
+/// a one-line summary unless `show_tools`.
+pub fn session_transcript(
+    index: &SearchIndex,
+    config: &UserConfig,
+    session_id: &str,
+    show_tools: bool,
+    color: bool,
+) -> Result<Vec<String>> {
+    let mut sessions = HashMap::new();
+    for record in index.records_by_session_id(session_id)? {
+        add_record_to_session(&mut sessions, 0.0, record, &[]);
+    }
+    let mut session = sessions
+        .remove(session_id)
+        .ok_or_else(|| anyhow::anyhow!("session not found: {session_id}"))?;
+    // Without a query there is no top hit to lead with.
+    session.snippet.clear();
+    let lines = build_detail_lines(
+        index,
+        &session,
+        PreviewMode::History,
+        "",
+        show_tools,
+        true,
+        PreviewLimits::from_config(config),
+    )?;
+    let mut lines = unfold_compactions(lines, true);
+    if config.highlight_code() {
+        lines = highlight_code_blocks(lines);
+    }
+    let theme = Theme::new();
+    Ok(lines
+        .iter()
+        .map(|line| match line {
+            PreviewLine::ToolGroup(group) => {
+                let noun = if group.calls == 1 {
+                    "tool call"
+                } else {
+                    "tool calls"
+                };
+                ansi_line(
+                    &Line::from(Span::styled(
+                        format!("⚙ {} {noun}", group.calls),
+                        theme.accent,
+                    )),
+                    color,
+                )
+            }
+            line => ansi_line(&render_preview_line(line, &theme), color),
+        })
+        .collect())
+}
+
 impl App {
     fn new(paths: Paths, config: UserConfig, index: SearchIndex, channels: AppChannels) -> Self {
+        let (scoring_profile, profile) = match config.resolve_scoring_profile(None) {
+            Ok(profile) => (
+                config
+                    .scoring_profile
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+                profile,
+            ),
+            Err(_) => (DEFAULT_PROFILE.to_string(), ScoringProfile::default()),
+        };
+        let tuning = config.query_tuning().unwrap_or_default();
+        let session_columns = config.session_list_columns().unwrap_or_default();
+        let split_preview = config.preview_split();
+        let all_terms = config.preview_all_terms();
+        let pinned = PinnedQueries::load(&pinned_queries_path(&paths.state)).unwrap_or_default();
+        let tasks = BackgroundTasks::new(BACKGROUND_WORKERS, channels.search_tx.clone());
         Self {
             paths,
+            project_aliases: ProjectAliases::default(),
             config,
             index,
             focus: Focus::Query,
@@ -794,8 +1805,13 @@ impl App {
             results: Vec::new(),
             sessions_state: LoadState::Idle,
             sessions_since: None,
+            scoring_profile,
+            profile,
+            tuning,
+            sort: SessionSort::default(),
+            session_columns,
             active_search_request: 0,
-            pending_home_search: None,
+            pending_search: None,
             selected: ListState::default(),
             layout_mode: LayoutMode::Home,
             detail_return_mode: LayoutMode::List,
@@ -809,13 +1825,41 @@ impl App {
             timeline_displayed: None,
             timeline_state: LoadState::Idle,
             active_timeline_request: 0,
+            heatmap_counts: HashMap::new(),
+            heatmap_state: LoadState::Idle,
+            active_heatmap_request: 0,
             quick_popup: false,
             quick_scroll: 0,
             quick_lines: Vec::new(),
+            resume_prompt: None,
+            note_prompt: None,
+            palette: None,
+            replay: None,
             preview_mode: PreviewMode::Matches,
+            split_preview,
+            all_terms,
+            preview_matches: Vec::new(),
+            preview_match_state: ListState::default(),
             show_tools: false,
+            show_threads: false,
+            lexical_only: false,
+            translate: false,
+            debug_overlay: false,
+            last_search_timings: None,
+            last_ingest: None,
+            embed_status: None,
+            debug_segments: None,
+            expand_compactions: false,
             find_query: String::new(),
             detail_lines: Vec::new(),
+            detail_related: Vec::new(),
+            basket: Vec::new(),
+            health: Vec::new(),
+            pinned,
+            pinned_counts: Vec::new(),
+            pinned_counts_for: None,
+            active_pinned_request: 0,
+            pinned_areas: Vec::new(),
             detail_state: LoadState::Idle,
             active_detail_request: 0,
             detail_scroll: 0,
@@ -823,10 +1867,14 @@ impl App {
             last_detail_query: None,
             last_detail_mode: PreviewMode::Matches,
             last_detail_find: None,
+            detail_request: None,
+            loading_earlier: false,
             status: String::new(),
             last_status_at: None,
             update_message: None,
             index_state: IndexState::Idle,
+            service_failed: false,
+            service_checked_at: None,
             next_request_id: 0,
             spinner_frame: 0,
             last_spinner_at: Instant::now(),
@@ -835,9 +1883,13 @@ impl App {
             search_tx: channels.search_tx,
             search_rx: channels.search_rx,
             search_request_tx: channels.search_request_tx,
+            latest_search: channels.latest_search,
             detail_tx: channels.detail_tx,
+            tasks,
             update_rx: None,
+            overrides: TuiOverrides::default(),
             querybar_area: Rect::default(),
+            footer_links: Vec::new(),
             body_area: Rect::default(),
             list_area: Rect::default(),
             preview_area: Rect::default(),
@@ -885,6 +1937,7 @@ impl App {
             || self.sessions_state == LoadState::Loading
             || self.project_state == LoadState::Loading
             || self.timeline_state == LoadState::Loading
+            || self.heatmap_state == LoadState::Loading
             || self.detail_state == LoadState::Loading
             || self.home_activity_state == LoadState::Loading
             || (self.home_chart_mode == HomeChartMode::Tokens
@@ -905,14 +1958,30 @@ impl App {
         self.last_spinner_at = Instant::now();
         let paths = self.paths.clone();
         let config = self.config.clone();
+        let lexical_only = self.lexical_only;
         let tx = self.index_tx.clone();
+        // Ingest holds the write lock for its whole run, so it gets a thread
+        // of its own instead of tying up a pool worker.
         std::thread::spawn(move || {
             let _ = tx.send(IndexUpdate::Started);
-            let result = (|| -> Result<Option<crate::ingest::IngestReport>> {
+            let started = Instant::now();
+            let lock = match WriteLock::try_acquire(&paths.state) {
+                Ok(Some(lock)) => lock,
+                Ok(None) => {
+                    let _ = tx.send(IndexUpdate::ReadOnly(busy_message(&paths.state)));
+                    return;
+                }
+                Err(err) => {
+                    let _ = tx.send(IndexUpdate::Error(err.to_string()));
+                    return;
+                }
+            };
+            let result = catch_panic(|| -> Result<Option<crate::ingest::IngestReport>> {
                 let index = SearchIndex::open_or_create_for_ingest(&paths.index)?;
-                let embeddings_default = config.embeddings_default();
+                let embeddings = config.embeddings_default() && !lexical_only;
                 let model_choice = config.resolve_model(None)?;
                 let tool_content_limits = config.indexed_tool_content_limits()?;
+                let embed_tx = tx.clone();
                 let opts = IngestOptions {
                     claude_source: default_claude_source(),
                     include_agents: false,
@@ -921,19 +1990,29 @@ impl App {
                     include_cursor: true,
                     include_pi: true,
                     include_copilot: true,
-                    embeddings: embeddings_default,
+                    embeddings,
                     backfill_embeddings: false,
                     model: model_choice,
                     embed_runtime: config.resolve_embed_runtime()?,
                     tool_content_limits,
+                    max_threads: config.max_cpu_threads()?,
+                    ocr_images: config.ocr_images(),
+                    embed_listener: Some(EmbedListener::new(move |status| {
+                        let _ = embed_tx.send(IndexUpdate::Embedding(status));
+                    })),
+                    archive_tier: config.archive_tier()?,
                 };
                 ingest_if_stale(&paths, &index, &opts, config.scan_cache_ttl())
-            })();
+            });
+            drop(lock);
             match result {
                 Ok(Some(report)) => {
                     let _ = tx.send(IndexUpdate::Done {
                         added: report.records_added,
                         embedded: report.records_embedded,
+                        malformed: report.malformed_lines,
+                        files_scanned: report.files_scanned,
+                        elapsed: started.elapsed(),
                     });
                 }
                 Ok(None) => {
@@ -982,35 +2061,80 @@ impl App {
         if !session_changed && !query_changed && !mode_changed && !find_changed {
             return;
         }
-        let active_query = if self.find_query.trim().is_empty() {
-            query_now.clone()
-        } else {
-            self.find_query.trim().to_string()
-        };
+        let active_query = self.detail_query();
         let request_id = self.next_request_id();
         self.active_detail_request = request_id;
         self.detail_state = LoadState::Loading;
         self.detail_lines.clear();
+        self.detail_related.clear();
         self.detail_scroll = 0;
         self.last_detail_session = Some(session.session_id.clone());
         self.last_detail_query = Some(query_now);
         self.last_detail_mode = self.preview_mode;
         self.last_detail_find = Some(find_now);
+        // The split preview lists matches from the whole history, so only
+        // the plain history view starts at the end and loads upwards.
+        let split = self.split_preview_active();
         let request = DetailRequest {
             request_id,
             session,
-            mode: self.preview_mode,
+            mode: if split {
+                PreviewMode::History
+            } else {
+                self.preview_mode
+            },
             query: active_query,
             show_tools: self.show_tools,
+            show_threads: self.show_threads,
+            limits: self.preview_limits(),
+            related: !self.lexical_only,
+            translate: self.translate.then(|| self.config.translator()).flatten(),
+            lazy_history: !split && self.preview_mode == PreviewMode::History,
+            earlier: None,
         };
+        self.loading_earlier = false;
+        self.detail_request = Some(request.clone());
         if self.detail_tx.send(request).is_err() {
             self.detail_state = LoadState::Error("preview worker stopped".to_string());
         }
     }
 
+    /// Asks for the history before the preview's first record once the
+    /// user scrolls within a screen of it.
+    fn load_earlier_history(&mut self) {
+        if self.loading_earlier {
+            return;
+        }
+        let Some((at, before)) =
+            self.detail_lines
+                .iter()
+                .enumerate()
+                .find_map(|(at, line)| match line {
+                    PreviewLine::EarlierHistory(before) => Some((at, *before)),
+                    _ => None,
+                })
+        else {
+            return;
+        };
+        if self.detail_scroll > at + self.preview_area.height as usize {
+            return;
+        }
+        let Some(mut request) = self
+            .detail_request
+            .clone()
+            .filter(|request| request.request_id == self.active_detail_request)
+        else {
+            return;
+        };
+        request.earlier = Some(before);
+        self.loading_earlier = self.detail_tx.send(request).is_ok();
+    }
+
     fn clear_detail(&mut self, message: &str) {
         self.active_detail_request = self.next_request_id();
         self.detail_lines = vec![PreviewLine::Text(message.to_string())];
+        self.detail_related.clear();
+        self.preview_matches.clear();
         self.detail_state = LoadState::Empty;
         self.detail_scroll = 0;
         self.last_detail_session = None;
@@ -1019,15 +2143,16 @@ impl App {
     }
 
     fn kickoff_search(&mut self) {
-        let was_pending = self.pending_home_search.take().is_some();
+        self.pending_search = None;
         let refresh_home_tokens =
             self.layout_mode == LayoutMode::Home && self.home_chart_mode == HomeChartMode::Tokens;
-        if refresh_home_tokens && !was_pending {
+        if refresh_home_tokens {
             self.invalidate_home_token_activity();
             self.home_token_activity_state = LoadState::Loading;
         }
         let request_id = self.next_request_id();
         self.active_search_request = request_id;
+        self.latest_search.set(request_id);
         self.sessions_state = LoadState::Loading;
         self.last_spinner_at = Instant::now();
         let query = self.query.trim().to_string();
@@ -1040,6 +2165,9 @@ impl App {
             source: self.source,
             since: self.sessions_since,
             grouping: self.project_display.grouping(),
+            profile: self.profile,
+            tuning: self.tuning.clone(),
+            limit: self.config.tui_result_limit(),
         };
         if self.search_request_tx.send(request).is_err() {
             let message = "search worker stopped".to_string();
@@ -1050,33 +2178,134 @@ impl App {
         } else if refresh_home_tokens && query_is_empty {
             self.kickoff_home_token_activity();
         }
+        self.kickoff_pinned_counts();
     }
 
-    fn schedule_home_search(&mut self) {
-        if self.home_chart_mode == HomeChartMode::Tokens {
-            self.invalidate_home_token_activity();
-            self.home_token_activity_state = LoadState::Loading;
+    /// Counts the records each pinned query matches under the current
+    /// source, project and time filter, unless they were counted for those
+    /// already.
+    fn kickoff_pinned_counts(&mut self) {
+        let filters = (
+            self.source,
+            self.project.trim().to_string(),
+            self.sessions_since,
+        );
+        if self.pinned.queries.is_empty() || self.pinned_counts_for.as_ref() == Some(&filters) {
+            return;
         }
-        self.active_search_request = self.next_request_id();
-        self.sessions_state = LoadState::Loading;
-        self.pending_home_search = Some(Instant::now() + HOME_SEARCH_DEBOUNCE);
-        self.last_spinner_at = Instant::now();
-        self.set_status("searching...");
-    }
-
-    fn flush_home_search_if_due(&mut self) -> bool {
-        if self
-            .pending_home_search
-            .is_some_and(|at| Instant::now() >= at)
-        {
-            self.kickoff_search();
-            return true;
+        let request_id = self.next_request_id();
+        self.active_pinned_request = request_id;
+        self.pinned_counts = vec![None; self.pinned.queries.len()];
+        let resolved = self.project_aliases.resolve(&filters.1);
+        self.pinned_counts_for = Some(filters);
+        let index = self.index.clone();
+        let queries = self.pinned.queries.clone();
+        let source = self.source.as_filter();
+        let since = self.sessions_since;
+        let profile = self.profile;
+        let tuning = self.tuning.clone();
+        let tx = self.search_tx.clone();
+        let pinned = queries.len();
+        let run = move || {
+            let project = (!resolved.is_empty()).then_some(resolved.as_str());
+            let counts = queries
+                .iter()
+                .map(|query| {
+                    let (query, _) = take_topic_filter(query);
+                    let settings = SearchSettings {
+                        profile: &profile,
+                        tuning: &tuning,
+                        limit: 1,
+                    };
+                    let options = search_options(&query, source, project, since, settings);
+                    index.count(&options).ok()
+                })
+                .collect();
+            let _ = tx.send(SearchUpdate::PinnedCounts { request_id, counts });
+        };
+        self.tasks.spawn(run, move |_| SearchUpdate::PinnedCounts {
+            request_id,
+            counts: vec![None; pinned],
+        });
+    }
+
+    /// Pins the current query to the bar, or unpins it.
+    fn toggle_pinned_query(&mut self) {
+        let query = self.query.trim().to_string();
+        let result = self.pinned.toggle(&query).and_then(|pinned| {
+            self.pinned
+                .save(&pinned_queries_path(&self.paths.state))
+                .map(|()| pinned)
+        });
+        match result {
+            Ok(true) => self.set_status(format!("pinned \"{query}\"")),
+            Ok(false) => self.set_status(format!("unpinned \"{query}\"")),
+            Err(err) => self.set_status(err.to_string()),
+        }
+        self.pinned_counts_for = None;
+        self.kickoff_pinned_counts();
+    }
+
+    /// Searches for the pinned query after the one showing, or the first.
+    fn next_pinned_query(&mut self) {
+        if self.pinned.queries.is_empty() {
+            self.set_status("no pinned queries (B pins the current query)");
+            return;
+        }
+        let next = self
+            .pinned
+            .queries
+            .iter()
+            .position(|pinned| pinned == self.query.trim())
+            .map_or(0, |at| (at + 1) % self.pinned.queries.len());
+        self.activate_pinned_query(next);
+    }
+
+    fn activate_pinned_query(&mut self, idx: usize) {
+        let Some(query) = self.pinned.queries.get(idx).cloned() else {
+            return;
+        };
+        self.query = query;
+        match self.layout_mode {
+            LayoutMode::Timeline => self.kickoff_timeline_load(),
+            LayoutMode::Heatmap => self.kickoff_heatmap_load(),
+            _ => self.refresh_results(),
+        }
+    }
+
+    /// Searches once typing pauses for `search_debounce_ms`. The search in
+    /// flight is superseded right away, so the worker can drop it.
+    fn schedule_search(&mut self) {
+        if self.layout_mode == LayoutMode::Home && self.home_chart_mode == HomeChartMode::Tokens {
+            self.invalidate_home_token_activity();
+            self.home_token_activity_state = LoadState::Loading;
+        }
+        self.active_search_request = self.next_request_id();
+        self.latest_search.set(self.active_search_request);
+        self.sessions_state = LoadState::Loading;
+        self.pending_search = Some(Instant::now() + self.config.search_debounce());
+        self.last_spinner_at = Instant::now();
+        self.set_status("searching...");
+    }
+
+    /// Result lists follow the query as it is typed; the timeline, heatmap
+    /// and transcript views still wait for Enter.
+    fn search_as_you_type(&mut self) {
+        if matches!(self.layout_mode, LayoutMode::List | LayoutMode::Split) {
+            self.schedule_search();
+        }
+    }
+
+    fn flush_search_if_due(&mut self) -> bool {
+        if self.pending_search.is_some_and(|at| Instant::now() >= at) {
+            self.kickoff_search();
+            return true;
         }
         false
     }
 
-    fn flush_home_search(&mut self) {
-        if self.pending_home_search.is_some() {
+    fn flush_search(&mut self) {
+        if self.pending_search.is_some() {
             self.kickoff_search();
         }
     }
@@ -1089,14 +2318,15 @@ impl App {
         let paths = self.paths.clone();
         let tx = self.search_tx.clone();
         let grouping = self.project_display.grouping();
-        std::thread::spawn(move || {
-            let result = collect_projects_from_analytics(&paths, source.as_filter(), grouping)
-                .or_else(|_| {
-                    let index = SearchIndex::open_or_create(&paths.index)?;
-                    collect_projects(&index, source.as_filter())
-                });
+        let index = self.index.clone();
+        let aliases = self.project_aliases.clone();
+        let run = move || {
+            let result =
+                collect_projects_from_analytics(&paths, &aliases, source.as_filter(), grouping)
+                    .or_else(|_| collect_projects(&index, source.as_filter()));
             match result {
-                Ok(projects) => {
+                Ok(mut projects) => {
+                    rank_projects(&project_frecency_path(&paths.state), &mut projects);
                     let _ = tx.send(SearchUpdate::Projects {
                         request_id,
                         projects,
@@ -1110,7 +2340,12 @@ impl App {
                     });
                 }
             }
-        });
+        };
+        self.tasks
+            .spawn(run, move |message| SearchUpdate::ProjectsError {
+                request_id,
+                message,
+            });
     }
 
     fn kickoff_timeline_load(&mut self) {
@@ -1121,13 +2356,24 @@ impl App {
         let range = self.timeline_range;
         let grouping = self.project_display;
         let query = self.query.trim().to_string();
+        let limit = self.config.tui_result_limit();
         let paths = self.paths.clone();
+        let index = self.index.clone();
         let tx = self.search_tx.clone();
+        let aliases = self.project_aliases.clone();
         self.timeline_loaded = Some((source, range, grouping, query.clone()));
         self.set_status("loading timeline...");
-        std::thread::spawn(move || {
-            let result =
-                build_project_timeline(&paths, source.as_filter(), range, grouping, &query);
+        let run = move || {
+            let result = build_project_timeline(
+                &paths,
+                &aliases,
+                &index,
+                source.as_filter(),
+                range,
+                grouping,
+                &query,
+                limit,
+            );
             match result {
                 Ok(rows) => {
                     let _ = tx.send(SearchUpdate::Timeline {
@@ -1146,7 +2392,67 @@ impl App {
                     });
                 }
             }
-        });
+        };
+        self.tasks
+            .spawn(run, move |message| SearchUpdate::TimelineError {
+                request_id,
+                message,
+            });
+    }
+
+    /// Loads records per day over the last `HEATMAP_WEEKS`, filtered like a
+    /// search: query text, project and source all apply.
+    fn kickoff_heatmap_load(&mut self) {
+        let request_id = self.next_request_id();
+        self.active_heatmap_request = request_id;
+        self.heatmap_state = LoadState::Loading;
+        let (query, filters) = extract_query_filters(self.query.trim());
+        let since = filters.since(Some(
+            now_millis().saturating_sub(HEATMAP_WEEKS * 7 * DAY_MS),
+        ));
+        let project = self.project_aliases.resolve(self.project.trim());
+        let options = QueryOptions {
+            query,
+            project: (!project.is_empty()).then_some(project),
+            role: filters.role,
+            tool: None,
+            session_id: None,
+            source: self.source.as_filter(),
+            since,
+            until: filters.before,
+            as_of: None,
+            settings: filters.settings,
+            has: filters.has,
+            edited: filters.edited,
+            commands: filters.commands,
+            files: filters.files,
+            langs: filters.langs,
+            errors: filters.errors,
+            require_all_terms: false,
+            fuzzy_distance: 0,
+            tuning: self.tuning.clone(),
+            limit: 0,
+        };
+        let index = self.index.clone();
+        let tx = self.search_tx.clone();
+        self.set_status("loading heatmap...");
+        let run = move || {
+            let _ = match index.matching_timestamps(&options) {
+                Ok(timestamps) => tx.send(SearchUpdate::Heatmap {
+                    request_id,
+                    counts: heatmap_day_counts(&timestamps),
+                }),
+                Err(err) => tx.send(SearchUpdate::HeatmapError {
+                    request_id,
+                    message: err.to_string(),
+                }),
+            };
+        };
+        self.tasks
+            .spawn(run, move |message| SearchUpdate::HeatmapError {
+                request_id,
+                message,
+            });
     }
 
     fn kickoff_home_activity(&mut self) {
@@ -1160,10 +2466,10 @@ impl App {
         let paths = self.paths.clone();
         let tx = self.search_tx.clone();
         let range = self.home_activity_range;
-        std::thread::spawn(move || {
+        let run = move || {
             let result = (|| -> Result<Vec<HomeChartPoint>> {
                 let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?;
-                let rows = store.query_source_timestamps(range.since_ms(now_ms()))?;
+                let rows = store.query_source_timestamps(range.since_ms(now_millis()))?;
                 Ok(rows
                     .into_iter()
                     .filter(|(_, timestamp_ms)| *timestamp_ms > 0)
@@ -1181,7 +2487,12 @@ impl App {
                     message: error.to_string(),
                 }),
             };
-        });
+        };
+        self.tasks
+            .spawn(run, move |message| SearchUpdate::HomeActivityError {
+                request_id,
+                message,
+            });
         if refresh_tokens {
             self.kickoff_home_token_activity();
         }
@@ -1204,10 +2515,10 @@ impl App {
             self.project_display.grouping(),
             home_token_session_keys(&self.query, &self.results),
             self.home_activity_range,
-            now_ms(),
+            now_millis(),
             self.paths.state.join("usage-cache.sqlite3"),
         );
-        std::thread::spawn(move || {
+        let run = move || {
             let result = scan_usage_activity(&query).map(|(events, partial)| {
                 let points = events
                     .into_iter()
@@ -1235,7 +2546,12 @@ impl App {
                     message: error.to_string(),
                 }),
             };
-        });
+        };
+        self.tasks
+            .spawn(run, move |message| SearchUpdate::HomeTokenActivityError {
+                request_id,
+                message,
+            });
     }
 
     fn kickoff_home_filters(&mut self) {
@@ -1244,9 +2560,11 @@ impl App {
         let paths = self.paths.clone();
         let tx = self.search_tx.clone();
         let grouping = self.project_display.grouping();
-        std::thread::spawn(move || {
+        let aliases = self.project_aliases.clone();
+        let run = move || {
             let (sources, projects) = (|| -> Result<(Vec<SourceChoice>, Vec<String>)> {
-                let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?;
+                let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?
+                    .with_project_aliases(aliases);
                 let labels = store.query_source_labels()?;
                 let sources = [
                     SourceChoice::Claude,
@@ -1255,6 +2573,7 @@ impl App {
                     SourceChoice::Cursor,
                     SourceChoice::Pi,
                     SourceChoice::Copilot,
+                    SourceChoice::ChatGPT,
                 ]
                 .into_iter()
                 .filter(|choice| {
@@ -1276,7 +2595,9 @@ impl App {
                 }
                 let mut projects: Vec<(String, u64)> = latest.into_iter().collect();
                 projects.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
-                Ok((sources, projects.into_iter().map(|(p, _)| p).collect()))
+                let mut projects: Vec<String> = projects.into_iter().map(|(p, _)| p).collect();
+                rank_projects(&project_frecency_path(&paths.state), &mut projects);
+                Ok((sources, projects))
             })()
             .unwrap_or_default();
             let _ = tx.send(SearchUpdate::HomeFilters {
@@ -1284,9 +2605,45 @@ impl App {
                 sources,
                 projects,
             });
+        };
+        self.tasks.spawn(run, move |_| SearchUpdate::HomeFilters {
+            request_id,
+            sources: Vec::new(),
+            projects: Vec::new(),
+        });
+    }
+
+    /// Checks the index and vector store off the UI thread; anything wrong
+    /// comes back as a banner.
+    fn kickoff_health_check(&mut self) {
+        let paths = self.paths.clone();
+        let config = self.config.clone();
+        let index = self.index.clone();
+        let tx = self.search_tx.clone();
+        let run = move || {
+            let issues =
+                check_health(&paths, &config, &index, now_millis() / 1000).unwrap_or_else(|err| {
+                    vec![HealthIssue::new(
+                        format!("health check failed: {err}"),
+                        "memex repair",
+                    )]
+                });
+            let _ = tx.send(SearchUpdate::Health { issues });
+        };
+        self.tasks.spawn(run, |message| SearchUpdate::Health {
+            issues: vec![HealthIssue::new(
+                format!("health check failed: {message}"),
+                "memex repair",
+            )],
         });
     }
 
+    fn dismiss_health_issue(&mut self) {
+        if !self.health.is_empty() {
+            self.health.remove(0);
+        }
+    }
+
     fn home_dropdown_options(&self) -> Vec<String> {
         match self.home_dropdown {
             HomeDropdown::Range => TimelineRange::ALL
@@ -1391,6 +2748,9 @@ impl App {
         self.close_home_dropdown();
         let source_changed = source_selection && self.source != previous_source;
         let project_changed = project_selection && self.project != previous_project;
+        if project_changed {
+            self.record_project_visit();
+        }
         let token_filter_changed = source_changed || project_changed;
         if token_filter_changed {
             self.invalidate_home_token_activity();
@@ -1487,6 +2847,32 @@ impl App {
         self.focus = Focus::List;
     }
 
+    /// Counts choosing the current project filter toward its frecency rank.
+    fn record_project_visit(&self) {
+        let project = self.project.trim();
+        if !project.is_empty() {
+            let _ = record_project_visits(&project_frecency_path(&self.paths.state), [project]);
+        }
+    }
+
+    /// Puts the cursor in the project filter, loading projects for the
+    /// current source if needed.
+    fn focus_project_filter(&mut self) {
+        self.focus = Focus::Project;
+        if self.all_projects.is_empty() || self.project_source != self.source {
+            self.kickoff_project_load();
+        }
+    }
+
+    /// Reloads whatever the current view shows after a filter changed.
+    fn refresh_view(&mut self) {
+        match self.layout_mode {
+            LayoutMode::Timeline => self.kickoff_timeline_load(),
+            LayoutMode::Heatmap => self.kickoff_heatmap_load(),
+            _ => self.refresh_results(),
+        }
+    }
+
     fn update_project_options(&mut self) {
         let filter = self.project.trim().to_lowercase();
         let mut options = Vec::new();
@@ -1505,21 +2891,59 @@ impl App {
         match update {
             IndexUpdate::Started => {
                 self.index_state = IndexState::Loading;
+                self.embed_status = None;
+            }
+            IndexUpdate::Embedding(status) => {
+                self.embed_status = Some(status);
             }
             IndexUpdate::Skipped => {
                 self.index_state = IndexState::Complete;
                 self.set_status("index up to date");
             }
-            IndexUpdate::Done { added, embedded } => {
+            IndexUpdate::Done {
+                added,
+                embedded,
+                malformed,
+                files_scanned,
+                elapsed,
+            } => {
                 self.index_state = IndexState::Complete;
+                self.pinned_counts_for = None;
+                self.last_ingest = Some(IngestTimings {
+                    files_scanned,
+                    added,
+                    embedded,
+                    elapsed,
+                    embed: self.embed_status.take().filter(|status| status.finished),
+                });
                 self.refresh_results();
                 if self.layout_mode == LayoutMode::Home {
                     self.kickoff_home_activity();
                     self.kickoff_home_filters();
                 }
-                self.set_status(format!("indexed {added} records, embedded {embedded}"));
+                let mut status = format!("indexed {added} records, embedded {embedded}");
+                if let Some(rate) = self
+                    .last_ingest
+                    .and_then(|ingest| ingest.embed)
+                    .and_then(|embed| embed.rate())
+                {
+                    status.push_str(&format!(" (avg {})", format_rate(rate)));
+                }
+                if malformed > 0 {
+                    status.push_str(&format!(
+                        "; skipped {malformed} malformed lines (memex ingest-errors)"
+                    ));
+                }
+                self.set_status(status);
+            }
+            IndexUpdate::ReadOnly(message) => {
+                self.set_status(format!("read-only: {message}"));
+                self.index_state = IndexState::ReadOnly(message);
+                // Show whatever the other process has committed so far.
+                self.refresh_results();
             }
             IndexUpdate::Error(message) => {
+                self.embed_status = None;
                 self.index_state = IndexState::Error(message.clone());
                 self.set_status(format!("index error: {message}"));
             }
@@ -1530,9 +2954,13 @@ impl App {
         match update {
             SearchUpdate::Results {
                 request_id,
-                sessions,
+                mut sessions,
             } if request_id == self.active_search_request => {
                 self.home_result_activity = session_activity(&sessions);
+                // Workers already return the best matches first.
+                if self.sort == SessionSort::Recent {
+                    sort_sessions(&mut sessions, self.sort);
+                }
                 self.results = sessions;
                 self.sessions_state = if self.results.is_empty() {
                     LoadState::Empty
@@ -1565,6 +2993,12 @@ impl App {
                     }
                 }
             }
+            SearchUpdate::SearchTimings {
+                request_id,
+                timings,
+            } if request_id == self.active_search_request => {
+                self.last_search_timings = Some(timings);
+            }
             SearchUpdate::Projects {
                 request_id,
                 projects,
@@ -1607,6 +3041,25 @@ impl App {
                 self.timeline_displayed = Some((source, range, grouping, query));
                 self.set_status(format!("{} projects", self.timeline_rows.len()));
             }
+            SearchUpdate::Heatmap { request_id, counts }
+                if request_id == self.active_heatmap_request =>
+            {
+                let total: u64 = counts.values().sum();
+                self.heatmap_counts = counts;
+                self.heatmap_state = if total == 0 {
+                    LoadState::Empty
+                } else {
+                    LoadState::Loaded
+                };
+                self.set_status(format!("{total} records"));
+            }
+            SearchUpdate::HeatmapError {
+                request_id,
+                message,
+            } if request_id == self.active_heatmap_request => {
+                self.heatmap_state = LoadState::Error(message.clone());
+                self.set_status(format!("heatmap error: {message}"));
+            }
             SearchUpdate::SearchError {
                 request_id,
                 message,
@@ -1635,16 +3088,67 @@ impl App {
                 self.timeline_state = LoadState::Error(message.clone());
                 self.set_status(format!("timeline error: {message}"));
             }
-            SearchUpdate::DetailResults { request_id, lines }
-                if request_id == self.active_detail_request =>
-            {
-                self.detail_lines = lines;
+            SearchUpdate::DetailResults {
+                request_id,
+                lines,
+                related,
+            } if request_id == self.active_detail_request => {
+                self.detail_lines = self.prepare_preview_lines(lines);
+                self.detail_related = related;
                 self.detail_state = if self.detail_lines.is_empty() {
                     LoadState::Empty
                 } else {
                     LoadState::Loaded
                 };
                 self.detail_scroll = 0;
+                if self
+                    .detail_request
+                    .as_ref()
+                    .is_some_and(|request| request.lazy_history)
+                {
+                    let view_height = self.preview_area.height as usize;
+                    self.detail_scroll = self.detail_lines.len().saturating_sub(view_height);
+                }
+                self.preview_matches.clear();
+                self.preview_match_state = ListState::default();
+                self.refresh_preview_matches();
+                self.load_earlier_history();
+            }
+            SearchUpdate::DetailAppend {
+                request_id,
+                lines,
+                related,
+            } if request_id == self.active_detail_request => {
+                let lines = self.prepare_preview_lines(lines);
+                self.detail_lines.extend(lines);
+                if !related.is_empty() {
+                    self.detail_related = related;
+                }
+                self.refresh_preview_matches();
+                if !self.detail_lines.is_empty() {
+                    self.detail_state = LoadState::Loaded;
+                }
+            }
+            SearchUpdate::DetailEarlier { request_id, lines }
+                if request_id == self.active_detail_request =>
+            {
+                self.loading_earlier = false;
+                let Some(at) = self
+                    .detail_lines
+                    .iter()
+                    .position(|line| matches!(line, PreviewLine::EarlierHistory(_)))
+                else {
+                    return;
+                };
+                // Keep the lines on screen where they are.
+                let lines = self.prepare_preview_lines(lines);
+                let added = lines.len();
+                self.detail_lines.splice(at..=at, lines);
+                if self.detail_scroll >= at {
+                    self.detail_scroll += added.saturating_sub(1);
+                }
+                self.refresh_preview_matches();
+                self.load_earlier_history();
             }
             SearchUpdate::DetailError {
                 request_id,
@@ -1652,6 +3156,8 @@ impl App {
             } if request_id == self.active_detail_request => {
                 self.detail_state = LoadState::Error(message.clone());
                 self.detail_lines = vec![PreviewLine::Text(format!("preview error: {message}"))];
+                self.detail_related.clear();
+                self.preview_matches.clear();
                 self.detail_scroll = 0;
             }
             SearchUpdate::HomeActivity { request_id, points }
@@ -1697,6 +3203,14 @@ impl App {
                 self.home_sources = sources;
                 self.home_projects = projects;
             }
+            SearchUpdate::Health { issues } => {
+                self.health = issues;
+            }
+            SearchUpdate::PinnedCounts { request_id, counts }
+                if request_id == self.active_pinned_request =>
+            {
+                self.pinned_counts = counts;
+            }
             _ => {}
         }
     }
@@ -1717,6 +3231,21 @@ impl App {
         false
     }
 
+    fn refresh_service_status(&mut self) -> bool {
+        if self
+            .service_checked_at
+            .is_some_and(|at| at.elapsed() < SERVICE_STATUS_POLL)
+        {
+            return false;
+        }
+        self.service_checked_at = Some(Instant::now());
+        let failed = ServiceStatus::load(&service_status_path(&self.paths.state))
+            .is_ok_and(|status| status.failed());
+        let changed = failed != self.service_failed;
+        self.service_failed = failed;
+        changed
+    }
+
     fn move_selection(&mut self, delta: isize) {
         if self.results.is_empty() {
             self.selected.select(None);
@@ -1740,20 +3269,196 @@ impl App {
     }
 
     fn toggle_preview_mode(&mut self) {
-        self.preview_mode = match self.preview_mode {
-            PreviewMode::Matches => PreviewMode::History,
-            PreviewMode::History => PreviewMode::Matches,
+        self.preview_mode = if self.split_preview_active() {
+            self.split_preview = false;
+            PreviewMode::Matches
+        } else {
+            match self.preview_mode {
+                PreviewMode::Matches | PreviewMode::Project => PreviewMode::History,
+                PreviewMode::History => PreviewMode::Matches,
+            }
         };
         self.last_detail_session = None;
         self.update_detail();
     }
 
+    /// Whether the preview shows the query's matches above the full history.
+    /// Project history keeps its single pane.
+    fn split_preview_active(&self) -> bool {
+        self.split_preview && self.preview_mode != PreviewMode::Project
+    }
+
+    fn toggle_split_preview(&mut self) {
+        self.split_preview = !self.split_preview;
+        if self.split_preview && self.preview_mode == PreviewMode::Project {
+            self.preview_mode = PreviewMode::History;
+        }
+        self.set_status(if self.split_preview {
+            "preview: matches above history"
+        } else {
+            "preview: one pane"
+        });
+        self.last_detail_session = None;
+        self.update_detail();
+    }
+
+    /// Switches preview matches between messages holding any query term and
+    /// those holding all of them.
+    fn toggle_all_terms(&mut self) {
+        self.all_terms = !self.all_terms;
+        self.set_status(if self.all_terms {
+            "preview matches: all terms"
+        } else {
+            "preview matches: any term"
+        });
+        self.last_detail_session = None;
+        self.preview_matches.clear();
+        self.update_detail();
+    }
+
+    fn preview_limits(&self) -> PreviewLimits {
+        PreviewLimits {
+            all_terms: self.all_terms,
+            ..PreviewLimits::from_config(&self.config)
+        }
+    }
+
+    /// Lists the history's messages that match the query for the split
+    /// preview, keeping the selected match. The history scrolls to the
+    /// first match as soon as there is one.
+    fn refresh_preview_matches(&mut self) {
+        if !self.split_preview_active() {
+            self.preview_matches.clear();
+            return;
+        }
+        let had_matches = !self.preview_matches.is_empty();
+        let terms = query_terms(&self.detail_query());
+        self.preview_matches = preview_matches(&self.detail_lines, &terms, self.all_terms);
+        if self.preview_matches.is_empty() {
+            self.preview_match_state.select(None);
+            return;
+        }
+        let selected = self
+            .preview_match_state
+            .selected()
+            .unwrap_or(0)
+            .min(self.preview_matches.len() - 1);
+        self.preview_match_state.select(Some(selected));
+        if !had_matches {
+            self.jump_to_preview_match();
+        }
+    }
+
+    /// Selects the next or previous match and scrolls the history to it;
+    /// scrolls the history when nothing matches.
+    fn move_preview_match(&mut self, delta: isize) {
+        if self.preview_matches.is_empty() {
+            self.scroll_detail(delta);
+            return;
+        }
+        let last = self.preview_matches.len() - 1;
+        let selected = self.preview_match_state.selected().unwrap_or(0) as isize;
+        let next = (selected + delta).clamp(0, last as isize) as usize;
+        self.preview_match_state.select(Some(next));
+        self.jump_to_preview_match();
+    }
+
+    fn jump_to_preview_match(&mut self) {
+        if let Some(found) = self
+            .preview_match_state
+            .selected()
+            .and_then(|selected| self.preview_matches.get(selected))
+        {
+            self.detail_scroll = found.line;
+        }
+    }
+
+    /// The query the preview is built for: the find query while there is
+    /// one, else the search.
+    fn detail_query(&self) -> String {
+        if self.find_query.trim().is_empty() {
+            self.query.trim().to_string()
+        } else {
+            self.find_query.trim().to_string()
+        }
+    }
+
     fn toggle_tools(&mut self) {
         self.show_tools = !self.show_tools;
         self.last_detail_session = None;
         self.update_detail();
     }
 
+    fn toggle_threads(&mut self) {
+        self.show_threads = !self.show_threads;
+        self.last_detail_session = None;
+        self.update_detail();
+    }
+
+    /// Turns lexical-only mode on or off. Turning it off doesn't index
+    /// right away; the next refresh embeds whatever was skipped.
+    fn toggle_lexical_only(&mut self) {
+        self.lexical_only = !self.lexical_only;
+        self.set_status(if self.lexical_only {
+            "lexical only: embeddings off"
+        } else {
+            "lexical only: off"
+        });
+        self.last_detail_session = None;
+        self.update_detail();
+    }
+
+    /// Turns preview translations on or off; they need `translate_command`.
+    fn toggle_translate(&mut self) {
+        let Some(translator) = self.config.translator() else {
+            self.set_status("set translate_command in config.toml to translate messages");
+            return;
+        };
+        self.translate = !self.translate;
+        if self.translate {
+            self.set_status(format!("translating into {}", translator.to));
+        } else {
+            self.set_status("translation: off");
+        }
+        self.last_detail_session = None;
+        self.update_detail();
+    }
+
+    fn toggle_debug_overlay(&mut self) {
+        self.debug_overlay = !self.debug_overlay;
+        if self.debug_overlay {
+            self.debug_segments = self.index.segment_stats().ok();
+        }
+    }
+
+    fn toggle_compactions(&mut self) {
+        self.expand_compactions = !self.expand_compactions;
+        self.last_detail_session = None;
+        self.update_detail();
+    }
+
+    /// Selects the `idx`th related session of the current preview, adding it
+    /// to the top of the results when the current search did not return it.
+    fn open_related_session(&mut self, idx: usize) {
+        let Some(session) = self.detail_related.get(idx).cloned() else {
+            return;
+        };
+        let position = match self
+            .results
+            .iter()
+            .position(|result| result.session_id == session.session_id)
+        {
+            Some(position) => position,
+            None => {
+                self.results.insert(0, session);
+                0
+            }
+        };
+        self.selected.select(Some(position));
+        self.quick_scroll = 0;
+        self.update_detail();
+    }
+
     fn focus_next(&mut self) {
         self.focus = match self.layout_mode {
             LayoutMode::Home => match self.focus {
@@ -1767,7 +3472,7 @@ impl App {
                 Focus::List | Focus::Preview => Focus::Find,
                 Focus::Find => Focus::Query,
             },
-            LayoutMode::Timeline => Focus::List,
+            LayoutMode::Timeline | LayoutMode::Heatmap => Focus::List,
             LayoutMode::Detail => match self.focus {
                 Focus::Preview => Focus::Find,
                 Focus::Find | Focus::Query | Focus::Project | Focus::List => Focus::Preview,
@@ -1788,7 +3493,7 @@ impl App {
                 Focus::List | Focus::Preview => Focus::Project,
                 Focus::Find => Focus::List,
             },
-            LayoutMode::Timeline => Focus::List,
+            LayoutMode::Timeline | LayoutMode::Heatmap => Focus::List,
             LayoutMode::Detail => match self.focus {
                 Focus::Preview | Focus::Query | Focus::Project | Focus::List => Focus::Find,
                 Focus::Find => Focus::Preview,
@@ -1808,6 +3513,9 @@ impl App {
         };
         let next = (self.detail_scroll as isize + delta).clamp(0, max_scroll as isize) as usize;
         self.detail_scroll = next;
+        if delta < 0 {
+            self.load_earlier_history();
+        }
     }
 
     fn scroll_quick_popup(&mut self, delta: isize) {
@@ -1840,7 +3548,11 @@ impl App {
                 self.kickoff_timeline_load();
                 LayoutMode::Timeline
             }
-            LayoutMode::Timeline | LayoutMode::Detail => LayoutMode::Split,
+            LayoutMode::Timeline => {
+                self.kickoff_heatmap_load();
+                LayoutMode::Heatmap
+            }
+            LayoutMode::Heatmap | LayoutMode::Detail => LayoutMode::Split,
         };
     }
 
@@ -1857,22 +3569,145 @@ impl App {
         }
     }
 
-    fn cycle_timeline_range(&mut self, delta: isize) {
-        self.timeline_range = if delta < 0 {
-            self.timeline_range.prev()
-        } else {
-            self.timeline_range.next()
-        };
-        if matches!(self.layout_mode, LayoutMode::Timeline) {
-            self.timeline_scroll = 0;
-            self.timeline_selected = 0;
-            self.kickoff_timeline_load();
+    /// Moves to the next built-in or configured scoring profile, skipping
+    /// configured ones that fail validation.
+    fn cycle_scoring_profile(&mut self) {
+        let names = self.config.scoring_profile_names();
+        let start = names
+            .iter()
+            .position(|name| name == &self.scoring_profile)
+            .unwrap_or(0);
+        for offset in 1..=names.len() {
+            let name = &names[(start + offset) % names.len()];
+            match self.config.resolve_scoring_profile(Some(name)) {
+                Ok(profile) => {
+                    self.scoring_profile = name.clone();
+                    self.profile = profile;
+                    self.set_status(format!("profile: {name}"));
+                    if !matches!(self.layout_mode, LayoutMode::Timeline) {
+                        self.refresh_results();
+                    }
+                    return;
+                }
+                Err(err) => self.set_status(err.to_string()),
+            }
         }
     }
 
-    fn toggle_timeline_density(&mut self) {
-        self.timeline_density = self.timeline_density.toggle();
-        self.set_status(format!("density: {}", self.timeline_density.label()));
+    fn toggle_sort(&mut self) {
+        self.sort = match self.sort {
+            SessionSort::Relevance => SessionSort::Recent,
+            SessionSort::Recent => SessionSort::Relevance,
+        };
+        self.set_status(format!("sort: {}", self.sort.label()));
+        if self.results.is_empty() {
+            return;
+        }
+        sort_sessions(&mut self.results, self.sort);
+        self.selected.select(Some(0));
+        self.last_detail_session = None;
+        self.detail_scroll = 0;
+        self.update_detail();
+    }
+
+    /// Re-reads config.toml and applies it in place. A file that fails to
+    /// parse or validate leaves the running config untouched.
+    fn reload_config(&mut self) {
+        let mut config = match UserConfig::load(&self.paths) {
+            Ok(config) => config,
+            Err(err) => {
+                self.set_status(format!("config.toml not reloaded: {err}"));
+                return;
+            }
+        };
+        let session_columns = match config.session_list_columns() {
+            Ok(columns) => columns,
+            Err(err) => {
+                self.set_status(format!("config.toml not reloaded: {err}"));
+                return;
+            }
+        };
+        let tuning = match config.query_tuning() {
+            Ok(tuning) => tuning,
+            Err(err) => {
+                self.set_status(format!("config.toml not reloaded: {err}"));
+                return;
+            }
+        };
+        self.overrides.apply(&mut config);
+        let changed = self.config.changed_keys(&config);
+        if changed.is_empty() {
+            self.set_status("config.toml unchanged");
+            return;
+        }
+        // Keep the profile picked with `o` unless the default itself changed
+        // or the picked one is gone.
+        let scoring_changed = changed
+            .iter()
+            .any(|key| key == "scoring_profile" || key == "scoring_profiles");
+        if scoring_changed {
+            let configured = config
+                .scoring_profile
+                .clone()
+                .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+            let name = if changed.iter().any(|key| key == "scoring_profile") {
+                configured
+            } else {
+                self.scoring_profile.clone()
+            };
+            let resolved = config
+                .resolve_scoring_profile(Some(&name))
+                .map(|profile| (name, profile))
+                .or_else(|_| {
+                    config
+                        .resolve_scoring_profile(Some(DEFAULT_PROFILE))
+                        .map(|profile| (DEFAULT_PROFILE.to_string(), profile))
+                });
+            match resolved {
+                Ok((name, profile)) => {
+                    self.scoring_profile = name;
+                    self.profile = profile;
+                }
+                Err(err) => {
+                    self.set_status(format!("config.toml not reloaded: {err}"));
+                    return;
+                }
+            }
+        }
+        let tuning_changed = tuning != self.tuning;
+        let limit_changed = config.tui_result_limit() != self.config.tui_result_limit();
+        let preview_changed =
+            PreviewLimits::from_config(&config) != PreviewLimits::from_config(&self.config);
+        self.config = config;
+        self.session_columns = session_columns;
+        self.tuning = tuning;
+        self.set_status(format!("config reloaded: {}", changed.join(", ")));
+        if (scoring_changed || tuning_changed || limit_changed)
+            && !matches!(self.layout_mode, LayoutMode::Timeline)
+        {
+            self.refresh_results();
+        } else if preview_changed {
+            self.last_detail_session = None;
+            self.update_detail();
+        }
+    }
+
+    fn cycle_timeline_range(&mut self, delta: isize) {
+        self.timeline_range = if delta < 0 {
+            self.timeline_range.prev()
+        } else {
+            self.timeline_range.next()
+        };
+        if matches!(self.layout_mode, LayoutMode::Timeline) {
+            self.timeline_scroll = 0;
+            self.timeline_selected = 0;
+            self.kickoff_timeline_load();
+        }
+    }
+
+    fn toggle_timeline_density(&mut self) {
+        self.timeline_density = self.timeline_density.toggle();
+        self.set_status(format!("density: {}", self.timeline_density.label()));
         if matches!(self.layout_mode, LayoutMode::Timeline) {
             self.scroll_timeline(0);
         }
@@ -1934,7 +3769,7 @@ impl App {
         self.project_display = display;
         self.query = query;
         self.project = project;
-        self.sessions_since = range.since_ms(now_ms());
+        self.sessions_since = range.since_ms(now_millis());
         self.layout_mode = LayoutMode::List;
         self.focus = Focus::List;
         self.quick_popup = false;
@@ -1974,12 +3809,34 @@ impl App {
             PreviewMode::Matches,
             active_query,
             self.show_tools,
+            false,
+            self.preview_limits(),
         ) {
-            Ok(lines) => lines,
+            Ok(lines) => self.prepare_preview_lines(lines),
             Err(err) => vec![PreviewLine::Text(format!("detail error: {err}"))],
         };
     }
 
+    /// Preview lines as loaded, with compactions unfolded when expanded and
+    /// code blocks highlighted when `highlight_code` is on.
+    fn prepare_preview_lines(&self, lines: Vec<PreviewLine>) -> Vec<PreviewLine> {
+        let lines = unfold_compactions(lines, self.expand_compactions)
+            .into_iter()
+            .map(|line| match line {
+                PreviewLine::ToolGroup(mut group) => {
+                    group.lines = self.prepare_preview_lines(group.lines);
+                    PreviewLine::ToolGroup(group)
+                }
+                line => line,
+            })
+            .collect();
+        if self.config.highlight_code() {
+            highlight_code_blocks(lines)
+        } else {
+            lines
+        }
+    }
+
     fn enter_preview(&mut self) {
         self.layout_mode = LayoutMode::Split;
         self.quick_popup = false;
@@ -1988,15 +3845,33 @@ impl App {
     }
 
     fn enter_full_history(&mut self) {
-        self.detail_return_mode = if self.layout_mode == LayoutMode::Home {
-            LayoutMode::Home
+        self.open_detail(PreviewMode::History);
+    }
+
+    /// Shows the selected session's project as one stream, or goes back to
+    /// the session's own history when it is already showing.
+    fn toggle_project_history(&mut self) {
+        if self.preview_mode == PreviewMode::Project {
+            self.preview_mode = PreviewMode::History;
+            self.last_detail_session = None;
+            self.update_detail();
         } else {
-            LayoutMode::List
-        };
+            self.open_detail(PreviewMode::Project);
+        }
+    }
+
+    fn open_detail(&mut self, mode: PreviewMode) {
+        if self.layout_mode != LayoutMode::Detail {
+            self.detail_return_mode = if self.layout_mode == LayoutMode::Home {
+                LayoutMode::Home
+            } else {
+                LayoutMode::List
+            };
+        }
         self.layout_mode = LayoutMode::Detail;
         self.quick_popup = false;
         self.quick_lines.clear();
-        self.preview_mode = PreviewMode::History;
+        self.preview_mode = mode;
         self.focus = Focus::Preview;
         self.last_detail_session = None;
         self.update_detail();
@@ -2031,58 +3906,458 @@ impl App {
         self.update_detail();
     }
 
-    fn resume_selected(&mut self, terminal: &mut TuiTerminal) -> Result<()> {
+    /// Opens the resume confirmation with the expanded command for the
+    /// selected session; nothing runs until it is confirmed.
+    fn resume_selected(&mut self) {
         let Some(idx) = self.selected.selected() else {
             self.set_status("no session selected");
-            return Ok(());
+            return;
         };
-        let Some(session) = self.results.get(idx) else {
+        let Some(session) = self.results.get(idx).cloned() else {
             self.set_status("no session selected");
-            return Ok(());
-        };
-        let template = match session.source {
-            SourceKind::Claude => self
-                .config
-                .claude_resume_cmd
-                .clone()
-                .or_else(|| default_resume_template("claude")),
-            SourceKind::CodexSession | SourceKind::CodexHistory => self
-                .config
-                .codex_resume_cmd
-                .clone()
-                .or_else(|| default_resume_template("codex")),
-            SourceKind::Opencode => self
-                .config
-                .opencode_resume_cmd
-                .clone()
-                .or_else(|| default_resume_template("opencode")),
-            SourceKind::Cursor => self
-                .config
-                .cursor_resume_cmd
-                .clone()
-                .or_else(|| default_resume_template("cursor")),
-            SourceKind::Pi => self
-                .config
-                .pi_resume_cmd
-                .clone()
-                .or_else(|| default_resume_template("pi")),
-            SourceKind::Copilot => self
-                .config
-                .copilot_resume_cmd
-                .clone()
-                .or_else(|| default_resume_template("copilot")),
+            return;
         };
-        let Some(template) = template else {
+        let Some(template) = self.resume_template(session.source) else {
             self.set_status("resume command not configured in config.toml");
+            return;
+        };
+        let session_cwd = resolve_session_cwd(&session);
+        let cwd = session_cwd
+            .clone()
+            .unwrap_or_else(|| session.source_dir.clone());
+        let cd =
+            session_cwd.is_some() && self.config.resume_cd(resume_source_label(session.source));
+        let command = resume_command(&template, &session, &cwd, cd);
+        self.resume_prompt = Some(ResumePrompt::new(session, cwd, command));
+    }
+
+    /// Saved override from the resume popup, then config.toml, then the
+    /// built-in default when the CLI is on PATH.
+    fn resume_template(&self, source: SourceKind) -> Option<String> {
+        let label = resume_source_label(source);
+        if let Ok(overrides) = ResumeOverrides::load(&resume_overrides_path(&self.paths.state))
+            && let Some(template) = overrides.templates.get(label)
+        {
+            return Some(template.clone());
+        }
+        let configured = match source {
+            SourceKind::Claude => &self.config.claude_resume_cmd,
+            SourceKind::CodexSession | SourceKind::CodexHistory => &self.config.codex_resume_cmd,
+            SourceKind::Opencode => &self.config.opencode_resume_cmd,
+            SourceKind::Cursor => &self.config.cursor_resume_cmd,
+            SourceKind::Pi => &self.config.pi_resume_cmd,
+            SourceKind::Copilot => &self.config.copilot_resume_cmd,
+            SourceKind::ChatGPT | SourceKind::External(_) => &None,
+        };
+        configured
+            .clone()
+            .or_else(|| default_resume_template(label))
+    }
+
+    fn confirm_resume(&mut self, terminal: &mut TuiTerminal) -> Result<()> {
+        let Some(prompt) = self.resume_prompt.take() else {
             return Ok(());
         };
-        let cwd = resolve_session_cwd(session).unwrap_or_else(|| session.source_dir.clone());
-        let command = expand_resume_template(&template, session, &cwd);
+        let command = prompt.command.text.trim().to_string();
+        if command.is_empty() {
+            self.set_status("resume command is empty");
+            return Ok(());
+        }
         run_external_command(self, terminal, &command)?;
         self.set_status(format!("ran: {command}"));
         Ok(())
     }
 
+    /// Opens the note editor on the selected session, holding its current
+    /// note.
+    fn note_selected(&mut self) {
+        let Some(session) = self
+            .selected
+            .selected()
+            .and_then(|idx| self.results.get(idx))
+        else {
+            self.set_status("no session selected");
+            return;
+        };
+        self.note_prompt = Some(NotePrompt {
+            session_id: session.session_id.clone(),
+            text: LineInput::new(session.note.clone()),
+        });
+    }
+
+    fn open_palette(&mut self) {
+        self.palette = Some(Palette {
+            filter: LineInput::new(String::new()),
+            selected: 0,
+        });
+    }
+
+    /// Runs what the palette's `action` stands for, as its key would.
+    fn run_palette_action(&mut self, action: PaletteAction) {
+        match action {
+            PaletteAction::ToggleSort => self.toggle_sort(),
+            PaletteAction::CycleScoring => self.cycle_scoring_profile(),
+            PaletteAction::CycleSource => {
+                self.source = self.source.cycle();
+                self.set_status("searching...");
+                self.refresh_view();
+            }
+            PaletteAction::FilterProject => self.focus_project_filter(),
+            PaletteAction::ToggleLayout => self.toggle_layout_mode(),
+            PaletteAction::TogglePreviewMode => self.toggle_preview_mode(),
+            PaletteAction::ToggleSplitPreview => self.toggle_split_preview(),
+            PaletteAction::ToggleAllTerms => self.toggle_all_terms(),
+            PaletteAction::ToggleTools => self.toggle_tools(),
+            PaletteAction::ToggleThreads => self.toggle_threads(),
+            PaletteAction::ToggleCompactions => self.toggle_compactions(),
+            PaletteAction::ToggleProjectDisplay => self.toggle_project_display(),
+            PaletteAction::ToggleProjectHistory => self.toggle_project_history(),
+            PaletteAction::ToggleLexicalOnly => self.toggle_lexical_only(),
+            PaletteAction::ToggleTranslate => self.toggle_translate(),
+            PaletteAction::TogglePinnedQuery => self.toggle_pinned_query(),
+            PaletteAction::NextPinnedQuery => self.next_pinned_query(),
+            PaletteAction::Resume => self.resume_selected(),
+            PaletteAction::Note => self.note_selected(),
+            PaletteAction::CycleOutcome => self.cycle_outcome_selected(),
+            PaletteAction::Replay => self.replay_selected(),
+            PaletteAction::Share => {
+                let _ = self.share_selected();
+            }
+            PaletteAction::Explain => self.explain_selected(),
+            PaletteAction::ExportCollected => self.export_basket(),
+            PaletteAction::RefreshIndex => self.kickoff_index_refresh(true),
+            PaletteAction::ReloadConfig => self.reload_config(),
+            // Needs the terminal; run by the key handler.
+            PaletteAction::EditConfig => {}
+            PaletteAction::ToggleTimings => self.toggle_debug_overlay(),
+            PaletteAction::GoHome => self.go_home(),
+        }
+    }
+
+    /// Moves the selected session to its next outcome label: success,
+    /// abandoned, rework, then none.
+    fn cycle_outcome_selected(&mut self) {
+        let Some(session) = self
+            .selected
+            .selected()
+            .and_then(|idx| self.results.get(idx))
+        else {
+            self.set_status("no session selected");
+            return;
+        };
+        let session_id = session.session_id.clone();
+        let next = Outcome::cycle(session.outcome);
+        if let Err(err) = set_outcome(&self.paths, &self.index, &session_id, next, now_millis()) {
+            self.set_status(format!("failed to save outcome: {err}"));
+            return;
+        }
+        self.set_status(match next {
+            Some(outcome) => format!("outcome: {}", outcome.label()),
+            None => "outcome cleared".to_string(),
+        });
+        for session in &mut self.results {
+            if session.session_id == session_id {
+                session.outcome = next;
+            }
+        }
+        self.last_detail_session = None;
+        self.update_detail();
+    }
+
+    /// Opens the selected session in the replay view, with tool calls and
+    /// threads as the current toggles show them.
+    fn replay_selected(&mut self) {
+        let Some(session) = self
+            .selected
+            .selected()
+            .and_then(|idx| self.results.get(idx))
+        else {
+            self.set_status("no session selected");
+            return;
+        };
+        let mut records = match self.index.records_by_session_id(&session.session_id) {
+            Ok(records) => records,
+            Err(err) => {
+                self.set_status(format!("replay failed: {err}"));
+                return;
+            }
+        };
+        records.retain(|record| {
+            (self.show_tools || !is_tool_role(&record.role))
+                && (self.show_threads || record.links.thread.is_none())
+        });
+        if records.is_empty() {
+            self.set_status("nothing to replay");
+            return;
+        }
+        records.sort_by(|a, b| {
+            a.turn_id
+                .cmp(&b.turn_id)
+                .then_with(|| a.ts.cmp(&b.ts))
+                .then_with(|| a.doc_id.cmp(&b.doc_id))
+        });
+        self.quick_popup = false;
+        self.quick_lines.clear();
+        self.replay = Some(Replay::new(session, records, self.config.replay_interval()));
+    }
+
+    fn tick_replay(&mut self) -> bool {
+        self.replay
+            .as_mut()
+            .is_some_and(|replay| replay.tick(Instant::now()))
+    }
+
+    fn save_note(&mut self) {
+        let Some(prompt) = self.note_prompt.take() else {
+            return;
+        };
+        let lock = match WriteLock::try_acquire(&self.paths.state) {
+            Ok(Some(lock)) => lock,
+            Ok(None) => {
+                self.set_status(busy_message(&self.paths.state));
+                self.note_prompt = Some(prompt);
+                return;
+            }
+            Err(err) => {
+                self.set_status(format!("failed to save note: {err}"));
+                return;
+            }
+        };
+        let result = set_note(
+            &self.paths,
+            &self.index,
+            &prompt.session_id,
+            &prompt.text.text,
+            now_millis(),
+        );
+        drop(lock);
+        let note = match result {
+            Ok(note) => note.map(|note| note.text).unwrap_or_default(),
+            Err(err) => {
+                self.set_status(format!("failed to save note: {err}"));
+                return;
+            }
+        };
+        self.set_status(if note.is_empty() {
+            "note cleared"
+        } else {
+            "note saved"
+        });
+        for session in &mut self.results {
+            if session.session_id == prompt.session_id {
+                session.note = note.clone();
+            }
+        }
+        self.last_detail_session = None;
+        self.update_detail();
+    }
+
+    /// Remembers the edited command as this source's resume template, with
+    /// the session's values turned back into placeholders.
+    fn save_resume_override(&mut self) {
+        let Some(prompt) = self.resume_prompt.as_ref() else {
+            return;
+        };
+        let label = resume_source_label(prompt.session.source);
+        let template =
+            templatize_resume_command(&prompt.command.text, &prompt.session, &prompt.cwd);
+        let path = resume_overrides_path(&self.paths.state);
+        let mut overrides = ResumeOverrides::load(&path).unwrap_or_default();
+        overrides.templates.insert(label.to_string(), template);
+        match overrides.save(&path) {
+            Ok(()) => self.set_status(format!("saved resume command for {label}")),
+            Err(err) => self.set_status(format!("failed to save resume command: {err}")),
+        }
+    }
+
+    /// Message shown at the top of the preview: the last header at or above
+    /// the first visible line.
+    fn focused_message(&self) -> Option<u64> {
+        if self.detail_lines.is_empty() {
+            return None;
+        }
+        let top = self.detail_scroll.min(self.detail_lines.len() - 1);
+        self.detail_lines[..=top]
+            .iter()
+            .rev()
+            .find_map(preview_line_doc_id)
+            .or_else(|| {
+                self.detail_lines[top..]
+                    .iter()
+                    .find_map(preview_line_doc_id)
+            })
+    }
+
+    /// The tool group Enter acts on: an expanded one the view has scrolled
+    /// into, else the first one in view.
+    fn focused_tool_group(&self) -> Option<usize> {
+        if self.detail_lines.is_empty() {
+            return None;
+        }
+        let top = self.detail_scroll.min(self.detail_lines.len() - 1);
+        let containing = (0..=top).rev().find(|&at| {
+            matches!(&self.detail_lines[at], PreviewLine::ToolGroup(group)
+                if group.expanded > 0 && at + group.expanded >= top)
+        });
+        let height = (self.preview_area.height as usize).max(1);
+        containing.or_else(|| {
+            (top..(top + height).min(self.detail_lines.len()))
+                .find(|&at| matches!(self.detail_lines[at], PreviewLine::ToolGroup(_)))
+        })
+    }
+
+    /// Expands the focused run of tool messages in place, or folds it back.
+    fn toggle_tool_group(&mut self) {
+        let Some(at) = self.focused_tool_group() else {
+            return;
+        };
+        let PreviewLine::ToolGroup(group) = &mut self.detail_lines[at] else {
+            return;
+        };
+        if group.expanded > 0 {
+            let end = at + 1 + std::mem::take(&mut group.expanded);
+            let lines: Vec<PreviewLine> = self.detail_lines.drain(at + 1..end).collect();
+            if let PreviewLine::ToolGroup(group) = &mut self.detail_lines[at] {
+                group.lines = lines;
+            }
+            self.detail_scroll = self.detail_scroll.min(at);
+        } else {
+            let lines = std::mem::take(&mut group.lines);
+            group.expanded = lines.len();
+            self.detail_lines.splice(at + 1..at + 1, lines);
+        }
+        self.refresh_preview_matches();
+    }
+
+    /// Shows in the status line why the selected session ranked where it
+    /// did, by breaking down the score of its best message.
+    fn explain_selected(&mut self) {
+        let Some(session) = self
+            .selected
+            .selected()
+            .and_then(|idx| self.results.get(idx))
+        else {
+            self.set_status("no session selected");
+            return;
+        };
+        if self.query.trim().is_empty() {
+            self.set_status("nothing to explain without a query");
+            return;
+        }
+        let session_id = session.session_id.clone();
+        // Rerun the list's own query so the scores match what ranked it.
+        let project = self.project_aliases.resolve(self.project.trim());
+        let project = project.as_str();
+        let project = (self.project_display.grouping() == ProjectGrouping::Flat
+            && !project.is_empty())
+        .then_some(project);
+        let options = search_options(
+            self.query.trim(),
+            self.source.as_filter(),
+            project,
+            self.sessions_since,
+            SearchSettings {
+                profile: &self.profile,
+                tuning: &self.tuning,
+                limit: self.config.tui_result_limit(),
+            },
+        );
+        let profile = self.profile;
+        let now = now_millis();
+        let best = self.index.search(&options).map(|results| {
+            results
+                .into_iter()
+                .filter(|(_, record)| record.session_id == session_id)
+                .map(|(bm25, record)| {
+                    let mut explanation = ScoreExplanation::new(
+                        bm25,
+                        record.ts,
+                        now,
+                        profile.recency_weight,
+                        profile.recency_half_life_days,
+                    );
+                    explanation.bm25 = Some(bm25);
+                    (explanation, record)
+                })
+                .max_by(|a, b| a.0.score.total_cmp(&b.0.score))
+        });
+        let (mut explanation, record) = match best {
+            Ok(Some(best)) => best,
+            Ok(None) => {
+                self.set_status("no matching message to explain");
+                return;
+            }
+            Err(err) => {
+                self.set_status(format!("explain failed: {err}"));
+                return;
+            }
+        };
+        match self.index.term_scores(&options, record.doc_id) {
+            Ok(terms) => explanation.terms = terms,
+            Err(err) => {
+                self.set_status(format!("explain failed: {err}"));
+                return;
+            }
+        }
+        self.set_status(format!(
+            "{} {}: {}",
+            record.role,
+            format_ts(record.ts),
+            explanation.lines().join(" · ")
+        ));
+    }
+
+    fn toggle_collected(&mut self) {
+        let Some(doc_id) = self.focused_message() else {
+            self.set_status("no message to collect");
+            return;
+        };
+        if let Some(pos) = self
+            .basket
+            .iter()
+            .position(|record| record.doc_id == doc_id)
+        {
+            self.basket.remove(pos);
+            self.set_status(format!("removed from basket ({} left)", self.basket.len()));
+            return;
+        }
+        match self.index.get_by_doc_id(doc_id) {
+            Ok(Some(record)) => {
+                self.set_status(format!(
+                    "collected {} {} ({} in basket, C exports)",
+                    record.role,
+                    format_ts(record.ts),
+                    self.basket.len() + 1
+                ));
+                self.basket.push(record);
+            }
+            Ok(None) => self.set_status("message is no longer indexed"),
+            Err(err) => self.set_status(format!("collect failed: {err}")),
+        }
+    }
+
+    /// Writes the basket as one context block under `<root>/context` and
+    /// empties it.
+    fn export_basket(&mut self) {
+        if self.basket.is_empty() {
+            self.set_status("basket is empty (c collects the message at the top)");
+            return;
+        }
+        let format = self.config.context_format();
+        let dir = self.paths.root.join("context");
+        let path = dir.join(format!("context-{}.{}", now_millis(), format.extension()));
+        let result = std::fs::create_dir_all(&dir)
+            .and_then(|()| std::fs::write(&path, context::render(&self.basket, format)));
+        match result {
+            Ok(()) => {
+                let count = self.basket.len();
+                self.basket.clear();
+                self.set_status(format!("wrote {count} messages to {}", path.display()));
+            }
+            Err(err) => self.set_status(format!("export failed: {err}")),
+        }
+    }
+
     fn share_selected(&mut self) -> Result<()> {
         let Some(idx) = self.selected.selected() else {
             self.set_status("no session selected");
@@ -2106,6 +4381,10 @@ impl App {
             SourceKind::Cursor => "cursor",
             SourceKind::Pi => "pi",
             SourceKind::Copilot => "copilot",
+            SourceKind::ChatGPT | SourceKind::External(_) => {
+                self.set_status("sharing is not supported for imported or external sources");
+                return Ok(());
+            }
         };
         let source_path = session.source_path.clone();
 
@@ -2156,10 +4435,16 @@ impl App {
 }
 
 fn run_loop(terminal: &mut TuiTerminal, app: &mut App) -> Result<()> {
-    terminal.draw(|frame| draw_ui(frame, app))?;
+    draw_frame(terminal, app)?;
     loop {
         let mut dirty = app.clear_status_if_old() || app.tick_spinner();
-        if app.flush_home_search_if_due() {
+        if app.refresh_service_status() {
+            dirty = true;
+        }
+        if app.flush_search_if_due() {
+            dirty = true;
+        }
+        if app.tick_replay() {
             dirty = true;
         }
         if let Some(update_rx) = app.update_rx.as_ref() {
@@ -2190,7 +4475,7 @@ fn run_loop(terminal: &mut TuiTerminal, app: &mut App) -> Result<()> {
                     Event::Mouse(mouse) => {
                         // Mouse capture also reports pure motion; only redraw
                         // when the handler actually changed something.
-                        if handle_mouse(mouse, terminal, app)? {
+                        if handle_mouse(mouse, app)? {
                             dirty = true;
                         }
                     }
@@ -2207,7 +4492,7 @@ fn run_loop(terminal: &mut TuiTerminal, app: &mut App) -> Result<()> {
             break;
         }
         if dirty {
-            terminal.draw(|f| draw_ui(f, app))?;
+            draw_frame(terminal, app)?;
         }
     }
     Ok(())
@@ -2223,6 +4508,35 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         return Ok(true);
     }
 
+    if app.resume_prompt.is_some() {
+        handle_resume_prompt_key(key, terminal, app)?;
+        return Ok(false);
+    }
+
+    if app.note_prompt.is_some() {
+        handle_note_prompt_key(key, app);
+        return Ok(false);
+    }
+
+    if app.replay.is_some() {
+        handle_replay_key(key, app);
+        return Ok(false);
+    }
+
+    if app.palette.is_some() {
+        match handle_palette_key(key, app) {
+            Some(PaletteAction::EditConfig) => edit_config(app, terminal)?,
+            Some(action) => app.run_palette_action(action),
+            None => {}
+        }
+        return Ok(false);
+    }
+
+    if key.code == KeyCode::Char('p') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        app.open_palette();
+        return Ok(false);
+    }
+
     if app.quick_popup {
         match key.code {
             KeyCode::Esc | KeyCode::Char(' ') => {
@@ -2248,8 +4562,16 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         return Ok(false);
     }
 
+    if key.code == KeyCode::Char('x')
+        && key.modifiers.contains(KeyModifiers::CONTROL)
+        && !app.health.is_empty()
+    {
+        app.dismiss_health_issue();
+        return Ok(false);
+    }
+
     if app.layout_mode == LayoutMode::Home {
-        return handle_home_key(key, terminal, app);
+        return handle_home_key(key, app);
     }
 
     if matches!(key.code, KeyCode::Esc) {
@@ -2282,14 +4604,11 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
                     && let Some(project) = app.project_options.get(app.project_selected)
                 {
                     app.project = project.clone();
+                    app.record_project_visit();
                 }
                 app.set_status("searching...");
-                terminal.draw(|f| draw_ui(f, app))?;
-                if app.layout_mode == LayoutMode::Timeline {
-                    app.kickoff_timeline_load();
-                } else {
-                    app.refresh_results();
-                }
+                draw_frame(terminal, app)?;
+                app.refresh_view();
                 app.focus = if app.layout_mode == LayoutMode::Detail {
                     Focus::Preview
                 } else {
@@ -2298,7 +4617,9 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
             }
             KeyCode::Backspace => match app.focus {
                 Focus::Query => {
-                    app.query.pop();
+                    if app.query.pop().is_some() {
+                        app.search_as_you_type();
+                    }
                 }
                 Focus::Project => {
                     app.project.pop();
@@ -2320,7 +4641,10 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
             }
             KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
                 match app.focus {
-                    Focus::Query => app.query.push(ch),
+                    Focus::Query => {
+                        app.query.push(ch);
+                        app.search_as_you_type();
+                    }
                     Focus::Project => {
                         app.project.push(ch);
                         app.update_project_options();
@@ -2371,6 +4695,13 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         return Ok(false);
     }
 
+    // The heatmap has no selection, so only filter and view keys apply.
+    if app.layout_mode == LayoutMode::Heatmap
+        && !matches!(key.code, KeyCode::Char('s' | 'p' | '/' | 'v' | 'i'))
+    {
+        return Ok(false);
+    }
+
     match key.code {
         KeyCode::Tab => {
             app.focus_next();
@@ -2395,6 +4726,8 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         KeyCode::Char('j') => {
             if matches!(app.layout_mode, LayoutMode::Timeline) {
                 app.move_timeline_selection(1);
+            } else if matches!(app.focus, Focus::Preview) && app.split_preview_active() {
+                app.move_preview_match(1);
             } else if matches!(app.focus, Focus::Preview) {
                 app.scroll_detail(1);
             } else {
@@ -2404,6 +4737,8 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         KeyCode::Char('k') => {
             if matches!(app.layout_mode, LayoutMode::Timeline) {
                 app.move_timeline_selection(-1);
+            } else if matches!(app.focus, Focus::Preview) && app.split_preview_active() {
+                app.move_preview_match(-1);
             } else if matches!(app.focus, Focus::Preview) {
                 app.scroll_detail(-1);
             } else {
@@ -2439,6 +4774,8 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
                 } else {
                     app.enter_preview();
                 }
+            } else if matches!(app.focus, Focus::Preview) {
+                app.toggle_tool_group();
             }
         }
         KeyCode::PageDown => {
@@ -2458,12 +4795,8 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         KeyCode::Char('s') => {
             app.source = app.source.cycle();
             app.set_status("searching...");
-            terminal.draw(|f| draw_ui(f, app))?;
-            if matches!(app.layout_mode, LayoutMode::Timeline) {
-                app.kickoff_timeline_load();
-            } else {
-                app.refresh_results();
-            }
+            draw_frame(terminal, app)?;
+            app.refresh_view();
         }
         KeyCode::Char('[') => {
             app.cycle_timeline_range(-1);
@@ -2477,11 +4810,26 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         KeyCode::Char('g') => {
             app.toggle_project_display();
         }
+        KeyCode::Char('o') => {
+            app.cycle_scoring_profile();
+        }
+        KeyCode::Char('O') => {
+            app.toggle_sort();
+        }
         KeyCode::Char('m') => {
             app.toggle_preview_mode();
         }
-        KeyCode::Char('v') => {
-            app.toggle_layout_mode();
+        KeyCode::Char('M') => {
+            app.toggle_split_preview();
+        }
+        KeyCode::Char('b') => {
+            app.next_pinned_query();
+        }
+        KeyCode::Char('B') => {
+            app.toggle_pinned_query();
+        }
+        KeyCode::Char('v') => {
+            app.toggle_layout_mode();
         }
         KeyCode::Char(' ')
             if app.layout_mode == LayoutMode::List && matches!(app.focus, Focus::List) =>
@@ -2491,8 +4839,41 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         KeyCode::Char('t') => {
             app.toggle_tools();
         }
+        KeyCode::Char('a') => {
+            app.toggle_threads();
+        }
+        KeyCode::Char('z') => {
+            app.toggle_compactions();
+        }
+        KeyCode::Char('L') => {
+            app.toggle_lexical_only();
+        }
+        KeyCode::Char('A') => {
+            app.toggle_all_terms();
+        }
+        KeyCode::Char('T') => {
+            app.toggle_translate();
+        }
+        KeyCode::Char('~') => {
+            app.toggle_debug_overlay();
+        }
+        KeyCode::Char(ch @ '1'..='9') => {
+            app.open_related_session(ch as usize - '1' as usize);
+        }
         KeyCode::Char('r') => {
-            let _ = app.resume_selected(terminal);
+            app.resume_selected();
+        }
+        KeyCode::Char('n') => {
+            app.note_selected();
+        }
+        KeyCode::Char('u') => {
+            app.cycle_outcome_selected();
+        }
+        KeyCode::Char('P') => {
+            app.replay_selected();
+        }
+        KeyCode::Char('H') => {
+            app.toggle_project_history();
         }
         KeyCode::Char('/') => {
             if matches!(app.focus, Focus::Preview) {
@@ -2505,10 +4886,7 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
             }
         }
         KeyCode::Char('p') => {
-            app.focus = Focus::Project;
-            if app.all_projects.is_empty() || app.project_source != app.source {
-                app.kickoff_project_load();
-            }
+            app.focus_project_filter();
         }
         KeyCode::Char('f') => {
             app.focus = Focus::Find;
@@ -2521,12 +4899,174 @@ fn handle_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Resul
         KeyCode::Char('S') => {
             let _ = app.share_selected();
         }
+        KeyCode::Char('e') => {
+            app.explain_selected();
+        }
+        KeyCode::Char('c') => {
+            if matches!(app.focus, Focus::Preview) {
+                app.toggle_collected();
+            } else {
+                app.set_status("focus the preview to collect messages");
+            }
+        }
+        KeyCode::Char('C') => {
+            app.export_basket();
+        }
+        KeyCode::Char('R') => {
+            app.reload_config();
+        }
         _ => {}
     }
     Ok(false)
 }
 
-fn handle_home_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) -> Result<bool> {
+fn handle_resume_prompt_key(
+    key: KeyEvent,
+    terminal: &mut TuiTerminal,
+    app: &mut App,
+) -> Result<()> {
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        if key.code == KeyCode::Char('s') {
+            app.save_resume_override();
+        }
+        return Ok(());
+    }
+    let Some(prompt) = app.resume_prompt.as_mut() else {
+        return Ok(());
+    };
+    match key.code {
+        KeyCode::Esc => {
+            app.resume_prompt = None;
+            app.set_status("resume cancelled");
+        }
+        KeyCode::Enter => {
+            app.confirm_resume(terminal)?;
+        }
+        code => {
+            prompt.command.handle_key(code);
+        }
+    }
+    Ok(())
+}
+
+fn handle_replay_key(key: KeyEvent, app: &mut App) {
+    let Some(replay) = app.replay.as_mut() else {
+        return;
+    };
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.replay = None;
+        }
+        KeyCode::Char(' ') | KeyCode::Right | KeyCode::Char('l') => {
+            replay.step(1);
+        }
+        KeyCode::Left | KeyCode::Char('h') | KeyCode::Backspace => {
+            replay.step(-1);
+        }
+        KeyCode::Home | KeyCode::Char('g') => {
+            replay.step(-(replay.records.len() as isize));
+        }
+        KeyCode::End | KeyCode::Char('G') => {
+            replay.step(replay.records.len() as isize);
+        }
+        KeyCode::Char('a') => {
+            replay.toggle_autoplay();
+        }
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            replay.change_speed(true);
+        }
+        KeyCode::Char('-') => {
+            replay.change_speed(false);
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            replay.scroll_back += 1;
+        }
+        KeyCode::Down | KeyCode::Char('j') => {
+            replay.scroll_back = replay.scroll_back.saturating_sub(1);
+        }
+        KeyCode::PageUp => {
+            replay.scroll_back += 8;
+        }
+        KeyCode::PageDown => {
+            replay.scroll_back = replay.scroll_back.saturating_sub(8);
+        }
+        _ => {}
+    }
+}
+
+fn handle_note_prompt_key(key: KeyEvent, app: &mut App) {
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        return;
+    }
+    let Some(prompt) = app.note_prompt.as_mut() else {
+        return;
+    };
+    match key.code {
+        KeyCode::Esc => {
+            app.note_prompt = None;
+            app.set_status("note cancelled");
+        }
+        KeyCode::Enter => app.save_note(),
+        code => {
+            prompt.text.handle_key(code);
+        }
+    }
+}
+
+/// Edits the palette's filter and moves through its matches, returning the
+/// action chosen with enter.
+fn handle_palette_key(key: KeyEvent, app: &mut App) -> Option<PaletteAction> {
+    let palette = app.palette.as_mut()?;
+    let count = palette.matches().len();
+    match key.code {
+        KeyCode::Esc => app.palette = None,
+        KeyCode::Enter => {
+            let action = palette
+                .matches()
+                .get(palette.selected)
+                .map(|(action, _, _)| *action);
+            app.palette = None;
+            return action;
+        }
+        KeyCode::Up => palette.selected = palette.selected.saturating_sub(1),
+        KeyCode::Down => palette.selected = (palette.selected + 1).min(count.saturating_sub(1)),
+        KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            palette.selected = palette.selected.saturating_sub(1);
+        }
+        KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            palette.selected = (palette.selected + 1).min(count.saturating_sub(1));
+        }
+        _ if key.modifiers.contains(KeyModifiers::CONTROL) => {}
+        code => {
+            if palette.filter.handle_key(code) {
+                palette.selected = 0;
+            }
+        }
+    }
+    None
+}
+
+/// Opens config.toml in `$VISUAL` or `$EDITOR` (vi without either) and
+/// reloads it once the editor exits.
+fn edit_config(app: &mut App, terminal: &mut TuiTerminal) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let path = app.paths.config.to_string_lossy().to_string();
+    app.restore_stdio()?;
+    exit_terminal(terminal)?;
+    let status = shell_command(&format!("{editor} {}", shell_quote(&path))).status();
+    *terminal = enter_terminal()?;
+    app.suppress_stdio()?;
+    match status {
+        Ok(status) if status.success() => app.reload_config(),
+        Ok(status) => app.set_status(format!("{editor} exited with {status}")),
+        Err(err) => app.set_status(format!("could not run {editor}: {err}")),
+    }
+    Ok(())
+}
+
+fn handle_home_key(key: KeyEvent, app: &mut App) -> Result<bool> {
     if app.home_dropdown != HomeDropdown::None {
         match key.code {
             KeyCode::Esc => {
@@ -2564,10 +5104,10 @@ fn handle_home_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) ->
         match key.code {
             KeyCode::Esc if !app.query.is_empty() => {
                 app.query.clear();
-                app.schedule_home_search();
+                app.schedule_search();
             }
             KeyCode::Enter => {
-                app.flush_home_search();
+                app.flush_search();
                 if !app.query.trim().is_empty() {
                     app.enter_browse();
                 } else {
@@ -2581,11 +5121,11 @@ fn handle_home_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) ->
                 app.enter_browse();
             }
             KeyCode::Backspace if app.query.pop().is_some() => {
-                app.schedule_home_search();
+                app.schedule_search();
             }
             KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
                 app.query.push(ch);
-                app.schedule_home_search();
+                app.schedule_search();
             }
             _ => {}
         }
@@ -2610,7 +5150,7 @@ fn handle_home_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) ->
             app.move_selection(-8);
         }
         KeyCode::Enter | KeyCode::Char('r') => {
-            let _ = app.resume_selected(terminal);
+            app.resume_selected();
         }
         KeyCode::Tab | KeyCode::BackTab | KeyCode::Char('l') => {
             app.enter_browse();
@@ -2633,21 +5173,79 @@ fn handle_home_key(key: KeyEvent, terminal: &mut TuiTerminal, app: &mut App) ->
         KeyCode::Char('S') => {
             let _ = app.share_selected();
         }
+        KeyCode::Char('R') => {
+            app.reload_config();
+        }
         _ => {}
     }
     Ok(false)
 }
 
+/// Text on screen that links to `url`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ScreenLink {
+    x: u16,
+    y: u16,
+    text: String,
+    url: String,
+}
+
+fn draw_frame(terminal: &mut TuiTerminal, app: &mut App) -> Result<()> {
+    terminal.draw(|frame| draw_ui(frame, app))?;
+    if !app.footer_links.is_empty() && terminal_allows(&app.config) {
+        write_screen_links(terminal, &app.footer_links)?;
+    }
+    Ok(())
+}
+
+/// ratatui counts the bytes of an OSC 8 escape as visible width, so links
+/// are written straight to the terminal after the frame, over the same
+/// text in the default colors the footer labels use.
+fn write_screen_links(terminal: &mut TuiTerminal, links: &[ScreenLink]) -> Result<()> {
+    use crossterm::cursor::{MoveTo, RestorePosition, SavePosition};
+    use crossterm::style::{Print, ResetColor};
+    let backend = terminal.backend_mut();
+    crossterm::queue!(backend, SavePosition)?;
+    for link in links {
+        crossterm::queue!(
+            backend,
+            MoveTo(link.x, link.y),
+            ResetColor,
+            Print(hyperlink(&link.url, &link.text))
+        )?;
+    }
+    crossterm::queue!(backend, RestorePosition)?;
+    backend.flush()?;
+    Ok(())
+}
+
 fn draw_ui(frame: &mut ratatui::Frame, app: &mut App) {
     let theme = Theme::new();
     frame.render_widget(Block::default().style(theme.base), frame.area());
-    let area = inset(
+    let mut area = inset(
         frame.area(),
         OUTER_PAD_X,
         OUTER_PAD_X,
         OUTER_PAD_Y,
         OUTER_PAD_Y,
     );
+    if !app.health.is_empty() && area.height > HEALTH_BANNER_HEIGHT + 5 {
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(HEALTH_BANNER_HEIGHT), Constraint::Min(5)])
+            .split(area);
+        draw_health_banner(frame, app, &theme, split[0]);
+        area = split[1];
+    }
+    app.pinned_areas.clear();
+    if !app.pinned.queries.is_empty() && area.height > PINNED_BAR_HEIGHT + 5 {
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(PINNED_BAR_HEIGHT), Constraint::Min(5)])
+            .split(area);
+        draw_pinned_bar(frame, app, &theme, split[0]);
+        area = split[1];
+    }
 
     if app.layout_mode == LayoutMode::Home {
         let root = Layout::default()
@@ -2657,10 +5255,15 @@ fn draw_ui(frame: &mut ratatui::Frame, app: &mut App) {
         app.body_area = root[0];
         app.querybar_area = Rect::default();
         draw_home(frame, app, &theme, root[0]);
-        draw_footer(frame, app, &theme, root[1]);
+        app.footer_links = draw_footer(frame, app, &theme, root[1]);
         if app.quick_popup {
             draw_quick_popup(frame, app, &theme, app.body_area);
         }
+        draw_replay(frame, app, &theme, app.body_area);
+        draw_resume_prompt(frame, app, &theme, app.body_area);
+        draw_note_prompt(frame, app, &theme, app.body_area);
+        draw_palette(frame, app, &theme, app.body_area);
+        draw_debug_overlay(frame, app, &theme, app.body_area);
         return;
     }
 
@@ -2685,10 +5288,73 @@ fn draw_ui(frame: &mut ratatui::Frame, app: &mut App) {
     if editing {
         draw_query_bar(frame, app, &theme, root[1]);
     }
-    draw_footer(frame, app, &theme, root[2]);
+    app.footer_links = draw_footer(frame, app, &theme, root[2]);
     if app.quick_popup {
         draw_quick_popup(frame, app, &theme, app.body_area);
     }
+    draw_replay(frame, app, &theme, app.body_area);
+    draw_resume_prompt(frame, app, &theme, app.body_area);
+    draw_note_prompt(frame, app, &theme, app.body_area);
+    draw_palette(frame, app, &theme, app.body_area);
+    draw_debug_overlay(frame, app, &theme, app.body_area);
+}
+
+/// Pinned queries with their counts; the one being searched is highlighted.
+fn draw_pinned_bar(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, area: Rect) {
+    let mut spans = vec![Span::styled(" pinned ", theme.muted)];
+    let mut x = area.x + spans[0].width() as u16;
+    let mut areas = Vec::with_capacity(app.pinned.queries.len());
+    for (idx, query) in app.pinned.queries.iter().enumerate() {
+        let count = match app.pinned_counts.get(idx).copied().flatten() {
+            Some(count) => format_count(count as u64),
+            None => "…".to_string(),
+        };
+        let active = query == app.query.trim();
+        let label = Span::styled(
+            format!(" {query} "),
+            if active { theme.selection } else { theme.text },
+        );
+        let count = Span::styled(format!("{count} "), theme.accent);
+        let width = (label.width() + count.width()) as u16;
+        areas.push(Rect {
+            x,
+            y: area.y,
+            width: width.min(area.right().saturating_sub(x)),
+            height: 1,
+        });
+        x = x.saturating_add(width + 1);
+        spans.extend([label, count, Span::raw(" ")]);
+    }
+    spans.push(Span::styled(" b next  B pin", theme.muted));
+    app.pinned_areas = areas;
+    frame.render_widget(
+        Paragraph::new(Line::from(spans)).style(theme.panel_alt),
+        area,
+    );
+}
+
+/// The first unresolved health issue, its fix and how to dismiss it.
+fn draw_health_banner(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect) {
+    let Some(issue) = app.health.first() else {
+        return;
+    };
+    let mut spans = vec![
+        Span::styled(" ⚠ ", theme.focus),
+        Span::styled(issue.problem.as_str(), theme.text),
+        Span::styled("  fix: ", theme.muted),
+        Span::styled(issue.fix.as_str(), theme.accent),
+    ];
+    if app.health.len() > 1 {
+        spans.push(Span::styled(
+            format!("  (1 of {})", app.health.len()),
+            theme.muted,
+        ));
+    }
+    spans.push(Span::styled("  ctrl-x dismiss", theme.muted));
+    frame.render_widget(
+        Paragraph::new(Line::from(spans)).style(theme.panel_alt),
+        area,
+    );
 }
 
 fn home_column_width(area_width: u16) -> u16 {
@@ -2736,7 +5402,7 @@ fn draw_home(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, area: Rec
     let filtered_chart =
         app.home_chart_mode == HomeChartMode::Sessions && app.home_chart_is_filtered();
     let chart_activity = app.home_chart_activity();
-    let now = now_ms();
+    let now = now_millis();
     let bounds = home_activity_bounds_at(chart_activity, app.home_activity_range, now);
     let plotted_count = activity_count_in_bounds(chart_activity, bounds);
     let total_count = chart_activity.len();
@@ -2908,8 +5574,8 @@ fn draw_home(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, area: Rec
             truncate_end(&app.project, 16)
         }
     );
-    let source_width = source_word.chars().count() as u16;
-    let project_width_hdr = project_word.chars().count() as u16;
+    let source_width = text::width(&source_word) as u16;
+    let project_width_hdr = text::width(&project_word) as u16;
     let header_cols = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
@@ -2971,17 +5637,24 @@ fn draw_home(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, area: Rec
         return;
     }
 
-    let (project_width, detail_width) = session_row_layout(&app.results, col_width as usize);
+    let (project_width, detail_width) = session_row_layout(
+        &app.results,
+        &app.project_aliases,
+        col_width as usize,
+        app.session_columns,
+    );
     let terms = query_terms(&app.query);
-    let items: Vec<ListItem> = app
-        .results
+    let window = list_window(&app.selected, app.results.len(), list_area.height as usize);
+    let items: Vec<ListItem> = app.results[window.clone()]
         .iter()
         .map(|session| {
             ListItem::new(session_result_line(
                 session,
+                &app.project_aliases,
                 &terms,
                 project_width,
                 detail_width,
+                app.session_columns,
                 theme,
             ))
         })
@@ -2995,7 +5668,7 @@ fn draw_home(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, area: Rec
         .style(theme.text)
         .highlight_style(highlight)
         .highlight_symbol("");
-    frame.render_stateful_widget(list, list_area, &mut app.selected);
+    render_list_window(frame, list, list_area, &mut app.selected, window);
 
     draw_home_dropdown(frame, app, theme, area);
 }
@@ -3019,7 +5692,7 @@ fn draw_home_dropdown(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme,
     }
     let width = options
         .iter()
-        .map(|o| o.chars().count())
+        .map(|o| text::width(o))
         .max()
         .unwrap_or(8)
         .clamp(8, 32) as u16
@@ -3374,19 +6047,10 @@ fn timeline_chart_row_line(cells: &[(char, Color)], selected: bool) -> Line<'sta
 }
 
 fn query_terms(query: &str) -> Vec<Vec<char>> {
-    let mut seen = HashSet::new();
-    let mut terms = Vec::new();
-    for part in query.split_whitespace() {
-        let cleaned = part.trim_matches(|c: char| !c.is_alphanumeric());
-        if cleaned.chars().count() < 2 {
-            continue;
-        }
-        let key = cleaned.to_lowercase();
-        if seen.insert(key.clone()) {
-            terms.push(key.chars().collect());
-        }
-    }
-    terms
+    text::query_terms(query)
+        .iter()
+        .map(|term| term.chars().collect())
+        .collect()
 }
 
 fn find_term(hay: &[char], term: &[char], from: usize) -> Option<usize> {
@@ -3421,8 +6085,21 @@ fn match_context_spans(
     let Some(first) = first else {
         return vec![Span::styled(truncate_end(text, width), theme.muted)];
     };
-    let start = first.saturating_sub(width / 3);
-    let end = (start + width).min(chars.len());
+    // The window is measured in columns so wide glyphs don't overflow it:
+    // about a third of it before the first match, the rest after.
+    let columns = |ch: &char| ch.width().unwrap_or(0);
+    let mut start = first;
+    let mut lead = width / 3;
+    while start > 0 && columns(&chars[start - 1]) <= lead {
+        lead -= columns(&chars[start - 1]);
+        start -= 1;
+    }
+    let mut end = start;
+    let mut used = 0usize;
+    while end < chars.len() && used + columns(&chars[end]) <= width {
+        used += columns(&chars[end]);
+        end += 1;
+    }
 
     let mut spans = Vec::new();
     if start > 0 {
@@ -3477,6 +6154,7 @@ fn source_choice_matches_storage_label(choice: SourceChoice, label: &str) -> boo
         SourceChoice::Cursor => label == "cursor",
         SourceChoice::Pi => label == "pi",
         SourceChoice::Copilot => label == "copilot",
+        SourceChoice::ChatGPT => label == "chatgpt",
         SourceChoice::All => false,
     }
 }
@@ -3489,47 +6167,75 @@ fn source_color(source: SourceKind) -> Color {
         SourceKind::Cursor => Color::Rgb(170, 150, 200),
         SourceKind::Pi => Color::Rgb(120, 190, 190),
         SourceKind::Copilot => Color::Rgb(140, 160, 220),
+        SourceKind::ChatGPT => Color::Rgb(120, 200, 160),
+        SourceKind::External(_) => Color::Rgb(180, 180, 180),
     }
 }
 
 /// Widest project name among the visible results, clamped so one long name
 /// can't push the detail column off screen.
-fn results_project_width(results: &[SessionSummary]) -> usize {
+fn results_project_width(results: &[SessionSummary], aliases: &ProjectAliases) -> usize {
     results
         .iter()
         .take(60)
-        .map(|session| session.project.chars().count())
+        .map(|session| text::width(&aliases.shown(&session.project)))
         .max()
         .unwrap_or(8)
         .clamp(6, 24)
 }
 
+/// Width of the message count column ("1.2K msg").
+const MESSAGES_COL: usize = 8;
+/// Width of the duration column ("45m").
+const DURATION_COL: usize = 4;
+/// Width of the topic column; longer labels are cut.
+const TOPIC_COL: usize = 16;
+
 /// Columns consumed by everything before the detail text in a session row:
-/// relative time, source dot + label, project column, and the gaps between.
-fn session_row_fixed_cols(project_width: usize) -> usize {
-    4 + 2 + 2 + 9 + project_width + 2
+/// relative time, source dot + label, project column, the optional message
+/// count, duration and topic columns, and the gaps between.
+fn session_row_fixed_cols(project_width: usize, columns: SessionListColumns) -> usize {
+    let mut cols = 4 + 2 + 2 + 9 + project_width + 2;
+    if columns.messages {
+        cols += MESSAGES_COL + 2;
+    }
+    if columns.duration {
+        cols += DURATION_COL + 2;
+    }
+    if columns.topic {
+        cols += TOPIC_COL + 2;
+    }
+    cols
 }
 
 /// Splits a row of `total_width` cells into (project_width, detail_width):
 /// the project column takes its natural width, shrinking on narrow rows so
 /// the match-context detail keeps a readable minimum.
-fn session_row_layout(results: &[SessionSummary], total_width: usize) -> (usize, usize) {
+fn session_row_layout(
+    results: &[SessionSummary],
+    aliases: &ProjectAliases,
+    total_width: usize,
+    columns: SessionListColumns,
+) -> (usize, usize) {
     const MIN_DETAIL: usize = 16;
-    let project_width = results_project_width(results)
-        .min(total_width.saturating_sub(session_row_fixed_cols(0) + MIN_DETAIL))
+    let project_width = results_project_width(results, aliases)
+        .min(total_width.saturating_sub(session_row_fixed_cols(0, columns) + MIN_DETAIL))
         .max(8);
-    let detail_width = total_width.saturating_sub(session_row_fixed_cols(project_width));
+    let detail_width = total_width.saturating_sub(session_row_fixed_cols(project_width, columns));
     (project_width, detail_width)
 }
 
 /// One session as a mini search result — the home-screen list row, shared by
 /// the browse Sessions panel: time, source, project, then the match context
-/// (or the session id when there's no snippet to show).
+/// (or the session title, falling back to the id, when there's no snippet).
+/// Message count and duration columns are blank when unknown.
 fn session_result_line(
     session: &SessionSummary,
+    aliases: &ProjectAliases,
     terms: &[Vec<char>],
     project_width: usize,
     detail_width: usize,
+    columns: SessionListColumns,
     theme: &Theme,
 ) -> Line<'static> {
     let ts = format_relative_ts(session.last_ts);
@@ -3541,16 +6247,51 @@ fn session_result_line(
         Span::styled(format!("{:<8}", session.source.label()), theme.muted),
         Span::raw(" "),
         Span::styled(
-            format!(
-                "{:<width$}",
-                truncate_middle(&session.project, project_width),
-                width = project_width
+            pad_end(
+                &truncate_middle(&aliases.shown(&session.project), project_width),
+                project_width,
             ),
             theme.text,
         ),
         Span::raw("  "),
     ];
-    if session.snippet.is_empty() {
+    if columns.messages {
+        let messages = if session.message_count == 0 {
+            String::new()
+        } else {
+            format!("{} msg", compact_metric(session.message_count as u64))
+        };
+        spans.push(Span::styled(
+            format!("{messages:>MESSAGES_COL$}"),
+            theme.muted,
+        ));
+        spans.push(Span::raw("  "));
+    }
+    if columns.duration {
+        let duration = if session.duration_ms == 0 {
+            String::new()
+        } else {
+            format_duration_compact(session.duration_ms)
+        };
+        spans.push(Span::styled(
+            format!("{duration:>DURATION_COL$}"),
+            theme.muted,
+        ));
+        spans.push(Span::raw("  "));
+    }
+    if columns.topic {
+        spans.push(Span::styled(
+            pad_end(&truncate_end(&session.topic, TOPIC_COL), TOPIC_COL),
+            theme.accent,
+        ));
+        spans.push(Span::raw("  "));
+    }
+    if session.snippet.is_empty() && !session.title.is_empty() {
+        spans.push(Span::styled(
+            truncate_end(&strip_ansi_and_controls(&session.title), detail_width),
+            theme.text,
+        ));
+    } else if session.snippet.is_empty() {
         spans.push(Span::styled(
             truncate_middle(&session.session_id, detail_width),
             theme.muted,
@@ -3562,21 +6303,6 @@ fn session_result_line(
     Line::from(spans)
 }
 
-fn truncate_end(value: &str, width: usize) -> String {
-    if width == 0 {
-        return String::new();
-    }
-    if value.chars().count() <= width {
-        return value.to_string();
-    }
-    if width <= 1 {
-        return "…".to_string();
-    }
-    let mut out: String = value.chars().take(width - 1).collect();
-    out.push('…');
-    out
-}
-
 fn draw_query_bar(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect) {
     frame.render_widget(Block::default().style(theme.panel), area);
     let inner = inset(area, PANEL_PAD_X, PANEL_PAD_X, 0, 0);
@@ -3683,6 +6409,15 @@ fn draw_body(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, area: Rec
         return;
     }
 
+    if app.layout_mode == LayoutMode::Heatmap {
+        app.preview_area = Rect::default();
+        app.project_area = None;
+        app.list_area = Rect::default();
+        app.dragging = false;
+        draw_heatmap(frame, app, theme, area);
+        return;
+    }
+
     let min_left = 20u16;
     let min_right = 24u16;
     let total = area.width.max(min_left + min_right + SPLIT_GAP);
@@ -3766,6 +6501,7 @@ fn draw_sessions_panel(
     let title = Paragraph::new(Line::from(title_spans));
     frame.render_widget(title, header);
 
+    let window = list_window(&app.selected, app.results.len(), content.height as usize);
     let list_items: Vec<ListItem> = if app.results.is_empty() {
         let message = match &app.sessions_state {
             LoadState::Loading | LoadState::Empty if app.index_state == IndexState::Loading => {
@@ -3784,6 +6520,11 @@ fn draw_sessions_panel(
                 {
                     "No conversations indexed · press i to index".to_string()
                 }
+                IndexState::ReadOnly(message)
+                    if app.query.trim().is_empty() && app.project.trim().is_empty() =>
+                {
+                    format!("Read-only: {message} · results appear as it commits")
+                }
                 _ => "No conversations found".to_string(),
             },
         };
@@ -3793,17 +6534,23 @@ fn draw_sessions_panel(
         )))]
     } else {
         // Same mini-search-result rows as the home screen list.
-        let (project_width, detail_width) =
-            session_row_layout(&app.results, content.width as usize);
+        let (project_width, detail_width) = session_row_layout(
+            &app.results,
+            &app.project_aliases,
+            content.width as usize,
+            app.session_columns,
+        );
         let terms = query_terms(&app.query);
-        app.results
+        app.results[window.clone()]
             .iter()
             .map(|session| {
                 ListItem::new(session_result_line(
                     session,
+                    &app.project_aliases,
                     &terms,
                     project_width,
                     detail_width,
+                    app.session_columns,
                     theme,
                 ))
             })
@@ -3815,10 +6562,45 @@ fn draw_sessions_panel(
         .highlight_style(theme.selection)
         .highlight_symbol("");
 
-    frame.render_stateful_widget(list, content, &mut app.selected);
+    render_list_window(frame, list, content, &mut app.selected, window);
     content
 }
 
+/// Rows of a one-line-per-item list that fit in `height`, scrolled only as
+/// far as needed to keep the selection visible and never past the last full
+/// page. Lets the session lists build items for visible rows only.
+fn list_window(state: &ListState, len: usize, height: usize) -> std::ops::Range<usize> {
+    let height = height.max(1);
+    let last = len.saturating_sub(1);
+    let mut offset = state.offset().min(len.saturating_sub(height));
+    if let Some(selected) = state.selected().map(|selected| selected.min(last)) {
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + height {
+            offset = selected + 1 - height;
+        }
+    }
+    offset..(offset + height).min(len)
+}
+
+/// Renders `list`, built from the rows in `window`, and records the window
+/// start as `state`'s offset so mouse hit-testing keeps working.
+fn render_list_window(
+    frame: &mut ratatui::Frame,
+    list: List<'_>,
+    area: Rect,
+    state: &mut ListState,
+    window: std::ops::Range<usize>,
+) {
+    let mut visible = ListState::default().with_selected(
+        state
+            .selected()
+            .map(|selected| selected.saturating_sub(window.start)),
+    );
+    frame.render_stateful_widget(list, area, &mut visible);
+    *state.offset_mut() = window.start;
+}
+
 fn draw_project_timeline(
     frame: &mut ratatui::Frame,
     app: &mut App,
@@ -4070,8 +6852,10 @@ fn draw_preview_panel(
         height: inner.height.saturating_sub(PANEL_TITLE_HEIGHT),
     };
     let detail_title = match app.preview_mode {
+        _ if app.split_preview_active() => "Preview · Matches + History",
         PreviewMode::Matches => "Preview · Matches",
         PreviewMode::History => "Preview · History",
+        PreviewMode::Project => "Preview · Project history",
     };
     let title_style = if matches!(app.focus, Focus::Preview | Focus::Find) {
         theme.focus
@@ -4102,6 +6886,14 @@ fn draw_preview_panel(
         );
         return content;
     }
+    let query = app.detail_query();
+    let content = if app.split_preview_active() && !query.is_empty() {
+        let (matches_area, history_area) = split_preview_areas(content, app.preview_matches.len());
+        draw_preview_matches(frame, app, theme, &query, matches_area);
+        history_area
+    } else {
+        content
+    };
     let view_height = content.height as usize;
     let start = app.detail_scroll.min(app.detail_lines.len());
     let end = if view_height == 0 {
@@ -4111,7 +6903,15 @@ fn draw_preview_panel(
     };
     let visible_lines: Vec<Line> = app.detail_lines[start..end]
         .iter()
-        .map(|line| render_preview_line(line, theme))
+        .map(|line| {
+            let mut rendered = render_preview_line(line, theme);
+            if preview_line_doc_id(line)
+                .is_some_and(|doc_id| app.basket.iter().any(|record| record.doc_id == doc_id))
+            {
+                rendered.spans.insert(0, Span::styled("● ", theme.accent));
+            }
+            rendered
+        })
         .collect();
     let detail = Paragraph::new(visible_lines)
         .style(theme.text)
@@ -4120,6 +6920,77 @@ fn draw_preview_panel(
     content
 }
 
+/// Splits the preview into the match list, with a rule under it, and the
+/// history: a row per match, up to a third of the height.
+fn split_preview_areas(area: Rect, matches: usize) -> (Rect, Rect) {
+    let rows = (matches.max(1) as u16).min((area.height / 3).max(1));
+    let top = (rows + 1).min(area.height);
+    let matches_area = Rect {
+        height: top,
+        ..area
+    };
+    let history_area = Rect {
+        y: area.y.saturating_add(top),
+        height: area.height - top,
+        ..area
+    };
+    (matches_area, history_area)
+}
+
+/// The split preview's upper pane: each matching message with the text
+/// around its first hit, the selected one highlighted.
+fn draw_preview_matches(
+    frame: &mut ratatui::Frame,
+    app: &mut App,
+    theme: &Theme,
+    query: &str,
+    area: Rect,
+) {
+    if area.height == 0 {
+        return;
+    }
+    let rows = area.height.saturating_sub(1).max(1) as usize;
+    let total = app.preview_matches.len();
+    let mut lines: Vec<Line> = Vec::new();
+    if total == 0 {
+        let message = if app.detail_state == LoadState::Loaded {
+            "no literal matches in this history"
+        } else {
+            ""
+        };
+        lines.push(Line::from(Span::styled(message, theme.muted)));
+    } else {
+        let terms = query_terms(query);
+        let window = list_window(&app.preview_match_state, total, rows);
+        *app.preview_match_state.offset_mut() = window.start;
+        let selected = app.preview_match_state.selected();
+        for (idx, found) in app.preview_matches[window.clone()].iter().enumerate() {
+            let is_selected = selected == Some(window.start + idx);
+            let marker = if is_selected { "\u{25b8} " } else { "  " };
+            let label = Span::styled(format!("{} {} ", found.ts, found.role), theme.muted);
+            let width = (area.width as usize).saturating_sub(label.width() + 2);
+            let mut spans = vec![Span::styled(marker, theme.accent), label];
+            spans.extend(match_context_spans(&found.text, &terms, width, theme));
+            let line = Line::from(spans);
+            lines.push(if is_selected {
+                line.style(theme.selection)
+            } else {
+                line
+            });
+        }
+    }
+    let rule = match app.preview_match_state.selected() {
+        Some(selected) if total > 0 => format!("match {} of {total} · j/k jump", selected + 1),
+        _ => "history".to_string(),
+    };
+    let fill = (area.width as usize).saturating_sub(rule.chars().count() + 4);
+    lines.push(Line::from(Span::styled(
+        format!("── {rule} {}", "─".repeat(fill)),
+        theme.muted,
+    )));
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
 fn draw_quick_popup(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, area: Rect) -> Rect {
     let popup = quick_popup_area(area);
     frame.render_widget(Clear, popup);
@@ -4163,41 +7034,316 @@ fn draw_quick_popup(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, ar
     content
 }
 
-fn draw_footer(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect) {
-    frame.render_widget(Block::default().style(theme.panel), area);
-    let inner = inset(area, PANEL_PAD_X, PANEL_PAD_X, 0, 0);
-
-    let mode = match app.preview_mode {
-        PreviewMode::Matches => "matches",
-        PreviewMode::History => "history",
+/// The replay view covers the whole body; the newest revealed message sits at
+/// the bottom unless scrolled back.
+fn draw_replay(frame: &mut ratatui::Frame, app: &mut App, theme: &Theme, area: Rect) {
+    let max_chars = app.config.preview_message_chars();
+    let Some(replay) = app.replay.as_mut() else {
+        return;
     };
-    let view = match app.layout_mode {
-        LayoutMode::Home => "home",
-        LayoutMode::Split => "split",
-        LayoutMode::List => "list",
-        LayoutMode::Timeline => "timeline",
-        LayoutMode::Detail => "detail",
+    frame.render_widget(Clear, area);
+    frame.render_widget(Block::default().style(theme.panel_alt), area);
+    let inner = panel_inner(area);
+    let header = Rect {
+        height: PANEL_TITLE_HEIGHT.min(inner.height),
+        ..inner
     };
-    let mut right_spans = Vec::new();
-    if !app.status.is_empty() {
-        right_spans.push(Span::styled("\u{25cf} ", theme.accent));
-        right_spans.push(Span::styled(app.status.as_str(), theme.text));
-        right_spans.push(Span::raw("   "));
-    }
-    if app.timeline_state == LoadState::Loading
-        && app.layout_mode == LayoutMode::Timeline
-        && !app.timeline_rows.is_empty()
-    {
-        right_spans.push(Span::styled(
-            format!("{} loading timeline", app.spinner()),
-            theme.accent,
-        ));
-        right_spans.push(Span::raw("   "));
-    }
-    if let LoadState::Error(message) = &app.timeline_state
-        && app.layout_mode == LayoutMode::Timeline
-        && !app.timeline_rows.is_empty()
-    {
+    let content = Rect {
+        y: inner.y.saturating_add(PANEL_TITLE_HEIGHT),
+        height: inner.height.saturating_sub(PANEL_TITLE_HEIGHT),
+        ..inner
+    };
+
+    let playback = if replay.at_end() {
+        "end".to_string()
+    } else if replay.autoplay {
+        format!("playing every {:.1}s", replay.interval.as_secs_f32())
+    } else {
+        format!("paused ({:.1}s)", replay.interval.as_secs_f32())
+    };
+    let title = Line::from(vec![
+        Span::styled("Replay ", theme.text_bold),
+        Span::styled(
+            format!("{} · {}  ", replay.project, replay.session_id),
+            theme.muted,
+        ),
+        Span::styled(replay.position(), theme.accent),
+        Span::styled(format!("  {playback}"), theme.muted),
+    ]);
+    frame.render_widget(Paragraph::new(title), header);
+
+    let preview = replay.lines(max_chars);
+    let lines: Vec<Line> = preview
+        .iter()
+        .map(|line| render_preview_line(line, theme))
+        .collect();
+    replay.scroll_back = replay.scroll_back.min(lines.len().saturating_sub(1));
+    let end = lines.len() - replay.scroll_back;
+    // Walk back from `end` while the wrapped lines still fit, always keeping
+    // the newest one.
+    let width = usize::from(content.width.max(1));
+    let mut rows = 0usize;
+    let mut start = end;
+    while start > 0 {
+        let line_rows = lines[start - 1].width().div_ceil(width).max(1);
+        if start < end && rows + line_rows > usize::from(content.height) {
+            break;
+        }
+        rows += line_rows;
+        start -= 1;
+    }
+    let visible: Vec<Line> = lines.into_iter().take(end).skip(start).collect();
+    frame.render_widget(
+        Paragraph::new(visible)
+            .style(theme.text)
+            .wrap(Wrap { trim: true }),
+        content,
+    );
+}
+
+fn draw_resume_prompt(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect) {
+    let Some(prompt) = app.resume_prompt.as_ref() else {
+        return;
+    };
+    let popup = resume_prompt_area(area);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Block::default().style(theme.panel_alt), popup);
+    let inner = panel_inner(popup);
+
+    let label = resume_source_label(prompt.session.source);
+    let lines = vec![
+        Line::from(vec![
+            Span::styled(format!("Resume {label} session"), theme.text_bold),
+            Span::styled(
+                format!("  enter run  ctrl-s save for {label}  esc cancel"),
+                theme.muted,
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled("cwd ", theme.muted),
+            Span::styled(prompt.cwd.clone(), theme.text),
+        ]),
+        Line::from(""),
+        prompt.command.line(theme),
+    ];
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
+}
+
+fn draw_note_prompt(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect) {
+    let Some(prompt) = app.note_prompt.as_ref() else {
+        return;
+    };
+    let popup = resume_prompt_area(area);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Block::default().style(theme.panel_alt), popup);
+    let inner = panel_inner(popup);
+    let lines = vec![
+        Line::from(vec![
+            Span::styled("Note", theme.text_bold),
+            Span::styled("  enter save (empty clears)  esc cancel", theme.muted),
+        ]),
+        Line::from(vec![
+            Span::styled("session ", theme.muted),
+            Span::styled(prompt.session_id.clone(), theme.text),
+        ]),
+        Line::from(""),
+        prompt.text.line(theme),
+    ];
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
+}
+
+/// The Ctrl-P palette: its filter over the actions it matches, each with
+/// the key that runs it directly.
+fn draw_palette(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect) {
+    let Some(palette) = app.palette.as_ref() else {
+        return;
+    };
+    let width = area.width.saturating_mul(3).saturating_div(5).clamp(40, 72);
+    let height = area.height.saturating_mul(3).saturating_div(5).max(8);
+    let popup = Rect {
+        x: area.x + area.width.saturating_sub(width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 3,
+        width: width.min(area.width),
+        height: height.min(area.height),
+    };
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Block::default().style(theme.panel_alt), popup);
+    let inner = panel_inner(popup);
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Commands", theme.text_bold),
+            Span::styled("  enter run  esc close", theme.muted),
+        ]),
+        palette.filter.line(theme),
+        Line::from(""),
+    ];
+    let matches = palette.matches();
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "no matching commands",
+            theme.muted,
+        )));
+    }
+    let rows = (inner.height as usize).saturating_sub(lines.len()).max(1);
+    let skip = (palette.selected + 1).saturating_sub(rows);
+    for (at, (_, label, key)) in matches.iter().enumerate().skip(skip).take(rows) {
+        let hint = if key.is_empty() {
+            String::new()
+        } else {
+            format!(" {key}")
+        };
+        let room = (inner.width as usize).saturating_sub(hint.chars().count());
+        let label = pad_end(&truncate_end(label, room), room);
+        let style = if at == palette.selected {
+            theme.selection
+        } else {
+            theme.text
+        };
+        lines.push(Line::from(vec![
+            Span::styled(label, style),
+            Span::styled(hint, theme.accent),
+        ]));
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// The `~` overlay: where the last search spent its time, and what the
+/// last index refresh did.
+fn draw_debug_overlay(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect) {
+    if !app.debug_overlay {
+        return;
+    }
+    let lines = debug_overlay_lines(app, theme);
+    let width = area.width.saturating_mul(3).saturating_div(5).clamp(40, 90);
+    let height = lines.len() as u16 + 2;
+    let popup = Rect {
+        x: area.x + area.width.saturating_sub(width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width: width.min(area.width),
+        height: height.min(area.height),
+    };
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Block::default().style(theme.panel_alt), popup);
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }),
+        panel_inner(popup),
+    );
+}
+
+fn debug_overlay_lines(app: &App, theme: &Theme) -> Vec<Line<'static>> {
+    let row = |label: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{label:<18}"), theme.muted),
+            Span::styled(value, theme.text),
+        ])
+    };
+    let ms = |duration: Duration| format!("{:.1} ms", duration.as_secs_f64() * 1000.0);
+    let mut lines = vec![Line::from(vec![
+        Span::styled("Timings", theme.text_bold),
+        Span::styled("  ~ close", theme.muted),
+    ])];
+    match &app.last_search_timings {
+        None => lines.push(row("last search", "none yet".to_string())),
+        Some(timings) => {
+            let query = if timings.query.is_empty() {
+                "(recent sessions)".to_string()
+            } else {
+                timings.query.clone()
+            };
+            lines.push(row("last search", query));
+            if timings.cached {
+                lines.push(row("cache", "hit, index not searched".to_string()));
+            } else {
+                lines.push(row("index open", ms(timings.phases.open)));
+                lines.push(row("lexical query", ms(timings.phases.query)));
+                lines.push(row("load records", ms(timings.phases.load)));
+                lines.push(row(
+                    "vector query",
+                    "not used (TUI search is lexical)".to_string(),
+                ));
+                lines.push(row("fusion", "not used".to_string()));
+                lines.push(row("aggregation", ms(timings.aggregation)));
+                lines.push(row("enrichment", ms(timings.enrichment)));
+            }
+            lines.push(row("total", ms(timings.total)));
+            lines.push(row(
+                "results",
+                format!("{} hits, {} sessions", timings.hits, timings.sessions),
+            ));
+        }
+    }
+    lines.push(Line::from(""));
+    match app.last_ingest {
+        None => lines.push(row("last ingest", "none this session".to_string())),
+        Some(ingest) => {
+            lines.push(row("last ingest", ms(ingest.elapsed)));
+            lines.push(row(
+                "ingested",
+                format!(
+                    "{} files scanned, {} records added, {} embedded",
+                    ingest.files_scanned, ingest.added, ingest.embedded
+                ),
+            ));
+            if let Some(embed) = ingest.embed {
+                lines.push(row("embedding", embed.summary()));
+            }
+        }
+    }
+    if let Some(stats) = app.debug_segments {
+        lines.push(row(
+            "index",
+            format!(
+                "{} documents in {} segments, {} deleted",
+                stats.alive_docs, stats.segments, stats.deleted_docs
+            ),
+        ));
+    }
+    lines
+}
+
+/// Draws the footer and returns its labels that link to files.
+fn draw_footer(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    theme: &Theme,
+    area: Rect,
+) -> Vec<ScreenLink> {
+    frame.render_widget(Block::default().style(theme.panel), area);
+    let inner = inset(area, PANEL_PAD_X, PANEL_PAD_X, 0, 0);
+
+    let mode = match app.preview_mode {
+        PreviewMode::Matches => "matches",
+        PreviewMode::History => "history",
+        PreviewMode::Project => "project",
+    };
+    let view = match app.layout_mode {
+        LayoutMode::Home => "home",
+        LayoutMode::Split => "split",
+        LayoutMode::List => "list",
+        LayoutMode::Timeline => "timeline",
+        LayoutMode::Heatmap => "heatmap",
+        LayoutMode::Detail => "detail",
+    };
+    let mut right_spans = Vec::new();
+    if !app.status.is_empty() {
+        right_spans.push(Span::styled("\u{25cf} ", theme.accent));
+        right_spans.push(Span::styled(app.status.as_str(), theme.text));
+        right_spans.push(Span::raw("   "));
+    }
+    if app.timeline_state == LoadState::Loading
+        && app.layout_mode == LayoutMode::Timeline
+        && !app.timeline_rows.is_empty()
+    {
+        right_spans.push(Span::styled(
+            format!("{} loading timeline", app.spinner()),
+            theme.accent,
+        ));
+        right_spans.push(Span::raw("   "));
+    }
+    if let LoadState::Error(message) = &app.timeline_state
+        && app.layout_mode == LayoutMode::Timeline
+        && !app.timeline_rows.is_empty()
+    {
         right_spans.push(Span::styled(
             format!("timeline error: {message}"),
             theme.muted,
@@ -4216,9 +7362,32 @@ fn draw_footer(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect)
         right_spans.push(Span::styled(format!("load error: {message}"), theme.muted));
         right_spans.push(Span::raw("   "));
     }
+    if let IndexState::ReadOnly(message) = &app.index_state {
+        right_spans.push(Span::styled("read-only", theme.accent));
+        right_spans.push(Span::styled(format!(" ({message})"), theme.muted));
+        right_spans.push(Span::raw("   "));
+    }
+    if !app.basket.is_empty() {
+        right_spans.push(Span::styled("basket", theme.muted));
+        right_spans.push(Span::styled("(C) ", theme.accent));
+        right_spans.push(Span::styled(app.basket.len().to_string(), theme.text));
+        right_spans.push(Span::raw("   "));
+    }
+    if app.service_failed {
+        right_spans.push(Span::styled(
+            "\u{26a0} background index failed",
+            theme.accent,
+        ));
+        right_spans.push(Span::styled(" (memex status)", theme.muted));
+        right_spans.push(Span::raw("   "));
+    }
     if app.index_state == IndexState::Loading {
+        let activity = match app.embed_status {
+            Some(status) => status.describe(),
+            None => "indexing".to_string(),
+        };
         right_spans.push(Span::styled(
-            format!("{} indexing", app.spinner()),
+            format!("{} {activity}", app.spinner()),
             theme.accent,
         ));
         right_spans.push(Span::raw("   "));
@@ -4237,6 +7406,12 @@ fn draw_footer(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect)
         right_spans.push(Span::styled(app.source.label(), theme.accent));
         right_spans.push(Span::raw("   "));
     }
+    if app.scoring_profile != DEFAULT_PROFILE && app.layout_mode != LayoutMode::Timeline {
+        right_spans.push(Span::styled("profile", theme.muted));
+        right_spans.push(Span::styled("(o) ", theme.accent));
+        right_spans.push(Span::styled(app.scoring_profile.clone(), theme.accent));
+        right_spans.push(Span::raw("   "));
+    }
     if app.layout_mode == LayoutMode::Timeline {
         right_spans.push(Span::styled("source", theme.muted));
         right_spans.push(Span::styled("(s) ", theme.accent));
@@ -4261,8 +7436,33 @@ fn draw_footer(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect)
         right_spans.push(Span::styled(app.timeline_density.label(), theme.text));
         right_spans.push(Span::raw("   "));
     }
+    if app.layout_mode == LayoutMode::Heatmap {
+        right_spans.push(Span::styled("project", theme.muted));
+        right_spans.push(Span::styled("(p) ", theme.accent));
+        right_spans.push(Span::styled(
+            if app.project.trim().is_empty() {
+                "all"
+            } else {
+                app.project.trim()
+            },
+            theme.text,
+        ));
+        right_spans.push(Span::raw("   "));
+    }
+    // Index of each linked span in `right_spans`, with its URL.
+    let mut linked_spans = Vec::new();
+    if inner.width >= FOOTER_PATHS_MIN_WIDTH {
+        for (label, path, text) in footer_paths(app) {
+            if let Some(url) = file_url(&path) {
+                right_spans.push(Span::styled(label, theme.muted));
+                linked_spans.push((right_spans.len(), url));
+                right_spans.push(Span::styled(text, theme.text));
+                right_spans.push(Span::raw("   "));
+            }
+        }
+    }
     right_spans.push(Span::styled("view", theme.muted));
-    if app.layout_mode == LayoutMode::Timeline {
+    if matches!(app.layout_mode, LayoutMode::Timeline | LayoutMode::Heatmap) {
         right_spans.push(Span::styled("(v) ", theme.accent));
     } else {
         right_spans.push(Span::raw(" "));
@@ -4278,7 +7478,10 @@ fn draw_footer(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect)
         }
         right_spans.push(Span::styled(app.home_chart_mode.label(), theme.text));
     }
-    if !matches!(app.layout_mode, LayoutMode::Timeline | LayoutMode::Home) {
+    if !matches!(
+        app.layout_mode,
+        LayoutMode::Timeline | LayoutMode::Heatmap | LayoutMode::Home
+    ) {
         right_spans.push(Span::raw("   "));
         right_spans.push(Span::styled("mode ", theme.muted));
         right_spans.push(Span::styled(mode, theme.text));
@@ -4293,11 +7496,92 @@ fn draw_footer(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect)
         .constraints([Constraint::Min(10), Constraint::Length(right_width)])
         .split(inner);
 
+    let links = screen_links(&right, &linked_spans, cols[1]);
     frame.render_widget(Paragraph::new(shortcuts), cols[0]);
     frame.render_widget(Paragraph::new(right).alignment(Alignment::Right), cols[1]);
+    links
+}
+
+/// The selected session's transcript and working directory, as
+/// (label, path, shown name), in browse views.
+fn footer_paths(app: &App) -> Vec<(&'static str, String, String)> {
+    if !matches!(
+        app.layout_mode,
+        LayoutMode::Split | LayoutMode::List | LayoutMode::Detail
+    ) {
+        return Vec::new();
+    }
+    let Some(session) = app.selected.selected().and_then(|idx| app.results.get(idx)) else {
+        return Vec::new();
+    };
+    let name = |path: &str| {
+        std::path::Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let mut paths = Vec::new();
+    if !is_note_source_path(&session.source_path) {
+        paths.push((
+            "file ",
+            session.source_path.clone(),
+            name(&session.source_path),
+        ));
+    }
+    if !session.source_dir.is_empty() {
+        paths.push((
+            "dir ",
+            session.source_dir.clone(),
+            name(&session.source_dir),
+        ));
+    }
+    paths
+}
+
+/// Where the spans at `linked` land when `line` is right-aligned in
+/// `area`.
+fn screen_links(line: &Line, linked: &[(usize, String)], area: Rect) -> Vec<ScreenLink> {
+    // An overflowing line is cut, so its spans aren't where they'd be.
+    if line.width() > area.width as usize {
+        return Vec::new();
+    }
+    let start = area.x + area.width - line.width() as u16;
+    let mut links = Vec::new();
+    for (idx, url) in linked {
+        let offset: usize = line.spans[..*idx].iter().map(|span| span.width()).sum();
+        let span = &line.spans[*idx];
+        if span.content.is_empty() {
+            continue;
+        }
+        links.push(ScreenLink {
+            x: start + offset as u16,
+            y: area.y,
+            text: span.content.to_string(),
+            url: url.clone(),
+        });
+    }
+    links
 }
 
 fn footer_shortcuts<'a>(app: &App, theme: &Theme, width: u16) -> Line<'a> {
+    if app.replay.is_some() {
+        return Line::from(vec![
+            Span::styled("space", theme.accent),
+            Span::styled(" next  ", theme.muted),
+            Span::styled("h", theme.accent),
+            Span::styled(" back  ", theme.muted),
+            Span::styled("a", theme.accent),
+            Span::styled(" autoplay  ", theme.muted),
+            Span::styled("+/-", theme.accent),
+            Span::styled(" speed  ", theme.muted),
+            Span::styled("g/G", theme.accent),
+            Span::styled(" start/end  ", theme.muted),
+            Span::styled("j/k", theme.accent),
+            Span::styled(" scroll  ", theme.muted),
+            Span::styled("esc", theme.accent),
+            Span::styled(" close", theme.muted),
+        ]);
+    }
     if app.layout_mode == LayoutMode::Home {
         if app.home_dropdown != HomeDropdown::None {
             return Line::from(vec![
@@ -4346,18 +7630,82 @@ fn footer_shortcuts<'a>(app: &App, theme: &Theme, width: u16) -> Line<'a> {
             Span::styled("h", theme.accent),
             Span::styled(" list  ", theme.muted),
             Span::styled("j/k", theme.accent),
-            Span::styled(" scroll  ", theme.muted),
+            Span::styled(
+                if app.split_preview_active() {
+                    " next match  "
+                } else {
+                    " scroll  "
+                },
+                theme.muted,
+            ),
+            Span::styled("M", theme.accent),
+            Span::styled(" two-pane  ", theme.muted),
+            Span::styled("P", theme.accent),
+            Span::styled(" replay  ", theme.muted),
+            Span::styled("H", theme.accent),
+            Span::styled(" project history  ", theme.muted),
             Span::styled("f", theme.accent),
             Span::styled(" find  ", theme.muted),
+            Span::styled("c", theme.accent),
+            Span::styled(" collect  ", theme.muted),
             Span::styled("t", theme.accent),
             Span::styled(
                 if app.show_tools {
-                    " tools:on"
+                    " tools:on  "
+                } else {
+                    " tools:off  "
+                },
+                theme.muted,
+            ),
+            Span::styled("a", theme.accent),
+            Span::styled(
+                if app.show_threads {
+                    " threads:on  "
+                } else {
+                    " threads:off  "
+                },
+                theme.muted,
+            ),
+            Span::styled("z", theme.accent),
+            Span::styled(
+                if app.expand_compactions {
+                    " summaries:open  "
+                } else {
+                    " summaries:folded  "
+                },
+                theme.muted,
+            ),
+            Span::styled("L", theme.accent),
+            Span::styled(
+                if app.lexical_only {
+                    " lexical only  "
+                } else {
+                    " embeddings  "
+                },
+                theme.muted,
+            ),
+            Span::styled("T", theme.accent),
+            Span::styled(
+                if app.translate {
+                    " translate:on  "
+                } else {
+                    " translate:off  "
+                },
+                theme.muted,
+            ),
+            Span::styled("A", theme.accent),
+            Span::styled(
+                if app.all_terms {
+                    " terms:all  "
                 } else {
-                    " tools:off"
+                    " terms:any  "
                 },
                 theme.muted,
             ),
+            Span::styled("~", theme.accent),
+            Span::styled(" timings  ", theme.muted),
+            Span::styled("^p", theme.accent),
+            Span::styled(" commands", theme.muted),
         ]);
     }
 
@@ -4382,12 +7730,22 @@ fn footer_shortcuts<'a>(app: &App, theme: &Theme, width: u16) -> Line<'a> {
                 Span::styled(" source  ", theme.muted),
                 Span::styled("m", theme.accent),
                 Span::styled(" mode  ", theme.muted),
+                Span::styled("M", theme.accent),
+                Span::styled(" two-pane  ", theme.muted),
                 Span::styled("v", theme.accent),
                 Span::styled(" list  ", theme.muted),
                 Span::styled("t", theme.accent),
                 Span::styled(tools_hint, theme.muted),
                 Span::styled("r", theme.accent),
                 Span::styled(" resume  ", theme.muted),
+                Span::styled("n", theme.accent),
+                Span::styled(" note  ", theme.muted),
+                Span::styled("u", theme.accent),
+                Span::styled(" outcome  ", theme.muted),
+                Span::styled("P", theme.accent),
+                Span::styled(" replay  ", theme.muted),
+                Span::styled("H", theme.accent),
+                Span::styled(" project history  ", theme.muted),
                 Span::styled("S", theme.accent),
                 Span::styled(" share", theme.muted),
             ]);
@@ -4405,6 +7763,19 @@ fn footer_shortcuts<'a>(app: &App, theme: &Theme, width: u16) -> Line<'a> {
         ]);
     }
 
+    if app.layout_mode == LayoutMode::Heatmap {
+        return Line::from(vec![
+            Span::styled("/", theme.accent),
+            Span::styled(" search  ", theme.muted),
+            Span::styled("p", theme.accent),
+            Span::styled(" project  ", theme.muted),
+            Span::styled("s", theme.accent),
+            Span::styled(" source  ", theme.muted),
+            Span::styled("esc", theme.accent),
+            Span::styled(" home", theme.muted),
+        ]);
+    }
+
     if app.layout_mode == LayoutMode::Timeline {
         return Line::from(vec![
             Span::styled("j/k", theme.accent),
@@ -4440,6 +7811,14 @@ fn footer_shortcuts<'a>(app: &App, theme: &Theme, width: u16) -> Line<'a> {
             Span::styled(tools_hint, theme.muted),
             Span::styled("r", theme.accent),
             Span::styled(" resume  ", theme.muted),
+            Span::styled("n", theme.accent),
+            Span::styled(" note  ", theme.muted),
+            Span::styled("u", theme.accent),
+            Span::styled(" outcome  ", theme.muted),
+            Span::styled("P", theme.accent),
+            Span::styled(" replay  ", theme.muted),
+            Span::styled("H", theme.accent),
+            Span::styled(" project history  ", theme.muted),
             Span::styled("S", theme.accent),
             Span::styled(" share", theme.muted),
         ]);
@@ -4476,47 +7855,130 @@ fn footer_shortcuts<'a>(app: &App, theme: &Theme, width: u16) -> Line<'a> {
     ])
 }
 
-fn sessions_from_query(
-    index: &SearchIndex,
+/// How a session search scores and tunes its query, and how many sessions it
+/// keeps.
+#[derive(Clone, Copy)]
+struct SearchSettings<'a> {
+    profile: &'a ScoringProfile,
+    tuning: &'a QueryTuning,
+    limit: usize,
+}
+
+fn search_options(
     query: &str,
     source: Option<SourceFilter>,
     project: Option<&str>,
     since: Option<u64>,
-    limit: usize,
-) -> Result<Vec<SessionSummary>> {
-    let options = QueryOptions {
-        query: query.to_string(),
+    settings: SearchSettings,
+) -> QueryOptions {
+    let SearchSettings {
+        profile,
+        tuning,
+        limit,
+    } = settings;
+    let (query, filters) = extract_query_filters(query);
+    let since = filters.since(since);
+    QueryOptions {
+        query,
         project: project.map(|s| s.to_string()),
-        role: None,
+        role: filters.role,
         tool: None,
         session_id: None,
         source,
         since,
-        until: None,
+        until: filters.before,
+        as_of: None,
+        settings: filters.settings,
+        has: filters.has,
+        edited: filters.edited,
+        commands: filters.commands,
+        files: filters.files,
+        langs: filters.langs,
+        errors: filters.errors,
+        require_all_terms: profile.require_all_terms,
+        fuzzy_distance: profile.fuzzy_distance,
+        tuning: tuning.clone(),
         limit: limit.max(20),
-    };
-    let results = index.search(&options)?;
-    let mut sessions: HashMap<String, SessionSummary> = HashMap::new();
-    for (score, record) in results {
-        add_record_to_session(&mut sessions, score, record);
     }
-    let mut out: Vec<SessionSummary> = sessions.into_values().collect();
-    out.sort_by(|a, b| {
-        b.top_score
-            .partial_cmp(&a.top_score)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| b.last_ts.cmp(&a.last_ts))
-    });
-    if out.len() > limit {
-        out.truncate(limit);
+}
+
+/// Orders `sessions` for the list; best match breaks ties in recency order
+/// and vice versa.
+fn sort_sessions(sessions: &mut [SessionSummary], sort: SessionSort) {
+    match sort {
+        SessionSort::Relevance => sessions.sort_by(|a, b| {
+            b.top_score
+                .partial_cmp(&a.top_score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b.last_ts.cmp(&a.last_ts))
+        }),
+        SessionSort::Recent => sessions.sort_by(|a, b| {
+            b.last_ts.cmp(&a.last_ts).then_with(|| {
+                b.top_score
+                    .partial_cmp(&a.top_score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+        }),
     }
-    Ok(out)
 }
 
-/// Reduces accepted search results to the only two values the home chart
-/// needs. This is computed once per completed search, not once per frame.
-fn session_activity(sessions: &[SessionSummary]) -> Vec<HomeChartPoint> {
-    sessions
+fn sessions_from_query(
+    index: &SearchIndex,
+    query: &str,
+    source: Option<SourceFilter>,
+    project: Option<&str>,
+    since: Option<u64>,
+    settings: SearchSettings,
+) -> Result<Vec<SessionSummary>> {
+    let (sessions, _) = sessions_from_query_timed(index, query, source, project, since, settings)?;
+    Ok(sessions)
+}
+
+/// [`sessions_from_query`], also returning how long the index took and how
+/// long grouping its hits into sessions took.
+fn sessions_from_query_timed(
+    index: &SearchIndex,
+    query: &str,
+    source: Option<SourceFilter>,
+    project: Option<&str>,
+    since: Option<u64>,
+    settings: SearchSettings,
+) -> Result<(Vec<SessionSummary>, SearchTimings)> {
+    let options = search_options(query, source, project, since, settings);
+    let SearchSettings { profile, limit, .. } = settings;
+    let (results, phases) = index.search_timed(&options)?;
+    let started = Instant::now();
+    let mut timings = SearchTimings {
+        phases,
+        hits: results.len(),
+        ..SearchTimings::default()
+    };
+    let terms = text::query_terms(&options.query);
+    let now = now_millis();
+    let mut sessions: HashMap<String, SessionSummary> = HashMap::new();
+    for (score, record) in results {
+        let score = apply_recency(
+            score,
+            record.ts,
+            now,
+            profile.recency_weight,
+            profile.recency_half_life_days,
+        );
+        add_record_to_session(&mut sessions, score, record, &terms);
+    }
+    let mut out: Vec<SessionSummary> = sessions.into_values().collect();
+    sort_sessions(&mut out, SessionSort::Relevance);
+    if out.len() > limit {
+        out.truncate(limit);
+    }
+    timings.aggregation = started.elapsed();
+    Ok((out, timings))
+}
+
+/// Reduces accepted search results to the only two values the home chart
+/// needs. This is computed once per completed search, not once per frame.
+fn session_activity(sessions: &[SessionSummary]) -> Vec<HomeChartPoint> {
+    sessions
         .iter()
         .filter(|session| session.last_ts > 0)
         .map(|session| HomeChartPoint {
@@ -4550,7 +8012,7 @@ fn sessions_from_recent(
         {
             continue;
         }
-        add_record_to_session(&mut sessions, 0.0, record);
+        add_record_to_session(&mut sessions, 0.0, record, &[]);
         if sessions.len() >= RECENT_SESSIONS_LIMIT {
             break;
         }
@@ -4562,12 +8024,14 @@ fn sessions_from_recent(
 
 fn sessions_from_analytics(
     paths: &Paths,
+    aliases: &ProjectAliases,
     source: Option<SourceFilter>,
     since: Option<u64>,
     project: Option<&str>,
     grouping: ProjectGrouping,
 ) -> Result<Vec<SessionSummary>> {
-    let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?;
+    let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?
+        .with_project_aliases(aliases.clone());
     let rows = store.query_sessions(
         source,
         since,
@@ -4588,24 +8052,31 @@ fn session_summary_from_row(row: SessionRow) -> SessionSummary {
         source: row.source,
         last_ts: row.last_at,
         hit_count: row.message_count.max(1) as usize,
+        message_count: row.message_count as usize,
+        duration_ms: row.last_at.saturating_sub(row.started_at),
         top_score: 0.0,
         snippet: String::new(),
+        title: row.title.unwrap_or_default(),
         source_dir: row.cwd.unwrap_or_else(|| parent_dir(&row.source_path)),
+        topic: String::new(),
+        note: String::new(),
+        outcome: None,
         source_path: row.source_path,
     }
 }
 
-fn enrich_session_projects(
+/// Fills in what search hits alone cannot tell: message counts, durations
+/// and, when grouping by repository, the repository project.
+fn enrich_sessions(
     paths: &Paths,
+    aliases: &ProjectAliases,
     sessions: &mut [SessionSummary],
     grouping: ProjectGrouping,
 ) {
-    if grouping == ProjectGrouping::Flat {
-        return;
-    }
     let Ok(store) = AnalyticsStore::open_read_only(analytics_path(&paths.state)) else {
         return;
     };
+    let store = store.with_project_aliases(aliases.clone());
     let keys: Vec<_> = sessions
         .iter()
         .map(|session| {
@@ -4616,7 +8087,7 @@ fn enrich_session_projects(
             )
         })
         .collect();
-    let Ok(projects) = store.query_session_projects(&keys, grouping) else {
+    let Ok(details) = store.query_session_details(&keys, grouping) else {
         return;
     };
     for session in sessions {
@@ -4625,18 +8096,25 @@ fn enrich_session_projects(
             session.session_id.clone(),
             session.source_path.clone(),
         );
-        if let Some(project) = projects.get(&key) {
-            session.project.clone_from(project);
+        let Some(detail) = details.get(&key) else {
+            continue;
+        };
+        if grouping != ProjectGrouping::Flat {
+            session.project.clone_from(&detail.display_project);
         }
+        session.message_count = detail.message_count as usize;
+        session.duration_ms = detail.last_at.saturating_sub(detail.started_at);
     }
 }
 
 fn collect_projects_from_analytics(
     paths: &Paths,
+    aliases: &ProjectAliases,
     source: Option<SourceFilter>,
     grouping: ProjectGrouping,
 ) -> Result<Vec<String>> {
-    let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?;
+    let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?
+        .with_project_aliases(aliases.clone());
     let projects = store.query_projects(source, grouping)?;
     if projects.is_empty() {
         anyhow::bail!("no analytics projects");
@@ -4644,26 +8122,41 @@ fn collect_projects_from_analytics(
     Ok(projects)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_project_timeline(
     paths: &Paths,
+    aliases: &ProjectAliases,
+    index: &SearchIndex,
     source: Option<SourceFilter>,
     range: TimelineRange,
     display: ProjectDisplayMode,
     query: &str,
+    limit: usize,
 ) -> Result<Vec<ProjectTimelineRow>> {
-    let now = now_ms();
+    let now = now_millis();
     let since = range.since_ms(now);
     let rows: Vec<SessionSummary> = if query.trim().is_empty() {
-        let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?;
+        let store = AnalyticsStore::open_read_only(analytics_path(&paths.state))?
+            .with_project_aliases(aliases.clone());
         store
             .query_sessions(source, since, None, display.grouping(), None)?
             .into_iter()
             .map(session_summary_from_row)
             .collect()
     } else {
-        let index = SearchIndex::open_or_create(&paths.index)?;
-        let mut sessions = sessions_from_query(&index, query, source, None, since, RESULT_LIMIT)?;
-        enrich_session_projects(paths, &mut sessions, display.grouping());
+        let mut sessions = sessions_from_query(
+            index,
+            query,
+            source,
+            None,
+            since,
+            SearchSettings {
+                profile: &ScoringProfile::default(),
+                tuning: &QueryTuning::default(),
+                limit,
+            },
+        )?;
+        enrich_sessions(paths, aliases, &mut sessions, display.grouping());
         sessions
     };
     let mut projects: HashMap<String, ProjectTimelineRow> = HashMap::new();
@@ -4698,10 +8191,54 @@ fn build_project_timeline(
     Ok(out)
 }
 
+/// Records per local calendar day.
+fn heatmap_day_counts(timestamps: &[u64]) -> HashMap<chrono::NaiveDate, u64> {
+    let mut counts = HashMap::new();
+    for &ts in timestamps.iter().filter(|ts| **ts > 0) {
+        if let Some(dt) = chrono::DateTime::from_timestamp_millis(ts as i64) {
+            *counts
+                .entry(dt.with_timezone(&chrono::Local).date_naive())
+                .or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Index into `HEATMAP_GLYPHS`: 0 for an idle day, otherwise quartiles of
+/// the busiest day shown.
+fn heatmap_level(count: u64, max: u64) -> usize {
+    if count == 0 || max == 0 {
+        return 0;
+    }
+    (count * 4).div_ceil(max).clamp(1, 4) as usize
+}
+
+/// Consecutive active days ending today, or yesterday when today is still
+/// empty.
+fn heatmap_streak(counts: &HashMap<chrono::NaiveDate, u64>, today: chrono::NaiveDate) -> usize {
+    let active = |day: &chrono::NaiveDate| counts.get(day).is_some_and(|count| *count > 0);
+    let mut day = today;
+    if !active(&day) {
+        day = day.pred_opt().unwrap_or(day);
+    }
+    let mut streak = 0;
+    while active(&day) {
+        streak += 1;
+        let Some(prev) = day.pred_opt() else {
+            break;
+        };
+        day = prev;
+    }
+    streak
+}
+
+/// Folds one hit into its session's summary. The snippet comes from the
+/// best-scoring hit, centered on where it matches `terms`.
 fn add_record_to_session(
     sessions: &mut HashMap<String, SessionSummary>,
     score: f32,
     record: Record,
+    terms: &[String],
 ) {
     let entry = sessions
         .entry(record.session_id.clone())
@@ -4711,33 +8248,51 @@ fn add_record_to_session(
             source: record.source,
             last_ts: record.ts,
             hit_count: 0,
+            message_count: 0,
+            duration_ms: 0,
             top_score: score,
-            snippet: summarize(&record.text, 160),
+            snippet: text::snippet(&record.text, terms, 160).text,
+            title: String::new(),
             source_path: record.source_path.clone(),
             source_dir: parent_dir(&record.source_path),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
         });
-    entry.hit_count += 1;
     if record.ts > entry.last_ts {
         entry.last_ts = record.ts;
     }
-    if score >= entry.top_score {
+    // A compaction summary restates the whole session and would win every
+    // match; it only stands in until one of the session's own messages hits.
+    if record.role == COMPACT_SUMMARY_ROLE {
+        return;
+    }
+    let first_hit = entry.hit_count == 0;
+    entry.hit_count += 1;
+    if first_hit || score >= entry.top_score {
         entry.top_score = score;
-        let snippet = summarize(&record.text, 160);
+        let snippet = text::snippet(&record.text, terms, 160).text;
         if !snippet.is_empty() {
             entry.snippet = snippet;
         }
-        entry.source_path = record.source_path;
-        entry.source_dir = parent_dir(&entry.source_path);
+        // A note's path only keys it in the index; resume needs the transcript.
+        if !is_note_source_path(&record.source_path) {
+            entry.source_path = record.source_path;
+            entry.source_dir = parent_dir(&entry.source_path);
+        }
     }
 }
 
 fn spawn_search_worker(
     paths: Paths,
+    aliases: ProjectAliases,
     index: SearchIndex,
+    latest: LatestSearch,
     rx: std::sync::mpsc::Receiver<SearchRequest>,
     tx: std::sync::mpsc::Sender<SearchUpdate>,
 ) {
     std::thread::spawn(move || {
+        let mut cache = SearchCache::default();
         while let Ok(mut request) = rx.recv() {
             // Keep only the newest queued query so fast typing cannot build a
             // backlog of obsolete searches.
@@ -4745,11 +8300,38 @@ fn spawn_search_worker(
                 request = newer;
             }
             let request_id = request.request_id;
-            let update = match run_search_request(&paths, &index, request) {
-                Ok(sessions) => SearchUpdate::Results {
-                    request_id,
-                    sessions,
-                },
+            let superseded = || latest.is_superseded(request_id);
+            let mut timings = SearchTimings {
+                query: request.query.clone(),
+                ..SearchTimings::default()
+            };
+            let started = Instant::now();
+            let result = catch_panic(|| {
+                run_cached_search_request(
+                    &paths,
+                    &aliases,
+                    &index,
+                    &mut cache,
+                    request,
+                    &superseded,
+                    &mut timings,
+                )
+            });
+            timings.total = started.elapsed();
+            let update = match result {
+                Ok(Some(sessions)) => {
+                    timings.sessions = sessions.len();
+                    let _ = tx.send(SearchUpdate::SearchTimings {
+                        request_id,
+                        timings,
+                    });
+                    SearchUpdate::Results {
+                        request_id,
+                        sessions,
+                    }
+                }
+                // Nobody is waiting for it any more.
+                Ok(None) => continue,
                 Err(err) => SearchUpdate::SearchError {
                     request_id,
                     message: err.to_string(),
@@ -4762,15 +8344,114 @@ fn spawn_search_worker(
     });
 }
 
+/// Query searches are served from `cache` while the index generation is
+/// unchanged. Empty queries read analytics, which the generation does not
+/// cover, so they always run. `None` when `superseded` reported a newer
+/// search before this one finished. `timings` gets where the time went.
+fn run_cached_search_request(
+    paths: &Paths,
+    aliases: &ProjectAliases,
+    index: &SearchIndex,
+    cache: &mut SearchCache,
+    request: SearchRequest,
+    superseded: &dyn Fn() -> bool,
+    timings: &mut SearchTimings,
+) -> Result<Option<Vec<SessionSummary>>> {
+    if superseded() {
+        return Ok(None);
+    }
+    let key = if request.query.is_empty() {
+        None
+    } else {
+        index
+            .generation()
+            .ok()
+            .map(|generation| SearchCacheKey::new(&request, generation))
+    };
+    if let Some(key) = &key
+        && let Some(sessions) = cache.get(key)
+    {
+        timings.cached = true;
+        return Ok(Some(sessions));
+    }
+    let Some(sessions) = run_search_request(paths, aliases, index, request, superseded, timings)?
+    else {
+        return Ok(None);
+    };
+    if let Some(key) = key {
+        cache.insert(key, sessions.clone());
+    }
+    Ok(Some(sessions))
+}
+
 fn run_search_request(
     paths: &Paths,
+    aliases: &ProjectAliases,
     index: &SearchIndex,
     request: SearchRequest,
-) -> Result<Vec<SessionSummary>> {
-    let project = (!request.project.is_empty()).then_some(request.project.as_str());
+    superseded: &dyn Fn() -> bool,
+    timings: &mut SearchTimings,
+) -> Result<Option<Vec<SessionSummary>>> {
+    let (query, topic) = take_topic_filter(&request.query);
+    let request = SearchRequest { query, ..request };
+    let Some(mut sessions) =
+        sessions_for_request(paths, aliases, index, &request, superseded, timings)?
+    else {
+        return Ok(None);
+    };
+    let started = Instant::now();
+    let topics = Topics::load(&topics_path(&paths.state)).unwrap_or_default();
+    if let Some(name) = topic {
+        let members: HashSet<&str> = topics
+            .find(&name)
+            .map(|topic| {
+                topic
+                    .sessions
+                    .iter()
+                    .map(|session| session.session_id.as_str())
+                    .collect()
+            })
+            .unwrap_or_default();
+        sessions.retain(|session| members.contains(session.session_id.as_str()));
+    }
+    let labels = topics.labels_by_session();
+    let notes = SessionNotes::load(&notes_path(&paths.state)).unwrap_or_default();
+    let outcomes = SessionOutcomes::load(&outcomes_path(&paths.state)).unwrap_or_default();
+    for session in &mut sessions {
+        if let Some(label) = labels.get(&session.session_id) {
+            session.topic.clone_from(label);
+        }
+        session.outcome = outcomes.get(&session.session_id);
+        if let Some(note) = notes.get(&session.session_id) {
+            session.note.clone_from(&note.text);
+            // Only the note matched, so the session has no transcript path yet.
+            if is_note_source_path(&session.source_path) {
+                session.source_path.clone_from(&note.source_path);
+                session.source_dir = parent_dir(&note.source_path);
+            }
+        }
+    }
+    timings.enrichment += started.elapsed();
+    Ok(Some(sessions))
+}
+
+fn sessions_for_request(
+    paths: &Paths,
+    aliases: &ProjectAliases,
+    index: &SearchIndex,
+    request: &SearchRequest,
+    superseded: &dyn Fn() -> bool,
+    timings: &mut SearchTimings,
+) -> Result<Option<Vec<SessionSummary>>> {
+    // Filters match stored names; grouped lists show the alias instead.
+    let resolved = aliases.resolve(&request.project);
+    let shown = aliases.shown(&resolved);
+    let project = (!resolved.is_empty()).then_some(resolved.as_str());
     if request.query.is_empty() {
-        return sessions_from_analytics(
+        let started = Instant::now();
+        let sessions = sessions_from_analytics(
             paths,
+            aliases,
             request.source.as_filter(),
             request.since,
             project,
@@ -4779,6 +8460,8 @@ fn run_search_request(
         .or_else(|_| {
             sessions_from_recent(index, request.source.as_filter(), request.since, project)
         });
+        timings.aggregation = started.elapsed();
+        return sessions.map(Some);
     }
 
     let tantivy_project = if request.grouping == ProjectGrouping::Flat {
@@ -4786,42 +8469,175 @@ fn run_search_request(
     } else {
         None
     };
-    let mut sessions = sessions_from_query(
+    let (mut sessions, query_timings) = sessions_from_query_timed(
         index,
         &request.query,
         request.source.as_filter(),
         tantivy_project,
         request.since,
-        RESULT_LIMIT,
+        SearchSettings {
+            profile: &request.profile,
+            tuning: &request.tuning,
+            limit: request.limit,
+        },
     )?;
-    enrich_session_projects(paths, &mut sessions, request.grouping);
+    *timings = SearchTimings {
+        query: std::mem::take(&mut timings.query),
+        ..query_timings
+    };
+    // Enriching reads analytics for every hit; skip it once the user has
+    // typed past this query.
+    if superseded() {
+        return Ok(None);
+    }
+    let started = Instant::now();
+    enrich_sessions(paths, aliases, &mut sessions, request.grouping);
+    timings.enrichment = started.elapsed();
     if let Some(project) = project {
-        sessions.retain(|session| session.project == project);
+        sessions.retain(|session| session.project == project || session.project == shown);
     }
-    Ok(sessions)
+    Ok(Some(sessions))
 }
 
 fn spawn_detail_worker(
     index: SearchIndex,
+    vectors_dir: PathBuf,
     rx: std::sync::mpsc::Receiver<DetailRequest>,
     tx: std::sync::mpsc::Sender<SearchUpdate>,
 ) {
     std::thread::spawn(move || {
-        while let Ok(mut request) = rx.recv() {
+        let mut vectors = None;
+        let mut pending = None;
+        let mut translations = HashMap::new();
+        while let Some(mut request) = pending.take().or_else(|| rx.recv().ok()) {
             while let Ok(newer) = rx.try_recv() {
                 request = newer;
             }
-            let update = match build_detail_lines(
-                &index,
-                &request.session,
-                request.mode,
-                &request.query,
-                request.show_tools,
-            ) {
-                Ok(lines) => SearchUpdate::DetailResults {
+            if let Some(before) = request.earlier {
+                let mut lines = earlier_history_lines(
+                    &index,
+                    &request.session.session_id,
+                    before,
+                    request.show_tools,
+                    request.limits.message_chars,
+                )
+                .unwrap_or_else(|err| {
+                    vec![PreviewLine::Text(format!(
+                        "could not load earlier messages: {err}"
+                    ))]
+                });
+                if let Some(translator) = &request.translate {
+                    let mut failed = false;
+                    lines = translate_preview_lines(
+                        &index,
+                        translator,
+                        &mut translations,
+                        &mut failed,
+                        lines,
+                    );
+                }
+                let update = SearchUpdate::DetailEarlier {
                     request_id: request.request_id,
                     lines,
-                },
+                };
+                if tx.send(update).is_err() {
+                    break;
+                }
+                continue;
+            }
+            if request.related {
+                // Reopened once an ingest commits or a writer replaces the
+                // manifest, so newly embedded sessions show up.
+                let version = (
+                    index.generation().unwrap_or_default(),
+                    manifest_modified(&vectors_dir),
+                );
+                if vectors
+                    .as_ref()
+                    .is_none_or(|(opened, _)| *opened != version)
+                {
+                    vectors = VectorIndex::open(&vectors_dir)
+                        .ok()
+                        .map(|store| (version, store));
+                }
+            }
+            // The first chunk replaces the preview and later ones append, so
+            // long transcripts render while the rest is still being built. A
+            // newer request cuts the stream short.
+            let mut started = false;
+            let mut disconnected = false;
+            let mut translate_failed = false;
+            let result = catch_panic(|| {
+                stream_detail_lines(
+                    &index,
+                    &request.session,
+                    request.mode,
+                    &request.query,
+                    request.show_tools,
+                    request.show_threads,
+                    request.limits,
+                    request.lazy_history,
+                    &mut |lines| {
+                        let lines = match &request.translate {
+                            Some(translator) => translate_preview_lines(
+                                &index,
+                                translator,
+                                &mut translations,
+                                &mut translate_failed,
+                                lines,
+                            ),
+                            None => lines,
+                        };
+                        let update = if started {
+                            SearchUpdate::DetailAppend {
+                                request_id: request.request_id,
+                                lines,
+                                related: Vec::new(),
+                            }
+                        } else {
+                            SearchUpdate::DetailResults {
+                                request_id: request.request_id,
+                                lines,
+                                related: Vec::new(),
+                            }
+                        };
+                        started = true;
+                        if tx.send(update).is_err() {
+                            disconnected = true;
+                            return false;
+                        }
+                        while let Ok(newer) = rx.try_recv() {
+                            pending = Some(newer);
+                        }
+                        !superseded(&request, pending.as_ref())
+                    },
+                )
+            });
+            if disconnected {
+                break;
+            }
+            if superseded(&request, pending.as_ref()) {
+                continue;
+            }
+            let update = match result {
+                Ok(()) => {
+                    // Related sessions are a best-effort extra; a vector
+                    // failure should not hide the transcript itself.
+                    let related = vectors
+                        .as_ref()
+                        .filter(|_| request.related)
+                        .and_then(|(_, vectors)| {
+                            related_sessions(&index, vectors, &request.session.session_id).ok()
+                        })
+                        .unwrap_or_default();
+                    let mut lines = Vec::new();
+                    append_related_lines(&mut lines, &related);
+                    SearchUpdate::DetailAppend {
+                        request_id: request.request_id,
+                        lines,
+                        related,
+                    }
+                }
                 Err(err) => SearchUpdate::DetailError {
                     request_id: request.request_id,
                     message: err.to_string(),
@@ -4834,28 +8650,236 @@ fn spawn_detail_worker(
     });
 }
 
+/// Whether `pending` replaces the preview `request` is building; asking
+/// for earlier history of the same preview waits for it instead.
+fn superseded(request: &DetailRequest, pending: Option<&DetailRequest>) -> bool {
+    pending.is_some_and(|pending| pending.request_id != request.request_id)
+}
+
+/// Sessions whose records sit closest to the centroid of `session_id`'s
+/// embeddings, best first.
+fn related_sessions(
+    index: &SearchIndex,
+    vectors: &VectorIndex,
+    session_id: &str,
+) -> Result<Vec<SessionSummary>> {
+    let records = index.records_by_session_id(session_id)?;
+    let Some(centroid) = session_centroid(vectors, &records)? else {
+        return Ok(Vec::new());
+    };
+    // A long session's own records would otherwise fill every neighbour.
+    let own: HashSet<u64> = records.iter().map(|record| record.doc_id).collect();
+    let mut sessions = HashMap::new();
+    let mut order = Vec::new();
+    for (doc_id, distance) in vectors.search_excluding(&centroid, RELATED_SEARCH_LIMIT, &own)? {
+        let Some(record) = index.get_by_doc_id(doc_id)? else {
+            continue;
+        };
+        if record.session_id == session_id {
+            continue;
+        }
+        if !sessions.contains_key(&record.session_id) {
+            if order.len() == RELATED_SESSIONS_LIMIT {
+                continue;
+            }
+            order.push(record.session_id.clone());
+        }
+        add_record_to_session(&mut sessions, 1.0 - distance, record, &[]);
+    }
+    Ok(order
+        .into_iter()
+        .filter_map(|session_id| sessions.remove(&session_id))
+        .collect())
+}
+
+fn session_centroid(vectors: &VectorIndex, records: &[Record]) -> Result<Option<Vec<f32>>> {
+    let mut centroid = vec![0f32; vectors.dimensions()];
+    let mut count = 0usize;
+    for record in records {
+        let Some(embedding) = vectors.get(record.doc_id)? else {
+            continue;
+        };
+        for (sum, value) in centroid.iter_mut().zip(embedding) {
+            *sum += value;
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return Ok(None);
+    }
+    for value in &mut centroid {
+        *value /= count as f32;
+    }
+    Ok(Some(centroid))
+}
+
+/// Adds a translation under each message in `lines` written in another
+/// language than the translator's. Translations are kept in `cache` by doc
+/// id (`None` for messages that need none); after the command fails once,
+/// the rest of the preview is left as is.
+fn translate_preview_lines(
+    index: &SearchIndex,
+    translator: &Translator,
+    cache: &mut HashMap<u64, Option<String>>,
+    failed: &mut bool,
+    lines: Vec<PreviewLine>,
+) -> Vec<PreviewLine> {
+    let mut out = Vec::with_capacity(lines.len());
+    let mut message = None;
+    for line in lines {
+        match &line {
+            PreviewLine::Meta { doc_id, .. } => message = Some(*doc_id),
+            PreviewLine::Empty => {
+                if let Some(doc_id) = message.take()
+                    && !*failed
+                {
+                    out.extend(translation_lines(index, translator, cache, failed, doc_id));
+                }
+            }
+            _ => {}
+        }
+        out.push(line);
+    }
+    out
+}
+
+fn translation_lines(
+    index: &SearchIndex,
+    translator: &Translator,
+    cache: &mut HashMap<u64, Option<String>>,
+    failed: &mut bool,
+    doc_id: u64,
+) -> Vec<PreviewLine> {
+    let translated = match cache.entry(doc_id) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+            let Ok(Some(record)) = index.get_by_doc_id(doc_id) else {
+                return Vec::new();
+            };
+            let translated = match record_language(&record).filter(|lang| translator.wants(lang)) {
+                None => None,
+                Some(from) => match translator.translate(&record.text, from) {
+                    Ok(text) => Some(format!("{from}→{}: {text}", translator.to)),
+                    Err(err) => {
+                        *failed = true;
+                        return vec![PreviewLine::Translation(format!(
+                            "translation failed: {err}"
+                        ))];
+                    }
+                },
+            };
+            entry.insert(translated)
+        }
+    };
+    match translated {
+        Some(text) => sanitize_preview_lines(text)
+            .into_iter()
+            .map(PreviewLine::Translation)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn append_related_lines(lines: &mut Vec<PreviewLine>, related: &[SessionSummary]) {
+    if related.is_empty() {
+        return;
+    }
+    lines.push(PreviewLine::Empty);
+    lines.push(PreviewLine::Text(format!(
+        "related (press 1-{} to open):",
+        related.len()
+    )));
+    for (idx, session) in related.iter().enumerate() {
+        let label = if !session.title.is_empty() {
+            session.title.clone()
+        } else if !session.snippet.is_empty() {
+            strip_ansi_and_controls(&session.snippet)
+        } else {
+            session.session_id.clone()
+        };
+        lines.push(PreviewLine::Related {
+            key: idx + 1,
+            label,
+            project: session.project.clone(),
+            ts: format_relative_ts(session.last_ts),
+        });
+    }
+}
+
 fn build_detail_lines(
     index: &SearchIndex,
     session: &SessionSummary,
     mode: PreviewMode,
     query: &str,
     show_tools: bool,
+    show_threads: bool,
+    limits: PreviewLimits,
 ) -> Result<Vec<PreviewLine>> {
-    let mut records = index.records_by_session_id(&session.session_id)?;
-    records.sort_by(|a, b| {
-        a.turn_id
-            .cmp(&b.turn_id)
-            .then_with(|| a.ts.cmp(&b.ts))
-            .then_with(|| a.doc_id.cmp(&b.doc_id))
-    });
+    let mut lines = Vec::new();
+    stream_detail_lines(
+        index,
+        session,
+        mode,
+        query,
+        show_tools,
+        show_threads,
+        limits,
+        false,
+        &mut |chunk| {
+            lines.extend(chunk);
+            true
+        },
+    )?;
+    Ok(lines)
+}
+
+/// Builds the preview of `session`, handing lines to `emit` as they are
+/// ready. Full history goes out every [`DETAIL_STREAM_RECORDS`] records,
+/// or with `lazy_history` only its newest ones behind a
+/// [`PreviewLine::EarlierHistory`] marker; stops early once `emit` returns
+/// false.
+#[allow(clippy::too_many_arguments)]
+fn stream_detail_lines(
+    index: &SearchIndex,
+    session: &SessionSummary,
+    mode: PreviewMode,
+    query: &str,
+    show_tools: bool,
+    show_threads: bool,
+    limits: PreviewLimits,
+    lazy_history: bool,
+    emit: &mut dyn FnMut(Vec<PreviewLine>) -> bool,
+) -> Result<()> {
+    let max_chars = limits.message_chars;
+    if mode == PreviewMode::Project {
+        return stream_project_history(index, session, show_tools, max_chars, emit);
+    }
+    let records = sorted_session_records(index, &session.session_id)?;
     let mut lines = vec![PreviewLine::SessionHeader {
         project: session.project.clone(),
         source: session.source.label().to_string(),
         session_id: session.session_id.clone(),
     }];
+    if !session.note.is_empty() {
+        lines.push(PreviewLine::Note(strip_ansi_and_controls(&session.note)));
+    }
+    if let Some(outcome) = session.outcome {
+        lines.push(PreviewLine::Outcome(outcome));
+    }
+    let todos = index.session_records_with_role(&session.session_id, TODO_ROLE)?;
+    lines.extend(current_todos(&todos).into_iter().map(PreviewLine::Todo));
     if records.is_empty() {
         lines.push(PreviewLine::Text("no records in session".to_string()));
-        return Ok(lines);
+        emit(lines);
+        return Ok(());
+    }
+    if let Some(settings) = records
+        .iter()
+        .rev()
+        .map(|record| &record.settings)
+        .find(|settings| !settings.is_empty())
+    {
+        lines.push(PreviewLine::Settings(settings.clone()));
     }
     if !session.snippet.is_empty() {
         let snippet = strip_ansi_and_controls(&session.snippet);
@@ -4870,38 +8894,46 @@ fn build_detail_lines(
                 let tail = records
                     .into_iter()
                     .rev()
-                    .take(DETAIL_TAIL_LINES)
+                    .take(limits.tail_messages)
                     .collect::<Vec<_>>();
-                append_records(&mut lines, tail.iter().rev());
+                append_records(&mut lines, tail.iter().rev(), max_chars);
             } else {
                 let matchers = build_matchers(query)?;
                 if matchers.is_empty() {
                     lines.push(PreviewLine::Text("no valid query terms".to_string()));
                 } else {
-                    let mut matches_all = false;
-                    let mut matches_non_tools = false;
-                    for record in records.iter() {
-                        if matches_any(&record.text, &matchers) {
-                            matches_all = true;
-                            if !is_tool_role(&record.role) {
-                                matches_non_tools = true;
-                            }
-                        }
-                    }
+                    let needed = if limits.all_terms { matchers.len() } else { 1 };
+                    let counts: Vec<usize> = records
+                        .iter()
+                        .map(|record| matched_terms(&record.text, &matchers))
+                        .collect();
+                    let matches_any = counts.iter().any(|&count| count > 0);
+                    let matches_needed = counts.iter().any(|&count| count >= needed);
+                    let matches_non_tools = records
+                        .iter()
+                        .zip(&counts)
+                        .any(|(record, &count)| count >= needed && !is_tool_role(&record.role));
                     let mut indices = Vec::new();
                     for (idx, record) in records.iter().enumerate() {
                         if !show_tools && is_tool_role(&record.role) {
                             continue;
                         }
-                        if matches_any(&record.text, &matchers) {
+                        if counts[idx] >= needed {
                             indices.push(idx);
                         }
                     }
+                    // Messages holding more of the terms come first; the
+                    // sort is stable, so ties stay in order.
+                    indices.sort_by_key(|&idx| std::cmp::Reverse(counts[idx]));
                     if indices.is_empty() {
-                        if !matches_all {
+                        if !matches_any {
                             lines.push(PreviewLine::Text(
                                 "no literal matches (search matched via tokenizer)".to_string(),
                             ));
+                        } else if !matches_needed {
+                            lines.push(PreviewLine::Text(
+                                "no message holds every term (press A to match any)".to_string(),
+                            ));
                         } else if !show_tools && !matches_non_tools {
                             lines.push(PreviewLine::Text(
                                 "matches only in tool messages (press t to show)".to_string(),
@@ -4910,103 +8942,356 @@ fn build_detail_lines(
                             lines.push(PreviewLine::Text("no matches in session".to_string()));
                         }
                     } else {
-                        let mut last_added: Option<usize> = None;
+                        let mut added = std::collections::HashSet::new();
                         for idx in indices {
-                            let start = idx.saturating_sub(CONTEXT_AROUND_MATCH);
-                            let end = (idx + CONTEXT_AROUND_MATCH).min(records.len() - 1);
-                            for (i, record) in records.iter().enumerate().take(end + 1).skip(start)
+                            let shown = match limits.match_context {
+                                Some(context) => {
+                                    idx.saturating_sub(context)
+                                        ..idx.saturating_add(context + 1).min(records.len())
+                                }
+                                None => crate::turns::exchange_bounds(&records, idx),
+                            };
+                            for (i, record) in
+                                records.iter().enumerate().take(shown.end).skip(shown.start)
                             {
                                 if !show_tools && is_tool_role(&record.role) {
                                     continue;
                                 }
-                                if let Some(last) = last_added
-                                    && i <= last
-                                {
+                                if !added.insert(i) {
                                     continue;
                                 }
-                                last_added = Some(i);
-                                append_record(&mut lines, record, true);
+                                append_record(&mut lines, record, true, max_chars);
                             }
                         }
                     }
                 }
             }
         }
+        PreviewMode::History if show_threads => {
+            let children: Vec<Record> = index
+                .records_by_parent_session_id(&session.session_id)?
+                .into_iter()
+                .filter(|record| record.session_id != session.session_id)
+                .collect();
+            append_threaded_history(&mut lines, records, children, show_tools, max_chars);
+        }
+        PreviewMode::History if lazy_history => {
+            let start = history_window_start(&records, records.len(), show_tools);
+            append_history_window(&mut lines, &records, start, show_tools, max_chars);
+        }
         PreviewMode::History => {
-            for record in records.iter() {
+            let mut run = ToolRun::new(max_chars);
+            for (idx, record) in records.iter().enumerate() {
+                if idx > 0 && idx % DETAIL_STREAM_RECORDS == 0 && !emit(std::mem::take(&mut lines))
+                {
+                    return Ok(());
+                }
                 if !show_tools && is_tool_role(&record.role) {
+                    run.push(record);
                     continue;
                 }
-                append_record(&mut lines, record, false);
+                run.flush(&mut lines);
+                append_record(&mut lines, record, false, max_chars);
             }
+            run.flush(&mut lines);
         }
+        PreviewMode::Project => unreachable!("project history returns early"),
     }
-    Ok(lines)
+    if !lines.is_empty() {
+        emit(lines);
+    }
+    Ok(())
 }
 
-fn expand_resume_template(template: &str, session: &SessionSummary, cwd: &str) -> String {
-    template
-        .replace("{session_id}", &session.session_id)
-        .replace("{project}", &session.project)
-        .replace("{source}", session.source.label())
-        .replace("{source_path_shell}", &shell_quote(&session.source_path))
-        .replace("{source_path}", &session.source_path)
-        .replace("{source_dir_shell}", &shell_quote(&session.source_dir))
-        .replace("{source_dir}", &session.source_dir)
-        .replace("{cwd_shell}", &shell_quote(cwd))
-        .replace("{cwd}", cwd)
+fn sorted_session_records(index: &SearchIndex, session_id: &str) -> Result<Vec<Record>> {
+    let mut records = index.records_by_session_id(session_id)?;
+    records.sort_by(|a, b| {
+        a.turn_id
+            .cmp(&b.turn_id)
+            .then_with(|| a.ts.cmp(&b.ts))
+            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    });
+    Ok(records)
 }
 
-fn default_resume_template(cmd: &str) -> Option<String> {
-    match cmd {
-        "claude" => {
-            find_in_path("claude").map(|_| "cd {cwd} && claude --resume {session_id}".to_string())
-        }
-        "codex" => find_in_path("codex").map(|_| "codex resume {session_id}".to_string()),
-        "opencode" => find_in_path("opencode").map(|_| "opencode resume {session_id}".to_string()),
-        "cursor" => {
-            find_in_path("cursor-agent").map(|_| "cursor-agent --resume {session_id}".to_string())
-        }
-        "pi" => find_in_path("pi").map(|_| "pi --session {source_path_shell}".to_string()),
-        _ => None,
+/// Where the window of history ending at `end` starts: up to
+/// [`DETAIL_STREAM_RECORDS`] records back, further while tools are hidden
+/// so a run of them folds into one group.
+fn history_window_start(records: &[Record], end: usize, show_tools: bool) -> usize {
+    let mut start = end.saturating_sub(DETAIL_STREAM_RECORDS);
+    while start > 0 && !show_tools && is_tool_role(&records[start].role) {
+        start -= 1;
     }
+    start
 }
 
-fn shell_quote(value: &str) -> String {
-    if value.is_empty() {
-        return "''".to_string();
+/// Adds `records[start..]` as history, behind a marker for the records
+/// before it.
+fn append_history_window(
+    lines: &mut Vec<PreviewLine>,
+    records: &[Record],
+    start: usize,
+    show_tools: bool,
+    max_chars: usize,
+) {
+    if start > 0 {
+        lines.push(PreviewLine::EarlierHistory(start));
     }
-    let mut out = String::with_capacity(value.len() + 2);
-    out.push('\'');
-    for ch in value.chars() {
-        if ch == '\'' {
-            out.push_str("'\\''");
-        } else {
-            out.push(ch);
+    let mut run = ToolRun::new(max_chars);
+    for record in &records[start..] {
+        if !show_tools && is_tool_role(&record.role) {
+            run.push(record);
+            continue;
         }
+        run.flush(lines);
+        append_record(lines, record, false, max_chars);
     }
-    out.push('\'');
-    out
+    run.flush(lines);
+}
+
+/// The window of `session_id`'s history just before record `before`, for
+/// the preview to put above what it shows.
+fn earlier_history_lines(
+    index: &SearchIndex,
+    session_id: &str,
+    before: usize,
+    show_tools: bool,
+    max_chars: usize,
+) -> Result<Vec<PreviewLine>> {
+    let records = sorted_session_records(index, session_id)?;
+    let end = before.min(records.len());
+    let start = history_window_start(&records, end, show_tools);
+    let mut lines = Vec::new();
+    append_history_window(&mut lines, &records[..end], start, show_tools, max_chars);
+    Ok(lines)
 }
 
-fn find_in_path(name: &str) -> Option<PathBuf> {
-    let path = std::env::var_os("PATH")?;
-    for dir in std::env::split_paths(&path) {
-        let candidate = dir.join(name);
-        if candidate.exists() {
-            return Some(candidate);
+/// The week of `session`'s project up to its last message, every session's
+/// records interleaved by time, with a session header wherever the stream
+/// moves to another session.
+fn stream_project_history(
+    index: &SearchIndex,
+    session: &SessionSummary,
+    show_tools: bool,
+    max_chars: usize,
+    emit: &mut dyn FnMut(Vec<PreviewLine>) -> bool,
+) -> Result<()> {
+    let since = session.last_ts.saturating_sub(PROJECT_HISTORY_WINDOW_MS);
+    let records = index.records_by_project(
+        &session.project,
+        since,
+        session.last_ts,
+        PROJECT_HISTORY_LIMIT,
+    )?;
+    let sessions: HashSet<&str> = records
+        .iter()
+        .map(|record| record.session_id.as_str())
+        .collect();
+    let mut lines = vec![
+        PreviewLine::Text(format!(
+            "{} · {} sessions · {} to {}",
+            session.project,
+            sessions.len(),
+            format_ts(records.first().map_or(since, |record| record.ts)),
+            format_ts(session.last_ts)
+        )),
+        PreviewLine::Empty,
+    ];
+    let mut current: Option<&str> = None;
+    let mut run = ToolRun::new(max_chars);
+    for (idx, record) in records.iter().enumerate() {
+        if idx > 0 && idx % DETAIL_STREAM_RECORDS == 0 && !emit(std::mem::take(&mut lines)) {
+            return Ok(());
+        }
+        if current != Some(record.session_id.as_str()) {
+            run.flush(&mut lines);
+            if current.is_some() {
+                lines.push(PreviewLine::Empty);
+            }
+            lines.push(PreviewLine::SessionHeader {
+                project: record.project.clone(),
+                source: record.source.label().to_string(),
+                session_id: record.session_id.clone(),
+            });
+            current = Some(record.session_id.as_str());
+        }
+        if !show_tools && is_tool_role(&record.role) {
+            run.push(record);
+            continue;
         }
+        run.flush(&mut lines);
+        append_record(&mut lines, record, false, max_chars);
+    }
+    run.flush(&mut lines);
+    if !lines.is_empty() {
+        emit(lines);
+    }
+    Ok(())
+}
+
+/// Renders a session's main line with each subagent/sidechain thread nested
+/// after the last main-line turn that precedes it. Threads come from inline
+/// sidechain records (`links.thread`) and from child sessions.
+fn append_threaded_history(
+    lines: &mut Vec<PreviewLine>,
+    records: Vec<Record>,
+    children: Vec<Record>,
+    show_tools: bool,
+    max_chars: usize,
+) {
+    let mut main = Vec::new();
+    let mut threads: HashMap<String, Vec<Record>> = HashMap::new();
+    for record in records {
+        match record.links.thread.clone() {
+            Some(thread) => threads.entry(thread).or_default().push(record),
+            None => main.push(record),
+        }
+    }
+    for record in children {
+        let thread = record
+            .links
+            .thread
+            .clone()
+            .unwrap_or_else(|| record.session_id.clone());
+        threads.entry(thread).or_default().push(record);
+    }
+
+    // Anchor each thread to the index of the main-line record it follows;
+    // threads that start before any main turn render first.
+    let mut anchored: Vec<(Option<usize>, String, Vec<Record>)> = threads
+        .into_iter()
+        .map(|(thread, mut records)| {
+            records.sort_by(|a, b| {
+                a.ts.cmp(&b.ts)
+                    .then_with(|| a.turn_id.cmp(&b.turn_id))
+                    .then_with(|| a.doc_id.cmp(&b.doc_id))
+            });
+            let start = records.first().map(|record| record.ts).unwrap_or(0);
+            let anchor = main.iter().rposition(|record| record.ts <= start);
+            (anchor, thread, records)
+        })
+        .collect();
+    anchored.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then_with(|| a.2.first().map(|r| r.ts).cmp(&b.2.first().map(|r| r.ts)))
+    });
+
+    let mut pending = anchored.into_iter().peekable();
+    let mut run = ToolRun::new(max_chars);
+    let mut emit_threads =
+        |lines: &mut Vec<PreviewLine>, run: &mut ToolRun, anchor: Option<usize>| {
+            while let Some((_, thread, records)) = pending.next_if(|(at, _, _)| *at == anchor) {
+                run.flush(lines);
+                let kind = records
+                    .first()
+                    .and_then(|record| record.links.conversation_kind.clone())
+                    .unwrap_or_else(|| "thread".to_string());
+                lines.push(PreviewLine::ThreadHeader { kind, thread });
+                let mut nested = Vec::new();
+                append_records(
+                    &mut nested,
+                    records
+                        .iter()
+                        .filter(|record| show_tools || !is_tool_role(&record.role)),
+                    max_chars,
+                );
+                lines.extend(
+                    nested
+                        .into_iter()
+                        .map(|line| PreviewLine::Nested(Box::new(line))),
+                );
+            }
+        };
+    emit_threads(lines, &mut run, None);
+    for (idx, record) in main.iter().enumerate() {
+        if show_tools || !is_tool_role(&record.role) {
+            run.flush(lines);
+            append_record(lines, record, false, max_chars);
+        } else {
+            run.push(record);
+        }
+        emit_threads(lines, &mut run, Some(idx));
+    }
+    run.flush(lines);
+}
+
+fn expand_resume_template(template: &str, session: &SessionSummary, cwd: &str) -> String {
+    template
+        .replace("{session_id}", &session.session_id)
+        .replace("{project}", &session.project)
+        .replace("{source}", session.source.label())
+        .replace("{source_path_shell}", &shell_quote(&session.source_path))
+        .replace("{source_path}", &session.source_path)
+        .replace("{source_dir_shell}", &shell_quote(&session.source_dir))
+        .replace("{source_dir}", &session.source_dir)
+        .replace("{cwd_shell}", &shell_quote(cwd))
+        .replace("{cwd}", cwd)
+}
+
+/// Expands `template`, prefixed with a `cd` into `cwd` when `cd` is set and
+/// the template does not already place itself with `{cwd}`/`{cwd_shell}`.
+fn resume_command(template: &str, session: &SessionSummary, cwd: &str, cd: bool) -> String {
+    let command = expand_resume_template(template, session, cwd);
+    if cd && !template.contains("{cwd") {
+        format!("{} {} && {command}", change_dir_command(), shell_quote(cwd))
+    } else {
+        command
+    }
+}
+
+/// Inverse of [`expand_resume_template`] for saving an edited command: the
+/// session's paths and id become placeholders again, quoted forms first.
+fn templatize_resume_command(command: &str, session: &SessionSummary, cwd: &str) -> String {
+    let replacements = [
+        (shell_quote(cwd), "{cwd_shell}"),
+        (shell_quote(&session.source_path), "{source_path_shell}"),
+        (shell_quote(&session.source_dir), "{source_dir_shell}"),
+        (cwd.to_string(), "{cwd}"),
+        (session.source_path.clone(), "{source_path}"),
+        (session.source_dir.clone(), "{source_dir}"),
+        (session.session_id.clone(), "{session_id}"),
+    ];
+    let mut template = command.to_string();
+    for (value, placeholder) in replacements {
+        if !value.is_empty() && value != "''" && value != "\"\"" {
+            template = template.replace(&value, placeholder);
+        }
+    }
+    template
+}
+
+/// Source name used for resume templates: config keys, defaults and saved
+/// overrides.
+fn resume_source_label(source: SourceKind) -> &'static str {
+    match source {
+        SourceKind::Claude => "claude",
+        SourceKind::CodexSession | SourceKind::CodexHistory => "codex",
+        SourceKind::Opencode => "opencode",
+        SourceKind::Cursor => "cursor",
+        SourceKind::Pi => "pi",
+        SourceKind::Copilot => "copilot",
+        SourceKind::ChatGPT => "chatgpt",
+        SourceKind::External(name) => name,
+    }
+}
+
+fn default_resume_template(cmd: &str) -> Option<String> {
+    match cmd {
+        "claude" => find_in_path("claude").map(|_| "claude --resume {session_id}".to_string()),
+        "codex" => find_in_path("codex").map(|_| "codex resume {session_id}".to_string()),
+        "opencode" => find_in_path("opencode").map(|_| "opencode resume {session_id}".to_string()),
+        "cursor" => {
+            find_in_path("cursor-agent").map(|_| "cursor-agent --resume {session_id}".to_string())
+        }
+        "pi" => find_in_path("pi").map(|_| "pi --session {source_path_shell}".to_string()),
+        _ => None,
     }
-    None
 }
 
 fn run_external_command(app: &mut App, terminal: &mut TuiTerminal, command: &str) -> Result<()> {
     app.restore_stdio()?;
     exit_terminal(terminal)?;
-    let status = std::process::Command::new("sh")
-        .arg("-lc")
-        .arg(command)
-        .status();
+    let status = shell_command(command).status();
     match status {
         Ok(status) => {
             println!("command exited with {status}");
@@ -5042,6 +9327,74 @@ fn enter_terminal() -> Result<TuiTerminal> {
     Ok(terminal)
 }
 
+/// Panic message from the UI thread, reported after the terminal is restored.
+static UI_PANIC: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+type PanicHook = dyn Fn(&std::panic::PanicHookInfo<'_>) + Send + Sync;
+
+/// Runs `run_loop` with a panic hook that leaves the alternate screen, raw
+/// mode and mouse capture before anything else happens, so a panicking draw
+/// or update path cannot garble the shell. The panic is turned into an error
+/// carrying its message. Panics on worker threads print nothing over the
+/// screen; the workers catch them and answer the request with the message
+/// instead.
+fn run_loop_restoring_on_panic(terminal: &mut TuiTerminal, app: &mut App) -> Result<()> {
+    let ui_thread = std::thread::current().id();
+    let previous: std::sync::Arc<PanicHook> = std::panic::take_hook().into();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().id() != ui_thread {
+            return;
+        }
+        let _ = restore_terminal_after_panic();
+        if let Ok(mut slot) = UI_PANIC.lock() {
+            *slot = Some(info.to_string());
+        }
+    }));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run_loop(terminal, app)));
+    std::panic::set_hook(Box::new(move |info| previous(info)));
+    match result {
+        Ok(res) => res,
+        Err(payload) => {
+            let message = UI_PANIC
+                .lock()
+                .ok()
+                .and_then(|mut slot| slot.take())
+                .unwrap_or_else(|| panic_payload_message(payload.as_ref()));
+            Err(anyhow::anyhow!("memex tui crashed: {message}"))
+        }
+    }
+}
+
+/// Runs `load` on a worker thread, turning a panic into an error carrying
+/// its message so the request it serves gets an answer instead of staying
+/// loading.
+fn catch_panic<T>(load: impl FnOnce() -> Result<T>) -> Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(load)).unwrap_or_else(|payload| {
+        Err(anyhow::anyhow!(
+            "panicked: {}",
+            panic_payload_message(payload.as_ref())
+        ))
+    })
+}
+
+fn restore_terminal_after_panic() -> Result<()> {
+    terminal::disable_raw_mode()?;
+    let mut writer = open_tty()?;
+    execute!(writer, terminal::LeaveAlternateScreen, DisableMouseCapture)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn panic_payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
 fn exit_terminal(terminal: &mut TuiTerminal) -> Result<()> {
     terminal::disable_raw_mode()?;
     execute!(
@@ -5053,47 +9406,6 @@ fn exit_terminal(terminal: &mut TuiTerminal) -> Result<()> {
     Ok(())
 }
 
-fn summarize(text: &str, max: usize) -> String {
-    if max == 0 {
-        return String::new();
-    }
-    let mut out = String::new();
-    let mut count = 0usize;
-    let mut last_space = false;
-    let mut truncated = false;
-    for ch in text.chars() {
-        if count >= max {
-            truncated = true;
-            break;
-        }
-        if ch.is_whitespace() {
-            if out.is_empty() || last_space {
-                continue;
-            }
-            out.push(' ');
-            last_space = true;
-            count += 1;
-            continue;
-        }
-        out.push(ch);
-        last_space = false;
-        count += 1;
-    }
-    if truncated && max >= 3 {
-        let keep = max.saturating_sub(3);
-        let mut short = String::new();
-        for (i, ch) in out.chars().enumerate() {
-            if i >= keep {
-                break;
-            }
-            short.push(ch);
-        }
-        short.push_str("...");
-        return short.trim().to_string();
-    }
-    out.trim().to_string()
-}
-
 fn format_ts(ts: u64) -> String {
     if ts == 0 {
         return "-".to_string();
@@ -5104,6 +9416,23 @@ fn format_ts(ts: u64) -> String {
     dt.to_rfc3339_opts(SecondsFormat::Secs, true)
 }
 
+/// Wall-clock span in its largest whole unit: "45s", "12m", "3h", "2d".
+fn format_duration_compact(ms: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = MINUTE * 60;
+    const DAY: u64 = HOUR * 24;
+    let secs = ms / 1000;
+    if secs < MINUTE {
+        format!("{secs}s")
+    } else if secs < HOUR {
+        format!("{}m", secs / MINUTE)
+    } else if secs < DAY {
+        format!("{}h", secs / HOUR)
+    } else {
+        format!("{}d", secs / DAY)
+    }
+}
+
 fn format_relative_ts(ts: u64) -> String {
     let now = chrono::Utc::now().timestamp_millis();
     let now = u64::try_from(now).unwrap_or(0);
@@ -5140,13 +9469,8 @@ fn format_relative_ts_at(ts: u64, now: u64) -> String {
     }
 }
 
-fn now_ms() -> u64 {
-    let now = chrono::Utc::now().timestamp_millis();
-    u64::try_from(now).unwrap_or(0)
-}
-
 fn timeline_bounds(rows: &[ProjectTimelineRow], range: TimelineRange) -> (u64, u64) {
-    let now = now_ms();
+    let now = now_millis();
     let min_seen = rows
         .iter()
         .flat_map(|row| row.session_ts.iter())
@@ -5178,18 +9502,138 @@ fn format_day(ts: u64) -> String {
         .unwrap_or_else(|| "-".to_string())
 }
 
+fn draw_heatmap(frame: &mut ratatui::Frame, app: &App, theme: &Theme, area: Rect) {
+    use chrono::Datelike;
+
+    frame.render_widget(Block::default().style(theme.panel), area);
+    let inner = inset(area, PANEL_PAD_X, PANEL_PAD_X, PANEL_PAD_Y, PANEL_PAD_Y);
+    if app.heatmap_counts.is_empty() {
+        let message = match &app.heatmap_state {
+            LoadState::Loading => format!("{} Loading activity…", app.spinner()),
+            LoadState::Error(message) => format!("Couldn’t load activity: {message}"),
+            _ => "No records in the last year".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(Line::from(Span::styled(message, theme.muted))),
+            inner,
+        );
+        return;
+    }
+
+    // Columns are weeks (Monday first), ending with the current one.
+    let today = chrono::Local::now().date_naive();
+    let weeks =
+        (inner.width.saturating_sub(HEATMAP_LABEL_WIDTH) / 2).clamp(1, HEATMAP_WEEKS as u16) as i64;
+    let start = today
+        - chrono::Duration::days(
+            i64::from(today.weekday().num_days_from_monday()) + (weeks - 1) * 7,
+        );
+    let day_at = |week: i64, weekday: i64| start + chrono::Duration::days(week * 7 + weekday);
+    let count_on = |day: &chrono::NaiveDate| app.heatmap_counts.get(day).copied().unwrap_or(0);
+
+    let mut total = 0;
+    let mut busiest: Option<(chrono::NaiveDate, u64)> = None;
+    for offset in 0..=(today - start).num_days() {
+        let day = start + chrono::Duration::days(offset);
+        let count = count_on(&day);
+        total += count;
+        if count > 0 && busiest.is_none_or(|(_, most)| count > most) {
+            busiest = Some((day, count));
+        }
+    }
+    let max = busiest.map_or(0, |(_, count)| count);
+
+    let mut lines = Vec::with_capacity(11);
+    let mut header = vec![
+        Span::styled(format!("{total} records"), theme.text_bold),
+        Span::styled(format!(" in {weeks} weeks"), theme.muted),
+    ];
+    if let Some((day, count)) = busiest {
+        header.push(Span::styled("   busiest ", theme.muted));
+        header.push(Span::styled(
+            format!("{} ({count})", day.format("%Y-%m-%d")),
+            theme.text,
+        ));
+    }
+    header.push(Span::styled("   streak ", theme.muted));
+    header.push(Span::styled(
+        format!("{} days", heatmap_streak(&app.heatmap_counts, today)),
+        theme.text,
+    ));
+    lines.push(Line::from(header));
+    lines.push(Line::default());
+
+    let mut months = " ".repeat(HEATMAP_LABEL_WIDTH as usize);
+    let mut last_month = None;
+    for week in 0..weeks {
+        let month = day_at(week, 0).month();
+        let col = HEATMAP_LABEL_WIDTH as usize + week as usize * 2;
+        if last_month != Some(month) && months.len() <= col {
+            months.push_str(&" ".repeat(col - months.len()));
+            months.push_str(&day_at(week, 0).format("%b").to_string());
+        }
+        last_month = Some(month);
+    }
+    lines.push(Line::from(Span::styled(months, theme.muted)));
+
+    for weekday in 0..7 {
+        let label = match weekday {
+            0 => "Mon",
+            2 => "Wed",
+            4 => "Fri",
+            _ => "",
+        };
+        let mut spans = vec![Span::styled(
+            format!("{label:<width$}", width = HEATMAP_LABEL_WIDTH as usize),
+            theme.muted,
+        )];
+        for week in 0..weeks {
+            let day = day_at(week, weekday);
+            if day > today {
+                break;
+            }
+            let level = heatmap_level(count_on(&day), max);
+            let style = if level == 0 {
+                theme.muted
+            } else {
+                theme.accent
+            };
+            spans.push(Span::styled(format!("{} ", HEATMAP_GLYPHS[level]), style));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    lines.push(Line::default());
+    let mut legend = vec![Span::styled(
+        format!("{:width$}less ", "", width = HEATMAP_LABEL_WIDTH as usize),
+        theme.muted,
+    )];
+    for (level, glyph) in HEATMAP_GLYPHS.iter().enumerate() {
+        let style = if level == 0 {
+            theme.muted
+        } else {
+            theme.accent
+        };
+        legend.push(Span::styled(format!("{glyph} "), style));
+    }
+    legend.push(Span::styled("more", theme.muted));
+    lines.push(Line::from(legend));
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
 fn timeline_project_width(rows: &[ProjectTimelineRow], total_width: u16) -> u16 {
     let max_sessions = rows.iter().map(|row| row.session_count).max().unwrap_or(0);
     let significant_sessions = (max_sessions / 20).max(3);
     let mut widths: Vec<usize> = rows
         .iter()
         .filter(|row| row.session_count >= significant_sessions)
-        .map(|row| row.project.chars().count().saturating_add(1))
+        .map(|row| text::width(&row.project).saturating_add(1))
         .collect();
     if widths.is_empty() {
         widths = rows
             .iter()
-            .map(|row| row.project.chars().count().saturating_add(1))
+            .map(|row| text::width(&row.project).saturating_add(1))
             .collect();
     }
     let width = widths.iter().max().copied().unwrap_or(12);
@@ -5248,25 +9692,6 @@ fn timeline_density_level(count: usize, max: usize, levels: usize) -> usize {
     ((count * levels).saturating_add(max - 1)) / max
 }
 
-fn truncate_middle(value: &str, width: usize) -> String {
-    let len = value.chars().count();
-    if len <= width {
-        return value.to_string();
-    }
-    if width <= 1 {
-        return "…".to_string();
-    }
-    let keep = width.saturating_sub(1);
-    let head = keep / 2;
-    let tail = keep.saturating_sub(head);
-    let mut out = String::new();
-    out.extend(value.chars().take(head));
-    out.push('…');
-    let tail_chars: Vec<char> = value.chars().rev().take(tail).collect();
-    out.extend(tail_chars.into_iter().rev());
-    out
-}
-
 fn build_matchers(query: &str) -> Result<Vec<regex::Regex>> {
     let mut terms = Vec::new();
     let mut seen = std::collections::HashSet::new();
@@ -5290,20 +9715,93 @@ fn build_matchers(query: &str) -> Result<Vec<regex::Regex>> {
     Ok(out)
 }
 
-fn matches_any(text: &str, matchers: &[regex::Regex]) -> bool {
-    matchers.iter().any(|re| re.is_match(text))
+/// How many of the query's distinct terms `text` holds.
+fn matched_terms(text: &str, matchers: &[regex::Regex]) -> usize {
+    matchers.iter().filter(|re| re.is_match(text)).count()
 }
 
-fn append_records<'a, I>(lines: &mut Vec<PreviewLine>, records: I)
+fn append_records<'a, I>(lines: &mut Vec<PreviewLine>, records: I, max_chars: usize)
 where
     I: IntoIterator<Item = &'a Record>,
 {
     for record in records {
-        append_record(lines, record, false);
+        append_record(lines, record, false, max_chars);
+    }
+}
+
+/// Messages in `lines` whose text holds one of `terms`, or every one with
+/// `all_terms`, each pointing at its `Meta` line. Messages holding more of
+/// the terms come first.
+fn preview_matches(
+    lines: &[PreviewLine],
+    terms: &[Vec<char>],
+    all_terms: bool,
+) -> Vec<PreviewMatch> {
+    let mut messages = Vec::new();
+    if terms.is_empty() {
+        return messages;
+    }
+    let mut open: Option<PreviewMatch> = None;
+    for (at, line) in lines.iter().enumerate() {
+        let line = match line {
+            PreviewLine::Nested(inner) => inner.as_ref(),
+            line => line,
+        };
+        let text = match line {
+            PreviewLine::Meta { role, ts, .. } => {
+                messages.extend(open.take());
+                open = Some(PreviewMatch {
+                    line: at,
+                    role: role.clone(),
+                    ts: ts.clone(),
+                    text: String::new(),
+                });
+                continue;
+            }
+            // Related sessions follow the transcript.
+            PreviewLine::Related { .. } => {
+                messages.extend(open.take());
+                continue;
+            }
+            PreviewLine::Text(text) | PreviewLine::Diff(text) => text.clone(),
+            PreviewLine::Code(tokens) => tokens.iter().map(|(_, token)| token.as_str()).collect(),
+            _ => continue,
+        };
+        if let Some(found) = open.as_mut() {
+            if !found.text.is_empty() {
+                found.text.push(' ');
+            }
+            found.text.push_str(text.trim());
+        }
+    }
+    messages.extend(open);
+    let needed = if all_terms { terms.len() } else { 1 };
+    let mut matches: Vec<(usize, PreviewMatch)> = messages
+        .into_iter()
+        .map(|found| {
+            let chars: Vec<char> = found.text.chars().collect();
+            let count = terms
+                .iter()
+                .filter(|term| find_term(&chars, term, 0).is_some())
+                .count();
+            (count, found)
+        })
+        .filter(|(count, _)| *count >= needed)
+        .collect();
+    matches.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+    matches.into_iter().map(|(_, found)| found).collect()
+}
+
+fn preview_line_doc_id(line: &PreviewLine) -> Option<u64> {
+    match line {
+        PreviewLine::Meta { doc_id, .. } => Some(*doc_id),
+        PreviewLine::Nested(inner) => preview_line_doc_id(inner),
+        _ => None,
     }
 }
 
-fn append_record(lines: &mut Vec<PreviewLine>, record: &Record, highlight: bool) {
+/// Appends `record` with its text cut off after `max_chars` characters.
+fn append_record(lines: &mut Vec<PreviewLine>, record: &Record, highlight: bool, max_chars: usize) {
     let role = if record.role.is_empty() {
         "unknown"
     } else {
@@ -5311,13 +9809,26 @@ fn append_record(lines: &mut Vec<PreviewLine>, record: &Record, highlight: bool)
     };
     let ts = format_ts(record.ts);
     lines.push(PreviewLine::Meta {
+        doc_id: record.doc_id,
         role: role.to_string(),
         ts,
         highlight,
     });
+    if record.role == COMPACT_SUMMARY_ROLE {
+        lines.push(PreviewLine::Compaction(sanitize_preview_lines(
+            record.text.trim(),
+        )));
+        lines.push(PreviewLine::Empty);
+        return;
+    }
+    if let Some(patch) = record_patch(record) {
+        append_diff(lines, &patch, max_chars);
+        lines.push(PreviewLine::Empty);
+        return;
+    }
     let preview_text = record_preview_text(record);
-    let text = if preview_text.len() > MAX_MESSAGE_CHARS {
-        let trimmed = summarize(&preview_text, MAX_MESSAGE_CHARS);
+    let text = if text::width(&preview_text) > max_chars {
+        let trimmed = summarize(&preview_text, max_chars);
         Cow::Owned(format!("{trimmed} …"))
     } else {
         preview_text
@@ -5330,9 +9841,159 @@ fn append_record(lines: &mut Vec<PreviewLine>, record: &Record, highlight: bool)
             lines.push(PreviewLine::Text(line));
         }
     }
+    if !record.attachments.is_empty() {
+        lines.push(PreviewLine::Attachments(record.attachments.clone()));
+    }
     lines.push(PreviewLine::Empty);
 }
 
+fn record_patch(record: &Record) -> Option<String> {
+    if !is_tool_role(&record.role) {
+        return None;
+    }
+    let input = record.tool_input.as_deref()?;
+    crate::patch::patch_text(record.tool_name.as_deref(), input)
+}
+
+/// Whole lines of `patch` up to `max_chars`; unlike other text,
+/// indentation is kept.
+fn append_diff(lines: &mut Vec<PreviewLine>, patch: &str, max_chars: usize) {
+    let mut used = 0;
+    for line in sanitize_preview_lines(patch) {
+        used += text::width(&line) + 1;
+        if used > max_chars {
+            lines.push(PreviewLine::Text("…".to_string()));
+            return;
+        }
+        lines.push(PreviewLine::Diff(line));
+    }
+}
+
+/// Fence state while scanning a record's lines.
+enum CodeBlock {
+    Code(Highlighter),
+    Diff,
+    /// A language memex doesn't highlight.
+    Plain,
+}
+
+/// Text of a transcript line, nested or not.
+fn preview_line_text(line: &PreviewLine) -> Option<&str> {
+    match line {
+        PreviewLine::Text(text) => Some(text),
+        PreviewLine::Nested(inner) => preview_line_text(inner),
+        _ => None,
+    }
+}
+
+fn is_fence(text: &str) -> bool {
+    let text = text.trim_start();
+    text.starts_with("```") || text.starts_with("~~~")
+}
+
+/// Turns lines inside fenced code blocks into highlighted `Code` lines, or
+/// `Diff` lines for ```` ```diff ```` blocks. Untagged blocks get a guessed
+/// language; fences never carry over from one record to the next.
+fn highlight_code_blocks(lines: Vec<PreviewLine>) -> Vec<PreviewLine> {
+    let mut styled: Vec<Option<PreviewLine>> = vec![None; lines.len()];
+    let mut block: Option<CodeBlock> = None;
+    for (at, line) in lines.iter().enumerate() {
+        let Some(text) = preview_line_text(line) else {
+            if matches!(line, PreviewLine::Meta { .. })
+                || matches!(line, PreviewLine::Nested(inner) if matches!(**inner, PreviewLine::Meta { .. }))
+            {
+                block = None;
+            }
+            continue;
+        };
+        if is_fence(text) {
+            if block.take().is_none() {
+                let info = text.trim_start()[3..].trim();
+                let body = lines[at + 1..]
+                    .iter()
+                    .map_while(preview_line_text)
+                    .take_while(|text| !is_fence(text));
+                block = Some(if info.eq_ignore_ascii_case("diff") || info == "patch" {
+                    CodeBlock::Diff
+                } else {
+                    match lang_for_fence(info).or_else(|| {
+                        if info.is_empty() {
+                            guess_lang(body)
+                        } else {
+                            None
+                        }
+                    }) {
+                        Some(lang) => CodeBlock::Code(Highlighter::new(lang)),
+                        None => CodeBlock::Plain,
+                    }
+                });
+            }
+            continue;
+        }
+        styled[at] = match &mut block {
+            Some(CodeBlock::Code(highlighter)) => Some(PreviewLine::Code(highlighter.line(text))),
+            Some(CodeBlock::Diff) => Some(PreviewLine::Diff(text.to_string())),
+            Some(CodeBlock::Plain) | None => None,
+        };
+    }
+    lines
+        .into_iter()
+        .zip(styled)
+        .map(|(line, styled)| match (line, styled) {
+            (PreviewLine::Nested(_), Some(styled)) => PreviewLine::Nested(Box::new(styled)),
+            (line, styled) => styled.unwrap_or(line),
+        })
+        .collect()
+}
+
+/// Replaces folded compaction summaries with their text when `expand` is
+/// set; folded ones stay a single line either way.
+fn unfold_compactions(lines: Vec<PreviewLine>, expand: bool) -> Vec<PreviewLine> {
+    if !expand {
+        return lines;
+    }
+    let mut out = Vec::with_capacity(lines.len());
+    for line in lines {
+        match line {
+            PreviewLine::Compaction(text) => out.extend(text.into_iter().map(PreviewLine::Text)),
+            PreviewLine::Nested(inner) => match *inner {
+                PreviewLine::Compaction(text) => out.extend(
+                    text.into_iter()
+                        .map(|line| PreviewLine::Nested(Box::new(PreviewLine::Text(line)))),
+                ),
+                inner => out.push(PreviewLine::Nested(Box::new(inner))),
+            },
+            line => out.push(line),
+        }
+    }
+    out
+}
+
+/// "📎 2 attachments  shot.png, spec.pdf (1.2K)": count, then names with
+/// sizes where the transcript recorded them.
+fn attachments_summary(attachments: &[Attachment]) -> (String, String) {
+    let count = match attachments.len() {
+        1 => "📎 1 attachment".to_string(),
+        n => format!("📎 {n} attachments"),
+    };
+    let names = attachments
+        .iter()
+        .map(|attachment| {
+            let name = attachment
+                .filename
+                .clone()
+                .or_else(|| attachment.media_type.clone())
+                .unwrap_or_else(|| attachment.kind.clone());
+            match attachment.size {
+                Some(size) => format!("{name} ({})", compact_metric(size)),
+                None => name,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    (count, strip_ansi_and_controls(&names))
+}
+
 fn sanitize_preview_lines(text: &str) -> Vec<String> {
     text.split('\n').map(strip_ansi_and_controls).collect()
 }
@@ -5347,7 +10008,7 @@ fn record_preview_text(record: &Record) -> Cow<'_, str> {
 }
 
 fn pretty_json_text(text: &str) -> Option<String> {
-    if text.len() > MAX_MESSAGE_CHARS {
+    if text.len() > PRETTY_JSON_MAX_BYTES {
         return None;
     }
     let trimmed = text.trim();
@@ -5452,15 +10113,45 @@ fn role_color(role: &str) -> Color {
         "assistant" => Color::Rgb(160, 180, 200),
         "system" => Color::Rgb(170, 150, 200),
         "tool_use" | "tool_result" | "tool" => Color::Rgb(150, 180, 150),
+        COMPACT_SUMMARY_ROLE => Color::Rgb(200, 175, 120),
         _ => COLOR_MUTED,
     }
 }
 
-fn render_preview_line<'a>(line: &'a PreviewLine, theme: &Theme) -> Line<'a> {
-    match line {
-        PreviewLine::SessionHeader {
-            project,
-            source,
+fn code_style(kind: TokenKind, theme: &Theme) -> Style {
+    match kind {
+        TokenKind::Plain => theme.text,
+        TokenKind::Keyword => Style::default().fg(COLOR_CODE_KEYWORD),
+        TokenKind::String => Style::default().fg(COLOR_CODE_STRING),
+        TokenKind::Number => Style::default().fg(COLOR_CODE_NUMBER),
+        TokenKind::Comment => theme.muted.add_modifier(Modifier::ITALIC),
+        TokenKind::Type => Style::default().fg(COLOR_CODE_TYPE),
+    }
+}
+
+fn diff_style(line: &str, theme: &Theme) -> Style {
+    if line.starts_with("***")
+        || line.starts_with("+++ ")
+        || line.starts_with("--- ")
+        || line.starts_with("diff ")
+    {
+        theme.text_bold
+    } else if line.starts_with("@@") {
+        theme.accent
+    } else if line.starts_with('+') {
+        Style::default().fg(COLOR_DIFF_ADD)
+    } else if line.starts_with('-') {
+        Style::default().fg(COLOR_DIFF_DEL)
+    } else {
+        theme.text
+    }
+}
+
+fn render_preview_line<'a>(line: &'a PreviewLine, theme: &Theme) -> Line<'a> {
+    match line {
+        PreviewLine::SessionHeader {
+            project,
+            source,
             session_id,
         } => Line::from(vec![
             Span::styled("project ", theme.muted),
@@ -5472,10 +10163,66 @@ fn render_preview_line<'a>(line: &'a PreviewLine, theme: &Theme) -> Line<'a> {
             Span::styled("session ", theme.muted),
             Span::styled(session_id.as_str(), theme.text),
         ]),
+        PreviewLine::Settings(settings) => {
+            let mut spans = Vec::new();
+            for (label, value) in [
+                ("model ", &settings.model),
+                ("sandbox ", &settings.sandbox),
+                ("approval ", &settings.approval_policy),
+            ] {
+                let Some(value) = value else {
+                    continue;
+                };
+                if !spans.is_empty() {
+                    spans.push(Span::raw("  "));
+                }
+                spans.push(Span::styled(label, theme.muted));
+                spans.push(Span::styled(value.as_str(), theme.text));
+            }
+            Line::from(spans)
+        }
+        PreviewLine::Note(text) => Line::from(vec![
+            Span::styled("\u{270e} note ", theme.focus),
+            Span::styled(text.as_str(), theme.text_bold),
+        ]),
+        PreviewLine::Outcome(outcome) => Line::from(vec![
+            Span::styled("\u{25c6} outcome ", theme.focus),
+            Span::styled(outcome.label(), theme.text_bold),
+        ]),
+        PreviewLine::Todo(item) => {
+            let (marker, style) = if item.done() {
+                ("\u{2611} ", theme.muted)
+            } else if item.in_progress() {
+                ("\u{25b6} ", theme.text_bold)
+            } else {
+                ("\u{2610} ", theme.text)
+            };
+            let text = match &item.active_form {
+                Some(active) if item.in_progress() => active.as_str(),
+                _ => item.content.as_str(),
+            };
+            Line::from(vec![
+                Span::styled(marker, theme.accent),
+                Span::styled(text, style),
+            ])
+        }
+        PreviewLine::Translation(text) => Line::from(vec![
+            Span::styled("\u{21c4} ", theme.accent),
+            Span::styled(text.as_str(), theme.muted),
+        ]),
+        PreviewLine::Attachments(attachments) => {
+            let (count, names) = attachments_summary(attachments);
+            Line::from(vec![
+                Span::styled(count, theme.accent),
+                Span::raw("  "),
+                Span::styled(names, theme.muted),
+            ])
+        }
         PreviewLine::Meta {
             role,
             ts,
             highlight,
+            ..
         } => {
             let meta_style = if *highlight {
                 Style::default().fg(COLOR_ACCENT)
@@ -5493,10 +10240,131 @@ fn render_preview_line<'a>(line: &'a PreviewLine, theme: &Theme) -> Line<'a> {
             ])
         }
         PreviewLine::Text(text) => Line::from(Span::raw(text.as_str())),
+        PreviewLine::Diff(text) => Line::from(Span::styled(text.as_str(), diff_style(text, theme))),
+        PreviewLine::Code(tokens) => Line::from(
+            tokens
+                .iter()
+                .map(|(kind, text)| Span::styled(text.as_str(), code_style(*kind, theme)))
+                .collect::<Vec<_>>(),
+        ),
+        PreviewLine::Compaction(text) => Line::from(vec![
+            Span::styled("▸ compacted context", theme.accent),
+            Span::styled(
+                format!(" · {} lines · z to expand", text.len()),
+                theme.muted,
+            ),
+        ]),
+        PreviewLine::ToolGroup(group) => {
+            let noun = if group.calls == 1 {
+                "tool call"
+            } else {
+                "tool calls"
+            };
+            let (marker, hint) = if group.expanded > 0 {
+                ("▾ ", "enter to collapse")
+            } else {
+                ("", "press enter to expand")
+            };
+            Line::from(vec![
+                Span::styled(format!("{marker}⚙ {} {noun}", group.calls), theme.accent),
+                Span::styled(format!(" — {hint}"), theme.muted),
+            ])
+        }
+        PreviewLine::EarlierHistory(count) => Line::from(vec![
+            Span::styled(format!("↑ {count} earlier messages"), theme.accent),
+            Span::styled(" — scroll up to load", theme.muted),
+        ]),
+        PreviewLine::ThreadHeader { kind, thread } => Line::from(vec![
+            Span::styled("↳ ", theme.accent),
+            Span::styled(kind.as_str(), theme.accent),
+            Span::raw(" "),
+            Span::styled(thread.as_str(), theme.muted),
+        ]),
+        PreviewLine::Nested(inner) => {
+            let mut line = render_preview_line(inner, theme);
+            line.spans.insert(0, Span::styled("│ ", theme.muted));
+            line
+        }
+        PreviewLine::Related {
+            key,
+            label,
+            project,
+            ts,
+        } => Line::from(vec![
+            Span::styled(format!("{key} "), theme.accent),
+            Span::styled(label.as_str(), theme.text),
+            Span::raw("  "),
+            Span::styled(project.as_str(), theme.muted),
+            Span::raw(" "),
+            Span::styled(ts.as_str(), theme.muted),
+        ]),
         PreviewLine::Empty => Line::from(""),
     }
 }
 
+/// `line` with its styles as ANSI SGR escapes, or as plain text.
+fn ansi_line(line: &Line, color: bool) -> String {
+    let mut out = String::new();
+    for span in &line.spans {
+        let codes = if color {
+            sgr_codes(span.style)
+        } else {
+            Vec::new()
+        };
+        if codes.is_empty() {
+            out.push_str(&span.content);
+        } else {
+            out.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), span.content));
+        }
+    }
+    out
+}
+
+fn sgr_codes(style: Style) -> Vec<String> {
+    let mut codes = Vec::new();
+    for (modifier, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+    ] {
+        if style.add_modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+    codes.extend(style.fg.and_then(|color| ansi_color(color, 30)));
+    codes.extend(style.bg.and_then(|color| ansi_color(color, 40)));
+    codes
+}
+
+/// SGR parameters for `color` as a foreground (`base` 30) or background
+/// (`base` 40); `None` for the terminal's default.
+fn ansi_color(color: Color, base: u8) -> Option<String> {
+    let named = |offset: u8| Some((base + offset).to_string());
+    match color {
+        Color::Reset => None,
+        Color::Black => named(0),
+        Color::Red => named(1),
+        Color::Green => named(2),
+        Color::Yellow => named(3),
+        Color::Blue => named(4),
+        Color::Magenta => named(5),
+        Color::Cyan => named(6),
+        Color::Gray => named(7),
+        Color::DarkGray => named(60),
+        Color::LightRed => named(61),
+        Color::LightGreen => named(62),
+        Color::LightYellow => named(63),
+        Color::LightBlue => named(64),
+        Color::LightMagenta => named(65),
+        Color::LightCyan => named(66),
+        Color::White => named(67),
+        Color::Indexed(index) => Some(format!("{};5;{index}", base + 8)),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{r};{g};{b}", base + 8)),
+    }
+}
+
 fn strip_ansi_and_controls(line: &str) -> String {
     let mut out = String::with_capacity(line.len());
     let mut chars = line.chars().peekable();
@@ -5628,7 +10496,10 @@ const WHEEL_SCROLL_LINES: isize = 3;
 
 /// Returns whether the event changed any visible state; pure motion events
 /// return false so the caller can skip redrawing.
-fn handle_mouse(mouse: MouseEvent, terminal: &mut TuiTerminal, app: &mut App) -> Result<bool> {
+fn handle_mouse(mouse: MouseEvent, app: &mut App) -> Result<bool> {
+    if app.resume_prompt.is_some() || app.note_prompt.is_some() || app.palette.is_some() {
+        return Ok(false);
+    }
     if app.quick_popup {
         return Ok(match mouse.kind {
             MouseEventKind::ScrollDown => {
@@ -5653,8 +10524,15 @@ fn handle_mouse(mouse: MouseEvent, terminal: &mut TuiTerminal, app: &mut App) ->
             _ => false,
         });
     }
+    if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
+        let pos = ratatui::layout::Position::new(mouse.column, mouse.row);
+        if let Some(idx) = app.pinned_areas.iter().position(|area| area.contains(pos)) {
+            app.activate_pinned_query(idx);
+            return Ok(true);
+        }
+    }
     if app.layout_mode == LayoutMode::Home {
-        return handle_home_mouse(mouse, terminal, app);
+        return handle_home_mouse(mouse, app);
     }
     match mouse.kind {
         MouseEventKind::Down(MouseButton::Left) => {
@@ -5742,7 +10620,7 @@ fn handle_mouse(mouse: MouseEvent, terminal: &mut TuiTerminal, app: &mut App) ->
     }
 }
 
-fn handle_home_mouse(mouse: MouseEvent, terminal: &mut TuiTerminal, app: &mut App) -> Result<bool> {
+fn handle_home_mouse(mouse: MouseEvent, app: &mut App) -> Result<bool> {
     if app.home_dropdown != HomeDropdown::None {
         return Ok(match mouse.kind {
             MouseEventKind::Down(MouseButton::Left) => {
@@ -5785,7 +10663,7 @@ fn handle_home_mouse(mouse: MouseEvent, terminal: &mut TuiTerminal, app: &mut Ap
                 if idx < app.results.len() {
                     // First click selects; a second click on the selected row resumes.
                     if app.selected.selected() == Some(idx) && matches!(app.focus, Focus::List) {
-                        app.resume_selected(terminal)?;
+                        app.resume_selected();
                     } else {
                         app.selected.select(Some(idx));
                         app.focus = Focus::List;
@@ -5893,11 +10771,11 @@ fn query_bar_focus_from_mouse(app: &App, x: u16) -> Focus {
 
 fn query_bar_field_width(label: &str, value: &str, placeholder: &str, active: bool) -> u16 {
     let value_width = if active {
-        value.chars().count().saturating_add(1)
+        text::width(value).saturating_add(1)
     } else if value.is_empty() {
-        placeholder.chars().count()
+        text::width(placeholder)
     } else {
-        value.chars().count()
+        text::width(value)
     };
     label
         .chars()
@@ -5929,6 +10807,21 @@ fn quick_popup_area(area: Rect) -> Rect {
     }
 }
 
+fn resume_prompt_area(area: Rect) -> Rect {
+    let width = area
+        .width
+        .saturating_mul(4)
+        .saturating_div(5)
+        .clamp(40, 120);
+    let height = 9;
+    Rect {
+        x: area.x + area.width.saturating_sub(width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width: width.min(area.width),
+        height: height.min(area.height),
+    }
+}
+
 fn quick_popup_content_height(area: Rect) -> u16 {
     let popup = quick_popup_area(area);
     let inner = panel_inner(popup);
@@ -6032,7 +10925,117 @@ fn parse_copilot_workspace_cwd(contents: &str) -> CopilotWorkspaceCwd {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{RecordLinks, SourceKind};
+    use crate::types::SourceKind;
+
+    fn resume_session() -> SessionSummary {
+        SessionSummary {
+            session_id: "abc-123".to_string(),
+            project: "memex".to_string(),
+            source: SourceKind::Claude,
+            last_ts: 0,
+            hit_count: 1,
+            message_count: 0,
+            duration_ms: 0,
+            top_score: 1.0,
+            snippet: String::new(),
+            title: String::new(),
+            source_path: "/logs/abc-123.jsonl".to_string(),
+            source_dir: "/logs".to_string(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn edited_resume_command_templatizes_back_to_placeholders() {
+        let session = resume_session();
+        let cwd = "/work/my project";
+        let template = "cd {cwd_shell} && claude --resume {session_id}";
+        let command = expand_resume_template(template, &session, cwd);
+        let edited = format!("{command} --verbose");
+
+        assert_eq!(
+            templatize_resume_command(&edited, &session, cwd),
+            "cd {cwd_shell} && claude --resume {session_id} --verbose"
+        );
+    }
+
+    #[test]
+    fn resume_command_changes_into_session_cwd_unless_template_does() {
+        let session = resume_session();
+        let cwd = "/work/my project";
+        let cd = change_dir_command();
+
+        assert_eq!(
+            resume_command("codex resume {session_id}", &session, cwd, true),
+            format!("{cd} {} && codex resume abc-123", shell_quote(cwd))
+        );
+        assert_eq!(
+            resume_command("codex resume {session_id}", &session, cwd, false),
+            "codex resume abc-123"
+        );
+        assert_eq!(
+            resume_command(
+                "cd {cwd_shell}; codex resume {session_id}",
+                &session,
+                cwd,
+                true
+            ),
+            format!("cd {}; codex resume abc-123", shell_quote(cwd))
+        );
+    }
+
+    #[test]
+    fn resume_prompt_edits_at_the_cursor() {
+        let prompt = ResumePrompt::new(resume_session(), "/".to_string(), "clade".to_string());
+        let mut input = prompt.command;
+        input.move_cursor(-2);
+        input.insert('u');
+        assert_eq!(input.text, "claude");
+        input.cursor = 0;
+        input.delete();
+        input.move_cursor(10);
+        input.backspace();
+        assert_eq!(input.text, "laud");
+        assert_eq!(input.cursor, 4);
+        assert!(input.handle_key(KeyCode::Home));
+        assert!(input.handle_key(KeyCode::Char('c')));
+        assert!(!input.handle_key(KeyCode::Tab));
+        assert_eq!(input.text, "claud");
+    }
+
+    #[test]
+    fn panic_payload_message_reads_str_and_string_payloads() {
+        let payload = std::panic::catch_unwind(|| panic!("static message")).unwrap_err();
+        assert_eq!(panic_payload_message(payload.as_ref()), "static message");
+        let payload = std::panic::catch_unwind(|| panic!("{} {}", "formatted", 1)).unwrap_err();
+        assert_eq!(panic_payload_message(payload.as_ref()), "formatted 1");
+    }
+
+    #[test]
+    fn a_panicking_background_task_answers_with_an_error() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tasks = BackgroundTasks::new(1, tx);
+        tasks.spawn(
+            || panic!("analytics store vanished"),
+            |message| SearchUpdate::ProjectsError {
+                request_id: 7,
+                message,
+            },
+        );
+        match rx.recv_timeout(Duration::from_secs(5)).expect("update") {
+            SearchUpdate::ProjectsError {
+                request_id,
+                message,
+            } => {
+                assert_eq!(request_id, 7);
+                assert_eq!(message, "analytics store vanished");
+            }
+            _ => panic!("expected a projects error"),
+        }
+        assert!(catch_panic(|| -> Result<()> { panic!("search blew up") }).is_err());
+    }
 
     fn test_app() -> (tempfile::TempDir, App) {
         let tmp = tempfile::tempdir().expect("tempdir");
@@ -6043,9 +11046,12 @@ mod tests {
         let (search_tx, search_rx) = std::sync::mpsc::channel();
         let (search_request_tx, search_request_rx) = std::sync::mpsc::channel();
         let (detail_tx, _detail_rx) = std::sync::mpsc::channel();
+        let latest_search = LatestSearch::default();
         spawn_search_worker(
             paths.clone(),
+            ProjectAliases::default(),
             index.clone(),
+            latest_search.clone(),
             search_request_rx,
             search_tx.clone(),
         );
@@ -6059,6 +11065,7 @@ mod tests {
                 search_tx,
                 search_rx,
                 search_request_tx,
+                latest_search,
                 detail_tx,
             },
         );
@@ -6069,20 +11076,720 @@ mod tests {
         Record {
             source: SourceKind::CodexSession,
             doc_id: 1,
-            ts: 0,
             project: "project".to_string(),
             session_id: "session".to_string(),
             turn_id: 1,
             role: role.to_string(),
             text: text.to_string(),
-            tool_name: None,
-            tool_input: None,
-            tool_output: None,
-            links: RecordLinks::default(),
             source_path: "source.jsonl".to_string(),
+            ..Default::default()
         }
     }
 
+    #[test]
+    fn project_history_interleaves_sessions_with_a_header_per_switch() {
+        let (_tmp, app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        let mut records = Vec::new();
+        for (doc_id, session_id, ts) in [(1, "a", 1_000), (2, "b", 2_000), (3, "a", 3_000)] {
+            let mut record = record("user", &format!("message {doc_id}"));
+            record.doc_id = doc_id;
+            record.turn_id = doc_id as u32;
+            record.session_id = session_id.to_string();
+            record.ts = ts;
+            app.index.add_record(&mut writer, &record).expect("add");
+            records.push(record);
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, records[2].clone(), &[]);
+        let session = sessions.remove("a").expect("session");
+        let lines = build_detail_lines(
+            &app.index,
+            &session,
+            PreviewMode::Project,
+            "",
+            false,
+            false,
+            PreviewLimits::default(),
+        )
+        .expect("lines");
+        assert!(matches!(&lines[0], PreviewLine::Text(text) if text.contains("2 sessions")));
+        let headers: Vec<&str> = lines
+            .iter()
+            .filter_map(|line| match line {
+                PreviewLine::SessionHeader { session_id, .. } => Some(session_id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(headers, ["a", "b", "a"]);
+    }
+
+    #[test]
+    fn hidden_tool_runs_fold_into_groups_that_expand_in_place() {
+        let (_tmp, mut app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        let mut records = Vec::new();
+        for (doc_id, role) in [
+            (1, "user"),
+            (2, "tool_use"),
+            (3, "tool_result"),
+            (4, "tool_use"),
+            (5, "tool_result"),
+            (6, "assistant"),
+        ] {
+            let mut record = record(role, &format!("message {doc_id}"));
+            record.doc_id = doc_id;
+            record.turn_id = doc_id as u32;
+            app.index.add_record(&mut writer, &record).expect("add");
+            records.push(record);
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, records[5].clone(), &[]);
+        let session = sessions.remove("session").expect("session");
+        let lines = build_detail_lines(
+            &app.index,
+            &session,
+            PreviewMode::History,
+            "",
+            false,
+            false,
+            PreviewLimits::default(),
+        )
+        .expect("lines");
+        let at = lines
+            .iter()
+            .position(|line| matches!(line, PreviewLine::ToolGroup(_)))
+            .expect("tool group");
+        assert!(matches!(&lines[at], PreviewLine::ToolGroup(group) if group.calls == 2));
+        // The run sits between the messages around it.
+        assert!(
+            lines[..at]
+                .iter()
+                .any(|line| preview_line_doc_id(line) == Some(1))
+        );
+        assert!(
+            lines[at..]
+                .iter()
+                .any(|line| preview_line_doc_id(line) == Some(6))
+        );
+
+        let folded = lines.len();
+        app.detail_lines = lines;
+        app.detail_scroll = at;
+        app.toggle_tool_group();
+        assert!(app.detail_lines.len() > folded);
+        assert_eq!(preview_line_doc_id(&app.detail_lines[at + 1]), Some(2));
+        app.detail_scroll = at + 2;
+        app.toggle_tool_group();
+        assert_eq!(app.detail_lines.len(), folded);
+        assert_eq!(app.detail_scroll, at);
+
+        let shown = build_detail_lines(
+            &app.index,
+            &session,
+            PreviewMode::History,
+            "",
+            true,
+            false,
+            PreviewLimits::default(),
+        )
+        .expect("lines");
+        assert!(
+            !shown
+                .iter()
+                .any(|line| matches!(line, PreviewLine::ToolGroup(_)))
+        );
+    }
+
+    #[test]
+    fn matches_preview_shows_each_hits_whole_exchange() {
+        let (_tmp, app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        let mut records = Vec::new();
+        for (doc_id, role, text) in [
+            (1, "user", "set up ci"),
+            (2, "assistant", "done"),
+            (3, "user", "the needle test is flaky"),
+            (4, "assistant", "looking at it"),
+            (5, "tool_use", "cargo test"),
+            (6, "assistant", "fixed the race"),
+            (7, "user", "and the docs?"),
+            (8, "assistant", "updated, needle included"),
+        ] {
+            let mut record = record(role, text);
+            record.doc_id = doc_id;
+            record.turn_id = doc_id as u32;
+            record.ts = doc_id * 1000;
+            app.index.add_record(&mut writer, &record).expect("add");
+            records.push(record);
+        }
+        for turn in crate::turns::build_turns(&records) {
+            app.index.add_record(&mut writer, &turn).expect("add turn");
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, records[7].clone(), &[]);
+        let session = sessions.remove("session").expect("session");
+        let limits = PreviewLimits {
+            match_context: None,
+            ..PreviewLimits::default()
+        };
+
+        let doc_ids: Vec<u64> = build_detail_lines(
+            &app.index,
+            &session,
+            PreviewMode::Matches,
+            "needle",
+            false,
+            false,
+            limits,
+        )
+        .expect("lines")
+        .iter()
+        .filter_map(preview_line_doc_id)
+        .collect();
+        assert_eq!(doc_ids, [3, 4, 6, 7, 8]);
+    }
+
+    #[test]
+    fn matches_preview_ranks_by_terms_held_and_can_require_all() {
+        let (_tmp, app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        for (doc_id, role, text) in [
+            (1, "user", "the needle test"),
+            (2, "assistant", "the test passes"),
+            (3, "user", "needle in the flaky test"),
+            (4, "assistant", "unrelated"),
+        ] {
+            let mut record = record(role, text);
+            record.doc_id = doc_id;
+            record.turn_id = doc_id as u32;
+            record.ts = doc_id * 1000;
+            app.index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, record("user", "the needle test"), &[]);
+        let session = sessions.remove("session").expect("session");
+        let shown = |mode, all_terms| -> Vec<u64> {
+            let limits = PreviewLimits {
+                match_context: Some(0),
+                all_terms,
+                ..PreviewLimits::default()
+            };
+            let lines = build_detail_lines(
+                &app.index,
+                &session,
+                mode,
+                "flaky needle test",
+                false,
+                false,
+                limits,
+            )
+            .expect("lines");
+            if mode == PreviewMode::Matches {
+                return lines.iter().filter_map(preview_line_doc_id).collect();
+            }
+            preview_matches(&lines, &query_terms("flaky needle test"), all_terms)
+                .iter()
+                .filter_map(|found| preview_line_doc_id(&lines[found.line]))
+                .collect()
+        };
+
+        assert_eq!(shown(PreviewMode::Matches, false), [3, 1, 2]);
+        assert_eq!(shown(PreviewMode::Matches, true), [3]);
+        assert_eq!(shown(PreviewMode::History, false), [3, 1, 2]);
+        assert_eq!(shown(PreviewMode::History, true), [3]);
+    }
+
+    #[test]
+    fn split_preview_jumps_through_matches_in_the_full_history() {
+        let (_tmp, mut app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        let mut records = Vec::new();
+        for (doc_id, role, text) in [
+            (1, "user", "set up ci"),
+            (2, "assistant", "done"),
+            (3, "user", "the needle test is flaky"),
+            (4, "assistant", "looking at it"),
+            (5, "assistant", "fixed, the Needle was a race"),
+        ] {
+            let mut record = record(role, text);
+            record.doc_id = doc_id;
+            record.turn_id = doc_id as u32;
+            record.ts = doc_id * 1000;
+            app.index.add_record(&mut writer, &record).expect("add");
+            records.push(record);
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, records[4].clone(), &[]);
+        let session = sessions.remove("session").expect("session");
+        let lines = build_detail_lines(
+            &app.index,
+            &session,
+            PreviewMode::History,
+            "",
+            false,
+            false,
+            PreviewLimits::default(),
+        )
+        .expect("lines");
+
+        app.split_preview = true;
+        app.query = "needle".to_string();
+        app.active_detail_request = 3;
+        app.handle_search_update(SearchUpdate::DetailResults {
+            request_id: 3,
+            lines,
+            related: Vec::new(),
+        });
+
+        let found: Vec<Option<u64>> = app
+            .preview_matches
+            .iter()
+            .map(|found| preview_line_doc_id(&app.detail_lines[found.line]))
+            .collect();
+        assert_eq!(found, [Some(3), Some(5)]);
+        assert_eq!(app.detail_scroll, app.preview_matches[0].line);
+        assert_eq!(app.focused_message(), Some(3));
+
+        app.move_preview_match(1);
+        assert_eq!(app.focused_message(), Some(5));
+        app.move_preview_match(1);
+        assert_eq!(app.preview_match_state.selected(), Some(1));
+
+        app.toggle_preview_mode();
+        assert!(!app.split_preview_active());
+        assert_eq!(app.preview_mode, PreviewMode::Matches);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn translations_follow_messages_in_other_languages() {
+        let (_tmp, app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        let mut records = Vec::new();
+        for (doc_id, role, text) in [
+            (
+                1,
+                "user",
+                "Kannst du bitte prüfen, warum der Test nicht durchläuft?",
+            ),
+            (
+                2,
+                "assistant",
+                "The test is flaky because of a race in the setup.",
+            ),
+        ] {
+            let mut record = record(role, text);
+            record.doc_id = doc_id;
+            record.turn_id = doc_id as u32;
+            app.index.add_record(&mut writer, &record).expect("add");
+            records.push(record);
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, records[1].clone(), &[]);
+        let session = sessions.remove("session").expect("session");
+        let lines = build_detail_lines(
+            &app.index,
+            &session,
+            PreviewMode::History,
+            "",
+            false,
+            false,
+            PreviewLimits::default(),
+        )
+        .expect("lines");
+
+        let translator = Translator {
+            command: "echo 'can you check why the test fails?'".to_string(),
+            to: "en".to_string(),
+        };
+        let mut cache = HashMap::new();
+        let mut failed = false;
+        let translated =
+            translate_preview_lines(&app.index, &translator, &mut cache, &mut failed, lines);
+        let translations: Vec<&str> = translated
+            .iter()
+            .filter_map(|line| match line {
+                PreviewLine::Translation(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(translations, ["de→en: can you check why the test fails?"]);
+        let at = translated
+            .iter()
+            .position(|line| matches!(line, PreviewLine::Translation(_)))
+            .expect("translation");
+        assert!(matches!(translated[at + 1], PreviewLine::Empty));
+        assert_eq!(cache.len(), 2);
+        assert!(!failed);
+    }
+
+    #[test]
+    fn preview_limits_set_tail_context_and_message_length() {
+        let (_tmp, app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        let mut last = None;
+        for doc_id in 1..=8u64 {
+            let text = if doc_id == 5 {
+                "needle ".repeat(20)
+            } else {
+                format!("message {doc_id}")
+            };
+            let mut record = record("user", &text);
+            record.doc_id = doc_id;
+            record.turn_id = doc_id as u32;
+            app.index.add_record(&mut writer, &record).expect("add");
+            last = Some(record);
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, last.expect("record"), &[]);
+        let session = sessions.remove("session").expect("session");
+        let limits = PreviewLimits {
+            tail_messages: 3,
+            match_context: Some(2),
+            message_chars: 30,
+            all_terms: false,
+        };
+        let doc_ids = |query: &str| -> Vec<u64> {
+            build_detail_lines(
+                &app.index,
+                &session,
+                PreviewMode::Matches,
+                query,
+                false,
+                false,
+                limits,
+            )
+            .expect("lines")
+            .iter()
+            .filter_map(preview_line_doc_id)
+            .collect()
+        };
+        assert_eq!(doc_ids(""), [6, 7, 8]);
+        assert_eq!(doc_ids("needle"), [3, 4, 5, 6, 7]);
+
+        let mut lines = Vec::new();
+        append_record(&mut lines, &record("user", &"x".repeat(100)), false, 30);
+        assert!(
+            matches!(&lines[1], PreviewLine::Text(text) if text.ends_with(" …") && text.len() < 40)
+        );
+
+        let mut config = UserConfig {
+            tui_result_limit: Some(50),
+            preview_tail_messages: Some(4),
+            ..UserConfig::default()
+        };
+        TuiOverrides {
+            result_limit: Some(1000),
+            match_context: Some(5),
+            ..TuiOverrides::default()
+        }
+        .apply(&mut config);
+        assert_eq!(config.tui_result_limit(), 1000);
+        assert_eq!(config.preview_tail_messages(), 4);
+        assert_eq!(config.preview_match_context(), Some(5));
+        assert_eq!(config.preview_message_chars(), 4000);
+    }
+
+    #[test]
+    fn session_transcripts_render_the_preview_as_ansi_text() {
+        let (_tmp, app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        for (doc_id, role, text) in [
+            (1, "user", "fix the build"),
+            (2, "tool_use", "cargo build"),
+            (3, "assistant", "done"),
+        ] {
+            let mut record = record(role, text);
+            record.doc_id = doc_id;
+            record.turn_id = doc_id as u32;
+            app.index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+
+        let config = UserConfig::default();
+        let plain =
+            session_transcript(&app.index, &config, "session", false, false).expect("plain");
+        assert!(plain.iter().all(|line| !line.contains('\u{1b}')));
+        assert!(plain.iter().any(|line| line == "⚙ 1 tool call"));
+        assert!(plain.iter().any(|line| line == "fix the build"));
+        assert!(!plain.iter().any(|line| line == "cargo build"));
+        let with_tools =
+            session_transcript(&app.index, &config, "session", true, false).expect("tools");
+        assert!(with_tools.iter().any(|line| line == "cargo build"));
+
+        let colored =
+            session_transcript(&app.index, &config, "session", false, true).expect("colored");
+        assert_eq!(colored.len(), plain.len());
+        assert!(colored.iter().any(|line| line.contains("\u{1b}[38;2;")));
+        assert!(session_transcript(&app.index, &config, "missing", false, false).is_err());
+
+        let line = Line::from(vec![
+            Span::raw("a "),
+            Span::styled(
+                "b",
+                Style::default()
+                    .fg(Color::Rgb(1, 2, 3))
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]);
+        assert_eq!(ansi_line(&line, true), "a \u{1b}[1;38;2;1;2;3mb\u{1b}[0m");
+        assert_eq!(ansi_line(&line, false), "a b");
+        assert_eq!(ansi_color(Color::LightRed, 40).as_deref(), Some("101"));
+    }
+
+    #[test]
+    fn replay_steps_through_messages_and_autoplays_to_the_end() {
+        let records: Vec<Record> = (0..3u64)
+            .map(|idx| {
+                let mut record = record("assistant", &format!("step {idx}"));
+                record.doc_id = idx;
+                record.ts = 1_700_000_000_000 + idx * 90_000;
+                record
+            })
+            .collect();
+        let mut replay = Replay::new(&resume_session(), records, Duration::from_secs(1));
+        assert_eq!(replay.position(), "1/3 · +0s");
+        assert!(!replay.step(-1));
+        assert!(replay.step(1));
+        assert_eq!(replay.position(), "2/3 · +1m");
+        let messages = |replay: &Replay| {
+            replay
+                .lines(PreviewLimits::default().message_chars)
+                .into_iter()
+                .filter(|line| matches!(line, PreviewLine::Meta { .. }))
+                .count()
+        };
+        assert_eq!(messages(&replay), 2);
+
+        let start = replay.last_step;
+        assert!(!replay.tick(start + Duration::from_secs(5)));
+        replay.toggle_autoplay();
+        let start = replay.last_step;
+        assert!(!replay.tick(start + Duration::from_millis(500)));
+        assert!(replay.tick(start + Duration::from_secs(1)));
+        assert!(replay.at_end());
+        assert!(replay.tick(replay.last_step + Duration::from_secs(1)));
+        assert!(!replay.autoplay);
+
+        replay.change_speed(true);
+        assert_eq!(replay.interval, Duration::from_millis(500));
+        for _ in 0..20 {
+            replay.change_speed(false);
+        }
+        assert_eq!(replay.interval, REPLAY_MAX_INTERVAL);
+    }
+
+    #[test]
+    fn reload_config_applies_changes_and_keeps_the_old_config_on_errors() {
+        let (_tmp, mut app) = test_app();
+        let path = app.paths.config.clone();
+        std::fs::write(
+            &path,
+            "session_list_columns = [\"topic\"]\ncontext_format = \"xml\"\n",
+        )
+        .expect("write config");
+        app.reload_config();
+        assert!(app.session_columns.topic && !app.session_columns.messages);
+        assert_eq!(app.config.context_format.as_deref(), Some("xml"));
+        assert_eq!(
+            app.status,
+            "config reloaded: context_format, session_list_columns"
+        );
+
+        std::fs::write(&path, "session_list_columns = [\"tokens\"]\n").expect("write config");
+        app.reload_config();
+        assert!(app.status.starts_with("config.toml not reloaded"));
+        assert!(app.session_columns.topic);
+        assert_eq!(app.config.context_format.as_deref(), Some("xml"));
+
+        std::fs::write(
+            &path,
+            "session_list_columns = [\"topic\"]\ncontext_format = \"xml\"\n",
+        )
+        .expect("write config");
+        app.reload_config();
+        assert_eq!(app.status, "config.toml unchanged");
+    }
+
+    #[test]
+    fn u_cycles_the_selected_sessions_outcome_and_shows_it_in_the_preview() {
+        let (_tmp, mut app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        let mut message = record("user", "ship the login fix");
+        message.ts = 5_000;
+        app.index.add_record(&mut writer, &message).expect("add");
+        writer.commit().expect("commit");
+        drop(writer);
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, message, &[]);
+        app.results = vec![sessions.remove("session").expect("session")];
+        app.selected.select(Some(0));
+
+        app.cycle_outcome_selected();
+        assert_eq!(app.status, "outcome: success");
+        assert_eq!(app.results[0].outcome, Some(Outcome::Success));
+        let stored = SessionOutcomes::load(&outcomes_path(&app.paths.state)).expect("load");
+        let label = &stored.outcomes["session"];
+        assert_eq!(
+            (label.project.as_str(), label.session_ts),
+            ("project", 5_000)
+        );
+
+        let lines = build_detail_lines(
+            &app.index,
+            &app.results[0],
+            PreviewMode::History,
+            "",
+            false,
+            false,
+            PreviewLimits::default(),
+        )
+        .expect("lines");
+        assert!(matches!(lines[1], PreviewLine::Outcome(Outcome::Success)));
+
+        for _ in 0..3 {
+            app.cycle_outcome_selected();
+        }
+        assert_eq!(app.status, "outcome cleared");
+        let stored = SessionOutcomes::load(&outcomes_path(&app.paths.state)).expect("load");
+        assert!(stored.outcomes.is_empty());
+    }
+
+    #[test]
+    fn notes_lead_the_preview_and_leave_the_resume_path_alone() {
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 1.0, record("user", "retry login"), &[]);
+        let mut note = record(crate::types::NOTE_ROLE, "where we fixed the flaky test");
+        note.source_path = "memex-note:session".to_string();
+        add_record_to_session(&mut sessions, 5.0, note, &[]);
+        let mut session = sessions.remove("session").expect("session");
+        assert_eq!(session.source_path, "source.jsonl");
+
+        let (_tmp, app) = test_app();
+        session.note = "where we fixed the flaky test".to_string();
+        let lines = build_detail_lines(
+            &app.index,
+            &session,
+            PreviewMode::History,
+            "",
+            false,
+            false,
+            PreviewLimits::default(),
+        )
+        .expect("lines");
+        assert!(matches!(&lines[1], PreviewLine::Note(text) if text == &session.note));
+    }
+
+    #[test]
+    fn compaction_summaries_fold_and_stay_out_of_hit_counts() {
+        let summary = record(COMPACT_SUMMARY_ROLE, "Summary:\nretry login\nflaky test");
+        let mut sessions = HashMap::new();
+        add_record_to_session(&mut sessions, 9.0, summary.clone(), &[]);
+        add_record_to_session(
+            &mut sessions,
+            2.0,
+            record("user", "make retries configurable"),
+            &[],
+        );
+        let session = &sessions["session"];
+        assert_eq!(session.hit_count, 1);
+        assert_eq!(session.top_score, 2.0);
+        assert_eq!(session.snippet, "make retries configurable");
+
+        let mut lines = Vec::new();
+        append_record(
+            &mut lines,
+            &summary,
+            false,
+            PreviewLimits::default().message_chars,
+        );
+        assert!(matches!(&lines[1], PreviewLine::Compaction(text) if text.len() == 3));
+        assert_eq!(unfold_compactions(lines.clone(), false).len(), lines.len());
+        let unfolded = unfold_compactions(lines, true);
+        assert!(matches!(&unfolded[2], PreviewLine::Text(text) if text == "retry login"));
+    }
+
+    #[test]
+    fn related_sessions_rank_by_centroid_similarity() {
+        let (tmp, mut app) = test_app();
+        let embedding = |seed: f32| (0..16).map(|i| (i as f32 + seed).sin()).collect::<Vec<_>>();
+        let mut vectors =
+            VectorIndex::open_or_create(&tmp.path().join("vectors"), 16, Some("test")).unwrap();
+        let mut writer = app.index.writer().expect("writer");
+        for (doc_id, session_id, seed) in [
+            (1, "current", 1.0),
+            (2, "current", 1.1),
+            (3, "near", 1.05),
+            (4, "far", 40.0),
+            (5, "near", 1.2),
+        ] {
+            let mut record = record("user", &format!("message {doc_id}"));
+            record.doc_id = doc_id;
+            record.ts = doc_id;
+            record.session_id = session_id.to_string();
+            app.index.add_record(&mut writer, &record).expect("add");
+            vectors.add(doc_id, &embedding(seed)).unwrap();
+        }
+        writer.commit().expect("commit");
+
+        let related = related_sessions(&app.index, &vectors, "current").expect("related");
+        let ids: Vec<&str> = related.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(ids, vec!["near", "far"]);
+        assert_eq!(related[0].hit_count, 2);
+
+        app.detail_related = related;
+        app.open_related_session(0);
+        assert_eq!(app.results[0].session_id, "near");
+        assert_eq!(app.selected.selected(), Some(0));
+    }
+
+    #[test]
+    fn related_sessions_look_past_a_long_sessions_own_records() {
+        let (tmp, app) = test_app();
+        let embedding = |seed: f32| (0..16).map(|i| (i as f32 + seed).sin()).collect::<Vec<_>>();
+        let mut vectors =
+            VectorIndex::open_or_create(&tmp.path().join("vectors"), 16, Some("test")).unwrap();
+        let mut writer = app.index.writer().expect("writer");
+        let own = RELATED_SEARCH_LIMIT as u64 + 50;
+        for doc_id in 1..=own + 1 {
+            let mut record = record("user", &format!("message {doc_id}"));
+            record.doc_id = doc_id;
+            record.ts = doc_id;
+            let seed = if doc_id > own {
+                record.session_id = "other".to_string();
+                3.0
+            } else {
+                record.session_id = "long".to_string();
+                1.0 + doc_id as f32 / 10_000.0
+            };
+            app.index.add_record(&mut writer, &record).expect("add");
+            vectors.add(doc_id, &embedding(seed)).unwrap();
+        }
+        writer.commit().expect("commit");
+
+        let related = related_sessions(&app.index, &vectors, "long").expect("related");
+        let ids: Vec<&str> = related.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(ids, vec!["other"]);
+    }
+
     #[test]
     fn tui_starts_on_home_with_search_focused() {
         let (_tmp, app) = test_app();
@@ -6099,15 +11806,133 @@ mod tests {
             source: SourceKind::Claude,
             last_ts: 1,
             hit_count: 1,
+            message_count: 0,
+            duration_ms: 0,
+            top_score: 0.0,
+            snippet: String::new(),
+            title: String::new(),
+            source_path: "source.jsonl".to_string(),
+            source_dir: String::new(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
+        });
+        app.enter_browse();
+        assert_eq!(app.layout_mode, LayoutMode::Split);
+        assert!(matches!(app.focus, Focus::List));
+        assert_eq!(app.selected.selected(), Some(0));
+    }
+
+    #[test]
+    fn lexical_only_mode_skips_related_sessions_in_previews() {
+        let (_tmp, mut app) = test_app();
+        let (detail_tx, detail_rx) = std::sync::mpsc::channel();
+        app.detail_tx = detail_tx;
+        app.results.push(SessionSummary {
+            session_id: "session".to_string(),
+            project: "project".to_string(),
+            source: SourceKind::Claude,
+            last_ts: 1,
+            hit_count: 1,
+            message_count: 0,
+            duration_ms: 0,
+            top_score: 0.0,
+            snippet: String::new(),
+            title: String::new(),
+            source_path: "source.jsonl".to_string(),
+            source_dir: String::new(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
+        });
+        app.enter_browse();
+        assert!(detail_rx.try_iter().all(|request| request.related));
+
+        app.toggle_lexical_only();
+        assert!(app.lexical_only);
+        assert_eq!(app.status, "lexical only: embeddings off");
+        let requests: Vec<DetailRequest> = detail_rx.try_iter().collect();
+        assert!(!requests.is_empty());
+        assert!(requests.iter().all(|request| !request.related));
+
+        app.toggle_lexical_only();
+        assert!(!app.lexical_only);
+        assert!(detail_rx.try_iter().all(|request| request.related));
+    }
+
+    #[test]
+    fn history_opens_at_the_newest_turn_and_loads_earlier_ones_on_scroll_up() {
+        let (_tmp, mut app) = test_app();
+        let (detail_tx, detail_rx) = std::sync::mpsc::channel();
+        app.detail_tx = detail_tx;
+        app.preview_area = Rect::new(0, 0, 80, 20);
+        let records: Vec<Record> = (0..450u64)
+            .map(|i| Record {
+                doc_id: i,
+                turn_id: i as u32,
+                ..record(
+                    if i == 250 { "tool_result" } else { "user" },
+                    &format!("message {i}"),
+                )
+            })
+            .collect();
+        // A window never starts inside a run of tool messages.
+        assert_eq!(history_window_start(&records, 450, false), 249);
+        assert_eq!(history_window_start(&records, 450, true), 250);
+        app.results.push(SessionSummary {
+            session_id: "session".to_string(),
+            project: "project".to_string(),
+            source: SourceKind::Claude,
+            last_ts: 1,
+            hit_count: 1,
+            message_count: 0,
+            duration_ms: 0,
             top_score: 0.0,
             snippet: String::new(),
+            title: String::new(),
             source_path: "source.jsonl".to_string(),
             source_dir: String::new(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
         });
-        app.enter_browse();
-        assert_eq!(app.layout_mode, LayoutMode::Split);
-        assert!(matches!(app.focus, Focus::List));
-        assert_eq!(app.selected.selected(), Some(0));
+        app.selected.select(Some(0));
+        app.enter_full_history();
+        let request = detail_rx.try_iter().last().expect("preview request");
+        assert!(request.lazy_history);
+        assert_eq!(request.earlier, None);
+
+        let mut lines = Vec::new();
+        append_history_window(&mut lines, &records, 249, false, 1_000);
+        assert!(matches!(lines[0], PreviewLine::EarlierHistory(249)));
+        app.handle_search_update(SearchUpdate::DetailResults {
+            request_id: request.request_id,
+            lines,
+            related: Vec::new(),
+        });
+        assert_eq!(app.detail_scroll, app.detail_lines.len() - 20);
+        assert!(detail_rx.try_recv().is_err());
+
+        app.scroll_detail(-(app.detail_scroll as isize));
+        let earlier = detail_rx.try_recv().expect("earlier request");
+        assert_eq!(earlier.earlier, Some(249));
+        assert!(detail_rx.try_recv().is_err());
+
+        let top = app.detail_lines[1].clone();
+        let mut lines = Vec::new();
+        append_history_window(&mut lines, &records[..249], 49, false, 1_000);
+        app.handle_search_update(SearchUpdate::DetailEarlier {
+            request_id: request.request_id,
+            lines,
+        });
+        assert!(matches!(
+            app.detail_lines[0],
+            PreviewLine::EarlierHistory(49)
+        ));
+        assert_eq!(
+            preview_line_doc_id(&app.detail_lines[app.detail_scroll + 1]),
+            preview_line_doc_id(&top)
+        );
     }
 
     #[test]
@@ -6234,10 +12059,16 @@ mod tests {
                 source: SourceKind::Pi,
                 last_ts: 42,
                 hit_count: 1,
+                message_count: 0,
+                duration_ms: 0,
                 top_score: 1.0,
                 snippet: String::new(),
+                title: String::new(),
                 source_path: "source.jsonl".to_string(),
                 source_dir: String::new(),
+                topic: String::new(),
+                note: String::new(),
+                outcome: None,
             }],
         });
 
@@ -6251,6 +12082,152 @@ mod tests {
         );
     }
 
+    #[test]
+    fn recent_sort_orders_results_by_last_activity_and_toggles_back() {
+        let (_tmp, mut app) = test_app();
+        let summary = |session_id: &str, last_ts: u64, top_score: f32| SessionSummary {
+            session_id: session_id.to_string(),
+            project: "project".to_string(),
+            source: SourceKind::Claude,
+            last_ts,
+            hit_count: 1,
+            message_count: 0,
+            duration_ms: 0,
+            top_score,
+            snippet: String::new(),
+            title: String::new(),
+            source_path: "source.jsonl".to_string(),
+            source_dir: String::new(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
+        };
+        app.sort = SessionSort::Recent;
+        app.active_search_request = 1;
+        app.handle_search_update(SearchUpdate::Results {
+            request_id: 1,
+            sessions: vec![summary("best", 10, 2.0), summary("newest", 30, 1.0)],
+        });
+        let order = |app: &App| {
+            app.results
+                .iter()
+                .map(|session| session.session_id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&app), ["newest", "best"]);
+
+        app.toggle_sort();
+        assert_eq!(app.sort, SessionSort::Relevance);
+        assert_eq!(order(&app), ["best", "newest"]);
+    }
+
+    #[test]
+    fn footer_links_the_selected_sessions_file_and_directory() {
+        let (_tmp, mut app) = test_app();
+        app.results = vec![SessionSummary {
+            session_id: "session".to_string(),
+            project: "project".to_string(),
+            source: SourceKind::Claude,
+            last_ts: 1,
+            hit_count: 1,
+            message_count: 0,
+            duration_ms: 0,
+            top_score: 1.0,
+            snippet: String::new(),
+            title: String::new(),
+            source_path: "/logs/app/s.jsonl".to_string(),
+            source_dir: "/work/app".to_string(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
+        }];
+        app.selected.select(Some(0));
+        assert!(footer_paths(&app).is_empty());
+        app.layout_mode = LayoutMode::List;
+        let paths = footer_paths(&app);
+        assert_eq!(paths[0].1, "/logs/app/s.jsonl");
+        assert_eq!(paths[0].2, "s.jsonl");
+        assert_eq!(paths[1].1, "/work/app");
+        assert_eq!(paths[1].2, "app");
+
+        let line = Line::from(vec![
+            Span::raw("ab"),
+            Span::raw("file "),
+            Span::raw("s.jsonl"),
+            Span::raw("   "),
+        ]);
+        let url = "file:///logs/app/s.jsonl".to_string();
+        let links = screen_links(&line, &[(2, url.clone())], Rect::new(10, 5, 20, 1));
+        assert_eq!(
+            links,
+            [ScreenLink {
+                x: 20,
+                y: 5,
+                text: "s.jsonl".to_string(),
+                url: url.clone(),
+            }]
+        );
+        assert!(screen_links(&line, &[(2, url)], Rect::new(10, 5, 12, 1)).is_empty());
+    }
+
+    #[test]
+    fn late_results_from_an_earlier_query_never_replace_newer_ones() {
+        let (_tmp, mut app) = test_app();
+        let summary = |session_id: &str| SessionSummary {
+            session_id: session_id.to_string(),
+            project: "project".to_string(),
+            source: SourceKind::Claude,
+            last_ts: 1,
+            hit_count: 1,
+            message_count: 0,
+            duration_ms: 0,
+            top_score: 1.0,
+            snippet: String::new(),
+            title: String::new(),
+            source_path: "source.jsonl".to_string(),
+            source_dir: String::new(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
+        };
+        app.config.search_debounce_ms = Some(0);
+        app.layout_mode = LayoutMode::List;
+        app.focus = Focus::Query;
+        app.query.push('r');
+        app.search_as_you_type();
+        assert!(app.flush_search_if_due());
+        let first = app.active_search_request;
+        app.query.push('s');
+        app.search_as_you_type();
+        assert!(app.flush_search_if_due());
+        let second = app.active_search_request;
+
+        app.handle_search_update(SearchUpdate::Results {
+            request_id: second,
+            sessions: vec![summary("rs")],
+        });
+        app.handle_search_update(SearchUpdate::Results {
+            request_id: first,
+            sessions: vec![summary("r")],
+        });
+        app.handle_search_update(SearchUpdate::SearchError {
+            request_id: first,
+            message: "stale".to_string(),
+        });
+        assert_eq!(app.results.len(), 1);
+        assert_eq!(app.results[0].session_id, "rs");
+        assert_eq!(app.sessions_state, LoadState::Loaded);
+
+        // Replies to the last query sent are ignored once more has been typed.
+        app.query.push('t');
+        app.search_as_you_type();
+        app.handle_search_update(SearchUpdate::Results {
+            request_id: second,
+            sessions: Vec::new(),
+        });
+        assert_eq!(app.results[0].session_id, "rs");
+    }
+
     #[test]
     fn home_chart_grid_scales_with_height() {
         let points = vec![HomeChartPoint {
@@ -6407,6 +12384,174 @@ mod tests {
         assert!(!query.include_events);
     }
 
+    #[test]
+    fn threaded_history_nests_subagents_under_spawning_turn() {
+        let at = |role: &str, text: &str, ts: u64| {
+            let mut record = record(role, text);
+            record.ts = ts;
+            record.turn_id = ts as u32;
+            record
+        };
+        let main = vec![at("user", "spawn", 10), at("assistant", "done", 30)];
+        let mut sidechain = at("assistant", "inline branch", 31);
+        sidechain.links.thread = Some("b1".to_string());
+        sidechain.links.conversation_kind = Some("sidechain".to_string());
+        let mut child = at("assistant", "agent work", 20);
+        child.session_id = "agent-1".to_string();
+        child.links.thread = Some("agent-1".to_string());
+        child.links.conversation_kind = Some("subagent".to_string());
+
+        let mut lines = Vec::new();
+        let mut records = main;
+        records.push(sidechain);
+        append_threaded_history(
+            &mut lines,
+            records,
+            vec![child],
+            false,
+            PreviewLimits::default().message_chars,
+        );
+
+        let texts: Vec<String> = lines
+            .iter()
+            .filter_map(|line| match line {
+                PreviewLine::Text(text) => Some(text.clone()),
+                PreviewLine::Nested(inner) => match inner.as_ref() {
+                    PreviewLine::Text(text) => Some(format!("> {text}")),
+                    _ => None,
+                },
+                PreviewLine::ThreadHeader { kind, .. } => Some(format!("# {kind}")),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "spawn",
+                "# subagent",
+                "> agent work",
+                "done",
+                "# sidechain",
+                "> inline branch",
+            ]
+        );
+    }
+
+    #[test]
+    fn session_rows_show_title_instead_of_id_without_snippet() {
+        let mut session = SessionSummary {
+            session_id: "0b8f6c1e-uuid".into(),
+            project: "memex".into(),
+            source: SourceKind::Claude,
+            last_ts: 1,
+            hit_count: 1,
+            message_count: 0,
+            duration_ms: 0,
+            top_score: 0.0,
+            snippet: String::new(),
+            title: "Fix flaky login test".into(),
+            source_path: "claude.jsonl".into(),
+            source_dir: String::new(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
+        };
+        let theme = Theme::new();
+        let text = |line: Line<'static>| -> String {
+            line.spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect()
+        };
+
+        let hidden = SessionListColumns {
+            messages: false,
+            duration: false,
+            topic: false,
+        };
+        let row = text(session_result_line(
+            &session,
+            &ProjectAliases::default(),
+            &[],
+            8,
+            40,
+            hidden,
+            &theme,
+        ));
+        assert!(row.ends_with("Fix flaky login test"), "{row}");
+        assert!(!row.contains("0b8f6c1e"), "{row}");
+
+        session.title.clear();
+        let row = text(session_result_line(
+            &session,
+            &ProjectAliases::default(),
+            &[],
+            8,
+            40,
+            hidden,
+            &theme,
+        ));
+        assert!(row.ends_with("0b8f6c1e-uuid"), "{row}");
+    }
+
+    #[test]
+    fn session_rows_show_message_count_and_duration_columns() {
+        let session = SessionSummary {
+            session_id: "s1".into(),
+            project: "memex".into(),
+            source: SourceKind::Claude,
+            last_ts: 1,
+            hit_count: 1,
+            message_count: 1_234,
+            duration_ms: 95 * 60 * 1000,
+            top_score: 0.0,
+            snippet: String::new(),
+            title: "Fix flaky login test".into(),
+            source_path: "claude.jsonl".into(),
+            source_dir: String::new(),
+            topic: String::new(),
+            note: String::new(),
+            outcome: None,
+        };
+        let theme = Theme::new();
+        let row: String = session_result_line(
+            &session,
+            &ProjectAliases::default(),
+            &[],
+            8,
+            40,
+            SessionListColumns::default(),
+            &theme,
+        )
+        .spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect();
+        assert!(row.contains("1.2K msg    1h  Fix flaky"), "{row}");
+
+        let unknown = SessionSummary {
+            message_count: 0,
+            duration_ms: 0,
+            ..session
+        };
+        let blank: String = session_result_line(
+            &unknown,
+            &ProjectAliases::default(),
+            &[],
+            8,
+            40,
+            SessionListColumns::default(),
+            &theme,
+        )
+        .spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect();
+        assert_eq!(blank.len(), row.len());
+        assert_eq!(format_duration_compact(30_000), "30s");
+        assert_eq!(format_duration_compact(3 * 24 * 3_600_000), "3d");
+    }
+
     #[test]
     fn token_session_filter_uses_accepted_source_qualified_results() {
         let sessions = vec![
@@ -6416,10 +12561,16 @@ mod tests {
                 source: SourceKind::CodexHistory,
                 last_ts: 1,
                 hit_count: 1,
+                message_count: 0,
+                duration_ms: 0,
                 top_score: 1.0,
                 snippet: String::new(),
+                title: String::new(),
                 source_path: "codex.jsonl".into(),
                 source_dir: String::new(),
+                topic: String::new(),
+                note: String::new(),
+                outcome: None,
             },
             SessionSummary {
                 session_id: "shared".into(),
@@ -6427,10 +12578,16 @@ mod tests {
                 source: SourceKind::Claude,
                 last_ts: 1,
                 hit_count: 1,
+                message_count: 0,
+                duration_ms: 0,
                 top_score: 1.0,
                 snippet: String::new(),
+                title: String::new(),
                 source_path: "claude.jsonl".into(),
                 source_dir: String::new(),
+                topic: String::new(),
+                note: String::new(),
+                outcome: None,
             },
         ];
 
@@ -6565,6 +12722,24 @@ mod tests {
         assert_eq!(truncate_end("hello", 0), "");
     }
 
+    #[test]
+    fn list_window_scrolls_only_to_keep_the_selection_visible() {
+        let mut state = ListState::default().with_selected(Some(3));
+        assert_eq!(list_window(&state, 1_000, 10), 0..10);
+
+        state.select(Some(25));
+        assert_eq!(list_window(&state, 1_000, 10), 16..26);
+
+        *state.offset_mut() = 16;
+        state.select(Some(20));
+        assert_eq!(list_window(&state, 1_000, 10), 16..26);
+
+        state.select(Some(4));
+        assert_eq!(list_window(&state, 1_000, 10), 4..14);
+        assert_eq!(list_window(&state, 6, 10), 0..6);
+        assert_eq!(list_window(&ListState::default(), 0, 10), 0..0);
+    }
+
     #[test]
     fn completed_initial_index_reloads_empty_conversation_list() {
         let (_tmp, mut app) = test_app();
@@ -6576,6 +12751,9 @@ mod tests {
         app.handle_index_update(IndexUpdate::Done {
             added: 12,
             embedded: 0,
+            malformed: 0,
+            files_scanned: 3,
+            elapsed: Duration::from_millis(40),
         });
 
         assert_eq!(app.index_state, IndexState::Complete);
@@ -6583,6 +12761,124 @@ mod tests {
         assert!(app.active_search_request > 7);
     }
 
+    #[test]
+    fn index_refresh_keeps_the_embedding_average() {
+        let (_tmp, mut app) = test_app();
+        app.handle_index_update(IndexUpdate::Started);
+        let running = EmbedStatus {
+            embedded: 64,
+            total: 256,
+            elapsed: Duration::from_secs(2),
+            finished: false,
+        };
+        app.handle_index_update(IndexUpdate::Embedding(running));
+        assert_eq!(app.embed_status, Some(running));
+        app.handle_index_update(IndexUpdate::Embedding(EmbedStatus {
+            embedded: 256,
+            elapsed: Duration::from_secs(8),
+            finished: true,
+            ..running
+        }));
+        app.handle_index_update(IndexUpdate::Done {
+            added: 256,
+            embedded: 256,
+            malformed: 0,
+            files_scanned: 2,
+            elapsed: Duration::from_secs(9),
+        });
+
+        assert_eq!(app.embed_status, None);
+        let embed = app
+            .last_ingest
+            .and_then(|ingest| ingest.embed)
+            .expect("embed");
+        assert_eq!(embed.summary(), "embedded 256 in 8s (avg 32/s)");
+        assert!(app.status.ends_with("(avg 32/s)"), "{}", app.status);
+    }
+
+    #[test]
+    fn debug_overlay_shows_where_the_last_search_spent_its_time() {
+        let (_tmp, mut app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        app.index
+            .add_record(&mut writer, &record("user", "needle"))
+            .expect("add");
+        writer.commit().expect("commit");
+        drop(writer);
+        let request = SearchRequest {
+            request_id: 3,
+            query: "needle".to_string(),
+            project: String::new(),
+            source: SourceChoice::All,
+            since: None,
+            grouping: ProjectGrouping::Flat,
+            profile: ScoringProfile::default(),
+            tuning: QueryTuning::default(),
+            limit: 200,
+        };
+        let mut cache = SearchCache::default();
+        let mut timings = SearchTimings {
+            query: "needle".to_string(),
+            ..SearchTimings::default()
+        };
+        run_cached_search_request(
+            &app.paths,
+            &ProjectAliases::default(),
+            &app.index,
+            &mut cache,
+            request.clone(),
+            &|| false,
+            &mut timings,
+        )
+        .expect("search");
+        assert!(!timings.cached);
+        assert_eq!(timings.hits, 1);
+        assert_eq!(timings.query, "needle");
+
+        let mut cached = SearchTimings::default();
+        run_cached_search_request(
+            &app.paths,
+            &ProjectAliases::default(),
+            &app.index,
+            &mut cache,
+            request,
+            &|| false,
+            &mut cached,
+        )
+        .expect("search");
+        assert!(cached.cached);
+
+        app.active_search_request = 3;
+        app.handle_search_update(SearchUpdate::SearchTimings {
+            request_id: 2,
+            timings: cached,
+        });
+        assert!(app.last_search_timings.is_none());
+        app.handle_search_update(SearchUpdate::SearchTimings {
+            request_id: 3,
+            timings,
+        });
+        app.toggle_debug_overlay();
+        assert!(app.debug_overlay);
+        assert!(app.debug_segments.is_some_and(|stats| stats.alive_docs > 0));
+
+        let theme = Theme::new();
+        let text: Vec<String> = debug_overlay_lines(&app, &theme)
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect()
+            })
+            .collect();
+        assert!(text.iter().any(|line| line.starts_with("lexical query")));
+        assert!(text.iter().any(|line| line.contains("1 hits")));
+        assert!(text.iter().any(|line| line.contains("none this session")));
+        app.toggle_debug_overlay();
+        assert!(!app.debug_overlay);
+    }
+
     #[test]
     fn stale_search_results_do_not_replace_active_request() {
         let (_tmp, mut app) = test_app();
@@ -6624,6 +12920,85 @@ mod tests {
         assert_eq!(app.query, "draft search");
     }
 
+    #[test]
+    fn collect_toggles_the_top_message_and_exports_the_basket() {
+        let (_tmp, mut app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        let mut question = record("user", "why is the lock global?");
+        question.doc_id = 1;
+        question.ts = 1_000;
+        let mut answer = record("assistant", "so two ingests cannot race");
+        answer.doc_id = 2;
+        answer.ts = 2_000;
+        for record in [&question, &answer] {
+            app.index.add_record(&mut writer, record).expect("add");
+        }
+        writer.commit().expect("commit");
+        append_records(
+            &mut app.detail_lines,
+            [&question, &answer],
+            PreviewLimits::default().message_chars,
+        );
+        app.focus = Focus::Preview;
+
+        app.detail_scroll = app.detail_lines.len() - 1;
+        app.toggle_collected();
+        app.detail_scroll = 0;
+        app.toggle_collected();
+        assert_eq!(
+            app.basket
+                .iter()
+                .map(|record| record.doc_id)
+                .collect::<Vec<_>>(),
+            [2, 1]
+        );
+        app.toggle_collected();
+        app.toggle_collected();
+        assert_eq!(app.basket.len(), 2);
+
+        app.export_basket();
+        assert!(app.basket.is_empty());
+        let exported = std::fs::read_dir(app.paths.root.join("context"))
+            .expect("context dir")
+            .next()
+            .expect("export")
+            .expect("entry")
+            .path();
+        let text = std::fs::read_to_string(exported).expect("read export");
+        let question_at = text.find("why is the lock global?").expect("question");
+        let answer_at = text.find("so two ingests cannot race").expect("answer");
+        assert!(question_at < answer_at);
+    }
+
+    #[test]
+    fn heatmap_counts_local_days_levels_and_streaks() {
+        use chrono::TimeZone;
+        let at = |day: u32, hour: u32| {
+            chrono::Local
+                .with_ymd_and_hms(2026, 3, day, hour, 0, 0)
+                .earliest()
+                .expect("local time")
+                .timestamp_millis() as u64
+        };
+        let day = |day: u32| chrono::NaiveDate::from_ymd_opt(2026, 3, day).expect("date");
+
+        let counts = heatmap_day_counts(&[at(1, 9), at(1, 23), at(2, 8), at(4, 12), 0]);
+        assert_eq!(counts.get(&day(1)), Some(&2));
+        assert_eq!(counts.get(&day(2)), Some(&1));
+        assert_eq!(counts.get(&day(3)), None);
+        assert_eq!(counts.values().sum::<u64>(), 4);
+
+        assert_eq!(heatmap_streak(&counts, day(2)), 2);
+        assert_eq!(heatmap_streak(&counts, day(3)), 2);
+        assert_eq!(heatmap_streak(&counts, day(4)), 1);
+        assert_eq!(heatmap_streak(&counts, day(6)), 0);
+
+        assert_eq!(heatmap_level(0, 10), 0);
+        assert_eq!(heatmap_level(1, 10), 1);
+        assert_eq!(heatmap_level(6, 10), 3);
+        assert_eq!(heatmap_level(10, 10), 4);
+    }
+
     #[test]
     fn timeline_query_filters_by_range_before_collecting_sessions() {
         let (_tmp, app) = test_app();
@@ -6644,14 +13019,194 @@ mod tests {
             .expect("add recent");
         writer.commit().expect("commit");
 
-        let sessions =
-            sessions_from_query(&app.index, "needle", None, None, Some(50), RESULT_LIMIT)
-                .expect("search");
+        let sessions = sessions_from_query(
+            &app.index,
+            "needle",
+            None,
+            None,
+            Some(50),
+            SearchSettings {
+                profile: &ScoringProfile::default(),
+                tuning: &QueryTuning::default(),
+                limit: UserConfig::default().tui_result_limit(),
+            },
+        )
+        .expect("search");
 
         assert_eq!(sessions.len(), 1);
         assert_eq!(sessions[0].session_id, "recent");
     }
 
+    #[test]
+    fn search_cache_is_reused_until_the_index_generation_changes() {
+        let (_tmp, app) = test_app();
+        let add = |session: &str, doc_id: u64| {
+            let mut writer = app.index.writer().expect("writer");
+            let mut rec = record("user", "needle");
+            rec.doc_id = doc_id;
+            rec.session_id = session.to_string();
+            rec.source_path = format!("{session}.jsonl");
+            app.index.add_record(&mut writer, &rec).expect("add");
+            writer.commit().expect("commit");
+        };
+        let request = SearchRequest {
+            request_id: 1,
+            query: "needle".to_string(),
+            project: String::new(),
+            source: SourceChoice::All,
+            since: None,
+            grouping: ProjectGrouping::Flat,
+            profile: ScoringProfile::default(),
+            tuning: QueryTuning::default(),
+            limit: 200,
+        };
+        let mut cache = SearchCache::default();
+        add("first", 1);
+
+        let search = |cache: &mut SearchCache| {
+            run_cached_search_request(
+                &app.paths,
+                &ProjectAliases::default(),
+                &app.index,
+                cache,
+                request.clone(),
+                &|| false,
+                &mut SearchTimings::default(),
+            )
+            .expect("search")
+            .expect("current search")
+        };
+        assert_eq!(search(&mut cache).len(), 1);
+        let generation = app.index.generation().expect("generation");
+        let key = SearchCacheKey::new(&request, generation);
+        cache.insert(key.clone(), Vec::new());
+        assert!(search(&mut cache).is_empty());
+
+        add("second", 2);
+        assert!(app.index.generation().expect("generation") > generation);
+        assert_eq!(search(&mut cache).len(), 2);
+        assert_eq!(cache.entries.len(), 2);
+    }
+
+    #[test]
+    fn superseded_searches_are_dropped_and_typing_in_the_list_searches_live() {
+        let (_tmp, mut app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        app.index
+            .add_record(&mut writer, &record("user", "needle"))
+            .expect("add");
+        writer.commit().expect("commit");
+        drop(writer);
+        let request = SearchRequest {
+            request_id: 1,
+            query: "needle".to_string(),
+            project: String::new(),
+            source: SourceChoice::All,
+            since: None,
+            grouping: ProjectGrouping::Flat,
+            profile: ScoringProfile::default(),
+            tuning: QueryTuning::default(),
+            limit: 200,
+        };
+        let latest = LatestSearch::default();
+        latest.set(2);
+        let mut cache = SearchCache::default();
+        let result = run_cached_search_request(
+            &app.paths,
+            &ProjectAliases::default(),
+            &app.index,
+            &mut cache,
+            request,
+            &|| latest.is_superseded(1),
+            &mut SearchTimings::default(),
+        )
+        .expect("search");
+        assert!(result.is_none());
+        assert!(cache.entries.is_empty());
+
+        app.layout_mode = LayoutMode::List;
+        app.focus = Focus::Query;
+        for ch in "needle".chars() {
+            app.query.push(ch);
+            app.search_as_you_type();
+        }
+        let pending = app.active_search_request;
+        assert!(app.pending_search.is_some());
+        assert!(!app.latest_search.is_superseded(pending));
+        app.flush_search();
+        assert!(app.pending_search.is_none());
+        assert!(app.latest_search.is_superseded(pending));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let sessions = loop {
+            let update = app
+                .search_rx
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .expect("search results");
+            if let SearchUpdate::Results {
+                request_id,
+                sessions,
+            } = update
+                && request_id == app.active_search_request
+            {
+                break sessions;
+            }
+        };
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn pinned_queries_count_matches_under_the_source_filter() {
+        let (_tmp, mut app) = test_app();
+        let mut writer = app.index.writer().expect("writer");
+        for (doc_id, source, text) in [
+            (1, SourceKind::CodexSession, "TODO wire up retries"),
+            (2, SourceKind::Claude, "left a TODO in main"),
+            (3, SourceKind::Claude, "thread panicked at main"),
+        ] {
+            let mut record = record("user", text);
+            record.doc_id = doc_id;
+            record.source = source;
+            app.index.add_record(&mut writer, &record).expect("add");
+        }
+        writer.commit().expect("commit");
+        drop(writer);
+
+        for query in ["TODO", "panicked"] {
+            app.query = query.to_string();
+            app.toggle_pinned_query();
+        }
+        assert_eq!(app.pinned.queries, ["TODO", "panicked"]);
+        let saved = PinnedQueries::load(&pinned_queries_path(&app.paths.state)).expect("load");
+        assert_eq!(saved.queries, app.pinned.queries);
+
+        let counts = |app: &mut App| {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                let update = app
+                    .search_rx
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .expect("pinned counts");
+                if let SearchUpdate::PinnedCounts { request_id, .. } = &update
+                    && *request_id == app.active_pinned_request
+                {
+                    app.handle_search_update(update);
+                    return app.pinned_counts.clone();
+                }
+            }
+        };
+        assert_eq!(counts(&mut app), [Some(2), Some(1)]);
+
+        app.source = SourceChoice::Claude;
+        app.kickoff_pinned_counts();
+        assert_eq!(counts(&mut app), [Some(1), Some(1)]);
+
+        app.next_pinned_query();
+        assert_eq!(app.query, "TODO");
+        app.next_pinned_query();
+        assert_eq!(app.query, "panicked");
+    }
+
     #[test]
     fn record_preview_text_pretty_prints_tool_json() {
         let record = record(
@@ -6838,6 +13393,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn apply_patch_calls_preview_as_colored_diffs() {
+        let patch = "*** Begin Patch\n*** Update File: src/a.rs\n@@\n     keep();\n-old();\n+new();\n*** End Patch";
+        let mut call = record("tool_use", patch);
+        call.tool_name = Some("apply_patch".to_string());
+        call.tool_input = Some(patch.to_string());
+        let mut lines = Vec::new();
+        append_record(
+            &mut lines,
+            &call,
+            false,
+            PreviewLimits::default().message_chars,
+        );
+        let diff: Vec<&str> = lines
+            .iter()
+            .filter_map(|line| match line {
+                PreviewLine::Diff(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(diff.len(), 7);
+        // Indentation survives, unlike in summarized text.
+        assert_eq!(diff[3], "     keep();");
+
+        let theme = Theme::new();
+        assert_eq!(diff_style("+new();", &theme).fg, Some(COLOR_DIFF_ADD));
+        assert_eq!(diff_style("-old();", &theme).fg, Some(COLOR_DIFF_DEL));
+        assert_eq!(diff_style("@@", &theme), theme.accent);
+    }
+
+    #[test]
+    fn fenced_code_is_highlighted_per_record() {
+        let mut lines = Vec::new();
+        append_record(
+            &mut lines,
+            &record(
+                "assistant",
+                "Try this:\n```rust\nlet n = 1; // one\n```\n```diff\n-a\n+b\n```\n```\ndef run(x):\n```\n```brainfuck\n+[-]\n",
+            ),
+            false,
+            PreviewLimits::default().message_chars,
+        );
+        // An unclosed fence ends with its record.
+        append_record(
+            &mut lines,
+            &record("assistant", "fn plain() {}"),
+            false,
+            PreviewLimits::default().message_chars,
+        );
+        let lines = highlight_code_blocks(lines);
+        let code: Vec<&Vec<(TokenKind, String)>> = lines
+            .iter()
+            .filter_map(|line| match line {
+                PreviewLine::Code(tokens) => Some(tokens),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(code.len(), 2);
+        assert_eq!(code[0][0], (TokenKind::Keyword, "let".to_string()));
+        assert_eq!(
+            code[0].last(),
+            Some(&(TokenKind::Comment, "// one".to_string()))
+        );
+        // Guessed from the body of an untagged fence.
+        assert_eq!(code[1][0], (TokenKind::Keyword, "def".to_string()));
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| matches!(line, PreviewLine::Diff(_)))
+                .count(),
+            2
+        );
+        assert!(
+            lines
+                .iter()
+                .any(|line| matches!(line, PreviewLine::Text(text) if text == "+[-]"))
+        );
+        assert!(
+            lines
+                .iter()
+                .any(|line| matches!(line, PreviewLine::Text(text) if text == "fn plain() {}"))
+        );
+    }
+
     #[test]
     fn record_preview_text_leaves_non_tool_json_unchanged() {
         let text = r#"{"content":"not a tool call"}"#;
@@ -6858,11 +13497,40 @@ mod tests {
 
     #[test]
     fn record_preview_text_leaves_large_tool_json_unchanged() {
-        let text = format!(r#"{{"payload":"{}"}}"#, "x".repeat(MAX_MESSAGE_CHARS));
+        let text = format!(r#"{{"payload":"{}"}}"#, "x".repeat(PRETTY_JSON_MAX_BYTES));
         let record = record("tool_result", &text);
         let preview = record_preview_text(&record);
 
         assert!(matches!(preview, Cow::Borrowed(_)));
         assert_eq!(preview, text);
     }
+
+    #[test]
+    fn command_palette_filters_fuzzily_and_runs_the_chosen_action() {
+        let (_tmp, mut app) = test_app();
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        app.open_palette();
+        let all = app.palette.as_ref().expect("palette").matches().len();
+        assert_eq!(all, PALETTE_ENTRIES.len());
+        for ch in "srt".chars() {
+            assert_eq!(handle_palette_key(key(KeyCode::Char(ch)), &mut app), None);
+        }
+        let matches = app.palette.as_ref().expect("palette").matches();
+        assert!(matches.len() < all);
+        assert_eq!(matches[0].0, PaletteAction::ToggleSort);
+        assert_eq!(fuzzy_score("Change sort order", "xyz"), None);
+
+        let before = app.sort;
+        let action = handle_palette_key(key(KeyCode::Enter), &mut app);
+        assert_eq!(action, Some(PaletteAction::ToggleSort));
+        assert!(app.palette.is_none());
+        app.run_palette_action(PaletteAction::ToggleSort);
+        assert_ne!(app.sort, before);
+
+        app.open_palette();
+        handle_palette_key(key(KeyCode::Down), &mut app);
+        assert_eq!(app.palette.as_ref().expect("palette").selected, 1);
+        handle_palette_key(key(KeyCode::Esc), &mut app);
+        assert!(app.palette.is_none());
+    }
 }
@@ -0,0 +1,209 @@
+//! Turns: a user prompt with the assistant's reply and the tool calls it
+//! made, up to the next prompt. They are indexed as one document each so a
+//! search can match a whole exchange, and the TUI preview shows a hit's
+//! exchange rather than the messages next to it.
+
+use crate::types::{AgentSettings, Record, TURN_ROLE};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Set on the doc id of a turn, which is otherwise its prompt's doc id, so
+/// turns never collide with records.
+pub const TURN_DOC_ID_BIT: u64 = 1 << 63;
+
+/// Characters of a tool call or result kept in a turn's text; the records
+/// themselves still have all of it.
+const TOOL_TEXT_CHARS: usize = 2000;
+
+pub fn turn_doc_id(prompt_doc_id: u64) -> u64 {
+    prompt_doc_id | TURN_DOC_ID_BIT
+}
+
+pub fn is_prompt(record: &Record) -> bool {
+    record.role == "user"
+}
+
+/// Indices of the exchange holding `records[at]`: from the prompt before it
+/// (or the start) to the next prompt (or the end). `records` are one
+/// session's, in order.
+pub fn exchange_bounds(records: &[Record], at: usize) -> Range<usize> {
+    let start = records[..=at].iter().rposition(is_prompt).unwrap_or(0);
+    let end = records[at + 1..]
+        .iter()
+        .position(is_prompt)
+        .map_or(records.len(), |offset| at + 1 + offset);
+    start..end
+}
+
+/// The turn document for an exchange starting with its prompt. Text is one
+/// line per message, labelled with its role, tool output cut short.
+pub fn build_turn(exchange: &[Record]) -> Option<Record> {
+    let prompt = exchange.first().filter(|record| is_prompt(record))?;
+    let mut text = String::new();
+    let mut settings = AgentSettings::default();
+    for record in exchange {
+        if !record.settings.is_empty() {
+            settings = record.settings.clone();
+        }
+        if record.text.trim().is_empty() {
+            continue;
+        }
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&record.role);
+        if let Some(tool) = &record.tool_name {
+            text.push(' ');
+            text.push_str(tool);
+        }
+        text.push_str(": ");
+        if record.role.starts_with("tool_") {
+            text.extend(record.text.chars().take(TOOL_TEXT_CHARS));
+        } else {
+            text.push_str(&record.text);
+        }
+    }
+    Some(Record {
+        doc_id: turn_doc_id(prompt.doc_id),
+        role: TURN_ROLE.to_string(),
+        text,
+        tool_name: None,
+        tool_input: None,
+        tool_output: None,
+        settings,
+        attachments: Vec::new(),
+        ..prompt.clone()
+    })
+}
+
+/// Every turn in one session's `records`, in order. Messages before the
+/// first prompt belong to none.
+pub fn build_turns(records: &[Record]) -> Vec<Record> {
+    let mut turns = Vec::new();
+    let mut at = match records.iter().position(is_prompt) {
+        Some(at) => at,
+        None => return turns,
+    };
+    while at < records.len() {
+        let exchange = exchange_bounds(records, at);
+        turns.extend(build_turn(&records[exchange.clone()]));
+        at = exchange.end;
+    }
+    turns
+}
+
+/// The exchange a session file ended on, from its last prompt: what a
+/// resumed file's first records continue. Empty without a prompt.
+pub fn last_exchange(mut records: Vec<Record>) -> Vec<Record> {
+    records.sort_by_key(|record| (record.turn_id, record.doc_id));
+    match records.iter().rposition(is_prompt) {
+        Some(start) => records.split_off(start),
+        None => Vec::new(),
+    }
+}
+
+/// Groups records streaming in from ingest into turns, per source file.
+#[derive(Default)]
+pub struct TurnBuilder {
+    open: HashMap<String, Vec<Record>>,
+}
+
+impl TurnBuilder {
+    /// Whether records of `source_path` have been pushed yet.
+    pub fn has_file(&self, source_path: &str) -> bool {
+        self.open.contains_key(source_path)
+    }
+
+    /// Continues a file's exchange from an earlier ingest: `exchange` is
+    /// what was indexed of it, starting with the prompt.
+    pub fn resume(&mut self, source_path: &str, exchange: Vec<Record>) {
+        self.open.insert(source_path.to_string(), exchange);
+    }
+
+    /// Adds `record` to its file's open exchange, returning the turn a new
+    /// prompt closes.
+    pub fn push(&mut self, record: &Record) -> Option<Record> {
+        let open = self.open.entry(record.source_path.clone()).or_default();
+        let closed = if is_prompt(record) {
+            build_turn(&std::mem::take(open))
+        } else {
+            None
+        };
+        if is_prompt(record) || !open.is_empty() {
+            open.push(record.clone());
+        }
+        closed
+    }
+
+    /// Turns still open once every record is in.
+    pub fn finish(self) -> Vec<Record> {
+        self.open
+            .into_values()
+            .filter_map(|exchange| build_turn(&exchange))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::test_record;
+
+    #[test]
+    fn groups_prompts_with_replies_and_tool_calls() {
+        let mut tool = Record {
+            role: "tool_use".to_string(),
+            text: "cargo test".to_string(),
+            ..test_record(4, "s1")
+        };
+        tool.tool_name = Some("Bash".to_string());
+        let records = vec![
+            Record {
+                role: "assistant".to_string(),
+                text: "how can I help".to_string(),
+                ..test_record(1, "s1")
+            },
+            Record {
+                text: "why is ci red".to_string(),
+                ..test_record(2, "s1")
+            },
+            Record {
+                role: "assistant".to_string(),
+                text: "let me run the tests".to_string(),
+                ..test_record(3, "s1")
+            },
+            tool,
+            Record {
+                role: "tool_result".to_string(),
+                text: "x".repeat(5000),
+                ..test_record(5, "s1")
+            },
+            Record {
+                text: "thanks".to_string(),
+                ..test_record(6, "s1")
+            },
+        ];
+
+        assert_eq!(exchange_bounds(&records, 4), 1..5);
+        assert_eq!(exchange_bounds(&records, 0), 0..1);
+        assert_eq!(exchange_bounds(&records, 5), 5..6);
+
+        let turns = build_turns(&records);
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].doc_id, turn_doc_id(2));
+        assert_eq!(turns[0].role, TURN_ROLE);
+        assert!(turns[0].text.starts_with(
+            "user: why is ci red\nassistant: let me run the tests\ntool_use Bash: cargo test\n"
+        ));
+        assert!(turns[0].text.len() < 2100);
+
+        let mut builder = TurnBuilder::default();
+        let mut streamed: Vec<Record> = records
+            .iter()
+            .filter_map(|record| builder.push(record))
+            .collect();
+        streamed.extend(builder.finish());
+        let texts: Vec<&str> = streamed.iter().map(|turn| turn.text.as_str()).collect();
+        assert_eq!(texts, [turns[0].text.as_str(), "user: thanks"]);
+    }
+}
@@ -1,5 +1,6 @@
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum SourceKind {
@@ -11,10 +12,24 @@ pub enum SourceKind {
     Cursor,
     Pi,
     Copilot,
+    /// Conversations imported from a ChatGPT data export with
+    /// `memex import-chatgpt`.
+    ChatGPT,
+    /// Records from an adapter registered through
+    /// [`crate::ingest::register_adapter`], carrying the adapter's name, or
+    /// written with `memex append` ([`SourceKind::APPENDED`]).
+    External(&'static str),
 }
 
+/// Names of external sources read back from storage, leaked once each so
+/// [`SourceKind`] stays `Copy`.
+static EXTERNAL_NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
 impl SourceKind {
-    pub const ALL: [SourceKind; 7] = [
+    /// Messages written with `memex append`.
+    pub const APPENDED: SourceKind = SourceKind::External("external");
+
+    pub const ALL: [SourceKind; 9] = [
         SourceKind::Claude,
         SourceKind::CodexSession,
         SourceKind::CodexHistory,
@@ -22,6 +37,8 @@ impl SourceKind {
         SourceKind::Cursor,
         SourceKind::Pi,
         SourceKind::Copilot,
+        SourceKind::APPENDED,
+        SourceKind::ChatGPT,
     ];
     pub const COUNT: usize = Self::ALL.len();
 
@@ -34,6 +51,8 @@ impl SourceKind {
             SourceKind::Cursor => 4,
             SourceKind::Pi => 5,
             SourceKind::Copilot => 6,
+            SourceKind::External(_) => 7,
+            SourceKind::ChatGPT => 8,
         }
     }
 
@@ -46,10 +65,28 @@ impl SourceKind {
             4 => Some(SourceKind::Cursor),
             5 => Some(SourceKind::Pi),
             6 => Some(SourceKind::Copilot),
+            7 => Some(SourceKind::APPENDED),
+            8 => Some(SourceKind::ChatGPT),
             _ => None,
         }
     }
 
+    /// The external source called `name`.
+    pub fn external(name: &str) -> Self {
+        if name == Self::APPENDED.storage_label() {
+            return Self::APPENDED;
+        }
+        let mut names = EXTERNAL_NAMES
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(&known) = names.iter().find(|known| **known == name) {
+            return SourceKind::External(known);
+        }
+        let leaked: &'static str = Box::leak(name.to_string().into_boxed_str());
+        names.push(leaked);
+        SourceKind::External(leaked)
+    }
+
     pub fn label(self) -> &'static str {
         match self {
             SourceKind::Claude => "claude",
@@ -58,6 +95,8 @@ impl SourceKind {
             SourceKind::Cursor => "cursor",
             SourceKind::Pi => "pi",
             SourceKind::Copilot => "copilot",
+            SourceKind::ChatGPT => "chatgpt",
+            SourceKind::External(name) => name,
         }
     }
 
@@ -70,6 +109,8 @@ impl SourceKind {
             SourceKind::Cursor => "cursor",
             SourceKind::Pi => "pi",
             SourceKind::Copilot => "copilot",
+            SourceKind::ChatGPT => "chatgpt",
+            SourceKind::External(name) => name,
         }
     }
 
@@ -103,6 +144,8 @@ impl SourceKind {
             || path.contains("\\session-state\\")
         {
             SourceKind::Copilot
+        } else if path.ends_with("conversations.json") {
+            SourceKind::ChatGPT
         } else {
             SourceKind::Claude
         }
@@ -117,13 +160,31 @@ impl SourceKind {
             "cursor" => Some(SourceKind::Cursor),
             "pi" => Some(SourceKind::Pi),
             "copilot" => Some(SourceKind::Copilot),
-            _ => None,
+            "chatgpt" => Some(SourceKind::ChatGPT),
+            "" => None,
+            name => Some(SourceKind::external(name)),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+/// Serialized as its storage label, so records written to JSON keep their
+/// source.
+impl Serialize for SourceKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.storage_label())
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceKind {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let label = String::deserialize(deserializer)?;
+        SourceKind::from_label(&label).ok_or_else(|| serde::de::Error::custom("empty source label"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
 #[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
 pub enum SourceFilter {
     Claude,
     Codex,
@@ -131,6 +192,9 @@ pub enum SourceFilter {
     Cursor,
     Pi,
     Copilot,
+    #[value(name = "chatgpt")]
+    #[serde(rename = "chatgpt")]
+    ChatGPT,
 }
 
 impl SourceFilter {
@@ -144,6 +208,7 @@ impl SourceFilter {
             SourceFilter::Cursor => source == SourceKind::Cursor,
             SourceFilter::Pi => source == SourceKind::Pi,
             SourceFilter::Copilot => source == SourceKind::Copilot,
+            SourceFilter::ChatGPT => source == SourceKind::ChatGPT,
         }
     }
 
@@ -155,6 +220,7 @@ impl SourceFilter {
             SourceFilter::Cursor => &["cursor"],
             SourceFilter::Pi => &["pi"],
             SourceFilter::Copilot => &["copilot"],
+            SourceFilter::ChatGPT => &["chatgpt"],
         }
     }
 
@@ -166,6 +232,7 @@ impl SourceFilter {
             SourceFilter::Cursor => "cursor",
             SourceFilter::Pi => "pi",
             SourceFilter::Copilot => "copilot",
+            SourceFilter::ChatGPT => "chatgpt",
         }
     }
 }
@@ -184,6 +251,10 @@ pub struct RecordLinks {
     pub thread_source: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub conversation_kind: Option<String>,
+    /// Branch within a conversation: the agent id for subagent transcripts or
+    /// the first event id of an inline sidechain. Unset on the main line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parent_tool_use_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -192,9 +263,77 @@ pub struct RecordLinks {
     pub source_tool_assistant_uuid: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How the agent was configured when a record was written, where the source
+/// logs it (Codex `turn_context` entries).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval_policy: Option<String>,
+}
+
+impl AgentSettings {
+    pub fn is_empty(&self) -> bool {
+        self.model.is_none() && self.sandbox.is_none() && self.approval_policy.is_none()
+    }
+}
+
+/// A pasted image or file attached to a message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// `image` or `document`.
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    /// Decoded size in bytes, when the content was inlined in the transcript.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Where the file lives, kept only if it still existed at ingest.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Text read from an image by OCR (`ocr_images`), indexed with the
+    /// message so screenshots are searchable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Role of the summary Claude writes when it compacts a conversation's
+/// context. It restates the whole session so far, so it is kept apart from
+/// the user's own messages.
+pub const COMPACT_SUMMARY_ROLE: &str = "compact_summary";
+
+/// Role of a note the user attached to a session with `memex note`. Indexed
+/// so searches find it, but not part of the transcript.
+pub const NOTE_ROLE: &str = "note";
+
+/// Role of a turn: a prompt and everything up to the next one, indexed as a
+/// single document (see [`crate::turns`]). Searched only with `--role turn`
+/// and left out of transcripts.
+pub const TURN_ROLE: &str = "turn";
+
+/// Role of an agent-written session summary, or an imported conversation's
+/// title. It only titles the session in analytics and is never indexed.
+pub const SUMMARY_ROLE: &str = "summary";
+
+/// Role of an item on a Claude Code todo list, found with `type:todo`.
+pub const TODO_ROLE: &str = "todo";
+
+/// Role of a Claude Code plan document, found with `type:plan`.
+pub const PLAN_ROLE: &str = "plan";
+
+/// Whether `role` is kept beside a session rather than in its transcript:
+/// the user's notes, todo items and plans.
+pub fn is_side_role(role: &str) -> bool {
+    matches!(role, NOTE_ROLE | TODO_ROLE | PLAN_ROLE)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Record {
-    #[serde(skip)]
     pub source: SourceKind,
     pub doc_id: u64,
     pub ts: u64,
@@ -211,12 +350,94 @@ pub struct Record {
     pub tool_output: Option<String>,
     #[serde(flatten)]
     pub links: RecordLinks,
+    #[serde(flatten)]
+    pub settings: AgentSettings,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
     pub source_path: String,
+    /// When memex first indexed this record (unix millis). Zero until the
+    /// index writer stamps it; `ts` stays the message time.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub ingested_at: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// How far `memex push` or `memex export` got: the last record sent, in
+/// `(ingested_at, doc_id)` order. One `memex index` run stamps every record
+/// with the same `ingested_at`, so the doc id is what lets a run cut short
+/// partway through resume after the last record that actually went out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "CursorRepr")]
+pub struct RecordCursor {
+    pub ingested_at: u64,
+    pub doc_id: u64,
+}
+
+impl RecordCursor {
+    pub fn of(record: &Record) -> Self {
+        Self {
+            ingested_at: record.ingested_at,
+            doc_id: record.doc_id,
+        }
+    }
+
+    /// Whether `record` comes after this cursor and so is still to send.
+    pub fn precedes(&self, record: &Record) -> bool {
+        *self < Self::of(record)
+    }
+}
+
+/// Cursors were once a bare `ingested_at`, meaning everything ingested up to
+/// and including it was sent.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CursorRepr {
+    Through(u64),
+    At { ingested_at: u64, doc_id: u64 },
+}
+
+impl From<CursorRepr> for RecordCursor {
+    fn from(repr: CursorRepr) -> Self {
+        match repr {
+            CursorRepr::Through(ingested_at) => Self {
+                ingested_at,
+                doc_id: u64::MAX,
+            },
+            CursorRepr::At {
+                ingested_at,
+                doc_id,
+            } => Self {
+                ingested_at,
+                doc_id,
+            },
+        }
+    }
+}
+
+/// A Claude user message for tests: `hello` in project `memex`, read from
+/// `/tmp/<session_id>.jsonl`, with `ts` and `turn_id` following `doc_id`.
+/// Override the fields a test cares about with struct update syntax.
+#[cfg(test)]
+pub(crate) fn test_record(doc_id: u64, session_id: &str) -> Record {
+    Record {
+        doc_id,
+        ts: doc_id,
+        project: "memex".to_string(),
+        session_id: session_id.to_string(),
+        turn_id: doc_id as u32,
+        role: "user".to_string(),
+        text: "hello".to_string(),
+        source_path: format!("/tmp/{session_id}.jsonl"),
+        ..Default::default()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::SourceKind;
+    use super::{Record, SourceKind, test_record};
     use std::collections::HashSet;
 
     #[test]
@@ -231,6 +452,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn external_sources_keep_their_adapter_name() {
+        let source = SourceKind::from_label("inhouse-agent").expect("external label");
+        assert_eq!(source, SourceKind::External("inhouse-agent"));
+        assert_eq!(source.label(), "inhouse-agent");
+        assert_eq!(source.storage_label(), "inhouse-agent");
+        assert_eq!(source.idx(), SourceKind::APPENDED.idx());
+        assert_ne!(source, SourceKind::APPENDED);
+        assert_eq!(
+            SourceKind::from_label("external"),
+            Some(SourceKind::APPENDED)
+        );
+        assert_eq!(SourceKind::from_label(""), None);
+    }
+
+    #[test]
+    fn records_serialize_their_source_label() {
+        let record = Record {
+            source: SourceKind::CodexHistory,
+            ..test_record(1, "s1")
+        };
+        let json = serde_json::to_value(&record).expect("serialize");
+        assert_eq!(json["source"], "codex-history");
+        let back: Record = serde_json::from_value(json).expect("deserialize");
+        assert_eq!(back.source, SourceKind::CodexHistory);
+
+        let external: Record = serde_json::from_str(
+            r#"{"source":"inhouse-agent","doc_id":2,"ts":0,"project":"","session_id":"s","turn_id":0,"role":"user","text":"","source_path":""}"#,
+        )
+        .expect("external source");
+        assert_eq!(external.source, SourceKind::External("inhouse-agent"));
+    }
+
     #[test]
     fn from_path_recognizes_archived_codex_sessions() {
         let unix_path = "/tmp/.codex/archived_sessions/rollout-2026-02-10T11-16-28-abc.jsonl";
@@ -1,23 +1,103 @@
-use anyhow::{Result, anyhow};
+use crate::embed::ModelChoice;
+use anyhow::{Context, Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::fs;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use usearch::{Index, IndexOptions, MetricKind, ScalarKind};
 
+/// Filtered searches with at most this many candidates score each one
+/// directly instead of walking the graph.
+const EXACT_SCAN_MAX: usize = 20_000;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const MANIFEST_LOCK_FILE: &str = "manifest.lock";
+const SEGMENTS_DIR: &str = "segments";
+
+/// A save that would leave a store with more segments than this merges them
+/// into one first, so searches between `memex vacuum` runs don't walk a
+/// graph per daemon tick.
+const MERGE_AT_SEGMENTS: usize = 16;
+
+/// Files of a store saved before segments. Read as a single segment until
+/// `memex vacuum` merges it into a new one.
+const LEGACY_INDEX_FILE: &str = "usearch.index";
+const LEGACY_IDS_FILE: &str = "doc_ids.bin";
+const LEGACY_META_FILE: &str = "meta.json";
+const LEGACY_SEGMENT_ID: &str = "legacy";
+
+/// Segment files the manifest doesn't list are a writer's work in progress
+/// until they are this old; after that, a writer that died before listing
+/// them.
+const UNLISTED_SEGMENT_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// `meta.json` of a store saved before segments.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct VectorMetadata {
     dimensions: usize,
     model: Option<String>,
     index_file: String,
     ids_file: String,
+    /// SHA-256 of the index and doc id files as saved; absent in stores
+    /// written before checksums were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ids_sha256: Option<String>,
 }
 
+/// The segments a store is made of, and the dimensions and model they
+/// share. Replaced whole (written beside and renamed over) under
+/// `manifest.lock`, so a reader always sees a complete list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Manifest {
+    dimensions: usize,
+    model: Option<String>,
+    segments: Vec<SegmentMeta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SegmentMeta {
+    id: String,
+    /// Paths relative to the store directory.
+    index_file: String,
+    ids_file: String,
+    count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ids_sha256: Option<String>,
+}
+
+/// One HNSW graph and the doc ids it holds. Never changed once written.
+struct Segment {
+    index: Index,
+    doc_ids: HashSet<u64>,
+}
+
+/// Segments loaded from a manifest, with their ids.
+type Segments = Vec<(String, Segment)>;
+
+/// A store of embeddings keyed by doc id: immutable segments listed in
+/// `manifest.json`. Each save writes the vectors added since the last one
+/// as a new segment under a name no other writer uses, then adds it to the
+/// manifest, so the daemon and a manual `memex index` can both write the
+/// same store and readers never load a half-written file. Searches query
+/// every segment and merge the hits; `memex vacuum`, or a save once there
+/// are more than `MERGE_AT_SEGMENTS`, merges the segments back into one.
 pub struct VectorIndex {
     dims: usize,
     model: Option<String>,
     path: PathBuf,
-    index: Index,
+    segments: Segments,
+    /// Vectors added since the last save.
+    pending: Option<Segment>,
+    /// Stored segments whose vectors `pending` now holds, after a compact,
+    /// remap or merge; saving drops them from the manifest.
+    replaces: Vec<String>,
     doc_id_set: HashSet<u64>,
     needs_backfill: bool,
 }
@@ -25,87 +105,62 @@ pub struct VectorIndex {
 impl VectorIndex {
     pub fn open_or_create(dir: &Path, dimensions: usize, model: Option<&str>) -> Result<Self> {
         fs::create_dir_all(dir)?;
-        let index_path = dir.join("usearch.index");
-        let ids_path = dir.join("doc_ids.bin");
-        let meta_path = dir.join("meta.json");
         let mut needs_backfill = false;
         let model = model.map(str::to_string);
-
-        // Check if existing index has different dimensions or an incompatible embedding model.
-        if index_path.exists() {
-            let existing = Index::new(&IndexOptions::default())?;
-            existing.load(index_path.to_str().ok_or_else(|| anyhow!("invalid path"))?)?;
-            let existing_meta = load_metadata_if_exists(&meta_path)?;
-            let model_mismatch = match (&existing_meta, &model) {
-                (Some(meta), Some(model)) => meta.model.as_deref() != Some(model),
-                (None, Some(_)) => true,
-                _ => false,
-            };
-            if existing.dimensions() != dimensions || model_mismatch {
-                // Dimension or model mismatch; remove the old vector store and backfill.
-                let _ = fs::remove_file(&index_path);
-                let _ = fs::remove_file(&ids_path);
-                let _ = fs::remove_file(&meta_path);
-                needs_backfill = true;
-            }
-        }
-
-        let options = IndexOptions {
-            dimensions,
-            metric: MetricKind::Cos,
-            quantization: ScalarKind::F32,
-            ..IndexOptions::default()
+        let incompatible = |manifest: &Manifest| {
+            manifest.dimensions != dimensions || (model.is_some() && manifest.model != model)
         };
 
-        let index = Index::new(&options)?;
+        // An existing store with other dimensions or another embedding model
+        // is dropped, and its records are embedded again.
+        if read_manifest(dir)?.is_some_and(|manifest| incompatible(&manifest)) {
+            reset_store(dir, dimensions, model.as_deref(), &incompatible)?;
+            needs_backfill = true;
+        }
 
-        let doc_id_set = if index_path.exists() {
-            index.load(index_path.to_str().ok_or_else(|| anyhow!("invalid path"))?)?;
-            if ids_path.exists() {
-                load_doc_ids(&ids_path)?
-            } else {
-                HashSet::new()
+        let (segments, doc_id_set) = match load_store(dir)? {
+            Some((manifest, segments)) if !incompatible(&manifest) => {
+                let doc_ids = segments
+                    .iter()
+                    .flat_map(|(_, segment)| segment.doc_ids.iter().copied())
+                    .collect();
+                (segments, doc_ids)
+            }
+            _ => {
+                needs_backfill = true;
+                (Vec::new(), HashSet::new())
             }
-        } else {
-            index.reserve(10000)?;
-            needs_backfill = true;
-            HashSet::new()
         };
+        needs_backfill |= segments.is_empty();
 
         Ok(Self {
             dims: dimensions,
             model,
             path: dir.to_path_buf(),
-            index,
+            segments,
+            pending: None,
+            replaces: Vec::new(),
             doc_id_set,
             needs_backfill,
         })
     }
 
     pub fn open(dir: &Path) -> Result<Self> {
-        let index_path = dir.join("usearch.index");
-        let ids_path = dir.join("doc_ids.bin");
-        let meta_path = dir.join("meta.json");
-
-        if !index_path.exists() {
+        let Some((manifest, segments)) = load_store(dir)? else {
             return Err(anyhow!("vector index not found"));
-        }
-
-        let index = Index::new(&IndexOptions::default())?;
-        index.load(index_path.to_str().ok_or_else(|| anyhow!("invalid path"))?)?;
-
-        let doc_id_set = if ids_path.exists() {
-            load_doc_ids(&ids_path)?
-        } else {
-            HashSet::new()
         };
-        let model = load_metadata_if_exists(&meta_path)?.and_then(|meta| meta.model);
+        let doc_id_set = segments
+            .iter()
+            .flat_map(|(_, segment)| segment.doc_ids.iter().copied())
+            .collect();
 
         Ok(Self {
-            dims: index.dimensions(),
-            model,
+            dims: manifest.dimensions,
+            model: manifest.model,
             path: dir.to_path_buf(),
-            index,
+            segments,
+            pending: None,
+            replaces: Vec::new(),
             doc_id_set,
             needs_backfill: false,
         })
@@ -123,13 +178,22 @@ impl VectorIndex {
             return Ok(());
         }
 
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => Segment {
+                index: empty_index(self.dims, 0)?,
+                doc_ids: HashSet::new(),
+            },
+        };
+        let pending = self.pending.insert(pending);
         // Expand capacity if needed
-        if self.index.size() >= self.index.capacity() {
-            let new_capacity = (self.index.capacity() * 2).max(10000);
-            self.index.reserve(new_capacity)?;
+        if pending.index.size() >= pending.index.capacity() {
+            let new_capacity = (pending.index.capacity() * 2).max(10000);
+            pending.index.reserve(new_capacity)?;
         }
 
-        self.index.add(doc_id, embedding)?;
+        pending.index.add(doc_id, embedding)?;
+        pending.doc_ids.insert(doc_id);
         Ok(())
     }
 
@@ -141,54 +205,283 @@ impl VectorIndex {
                 embedding.len()
             ));
         }
-        if self.index.size() == 0 {
+
+        let mut hits = Vec::new();
+        for segment in self.all_segments() {
+            if segment.index.size() == 0 {
+                continue;
+            }
+            let results = segment.index.search(embedding, limit)?;
+            hits.extend(results.keys.into_iter().zip(results.distances));
+        }
+        Ok(nearest(hits, limit))
+    }
+
+    /// Nearest neighbours among `allowed` doc ids only, for searches with
+    /// project/source/date filters. Small candidate sets are scored exactly;
+    /// larger ones walk the HNSW graph skipping everything else, so a narrow
+    /// filter can't starve the results the way filtering afterwards does.
+    pub fn search_among(
+        &self,
+        embedding: &[f32],
+        limit: usize,
+        allowed: &HashSet<u64>,
+    ) -> Result<Vec<(u64, f32)>> {
+        if embedding.len() != self.dims {
+            return Err(anyhow!(
+                "embedding dimensions mismatch: expected {}, got {}",
+                self.dims,
+                embedding.len()
+            ));
+        }
+        if self.is_empty() || allowed.is_empty() || limit == 0 {
             return Ok(Vec::new());
         }
+        if allowed.len() > EXACT_SCAN_MAX {
+            let mut hits = Vec::new();
+            for segment in self.all_segments() {
+                if segment.index.size() == 0 {
+                    continue;
+                }
+                let results = segment
+                    .index
+                    .filtered_search(embedding, limit, |key| allowed.contains(&key))?;
+                hits.extend(results.keys.into_iter().zip(results.distances));
+            }
+            return Ok(nearest(hits, limit));
+        }
+
+        let mut scored = Vec::with_capacity(allowed.len());
+        let mut buf = vec![0f32; self.dims];
+        for &doc_id in allowed {
+            if !self.read_into(doc_id, &mut buf)? {
+                continue;
+            }
+            scored.push((doc_id, cosine_distance(embedding, &buf)));
+        }
+        scored.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
+    /// Nearest neighbours other than the `excluded` doc ids. They are skipped
+    /// while walking the graph, so a large excluded set can't crowd everything
+    /// else out of the results.
+    pub fn search_excluding(
+        &self,
+        embedding: &[f32],
+        limit: usize,
+        excluded: &HashSet<u64>,
+    ) -> Result<Vec<(u64, f32)>> {
+        if embedding.len() != self.dims {
+            return Err(anyhow!(
+                "embedding dimensions mismatch: expected {}, got {}",
+                self.dims,
+                embedding.len()
+            ));
+        }
+        let mut hits = Vec::new();
+        for segment in self.all_segments() {
+            if segment.index.size() == 0 {
+                continue;
+            }
+            let results = segment
+                .index
+                .filtered_search(embedding, limit, |key| !excluded.contains(&key))?;
+            hits.extend(results.keys.into_iter().zip(results.distances));
+        }
+        Ok(nearest(hits, limit))
+    }
 
-        let results = self.index.search(embedding, limit)?;
-        Ok(results.keys.into_iter().zip(results.distances).collect())
+    /// Writes the vectors added since the last save as a new segment and
+    /// lists it in the manifest, dropping the segments a compact, remap or
+    /// merge replaced. Segments other writers added in the meantime stay
+    /// listed.
+    pub fn save(&mut self) -> Result<()> {
+        if self.segment_count() > MERGE_AT_SEGMENTS {
+            self.merge()?;
+        }
+        let pending = self
+            .pending
+            .as_ref()
+            .filter(|pending| !pending.doc_ids.is_empty());
+        if pending.is_none() && self.replaces.is_empty() && read_manifest(&self.path)?.is_some() {
+            return Ok(());
+        }
+        let written = match pending {
+            Some(pending) => Some(write_segment(&self.path, pending)?),
+            None => None,
+        };
+
+        let lock = ManifestLock::acquire(&self.path)?;
+        let mut manifest = read_manifest(&self.path)?.unwrap_or_else(|| Manifest {
+            dimensions: self.dims,
+            model: self.model.clone(),
+            segments: Vec::new(),
+        });
+        if manifest.dimensions != self.dims || manifest.model != self.model {
+            bail!(
+                "vector store {} was reset to {} ({} dims) by another process",
+                self.path.display(),
+                manifest.model.as_deref().unwrap_or("an unknown model"),
+                manifest.dimensions
+            );
+        }
+        let (dropped, mut kept): (Vec<_>, Vec<_>) = manifest
+            .segments
+            .into_iter()
+            .partition(|segment| self.replaces.contains(&segment.id));
+        kept.extend(written.clone());
+        manifest.segments = kept;
+        write_manifest(&self.path, &manifest)?;
+        drop(lock);
+
+        remove_segment_files(&self.path, &dropped);
+        self.replaces.clear();
+        if let (Some(written), Some(pending)) = (written, self.pending.take()) {
+            self.segments.push((written.id, pending));
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, doc_id: u64) -> Result<Option<Vec<f32>>> {
+        let mut embedding = vec![0f32; self.dims];
+        if !self.read_into(doc_id, &mut embedding)? {
+            return Ok(None);
+        }
+        Ok(Some(embedding))
     }
 
-    pub fn save(&self) -> Result<()> {
-        let index_path = self.path.join("usearch.index");
-        let ids_path = self.path.join("doc_ids.bin");
-        let meta_path = self.path.join("meta.json");
+    /// Number of stored vectors whose doc id is not in `live`.
+    pub fn orphan_count(&self, live: &HashSet<u64>) -> usize {
+        self.doc_id_set
+            .iter()
+            .filter(|doc_id| !live.contains(doc_id))
+            .count()
+    }
 
-        // Save index
-        self.index
-            .save(index_path.to_str().ok_or_else(|| anyhow!("invalid path"))?)?;
+    /// Rebuilds the store as one HNSW graph with only the vectors whose doc
+    /// id is in `live`. usearch only tombstones removed keys, so a rebuild is
+    /// the only way to reclaim their space. Returns the number of vectors
+    /// dropped.
+    pub fn compact(&mut self, live: &HashSet<u64>) -> Result<usize> {
+        let removed = self.orphan_count(live);
+        if removed == 0 {
+            return Ok(0);
+        }
 
-        // Save doc_ids
-        save_doc_ids(&ids_path, &self.doc_id_set)?;
-        save_metadata(
-            &meta_path,
-            &VectorMetadata {
-                dimensions: self.dims,
-                model: self.model.clone(),
-                index_file: "usearch.index".to_string(),
-                ids_file: "doc_ids.bin".to_string(),
-            },
-        )?;
+        let before = self.doc_id_set.len();
+        self.rewrite(before - removed, |doc_id| {
+            live.contains(&doc_id).then_some(doc_id)
+        })?;
+        Ok(before - self.doc_id_set.len())
+    }
 
+    /// Rebuilds the graph with each vector moved from its old doc id to
+    /// `doc_ids[old]`, for when rebuilding the search index gave the same
+    /// records new doc ids. Vectors without a new id are dropped. Returns the
+    /// number kept.
+    pub fn remap(&mut self, doc_ids: &HashMap<u64, u64>) -> Result<usize> {
+        self.rewrite(doc_ids.len(), |doc_id| doc_ids.get(&doc_id).copied())?;
+        Ok(self.doc_id_set.len())
+    }
+
+    /// Merges every segment into one, so searches walk a single graph again.
+    /// Returns the number of segments merged, 0 when there was only one.
+    pub fn merge(&mut self) -> Result<usize> {
+        let count = self.segment_count();
+        if count <= 1 {
+            return Ok(0);
+        }
+        self.rewrite(self.doc_id_set.len(), Some)?;
+        Ok(count)
+    }
+
+    /// Replaces every segment, saved or not, with a single pending one
+    /// holding each vector under the doc id `new_id` gives it; vectors it
+    /// maps to `None` are dropped.
+    fn rewrite(
+        &mut self,
+        capacity: usize,
+        mut new_id: impl FnMut(u64) -> Option<u64>,
+    ) -> Result<()> {
+        let rebuilt = empty_index(self.dims, capacity)?;
+        let mut kept = HashSet::with_capacity(capacity);
+        let mut buf = vec![0f32; self.dims];
+        for segment in self.all_segments() {
+            for &doc_id in &segment.doc_ids {
+                let Some(target) = new_id(doc_id) else {
+                    continue;
+                };
+                if kept.contains(&target) || segment.index.get(doc_id, &mut buf)? == 0 {
+                    continue;
+                }
+                if rebuilt.size() >= rebuilt.capacity() {
+                    rebuilt.reserve(rebuilt.capacity() * 2)?;
+                }
+                rebuilt.add(target, &buf)?;
+                kept.insert(target);
+            }
+        }
+
+        self.replaces
+            .extend(self.segments.drain(..).map(|(id, _)| id));
+        self.doc_id_set = kept.clone();
+        self.pending = Some(Segment {
+            index: rebuilt,
+            doc_ids: kept,
+        });
         Ok(())
     }
 
+    /// Copies the vector for `doc_id` into `buf`; false when it isn't stored.
+    fn read_into(&self, doc_id: u64, buf: &mut [f32]) -> Result<bool> {
+        if !self.doc_id_set.contains(&doc_id) {
+            return Ok(false);
+        }
+        for segment in self.all_segments() {
+            if segment.doc_ids.contains(&doc_id) && segment.index.get(doc_id, buf)? > 0 {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn all_segments(&self) -> impl Iterator<Item = &Segment> {
+        self.segments
+            .iter()
+            .map(|(_, segment)| segment)
+            .chain(self.pending.as_ref())
+    }
+
     pub fn contains(&self, doc_id: u64) -> bool {
         self.doc_id_set.contains(&doc_id)
     }
 
     pub fn len(&self) -> usize {
-        self.index.size()
+        self.all_segments()
+            .map(|segment| segment.index.size())
+            .sum()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.index.size() == 0
+        self.len() == 0
     }
 
     pub fn doc_id_count(&self) -> usize {
         self.doc_id_set.len()
     }
 
+    /// Segments searches walk: those saved, plus one for unsaved vectors.
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+            + usize::from(
+                self.pending
+                    .as_ref()
+                    .is_some_and(|pending| !pending.doc_ids.is_empty()),
+            )
+    }
+
     pub fn model(&self) -> Option<&str> {
         self.model.as_deref()
     }
@@ -197,12 +490,386 @@ impl VectorIndex {
         self.needs_backfill
     }
 
-    #[allow(dead_code)]
     pub fn dimensions(&self) -> usize {
         self.dims
     }
 }
 
+/// The `limit` nearest of hits gathered from several segments, each doc id
+/// once.
+fn nearest(hits: Vec<(u64, f32)>, limit: usize) -> Vec<(u64, f32)> {
+    let mut best: HashMap<u64, f32> = HashMap::with_capacity(hits.len());
+    for (doc_id, distance) in hits {
+        best.entry(doc_id)
+            .and_modify(|found| *found = found.min(distance))
+            .or_insert(distance);
+    }
+    let mut hits: Vec<(u64, f32)> = best.into_iter().collect();
+    hits.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    hits.truncate(limit);
+    hits
+}
+
+/// Serializes manifest updates between processes writing the same store.
+/// Only held while the manifest is read, edited and replaced; segments are
+/// written before it is taken.
+struct ManifestLock {
+    _file: File,
+}
+
+impl ManifestLock {
+    fn acquire(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_LOCK_FILE);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("open {}", path.display()))?;
+        file.lock().context("lock vector store manifest")?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// The store's manifest, or one listing the flat files of a store saved
+/// before segments as its only segment. `None` when `dir` has no store.
+fn read_manifest(dir: &Path) -> Result<Option<Manifest>> {
+    let path = dir.join(MANIFEST_FILE);
+    if path.exists() {
+        let data = fs::read_to_string(&path)?;
+        return Ok(Some(
+            serde_json::from_str(&data).with_context(|| format!("parse {}", path.display()))?,
+        ));
+    }
+    let Some(meta) = load_metadata_if_exists(&dir.join(LEGACY_META_FILE))? else {
+        return Ok(None);
+    };
+    let count = fs::metadata(dir.join(&meta.ids_file)).map_or(0, |ids| ids.len() / 8);
+    Ok(Some(Manifest {
+        dimensions: meta.dimensions,
+        model: meta.model,
+        segments: vec![SegmentMeta {
+            id: LEGACY_SEGMENT_ID.to_string(),
+            index_file: meta.index_file,
+            ids_file: meta.ids_file,
+            count: count as usize,
+            index_sha256: meta.index_sha256,
+            ids_sha256: meta.ids_sha256,
+        }],
+    }))
+}
+
+fn write_manifest(dir: &Path, manifest: &Manifest) -> Result<()> {
+    let path = dir.join(MANIFEST_FILE);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(manifest)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Reads the manifest and loads the segments it lists. A merge can replace
+/// the manifest and delete the segments it merged between the two, so a
+/// segment gone missing means reading the manifest again.
+fn load_store(dir: &Path) -> Result<Option<(Manifest, Segments)>> {
+    let mut attempts = 0;
+    loop {
+        let Some(manifest) = read_manifest(dir)? else {
+            return Ok(None);
+        };
+        let segments: Result<Vec<_>> = manifest
+            .segments
+            .iter()
+            .map(|meta| Ok((meta.id.clone(), load_segment(dir, meta)?)))
+            .collect();
+        match segments {
+            Ok(segments) => return Ok(Some((manifest, segments))),
+            Err(_) if attempts < 3 && manifest_changed(dir, &manifest)? => attempts += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn manifest_changed(dir: &Path, seen: &Manifest) -> Result<bool> {
+    let ids = |manifest: &Manifest| -> Vec<String> {
+        manifest
+            .segments
+            .iter()
+            .map(|meta| meta.id.clone())
+            .collect()
+    };
+    Ok(read_manifest(dir)?.is_none_or(|current| ids(&current) != ids(seen)))
+}
+
+fn load_segment(dir: &Path, meta: &SegmentMeta) -> Result<Segment> {
+    let index_path = dir.join(&meta.index_file);
+    let index = Index::new(&IndexOptions::default())?;
+    index.load(index_path.to_str().ok_or_else(|| anyhow!("invalid path"))?)?;
+    let ids_path = dir.join(&meta.ids_file);
+    let doc_ids = if meta.id == LEGACY_SEGMENT_ID && !ids_path.exists() {
+        HashSet::new()
+    } else {
+        load_doc_ids(&ids_path)?
+    };
+    Ok(Segment { index, doc_ids })
+}
+
+/// Writes `segment` under a fresh id. Nothing reads it until a manifest
+/// lists it, so it is written in place.
+fn write_segment(dir: &Path, segment: &Segment) -> Result<SegmentMeta> {
+    fs::create_dir_all(dir.join(SEGMENTS_DIR))?;
+    let id = new_segment_id();
+    let index_file = format!("{SEGMENTS_DIR}/{id}.usearch");
+    let ids_file = format!("{SEGMENTS_DIR}/{id}.ids");
+    let index_path = dir.join(&index_file);
+    let ids_path = dir.join(&ids_file);
+    segment
+        .index
+        .save(index_path.to_str().ok_or_else(|| anyhow!("invalid path"))?)?;
+    save_doc_ids(&ids_path, &segment.doc_ids)?;
+    Ok(SegmentMeta {
+        id,
+        index_file,
+        ids_file,
+        count: segment.doc_ids.len(),
+        index_sha256: Some(file_sha256(&index_path)?),
+        ids_sha256: Some(file_sha256(&ids_path)?),
+    })
+}
+
+/// Unique across processes (by pid) and within one (by counter); sorts by
+/// creation time.
+fn new_segment_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis());
+    format!(
+        "{millis:013x}-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Best effort: a reader may still be loading a segment that was just
+/// unlisted, and files left behind are swept by the next merge.
+fn remove_segment_files(dir: &Path, segments: &[SegmentMeta]) {
+    for segment in segments {
+        let _ = fs::remove_file(dir.join(&segment.index_file));
+        let _ = fs::remove_file(dir.join(&segment.ids_file));
+        if segment.id == LEGACY_SEGMENT_ID {
+            let _ = fs::remove_file(dir.join(LEGACY_META_FILE));
+        }
+    }
+    let Ok(entries) = fs::read_dir(dir.join(SEGMENTS_DIR)) else {
+        return;
+    };
+    let Ok(Some(manifest)) = read_manifest(dir) else {
+        return;
+    };
+    let listed: HashSet<PathBuf> = manifest
+        .segments
+        .iter()
+        .flat_map(|segment| [dir.join(&segment.index_file), dir.join(&segment.ids_file)])
+        .collect();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let stale = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > UNLISTED_SEGMENT_GRACE);
+        if stale && !listed.contains(&entry.path()) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Replaces the store's manifest with an empty one for `dimensions` and
+/// `model` and deletes its segments, unless another writer already reset it.
+fn reset_store(
+    dir: &Path,
+    dimensions: usize,
+    model: Option<&str>,
+    incompatible: &dyn Fn(&Manifest) -> bool,
+) -> Result<()> {
+    let lock = ManifestLock::acquire(dir)?;
+    let Some(old) = read_manifest(dir)? else {
+        return Ok(());
+    };
+    if !incompatible(&old) {
+        return Ok(());
+    }
+    write_manifest(
+        dir,
+        &Manifest {
+            dimensions,
+            model: model.map(str::to_string),
+            segments: Vec::new(),
+        },
+    )?;
+    drop(lock);
+    remove_segment_files(dir, &old.segments);
+    Ok(())
+}
+
+/// Whether `dir` holds a vector store, in segments or the flat files saved
+/// before them.
+pub fn vector_store_exists(dir: &Path) -> bool {
+    dir.join(MANIFEST_FILE).exists() || dir.join(LEGACY_INDEX_FILE).exists()
+}
+
+/// When the manifest of the store at `dir` was last replaced, so a reader
+/// that keeps a store open can tell a writer has added segments since.
+pub fn manifest_modified(dir: &Path) -> Option<SystemTime> {
+    fs::metadata(dir.join(MANIFEST_FILE))
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Deletes the vector store at `dir`, leaving other stores under it (the
+/// archive tier's) alone.
+pub fn remove_vector_store(dir: &Path) -> Result<()> {
+    for file in [
+        MANIFEST_FILE,
+        MANIFEST_LOCK_FILE,
+        LEGACY_INDEX_FILE,
+        LEGACY_IDS_FILE,
+        LEGACY_META_FILE,
+    ] {
+        let path = dir.join(file);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    let segments = dir.join(SEGMENTS_DIR);
+    if segments.exists() {
+        fs::remove_dir_all(segments)?;
+    }
+    Ok(())
+}
+
+/// What a vector store on disk holds, read from its manifest without
+/// loading any segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredVectors {
+    pub dimensions: usize,
+    pub model: Option<String>,
+    pub count: usize,
+    pub segments: usize,
+    /// Size of the segment files.
+    pub bytes: u64,
+}
+
+impl StoredVectors {
+    /// `None` when `dir` has no vector store.
+    pub fn read(dir: &Path) -> Result<Option<Self>> {
+        let Some(manifest) = read_manifest(dir)? else {
+            return Ok(None);
+        };
+        let bytes = manifest
+            .segments
+            .iter()
+            .flat_map(|segment| [&segment.index_file, &segment.ids_file])
+            .map(|file| fs::metadata(dir.join(file)).map_or(0, |meta| meta.len()))
+            .sum();
+        Ok(Some(Self {
+            dimensions: manifest.dimensions,
+            count: manifest.segments.iter().map(|segment| segment.count).sum(),
+            segments: manifest.segments.len(),
+            bytes,
+            model: manifest.model,
+        }))
+    }
+}
+
+/// Embeddings for old records: those more than `after_months` old when they
+/// are embedded go to a second store under the vectors directory, embedded
+/// with `model`, usually one far cheaper than the main model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveTier {
+    pub after_months: u32,
+    pub model: ModelChoice,
+}
+
+impl ArchiveTier {
+    /// Records older than this (Unix millis) belong in the archive store.
+    pub fn cutoff_ms(&self, now_ms: u64) -> u64 {
+        chrono::DateTime::from_timestamp_millis(now_ms as i64)
+            .and_then(|now| now.checked_sub_months(chrono::Months::new(self.after_months)))
+            .map_or(0, |cutoff| cutoff.timestamp_millis().max(0) as u64)
+    }
+}
+
+/// Store the archive tier's vectors live in. Each store's metadata names
+/// the model its vectors came from.
+pub fn archive_vectors_dir(vectors: &Path) -> PathBuf {
+    vectors.join("archive")
+}
+
+/// The vector stores under `vectors` that exist on disk: the main one, then
+/// the archive tier's.
+pub fn vector_store_dirs(vectors: &Path) -> Vec<PathBuf> {
+    [vectors.to_path_buf(), archive_vectors_dir(vectors)]
+        .into_iter()
+        .filter(|dir| vector_store_exists(dir))
+        .collect()
+}
+
+/// Same measure as the index's `MetricKind::Cos`: one minus cosine similarity.
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut norm_a, mut norm_b) = (0f32, 0f32, 0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    let norms = (norm_a * norm_b).sqrt();
+    if norms == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot / norms
+}
+
+fn empty_index(dimensions: usize, capacity: usize) -> Result<Index> {
+    let options = IndexOptions {
+        dimensions,
+        metric: MetricKind::Cos,
+        quantization: ScalarKind::F32,
+        ..IndexOptions::default()
+    };
+    let index = Index::new(&options)?;
+    index.reserve(capacity.max(10000))?;
+    Ok(index)
+}
+
+/// Whether every segment of the vector store at `dir` still matches the
+/// checksums recorded when it was written. `None` when there is no store,
+/// or part of it predates checksums.
+pub fn verify_checksums(dir: &Path) -> Result<Option<bool>> {
+    let Some(manifest) = read_manifest(dir)? else {
+        return Ok(None);
+    };
+    let mut checks = Vec::with_capacity(manifest.segments.len() * 2);
+    for segment in &manifest.segments {
+        let (Some(index_sha), Some(ids_sha)) = (&segment.index_sha256, &segment.ids_sha256) else {
+            return Ok(None);
+        };
+        checks.push((dir.join(&segment.index_file), index_sha));
+        checks.push((dir.join(&segment.ids_file), ids_sha));
+    }
+    for (path, sha) in checks {
+        if !path.exists() || file_sha256(&path)? != *sha {
+            return Ok(Some(false));
+        }
+    }
+    Ok(Some(true))
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
 fn load_metadata(path: &Path) -> Result<VectorMetadata> {
     let data = fs::read_to_string(path)?;
     Ok(serde_json::from_str(&data)?)
@@ -215,13 +882,6 @@ fn load_metadata_if_exists(path: &Path) -> Result<Option<VectorMetadata>> {
     Ok(Some(load_metadata(path)?))
 }
 
-fn save_metadata(path: &Path, metadata: &VectorMetadata) -> Result<()> {
-    let tmp = path.with_extension("json.tmp");
-    fs::write(&tmp, serde_json::to_string_pretty(metadata)?)?;
-    fs::rename(&tmp, path)?;
-    Ok(())
-}
-
 fn load_doc_ids(path: &Path) -> Result<HashSet<u64>> {
     let bytes = fs::read(path)?;
     let ids: Vec<u64> = bytes
@@ -236,9 +896,7 @@ fn save_doc_ids(path: &Path, ids: &HashSet<u64>) -> Result<()> {
     for id in ids {
         bytes.extend_from_slice(&id.to_le_bytes());
     }
-    let tmp = path.with_extension("bin.tmp");
-    fs::write(&tmp, &bytes)?;
-    fs::rename(&tmp, path)?;
+    fs::write(path, &bytes)?;
     Ok(())
 }
 
@@ -356,13 +1014,18 @@ mod tests {
             idx.save().unwrap();
         }
 
-        let metadata: serde_json::Value =
-            serde_json::from_str(&fs::read_to_string(tmp.path().join("meta.json")).unwrap())
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(tmp.path().join("manifest.json")).unwrap())
                 .unwrap();
-        assert_eq!(metadata["dimensions"], 64);
-        assert_eq!(metadata["model"], "bge");
-        assert_eq!(metadata["index_file"], "usearch.index");
-        assert_eq!(metadata["ids_file"], "doc_ids.bin");
+        assert_eq!(manifest["dimensions"], 64);
+        assert_eq!(manifest["model"], "bge");
+        let segment = &manifest["segments"][0];
+        assert_eq!(segment["count"], 1);
+        for file in ["index_file", "ids_file"] {
+            let file = segment[file].as_str().unwrap();
+            assert!(file.starts_with("segments/"));
+            assert!(tmp.path().join(file).exists());
+        }
     }
 
     #[test]
@@ -423,7 +1086,11 @@ mod tests {
             idx.save().unwrap();
         }
 
-        fs::remove_file(tmp.path().join("meta.json")).unwrap();
+        let manifest_path = tmp.path().join("manifest.json");
+        let mut manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        manifest["model"] = serde_json::Value::Null;
+        fs::write(&manifest_path, manifest.to_string()).unwrap();
 
         {
             let idx = VectorIndex::open_or_create(tmp.path(), 64, Some("alpha")).unwrap();
@@ -443,7 +1110,7 @@ mod tests {
             idx.save().unwrap();
         }
 
-        fs::write(tmp.path().join("meta.json"), "{").unwrap();
+        fs::write(tmp.path().join("manifest.json"), "{").unwrap();
 
         assert!(VectorIndex::open(tmp.path()).is_err());
         assert!(VectorIndex::open_or_create(tmp.path(), 64, Some("alpha")).is_err());
@@ -464,6 +1131,119 @@ mod tests {
         assert_eq!(idx.dimensions(), 64);
     }
 
+    #[test]
+    fn test_get_returns_stored_embedding() {
+        let tmp = TempDir::new().unwrap();
+        let mut idx = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+        let v1 = make_vector(64, 1.0);
+        idx.add(1, &v1).unwrap();
+
+        let stored = idx.get(1).unwrap().expect("stored embedding");
+        assert_eq!(stored.len(), 64);
+        assert!(stored.iter().zip(&v1).all(|(a, b)| (a - b).abs() < 1e-5));
+        assert!(idx.get(2).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_compact_drops_orphaned_vectors() {
+        let tmp = TempDir::new().unwrap();
+
+        {
+            let mut idx = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+            for i in 0..10 {
+                idx.add(i, &make_vector(64, i as f32)).unwrap();
+            }
+            let live: HashSet<u64> = (0..10).filter(|i| i % 2 == 0).collect();
+            assert_eq!(idx.orphan_count(&live), 5);
+            assert_eq!(idx.compact(&live).unwrap(), 5);
+            assert_eq!(idx.orphan_count(&live), 0);
+            assert_eq!(idx.compact(&live).unwrap(), 0);
+            idx.save().unwrap();
+        }
+
+        let idx = VectorIndex::open(tmp.path()).unwrap();
+        assert_eq!(idx.len(), 5);
+        assert_eq!(idx.doc_id_count(), 5);
+        assert!(idx.contains(4));
+        assert!(!idx.contains(3));
+        let results = idx.search(&make_vector(64, 4.0), 1).unwrap();
+        assert_eq!(results[0].0, 4);
+        assert!(results[0].1 < 0.01);
+    }
+
+    #[test]
+    fn test_checksums_and_remap() {
+        let tmp = TempDir::new().unwrap();
+        assert_eq!(verify_checksums(tmp.path()).unwrap(), None);
+
+        {
+            let mut idx = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+            for i in 0..4 {
+                idx.add(i, &make_vector(64, i as f32)).unwrap();
+            }
+            idx.save().unwrap();
+        }
+        assert_eq!(verify_checksums(tmp.path()).unwrap(), Some(true));
+
+        let mut idx = VectorIndex::open(tmp.path()).unwrap();
+        let doc_ids: HashMap<u64, u64> = [(1, 11), (2, 12)].into_iter().collect();
+        assert_eq!(idx.remap(&doc_ids).unwrap(), 2);
+        assert!(idx.contains(11) && !idx.contains(1) && !idx.contains(0));
+        assert_eq!(idx.get(12).unwrap(), Some(make_vector(64, 2.0)));
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(tmp.path().join("manifest.json")).unwrap())
+                .unwrap();
+        let ids = tmp
+            .path()
+            .join(manifest["segments"][0]["ids_file"].as_str().unwrap());
+        fs::write(&ids, [0u8; 8]).unwrap();
+        assert_eq!(verify_checksums(tmp.path()).unwrap(), Some(false));
+    }
+
+    #[test]
+    fn test_search_among_only_scores_allowed_doc_ids() {
+        let tmp = TempDir::new().unwrap();
+        let mut idx = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+        for i in 0..10 {
+            idx.add(i, &make_vector(64, i as f32)).unwrap();
+        }
+
+        let query = make_vector(64, 0.0);
+        let allowed: HashSet<u64> = [7, 8, 42].into_iter().collect();
+        let results = idx.search_among(&query, 5, &allowed).unwrap();
+        let ids: Vec<u64> = results.iter().map(|(doc_id, _)| *doc_id).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.iter().all(|doc_id| allowed.contains(doc_id)));
+        assert!(results[0].1 <= results[1].1);
+
+        let exact = idx
+            .search_among(&query, 1, &[0].into_iter().collect())
+            .unwrap();
+        assert_eq!(exact[0].0, 0);
+        assert!(exact[0].1 < 0.01);
+        assert!(
+            idx.search_among(&query, 5, &HashSet::new())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_search_excluding_skips_excluded_doc_ids() {
+        let tmp = TempDir::new().unwrap();
+        let mut idx = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+        for i in 0..10 {
+            idx.add(i, &make_vector(64, i as f32)).unwrap();
+        }
+
+        let query = make_vector(64, 0.0);
+        let excluded: HashSet<u64> = (0..5).collect();
+        let results = idx.search_excluding(&query, 3, &excluded).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|(doc_id, _)| !excluded.contains(doc_id)));
+    }
+
     #[test]
     fn test_search_with_limit() {
         let tmp = TempDir::new().unwrap();
@@ -478,4 +1258,104 @@ mod tests {
         let results = idx.search(&query, 3).unwrap();
         assert_eq!(results.len(), 3);
     }
+
+    #[test]
+    fn test_concurrent_writers_add_segments_and_vacuum_merges_them() {
+        let tmp = TempDir::new().unwrap();
+        let mut daemon = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+        let mut manual = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+        for i in 0..3 {
+            daemon.add(i, &make_vector(64, i as f32)).unwrap();
+            manual
+                .add(10 + i, &make_vector(64, 10.0 + i as f32))
+                .unwrap();
+        }
+        daemon.save().unwrap();
+        let reader = VectorIndex::open(tmp.path()).unwrap();
+        manual.save().unwrap();
+        daemon.save().unwrap();
+
+        assert_eq!(reader.doc_id_count(), 3);
+        let mut idx = VectorIndex::open(tmp.path()).unwrap();
+        assert_eq!(idx.segment_count(), 2);
+        assert_eq!(idx.doc_id_count(), 6);
+        let results = idx.search(&make_vector(64, 11.0), 2).unwrap();
+        assert_eq!(results[0].0, 11);
+        assert_eq!(
+            StoredVectors::read(tmp.path()).unwrap().unwrap().segments,
+            2
+        );
+
+        assert_eq!(idx.merge().unwrap(), 2);
+        idx.save().unwrap();
+        assert_eq!(verify_checksums(tmp.path()).unwrap(), Some(true));
+        let merged = VectorIndex::open(tmp.path()).unwrap();
+        assert_eq!(merged.segment_count(), 1);
+        assert_eq!(merged.len(), 6);
+        assert_eq!(merged.get(2).unwrap(), Some(make_vector(64, 2.0)));
+        assert_eq!(
+            fs::read_dir(tmp.path().join("segments")).unwrap().count(),
+            2
+        );
+        assert_eq!(reader.get(1).unwrap(), Some(make_vector(64, 1.0)));
+    }
+
+    #[test]
+    fn test_saves_merge_segments_past_the_threshold() {
+        let tmp = TempDir::new().unwrap();
+        let mut idx = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+        for i in 0..MERGE_AT_SEGMENTS as u64 {
+            idx.add(i, &make_vector(64, i as f32)).unwrap();
+            idx.save().unwrap();
+        }
+        assert_eq!(
+            VectorIndex::open(tmp.path()).unwrap().segment_count(),
+            MERGE_AT_SEGMENTS
+        );
+
+        let last = MERGE_AT_SEGMENTS as u64;
+        idx.add(last, &make_vector(64, last as f32)).unwrap();
+        idx.save().unwrap();
+        let merged = VectorIndex::open(tmp.path()).unwrap();
+        assert_eq!(merged.segment_count(), 1);
+        assert_eq!(merged.len(), MERGE_AT_SEGMENTS + 1);
+        assert_eq!(merged.get(3).unwrap(), Some(make_vector(64, 3.0)));
+        assert_eq!(
+            fs::read_dir(tmp.path().join("segments")).unwrap().count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_flat_store_reads_as_a_segment_until_merged() {
+        let tmp = TempDir::new().unwrap();
+        let index = empty_index(64, 0).unwrap();
+        index.add(1, &make_vector(64, 1.0)).unwrap();
+        index
+            .save(tmp.path().join("usearch.index").to_str().unwrap())
+            .unwrap();
+        save_doc_ids(&tmp.path().join("doc_ids.bin"), &[1].into_iter().collect()).unwrap();
+        fs::write(
+            tmp.path().join("meta.json"),
+            r#"{"dimensions":64,"model":"test","index_file":"usearch.index","ids_file":"doc_ids.bin"}"#,
+        )
+        .unwrap();
+        assert!(vector_store_exists(tmp.path()));
+
+        let mut idx = VectorIndex::open_or_create(tmp.path(), 64, Some("test")).unwrap();
+        assert!(idx.contains(1) && !idx.needs_backfill());
+        idx.add(2, &make_vector(64, 2.0)).unwrap();
+        idx.save().unwrap();
+        assert_eq!(VectorIndex::open(tmp.path()).unwrap().segment_count(), 2);
+        assert_eq!(verify_checksums(tmp.path()).unwrap(), None);
+
+        assert_eq!(idx.merge().unwrap(), 2);
+        idx.save().unwrap();
+        for file in ["usearch.index", "doc_ids.bin", "meta.json"] {
+            assert!(!tmp.path().join(file).exists());
+        }
+        let idx = VectorIndex::open(tmp.path()).unwrap();
+        assert!(idx.contains(1) && idx.contains(2));
+        assert_eq!(verify_checksums(tmp.path()).unwrap(), Some(true));
+    }
 }